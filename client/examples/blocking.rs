@@ -0,0 +1,43 @@
+//! blocking connects to a webrtc-unreliable server from a plain thread through BlockingSocket,
+//! without an async runtime of its own.
+//!
+//!     cargo run --example blocking --features blocking
+
+use std::time::Duration;
+
+use webrtc_unreliable_client::{BlockingSocket, SessionEndpoint, SocketOptions};
+
+fn main() {
+    let server_url = "http://127.0.0.1:14191/rtc_session".to_owned();
+
+    let options = match SocketOptions::builder().low_latency().build() {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("invalid options: {}", err);
+            return;
+        }
+    };
+
+    let mut socket = match BlockingSocket::connect(SessionEndpoint::Http(server_url), options) {
+        Ok(socket) => socket,
+        Err(err) => {
+            eprintln!("could not connect: {}", err);
+            return;
+        }
+    };
+
+    for i in 0..10 {
+        let message = format!("PING {}", i);
+        if let Err(err) = socket.send(message.as_bytes()) {
+            eprintln!("could not send: {}", err);
+            break;
+        }
+
+        if let Some(message) = socket.recv_timeout(Duration::from_secs(1)) {
+            println!("recv <- {}", String::from_utf8_lossy(&message));
+        }
+    }
+
+    println!("{:?}", socket.stats());
+    socket.close();
+}
@@ -0,0 +1,45 @@
+use bytes::Bytes;
+use tokio::time::{sleep, Duration};
+
+use webrtc_unreliable_client::{SessionEndpoint, Socket, SocketOptions};
+
+#[tokio::main]
+async fn main() {
+    let server_url = "http://127.0.0.1:14191/rtc_session".to_owned();
+
+    let options = match SocketOptions::builder().low_latency().build() {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("invalid options: {}", err);
+            return;
+        }
+    };
+
+    let (to_server, mut to_client, handle) =
+        match Socket::connect_session(SessionEndpoint::Http(server_url), options).await {
+            Ok(session) => session,
+            Err(err) => {
+                eprintln!("could not connect: {}", err);
+                return;
+            }
+        };
+
+    println!("connected to {:?}", handle.server_addr());
+
+    for i in 0..10 {
+        let message = format!("PING {}", i);
+        if to_server.send(Bytes::from(message)).await.is_err() {
+            break;
+        }
+
+        tokio::select! {
+            Some(message) = to_client.recv() => {
+                println!("recv <- {}", String::from_utf8_lossy(&message));
+            }
+            _ = sleep(Duration::from_secs(1)) => {}
+        }
+    }
+
+    println!("{:?}", handle.stats());
+    handle.close().await;
+}
@@ -0,0 +1,321 @@
+//! loopback connects to an in-process webrtc-unreliable echo server, sends numbered messages
+//! over the unordered, unreliable data channel and reports how many came back and how late.
+//!
+//!     cargo run --example loopback -- --loss 0.05 --rate 120
+//!
+//! Above a few hundred messages per second the DTLS encryption of a debug build is the
+//! bottleneck and round trips grow, pass --release to measure the protocol instead.
+//!
+//! Flags:
+//!   --count N      messages to send (default 600)
+//!   --rate N       messages per second (default 60)
+//!   --size N       bytes per message, at least 4 (default 64)
+//!   --loss P       probability in [0, 1) that a datagram is dropped, in each direction (default 0)
+//!   --latency MS   delay before each message is handed to the socket (default 0)
+
+use std::env;
+use std::net::{SocketAddr, UdpSocket};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use bytes::Bytes;
+use tinyjson::JsonValue;
+use tokio::sync::mpsc;
+use tokio::time::{interval, sleep, sleep_until, Duration, Instant};
+
+use webrtc_unreliable::Server as RtcServer;
+use webrtc_unreliable_client::{
+    PacketInterceptor, SessionEndpoint, SettingEngine, SignalingAnswer, Socket, SocketOptions,
+};
+
+/// DRAIN_TIMEOUT is how long the client waits for echoes after the last message was sent
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+struct Args {
+    count: u32,
+    rate: f64,
+    size: usize,
+    loss: f64,
+    latency: Duration,
+}
+
+impl Args {
+    fn parse() -> Result<Self, String> {
+        let mut args = Args {
+            count: 600,
+            rate: 60.0,
+            size: 64,
+            loss: 0.0,
+            latency: Duration::ZERO,
+        };
+
+        let mut iter = env::args().skip(1);
+        while let Some(flag) = iter.next() {
+            let value = iter
+                .next()
+                .ok_or_else(|| format!("{} needs a value", flag))?;
+            match flag.as_str() {
+                "--count" => args.count = parse_value(&flag, &value)?,
+                "--rate" => args.rate = parse_value(&flag, &value)?,
+                "--size" => args.size = parse_value(&flag, &value)?,
+                "--loss" => args.loss = parse_value(&flag, &value)?,
+                "--latency" => args.latency = Duration::from_millis(parse_value(&flag, &value)?),
+                _ => return Err(format!("unknown flag {}", flag)),
+            }
+        }
+
+        if args.count == 0 {
+            return Err("--count must not be zero".to_owned());
+        }
+        // the ticker needs a period of at least a nanosecond
+        if !(0.0..=1e9).contains(&args.rate) || args.rate == 0.0 {
+            return Err("--rate must be in (0, 1e9]".to_owned());
+        }
+        if args.size < 4 {
+            return Err("--size must be at least 4".to_owned());
+        }
+        if !(0.0..1.0).contains(&args.loss) {
+            return Err("--loss must be in [0, 1)".to_owned());
+        }
+        Ok(args)
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value for {}: {}", flag, value))
+}
+
+/// LossyLink drops datagrams at random once enabled, so that the handshake is not slowed down
+/// by retransmissions and only the data channel traffic is measured
+struct LossyLink {
+    loss: f64,
+    enabled: AtomicBool,
+}
+
+impl LossyLink {
+    fn keep(&self) -> bool {
+        !self.enabled.load(Ordering::Relaxed) || rand::random::<f64>() >= self.loss
+    }
+}
+
+impl PacketInterceptor for LossyLink {
+    fn before_send(&self, _packet: &[u8]) -> bool {
+        self.keep()
+    }
+
+    fn after_receive(&self, _packet: &[u8]) -> bool {
+        self.keep()
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let args = match Args::parse() {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(2);
+        }
+    };
+
+    let rtc_server = match start_echo_server().await {
+        Ok(rtc_server) => rtc_server,
+        Err(err) => {
+            eprintln!("could not start the echo server: {}", err);
+            process::exit(1);
+        }
+    };
+    let session_endpoint = rtc_server.session_endpoint();
+    tokio::spawn(echo(rtc_server));
+
+    let link = Arc::new(LossyLink {
+        loss: args.loss,
+        enabled: AtomicBool::new(false),
+    });
+    let mut setting_engine = SettingEngine::new();
+    setting_engine.add_packet_interceptor(Arc::clone(&link) as Arc<dyn PacketInterceptor>);
+
+    let options = match SocketOptions::builder()
+        .low_latency()
+        .setting_engine(setting_engine)
+        .build()
+    {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("invalid options: {}", err);
+            process::exit(1);
+        }
+    };
+
+    let endpoint = SessionEndpoint::Signaling(Box::new(move |offer| {
+        let mut session_endpoint = session_endpoint.clone();
+        Box::pin(async move {
+            let body = futures_util::stream::once(async move { Ok::<_, std::io::Error>(offer) });
+            let response = session_endpoint
+                .session_request(body)
+                .await
+                .map_err(|err| anyhow::anyhow!("session request: {}", err))?;
+            parse_session_response(&response)
+        })
+    }));
+
+    let (to_server, mut to_client, handle) = match Socket::connect_session(endpoint, options).await
+    {
+        Ok(session) => session,
+        Err(err) => {
+            eprintln!("could not connect: {}", err);
+            process::exit(1);
+        }
+    };
+    println!("connected to {:?}", handle.server_addr());
+    link.enabled.store(true, Ordering::Relaxed);
+
+    let started = Instant::now();
+    let mut sent_at: Vec<Option<Instant>> = vec![None; args.count as usize];
+    let mut rtts: Vec<Duration> = Vec::with_capacity(args.count as usize);
+    let mut duplicates = 0u32;
+
+    let mut ticker = interval(Duration::from_secs_f64(1.0 / args.rate));
+    let mut next_seq = 0u32;
+    let mut drain_deadline = None;
+    let mut sending_took = Duration::ZERO;
+    loop {
+        tokio::select! {
+            _ = ticker.tick(), if next_seq < args.count => {
+                let mut message = vec![0u8; args.size];
+                message[..4].copy_from_slice(&next_seq.to_be_bytes());
+                sent_at[next_seq as usize] = Some(Instant::now());
+                send_delayed(&to_server, Bytes::from(message), args.latency).await;
+
+                next_seq += 1;
+                if next_seq == args.count {
+                    sending_took = started.elapsed();
+                    drain_deadline = Some(Instant::now() + args.latency + DRAIN_TIMEOUT);
+                }
+            }
+            message = to_client.recv() => {
+                let message = match message {
+                    Some(message) => message,
+                    None => {
+                        eprintln!("the data channel closed early");
+                        break;
+                    }
+                };
+                let seq = match message.get(..4) {
+                    Some(seq) => u32::from_be_bytes([seq[0], seq[1], seq[2], seq[3]]) as usize,
+                    None => continue,
+                };
+                match sent_at.get_mut(seq).and_then(Option::take) {
+                    Some(sent) => rtts.push(sent.elapsed()),
+                    None => duplicates += 1,
+                }
+            }
+            _ = sleep_until(drain_deadline.unwrap_or_else(Instant::now)), if drain_deadline.is_some() => {
+                break;
+            }
+        }
+        if rtts.len() == args.count as usize {
+            break;
+        }
+    }
+    report(&args, next_seq, &mut rtts, duplicates, sending_took);
+    println!("{:?}", handle.stats());
+    handle.close().await;
+}
+
+/// start_echo_server binds the webrtc-unreliable server to a free port on the loopback
+/// interface and advertises that port
+async fn start_echo_server() -> std::io::Result<RtcServer> {
+    let addr: SocketAddr = UdpSocket::bind("127.0.0.1:0")?.local_addr()?;
+    RtcServer::new(addr, addr).await
+}
+
+/// echo sends every message back to the client it came from
+async fn echo(mut rtc_server: RtcServer) {
+    let mut message = Vec::new();
+    loop {
+        let (message_type, remote_addr) = match rtc_server.recv().await {
+            Ok(received) => {
+                message.clear();
+                message.extend_from_slice(received.message.as_ref());
+                (received.message_type, received.remote_addr)
+            }
+            Err(err) => {
+                eprintln!("echo server could not receive: {}", err);
+                return;
+            }
+        };
+        // the message was lost if the client is gone
+        let _ = rtc_server.send(&message, message_type, &remote_addr).await;
+    }
+}
+
+/// send_delayed hands the message to the socket after latency, like a slow uplink would
+async fn send_delayed(to_server: &mpsc::Sender<Bytes>, message: Bytes, latency: Duration) {
+    if latency.is_zero() {
+        let _ = to_server.send(message).await;
+        return;
+    }
+    let to_server = to_server.clone();
+    tokio::spawn(async move {
+        sleep(latency).await;
+        let _ = to_server.send(message).await;
+    });
+}
+
+fn parse_session_response(response: &str) -> anyhow::Result<SignalingAnswer> {
+    let json: JsonValue = response
+        .parse()
+        .map_err(|err| anyhow::anyhow!("session response is not json: {}", err))?;
+    let sdp: Option<&String> = json["answer"]["sdp"].get();
+    let candidate: Option<&String> = json["candidate"]["candidate"].get();
+    match (sdp, candidate) {
+        (Some(sdp), Some(candidate)) => Ok(SignalingAnswer {
+            sdp: sdp.clone(),
+            candidate: candidate.clone(),
+        }),
+        _ => Err(anyhow::anyhow!(
+            "session response is missing the answer sdp or candidate"
+        )),
+    }
+}
+
+fn report(args: &Args, sent: u32, rtts: &mut [Duration], duplicates: u32, sending_took: Duration) {
+    let received = rtts.len() as u32;
+    let lost = sent - received;
+    // a message and its echo each cross the link once, and a lost echo costs the next one too:
+    // webrtc-unreliable answers the gap with a FORWARD-TSN that also covers its next message
+    let expected_loss = 1.0 - (1.0 - args.loss).powi(3);
+
+    println!(
+        "sent {} messages of {} bytes at {}/s in {:.2?}",
+        sent, args.size, args.rate, sending_took
+    );
+    println!(
+        "received {} echoes, lost {} ({:.2}%, expected about {:.2}%), duplicates {}",
+        received,
+        lost,
+        100.0 * f64::from(lost) / f64::from(sent),
+        100.0 * expected_loss,
+        duplicates
+    );
+
+    if rtts.is_empty() {
+        return;
+    }
+    rtts.sort();
+    let percentile = |p: usize| rtts[(rtts.len() - 1) * p / 100];
+    let mean = rtts.iter().sum::<Duration>() / received;
+    println!(
+        "round trip min {:.2?} mean {:.2?} p50 {:.2?} p95 {:.2?} p99 {:.2?} max {:.2?}",
+        rtts[0],
+        mean,
+        percentile(50),
+        percentile(95),
+        percentile(99),
+        rtts[rtts.len() - 1]
+    );
+}
@@ -0,0 +1,107 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::Bytes;
+use tokio::runtime::{Builder, Runtime};
+use tokio::sync::mpsc;
+
+use crate::socket::{
+    SessionEndpoint, Socket, SocketError, SocketHandle, SocketOptions, SocketStats,
+};
+
+/// SHUTDOWN_TIMEOUT bounds how long dropping a BlockingSocket waits for the tasks of its runtime
+/// to stop, e.g. a DNS lookup still running on a blocking thread
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// BlockingSocket is a session for applications without an async runtime, e.g. a plugin that
+/// gets a plain thread from its host. It owns a runtime with a single background thread that
+/// runs the connection, its methods block the calling thread until they are done. They are safe
+/// to call from any single thread, but not from within an async runtime, where blocking panics.
+/// Dropping it closes the session and stops the background thread.
+pub struct BlockingSocket {
+    to_server: mpsc::Sender<Bytes>,
+    to_client: mpsc::Receiver<Bytes>,
+    handle: SocketHandle,
+    // runtime is only taken when the socket is dropped
+    runtime: Option<Runtime>,
+}
+
+impl BlockingSocket {
+    /// connect starts the background runtime and connects a session on it like
+    /// Socket::connect_session, blocking until the data channel is open or connecting failed.
+    /// It fails with SocketError::Runtime if the runtime cannot be started. On failure the
+    /// runtime is stopped again before connect returns.
+    pub fn connect(
+        endpoint: SessionEndpoint,
+        options: SocketOptions,
+    ) -> Result<BlockingSocket, SocketError> {
+        let runtime = Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("webrtc-unreliable-client")
+            .enable_all()
+            .build()
+            .map_err(|err| SocketError::Runtime(Arc::new(err)))?;
+
+        match runtime.block_on(Socket::connect_session(endpoint, options)) {
+            Ok((to_server, to_client, handle)) => Ok(BlockingSocket {
+                to_server,
+                to_client,
+                handle,
+                runtime: Some(runtime),
+            }),
+            Err(err) => {
+                runtime.shutdown_timeout(SHUTDOWN_TIMEOUT);
+                Err(err)
+            }
+        }
+    }
+
+    /// send queues message for the server. It blocks while channel_capacity messages are
+    /// waiting to be sent, and fails with SocketError::ConnectionClosed or ConnectionFailed once
+    /// the session has ended.
+    pub fn send(&self, message: &[u8]) -> Result<(), SocketError> {
+        self.handle.check_connection()?;
+        self.to_server
+            .blocking_send(Bytes::copy_from_slice(message))
+            .map_err(|_| {
+                self.handle
+                    .check_connection()
+                    .err()
+                    .unwrap_or(SocketError::ConnectionClosed)
+            })
+    }
+
+    /// recv_timeout waits up to timeout for the next message from the server. It returns None
+    /// if none arrived in time, and right away once the data channel is closed and every
+    /// message received before was returned.
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Option<Vec<u8>> {
+        let runtime = self.runtime.as_ref()?;
+        let to_client = &mut self.to_client;
+        let message = runtime.block_on(async move {
+            tokio::time::timeout(timeout, to_client.recv())
+                .await
+                .ok()
+                .flatten()
+        })?;
+        Some(message.to_vec())
+    }
+
+    /// stats returns the message counters of the session, see SocketHandle::stats
+    pub fn stats(&self) -> SocketStats {
+        self.handle.stats()
+    }
+
+    /// close closes the session and stops the background thread, like dropping the socket
+    pub fn close(self) {
+        drop(self);
+    }
+}
+
+impl Drop for BlockingSocket {
+    fn drop(&mut self) {
+        if let Some(runtime) = self.runtime.take() {
+            runtime.block_on(self.handle.close());
+            runtime.shutdown_timeout(SHUTDOWN_TIMEOUT);
+        }
+    }
+}
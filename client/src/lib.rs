@@ -7,9 +7,85 @@ extern crate lazy_static;
 extern crate serde_derive;
 
 mod addr_cell;
+#[cfg(feature = "blocking")]
+mod blocking;
+mod nat_probe;
+mod session_channel;
 mod socket;
 
 pub use addr_cell::{AddrCell, ServerAddr};
-pub use socket::Socket;
+#[cfg(feature = "blocking")]
+pub use blocking::BlockingSocket;
+pub use nat_probe::{nat_probe, NatMappingBehavior, NatProbeError, NatProbeReport, NatProbeServer};
+pub use session_channel::{
+    DataChannel, DataChannelReceiver, DataChannelSender, PausedOverflow, ReuniteError, TrySendError,
+};
+pub use socket::{
+    AnswerError, FailureCause, MessageChunk, PrewarmHandle, SessionEndpoint, SignalingAnswer,
+    SignalingHdlrFn, Socket, SocketError, SocketHandle, SocketOptions, SocketOptionsBuilder,
+    SocketStats, StatsSnapshot,
+};
+pub use webrtc::api::setting_engine::{
+    config_error::{ConfigError, ConfigIssue},
+    CandidateFilterFn, Clock, ConnectivityCheckResultFn, InterfaceFilterFn, SettingEngine,
+};
+#[cfg(feature = "channel-mux")]
+pub use webrtc::data_channel::mux::{ChannelMux, MuxOverflow, MuxReceiver, MuxSender};
+pub use webrtc::data_channel::{
+    chunking::{ChunkError, ChunkReassembler},
+    data_channel_parameters::ChannelReliability,
+    data_channel_state::RTCDataChannelState,
+    OnCloseHdlrFn, RTCDataChannel,
+};
+pub use webrtc::dtls_transport::{
+    dtls_backend::DtlsBackendKind,
+    dtls_connection_info::DtlsConnectionInfo,
+    dtls_security_profile::DtlsSecurityProfile,
+    dtls_transport_state::RTCDtlsTransportState,
+    packet_interceptor::PacketInterceptor,
+    raw_channel::{RawChannel, RawChannelDemuxFn},
+};
+pub use webrtc::ice::agent::agent_config::KeepaliveKind;
+pub use webrtc::ice::candidate::CandidateDisposition;
+pub use webrtc::ice::gathering_context::GatheringContext;
+pub use webrtc::ice::packet_capture::{
+    pcap_file_sink::PcapFileSink, CapturedPacket, PacketDirection, PacketSink,
+};
+pub use webrtc::ice::util::SocketBufferSizes;
+pub use webrtc::ice_transport::{
+    ice_candidate::{RTCIceCandidate, RTCIceCandidateInit},
+    ice_candidate_stats::{CandidateStats, CandidateStatsReport},
+    ice_candidate_type::RTCIceCandidateType,
+    ice_check_result::ConnectivityCheckResult,
+    ice_connection_state::RTCIceConnectionState,
+    ice_protocol::RTCIceProtocol,
+    ice_role::RTCIceRole,
+};
+#[cfg(feature = "serde")]
+pub use webrtc::peer_connection::diagnostics::{
+    CandidateDiagnostics, CandidatePairDiagnostics, DataChannelDiagnostics, Diagnostics,
+    DtlsDiagnostics, FingerprintDiagnostics, IceDiagnostics, SctpDiagnostics,
+};
+pub use webrtc::peer_connection::{
+    configuration::{RTCConfiguration, RTCConfigurationBuilder},
+    event_log::{ConnectTimings, ConnectionEvent, ConnectionEventKind},
+    peer_connection_state::RTCPeerConnectionState,
+    sdp::{
+        sdp_modifiers::SdpModifiers, sdp_type::RTCSdpType,
+        session_description::RTCSessionDescription,
+    },
+};
+pub use webrtc::sdp::description::{
+    common::{Address, Attribute, Bandwidth, ConnectionInformation},
+    media::{MediaDescription, MediaName, RangedPort},
+    session::{Origin, RepeatTime, SessionDescription, TimeDescription, TimeZone, Timing},
+};
+pub use webrtc::sdp::Error as SdpError;
+
+pub use webrtc::sctp::association::AssociationState;
+pub use webrtc::sctp::stream::{CloseReason, ReorderStats, TransportError};
+pub use webrtc::sctp_transport::transport_events::{TransportEvent, TransportEventKind};
+pub use webrtc::util::conn::send_error::SendErrorCounts;
+pub use webrtc::util::redaction::RedactionMode;
 
 mod webrtc;
@@ -0,0 +1,313 @@
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use thiserror::Error as ThisError;
+use tokio::{
+    net::{lookup_host, UdpSocket},
+    time::{timeout, timeout_at, Instant},
+};
+
+use crate::webrtc::stun::{
+    agent::TransactionId,
+    fingerprint::FINGERPRINT,
+    message::{is_message, Getter, Message, BINDING_REQUEST, BINDING_SUCCESS},
+    xoraddr::XorMappedAddress,
+};
+
+const MESSAGE_SIZE: usize = 1500;
+
+/// RETRANSMIT_INTERVAL is how long a binding request waits for its response before it is sent
+/// again, until the timeout of the server runs out
+const RETRANSMIT_INTERVAL: Duration = Duration::from_millis(250);
+
+/// NatMappingBehavior classifies how the NAT in front of the client maps its socket to external
+/// addresses, see RFC 4787 Section 4.1
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+pub enum NatMappingBehavior {
+    /// Unknown means fewer than two servers answered, or the answers could not tell the
+    /// behaviors apart
+    Unknown,
+    /// EndpointIndependent means every server saw the same mapped address, also called
+    /// full-cone, restricted-cone or port-restricted-cone NAT or no NAT at all
+    EndpointIndependent,
+    /// AddressDependent means the mapping changed with the server's IP address but not with
+    /// its port
+    AddressDependent,
+    /// AddressAndPortDependent means the mapping changed with the server's port, also called
+    /// symmetric NAT
+    AddressAndPortDependent,
+    /// EndpointDependent means the mapping changed between servers on different IP
+    /// addresses, but no two servers shared an IP address to tell if the port matters too
+    EndpointDependent,
+}
+
+impl NatMappingBehavior {
+    /// is_endpoint_dependent tells whether peers are likely to have trouble connecting
+    /// directly to the client, as its candidates only work with the server they were learned from
+    pub fn is_endpoint_dependent(&self) -> bool {
+        matches!(
+            self,
+            NatMappingBehavior::AddressDependent
+                | NatMappingBehavior::AddressAndPortDependent
+                | NatMappingBehavior::EndpointDependent
+        )
+    }
+}
+
+/// NatProbeServer is the outcome of querying one of the servers given to nat_probe
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NatProbeServer {
+    /// server is the server as it was given to nat_probe
+    pub server: String,
+    /// addr is the address the server resolved to
+    pub addr: Option<SocketAddr>,
+    /// mapped_addr is the address the server saw the binding request come from
+    pub mapped_addr: Option<SocketAddr>,
+    /// error tells why the server did not answer
+    pub error: Option<String>,
+}
+
+/// NatProbeReport is what nat_probe learned about the NAT in front of the client
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NatProbeReport {
+    /// local_addr is the address of the socket the probe was sent from
+    pub local_addr: SocketAddr,
+    /// external_addr is the mapped address the first answering server saw
+    pub external_addr: Option<SocketAddr>,
+    pub mapping: NatMappingBehavior,
+    /// hairpinning tells whether a binding request sent to external_addr came back to the
+    /// socket, it is None when no server answered
+    pub hairpinning: Option<bool>,
+    pub servers: Vec<NatProbeServer>,
+}
+
+/// NatProbeError reports why nat_probe could not probe at all, failures of single servers are
+/// reported in NatProbeReport::servers instead
+#[derive(ThisError, Debug)]
+#[non_exhaustive]
+pub enum NatProbeError {
+    /// TooFewServers indicates fewer than two servers were given
+    #[error("at least two STUN servers are needed, got {0}")]
+    TooFewServers(usize),
+    /// Socket indicates the probing socket could not be bound
+    #[error("socket: {0}")]
+    Socket(#[from] std::io::Error),
+}
+
+/// nat_probe classifies the NAT in front of the client before connecting, e.g. to warn that
+/// peers may have trouble reaching it. It sends binding requests from one UDP socket to each of
+/// stun_servers in turn, given as "stun:host:port" or "host:port", waiting up to timeout for
+/// each, then compares the mapped addresses the servers saw. For the best classification pass
+/// at least two servers on different IP addresses and two on the same IP address but different
+/// ports. Only IPv4 is probed.
+pub async fn nat_probe(
+    stun_servers: &[String],
+    timeout: Duration,
+) -> Result<NatProbeReport, NatProbeError> {
+    if stun_servers.len() < 2 {
+        return Err(NatProbeError::TooFewServers(stun_servers.len()));
+    }
+
+    let socket = UdpSocket::bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0))).await?;
+    let local_addr = socket.local_addr()?;
+
+    let mut servers = Vec::with_capacity(stun_servers.len());
+    for server in stun_servers {
+        servers.push(probe_server(&socket, server, timeout).await);
+    }
+
+    let external_addr = servers.iter().find_map(|s| s.mapped_addr);
+    let hairpinning = match external_addr {
+        Some(external_addr) => Some(probe_hairpinning(&socket, external_addr, timeout).await),
+        None => None,
+    };
+
+    Ok(NatProbeReport {
+        local_addr,
+        external_addr,
+        mapping: classify_mapping(&servers),
+        hairpinning,
+        servers,
+    })
+}
+
+async fn probe_server(socket: &UdpSocket, server: &str, timeout: Duration) -> NatProbeServer {
+    let mut result = NatProbeServer {
+        server: server.to_owned(),
+        addr: None,
+        mapped_addr: None,
+        error: None,
+    };
+
+    let addr = match resolve_server(server).await {
+        Ok(addr) => addr,
+        Err(err) => {
+            result.error = Some(err);
+            return result;
+        }
+    };
+    result.addr = Some(addr);
+
+    match binding_request(socket, addr, timeout).await {
+        Ok(mapped_addr) => result.mapped_addr = Some(mapped_addr),
+        Err(err) => result.error = Some(err),
+    }
+    result
+}
+
+async fn resolve_server(server: &str) -> Result<SocketAddr, String> {
+    let host_port = server.strip_prefix("stun:").unwrap_or(server);
+    let addrs = lookup_host(host_port)
+        .await
+        .map_err(|err| format!("resolve {}: {}", host_port, err))?;
+
+    let mut addrs = addrs.filter(|addr| addr.is_ipv4());
+    addrs
+        .next()
+        .ok_or_else(|| format!("resolve {}: no IPv4 address", host_port))
+}
+
+/// binding_request sends binding requests to addr until a success response with the same
+/// transaction id arrives, and returns its XOR-MAPPED-ADDRESS
+async fn binding_request(
+    socket: &UdpSocket,
+    addr: SocketAddr,
+    timeout: Duration,
+) -> Result<SocketAddr, String> {
+    let mut request = Message::new();
+    request
+        .build(&[
+            Box::new(TransactionId::new()),
+            Box::new(BINDING_REQUEST),
+            Box::new(FINGERPRINT),
+        ])
+        .map_err(|err| err.to_string())?;
+
+    let deadline = Instant::now() + timeout;
+    let mut buf = vec![0u8; MESSAGE_SIZE];
+    while Instant::now() < deadline {
+        socket
+            .send_to(&request.raw, addr)
+            .await
+            .map_err(|err| format!("send to {}: {}", addr, err))?;
+
+        let retransmit = Instant::now() + RETRANSMIT_INTERVAL;
+        while let Ok(received) =
+            timeout_at(retransmit.min(deadline), socket.recv_from(&mut buf)).await
+        {
+            let (n, src_addr) = match received {
+                Ok(received) => received,
+                // e.g. ICMP port unreachable from an earlier server, the response may still come
+                Err(_) => continue,
+            };
+            if src_addr != addr {
+                continue;
+            }
+            let response = match decode_message(&buf[..n]) {
+                Some(response) => response,
+                None => continue,
+            };
+            if response.transaction_id != request.transaction_id {
+                continue;
+            }
+            if response.typ != BINDING_SUCCESS {
+                return Err(format!("{} answered {}", addr, response.typ));
+            }
+
+            let mut mapped = XorMappedAddress::default();
+            mapped
+                .get_from(&response)
+                .map_err(|err| format!("{} answered without mapped address: {}", addr, err))?;
+            return Ok(SocketAddr::new(mapped.ip, mapped.port));
+        }
+    }
+
+    Err(format!("{} did not answer within {:?}", addr, timeout))
+}
+
+/// probe_hairpinning sends a binding request from socket to its own external address and tells
+/// whether the NAT looped it back
+async fn probe_hairpinning(socket: &UdpSocket, external_addr: SocketAddr, wait: Duration) -> bool {
+    let mut request = Message::new();
+    if request
+        .build(&[
+            Box::new(TransactionId::new()),
+            Box::new(BINDING_REQUEST),
+            Box::new(FINGERPRINT),
+        ])
+        .is_err()
+    {
+        return false;
+    }
+    if socket.send_to(&request.raw, external_addr).await.is_err() {
+        return false;
+    }
+
+    let mut buf = vec![0u8; MESSAGE_SIZE];
+    let looped_back = async {
+        loop {
+            if let Ok((n, _)) = socket.recv_from(&mut buf).await {
+                if let Some(m) = decode_message(&buf[..n]) {
+                    // a response would come from whatever answers at external_addr, only the
+                    // request itself proves the loop
+                    if m.typ == BINDING_REQUEST && m.transaction_id == request.transaction_id {
+                        return;
+                    }
+                }
+            }
+        }
+    };
+    timeout(wait, looped_back).await.is_ok()
+}
+
+fn decode_message(buf: &[u8]) -> Option<Message> {
+    if !is_message(buf) {
+        return None;
+    }
+    let mut m = Message {
+        raw: vec![],
+        ..Message::default()
+    };
+    m.raw.extend_from_slice(buf);
+    m.decode().ok().map(|_| m)
+}
+
+/// classify_mapping compares the mapped addresses of every pair of answering servers, a pair
+/// on the same IP address tells if the mapping depends on the port, a pair on different IP
+/// addresses if it depends on the address
+fn classify_mapping(servers: &[NatProbeServer]) -> NatMappingBehavior {
+    let answers: Vec<(SocketAddr, SocketAddr)> = servers
+        .iter()
+        .filter_map(|s| Some((s.addr?, s.mapped_addr?)))
+        .collect();
+
+    let mut same_ip_pair = false;
+    let mut same_ip_changed = false;
+    let mut other_ip_pair = false;
+    let mut other_ip_changed = false;
+    for (i, (server_a, mapped_a)) in answers.iter().enumerate() {
+        for (server_b, mapped_b) in &answers[i + 1..] {
+            if server_a.ip() == server_b.ip() {
+                same_ip_pair = true;
+                same_ip_changed |= mapped_a != mapped_b;
+            } else {
+                other_ip_pair = true;
+                other_ip_changed |= mapped_a != mapped_b;
+            }
+        }
+    }
+
+    if same_ip_changed {
+        NatMappingBehavior::AddressAndPortDependent
+    } else if other_ip_changed && same_ip_pair {
+        NatMappingBehavior::AddressDependent
+    } else if other_ip_changed {
+        NatMappingBehavior::EndpointDependent
+    } else if other_ip_pair {
+        NatMappingBehavior::EndpointIndependent
+    } else {
+        NatMappingBehavior::Unknown
+    }
+}
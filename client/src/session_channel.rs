@@ -0,0 +1,590 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    future::poll_fn,
+    io,
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+use bytes::Bytes;
+use log::warn;
+use thiserror::Error as ThisError;
+use tokio::{
+    runtime::Handle,
+    sync::{mpsc, Notify},
+    time::timeout,
+};
+
+use crate::webrtc::data_channel::chunking;
+use crate::webrtc::data_channel::data_channel_parameters::ChannelReliability;
+use crate::webrtc::data_channel::internal::data_channel::DataChannel as DetachedDataChannel;
+use crate::webrtc::sctp::stream::ReorderStats;
+
+/// DataChannel is an additional data channel of a session, see SocketHandle::open_data_channel.
+/// Unlike the sender and receiver of open_channel it closes the channel once dropped, and it can
+/// be split into halves that are owned by different tasks.
+pub struct DataChannel {
+    sender: DataChannelSender,
+    receiver: DataChannelReceiver,
+}
+
+/// DataChannelSender is the sending half of a DataChannel, see DataChannel::split
+pub struct DataChannelSender {
+    shared: Arc<Shared>,
+    to_server_sender: mpsc::Sender<Bytes>,
+}
+
+/// DataChannelReceiver is the receiving half of a DataChannel, see DataChannel::split
+pub struct DataChannelReceiver {
+    shared: Arc<Shared>,
+    inbox: Arc<Inbox>,
+}
+
+/// PausedOverflow decides what happens to a message that arrives while delivery is paused and
+/// channel_capacity messages are already queued, see DataChannelReceiver::pause
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PausedOverflow {
+    /// Block stops reading from the channel until there is room again. Reliable data then
+    /// backs up in SCTP, which shrinks the receive window advertised to the server.
+    #[default]
+    Block,
+    /// DropOldest discards the oldest queued message, keeping the latest channel_capacity
+    DropOldest,
+    /// DropNewest discards the message that arrived, keeping the first channel_capacity
+    DropNewest,
+}
+
+/// Inbox holds the messages the read loop received until the receiving half takes them
+pub(crate) struct Inbox {
+    capacity: usize,
+    overflow: PausedOverflow,
+    state: Mutex<InboxState>,
+    // wakes the read loop waiting for room in the queue
+    space: Notify,
+    dropped: AtomicU64,
+}
+
+struct InboxState {
+    messages: VecDeque<Bytes>,
+    paused: bool,
+    // the read loop exited, no more messages are coming
+    ended: bool,
+    // the receiving half is gone, the read loop stops
+    abandoned: bool,
+    waker: Option<Waker>,
+    // how many queued messages it takes to wake the receiving half
+    wake_at: usize,
+}
+
+impl Default for InboxState {
+    fn default() -> Self {
+        InboxState {
+            messages: VecDeque::new(),
+            paused: false,
+            ended: false,
+            abandoned: false,
+            waker: None,
+            wake_at: 1,
+        }
+    }
+}
+
+impl InboxState {
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Inbox {
+    pub(crate) fn new(capacity: usize, overflow: PausedOverflow) -> Self {
+        Inbox {
+            capacity,
+            overflow,
+            state: Mutex::new(InboxState::default()),
+            space: Notify::new(),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// push queues message for the receiving half, waiting while the queue is full unless
+    /// delivery is paused with a dropping PausedOverflow. It returns false once the receiving
+    /// half is dropped.
+    pub(crate) async fn push(&self, message: Bytes) -> bool {
+        loop {
+            {
+                let mut state = self.state.lock().unwrap();
+                if state.abandoned {
+                    return false;
+                }
+                if state.messages.len() < self.capacity {
+                    state.messages.push_back(message);
+                    if state.messages.len() >= state.wake_at {
+                        state.wake();
+                    }
+                    return true;
+                }
+                if state.paused {
+                    match self.overflow {
+                        PausedOverflow::Block => {}
+                        PausedOverflow::DropOldest => {
+                            state.messages.pop_front();
+                            state.messages.push_back(message);
+                            self.dropped.fetch_add(1, Ordering::SeqCst);
+                            return true;
+                        }
+                        PausedOverflow::DropNewest => {
+                            self.dropped.fetch_add(1, Ordering::SeqCst);
+                            return true;
+                        }
+                    }
+                }
+            }
+            // notify_one keeps the permit if the room was made before this waits
+            self.space.notified().await;
+        }
+    }
+
+    /// end tells the receiving half that no more messages are coming
+    pub(crate) fn end(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.ended = true;
+        state.wake();
+    }
+
+    fn poll_pop(&self, cx: &mut Context<'_>) -> Poll<Option<Bytes>> {
+        let mut state = self.state.lock().unwrap();
+        if !state.paused {
+            if let Some(message) = state.messages.pop_front() {
+                self.space.notify_one();
+                return Poll::Ready(Some(message));
+            }
+            if state.ended {
+                return Poll::Ready(None);
+            }
+        }
+        state.waker = Some(cx.waker().clone());
+        state.wake_at = 1;
+        Poll::Pending
+    }
+
+    /// poll_queued is ready once delivery is not paused and min messages are queued, or the
+    /// read loop ended. It takes no message.
+    fn poll_queued(&self, cx: &mut Context<'_>, min: usize) -> Poll<()> {
+        // a full queue is as many as there will be while the read loop waits for room
+        let min = min.min(self.capacity);
+        let mut state = self.state.lock().unwrap();
+        if !state.paused && (state.messages.len() >= min || state.ended) {
+            return Poll::Ready(());
+        }
+        state.waker = Some(cx.waker().clone());
+        state.wake_at = min;
+        Poll::Pending
+    }
+
+    /// take returns up to max queued messages, None if delivery is paused or nothing is queued
+    fn take(&self, max: usize) -> Option<Vec<Bytes>> {
+        let mut state = self.state.lock().unwrap();
+        if state.paused || state.messages.is_empty() {
+            return None;
+        }
+        let n = max.min(state.messages.len());
+        let batch = state.messages.drain(..n).collect();
+        self.space.notify_one();
+        Some(batch)
+    }
+
+    fn is_ended(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.ended && state.messages.is_empty()
+    }
+
+    fn set_paused(&self, paused: bool) {
+        let mut state = self.state.lock().unwrap();
+        state.paused = paused;
+        state.wake();
+    }
+}
+
+/// Shared is held by both halves, the channel is closed when the last one is dropped
+struct Shared {
+    data_channel: Arc<DetachedDataChannel>,
+    next_transfer_id: AtomicU32,
+}
+
+impl Shared {
+    /// closed_error is the error a send fails with once the channel is closed, see
+    /// DataChannelSender::send
+    fn closed_error(&self) -> io::Error {
+        match self.data_channel.close_reason() {
+            Some(reason) => reason.into_io_error(),
+            None => io::ErrorKind::NotConnected.into(),
+        }
+    }
+
+    // close runs on a task of its own, so that a cancelled close still completes
+    async fn close(&self) {
+        let data_channel = Arc::clone(&self.data_channel);
+        let closing = tokio::spawn(async move { data_channel.close().await });
+        if let Ok(Err(err)) = closing.await {
+            warn!("error closing data channel: {}", err);
+        }
+    }
+}
+
+impl Drop for Shared {
+    fn drop(&mut self) {
+        if self.data_channel.is_closed() {
+            return;
+        }
+        // without a runtime the channel is closed with its peer connection
+        if let Ok(handle) = Handle::try_current() {
+            let data_channel = Arc::clone(&self.data_channel);
+            handle.spawn(async move {
+                if let Err(err) = data_channel.close().await {
+                    warn!("error closing data channel: {}", err);
+                }
+            });
+        }
+    }
+}
+
+impl DataChannel {
+    /// new wraps a detached data channel whose messages are passed on by the write loop at the
+    /// other end of to_server_sender and the read loop filling inbox
+    pub(crate) fn new(
+        data_channel: Arc<DetachedDataChannel>,
+        to_server_sender: mpsc::Sender<Bytes>,
+        inbox: Arc<Inbox>,
+    ) -> Self {
+        let shared = Arc::new(Shared {
+            data_channel,
+            next_transfer_id: AtomicU32::new(0),
+        });
+        DataChannel {
+            sender: DataChannelSender {
+                shared: Arc::clone(&shared),
+                to_server_sender,
+            },
+            receiver: DataChannelReceiver { shared, inbox },
+        }
+    }
+
+    /// send is DataChannelSender::send
+    pub async fn send(&self, message: Bytes) -> io::Result<usize> {
+        self.sender.send(message).await
+    }
+
+    /// send_chunked is DataChannelSender::send_chunked
+    pub async fn send_chunked(
+        &self,
+        data: Bytes,
+        chunk_size: usize,
+        progress: impl Fn(u64, u64),
+    ) -> io::Result<u32> {
+        self.sender.send_chunked(data, chunk_size, progress).await
+    }
+
+    /// try_send is DataChannelSender::try_send
+    pub fn try_send(&self, message: &Bytes) -> Result<usize, TrySendError> {
+        self.sender.try_send(message)
+    }
+
+    /// buffered_amount is DataChannelSender::buffered_amount
+    pub fn buffered_amount(&self) -> usize {
+        self.sender.buffered_amount()
+    }
+
+    /// negotiated_reliability is DataChannelSender::negotiated_reliability
+    pub fn negotiated_reliability(&self) -> Option<ChannelReliability> {
+        self.sender.negotiated_reliability()
+    }
+
+    /// recv is DataChannelReceiver::recv
+    pub async fn recv(&mut self) -> Option<Bytes> {
+        self.receiver.recv().await
+    }
+
+    /// poll_recv is DataChannelReceiver::poll_recv
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<Bytes>> {
+        self.receiver.poll_recv(cx)
+    }
+
+    /// recv_batch is DataChannelReceiver::recv_batch
+    pub async fn recv_batch(
+        &mut self,
+        max_batch: usize,
+        max_delay: Duration,
+    ) -> Option<Vec<Bytes>> {
+        self.receiver.recv_batch(max_batch, max_delay).await
+    }
+
+    /// pause is DataChannelReceiver::pause
+    pub fn pause(&self) {
+        self.receiver.pause()
+    }
+
+    /// resume is DataChannelReceiver::resume
+    pub fn resume(&self) {
+        self.receiver.resume()
+    }
+
+    /// is_paused is DataChannelReceiver::is_paused
+    pub fn is_paused(&self) -> bool {
+        self.receiver.is_paused()
+    }
+
+    /// messages_dropped is DataChannelReceiver::messages_dropped
+    pub fn messages_dropped(&self) -> u64 {
+        self.receiver.messages_dropped()
+    }
+
+    /// reorder_stats is DataChannelReceiver::reorder_stats
+    pub fn reorder_stats(&self) -> ReorderStats {
+        self.receiver.reorder_stats()
+    }
+
+    /// close closes the channel in both directions
+    pub async fn close(&self) {
+        self.sender.shared.close().await;
+    }
+
+    /// split separates the channel into a sending and a receiving half, e.g. to send from one
+    /// task and receive in another. The channel is closed once both halves are dropped or either
+    /// calls close, see DataChannelSender::reunite to put them back together.
+    pub fn split(self) -> (DataChannelSender, DataChannelReceiver) {
+        (self.sender, self.receiver)
+    }
+}
+
+impl DataChannelSender {
+    /// send queues message to be sent and returns its length, waiting while the queue of
+    /// channel_capacity messages is full. The queue is only taken from while less than 1 MiB is
+    /// buffered in SCTP, so it fills up when the server stops acknowledging. Once the channel is
+    /// closed it fails with std::io::ErrorKind::ConnectionAborted if the connection failed and
+    /// NotConnected otherwise, holding the CloseReason as the inner error. Cancelling it while it
+    /// waits leaves the message unsent.
+    pub async fn send(&self, message: Bytes) -> io::Result<usize> {
+        if self.shared.data_channel.is_closed() {
+            return Err(self.shared.closed_error());
+        }
+        let length = message.len();
+        self.to_server_sender
+            .send(message)
+            .await
+            .map_err(|_| self.shared.closed_error())?;
+        Ok(length)
+    }
+
+    /// send_chunked sends data larger than a message should be as chunks of up to chunk_size
+    /// bytes and returns the transfer id they carry. Each chunk has a 12 byte header with the
+    /// transfer id, its index and the chunk count, so a ChunkReassembler on the other side can
+    /// put it back together. Chunks are queued with send, so sending waits while the server
+    /// lags behind, and progress is called with the bytes queued so far and the total after
+    /// each one.
+    ///
+    /// It fails with std::io::ErrorKind::InvalidInput if chunk_size is zero or data takes more
+    /// than u32::MAX chunks, and like send once the channel is closed. The chunks queued before
+    /// are still sent then, as they are when sending is cancelled part way, which leaves the
+    /// transfer incomplete for the ChunkReassembler.
+    pub async fn send_chunked(
+        &self,
+        data: Bytes,
+        chunk_size: usize,
+        progress: impl Fn(u64, u64),
+    ) -> io::Result<u32> {
+        if chunk_size == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "chunk size must not be zero",
+            ));
+        }
+        let total = u32::try_from(chunking::chunk_count(data.len(), chunk_size)).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "too many chunks for a transfer",
+            )
+        })?;
+        let transfer_id = self.shared.next_transfer_id.fetch_add(1, Ordering::SeqCst);
+
+        let total_bytes = data.len() as u64;
+        let mut sent_bytes = 0;
+        for index in 0..total {
+            let chunk = chunking::chunk(&data, chunk_size, transfer_id, index, total);
+            sent_bytes += (chunk.len() - chunking::CHUNK_HEADER_LEN) as u64;
+            self.send(chunk).await?;
+            progress(sent_bytes, total_bytes);
+        }
+        Ok(transfer_id)
+    }
+
+    /// try_send is send without waiting, e.g. from a game loop. It either queues the whole
+    /// message and returns its length or fails with TrySendError::Full if the queue is full and
+    /// TrySendError::Closed once the channel is closed.
+    pub fn try_send(&self, message: &Bytes) -> Result<usize, TrySendError> {
+        if self.shared.data_channel.is_closed() {
+            return Err(TrySendError::Closed);
+        }
+        self.to_server_sender
+            .try_send(message.clone())
+            .map_err(|err| match err {
+                mpsc::error::TrySendError::Full(_) => TrySendError::Full {
+                    buffered: self.buffered_amount(),
+                },
+                mpsc::error::TrySendError::Closed(_) => TrySendError::Closed,
+            })?;
+        Ok(message.len())
+    }
+
+    /// buffered_amount returns the number of bytes handed to SCTP that are not sent or not
+    /// acknowledged yet. Messages still waiting in the queue of send are not included.
+    pub fn buffered_amount(&self) -> usize {
+        self.shared.data_channel.buffered_amount()
+    }
+
+    /// negotiated_reliability returns the reliability the remote runs the channel with, None
+    /// until it confirmed the channel, see RTCDataChannel::negotiated_reliability
+    pub fn negotiated_reliability(&self) -> Option<ChannelReliability> {
+        self.shared.data_channel.negotiated_reliability()
+    }
+
+    /// close closes the channel in both directions, the receiving half returns None once the
+    /// messages received before are drained
+    pub async fn close(&self) {
+        self.shared.close().await;
+    }
+
+    /// reunite puts the halves of a DataChannel back together. It fails and hands both back if
+    /// they were split from different channels.
+    pub fn reunite(self, receiver: DataChannelReceiver) -> Result<DataChannel, ReuniteError> {
+        if !Arc::ptr_eq(&self.shared, &receiver.shared) {
+            return Err(ReuniteError(self, receiver));
+        }
+        Ok(DataChannel {
+            sender: self,
+            receiver,
+        })
+    }
+}
+
+impl DataChannelReceiver {
+    /// recv returns the next message, or None once the channel is closed and the messages
+    /// received before are drained. While delivery is paused it waits for resume. Cancelling it
+    /// loses no message, the next call returns it.
+    pub async fn recv(&mut self) -> Option<Bytes> {
+        poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// poll_recv is recv for use in a Stream implementation or a hand written future
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<Bytes>> {
+        self.inbox.poll_pop(cx)
+    }
+
+    /// recv_batch returns the next messages in the order they arrived, up to max_batch of them,
+    /// or None like recv. Once a message is queued it waits up to max_delay for max_batch to
+    /// fill, so at high message rates the receiving task wakes once per batch instead of once
+    /// per message. A max_batch of zero is taken as one.
+    pub async fn recv_batch(
+        &mut self,
+        max_batch: usize,
+        max_delay: Duration,
+    ) -> Option<Vec<Bytes>> {
+        let max_batch = max_batch.max(1);
+        loop {
+            poll_fn(|cx| self.inbox.poll_queued(cx, 1)).await;
+            if max_batch > 1 && !max_delay.is_zero() {
+                let _ = timeout(
+                    max_delay,
+                    poll_fn(|cx| self.inbox.poll_queued(cx, max_batch)),
+                )
+                .await;
+            }
+            if let Some(batch) = self.inbox.take(max_batch) {
+                return Some(batch);
+            }
+            if self.inbox.is_ended() {
+                return None;
+            }
+            // paused in between, wait for resume
+        }
+    }
+
+    /// pause holds back delivery, e.g. while loading a level, so recv returns nothing until
+    /// resume. Messages keep being received and acknowledged and are queued, up to
+    /// channel_capacity of them. What happens to the ones beyond is decided by
+    /// SocketOptions::paused_overflow.
+    pub fn pause(&self) {
+        self.inbox.set_paused(true);
+    }
+
+    /// resume ends a pause, recv returns the queued messages in the order they arrived before
+    /// any received later
+    pub fn resume(&self) {
+        self.inbox.set_paused(false);
+    }
+
+    /// is_paused tells whether delivery is paused
+    pub fn is_paused(&self) -> bool {
+        self.inbox.state.lock().unwrap().paused
+    }
+
+    /// messages_dropped returns how many messages were discarded because they arrived while
+    /// delivery was paused and the queue was full
+    pub fn messages_dropped(&self) -> u64 {
+        self.inbox.dropped.load(Ordering::SeqCst)
+    }
+
+    /// reorder_stats returns how much the network reordered and duplicated the messages
+    /// received on an unordered channel, see RTCDataChannel::reorder_stats
+    pub fn reorder_stats(&self) -> ReorderStats {
+        self.shared.data_channel.reorder_stats()
+    }
+
+    /// close closes the channel in both directions, the sending half fails from then on
+    pub async fn close(&self) {
+        self.shared.close().await;
+    }
+}
+
+impl Drop for DataChannelReceiver {
+    fn drop(&mut self) {
+        self.inbox.state.lock().unwrap().abandoned = true;
+        self.inbox.space.notify_one();
+    }
+}
+
+/// ReuniteError is returned by DataChannelSender::reunite for halves of different channels,
+/// it holds both of them
+pub struct ReuniteError(pub DataChannelSender, pub DataChannelReceiver);
+
+impl fmt::Debug for ReuniteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ReuniteError").finish()
+    }
+}
+
+impl fmt::Display for ReuniteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "tried to reunite halves of different data channels")
+    }
+}
+
+impl std::error::Error for ReuniteError {}
+
+/// TrySendError is returned by DataChannelSender::try_send for a message that was not queued
+#[derive(ThisError, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrySendError {
+    /// Full indicates the queue of channel_capacity messages is full. buffered is the
+    /// buffered_amount at the time, the bytes SCTP has not had acknowledged yet.
+    #[error("send queue full, {buffered} bytes buffered")]
+    Full { buffered: usize },
+    /// Closed indicates the channel is closed, send tells why
+    #[error("data channel closed")]
+    Closed,
+}
@@ -1,22 +1,994 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Weak,
+    },
+    time::{Duration, Instant},
+};
 
 use anyhow::{Error, Result};
 use bytes::Bytes;
 use log::warn;
 use reqwest::{Client as HttpClient, Response};
+use thiserror::Error as ThisError;
 use tinyjson::JsonValue;
-use tokio::{sync::mpsc, time::sleep};
+use tokio::{
+    sync::{broadcast, mpsc, oneshot, Notify},
+    time::{sleep, timeout, MissedTickBehavior},
+};
 
 use crate::webrtc::{
-    data_channel::internal::data_channel::DataChannel,
-    peer_connection::{sdp::session_description::RTCSessionDescription, RTCPeerConnection},
+    api::{
+        setting_engine::{
+            config_error::{ConfigError, ConfigIssue},
+            SettingEngine,
+        },
+        API,
+    },
+    data_channel::{internal::data_channel::DataChannel, RTCDataChannel, DEFAULT_PRIORITY},
+    dtls_transport::{
+        dtls_connection_info::DtlsConnectionInfo,
+        dtls_transport_state::RTCDtlsTransportState,
+        raw_channel::{RawChannel, RawChannelDemuxFn},
+    },
+    error::Error as RtcError,
+    ice::util::SocketBufferSizes,
+    ice_transport::{
+        ice_candidate::RTCIceCandidateInit, ice_candidate_stats::CandidateStatsReport,
+        ice_connection_state::RTCIceConnectionState, ice_role::RTCIceRole,
+    },
+    internal::Error as DataChannelError,
+    peer_connection::{
+        configuration::RTCConfiguration,
+        event_log::{ConnectTimings, ConnectionEvent},
+        peer_connection_state::RTCPeerConnectionState,
+        sdp::session_description::RTCSessionDescription,
+        RTCPeerConnection,
+    },
+    sctp::Error as SctpError,
+    sctp_transport::{transport_events::TransportEvent, SctpTraffic},
+    util::{conn::send_error::SendErrorCounts, spawner::Spawner},
 };
 
-use super::addr_cell::AddrCell;
+use super::addr_cell::{AddrCell, ServerAddr};
+use super::session_channel::{DataChannel as SessionDataChannel, Inbox, PausedOverflow};
 
 const MESSAGE_SIZE: usize = 1500;
 
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_CHANNEL_CAPACITY: usize = 1024;
+const DEFAULT_PREWARM_TTL: Duration = Duration::from_secs(60);
+// bytes handed to SCTP but not acknowledged yet at which the write loop stops taking messages
+// from the queue, it resumes once half of them are acknowledged
+const MAX_BUFFERED_AMOUNT: usize = 1024 * 1024;
+
+// SocketOptionsBuilder::low_latency
+const LOW_LATENCY_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const LOW_LATENCY_ICE_CHECK_INTERVAL: Duration = Duration::from_millis(20);
+const LOW_LATENCY_ICE_MAX_BINDING_REQUESTS: u16 = 5;
+const LOW_LATENCY_ICE_DISCONNECTED_TIMEOUT: Duration = Duration::from_secs(2);
+const LOW_LATENCY_ICE_FAILED_TIMEOUT: Duration = Duration::from_secs(3);
+const LOW_LATENCY_ICE_KEEPALIVE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// SignalingHdlrFn delivers the local offer SDP to the server and resolves with its answer
+pub type SignalingHdlrFn = Box<
+    dyn (FnOnce(String) -> Pin<Box<dyn Future<Output = Result<SignalingAnswer>> + Send + 'static>>)
+        + Send
+        + Sync,
+>;
+
+/// SessionEndpoint describes how the offer reaches the server
+pub enum SessionEndpoint {
+    /// Http posts the offer to a webrtc-unreliable session url, e.g. "http://host:port/rtc_session"
+    Http(String),
+    /// Signaling hands the offer to a user supplied callback
+    Signaling(SignalingHdlrFn),
+}
+
+/// SignalingAnswer is the server's answer SDP together with its ICE candidate
+#[derive(Debug, Clone)]
+pub struct SignalingAnswer {
+    pub sdp: String,
+    pub candidate: String,
+}
+
+/// SocketOptions configures Socket::connect_session
+pub struct SocketOptions {
+    pub setting_engine: SettingEngine,
+    pub configuration: RTCConfiguration,
+    /// connect_timeout bounds the time from sending the offer until the data channel is open
+    pub connect_timeout: Duration,
+    /// channel_capacity is the buffer size of the returned sender and receiver
+    pub channel_capacity: usize,
+    /// paused_overflow decides what a paused DataChannel does with messages once it holds
+    /// channel_capacity of them, see DataChannelReceiver::pause
+    pub paused_overflow: PausedOverflow,
+    /// prewarm_ttl is how long the result of Socket::prewarm is used for, see
+    /// PrewarmHandle::is_fresh
+    pub prewarm_ttl: Duration,
+}
+
+impl Default for SocketOptions {
+    fn default() -> Self {
+        SocketOptions {
+            setting_engine: SettingEngine::default(),
+            configuration: RTCConfiguration::default(),
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            channel_capacity: DEFAULT_CHANNEL_CAPACITY,
+            paused_overflow: PausedOverflow::default(),
+            prewarm_ttl: DEFAULT_PREWARM_TTL,
+        }
+    }
+}
+
+impl SocketOptions {
+    /// builder starts from the default options
+    pub fn builder() -> SocketOptionsBuilder {
+        SocketOptionsBuilder::default()
+    }
+
+    /// validate checks the options and their setting engine for settings that contradict each
+    /// other or would only fail the connection once it is being set up. It returns all of them,
+    /// not just the first. Socket::connect_session and prewarm validate the options they get.
+    pub fn validate(&self) -> std::result::Result<(), ConfigError> {
+        let mut issues = vec![];
+        if self.connect_timeout.is_zero() {
+            issues.push(ConfigIssue::ConnectTimeoutZero);
+        }
+        if self.channel_capacity == 0 {
+            issues.push(ConfigIssue::ChannelCapacityZero);
+        }
+        issues.extend(self.setting_engine.config_issues());
+
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError { issues })
+        }
+    }
+
+    /// low_latency_defaults are the default options with SocketOptionsBuilder::low_latency
+    /// applied
+    pub fn low_latency_defaults() -> Self {
+        SocketOptions::builder().low_latency().options
+    }
+}
+
+/// SocketOptionsBuilder builds SocketOptions and checks them before Socket::connect_session
+/// gets to use them, see SocketOptions::builder
+#[derive(Default)]
+pub struct SocketOptionsBuilder {
+    options: SocketOptions,
+}
+
+impl SocketOptionsBuilder {
+    /// setting_engine replaces the setting engine, including what low_latency set on it
+    pub fn setting_engine(mut self, setting_engine: SettingEngine) -> Self {
+        self.options.setting_engine = setting_engine;
+        self
+    }
+
+    pub fn configuration(mut self, configuration: RTCConfiguration) -> Self {
+        self.options.configuration = configuration;
+        self
+    }
+
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.options.connect_timeout = connect_timeout;
+        self
+    }
+
+    pub fn channel_capacity(mut self, channel_capacity: usize) -> Self {
+        self.options.channel_capacity = channel_capacity;
+        self
+    }
+
+    pub fn paused_overflow(mut self, paused_overflow: PausedOverflow) -> Self {
+        self.options.paused_overflow = paused_overflow;
+        self
+    }
+
+    pub fn prewarm_ttl(mut self, prewarm_ttl: Duration) -> Self {
+        self.options.prewarm_ttl = prewarm_ttl;
+        self
+    }
+
+    /// low_latency tunes the options for a game client that connects to a known server and
+    /// wants to notice a lost connection within seconds: ICE checks are paced at 20ms and a
+    /// candidate pair fails after 5 unanswered checks, the connection counts as disconnected
+    /// after 2s and failed 3s later with keepalives every 500ms, and connecting gives up after
+    /// 5s. The other settings of the current setting engine are kept.
+    pub fn low_latency(mut self) -> Self {
+        let setting_engine = &mut self.options.setting_engine;
+        setting_engine.set_ice_check_interval(LOW_LATENCY_ICE_CHECK_INTERVAL);
+        setting_engine.set_ice_max_binding_requests(LOW_LATENCY_ICE_MAX_BINDING_REQUESTS);
+        setting_engine.set_ice_timeouts(
+            Some(LOW_LATENCY_ICE_DISCONNECTED_TIMEOUT),
+            Some(LOW_LATENCY_ICE_FAILED_TIMEOUT),
+            Some(LOW_LATENCY_ICE_KEEPALIVE_INTERVAL),
+        );
+        self.options.connect_timeout = LOW_LATENCY_CONNECT_TIMEOUT;
+        self
+    }
+
+    /// build returns the options, or SocketError::Options listing every setting that would
+    /// make the connect fail, e.g. a zero connect_timeout or channel_capacity, see
+    /// SocketOptions::validate
+    pub fn build(self) -> std::result::Result<SocketOptions, SocketError> {
+        self.options.validate().map_err(SocketError::Options)?;
+
+        Ok(self.options)
+    }
+}
+
+/// SocketError reports which phase of Socket::connect_session failed, the FailureCause of the
+/// phase is its source. The messages name only the error itself, walk the sources for the whole
+/// chain.
+#[derive(ThisError, Debug, Clone)]
+#[non_exhaustive]
+pub enum SocketError {
+    /// Options indicates SocketOptionsBuilder::build or the connect rejected the options
+    #[error("invalid options")]
+    Options(#[source] ConfigError),
+    /// Offer indicates the local offer could not be created or applied
+    #[error("offer failed")]
+    Offer(#[source] FailureCause),
+    /// Signaling indicates the offer/answer exchange failed or the answer was rejected
+    #[error("signaling failed")]
+    Signaling(#[source] FailureCause),
+    /// Ice indicates no working candidate pair was found
+    #[error("ice failed")]
+    Ice(#[source] FailureCause, Vec<ConnectionEvent>),
+    /// Dtls indicates the DTLS handshake failed, or the connection refused an export of keying
+    /// material
+    #[error("dtls failed")]
+    Dtls(#[source] FailureCause, Vec<ConnectionEvent>),
+    /// ChannelOpen indicates the transports came up but the data channel did not open
+    #[error("data channel did not open")]
+    ChannelOpen(#[source] FailureCause, Vec<ConnectionEvent>),
+    /// ConnectionClosed indicates SocketHandle was used after the session was closed
+    #[error("connection closed")]
+    ConnectionClosed,
+    /// ConnectionFailed indicates SocketHandle was used after ICE or DTLS gave up on the
+    /// session, see SocketHandle::recent_events for why
+    #[error("connection failed")]
+    ConnectionFailed,
+    /// Runtime indicates BlockingSocket::connect could not start its runtime
+    #[cfg(feature = "blocking")]
+    #[error("runtime could not be started")]
+    Runtime(#[source] Arc<std::io::Error>),
+}
+
+/// FailureCause is why a phase of the session failed, see SocketError. The causes raised by
+/// the WebRTC stack, the signaling endpoint or the server's answer carry that error as their
+/// source.
+#[derive(ThisError, Debug, Clone)]
+#[non_exhaustive]
+pub enum FailureCause {
+    /// TimedOut indicates the connect_timeout passed before the phase completed
+    #[error("timed out")]
+    TimedOut,
+    /// ChecksFailed indicates the connectivity checks failed on every candidate pair
+    #[error("connectivity checks failed")]
+    ChecksFailed,
+    /// NoCandidates indicates no local ICE candidate could be gathered
+    #[error("no candidates gathered")]
+    NoCandidates,
+    /// NoLocalDescription indicates the offer was not applied when it was to be sent
+    #[error("no local description")]
+    NoLocalDescription,
+    /// NoDataChannel indicates the data channel was gone before it opened
+    #[error("no data channel")]
+    NoDataChannel,
+    /// PeerConnectionDropped indicates the peer connection was dropped before the phase
+    /// completed
+    #[error("peer connection dropped")]
+    PeerConnectionDropped,
+    /// Endpoint indicates the SessionEndpoint failed to exchange the offer for an answer, e.g.
+    /// the HTTP request failed or the signaling callback returned an error
+    #[error("signaling endpoint failed")]
+    Endpoint(#[source] Arc<dyn std::error::Error + Send + Sync>),
+    /// InvalidAnswer indicates the server's answer could not be parsed or applied
+    #[error("invalid answer")]
+    InvalidAnswer(#[source] Arc<dyn std::error::Error + Send + Sync>),
+    /// InvalidCandidate indicates an ICE candidate could not be parsed or added
+    #[error("invalid candidate")]
+    InvalidCandidate(#[source] Arc<dyn std::error::Error + Send + Sync>),
+    /// Handshake indicates the DTLS handshake failed
+    #[error("handshake failed")]
+    Handshake(#[source] Arc<dyn std::error::Error + Send + Sync>),
+    /// Rtc indicates any other failure of the WebRTC stack
+    #[error("webrtc stack failed")]
+    Rtc(#[source] Arc<dyn std::error::Error + Send + Sync>),
+}
+
+impl FailureCause {
+    fn rtc(err: RtcError) -> Self {
+        FailureCause::Rtc(Arc::new(err))
+    }
+}
+
+/// AnswerError is why the answer of a webrtc-unreliable server could not be read, the source of
+/// FailureCause::InvalidAnswer for SessionEndpoint::Http
+#[derive(ThisError, Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AnswerError {
+    /// NotJson indicates the response body is not json
+    #[error("session response is not json: {0}")]
+    NotJson(String),
+    /// Missing indicates the response has no string at the object's key, e.g. answer.sdp
+    #[error("session response is missing {0}.{1}")]
+    Missing(&'static str, &'static str),
+}
+
+impl SocketError {
+    /// cause returns why the phase failed, None for the variants without a FailureCause
+    pub fn cause(&self) -> Option<&FailureCause> {
+        match self {
+            SocketError::Offer(cause)
+            | SocketError::Signaling(cause)
+            | SocketError::Ice(cause, _)
+            | SocketError::Dtls(cause, _)
+            | SocketError::ChannelOpen(cause, _) => Some(cause),
+            _ => None,
+        }
+    }
+
+    /// recent_events returns what the peer connection went through up to the failure, see
+    /// SocketHandle::recent_events. It is empty for the phases before connecting.
+    pub fn recent_events(&self) -> &[ConnectionEvent] {
+        match self {
+            SocketError::Ice(_, events)
+            | SocketError::Dtls(_, events)
+            | SocketError::ChannelOpen(_, events) => events,
+            _ => &[],
+        }
+    }
+
+    fn with_recent_events(mut self, recent_events: Vec<ConnectionEvent>) -> Self {
+        match &mut self {
+            SocketError::Ice(_, events)
+            | SocketError::Dtls(_, events)
+            | SocketError::ChannelOpen(_, events) => *events = recent_events,
+            _ => {}
+        }
+        self
+    }
+}
+
+/// PrewarmHandle is a session prepared by Socket::prewarm, it is connected with
+/// Socket::connect_prewarmed_session
+pub struct PrewarmHandle {
+    api: API,
+    peer_connection: Arc<RTCPeerConnection>,
+    configuration: RTCConfiguration,
+    connect_timeout: Duration,
+    channel_capacity: usize,
+    paused_overflow: PausedOverflow,
+    prepared_at: Instant,
+    ttl: Duration,
+}
+
+impl PrewarmHandle {
+    /// age returns how long ago the handle was prepared
+    pub fn age(&self) -> Duration {
+        self.prepared_at.elapsed()
+    }
+
+    /// is_fresh tells whether the handle is younger than SocketOptions::prewarm_ttl. A stale
+    /// one is not trusted anymore, e.g. the local addresses may have changed since, so
+    /// Socket::connect_prewarmed_session prepares the session again instead.
+    pub fn is_fresh(&self) -> bool {
+        self.age() < self.ttl
+    }
+
+    // into_peer_connection returns the prepared peer connection, or a new one in place of a
+    // stale one
+    async fn into_peer_connection(self) -> Arc<RTCPeerConnection> {
+        if self.is_fresh() {
+            return self.peer_connection;
+        }
+
+        log::debug!(
+            "prewarmed session is {:?} old, past its ttl of {:?}, preparing it again",
+            self.age(),
+            self.ttl
+        );
+        // closed first, the new one may bind the same socket, see SettingEngine::set_udp_socket
+        if let Err(err) = self.peer_connection.close().await {
+            warn!("error closing peer connection: {}", err);
+        }
+        RTCPeerConnection::new(&self.api, self.configuration).await
+    }
+}
+
+/// SocketStats counts the messages carried by a connected session
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SocketStats {
+    pub messages_sent: u64,
+    pub bytes_sent: u64,
+    pub messages_received: u64,
+    pub bytes_received: u64,
+    /// oversized_messages counts the inbound messages dropped for exceeding the max message
+    /// size of the association, the channel stays open
+    pub oversized_messages: u64,
+    /// sctp_bad_verification_tags counts the SCTP packets dropped for a verification tag the
+    /// association does not know, e.g. from a peer that restarted its end, see
+    /// SettingEngine::set_sctp_accept_peer_restart
+    pub sctp_bad_verification_tags: u64,
+    /// send_errors counts the datagrams dropped or retried for a transient send error, e.g.
+    /// EMSGSIZE after the route MTU shrank, instead of failing the connection
+    pub send_errors: SendErrorCounts,
+    /// oversized_datagrams counts the inbound datagrams dropped for exceeding the receive MTU,
+    /// see SettingEngine::set_receive_mtu
+    pub oversized_datagrams: u64,
+    /// socket_buffer_sizes are the receive and send buffer sizes the OS reports for the UDP
+    /// sockets of the session, after SettingEngine::set_socket_buffer_sizes. Linux reports
+    /// twice the size set. None if the sockets are virtual or the size could not be read.
+    pub socket_buffer_sizes: SocketBufferSizes,
+    /// connect_timings is how long establishing the session took, see
+    /// SocketHandle::connect_timings
+    pub connect_timings: ConnectTimings,
+}
+
+/// StatsSnapshot is one item of SocketHandle::stats_stream, the counters of the session with
+/// the rates over the interval since the previous snapshot
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StatsSnapshot {
+    /// stats are the cumulative counters, as SocketHandle::stats returns them
+    pub stats: SocketStats,
+    /// sctp_packets_sent and the counters after it are cumulative too. They cover every data
+    /// channel of the session, and the bytes are whole SCTP packets including control chunks.
+    pub sctp_packets_sent: u64,
+    pub sctp_bytes_sent: u64,
+    pub sctp_packets_received: u64,
+    pub sctp_bytes_received: u64,
+    pub data_chunks_received: u64,
+    /// data_chunks_lost counts the inbound DATA chunks the server gave up on before they arrived
+    pub data_chunks_lost: u64,
+    /// elapsed is the time since the previous snapshot, the rates below are taken over it
+    pub elapsed: Duration,
+    /// send_throughput and receive_throughput are in bytes of SCTP packets per second
+    pub send_throughput: f64,
+    pub receive_throughput: f64,
+    /// send_packet_rate and receive_packet_rate are in SCTP packets per second
+    pub send_packet_rate: f64,
+    pub receive_packet_rate: f64,
+    /// loss_rate is the fraction of the inbound DATA chunks of the interval that were lost,
+    /// zero when none were due
+    pub loss_rate: f64,
+}
+
+impl StatsSnapshot {
+    fn new(
+        stats: SocketStats,
+        traffic: SctpTraffic,
+        previous: SctpTraffic,
+        elapsed: Duration,
+    ) -> Self {
+        let per_second = |current: u64, previous: u64| {
+            let seconds = elapsed.as_secs_f64();
+            if seconds > 0.0 {
+                current.saturating_sub(previous) as f64 / seconds
+            } else {
+                0.0
+            }
+        };
+        let lost = traffic
+            .data_chunks_skipped
+            .saturating_sub(previous.data_chunks_skipped);
+        let due = lost
+            + traffic
+                .data_chunks_received
+                .saturating_sub(previous.data_chunks_received);
+
+        StatsSnapshot {
+            stats,
+            sctp_packets_sent: traffic.packets_sent,
+            sctp_bytes_sent: traffic.bytes_sent,
+            sctp_packets_received: traffic.packets_received,
+            sctp_bytes_received: traffic.bytes_received,
+            data_chunks_received: traffic.data_chunks_received,
+            data_chunks_lost: traffic.data_chunks_skipped,
+            elapsed,
+            send_throughput: per_second(traffic.bytes_sent, previous.bytes_sent),
+            receive_throughput: per_second(traffic.bytes_received, previous.bytes_received),
+            send_packet_rate: per_second(traffic.packets_sent, previous.packets_sent),
+            receive_packet_rate: per_second(traffic.packets_received, previous.packets_received),
+            loss_rate: if due > 0 {
+                lost as f64 / due as f64
+            } else {
+                0.0
+            },
+        }
+    }
+}
+
+/// MessageChunk is a part of a message received on a channel opened by
+/// SocketHandle::open_streaming_channel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageChunk {
+    /// transfer_id identifies the message, the chunks of one message share it
+    pub transfer_id: u32,
+    /// offset is where data starts in the message
+    pub offset: u64,
+    pub data: Bytes,
+    /// is_last is set on the chunk that ends the message
+    pub is_last: bool,
+}
+
+#[derive(Default)]
+struct SocketCounters {
+    messages_sent: AtomicU64,
+    bytes_sent: AtomicU64,
+    messages_received: AtomicU64,
+    bytes_received: AtomicU64,
+    oversized_messages: AtomicU64,
+}
+
+/// SocketHandle controls a session opened by Socket::connect_session
+#[derive(Clone)]
+pub struct SocketHandle {
+    peer_connection: Arc<RTCPeerConnection>,
+    addr_cell: AddrCell,
+    counters: Arc<SocketCounters>,
+    channel_capacity: usize,
+    paused_overflow: PausedOverflow,
+}
+
+impl SocketHandle {
+    /// server_addr returns the address of the server's ICE candidate
+    pub fn server_addr(&self) -> ServerAddr {
+        self.addr_cell.get()
+    }
+
+    /// stats returns the message counters of the session and how long establishing it took
+    pub fn stats(&self) -> SocketStats {
+        socket_stats(&self.peer_connection, &self.counters)
+    }
+
+    /// stats_stream returns a receiver that gets a StatsSnapshot every interval, with the rates
+    /// computed from the previous one. It ends once the session is closed or the receiver is
+    /// dropped. The task behind it only holds weak references, it does not keep the session
+    /// alive.
+    pub fn stats_stream(&self, interval: Duration) -> mpsc::Receiver<StatsSnapshot> {
+        let (sender, receiver) = mpsc::channel(1);
+        self.spawner().spawn(stats_stream_loop(
+            Arc::downgrade(&self.peer_connection),
+            Arc::downgrade(&self.counters),
+            interval,
+            sender,
+        ));
+        receiver
+    }
+
+    /// subscribe_transport_events returns a receiver of the SCTP retransmissions, the messages
+    /// given up on and the congestion window cuts, e.g. to lower the send rate or the quality of
+    /// what is sent. Each kind is sent at most once per 50ms with the counts in between added
+    /// up. The first subscriber sizes the channel with buffer, a receiver falling further behind
+    /// gets RecvError::Lagged. Nothing is collected until the first subscription or once every
+    /// receiver is dropped.
+    pub fn subscribe_transport_events(&self, buffer: usize) -> broadcast::Receiver<TransportEvent> {
+        self.peer_connection.subscribe_transport_events(buffer)
+    }
+
+    /// connect_timings returns when each step of establishing the session was first reached,
+    /// measured from the creation of the peer connection, e.g. to see whether ICE or the DTLS
+    /// handshake takes most of the time
+    pub fn connect_timings(&self) -> ConnectTimings {
+        self.peer_connection.connect_timings()
+    }
+
+    /// recent_events returns the last state changes of ICE, DTLS, SCTP and the peer connection,
+    /// oldest first. SettingEngine::set_event_log_size sets how many are kept.
+    pub fn recent_events(&self) -> Vec<ConnectionEvent> {
+        self.peer_connection.recent_events()
+    }
+
+    /// remote_bandwidth_limit returns the bits per second the server's answer limits the data
+    /// channels to with b=TIAS or b=AS, None if it sets no limit. A sender can use it as a rate
+    /// hint, or have SCTP enforce it with SettingEngine::set_sctp_respect_remote_bandwidth.
+    pub fn remote_bandwidth_limit(&self) -> Option<u64> {
+        self.peer_connection.remote_bandwidth_limit()
+    }
+
+    /// candidate_stats returns how many STUN binding requests and responses went through each
+    /// local and remote ICE candidate, including the candidates that were pruned. With
+    /// redact_addresses the addresses and ports are left out, e.g. before uploading the stats.
+    pub async fn candidate_stats(&self, redact_addresses: bool) -> CandidateStatsReport {
+        self.peer_connection.candidate_stats(redact_addresses).await
+    }
+
+    /// ice_role returns whether the client is currently the controlling or the controlled ICE
+    /// agent, Unspecified before the answer was applied. It reflects role conflicts that were
+    /// resolved since, see SettingEngine::set_ice_role.
+    pub async fn ice_role(&self) -> RTCIceRole {
+        self.peer_connection.internal.ice_transport.role().await
+    }
+
+    /// dtls_connection_info returns the DTLS version, cipher suite and extensions the handshake
+    /// negotiated, None once the session is closed
+    pub async fn dtls_connection_info(&self) -> Option<DtlsConnectionInfo> {
+        self.peer_connection
+            .internal
+            .dtls_transport
+            .connection_info()
+            .await
+    }
+
+    /// dtls_remote_certificates returns the DER encoded certificate chain the server presented
+    /// in the DTLS handshake, e.g. to pin it. It is empty until the handshake completed and
+    /// once the session is closed.
+    pub async fn dtls_remote_certificates(&self) -> Vec<Vec<u8>> {
+        self.peer_connection
+            .internal
+            .dtls_transport
+            .remote_certificates()
+            .await
+    }
+
+    /// export_keying_material derives length bytes from the DTLS master secret for label and
+    /// context as in RFC 5705, e.g. to key an application protocol or bind a login to this
+    /// session. The server exporting with the same label and context gets the same bytes,
+    /// a different label or context gives unrelated ones. Labels reserved by TLS, e.g.
+    /// "master secret", are refused.
+    pub async fn export_keying_material(
+        &self,
+        label: &str,
+        context: &[u8],
+        length: usize,
+    ) -> std::result::Result<Vec<u8>, SocketError> {
+        self.check_connection()?;
+        self.peer_connection
+            .internal
+            .dtls_transport
+            .export_keying_material(label, context, length)
+            .await
+            .map_err(|err| SocketError::Dtls(FailureCause::rtc(err), Vec::new()))
+    }
+
+    /// create_raw_channel opens a RawChannel that exchanges datagrams as DTLS application data
+    /// next to the data channels, e.g. to tunnel a custom protocol. Inbound records demux
+    /// matches go to it instead of SCTP, see RawChannel for how channels with overlapping demux
+    /// functions are resolved. Up to channel_capacity datagrams are queued for recv.
+    pub async fn create_raw_channel(
+        &self,
+        demux: RawChannelDemuxFn,
+    ) -> std::result::Result<RawChannel, SocketError> {
+        self.check_connection()?;
+        self.peer_connection
+            .internal
+            .dtls_transport
+            .create_raw_channel(demux, self.channel_capacity)
+            .await
+            .ok_or(SocketError::ConnectionClosed)
+    }
+
+    /// local_description returns the offer the session was set up with, including the local
+    /// candidates gathered so far
+    pub async fn local_description(&self) -> Option<RTCSessionDescription> {
+        self.peer_connection.local_description().await
+    }
+
+    /// remote_description returns the answer the server sent
+    pub async fn remote_description(&self) -> Option<RTCSessionDescription> {
+        self.peer_connection.remote_description().await
+    }
+
+    /// select_candidate_pair switches the session to the candidate pair of the given local and
+    /// remote candidate ids, e.g. the stats_id of a ConnectivityCheckResult or the id of a
+    /// CandidateStats. It fails with SocketError::Ice if no connectivity check on the pair
+    /// succeeded yet.
+    pub async fn select_candidate_pair(
+        &self,
+        local_id: &str,
+        remote_id: &str,
+    ) -> std::result::Result<(), SocketError> {
+        self.check_connection()?;
+        let peer_connection = Arc::clone(&self.peer_connection);
+        let (local_id, remote_id) = (local_id.to_owned(), remote_id.to_owned());
+        self.run_to_completion(async move {
+            peer_connection
+                .select_candidate_pair(&local_id, &remote_id)
+                .await
+                .map_err(|err| SocketError::Ice(FailureCause::rtc(err), Vec::new()))
+        })
+        .await
+    }
+
+    /// add_ice_candidate adds a remote ICE candidate signaled after the answer, e.g. one the
+    /// server trickles. It fails with SocketError::Ice for a candidate that cannot be parsed or
+    /// does not belong to a media section of the answer, and drops one of an earlier ICE
+    /// generation, see RTCIceCandidateInit. It can be called while restart_ice is running, the
+    /// candidate is then added once the operations on the session description started before
+    /// it are done, and is checked against the description they leave.
+    pub async fn add_ice_candidate(
+        &self,
+        candidate: RTCIceCandidateInit,
+    ) -> std::result::Result<(), SocketError> {
+        self.check_connection()?;
+        let peer_connection = Arc::clone(&self.peer_connection);
+        self.run_to_completion(async move {
+            peer_connection
+                .add_ice_candidate(candidate)
+                .await
+                .map_err(|err| {
+                    SocketError::Ice(FailureCause::InvalidCandidate(Arc::new(err)), Vec::new())
+                })
+        })
+        .await
+    }
+
+    /// diagnostics returns a snapshot of the connection for troubleshooting, e.g. to attach to a
+    /// support ticket as JSON. It never blocks, so it is safe to call from any callback.
+    #[cfg(feature = "serde")]
+    pub fn diagnostics(&self) -> crate::Diagnostics {
+        self.peer_connection.diagnostics()
+    }
+
+    /// restart_ice renegotiates the session through endpoint with an ICE restart, e.g. after a
+    /// network change the agent could not follow. The candidates are gathered again and the
+    /// server answers with new ICE credentials. The data channels stay open if the answer keeps
+    /// the server's DTLS certificate. Otherwise they are closed with
+    /// TransportError::RemoteCertificateChanged, and new ones can be opened once DTLS and SCTP
+    /// are up again. It returns once the answer is applied, connectivity is then checked in the
+    /// background. Cancelling it does not stop the renegotiation, which still applies the answer.
+    pub async fn restart_ice(
+        &self,
+        endpoint: SessionEndpoint,
+    ) -> std::result::Result<(), SocketError> {
+        self.check_connection()?;
+        let peer_connection = Arc::clone(&self.peer_connection);
+        let addr_cell = self.addr_cell.clone();
+        self.run_to_completion(async move {
+            peer_connection.restart_ice().await.map_err(|e| {
+                SocketError::Ice(FailureCause::rtc(e), peer_connection.recent_events())
+            })?;
+            negotiate(&peer_connection, &addr_cell, endpoint).await
+        })
+        .await
+    }
+
+    /// open_channel opens an additional data channel labelled `label` on the running session and
+    /// returns its sender and receiver. The channel is announced over the existing SCTP
+    /// association, no new offer/answer is exchanged. Messages on it count toward stats. Like
+    /// the receiver of connect_session, its receiver returns None once the channel is closed.
+    pub async fn open_channel(
+        &self,
+        label: &str,
+        protocol: &str,
+    ) -> std::result::Result<(mpsc::Sender<Bytes>, mpsc::Receiver<Bytes>), SocketError> {
+        self.open_channel_with_priority(label, protocol, DEFAULT_PRIORITY)
+            .await
+    }
+
+    /// open_channel_with_priority is open_channel with a send priority for the channel, e.g. to
+    /// keep a latency sensitive channel responsive during a bulk transfer on another one. See
+    /// RTCDataChannel::priority for the values, open_channel uses 256 (normal).
+    pub async fn open_channel_with_priority(
+        &self,
+        label: &str,
+        protocol: &str,
+        priority: u16,
+    ) -> std::result::Result<(mpsc::Sender<Bytes>, mpsc::Receiver<Bytes>), SocketError> {
+        let detached = self.open_detached(label, protocol, priority, None).await?;
+        Ok(self.spawn_loops(detached))
+    }
+
+    /// open_streaming_channel is open_channel for messages too large to hold whole, e.g. a file
+    /// transfer. A received message larger than streaming_threshold bytes is handed out in
+    /// chunks of up to that size as its data arrives in order, instead of once complete, so
+    /// neither SCTP nor the receiver has to buffer all of it. Smaller ones come as a single
+    /// chunk. The chunks of a message come in order and before the next message. The threshold
+    /// should stay well below the SCTP receive buffer, see
+    /// SettingEngine::set_sctp_max_receive_buffer_size, and a message the server abandons part
+    /// way ends without a chunk that is_last.
+    pub async fn open_streaming_channel(
+        &self,
+        label: &str,
+        protocol: &str,
+        streaming_threshold: usize,
+    ) -> std::result::Result<(mpsc::Sender<Bytes>, mpsc::Receiver<MessageChunk>), SocketError> {
+        let detached = self
+            .open_detached(label, protocol, DEFAULT_PRIORITY, Some(streaming_threshold))
+            .await?;
+        let (to_client_sender, to_client_receiver) = mpsc::channel(self.channel_capacity);
+        let closed = Arc::new(Notify::new());
+        self.spawner().spawn(session_streaming_read_loop(
+            Arc::clone(&detached),
+            std::cmp::max(streaming_threshold, MESSAGE_SIZE),
+            to_client_sender,
+            Arc::clone(&self.counters),
+            Arc::clone(&closed),
+        ));
+        let to_server_sender = self.spawn_write_loop(detached, closed);
+
+        Ok((to_server_sender, to_client_receiver))
+    }
+
+    /// open_data_channel is open_channel returning a DataChannel, which closes the channel when
+    /// dropped and can be split into a sending and a receiving half owned by different tasks
+    pub async fn open_data_channel(
+        &self,
+        label: &str,
+        protocol: &str,
+    ) -> std::result::Result<SessionDataChannel, SocketError> {
+        self.open_data_channel_with_priority(label, protocol, DEFAULT_PRIORITY)
+            .await
+    }
+
+    /// open_data_channel_with_priority is open_data_channel with a send priority for the
+    /// channel, see open_channel_with_priority
+    pub async fn open_data_channel_with_priority(
+        &self,
+        label: &str,
+        protocol: &str,
+        priority: u16,
+    ) -> std::result::Result<SessionDataChannel, SocketError> {
+        let detached = self.open_detached(label, protocol, priority, None).await?;
+        let inbox = Arc::new(Inbox::new(self.channel_capacity, self.paused_overflow));
+        let closed = Arc::new(Notify::new());
+        self.spawner().spawn(session_inbox_read_loop(
+            Arc::clone(&detached),
+            Arc::clone(&inbox),
+            Arc::clone(&self.counters),
+            Arc::clone(&closed),
+        ));
+        let to_server_sender = self.spawn_write_loop(Arc::clone(&detached), closed);
+        Ok(SessionDataChannel::new(detached, to_server_sender, inbox))
+    }
+
+    /// check_connection fails with SocketError::ConnectionClosed or ConnectionFailed once the
+    /// session has ended
+    pub(crate) fn check_connection(&self) -> std::result::Result<(), SocketError> {
+        if self
+            .peer_connection
+            .internal
+            .is_closed
+            .load(Ordering::SeqCst)
+        {
+            return Err(SocketError::ConnectionClosed);
+        }
+        if self.peer_connection.connection_state() == RTCPeerConnectionState::Failed {
+            return Err(SocketError::ConnectionFailed);
+        }
+        Ok(())
+    }
+
+    // open_detached creates and detaches a data channel, with the given streaming threshold if
+    // any. A channel opened after the caller stopped waiting is closed again.
+    async fn open_detached(
+        &self,
+        label: &str,
+        protocol: &str,
+        priority: u16,
+        streaming_threshold: Option<usize>,
+    ) -> std::result::Result<Arc<DataChannel>, SocketError> {
+        self.check_connection()?;
+        let peer_connection = Arc::clone(&self.peer_connection);
+        let (label, protocol) = (label.to_owned(), protocol.to_owned());
+        let (result_tx, result_rx) = oneshot::channel();
+        self.spawner().spawn(async move {
+            let result = create_detached(&peer_connection, &label, &protocol, priority).await;
+            let result = match (result, streaming_threshold) {
+                (Ok(detached), Some(streaming_threshold)) => {
+                    detached.set_streaming_threshold(streaming_threshold).await;
+                    Ok(detached)
+                }
+                (result, _) => result,
+            };
+            if let Err(Ok(detached)) = result_tx.send(result) {
+                if let Err(err) = detached.close().await {
+                    warn!("error closing data channel: {}", err);
+                }
+            }
+        });
+        result_rx
+            .await
+            .unwrap_or(Err(SocketError::ConnectionClosed))
+    }
+
+    // run_to_completion runs future on the runtime of the connection and waits for it. Dropping
+    // the future of the caller, e.g. in a tokio::select! branch that lost, does not abort it half
+    // way, so the session is never left with a partly applied change.
+    async fn run_to_completion<T, F>(&self, future: F) -> std::result::Result<T, SocketError>
+    where
+        F: Future<Output = std::result::Result<T, SocketError>> + Send + 'static,
+        T: Send + 'static,
+    {
+        self.spawner()
+            .spawn(future)
+            .await
+            .unwrap_or(Err(SocketError::ConnectionClosed))
+    }
+
+    // spawn_loops passes messages between the detached channel and a sender and receiver that
+    // hold channel_capacity messages each, counting them toward stats
+    fn spawn_loops(
+        &self,
+        detached: Arc<DataChannel>,
+    ) -> (mpsc::Sender<Bytes>, mpsc::Receiver<Bytes>) {
+        let (to_client_sender, to_client_receiver) = mpsc::channel(self.channel_capacity);
+        let closed = Arc::new(Notify::new());
+        self.spawner().spawn(session_read_loop(
+            Arc::clone(&detached),
+            to_client_sender,
+            Arc::clone(&self.counters),
+            Arc::clone(&closed),
+        ));
+        let to_server_sender = self.spawn_write_loop(detached, closed);
+
+        (to_server_sender, to_client_receiver)
+    }
+
+    fn spawn_write_loop(
+        &self,
+        detached: Arc<DataChannel>,
+        closed: Arc<Notify>,
+    ) -> mpsc::Sender<Bytes> {
+        let (to_server_sender, to_server_receiver) = mpsc::channel(self.channel_capacity);
+        self.spawner().spawn(session_write_loop(
+            detached,
+            to_server_receiver,
+            Arc::clone(&self.counters),
+            closed,
+        ));
+        to_server_sender
+    }
+
+    fn spawner(&self) -> &Spawner {
+        self.peer_connection.internal.sctp_transport.spawner()
+    }
+
+    /// data_channels returns a snapshot of the data channels of the session, the ones opened by
+    /// this side as well as any the server opened, in the order they were created. A channel is
+    /// listed from its creation until it is closed for good, a channel waiting to be reopened on
+    /// a new association after a peer restart stays in the list. The session holds on to the
+    /// channels, so one can be looked up here after the task that opened it dropped its handle.
+    /// Labels need not be unique, channels with the same label are all listed.
+    pub async fn data_channels(&self) -> Vec<Arc<RTCDataChannel>> {
+        self.peer_connection
+            .internal
+            .sctp_transport
+            .live_data_channels()
+            .await
+    }
+
+    /// data_channel_by_label returns the oldest of the channels data_channels lists with the
+    /// given label, None if there is none
+    pub async fn data_channel_by_label(&self, label: &str) -> Option<Arc<RTCDataChannel>> {
+        self.data_channels()
+            .await
+            .into_iter()
+            .find(|d| d.label() == label)
+    }
+
+    /// close shuts the session down, ending all message channels. Once called the session is
+    /// closed completely, even if the call is cancelled.
+    pub async fn close(&self) {
+        let peer_connection = Arc::clone(&self.peer_connection);
+        let _ = self
+            .run_to_completion(async move {
+                if let Err(err) = peer_connection.close().await {
+                    warn!("error closing peer connection: {}", err);
+                }
+                Ok(())
+            })
+            .await;
+    }
+}
+
+enum ConnectEvent {
+    Open(Arc<DataChannel>),
+    SctpConnected,
+    Failed(SocketError),
+}
+
 pub struct Socket {
+    setting_engine: SettingEngine,
+    configuration: RTCConfiguration,
     addr_cell: AddrCell,
     to_server_receiver: mpsc::UnboundedReceiver<Box<[u8]>>,
     to_client_sender: mpsc::UnboundedSender<Box<[u8]>>,
@@ -30,12 +1002,25 @@ pub struct SocketIo {
 
 impl Socket {
     pub fn new() -> (Self, SocketIo) {
+        Self::with_configuration(RTCConfiguration::default())
+    }
+
+    pub fn with_configuration(configuration: RTCConfiguration) -> (Self, SocketIo) {
+        Self::with_setting_engine(SettingEngine::default(), configuration)
+    }
+
+    pub fn with_setting_engine(
+        setting_engine: SettingEngine,
+        configuration: RTCConfiguration,
+    ) -> (Self, SocketIo) {
         let addr_cell = AddrCell::default();
         let (to_server_sender, to_server_receiver) = mpsc::unbounded_channel();
         let (to_client_sender, to_client_receiver) = mpsc::unbounded_channel();
 
         (
             Self {
+                setting_engine,
+                configuration,
                 addr_cell: addr_cell.clone(),
                 to_server_receiver,
                 to_client_sender,
@@ -50,20 +1035,39 @@ impl Socket {
 
     pub async fn connect(self, server_url: &str) {
         let Self {
+            setting_engine,
+            configuration,
             addr_cell,
             to_server_receiver,
             to_client_sender,
         } = self;
 
         // create a new RTCPeerConnection
-        let peer_connection = RTCPeerConnection::new().await;
+        let api = API::new(setting_engine);
+        let peer_connection = RTCPeerConnection::new(&api, configuration).await;
+
+        // the server is not expected to open channels of its own, only our 'data' channel is used
+        peer_connection
+            .on_data_channel(Box::new(move |data_channel| {
+                warn!(
+                    "ignoring data channel '{}' (id {:?}, protocol '{}', ordered {}, max_retransmits {:?}, max_packet_lifetime {:?}) opened by the server",
+                    data_channel.label(),
+                    data_channel.id(),
+                    data_channel.protocol(),
+                    data_channel.ordered(),
+                    data_channel.max_retransmits(),
+                    data_channel.max_packet_lifetime()
+                );
+                Box::pin(async {})
+            }))
+            .await;
 
         let label = "data";
         let protocol = "";
 
         // create a datachannel with label 'data'
         let data_channel = peer_connection
-            .create_data_channel(label, protocol)
+            .create_data_channel(label, protocol, DEFAULT_PRIORITY)
             .await
             .expect("cannot create data channel");
 
@@ -75,13 +1079,28 @@ impl Socket {
             }))
             .await;
 
-        // datachannel on_open callback
-        let data_channel_ref = Arc::clone(&data_channel);
+        // datachannel on_open callback. connect returns before the channel opens and drops its
+        // reference to the peer connection, which would close it. A task spawned once signaling
+        // succeeded holds one until the channel opens or the connection fails, and the handler
+        // hands one to the loops, so the session lives as long as they run. The peer connection
+        // owns the handlers, they only hold weak references to it so that a connect that fails
+        // drops it with them.
+        let (settled_tx, mut settled_rx) = mpsc::channel::<()>(1);
+        let data_channel_ref = Arc::downgrade(&data_channel);
+        let peer_connection_ref = Arc::downgrade(&peer_connection);
+        let spawner = peer_connection.internal.sctp_transport.spawner().clone();
+        let opened_tx = settled_tx.clone();
         data_channel
             .on_open(Box::new(move || {
-                let data_channel_ref_2 = Arc::clone(&data_channel_ref);
+                let (data_channel, peer_connection) =
+                    match (data_channel_ref.upgrade(), peer_connection_ref.upgrade()) {
+                        (Some(data_channel), Some(peer_connection)) => {
+                            (data_channel, peer_connection)
+                        }
+                        _ => return Box::pin(async {}),
+                    };
                 Box::pin(async move {
-                    let detached_data_channel = data_channel_ref_2
+                    let detached_data_channel = data_channel
                         .detach()
                         .await
                         .expect("data channel detach got error");
@@ -89,22 +1108,39 @@ impl Socket {
                     // Handle reading from the data channel
                     let detached_data_channel_1 = Arc::clone(&detached_data_channel);
                     let detached_data_channel_2 = Arc::clone(&detached_data_channel);
-                    tokio::spawn(async move {
+                    let peer_connection_1 = Arc::clone(&peer_connection);
+                    let peer_connection_2 = peer_connection;
+                    spawner.spawn(async move {
+                        let _peer_connection = peer_connection_1;
                         let _loop_result =
                             read_loop(detached_data_channel_1, to_client_sender).await;
                         // do nothing with result, just close thread
                     });
 
                     // Handle writing to the data channel
-                    tokio::spawn(async move {
+                    spawner.spawn(async move {
+                        let _peer_connection = peer_connection_2;
                         let _loop_result =
                             write_loop(detached_data_channel_2, to_server_receiver).await;
                         // do nothing with result, just close thread
                     });
+
+                    let _ = opened_tx.try_send(());
                 })
             }))
             .await;
 
+        peer_connection
+            .on_peer_connection_state_change(Box::new(move |state| {
+                if state == RTCPeerConnectionState::Failed
+                    || state == RTCPeerConnectionState::Closed
+                {
+                    let _ = settled_tx.try_send(());
+                }
+                Box::pin(async {})
+            }))
+            .await;
+
         // create an offer to send to the server
         let offer = peer_connection
             .create_offer()
@@ -144,11 +1180,11 @@ impl Socket {
         let response_string = response.text().await.unwrap();
 
         // parse session from server response
-        let session_response: JsSessionResponse = get_session_response(response_string.as_str());
+        let session_response =
+            get_session_response(response_string.as_str()).expect("invalid session response");
 
         // apply the server's response as the remote description
-        let session_description =
-            RTCSessionDescription::answer(session_response.answer.sdp).unwrap();
+        let session_description = RTCSessionDescription::answer(session_response.sdp).unwrap();
 
         peer_connection
             .set_remote_description(session_description)
@@ -156,16 +1192,738 @@ impl Socket {
             .expect("cannot set remote description");
 
         addr_cell
-            .receive_candidate(session_response.candidate.candidate.as_str())
+            .receive_candidate(session_response.candidate.as_str())
             .await;
 
         // add ice candidate to connection
         if let Err(error) = peer_connection
-            .add_ice_candidate(session_response.candidate.candidate)
+            .add_ice_candidate(RTCIceCandidateInit {
+                candidate: session_response.candidate,
+                ..Default::default()
+            })
             .await
         {
             panic!("Error during add_ice_candidate: {:?}", error);
         }
+
+        let spawner = peer_connection.internal.sctp_transport.spawner().clone();
+        spawner.spawn(async move {
+            let _peer_connection = peer_connection;
+            let _ = settled_rx.recv().await;
+        });
+    }
+}
+
+impl Socket {
+    /// connect_session creates an offer, exchanges it through `endpoint`, and waits until the
+    /// data channel to the server is open. Messages written to the returned sender are sent
+    /// to the server, and messages from the server arrive on the returned receiver. The
+    /// receiver holds up to channel_capacity messages and returns None once the data channel
+    /// is closed and drained, see RTCDataChannel::close_reason for why it closed. The handle
+    /// owns the session, it is closed once every clone of the handle is dropped.
+    ///
+    /// The session is set up on the runtime of the connection, see
+    /// SettingEngine::set_runtime_handle. Dropping the returned future, e.g. in a tokio::select!
+    /// branch that lost to a user cancel, stops the setup and closes the peer connection, so
+    /// connect_session can simply be called again. The same holds for the other connect
+    /// functions and prewarm.
+    pub async fn connect_session(
+        endpoint: SessionEndpoint,
+        options: SocketOptions,
+    ) -> std::result::Result<(mpsc::Sender<Bytes>, mpsc::Receiver<Bytes>, SocketHandle), SocketError>
+    {
+        let session = Socket::establish_session(endpoint, options, true).await?;
+        Socket::spawn_session(session)
+    }
+
+    /// prewarm does ahead of time what connect_session can do without the server, e.g. while
+    /// the player is still in the lobby: it generates the DTLS certificate, enumerates the local
+    /// interfaces and binds the host candidates. connect_prewarmed_session then sends the offer
+    /// right away. The session only gathers host candidates, there is no STUN or TURN server to
+    /// query ahead. options apply to the session connected with the returned handle, it fails
+    /// with SocketError::Ice if no candidate is gathered.
+    pub async fn prewarm(
+        options: SocketOptions,
+    ) -> std::result::Result<PrewarmHandle, SocketError> {
+        options.validate().map_err(SocketError::Options)?;
+        let SocketOptions {
+            setting_engine,
+            configuration,
+            connect_timeout,
+            channel_capacity,
+            paused_overflow,
+            prewarm_ttl,
+        } = options;
+
+        let api = API::new(setting_engine);
+        let peer_connection = RTCPeerConnection::new(&api, configuration.clone()).await;
+        let spawner = api.setting_engine.spawner.clone();
+        spawn_setup(spawner, Arc::clone(&peer_connection), async move {
+            peer_connection
+                .gather_candidates()
+                .await
+                .map_err(|e| match e {
+                    RtcError::ErrNoCandidatesGathered => {
+                        SocketError::Ice(FailureCause::NoCandidates, Vec::new())
+                    }
+                    _ => SocketError::Offer(FailureCause::rtc(e)),
+                })?;
+
+            Ok(PrewarmHandle {
+                api,
+                peer_connection,
+                configuration,
+                connect_timeout,
+                channel_capacity,
+                paused_overflow,
+                prepared_at: Instant::now(),
+                ttl: prewarm_ttl,
+            })
+        })
+        .await
+    }
+
+    /// connect_prewarmed_session is connect_session for a session prepared by prewarm. A stale
+    /// handle, see PrewarmHandle::is_fresh, is prepared again first, so connecting takes as long
+    /// as with connect_session then.
+    pub async fn connect_prewarmed_session(
+        endpoint: SessionEndpoint,
+        prewarmed: PrewarmHandle,
+    ) -> std::result::Result<(mpsc::Sender<Bytes>, mpsc::Receiver<Bytes>, SocketHandle), SocketError>
+    {
+        let connect_timeout = prewarmed.connect_timeout;
+        let channel_capacity = prewarmed.channel_capacity;
+        let paused_overflow = prewarmed.paused_overflow;
+        let peer_connection = prewarmed.into_peer_connection().await;
+
+        let session = Socket::establish_peer_connection(
+            peer_connection,
+            endpoint,
+            connect_timeout,
+            channel_capacity,
+            paused_overflow,
+            true,
+        )
+        .await?;
+        Socket::spawn_session(session)
+    }
+
+    // spawn_session hands out the initial data channel of an established session through a
+    // sender and receiver
+    fn spawn_session(
+        (handle, data_channel): (SocketHandle, Option<Arc<DataChannel>>),
+    ) -> std::result::Result<(mpsc::Sender<Bytes>, mpsc::Receiver<Bytes>, SocketHandle), SocketError>
+    {
+        let data_channel = data_channel.ok_or_else(|| {
+            SocketError::ChannelOpen(FailureCause::NoDataChannel, handle.recent_events())
+        })?;
+        let (to_server_sender, to_client_receiver) = handle.spawn_loops(data_channel);
+
+        Ok((to_server_sender, to_client_receiver, handle))
+    }
+
+    /// connect_session_without_channel is connect_session for a session that opens its data
+    /// channels later, e.g. to get ICE and DTLS done while matchmaking is still going on. It
+    /// returns once the SCTP association is up, channels are then opened with
+    /// SocketHandle::open_channel or open_data_channel without a new offer/answer.
+    pub async fn connect_session_without_channel(
+        endpoint: SessionEndpoint,
+        options: SocketOptions,
+    ) -> std::result::Result<SocketHandle, SocketError> {
+        let (handle, _) = Socket::establish_session(endpoint, options, false).await?;
+        Ok(handle)
+    }
+
+    /// establish_session connects a new peer connection through endpoint, waiting for the
+    /// initial data channel "data" to open if open_channel is set and for the SCTP association
+    /// otherwise. The peer connection is closed again if that fails.
+    async fn establish_session(
+        endpoint: SessionEndpoint,
+        options: SocketOptions,
+        open_channel: bool,
+    ) -> std::result::Result<(SocketHandle, Option<Arc<DataChannel>>), SocketError> {
+        options.validate().map_err(SocketError::Options)?;
+        let SocketOptions {
+            setting_engine,
+            configuration,
+            connect_timeout,
+            channel_capacity,
+            paused_overflow,
+            prewarm_ttl: _,
+        } = options;
+
+        let api = API::new(setting_engine);
+        let peer_connection = RTCPeerConnection::new(&api, configuration).await;
+        Socket::establish_peer_connection(
+            peer_connection,
+            endpoint,
+            connect_timeout,
+            channel_capacity,
+            paused_overflow,
+            open_channel,
+        )
+        .await
+    }
+
+    /// establish_peer_connection is establish_session for a peer connection that was already
+    /// created, it runs through spawn_setup
+    async fn establish_peer_connection(
+        peer_connection: Arc<RTCPeerConnection>,
+        endpoint: SessionEndpoint,
+        connect_timeout: Duration,
+        channel_capacity: usize,
+        paused_overflow: PausedOverflow,
+        open_channel: bool,
+    ) -> std::result::Result<(SocketHandle, Option<Arc<DataChannel>>), SocketError> {
+        let spawner = peer_connection.internal.sctp_transport.spawner().clone();
+        spawn_setup(spawner, Arc::clone(&peer_connection), async move {
+            let addr_cell = AddrCell::default();
+
+            match establish(
+                &peer_connection,
+                &addr_cell,
+                endpoint,
+                connect_timeout,
+                open_channel,
+            )
+            .await
+            {
+                Ok(data_channel) => Ok((
+                    SocketHandle {
+                        peer_connection,
+                        addr_cell,
+                        counters: Arc::new(SocketCounters::default()),
+                        channel_capacity,
+                        paused_overflow,
+                    },
+                    data_channel,
+                )),
+                Err(err) => {
+                    // the events are taken before closing, which would add its own
+                    let err = err.with_recent_events(peer_connection.recent_events());
+                    if let Err(close_err) = peer_connection.close().await {
+                        warn!("error closing peer connection: {}", close_err);
+                    }
+                    Err(err)
+                }
+            }
+        })
+        .await
+    }
+}
+
+// spawn_setup runs setup of peer_connection on spawner and waits for it. Dropping the future of
+// the caller stops the setup and closes the peer connection, instead of leaving it half set up
+// with its tasks and sockets running. So does a setup that fails, or that completes after the
+// caller stopped waiting.
+async fn spawn_setup<T, F>(
+    spawner: Spawner,
+    peer_connection: Arc<RTCPeerConnection>,
+    setup: F,
+) -> std::result::Result<T, SocketError>
+where
+    F: Future<Output = std::result::Result<T, SocketError>> + Send + 'static,
+    T: Send + 'static,
+{
+    let (mut result_tx, result_rx) = oneshot::channel();
+    spawner.spawn(async move {
+        let result = tokio::select! {
+            result = setup => Some(result),
+            _ = result_tx.closed() => None,
+        };
+        let handed_out = match result {
+            Some(result) => {
+                let ok = result.is_ok();
+                result_tx.send(result).is_ok() && ok
+            }
+            None => false,
+        };
+        if !handed_out {
+            if let Err(err) = peer_connection.close().await {
+                warn!("error closing peer connection: {}", err);
+            }
+        }
+    });
+    result_rx
+        .await
+        .unwrap_or(Err(SocketError::ConnectionClosed))
+}
+
+async fn establish(
+    peer_connection: &Arc<RTCPeerConnection>,
+    addr_cell: &AddrCell,
+    endpoint: SessionEndpoint,
+    connect_timeout: Duration,
+    open_channel: bool,
+) -> std::result::Result<Option<Arc<DataChannel>>, SocketError> {
+    let (event_tx, mut event_rx) = mpsc::channel(4);
+
+    // a failed peer connection is blamed on DTLS if the handshake failed, on ICE otherwise. The
+    // handshake error is set before the DTLS transport turns Failed.
+    let dtls_transport = Arc::clone(&peer_connection.internal.dtls_transport);
+    let state_event_tx = event_tx.clone();
+    peer_connection
+        .on_peer_connection_state_change(Box::new(move |state| {
+            if state == RTCPeerConnectionState::Failed {
+                let handshake_error = match dtls_transport.state() {
+                    RTCDtlsTransportState::Failed => dtls_transport.handshake_error(),
+                    _ => None,
+                };
+                let err = match handshake_error {
+                    Some(err) => SocketError::Dtls(FailureCause::Handshake(err), Vec::new()),
+                    None => SocketError::Ice(FailureCause::ChecksFailed, Vec::new()),
+                };
+                let _ = state_event_tx.try_send(ConnectEvent::Failed(err));
+            }
+            Box::pin(async {})
+        }))
+        .await;
+
+    // the server takes no trickled candidates, the ones gathered after the offer (e.g. when
+    // the local addresses change) are only logged
+    peer_connection
+        .on_ice_candidate(Box::new(|candidate| {
+            if let Some(candidate) = candidate {
+                log::debug!("local ICE candidate: {}", candidate);
+            }
+            Box::pin(async {})
+        }))
+        .await;
+
+    if open_channel {
+        create_initial_channel(peer_connection, event_tx).await?;
+    } else {
+        // the offer carries the application section regardless, so SCTP still comes up
+        let sctp_transport = Arc::clone(&peer_connection.internal.sctp_transport);
+        let spawner = sctp_transport.spawner().clone();
+        spawner.spawn(async move {
+            if sctp_transport.connected().await {
+                let _ = event_tx.try_send(ConnectEvent::SctpConnected);
+            }
+        });
+    }
+
+    negotiate(peer_connection, addr_cell, endpoint).await?;
+
+    match timeout(connect_timeout, event_rx.recv()).await {
+        Ok(Some(ConnectEvent::Open(detached))) => Ok(Some(detached)),
+        Ok(Some(ConnectEvent::SctpConnected)) => Ok(None),
+        Ok(Some(ConnectEvent::Failed(err))) => Err(err),
+        Ok(None) => Err(SocketError::ChannelOpen(
+            FailureCause::PeerConnectionDropped,
+            Vec::new(),
+        )),
+        Err(_) => {
+            let ice_state: RTCIceConnectionState = peer_connection
+                .internal
+                .ice_connection_state
+                .load(Ordering::SeqCst)
+                .into();
+            Err(match ice_state {
+                RTCIceConnectionState::Connected | RTCIceConnectionState::Completed => {
+                    if peer_connection.internal.dtls_transport.state()
+                        == RTCDtlsTransportState::Connected
+                    {
+                        SocketError::ChannelOpen(FailureCause::TimedOut, Vec::new())
+                    } else {
+                        SocketError::Dtls(FailureCause::TimedOut, Vec::new())
+                    }
+                }
+                _ => SocketError::Ice(FailureCause::TimedOut, Vec::new()),
+            })
+        }
+    }
+}
+
+// create_detached creates a data channel on peer_connection and detaches it
+async fn create_detached(
+    peer_connection: &Arc<RTCPeerConnection>,
+    label: &str,
+    protocol: &str,
+    priority: u16,
+) -> std::result::Result<Arc<DataChannel>, SocketError> {
+    let data_channel = peer_connection
+        .create_data_channel(label, protocol, priority)
+        .await
+        .map_err(|e| match e {
+            RtcError::ErrConnectionClosed => SocketError::ConnectionClosed,
+            RtcError::ErrConnectionFailed => SocketError::ConnectionFailed,
+            e => SocketError::ChannelOpen(FailureCause::rtc(e), peer_connection.recent_events()),
+        })?;
+    data_channel.detach().await.map_err(|e| {
+        SocketError::ChannelOpen(FailureCause::rtc(e), peer_connection.recent_events())
+    })
+}
+
+// negotiate exchanges an offer with every local candidate for the answer through endpoint and
+// applies it along with the server's candidate
+async fn negotiate(
+    peer_connection: &Arc<RTCPeerConnection>,
+    addr_cell: &AddrCell,
+    endpoint: SessionEndpoint,
+) -> std::result::Result<(), SocketError> {
+    // the offer carries every local candidate inline, for servers that take no trickled ones
+    let offer = peer_connection
+        .create_offer_with_gathering_complete()
+        .await
+        .map_err(|e| match e {
+            RtcError::ErrNoCandidatesGathered => {
+                SocketError::Ice(FailureCause::NoCandidates, Vec::new())
+            }
+            _ => SocketError::Offer(FailureCause::rtc(e)),
+        })?;
+    peer_connection
+        .set_local_description(offer)
+        .await
+        .map_err(|e| SocketError::Offer(FailureCause::rtc(e)))?;
+    let sdp = peer_connection
+        .local_description()
+        .await
+        .ok_or_else(|| SocketError::Offer(FailureCause::NoLocalDescription))?
+        .sdp;
+
+    let answer = match endpoint {
+        SessionEndpoint::Http(url) => post_session_offer(&url, sdp).await?,
+        SessionEndpoint::Signaling(f) => f(sdp)
+            .await
+            .map_err(|e| SocketError::Signaling(FailureCause::Endpoint(Arc::from(Box::from(e)))))?,
+    };
+
+    let session_description = RTCSessionDescription::answer(answer.sdp)
+        .map_err(|e| SocketError::Signaling(FailureCause::InvalidAnswer(Arc::new(e))))?;
+    peer_connection
+        .set_remote_description(session_description)
+        .await
+        .map_err(|e| SocketError::Signaling(FailureCause::InvalidAnswer(Arc::new(e))))?;
+
+    addr_cell.receive_candidate(answer.candidate.as_str()).await;
+    peer_connection
+        .add_ice_candidate(RTCIceCandidateInit {
+            candidate: answer.candidate,
+            ..Default::default()
+        })
+        .await
+        .map_err(|e| SocketError::Ice(FailureCause::InvalidCandidate(Arc::new(e)), Vec::new()))?;
+
+    Ok(())
+}
+
+// create_initial_channel creates the data channel connect_session hands out, it reports on
+// event_tx once the channel is open or failed to open
+async fn create_initial_channel(
+    peer_connection: &Arc<RTCPeerConnection>,
+    event_tx: mpsc::Sender<ConnectEvent>,
+) -> std::result::Result<(), SocketError> {
+    let data_channel = peer_connection
+        .create_data_channel("data", "", DEFAULT_PRIORITY)
+        .await
+        .map_err(|e| SocketError::ChannelOpen(FailureCause::rtc(e), Vec::new()))?;
+
+    let error_event_tx = event_tx.clone();
+    data_channel
+        .on_error(Box::new(move |error| {
+            let _ = error_event_tx.try_send(ConnectEvent::Failed(SocketError::ChannelOpen(
+                FailureCause::rtc(error),
+                Vec::new(),
+            )));
+            Box::pin(async {})
+        }))
+        .await;
+
+    let data_channel_ref = Arc::clone(&data_channel);
+    data_channel
+        .on_open(Box::new(move || {
+            let data_channel_ref_2 = Arc::clone(&data_channel_ref);
+            Box::pin(async move {
+                let event = match data_channel_ref_2.detach().await {
+                    Ok(detached) => ConnectEvent::Open(detached),
+                    Err(err) => ConnectEvent::Failed(SocketError::ChannelOpen(
+                        FailureCause::rtc(err),
+                        Vec::new(),
+                    )),
+                };
+                let _ = event_tx.try_send(event);
+            })
+        }))
+        .await;
+
+    Ok(())
+}
+
+// post_session_offer sends the offer to a webrtc-unreliable server's session endpoint
+async fn post_session_offer(
+    server_url: &str,
+    sdp: String,
+) -> std::result::Result<SignalingAnswer, SocketError> {
+    let endpoint_error =
+        |e: reqwest::Error| SocketError::Signaling(FailureCause::Endpoint(Arc::new(e)));
+    let response = HttpClient::new()
+        .post(server_url)
+        .header("Content-Length", sdp.len())
+        .body(sdp)
+        .send()
+        .await
+        .and_then(Response::error_for_status)
+        .map_err(endpoint_error)?;
+    let response_string = response.text().await.map_err(endpoint_error)?;
+
+    get_session_response(response_string.as_str())
+        .map_err(|e| SocketError::Signaling(FailureCause::InvalidAnswer(Arc::new(e))))
+}
+
+// session_read_loop hands the messages of data_channel to to_client_sender. Once the channel is
+// closed it wakes the write loop through closed, so its sender fails right away.
+async fn session_read_loop(
+    data_channel: Arc<DataChannel>,
+    to_client_sender: mpsc::Sender<Bytes>,
+    counters: Arc<SocketCounters>,
+    closed: Arc<Notify>,
+) {
+    let mut buffer = read_buffer(&data_channel);
+    loop {
+        let message_length = match data_channel.read(&mut buffer).await {
+            Ok(length) => length,
+            Err(DataChannelError::Sctp(SctpError::ErrShortBuffer)) => {
+                drop_oversized_message(&counters, buffer.len());
+                continue;
+            }
+            Err(err) => {
+                log::debug!("data channel closed; exit the read loop: {}", err);
+                closed.notify_one();
+                return;
+            }
+        };
+
+        counters.messages_received.fetch_add(1, Ordering::SeqCst);
+        counters
+            .bytes_received
+            .fetch_add(message_length as u64, Ordering::SeqCst);
+
+        if to_client_sender
+            .send(Bytes::copy_from_slice(&buffer[..message_length]))
+            .await
+            .is_err()
+        {
+            return;
+        }
+    }
+}
+
+// read_buffer returns a buffer for the messages of data_channel, a message larger than the
+// association's max message size does not fit and is dropped by the read
+fn read_buffer(data_channel: &DataChannel) -> Vec<u8> {
+    vec![0u8; std::cmp::max(data_channel.max_message_size(), MESSAGE_SIZE)]
+}
+
+fn drop_oversized_message(counters: &SocketCounters, buffer_size: usize) {
+    log::warn!(
+        "dropped an inbound message larger than {} bytes",
+        buffer_size
+    );
+    counters.oversized_messages.fetch_add(1, Ordering::SeqCst);
+}
+
+fn socket_stats(peer_connection: &RTCPeerConnection, counters: &SocketCounters) -> SocketStats {
+    SocketStats {
+        messages_sent: counters.messages_sent.load(Ordering::SeqCst),
+        bytes_sent: counters.bytes_sent.load(Ordering::SeqCst),
+        messages_received: counters.messages_received.load(Ordering::SeqCst),
+        bytes_received: counters.bytes_received.load(Ordering::SeqCst),
+        oversized_messages: counters.oversized_messages.load(Ordering::SeqCst),
+        sctp_bad_verification_tags: peer_connection.sctp_bad_verification_tags(),
+        send_errors: peer_connection.send_errors(),
+        oversized_datagrams: peer_connection.oversized_datagrams(),
+        socket_buffer_sizes: peer_connection.socket_buffer_sizes(),
+        connect_timings: peer_connection.connect_timings(),
+    }
+}
+
+// stats_stream_loop sends a StatsSnapshot every interval until the session is closed or gone,
+// or the receiver is dropped. It only upgrades its references while taking a snapshot.
+async fn stats_stream_loop(
+    peer_connection: Weak<RTCPeerConnection>,
+    counters: Weak<SocketCounters>,
+    interval: Duration,
+    sender: mpsc::Sender<StatsSnapshot>,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    // the first tick completes immediately, it sets the baseline for the first rates
+    ticker.tick().await;
+    let mut previous = match peer_connection.upgrade() {
+        Some(peer_connection) => peer_connection.sctp_traffic(),
+        None => return,
+    };
+    let mut taken_at = Instant::now();
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = sender.closed() => return,
+        }
+
+        let snapshot = {
+            let (peer_connection, counters) = match (peer_connection.upgrade(), counters.upgrade())
+            {
+                (Some(peer_connection), Some(counters)) => (peer_connection, counters),
+                _ => return,
+            };
+            if peer_connection.internal.is_closed.load(Ordering::SeqCst) {
+                return;
+            }
+
+            let traffic = peer_connection.sctp_traffic();
+            let now = Instant::now();
+            let snapshot = StatsSnapshot::new(
+                socket_stats(&peer_connection, &counters),
+                traffic,
+                previous,
+                now - taken_at,
+            );
+            previous = traffic;
+            taken_at = now;
+            snapshot
+        };
+
+        if sender.send(snapshot).await.is_err() {
+            return;
+        }
+    }
+}
+
+// session_streaming_read_loop is session_read_loop for a channel with a streaming threshold,
+// which reads messages in parts of up to buffer_size bytes
+async fn session_streaming_read_loop(
+    data_channel: Arc<DataChannel>,
+    buffer_size: usize,
+    to_client_sender: mpsc::Sender<MessageChunk>,
+    counters: Arc<SocketCounters>,
+    closed: Arc<Notify>,
+) {
+    let mut buffer = vec![0u8; buffer_size];
+    loop {
+        let (length, part) = match data_channel.read_data_channel_part(&mut buffer).await {
+            Ok((length, _, part)) => (length, part),
+            Err(err) => {
+                log::debug!("data channel closed; exit the read loop: {}", err);
+                closed.notify_one();
+                return;
+            }
+        };
+
+        if part.is_last {
+            counters.messages_received.fetch_add(1, Ordering::SeqCst);
+        }
+        counters
+            .bytes_received
+            .fetch_add(length as u64, Ordering::SeqCst);
+
+        let chunk = MessageChunk {
+            transfer_id: part.message,
+            offset: part.offset,
+            data: Bytes::copy_from_slice(&buffer[..length]),
+            is_last: part.is_last,
+        };
+        if to_client_sender.send(chunk).await.is_err() {
+            return;
+        }
+    }
+}
+
+// session_inbox_read_loop is session_read_loop for a DataChannel, whose inbox can hold messages
+// back while delivery is paused
+async fn session_inbox_read_loop(
+    data_channel: Arc<DataChannel>,
+    inbox: Arc<Inbox>,
+    counters: Arc<SocketCounters>,
+    closed: Arc<Notify>,
+) {
+    let mut buffer = read_buffer(&data_channel);
+    loop {
+        let message_length = match data_channel.read(&mut buffer).await {
+            Ok(length) => length,
+            Err(DataChannelError::Sctp(SctpError::ErrShortBuffer)) => {
+                drop_oversized_message(&counters, buffer.len());
+                continue;
+            }
+            Err(err) => {
+                log::debug!("data channel closed; exit the read loop: {}", err);
+                inbox.end();
+                closed.notify_one();
+                return;
+            }
+        };
+
+        counters.messages_received.fetch_add(1, Ordering::SeqCst);
+        counters
+            .bytes_received
+            .fetch_add(message_length as u64, Ordering::SeqCst);
+
+        if !inbox
+            .push(Bytes::copy_from_slice(&buffer[..message_length]))
+            .await
+        {
+            return;
+        }
+    }
+}
+
+// session_write_loop sends the messages from to_server_receiver on data_channel until either
+// is closed. While MAX_BUFFERED_AMOUNT bytes are unacknowledged it leaves the messages in the
+// queue, so a stalled association fills it up instead of SCTP's. Messages still queued when the
+// channel closes are dropped, as are the ones the association has no room for, see
+// SettingEngine::set_sctp_max_send_buffer_size.
+async fn session_write_loop(
+    data_channel: Arc<DataChannel>,
+    mut to_server_receiver: mpsc::Receiver<Bytes>,
+    counters: Arc<SocketCounters>,
+    closed: Arc<Notify>,
+) {
+    let drained = Arc::new(Notify::new());
+    let drained_2 = Arc::clone(&drained);
+    data_channel.set_buffered_amount_low_threshold(MAX_BUFFERED_AMOUNT / 2);
+    data_channel
+        .on_buffered_amount_low(Box::new(move || {
+            let drained = Arc::clone(&drained_2);
+            Box::pin(async move {
+                drained.notify_one();
+            })
+        }))
+        .await;
+
+    loop {
+        while data_channel.buffered_amount() >= MAX_BUFFERED_AMOUNT {
+            tokio::select! {
+                _ = drained.notified() => {}
+                _ = closed.notified() => {
+                    log::debug!("data channel closed; exit the write loop");
+                    return;
+                }
+            }
+        }
+        let message = tokio::select! {
+            message = to_server_receiver.recv() => match message {
+                Some(message) => message,
+                None => return,
+            },
+            _ = closed.notified() => {
+                log::debug!("data channel closed; exit the write loop");
+                return;
+            }
+        };
+        match data_channel.write(&message).await {
+            Ok(n) => {
+                counters.messages_sent.fetch_add(1, Ordering::SeqCst);
+                counters.bytes_sent.fetch_add(n as u64, Ordering::SeqCst);
+            }
+            Err(DataChannelError::Sctp(SctpError::ErrSendBufferFull)) => {
+                log::debug!("SCTP send buffer full, dropping a message");
+            }
+            Err(err) => {
+                log::debug!("data channel closed; exit the write loop: {}", err);
+                return;
+            }
+        }
     }
 }
 
@@ -174,10 +1932,17 @@ async fn read_loop(
     data_channel: Arc<DataChannel>,
     to_client_sender: mpsc::UnboundedSender<Box<[u8]>>,
 ) -> Result<()> {
-    let mut buffer = vec![0u8; MESSAGE_SIZE];
+    let mut buffer = read_buffer(&data_channel);
     loop {
         let message_length = match data_channel.read(&mut buffer).await {
             Ok(length) => length,
+            Err(DataChannelError::Sctp(SctpError::ErrShortBuffer)) => {
+                warn!(
+                    "dropped an inbound message larger than {} bytes",
+                    buffer.len()
+                );
+                continue;
+            }
             Err(err) => {
                 println!("Datachannel closed; Exit the read_loop: {}", err);
                 return Ok(());
@@ -212,31 +1977,36 @@ async fn write_loop(
     }
 }
 
-#[derive(Clone)]
-pub(crate) struct SessionAnswer {
-    pub(crate) sdp: String,
-}
+fn get_session_response(input: &str) -> std::result::Result<SignalingAnswer, AnswerError> {
+    let json_obj: JsonValue = input
+        .parse()
+        .map_err(|e: tinyjson::JsonParseError| AnswerError::NotJson(e.to_string()))?;
 
-pub(crate) struct SessionCandidate {
-    pub(crate) candidate: String,
-}
+    let sdp = get_string(&json_obj, "answer", "sdp")?;
+    let candidate = get_string(&json_obj, "candidate", "candidate")?;
 
-pub(crate) struct JsSessionResponse {
-    pub(crate) answer: SessionAnswer,
-    pub(crate) candidate: SessionCandidate,
+    Ok(SignalingAnswer {
+        sdp: sdp.clone(),
+        candidate: candidate.clone(),
+    })
 }
 
-fn get_session_response(input: &str) -> JsSessionResponse {
-    let json_obj: JsonValue = input.parse().unwrap();
-
-    let sdp_opt: Option<&String> = json_obj["answer"]["sdp"].get();
-    let sdp: String = sdp_opt.unwrap().clone();
-
-    let candidate_opt: Option<&String> = json_obj["candidate"]["candidate"].get();
-    let candidate: String = candidate_opt.unwrap().clone();
-
-    JsSessionResponse {
-        answer: SessionAnswer { sdp },
-        candidate: SessionCandidate { candidate },
+// get_string returns the string at json_obj[object][key], tinyjson's Index panics where the
+// server's answer lacks a level
+fn get_string<'a>(
+    json_obj: &'a JsonValue,
+    object: &'static str,
+    key: &'static str,
+) -> std::result::Result<&'a String, AnswerError> {
+    let value = match json_obj {
+        JsonValue::Object(json_obj) => match json_obj.get(object) {
+            Some(JsonValue::Object(object)) => object.get(key),
+            _ => None,
+        },
+        _ => None,
+    };
+    match value {
+        Some(JsonValue::String(value)) => Ok(value),
+        _ => Err(AnswerError::Missing(object, key)),
     }
 }
@@ -0,0 +1,95 @@
+pub(crate) mod setting_engine;
+
+use crate::webrtc::dtls_transport::RTCDtlsTransport;
+use crate::webrtc::ice_transport::ice_gatherer::RTCIceGatherer;
+use crate::webrtc::ice_transport::RTCIceTransport;
+use crate::webrtc::peer_connection::certificate::RTCCertificate;
+use crate::webrtc::peer_connection::event_log::EventLog;
+
+use crate::webrtc::error::Result;
+use crate::webrtc::sctp_transport::RTCSctpTransport;
+use crate::webrtc::util::redaction;
+
+use setting_engine::SettingEngine;
+use std::sync::Arc;
+
+/// API bundles the global functions of the WebRTC and ORTC API.
+/// Some of these functions are also exported globally using the
+/// defaultAPI object. Note that the global version of the API
+/// may be phased out in the future.
+pub(crate) struct API {
+    pub(crate) setting_engine: Arc<SettingEngine>,
+}
+
+impl API {
+    pub(crate) fn new(setting_engine: SettingEngine) -> Self {
+        redaction::set_mode(setting_engine.address_redaction);
+        API {
+            setting_engine: Arc::new(setting_engine),
+        }
+    }
+
+    /// new_ice_gatherer creates a new ice gatherer.
+    /// This constructor is part of the ORTC API. It is not
+    /// meant to be used together with the basic WebRTC API.
+    pub(crate) fn new_ice_gatherer(&self, event_log: Arc<EventLog>) -> Result<RTCIceGatherer> {
+        Ok(RTCIceGatherer::new(
+            Arc::clone(&self.setting_engine),
+            event_log,
+        ))
+    }
+
+    /// new_ice_transport creates a new ice transport.
+    /// This constructor is part of the ORTC API. It is not
+    /// meant to be used together with the basic WebRTC API.
+    pub(crate) fn new_ice_transport(&self, gatherer: Arc<RTCIceGatherer>) -> RTCIceTransport {
+        RTCIceTransport::new(gatherer)
+    }
+
+    /// new_dtls_transport creates a new dtls_transport transport.
+    /// This constructor is part of the ORTC API. It is not
+    /// meant to be used together with the basic WebRTC API.
+    pub(crate) fn new_dtls_transport(
+        &self,
+        ice_transport: Arc<RTCIceTransport>,
+        event_log: Arc<EventLog>,
+    ) -> Result<RTCDtlsTransport> {
+        let certificates = vec![RTCCertificate::generate(&self.setting_engine.rng)?];
+
+        let dtls_transport = RTCDtlsTransport::new(
+            ice_transport,
+            certificates,
+            self.setting_engine.packet_interceptors.clone(),
+            self.setting_engine.dtls_security_profile,
+            self.setting_engine.dtls_backend,
+            event_log,
+            self.setting_engine.spawner.clone(),
+        );
+        #[cfg(feature = "dtls-connection-id")]
+        let dtls_transport = RTCDtlsTransport {
+            connection_id: self.setting_engine.dtls_connection_id_length.map(|length| {
+                let mut connection_id = vec![0u8; length as usize];
+                self.setting_engine.rng.fill_bytes(&mut connection_id);
+                connection_id
+            }),
+            ..dtls_transport
+        };
+
+        Ok(dtls_transport)
+    }
+
+    /// new_sctp_transport creates a new SCTPTransport.
+    /// This constructor is part of the ORTC API. It is not
+    /// meant to be used together with the basic WebRTC API.
+    pub(crate) fn new_sctp_transport(
+        &self,
+        dtls_transport: Arc<RTCDtlsTransport>,
+        event_log: Arc<EventLog>,
+    ) -> Result<RTCSctpTransport> {
+        Ok(RTCSctpTransport::new(
+            dtls_transport,
+            Arc::clone(&self.setting_engine),
+            event_log,
+        ))
+    }
+}
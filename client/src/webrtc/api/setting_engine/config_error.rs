@@ -0,0 +1,67 @@
+use std::fmt;
+
+use thiserror::Error;
+
+/// ConfigIssue is one inconsistency in SocketOptions that would otherwise only fail the
+/// connection once it is being set up
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConfigIssue {
+    /// ConnectTimeoutZero indicates a zero SocketOptions::connect_timeout
+    #[error("connect_timeout must not be zero")]
+    ConnectTimeoutZero,
+    /// ChannelCapacityZero indicates a zero SocketOptions::channel_capacity
+    #[error("channel_capacity must not be zero")]
+    ChannelCapacityZero,
+    /// IceUfragInvalid indicates a ufrag of SettingEngine::set_ice_credentials that is shorter
+    /// than 4 ice-chars or has other characters
+    #[error("ice ufrag must be at least 4 ice-chars (ALPHA / DIGIT / \"+\" / \"/\")")]
+    IceUfragInvalid,
+    /// IcePwdInvalid indicates a pwd of SettingEngine::set_ice_credentials that is shorter
+    /// than 22 ice-chars or has other characters
+    #[error("ice pwd must be at least 22 ice-chars (ALPHA / DIGIT / \"+\" / \"/\")")]
+    IcePwdInvalid,
+    /// DscpOutOfRange indicates a SettingEngine::set_dscp code point that does not fit in
+    /// 6 bits
+    #[error("dscp {0} is out of range, code points are 0 to 63")]
+    DscpOutOfRange(u8),
+    /// ReceiveMtuOutOfRange indicates a SettingEngine::set_receive_mtu below the 1200 bytes
+    /// WebRTC endpoints send or above the 65535 the buffers can hold
+    #[error("receive_mtu {0} is out of range, 1200 to 65535")]
+    ReceiveMtuOutOfRange(usize),
+    /// MaxRemoteCandidatesZero indicates SettingEngine::set_max_remote_candidates(0), which
+    /// drops every remote candidate
+    #[error("max_remote_candidates must not be zero")]
+    MaxRemoteCandidatesZero,
+    /// MaxCandidatePairsZero indicates SettingEngine::set_max_candidate_pairs(0), which leaves
+    /// nothing to check
+    #[error("max_candidate_pairs must not be zero")]
+    MaxCandidatePairsZero,
+    /// SctpPacingGainInvalid indicates a SettingEngine::set_sctp_pacing_gain that is negative
+    /// or not a number
+    #[error("sctp_pacing_gain must be zero or positive")]
+    SctpPacingGainInvalid,
+    /// IceKeepaliveTooSlow indicates a keepalive_interval of SettingEngine::set_ice_timeouts
+    /// that is not shorter than the disconnected_timeout, so an idle connection goes
+    /// disconnected between two keepalives
+    #[error("ice keepalive_interval must be shorter than disconnected_timeout")]
+    IceKeepaliveTooSlow,
+}
+
+/// ConfigError lists every ConfigIssue found in SocketOptions, see SocketOptionsBuilder::build
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    pub issues: Vec<ConfigIssue>,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, issue) in self.issues.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{}", issue)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,604 @@
+pub(crate) mod config_error;
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use tokio::net::UdpSocket;
+use tokio::runtime::Handle;
+
+use crate::webrtc::dtls_transport::dtls_backend::DtlsBackendKind;
+use crate::webrtc::dtls_transport::dtls_security_profile::DtlsSecurityProfile;
+use crate::webrtc::dtls_transport::packet_interceptor::PacketInterceptor;
+use crate::webrtc::ice::agent::agent_config::{
+    KeepaliveKind, DEFAULT_DISCONNECTED_TIMEOUT, DEFAULT_KEEPALIVE_INTERVAL,
+};
+use crate::webrtc::ice::candidate::CandidateDisposition;
+use crate::webrtc::ice::gathering_context::GatheringContext;
+use crate::webrtc::ice::packet_capture::PacketSink;
+use crate::webrtc::ice::rand::{is_ice_chars, MIN_PWD_LEN, MIN_UFRAG_LEN};
+use crate::webrtc::ice::util::SocketBufferSizes;
+use crate::webrtc::ice_transport::ice_candidate::RTCIceCandidate;
+use crate::webrtc::ice_transport::ice_check_result::ConnectivityCheckResult;
+use crate::webrtc::ice_transport::ice_role::RTCIceRole;
+use crate::webrtc::util::random_source::RandomSource;
+use crate::webrtc::util::redaction::RedactionMode;
+use crate::webrtc::util::spawner::Spawner;
+use crate::webrtc::{DEFAULT_RECEIVE_MTU, MAX_RECEIVE_MTU, MIN_RECEIVE_MTU};
+use config_error::ConfigIssue;
+
+/// CandidateFilterFn decides for each gathered local candidate whether it is used and
+/// advertised, see SettingEngine::set_candidate_filter
+pub type CandidateFilterFn = Box<dyn (Fn(&RTCIceCandidate) -> CandidateDisposition) + Send + Sync>;
+
+/// InterfaceFilterFn tells by its name whether a local network interface is gathered on, see
+/// SettingEngine::set_interface_filter
+pub type InterfaceFilterFn = Box<dyn (Fn(&str) -> bool) + Send + Sync>;
+
+/// ConnectivityCheckResultFn receives the outcome of every ICE binding request, see
+/// SettingEngine::set_on_connectivity_check_result
+pub type ConnectivityCheckResultFn = Box<dyn Fn(&ConnectivityCheckResult) + Send + Sync>;
+
+/// Clock tells the wall clock time written into SDP, see SettingEngine::set_clock
+pub trait Clock: Send + Sync {
+    fn now(&self) -> SystemTime;
+}
+
+#[derive(Default, Debug, Clone)]
+pub(crate) struct Candidates {
+    pub(crate) username_fragment: String,
+    pub(crate) password: String,
+    pub(crate) max_remote_candidates: Option<usize>,
+    pub(crate) max_candidate_pairs: Option<usize>,
+}
+
+#[derive(Default, Debug, Copy, Clone)]
+pub(crate) struct Timeout {
+    pub(crate) ice_disconnected_timeout: Option<Duration>,
+    pub(crate) ice_failed_timeout: Option<Duration>,
+    pub(crate) ice_keepalive_interval: Option<Duration>,
+}
+
+/// SettingEngine allows influencing behavior in ways that are not
+/// supported by the WebRTC API. This allows us to support additional
+/// use-cases without deviating from the WebRTC API elsewhere.
+#[derive(Default, Clone)]
+pub struct SettingEngine {
+    pub(crate) candidates: Candidates,
+    pub(crate) timeout: Timeout,
+    pub(crate) sctp_bundling_delay: Duration,
+    pub(crate) sctp_pacing: bool,
+    pub(crate) sctp_pacing_gain: f64,
+    pub(crate) sctp_respect_remote_bandwidth: bool,
+    pub(crate) sctp_mtu: u32,
+    pub(crate) sctp_max_receive_buffer_size: u32,
+    pub(crate) sctp_initial_recv_window: u32,
+    pub(crate) sctp_max_send_buffer_size: usize,
+    pub(crate) sctp_max_inbound_streams: u16,
+    pub(crate) sctp_max_outbound_streams: u16,
+    pub(crate) sctp_strict_priority: bool,
+    pub(crate) sctp_accept_peer_restart: bool,
+    pub(crate) udp_socket: Option<Arc<UdpSocket>>,
+    pub(crate) dscp: Option<u8>,
+    pub(crate) socket_buffer_sizes: SocketBufferSizes,
+    pub(crate) receive_mtu: usize,
+    pub(crate) ice_check_interval: Duration,
+    pub(crate) ice_max_binding_requests: Option<u16>,
+    pub(crate) ice_keepalive_kind: KeepaliveKind,
+    pub(crate) ice_tcp: bool,
+    pub(crate) ice_role: Option<RTCIceRole>,
+    pub(crate) candidate_filter: Option<Arc<CandidateFilterFn>>,
+    pub(crate) interface_filter: Option<Arc<InterfaceFilterFn>>,
+    pub(crate) allow_zero_candidates: bool,
+    pub(crate) on_connectivity_check_result: Option<Arc<ConnectivityCheckResultFn>>,
+    pub(crate) packet_interceptors: Vec<Arc<dyn PacketInterceptor>>,
+    pub(crate) dtls_security_profile: DtlsSecurityProfile,
+    pub(crate) dtls_backend: DtlsBackendKind,
+    pub(crate) packet_capture: Option<Arc<dyn PacketSink>>,
+    pub(crate) event_log_size: Option<usize>,
+    pub(crate) address_redaction: RedactionMode,
+    pub(crate) gathering_context: Option<GatheringContext>,
+    pub(crate) rng: RandomSource,
+    pub(crate) clock: Option<Arc<dyn Clock>>,
+    pub(crate) spawner: Spawner,
+    #[cfg(feature = "network-monitor")]
+    pub(crate) network_monitor_interval: Option<Duration>,
+    #[cfg(feature = "dtls-connection-id")]
+    pub(crate) dtls_connection_id_length: Option<u8>,
+}
+
+impl fmt::Debug for SettingEngine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("SettingEngine");
+        d.field("candidates", &self.candidates)
+            .field("timeout", &self.timeout)
+            .field("sctp_bundling_delay", &self.sctp_bundling_delay)
+            .field("sctp_pacing", &self.sctp_pacing)
+            .field("sctp_pacing_gain", &self.sctp_pacing_gain)
+            .field(
+                "sctp_respect_remote_bandwidth",
+                &self.sctp_respect_remote_bandwidth,
+            )
+            .field("sctp_mtu", &self.sctp_mtu)
+            .field(
+                "sctp_max_receive_buffer_size",
+                &self.sctp_max_receive_buffer_size,
+            )
+            .field("sctp_initial_recv_window", &self.sctp_initial_recv_window)
+            .field("sctp_max_send_buffer_size", &self.sctp_max_send_buffer_size)
+            .field("sctp_max_inbound_streams", &self.sctp_max_inbound_streams)
+            .field("sctp_max_outbound_streams", &self.sctp_max_outbound_streams)
+            .field("sctp_strict_priority", &self.sctp_strict_priority)
+            .field("sctp_accept_peer_restart", &self.sctp_accept_peer_restart)
+            .field("udp_socket", &self.udp_socket)
+            .field("dscp", &self.dscp)
+            .field("socket_buffer_sizes", &self.socket_buffer_sizes)
+            .field("receive_mtu", &self.receive_mtu)
+            .field("ice_check_interval", &self.ice_check_interval)
+            .field("ice_max_binding_requests", &self.ice_max_binding_requests)
+            .field("ice_keepalive_kind", &self.ice_keepalive_kind)
+            .field("ice_tcp", &self.ice_tcp)
+            .field("ice_role", &self.ice_role)
+            .field("candidate_filter", &self.candidate_filter.is_some())
+            .field("interface_filter", &self.interface_filter.is_some())
+            .field("allow_zero_candidates", &self.allow_zero_candidates)
+            .field(
+                "on_connectivity_check_result",
+                &self.on_connectivity_check_result.is_some(),
+            )
+            .field("packet_interceptors", &self.packet_interceptors.len())
+            .field("dtls_security_profile", &self.dtls_security_profile)
+            .field("dtls_backend", &self.dtls_backend)
+            .field("packet_capture", &self.packet_capture.is_some())
+            .field("event_log_size", &self.event_log_size)
+            .field("address_redaction", &self.address_redaction)
+            .field("gathering_context", &self.gathering_context)
+            .field("rng", &self.rng)
+            .field("clock", &self.clock.is_some())
+            .field("spawner", &self.spawner);
+        #[cfg(feature = "network-monitor")]
+        d.field("network_monitor_interval", &self.network_monitor_interval);
+        #[cfg(feature = "dtls-connection-id")]
+        d.field("dtls_connection_id_length", &self.dtls_connection_id_length);
+        d.finish()
+    }
+}
+
+impl SettingEngine {
+    pub fn new() -> Self {
+        SettingEngine::default()
+    }
+
+    /// set_ice_credentials sets a static ufrag/pwd to be used by the ICE agent instead of
+    /// randomly generated ones. This is useful if the remote needs to know the credentials
+    /// before the offer arrives. Per RFC 8445 the ufrag must be at least 4 and the pwd at least
+    /// 22 ice-chars (ALPHA / DIGIT / "+" / "/"), otherwise SocketOptionsBuilder::build fails
+    /// with ConfigIssue::IceUfragInvalid or IcePwdInvalid. An empty one is generated randomly.
+    pub fn set_ice_credentials(&mut self, username_fragment: String, password: String) {
+        self.candidates.username_fragment = username_fragment;
+        self.candidates.password = password;
+    }
+
+    /// set_max_remote_candidates caps how many remote candidates the ICE agent keeps (default
+    /// 64). Beyond the cap the lowest priority candidate is evicted, or the new one dropped if it
+    /// ranks lowest. Candidates of the selected pair are never evicted. Zero is rejected with
+    /// ConfigIssue::MaxRemoteCandidatesZero.
+    pub fn set_max_remote_candidates(&mut self, max_remote_candidates: usize) {
+        self.candidates.max_remote_candidates = Some(max_remote_candidates);
+    }
+
+    /// set_max_candidate_pairs caps the ICE checklist size (default 256), enforced like
+    /// set_max_remote_candidates. Zero is rejected with ConfigIssue::MaxCandidatePairsZero.
+    pub fn set_max_candidate_pairs(&mut self, max_candidate_pairs: usize) {
+        self.candidates.max_candidate_pairs = Some(max_candidate_pairs);
+    }
+
+    /// set_sctp_bundling_delay makes the SCTP association wait this long after a write before
+    /// sending, so that messages written in quick succession are bundled into one packet. This
+    /// trades latency for a lower packet rate. The default of zero only bundles messages that are
+    /// already queued when the association sends.
+    pub fn set_sctp_bundling_delay(&mut self, bundling_delay: Duration) {
+        self.sctp_bundling_delay = bundling_delay;
+    }
+
+    /// set_sctp_pacing makes the SCTP association spread the packets it sends over a round trip
+    /// at the pacing gain times the congestion window per smoothed round trip time, instead of
+    /// sending what the window allows back to back. This avoids the bursts that follow loss
+    /// recovery and window growth, which cause further loss on links with small queues. A
+    /// couple of packets still go out right away after an idle period, so a shallow queue is
+    /// not delayed. Off by default.
+    pub fn set_sctp_pacing(&mut self, pacing: bool) {
+        self.sctp_pacing = pacing;
+    }
+
+    /// set_sctp_pacing_gain sets the pacing gain of set_sctp_pacing (default 1.25). Above one the
+    /// window is sent in less than a round trip, so pacing does not lower the throughput. In
+    /// slow start a gain of at least 2 is used. Zero keeps the default, a negative or NaN gain
+    /// is rejected with ConfigIssue::SctpPacingGainInvalid.
+    pub fn set_sctp_pacing_gain(&mut self, pacing_gain: f64) {
+        self.sctp_pacing_gain = pacing_gain;
+    }
+
+    /// set_sctp_respect_remote_bandwidth makes the SCTP association never send faster than the
+    /// b=TIAS or b=AS limit of the remote description, see SocketHandle::remote_bandwidth_limit.
+    /// The limit counts SCTP packets, DTLS, UDP and IP overhead come on top. It applies with or
+    /// without set_sctp_pacing. Without a limit in the description, or with a zero one, the rate
+    /// is not capped. Off by default.
+    pub fn set_sctp_respect_remote_bandwidth(&mut self, respect_remote_bandwidth: bool) {
+        self.sctp_respect_remote_bandwidth = respect_remote_bandwidth;
+    }
+
+    /// set_sctp_mtu sets the largest SCTP packet handed to DTLS (default 1228). Messages are
+    /// fragmented into DATA chunks of at most this size minus 28 bytes of SCTP headers. DTLS,
+    /// UDP and IP overhead come on top, so lower this on links whose path MTU is below 1300.
+    /// Values below 512 are raised to 512.
+    pub fn set_sctp_mtu(&mut self, mtu: usize) {
+        self.sctp_mtu = u32::try_from(mtu).unwrap_or(u32::MAX);
+    }
+
+    /// set_sctp_max_receive_buffer_size caps how many bytes of received messages the SCTP
+    /// association queues for data channels that are not read from (default 1MiB). The receive
+    /// window advertised to the peer shrinks as the queue fills and reopens as the application
+    /// reads, so a well-behaved sender slows down to the rate messages are consumed. Zero keeps
+    /// the default.
+    pub fn set_sctp_max_receive_buffer_size(&mut self, max_receive_buffer_size: usize) {
+        self.sctp_max_receive_buffer_size =
+            u32::try_from(max_receive_buffer_size).unwrap_or(u32::MAX);
+    }
+
+    /// set_sctp_initial_recv_window sets the receive window advertised in the SCTP INIT and
+    /// INIT-ACK, which caps what the peer sends before the first SACK (default the max receive
+    /// buffer size). Later SACKs advertise what is left of the max receive buffer. Values below
+    /// the 1500 bytes RFC 4960 requires are raised to 1500, values above the max receive buffer
+    /// size raise the buffer to match. Zero keeps the default.
+    pub fn set_sctp_initial_recv_window(&mut self, initial_recv_window: usize) {
+        self.sctp_initial_recv_window = u32::try_from(initial_recv_window).unwrap_or(u32::MAX);
+    }
+
+    /// set_sctp_max_send_buffer_size caps how many bytes of messages the SCTP association holds
+    /// for sending across all data channels (default 16MiB): the ones queued and the ones sent
+    /// but not acknowledged yet, which a peer that stopped responding never releases. Past the
+    /// cap messages are dropped: RTCDataChannel::send_with_deadline fails with
+    /// std::io::ErrorKind::WouldBlock and the channels of a SocketHandle discard them, while
+    /// channel setup messages wait for room. A single message larger than the cap is still
+    /// sent on its own. Zero keeps the default.
+    pub fn set_sctp_max_send_buffer_size(&mut self, max_send_buffer_size: usize) {
+        self.sctp_max_send_buffer_size = max_send_buffer_size;
+    }
+
+    /// set_sctp_max_inbound_streams sets how many streams the peer may open towards the client,
+    /// advertised in the SCTP INIT and INIT-ACK (default 65535). The association uses the
+    /// smaller of this and the number of outbound streams the peer asks for. Zero keeps the
+    /// default.
+    pub fn set_sctp_max_inbound_streams(&mut self, max_inbound_streams: u16) {
+        self.sctp_max_inbound_streams = max_inbound_streams;
+    }
+
+    /// set_sctp_max_outbound_streams sets how many streams the client asks to open towards the
+    /// peer in the SCTP INIT and INIT-ACK (default 65535). The association uses the smaller of
+    /// this and the number of inbound streams the peer allows. Each data channel takes a stream
+    /// identifier and locally opened channels take every other one, so opening a channel whose
+    /// identifier is beyond the negotiated count fails with ErrStreamsExhausted. Zero keeps the
+    /// default.
+    pub fn set_sctp_max_outbound_streams(&mut self, max_outbound_streams: u16) {
+        self.sctp_max_outbound_streams = max_outbound_streams;
+    }
+
+    /// set_sctp_strict_priority makes data channels of higher priority always send first. By
+    /// default channels take turns weighted by their priority, so that a bulk transfer on a low
+    /// priority channel slows down but is never starved. Either way a message that started
+    /// sending is finished before the next one, so large messages still delay other channels.
+    pub fn set_sctp_strict_priority(&mut self, strict_priority: bool) {
+        self.sctp_strict_priority = strict_priority;
+    }
+
+    /// set_sctp_accept_peer_restart makes a new SCTP association come up after the peer
+    /// restarted its end, which is taken from an INIT with a new tag or from packets that keep
+    /// carrying an unknown verification tag. Either way the old association is closed and its
+    /// channels with TransportError::PeerRestart. With this set the channels opened here are
+    /// then opened again on the new association, see RTCDataChannel::on_open. Senders and
+    /// receivers handed out for the old channels stay closed. Off by default.
+    pub fn set_sctp_accept_peer_restart(&mut self, accept_peer_restart: bool) {
+        self.sctp_accept_peer_restart = accept_peer_restart;
+    }
+
+    /// set_udp_socket makes ICE use an already bound socket, e.g. one carrying custom socket
+    /// options, for its only host candidate and all traffic. The candidate advertises the
+    /// socket's local address, or the first local interface of the same family if it is bound
+    /// to the unspecified address. No other interface is gathered on.
+    pub fn set_udp_socket(&mut self, udp_socket: Arc<UdpSocket>) {
+        self.udp_socket = Some(udp_socket);
+    }
+
+    /// set_dscp marks all outbound STUN, DTLS and SCTP packets with the given DSCP code point,
+    /// e.g. 46 for Expedited Forwarding, by setting IP_TOS / IPV6_TCLASS on the ICE sockets.
+    /// Platforms that refuse the option log a warning and connect unmarked. Code points above
+    /// 63 are rejected with ConfigIssue::DscpOutOfRange.
+    pub fn set_dscp(&mut self, dscp: u8) {
+        self.dscp = Some(dscp);
+    }
+
+    /// set_socket_buffer_sizes sets SO_RCVBUF and SO_SNDBUF in bytes on the UDP sockets ICE
+    /// binds, and on the socket of set_udp_socket. A larger receive buffer keeps the kernel
+    /// from dropping datagrams that arrive in bursts at high throughput. None keeps the default
+    /// of the OS. The kernel clamps the sizes to its limits, SocketStats::socket_buffer_sizes
+    /// reports what it made of them. Sizes the OS refuses log a warning and connect anyway.
+    pub fn set_socket_buffer_sizes(&mut self, recv: Option<usize>, send: Option<usize>) {
+        self.socket_buffer_sizes = SocketBufferSizes { recv, send };
+    }
+
+    /// set_receive_mtu sets the largest inbound UDP payload accepted (default 1628). Larger
+    /// STUN, DTLS and SCTP datagrams are dropped and counted in SocketStats::oversized_datagrams
+    /// instead of being cut off. ICE, DTLS and SCTP size their read buffers for it, raise it for
+    /// peers sending jumbo frames. Zero keeps the default, values outside 1200 to 65535 are
+    /// rejected with ConfigIssue::ReceiveMtuOutOfRange.
+    pub fn set_receive_mtu(&mut self, receive_mtu: usize) {
+        self.receive_mtu = receive_mtu;
+    }
+
+    /// receive_mtu returns the receive MTU of set_receive_mtu, the default if none was set
+    pub(crate) fn receive_mtu(&self) -> usize {
+        if self.receive_mtu == 0 {
+            DEFAULT_RECEIVE_MTU
+        } else {
+            self.receive_mtu
+        }
+    }
+
+    /// set_ice_check_interval sets the pacing timer (Ta) of ICE connectivity checks (default
+    /// 50ms). At most one check is sent per interval across all candidate pairs, so a larger
+    /// value spreads the initial checks out at the cost of a slower connect. Zero keeps the
+    /// default.
+    pub fn set_ice_check_interval(&mut self, check_interval: Duration) {
+        self.ice_check_interval = check_interval;
+    }
+
+    /// set_ice_timeouts sets the behavior around ICE connectivity. disconnected_timeout
+    /// (default 5s) is how long without network activity before the connection is considered
+    /// disconnected, failed_timeout (default 25s) is how long after that before it is
+    /// considered failed, and keepalive_interval (default 2s) is how often a keepalive is
+    /// sent when there is no other traffic. None keeps the default and zero turns the
+    /// respective timer off. A keepalive_interval that is not shorter than the
+    /// disconnected_timeout is rejected with ConfigIssue::IceKeepaliveTooSlow, as an idle
+    /// connection would go disconnected between two keepalives.
+    pub fn set_ice_timeouts(
+        &mut self,
+        disconnected_timeout: Option<Duration>,
+        failed_timeout: Option<Duration>,
+        keepalive_interval: Option<Duration>,
+    ) {
+        self.timeout.ice_disconnected_timeout = disconnected_timeout;
+        self.timeout.ice_failed_timeout = failed_timeout;
+        self.timeout.ice_keepalive_interval = keepalive_interval;
+    }
+
+    /// set_ice_keepalive_kind sets what is sent on the selected pair every keepalive_interval
+    /// (see set_ice_timeouts) when there is no other traffic, BindingIndication by default. Use
+    /// BindingRequest through middleboxes that drop indications; their responses then also
+    /// count as connectivity check results.
+    pub fn set_ice_keepalive_kind(&mut self, keepalive_kind: KeepaliveKind) {
+        self.ice_keepalive_kind = keepalive_kind;
+    }
+
+    /// set_ice_max_binding_requests sets how many connectivity checks are sent over a candidate
+    /// pair without an answer before the pair is marked failed (default 7).
+    pub fn set_ice_max_binding_requests(&mut self, max_binding_requests: u16) {
+        self.ice_max_binding_requests = Some(max_binding_requests);
+    }
+
+    /// set_ice_tcp lets ICE fall back to TCP (RFC 6544) on networks that block UDP. Active TCP
+    /// host candidates are advertised with the discard port 9, and a connection is opened to
+    /// each passive TCP candidate of the remote and checked alongside the UDP pairs. UDP pairs
+    /// rank higher and a TCP pair is only nominated once no UDP pair is left to check. Packets
+    /// are framed with the 2 byte length prefix of RFC 4571. Off by default.
+    pub fn set_ice_tcp(&mut self, enabled: bool) {
+        self.ice_tcp = enabled;
+    }
+
+    /// set_ice_role overrides the ICE role the client starts in. By default it is controlling
+    /// when it made the offer or the remote is ice-lite (RFC 8445 section 6.1.1) and controlled
+    /// otherwise. Forcing a role is meant for ice-lite deployments that get the roles wrong and
+    /// for deterministic tests. If both agents end up in the same role the conflict is still
+    /// resolved by tie-breaker, so the role can change later, see SocketHandle::ice_role.
+    /// RTCIceRole::Unspecified goes back to picking the role automatically.
+    pub fn set_ice_role(&mut self, role: RTCIceRole) {
+        self.ice_role = match role {
+            RTCIceRole::Unspecified => None,
+            role => Some(role),
+        };
+    }
+
+    /// set_candidate_filter decides for each gathered local candidate what happens to it, e.g.
+    /// to keep LAN addresses out of signaling for privacy. UseAndAdvertise candidates are
+    /// reported through on_ice_candidate and written to the SDP, UseButDontAdvertise ones only
+    /// take part in connectivity checks towards the remote's candidates and Drop ones are
+    /// closed. Without a filter every candidate is used and advertised. The filter also sees
+    /// candidates gathered later on, e.g. by the network monitor.
+    pub fn set_candidate_filter(&mut self, filter: CandidateFilterFn) {
+        self.candidate_filter = Some(Arc::new(filter));
+    }
+
+    /// set_interface_filter restricts gathering to the local network interfaces whose name the
+    /// filter accepts, e.g. to keep VPN or container interfaces out of the candidates. Without a
+    /// filter every interface that is up is gathered on.
+    pub fn set_interface_filter(&mut self, filter: InterfaceFilterFn) {
+        self.interface_filter = Some(Arc::new(filter));
+    }
+
+    /// set_allow_zero_candidates decides what happens when gathering completes without a single
+    /// local candidate, e.g. because every interface was filtered out. By default the connection
+    /// fails right away with ErrNoCandidatesGathered, which data channels waiting to open get
+    /// through their on_error handler. With allow set it carries on, which only works if the
+    /// remote reaches the client as a peer reflexive candidate, and the condition is logged and
+    /// reported in CandidateStatsReport::no_candidates_gathered.
+    pub fn set_allow_zero_candidates(&mut self, allow: bool) {
+        self.allow_zero_candidates = allow;
+    }
+
+    /// set_on_connectivity_check_result sets a callback that gets the local and remote candidate,
+    /// round trip time and outcome of every binding request ICE sends, both the checks before a
+    /// pair is selected and the keepalives on the selected one. Together with
+    /// SocketHandle::select_candidate_pair it allows picking the path by measured RTT instead
+    /// of priority. The callback runs on the ICE agent's task, so it must not block.
+    pub fn set_on_connectivity_check_result(&mut self, f: ConnectivityCheckResultFn) {
+        self.on_connectivity_check_result = Some(Arc::new(f));
+    }
+
+    /// add_packet_interceptor registers an interceptor that sees every DTLS datagram sent to and
+    /// received from the remote, e.g. to capture the traffic or drop packets on purpose.
+    /// Interceptors run in the order they were added and a packet dropped by one is not shown to
+    /// the ones after it. Without interceptors packets go straight between DTLS and ICE.
+    pub fn add_packet_interceptor(&mut self, interceptor: Arc<dyn PacketInterceptor>) {
+        self.packet_interceptors.push(interceptor);
+    }
+
+    /// set_dtls_security_profile sets what the DTLS handshake requires of the server (default
+    /// Compatible). With Strict a server without the extended master secret or attempting a
+    /// renegotiation fails the connect with a SocketError::Dtls that names the requirement, see
+    /// SocketHandle::dtls_connection_info for what was negotiated.
+    pub fn set_dtls_security_profile(&mut self, profile: DtlsSecurityProfile) {
+        self.dtls_security_profile = profile;
+    }
+
+    /// set_dtls_backend selects the DTLS implementation (default Vendored). Other backends are
+    /// compiled in with their feature, e.g. OpenSsl with dtls-openssl. The security profile
+    /// applies to every backend.
+    pub fn set_dtls_backend(&mut self, backend: DtlsBackendKind) {
+        self.dtls_backend = backend;
+    }
+
+    /// set_packet_capture hands every UDP packet ICE sends or receives, STUN and DTLS, to sink
+    /// together with its direction, time and addresses, e.g. a PcapFileSink to debug a session
+    /// with Wireshark. The packets are seen as on the wire, so the DTLS ones are encrypted.
+    /// Without a sink the sockets are used directly.
+    pub fn set_packet_capture(&mut self, sink: Arc<dyn PacketSink>) {
+        self.packet_capture = Some(sink);
+    }
+
+    /// set_rng_seed makes the connections made with this setting engine draw their random values
+    /// from a generator seeded with seed instead of the secure system one, so that tests get the
+    /// same offers on every run. It covers the ICE credentials, candidate ids, tie breaker and
+    /// mDNS name, the SDP session id and the DTLS certificate, which is then an Ed25519 one
+    /// derived from the seed. The DTLS handshake secrets and the SCTP verification tags and
+    /// TSNs still come from the system generator. Clones of the setting engine share the
+    /// generator, every connection made with them continues its sequence. Only for tests, the
+    /// values are predictable to anyone knowing the seed.
+    pub fn set_rng_seed(&mut self, seed: u64) {
+        self.rng = RandomSource::from_seed(seed);
+    }
+
+    /// set_clock replaces the system clock for the time written into SDP, the origin's session
+    /// version. Together with set_rng_seed this makes generated offers reproducible.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = Some(clock);
+    }
+
+    /// set_runtime_handle makes the connections made with this setting engine run their
+    /// internal tasks, the ICE agent, DTLS, SCTP and data channel loops, on the runtime of
+    /// handle, e.g. a dedicated networking runtime, instead of the one they are created from.
+    /// The runtime has to outlive the connections.
+    pub fn set_runtime_handle(&mut self, handle: Handle) {
+        self.spawner = Spawner::new(handle);
+    }
+
+    /// config_issues returns the settings that contradict each other or would fail the
+    /// connection later on, see SocketOptions::validate
+    pub(crate) fn config_issues(&self) -> Vec<ConfigIssue> {
+        let mut issues = vec![];
+
+        let ufrag = &self.candidates.username_fragment;
+        if !ufrag.is_empty() && (ufrag.len() < MIN_UFRAG_LEN || !is_ice_chars(ufrag)) {
+            issues.push(ConfigIssue::IceUfragInvalid);
+        }
+        let pwd = &self.candidates.password;
+        if !pwd.is_empty() && (pwd.len() < MIN_PWD_LEN || !is_ice_chars(pwd)) {
+            issues.push(ConfigIssue::IcePwdInvalid);
+        }
+        if let Some(dscp) = self.dscp.filter(|dscp| *dscp > 0x3f) {
+            issues.push(ConfigIssue::DscpOutOfRange(dscp));
+        }
+        if self.receive_mtu != 0 && !(MIN_RECEIVE_MTU..=MAX_RECEIVE_MTU).contains(&self.receive_mtu)
+        {
+            issues.push(ConfigIssue::ReceiveMtuOutOfRange(self.receive_mtu));
+        }
+        if self.candidates.max_remote_candidates == Some(0) {
+            issues.push(ConfigIssue::MaxRemoteCandidatesZero);
+        }
+        if self.candidates.max_candidate_pairs == Some(0) {
+            issues.push(ConfigIssue::MaxCandidatePairsZero);
+        }
+        if self.sctp_pacing_gain.is_nan() || self.sctp_pacing_gain < 0.0 {
+            issues.push(ConfigIssue::SctpPacingGainInvalid);
+        }
+
+        let keepalive_interval = self
+            .timeout
+            .ice_keepalive_interval
+            .unwrap_or(DEFAULT_KEEPALIVE_INTERVAL);
+        let disconnected_timeout = self
+            .timeout
+            .ice_disconnected_timeout
+            .unwrap_or(DEFAULT_DISCONNECTED_TIMEOUT);
+        if !keepalive_interval.is_zero()
+            && !disconnected_timeout.is_zero()
+            && keepalive_interval >= disconnected_timeout
+        {
+            issues.push(ConfigIssue::IceKeepaliveTooSlow);
+        }
+
+        issues
+    }
+
+    /// now returns the time from the clock set with set_clock, the system time otherwise
+    pub(crate) fn now(&self) -> SystemTime {
+        self.clock
+            .as_ref()
+            .map_or_else(SystemTime::now, |clock| clock.now())
+    }
+
+    /// set_event_log_size sets how many of the latest state transitions, ICE check results and
+    /// SCTP packet summaries a peer connection keeps for SocketHandle::recent_events and the
+    /// errors of a failed connect (default 256). Zero turns the log off.
+    pub fn set_event_log_size(&mut self, size: usize) {
+        self.event_log_size = Some(size);
+    }
+
+    /// set_address_redaction sets how IP addresses and host names of candidates show up in log
+    /// lines, CandidateStats and Diagnostics (default Off), e.g. Hash to keep user addresses
+    /// out of collected logs while still telling them apart. Ports are kept and the addresses
+    /// sent to the remote are unaffected. Formatting has no session at hand, so the mode
+    /// applies to the whole process from when a connection is made with this setting engine.
+    pub fn set_address_redaction(&mut self, mode: RedactionMode) {
+        self.address_redaction = mode;
+    }
+
+    /// set_gathering_context shares context between the connections made with this setting
+    /// engine, or any other it is set on. Connections created within its ttl of each other
+    /// enumerate the local interfaces only once. Has no effect with set_udp_socket.
+    pub fn set_gathering_context(&mut self, context: GatheringContext) {
+        self.gathering_context = Some(context);
+    }
+
+    /// set_network_monitor_interval makes ICE poll the local interfaces at this interval and
+    /// follow address changes, e.g. a laptop switching WiFi networks, without an ICE restart.
+    /// Candidates on vanished addresses are dropped and new addresses are gathered on, checked
+    /// against the remote candidates and reported through on_ice_candidate. If the selected
+    /// pair is lost and the remote does not support trickle ICE, the connection goes to
+    /// disconnected while checks continue. Has no effect with set_udp_socket.
+    #[cfg(feature = "network-monitor")]
+    pub fn set_network_monitor_interval(&mut self, interval: Duration) {
+        self.network_monitor_interval = Some(interval);
+    }
+
+    /// set_dtls_connection_id makes the DTLS handshake offer Connection IDs (RFC 9146). If the
+    /// server agrees, the records sent to it carry the id it chose and are matched to the
+    /// connection by that id instead of their source address, so a NAT rebinding of this
+    /// client is transparent to DTLS and SCTP once ICE has checked the new path. The server is
+    /// asked to put a random id of length bytes on the records it sends; 0 asks for none,
+    /// which is enough to follow a rebinding of the client. Servers without the extension
+    /// simply ignore it, see DtlsConnectionInfo::connection_id_used.
+    #[cfg(feature = "dtls-connection-id")]
+    pub fn set_dtls_connection_id(&mut self, length: u8) {
+        self.dtls_connection_id_length = Some(length);
+    }
+}
@@ -96,9 +96,14 @@ impl CipherSuite for CipherSuiteAes128Ccm {
         Ok(())
     }
 
-    fn encrypt(&self, pkt_rlh: &RecordLayerHeader, raw: &[u8]) -> Result<Vec<u8>> {
+    fn encrypt(
+        &self,
+        pkt_rlh: &RecordLayerHeader,
+        connection_id: &[u8],
+        raw: &[u8],
+    ) -> Result<Vec<u8>> {
         if let Some(ccm) = &self.ccm {
-            ccm.encrypt(pkt_rlh, raw)
+            ccm.encrypt(pkt_rlh, connection_id, raw)
         } else {
             Err(Error::Other(
                 "CipherSuite has not been initialized, unable to encrypt".to_owned(),
@@ -106,9 +111,9 @@ impl CipherSuite for CipherSuiteAes128Ccm {
         }
     }
 
-    fn decrypt(&self, input: &[u8]) -> Result<Vec<u8>> {
+    fn decrypt(&self, connection_id: &[u8], input: &[u8]) -> Result<Vec<u8>> {
         if let Some(ccm) = &self.ccm {
-            ccm.decrypt(input)
+            ccm.decrypt(connection_id, input)
         } else {
             Err(Error::Other(
                 "CipherSuite has not been initialized, unable to decrypt".to_owned(),
@@ -91,9 +91,14 @@ impl CipherSuite for CipherSuiteAes128GcmSha256 {
         Ok(())
     }
 
-    fn encrypt(&self, pkt_rlh: &RecordLayerHeader, raw: &[u8]) -> Result<Vec<u8>> {
+    fn encrypt(
+        &self,
+        pkt_rlh: &RecordLayerHeader,
+        connection_id: &[u8],
+        raw: &[u8],
+    ) -> Result<Vec<u8>> {
         if let Some(cg) = &self.gcm {
-            cg.encrypt(pkt_rlh, raw)
+            cg.encrypt(pkt_rlh, connection_id, raw)
         } else {
             Err(Error::Other(
                 "CipherSuite has not been initialized, unable to encrypt".to_owned(),
@@ -101,9 +106,9 @@ impl CipherSuite for CipherSuiteAes128GcmSha256 {
         }
     }
 
-    fn decrypt(&self, input: &[u8]) -> Result<Vec<u8>> {
+    fn decrypt(&self, connection_id: &[u8], input: &[u8]) -> Result<Vec<u8>> {
         if let Some(cg) = &self.gcm {
-            cg.decrypt(input)
+            cg.decrypt(connection_id, input)
         } else {
             Err(Error::Other(
                 "CipherSuite has not been initialized, unable to decrypt".to_owned(),
@@ -131,8 +131,15 @@ pub(crate) trait CipherSuite {
         is_client: bool,
     ) -> Result<()>;
 
-    fn encrypt(&self, pkt_rlh: &RecordLayerHeader, raw: &[u8]) -> Result<Vec<u8>>;
-    fn decrypt(&self, input: &[u8]) -> Result<Vec<u8>>;
+    // encrypt and decrypt take the connection id in the header of a
+    // ContentType::ConnectionId record, RFC 9146, and an empty one for other records
+    fn encrypt(
+        &self,
+        pkt_rlh: &RecordLayerHeader,
+        connection_id: &[u8],
+        raw: &[u8],
+    ) -> Result<Vec<u8>>;
+    fn decrypt(&self, connection_id: &[u8], input: &[u8]) -> Result<Vec<u8>>;
 }
 
 // Taken from https://www.iana.org/assignments/tls-parameters/tls-parameters.xml
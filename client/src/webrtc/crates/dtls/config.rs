@@ -3,6 +3,7 @@ use crate::webrtc::dtls::crypto::*;
 use crate::webrtc::dtls::error::*;
 use crate::webrtc::dtls::extension::extension_use_srtp::SrtpProtectionProfile;
 use crate::webrtc::dtls::handshaker::VerifyPeerCertificateFn;
+use crate::webrtc::util::spawner::Spawner;
 
 use std::sync::Arc;
 use tokio::time::Duration;
@@ -33,6 +34,18 @@ pub(crate) struct Config {
     /// should be disabled, requested, or required (default requested).
     pub(crate) extended_master_secret: ExtendedMasterSecretType,
 
+    /// disallow_renegotiation fails the handshake when the peer's renegotiation_info extension
+    /// is not empty, i.e. it treats the handshake as a renegotiation. Renegotiation is never
+    /// started locally, so otherwise the extension is only logged.
+    pub(crate) disallow_renegotiation: bool,
+
+    /// connection_id offers the Connection ID extension of RFC 9146, asking the peer to put
+    /// this id on the records it sends, so they are matched by id rather than by the address
+    /// they came from. An empty id only agrees to put the peer's id on the records sent to
+    /// it. None (default) does not offer the extension.
+    #[cfg(feature = "dtls-connection-id")]
+    pub(crate) connection_id: Option<Vec<u8>>,
+
     /// flight_interval controls how often we send outbound handshake messages
     /// defaults to time.Second
     pub(crate) flight_interval: Duration,
@@ -83,11 +96,18 @@ pub(crate) struct Config {
     /// fit within the maximum transmission unit (default is 1200 bytes)
     pub(crate) mtu: usize,
 
+    /// receive_mtu is the largest datagram read from the underlying conn. Zero selects
+    /// INBOUND_BUFFER_SIZE.
+    pub(crate) receive_mtu: usize,
+
     /// replay_protection_window is the size of the replay attack protection window.
     /// Duplication of the sequence number is checked in this window size.
     /// Packet with sequence number older than this value compared to the latest
     /// accepted packet will be discarded. (default is 64)
     pub(crate) replay_protection_window: usize,
+
+    /// spawner runs the read and write loops of the connection
+    pub(crate) spawner: Spawner,
 }
 
 impl Default for Config {
@@ -98,6 +118,9 @@ impl Default for Config {
             srtp_protection_profiles: vec![],
             client_auth: ClientAuthType::default(),
             extended_master_secret: ExtendedMasterSecretType::default(),
+            disallow_renegotiation: false,
+            #[cfg(feature = "dtls-connection-id")]
+            connection_id: None,
             flight_interval: Duration::default(),
             psk: None,
             psk_identity_hint: None,
@@ -107,7 +130,9 @@ impl Default for Config {
             client_cas: rustls::RootCertStore::empty(),
             server_name: String::default(),
             mtu: 0,
+            receive_mtu: 0,
             replay_protection_window: 0,
+            spawner: Spawner::default(),
         }
     }
 }
@@ -20,7 +20,9 @@ use crate::webrtc::dtls::record_layer::*;
 use crate::webrtc::dtls::signature_hash_algorithm::default_signature_schemes;
 use crate::webrtc::dtls::state::*;
 
-use crate::webrtc::util::{replay_detector::*, Conn};
+use crate::webrtc::util::{
+    replay_detector::*, Conn, KeyingMaterialExporter, KeyingMaterialExporterError,
+};
 
 use async_trait::async_trait;
 use log::*;
@@ -62,6 +64,7 @@ struct ConnReaderContext {
     fragment_buffer: FragmentBuffer,
     cache: HandshakeCache,
     cipher_suite: Arc<Mutex<Option<Box<dyn CipherSuite + Send + Sync>>>>,
+    local_connection_id: Arc<Mutex<Vec<u8>>>,
     remote_epoch: Arc<AtomicU16>,
     handshake_tx: mpsc::Sender<mpsc::Sender<()>>,
     handshake_done_rx: mpsc::Receiver<()>,
@@ -108,9 +111,7 @@ type UtilResult<T> = std::result::Result<T, crate::webrtc::util::Error>;
 #[async_trait]
 impl Conn for DTLSConn {
     async fn connect(&self, _addr: SocketAddr) -> UtilResult<()> {
-        Err(crate::webrtc::util::Error::Other(
-            "Not applicable".to_owned(),
-        ))
+        Err(crate::webrtc::util::Error::ErrNotApplicable)
     }
     async fn recv(&self, buf: &mut [u8]) -> UtilResult<usize> {
         self.read(buf, None)
@@ -125,9 +126,7 @@ impl Conn for DTLSConn {
                 .map_err(crate::webrtc::util::Error::from_std)?;
             Ok((n, raddr))
         } else {
-            Err(crate::webrtc::util::Error::Other(
-                "No remote address is provided by underlying Conn".to_owned(),
-            ))
+            Err(crate::webrtc::util::Error::ErrNoRemAddr)
         }
     }
     async fn send(&self, buf: &[u8]) -> UtilResult<usize> {
@@ -136,9 +135,7 @@ impl Conn for DTLSConn {
             .map_err(crate::webrtc::util::Error::from_std)
     }
     async fn send_to(&self, _buf: &[u8], _target: SocketAddr) -> UtilResult<usize> {
-        Err(crate::webrtc::util::Error::Other(
-            "Not applicable".to_owned(),
-        ))
+        Err(crate::webrtc::util::Error::ErrNotApplicable)
     }
     async fn local_addr(&self) -> UtilResult<SocketAddr> {
         self.conn.local_addr().await
@@ -161,6 +158,7 @@ impl DTLSConn {
         initial_state: Option<State>,
     ) -> Result<Self> {
         validate_config(is_client, &config)?;
+        let spawner = config.spawner.clone();
 
         let local_cipher_suites: Vec<CipherSuiteId> = parse_cipher_suites(
             &config.cipher_suites,
@@ -187,6 +185,12 @@ impl DTLSConn {
 
            logger := loggerFactory.NewLogger("dtls")
         */
+        let receive_mtu = if config.receive_mtu == 0 {
+            INBOUND_BUFFER_SIZE
+        } else {
+            config.receive_mtu
+        };
+
         let maximum_transmission_unit = if config.mtu == 0 {
             DEFAULT_MTU
         } else {
@@ -217,6 +221,9 @@ impl DTLSConn {
             local_cipher_suites,
             local_signature_schemes,
             extended_master_secret: config.extended_master_secret,
+            disallow_renegotiation: config.disallow_renegotiation,
+            #[cfg(feature = "dtls-connection-id")]
+            local_connection_id: config.connection_id.take(),
             local_srtp_protection_profiles: config.srtp_protection_profiles.clone(),
             server_name,
             client_auth: config.client_auth,
@@ -300,8 +307,9 @@ impl DTLSConn {
 
         let cipher_suite1 = Arc::clone(&c.state.cipher_suite);
         let sequence_number = Arc::clone(&c.state.local_sequence_number);
+        let remote_connection_id = Arc::clone(&c.state.remote_connection_id);
 
-        tokio::spawn(async move {
+        spawner.spawn(async move {
             loop {
                 let rx = packet_rx.recv().await;
                 if let Some(r) = rx {
@@ -314,6 +322,7 @@ impl DTLSConn {
                         is_client,
                         &sequence_number,
                         &cipher_suite1,
+                        &remote_connection_id,
                         maximum_transmission_unit,
                     )
                     .await;
@@ -331,9 +340,10 @@ impl DTLSConn {
         let local_epoch = Arc::clone(&c.state.local_epoch);
         let remote_epoch = Arc::clone(&c.state.remote_epoch);
         let cipher_suite2 = Arc::clone(&c.state.cipher_suite);
+        let local_connection_id = Arc::clone(&c.state.local_connection_id);
 
-        tokio::spawn(async move {
-            let mut buf = vec![0u8; INBOUND_BUFFER_SIZE];
+        spawner.spawn(async move {
+            let mut buf = vec![0u8; receive_mtu];
             let mut ctx = ConnReaderContext {
                 is_client,
                 replay_protection_window,
@@ -343,6 +353,7 @@ impl DTLSConn {
                 fragment_buffer: FragmentBuffer::new(),
                 cache: cache2,
                 cipher_suite: cipher_suite2,
+                local_connection_id,
                 remote_epoch,
                 handshake_tx,
                 handshake_done_rx,
@@ -519,6 +530,7 @@ impl DTLSConn {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn handle_outgoing_packets(
         next_conn: &Arc<dyn crate::webrtc::util::Conn + Send + Sync>,
         mut pkts: Vec<Packet>,
@@ -526,8 +538,10 @@ impl DTLSConn {
         is_client: bool,
         local_sequence_number: &Arc<Mutex<Vec<u64>>>,
         cipher_suite: &Arc<Mutex<Option<Box<dyn CipherSuite + Send + Sync>>>>,
+        remote_connection_id: &Arc<Mutex<Vec<u8>>>,
         maximum_transmission_unit: usize,
     ) -> Result<()> {
+        let connection_id = remote_connection_id.lock().await.clone();
         let mut raw_packets = vec![];
         for p in &mut pkts {
             if let Content::Handshake(h) = &p.record.content {
@@ -556,6 +570,7 @@ impl DTLSConn {
                 let raw_handshake_packets = DTLSConn::process_handshake_packet(
                     local_sequence_number,
                     cipher_suite,
+                    &connection_id,
                     maximum_transmission_unit,
                     p,
                     h,
@@ -569,8 +584,13 @@ impl DTLSConn {
                     }
                 }*/
 
-                let raw_packet =
-                    DTLSConn::process_packet(local_sequence_number, cipher_suite, p).await?;
+                let raw_packet = DTLSConn::process_packet(
+                    local_sequence_number,
+                    cipher_suite,
+                    &connection_id,
+                    p,
+                )
+                .await?;
                 raw_packets.push(raw_packet);
             }
         }
@@ -590,6 +610,7 @@ impl DTLSConn {
     async fn process_packet(
         local_sequence_number: &Arc<Mutex<Vec<u64>>>,
         cipher_suite: &Arc<Mutex<Option<Box<dyn CipherSuite + Send + Sync>>>>,
+        connection_id: &[u8],
         p: &mut Packet,
     ) -> Result<Vec<u8>> {
         let epoch = p.record.record_layer_header.epoch as usize;
@@ -621,7 +642,12 @@ impl DTLSConn {
         if p.should_encrypt {
             let cipher_suite = cipher_suite.lock().await;
             if let Some(cipher_suite) = &*cipher_suite {
-                raw_packet = cipher_suite.encrypt(&p.record.record_layer_header, &raw_packet)?;
+                raw_packet = DTLSConn::encrypt_record(
+                    cipher_suite.as_ref(),
+                    &p.record.record_layer_header,
+                    connection_id,
+                    &raw_packet,
+                )?;
             }
         }
 
@@ -631,6 +657,7 @@ impl DTLSConn {
     async fn process_handshake_packet(
         local_sequence_number: &Arc<Mutex<Vec<u64>>>,
         cipher_suite: &Arc<Mutex<Option<Box<dyn CipherSuite + Send + Sync>>>>,
+        connection_id: &[u8],
         maximum_transmission_unit: usize,
         p: &Packet,
         h: &Handshake,
@@ -678,7 +705,12 @@ impl DTLSConn {
             if p.should_encrypt {
                 let cipher_suite = cipher_suite.lock().await;
                 if let Some(cipher_suite) = &*cipher_suite {
-                    raw_packet = cipher_suite.encrypt(&record_layer_header, &raw_packet)?;
+                    raw_packet = DTLSConn::encrypt_record(
+                        cipher_suite.as_ref(),
+                        &record_layer_header,
+                        connection_id,
+                        &raw_packet,
+                    )?;
                 }
             }
 
@@ -688,6 +720,66 @@ impl DTLSConn {
         Ok(raw_packets)
     }
 
+    // encrypt_record encrypts raw, a marshalled record. With a connection_id negotiated it is
+    // sent as a ContentType::ConnectionId record whose encrypted DTLSInnerPlaintext ends with
+    // the real content type, RFC 9146 Section 4. No padding is added.
+    fn encrypt_record(
+        cipher_suite: &(dyn CipherSuite + Send + Sync),
+        h: &RecordLayerHeader,
+        connection_id: &[u8],
+        raw: &[u8],
+    ) -> Result<Vec<u8>> {
+        if connection_id.is_empty() || h.epoch == 0 {
+            return cipher_suite.encrypt(h, &[], raw);
+        }
+
+        let content = &raw[RECORD_LAYER_HEADER_SIZE..];
+        let cid_header = RecordLayerHeader {
+            content_type: ContentType::ConnectionId,
+            content_len: (content.len() + 1) as u16,
+            ..*h
+        };
+
+        let mut inner = vec![];
+        {
+            let mut writer = BufWriter::<&mut Vec<u8>>::new(inner.as_mut());
+            cid_header.marshal_with_connection_id(connection_id, &mut writer)?;
+        }
+        inner.extend_from_slice(content);
+        inner.push(h.content_type as u8);
+
+        cipher_suite.encrypt(&cid_header, connection_id, &inner)
+    }
+
+    // decode_inner_plaintext turns a decrypted ContentType::ConnectionId record back into a
+    // record of its real content type, which is the last byte that is not zero padding
+    fn decode_inner_plaintext(
+        h: &RecordLayerHeader,
+        connection_id_len: usize,
+        pkt: &[u8],
+    ) -> Result<Vec<u8>> {
+        let inner = &pkt[RECORD_LAYER_HEADER_SIZE + connection_id_len..];
+        let end = match inner.iter().rposition(|&b| b != 0) {
+            Some(end) => end,
+            None => return Err(Error::ErrInvalidContentType),
+        };
+
+        let record_layer_header = RecordLayerHeader {
+            content_type: inner[end].into(),
+            content_len: end as u16,
+            ..*h
+        };
+
+        let mut out = vec![];
+        {
+            let mut writer = BufWriter::<&mut Vec<u8>>::new(out.as_mut());
+            record_layer_header.marshal(&mut writer)?;
+        }
+        out.extend_from_slice(&inner[..end]);
+
+        Ok(out)
+    }
+
     fn fragment_handshake(maximum_transmission_unit: usize, h: &Handshake) -> Result<Vec<Vec<u8>>> {
         let mut content = vec![];
         {
@@ -742,6 +834,23 @@ impl DTLSConn {
         self.handshake_completed_successfully.load(Ordering::SeqCst)
     }
 
+    /// export_keying_material derives length bytes from the master secret for label and
+    /// context, RFC 5705. It fails until the handshake completed successfully.
+    pub(crate) async fn export_keying_material(
+        &self,
+        label: &str,
+        context: &[u8],
+        length: usize,
+    ) -> Result<Vec<u8>> {
+        if !self.is_handshake_completed_successfully() {
+            return Err(KeyingMaterialExporterError::HandshakeInProgress.into());
+        }
+        Ok(self
+            .state
+            .export_keying_material(label, context, length)
+            .await?)
+    }
+
     async fn read_and_buffer(
         ctx: &mut ConnReaderContext,
         next_conn: &Arc<dyn crate::webrtc::util::Conn + Send + Sync>,
@@ -751,7 +860,8 @@ impl DTLSConn {
         handshake_completed_successfully: &Arc<AtomicBool>,
     ) -> Result<()> {
         let n = next_conn.recv(buf).await?;
-        let pkts = unpack_datagram(&buf[..n])?;
+        let connection_id_len = ctx.local_connection_id.lock().await.len();
+        let pkts = unpack_datagram(&buf[..n], connection_id_len)?;
         let mut has_handshake = false;
         for pkt in pkts {
             let (hs, alert, mut err) = DTLSConn::handle_incoming_packet(ctx, pkt, true).await;
@@ -879,8 +989,12 @@ impl DTLSConn {
         mut pkt: Vec<u8>,
         enqueue: bool,
     ) -> (bool, Option<Alert>, Option<Error>) {
+        let local_connection_id = ctx.local_connection_id.lock().await.clone();
         let mut reader = BufReader::new(pkt.as_slice());
-        let h = match RecordLayerHeader::unmarshal(&mut reader) {
+        let (h, connection_id) = match RecordLayerHeader::unmarshal_with_connection_id(
+            &mut reader,
+            local_connection_id.len(),
+        ) {
             Ok(h) => h,
             Err(err) => {
                 // Decode error must be silently discarded
@@ -894,6 +1008,22 @@ impl DTLSConn {
             }
         };
 
+        // Records are matched to the connection by their connection id, not by the address
+        // they came from, once one is negotiated [RFC9146 Section 6]
+        if h.content_type == ContentType::ConnectionId
+            && (local_connection_id.is_empty()
+                || connection_id != local_connection_id
+                || h.epoch == 0)
+        {
+            debug!(
+                "{}: discarded record with unknown connection id (epoch: {}, seq: {})",
+                srv_cli_str(ctx.is_client),
+                h.epoch,
+                h.sequence_number,
+            );
+            return (false, None, None);
+        }
+
         // Validate epoch
         let epoch = ctx.remote_epoch.load(Ordering::SeqCst);
         if h.epoch > epoch {
@@ -961,7 +1091,17 @@ impl DTLSConn {
 
             let cipher_suite = ctx.cipher_suite.lock().await;
             if let Some(cipher_suite) = &*cipher_suite {
-                pkt = match cipher_suite.decrypt(&pkt) {
+                pkt = match cipher_suite.decrypt(&connection_id, &pkt) {
+                    Ok(pkt) => pkt,
+                    Err(err) => {
+                        debug!("{}: decrypt failed: {}", srv_cli_str(ctx.is_client), err);
+                        return (false, None, None);
+                    }
+                };
+            }
+
+            if h.content_type == ContentType::ConnectionId {
+                pkt = match DTLSConn::decode_inner_plaintext(&h, connection_id.len(), &pkt) {
                     Ok(pkt) => pkt,
                     Err(err) => {
                         debug!("{}: decrypt failed: {}", srv_cli_str(ctx.is_client), err);
@@ -13,6 +13,8 @@ pub(crate) enum ContentType {
     Alert = 21,
     Handshake = 22,
     ApplicationData = 23,
+    // tls12_cid carries a connection id and hides the real content type, RFC 9146
+    ConnectionId = 25,
     Invalid,
 }
 
@@ -23,6 +25,7 @@ impl From<u8> for ContentType {
             21 => ContentType::Alert,
             22 => ContentType::Handshake,
             23 => ContentType::ApplicationData,
+            25 => ContentType::ConnectionId,
             _ => ContentType::Invalid,
         }
     }
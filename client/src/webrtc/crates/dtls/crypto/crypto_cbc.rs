@@ -11,6 +11,7 @@
 use std::io::Cursor;
 use std::ops::Not;
 
+use super::*;
 use crate::webrtc::dtls::content::*;
 use crate::webrtc::dtls::error::*;
 use crate::webrtc::dtls::prf::*;
@@ -53,18 +54,19 @@ impl CryptoCbc {
         })
     }
 
-    pub(crate) fn encrypt(&self, pkt_rlh: &RecordLayerHeader, raw: &[u8]) -> Result<Vec<u8>> {
-        let mut payload = raw[RECORD_LAYER_HEADER_SIZE..].to_vec();
-        let raw = &raw[..RECORD_LAYER_HEADER_SIZE];
+    pub(crate) fn encrypt(
+        &self,
+        pkt_rlh: &RecordLayerHeader,
+        connection_id: &[u8],
+        raw: &[u8],
+    ) -> Result<Vec<u8>> {
+        let header_size = RECORD_LAYER_HEADER_SIZE + connection_id.len();
+        let mut payload = raw[header_size..].to_vec();
+        let raw = &raw[..header_size];
 
         // Generate + Append MAC
-        let h = pkt_rlh;
-
         let mac = prf_mac(
-            h.epoch,
-            h.sequence_number,
-            h.content_type,
-            h.protocol_version,
+            &generate_aead_additional_data(pkt_rlh, connection_id, payload.len()),
             &payload,
             &self.write_mac,
         )?;
@@ -82,22 +84,23 @@ impl CryptoCbc {
         r.extend_from_slice(&iv);
         r.extend_from_slice(&encrypted);
 
-        let r_len = (r.len() - RECORD_LAYER_HEADER_SIZE) as u16;
-        r[RECORD_LAYER_HEADER_SIZE - 2..RECORD_LAYER_HEADER_SIZE]
-            .copy_from_slice(&r_len.to_be_bytes());
+        let r_len = (r.len() - header_size) as u16;
+        r[header_size - 2..header_size].copy_from_slice(&r_len.to_be_bytes());
 
         Ok(r)
     }
 
-    pub(crate) fn decrypt(&self, r: &[u8]) -> Result<Vec<u8>> {
+    pub(crate) fn decrypt(&self, connection_id: &[u8], r: &[u8]) -> Result<Vec<u8>> {
         let mut reader = Cursor::new(r);
-        let h = RecordLayerHeader::unmarshal(&mut reader)?;
+        let (h, _) =
+            RecordLayerHeader::unmarshal_with_connection_id(&mut reader, connection_id.len())?;
         if h.content_type == ContentType::ChangeCipherSpec {
             // Nothing to encrypt with ChangeCipherSpec
             return Ok(r.to_vec());
         }
+        let header_size = RECORD_LAYER_HEADER_SIZE + connection_id.len();
 
-        let body = &r[RECORD_LAYER_HEADER_SIZE..];
+        let body = &r[header_size..];
         let iv = &body[0..Self::BLOCK_SIZE];
         let body = &body[Self::BLOCK_SIZE..];
         //TODO: add body.len() check
@@ -109,10 +112,7 @@ impl CryptoCbc {
         let recv_mac = &decrypted[decrypted.len() - Self::MAC_SIZE..];
         let decrypted = &decrypted[0..decrypted.len() - Self::MAC_SIZE];
         let mac = prf_mac(
-            h.epoch,
-            h.sequence_number,
-            h.content_type,
-            h.protocol_version,
+            &generate_aead_additional_data(&h, connection_id, decrypted.len()),
             decrypted,
             &self.read_mac,
         )?;
@@ -121,8 +121,8 @@ impl CryptoCbc {
             return Err(BlockModeError.into());
         }
 
-        let mut d = Vec::with_capacity(RECORD_LAYER_HEADER_SIZE + decrypted.len());
-        d.extend_from_slice(&r[..RECORD_LAYER_HEADER_SIZE]);
+        let mut d = Vec::with_capacity(header_size + decrypted.len());
+        d.extend_from_slice(&r[..header_size]);
         d.extend_from_slice(decrypted);
 
         Ok(d)
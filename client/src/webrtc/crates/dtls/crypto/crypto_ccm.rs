@@ -104,16 +104,22 @@ impl CryptoCcm {
         }
     }
 
-    pub(crate) fn encrypt(&self, pkt_rlh: &RecordLayerHeader, raw: &[u8]) -> Result<Vec<u8>> {
-        let payload = &raw[RECORD_LAYER_HEADER_SIZE..];
-        let raw = &raw[..RECORD_LAYER_HEADER_SIZE];
+    pub(crate) fn encrypt(
+        &self,
+        pkt_rlh: &RecordLayerHeader,
+        connection_id: &[u8],
+        raw: &[u8],
+    ) -> Result<Vec<u8>> {
+        let header_size = RECORD_LAYER_HEADER_SIZE + connection_id.len();
+        let payload = &raw[header_size..];
+        let raw = &raw[..header_size];
 
         let mut nonce = vec![0u8; CRYPTO_CCM_NONCE_LENGTH];
         nonce[..4].copy_from_slice(&self.local_write_iv[..4]);
         rand::thread_rng().fill(&mut nonce[4..]);
         let nonce = GenericArray::from_slice(&nonce);
 
-        let additional_data = generate_aead_additional_data(pkt_rlh, payload.len());
+        let additional_data = generate_aead_additional_data(pkt_rlh, connection_id, payload.len());
 
         let mut buffer: Vec<u8> = Vec::new();
         buffer.extend_from_slice(payload);
@@ -136,52 +142,59 @@ impl CryptoCcm {
         r.extend_from_slice(&buffer);
 
         // Update recordLayer size to include explicit nonce
-        let r_len = (r.len() - RECORD_LAYER_HEADER_SIZE) as u16;
-        r[RECORD_LAYER_HEADER_SIZE - 2..RECORD_LAYER_HEADER_SIZE]
-            .copy_from_slice(&r_len.to_be_bytes());
+        let r_len = (r.len() - header_size) as u16;
+        r[header_size - 2..header_size].copy_from_slice(&r_len.to_be_bytes());
 
         Ok(r)
     }
 
-    pub(crate) fn decrypt(&self, r: &[u8]) -> Result<Vec<u8>> {
+    pub(crate) fn decrypt(&self, connection_id: &[u8], r: &[u8]) -> Result<Vec<u8>> {
         let mut reader = Cursor::new(r);
-        let h = RecordLayerHeader::unmarshal(&mut reader)?;
+        let (h, _) =
+            RecordLayerHeader::unmarshal_with_connection_id(&mut reader, connection_id.len())?;
         if h.content_type == ContentType::ChangeCipherSpec {
             // Nothing to encrypt with ChangeCipherSpec
             return Ok(r.to_vec());
         }
+        let header_size = RECORD_LAYER_HEADER_SIZE + connection_id.len();
 
-        if r.len() <= (RECORD_LAYER_HEADER_SIZE + 8) {
+        if r.len() <= (header_size + 8) {
             return Err(Error::ErrNotEnoughRoomForNonce);
         }
 
         let mut nonce = vec![];
         nonce.extend_from_slice(&self.remote_write_iv[..4]);
-        nonce.extend_from_slice(&r[RECORD_LAYER_HEADER_SIZE..RECORD_LAYER_HEADER_SIZE + 8]);
+        nonce.extend_from_slice(&r[header_size..header_size + 8]);
         let nonce = GenericArray::from_slice(&nonce);
 
-        let out = &r[RECORD_LAYER_HEADER_SIZE + 8..];
+        let out = &r[header_size + 8..];
 
         let mut buffer: Vec<u8> = Vec::new();
         buffer.extend_from_slice(out);
 
         match &self.remote_ccm {
             CryptoCcmType::CryptoCcm(ccm) => {
-                let additional_data =
-                    generate_aead_additional_data(&h, out.len() - CRYPTO_CCM_TAG_LENGTH);
+                let additional_data = generate_aead_additional_data(
+                    &h,
+                    connection_id,
+                    out.len() - CRYPTO_CCM_TAG_LENGTH,
+                );
                 ccm.decrypt_in_place(nonce, &additional_data, &mut buffer)
                     .map_err(|e| Error::Other(e.to_string()))?;
             }
             CryptoCcmType::CryptoCcm8(ccm8) => {
-                let additional_data =
-                    generate_aead_additional_data(&h, out.len() - CRYPTO_CCM_8_TAG_LENGTH);
+                let additional_data = generate_aead_additional_data(
+                    &h,
+                    connection_id,
+                    out.len() - CRYPTO_CCM_8_TAG_LENGTH,
+                );
                 ccm8.decrypt_in_place(nonce, &additional_data, &mut buffer)
                     .map_err(|e| Error::Other(e.to_string()))?;
             }
         }
 
-        let mut d = Vec::with_capacity(RECORD_LAYER_HEADER_SIZE + buffer.len());
-        d.extend_from_slice(&r[..RECORD_LAYER_HEADER_SIZE]);
+        let mut d = Vec::with_capacity(header_size + buffer.len());
+        d.extend_from_slice(&r[..header_size]);
         d.extend_from_slice(&buffer);
 
         Ok(d)
@@ -51,16 +51,22 @@ impl CryptoGcm {
         }
     }
 
-    pub(crate) fn encrypt(&self, pkt_rlh: &RecordLayerHeader, raw: &[u8]) -> Result<Vec<u8>> {
-        let payload = &raw[RECORD_LAYER_HEADER_SIZE..];
-        let raw = &raw[..RECORD_LAYER_HEADER_SIZE];
+    pub(crate) fn encrypt(
+        &self,
+        pkt_rlh: &RecordLayerHeader,
+        connection_id: &[u8],
+        raw: &[u8],
+    ) -> Result<Vec<u8>> {
+        let header_size = RECORD_LAYER_HEADER_SIZE + connection_id.len();
+        let payload = &raw[header_size..];
+        let raw = &raw[..header_size];
 
         let mut nonce = vec![0u8; CRYPTO_GCM_NONCE_LENGTH];
         nonce[..4].copy_from_slice(&self.local_write_iv[..4]);
         rand::thread_rng().fill(&mut nonce[4..]);
         let nonce = GenericArray::from_slice(&nonce);
 
-        let additional_data = generate_aead_additional_data(pkt_rlh, payload.len());
+        let additional_data = generate_aead_additional_data(pkt_rlh, connection_id, payload.len());
 
         let mut buffer: Vec<u8> = Vec::new();
         buffer.extend_from_slice(payload);
@@ -75,33 +81,35 @@ impl CryptoGcm {
         r.extend_from_slice(&buffer);
 
         // Update recordLayer size to include explicit nonce
-        let r_len = (r.len() - RECORD_LAYER_HEADER_SIZE) as u16;
-        r[RECORD_LAYER_HEADER_SIZE - 2..RECORD_LAYER_HEADER_SIZE]
-            .copy_from_slice(&r_len.to_be_bytes());
+        let r_len = (r.len() - header_size) as u16;
+        r[header_size - 2..header_size].copy_from_slice(&r_len.to_be_bytes());
 
         Ok(r)
     }
 
-    pub(crate) fn decrypt(&self, r: &[u8]) -> Result<Vec<u8>> {
+    pub(crate) fn decrypt(&self, connection_id: &[u8], r: &[u8]) -> Result<Vec<u8>> {
         let mut reader = Cursor::new(r);
-        let h = RecordLayerHeader::unmarshal(&mut reader)?;
+        let (h, _) =
+            RecordLayerHeader::unmarshal_with_connection_id(&mut reader, connection_id.len())?;
         if h.content_type == ContentType::ChangeCipherSpec {
             // Nothing to encrypt with ChangeCipherSpec
             return Ok(r.to_vec());
         }
+        let header_size = RECORD_LAYER_HEADER_SIZE + connection_id.len();
 
-        if r.len() <= (RECORD_LAYER_HEADER_SIZE + 8) {
+        if r.len() <= (header_size + 8) {
             return Err(Error::ErrNotEnoughRoomForNonce);
         }
 
         let mut nonce = vec![];
         nonce.extend_from_slice(&self.remote_write_iv[..4]);
-        nonce.extend_from_slice(&r[RECORD_LAYER_HEADER_SIZE..RECORD_LAYER_HEADER_SIZE + 8]);
+        nonce.extend_from_slice(&r[header_size..header_size + 8]);
         let nonce = GenericArray::from_slice(&nonce);
 
-        let out = &r[RECORD_LAYER_HEADER_SIZE + 8..];
+        let out = &r[header_size + 8..];
 
-        let additional_data = generate_aead_additional_data(&h, out.len() - CRYPTO_GCM_TAG_LENGTH);
+        let additional_data =
+            generate_aead_additional_data(&h, connection_id, out.len() - CRYPTO_GCM_TAG_LENGTH);
 
         let mut buffer: Vec<u8> = Vec::new();
         buffer.extend_from_slice(out);
@@ -110,8 +118,8 @@ impl CryptoGcm {
             .decrypt_in_place(nonce, &additional_data, &mut buffer)
             .map_err(|e| Error::Other(e.to_string()))?;
 
-        let mut d = Vec::with_capacity(RECORD_LAYER_HEADER_SIZE + buffer.len());
-        d.extend_from_slice(&r[..RECORD_LAYER_HEADER_SIZE]);
+        let mut d = Vec::with_capacity(header_size + buffer.len());
+        d.extend_from_slice(&r[..header_size]);
         d.extend_from_slice(&buffer);
 
         Ok(d)
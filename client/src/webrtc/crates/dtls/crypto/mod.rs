@@ -3,6 +3,7 @@ pub(crate) mod crypto_ccm;
 pub(crate) mod crypto_gcm;
 pub(crate) mod padding;
 
+use crate::webrtc::dtls::content::ContentType;
 use crate::webrtc::dtls::curve::named_curve::*;
 use crate::webrtc::dtls::error::*;
 use crate::webrtc::dtls::record_layer::record_layer_header::*;
@@ -311,7 +312,28 @@ pub(crate) fn verify_server_cert(
     Ok(chains)
 }
 
-pub(crate) fn generate_aead_additional_data(h: &RecordLayerHeader, payload_len: usize) -> Vec<u8> {
+// generate_aead_additional_data is also the MAC header of CBC. A ContentType::ConnectionId
+// record authenticates its connection_id in the layout of RFC 9146 Section 5.
+pub(crate) fn generate_aead_additional_data(
+    h: &RecordLayerHeader,
+    connection_id: &[u8],
+    payload_len: usize,
+) -> Vec<u8> {
+    if h.content_type == ContentType::ConnectionId {
+        let mut additional_data = Vec::with_capacity(23 + connection_id.len());
+        additional_data.extend_from_slice(&[0xff; 8]); // seq_num_placeholder
+        additional_data.push(ContentType::ConnectionId as u8);
+        additional_data.push(connection_id.len() as u8);
+        additional_data.push(ContentType::ConnectionId as u8);
+        additional_data.push(h.protocol_version.major);
+        additional_data.push(h.protocol_version.minor);
+        additional_data.extend_from_slice(&h.epoch.to_be_bytes());
+        additional_data.extend_from_slice(&h.sequence_number.to_be_bytes()[2..]);
+        additional_data.extend_from_slice(connection_id);
+        additional_data.extend_from_slice(&(payload_len as u16).to_be_bytes());
+        return additional_data;
+    }
+
     let mut additional_data = vec![0u8; 13];
     // SequenceNumber MUST be set first
     // we only want uint48, clobbering an extra 2 (using uint64, rust doesn't have uint48)
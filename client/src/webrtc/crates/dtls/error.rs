@@ -67,7 +67,7 @@ pub(crate) enum Error {
     ErrNoCertificates,
     #[error("client requested zero or more elliptic curves that are not supported by the server")]
     ErrNoSupportedEllipticCurves,
-    #[error("unsupported protocol version")]
+    #[error("unsupported protocol version, DTLS 1.2 is required")]
     ErrUnsupportedProtocolVersion,
     #[error("Certificate and PSK provided")]
     ErrPskAndCertificate,
@@ -83,6 +83,10 @@ pub(crate) enum Error {
         "server requires the Extended Master Secret extension, but the client does not support it"
     )]
     ErrServerRequiredButNoClientEms,
+    #[error("peer attempted a renegotiation, but renegotiation is disallowed")]
+    ErrRenegotiationDisallowed,
+    #[error("peer signed with the insecure hash algorithm {0}")]
+    ErrInsecureSignatureHash(String),
     #[error("expected and actual verify data does not match")]
     ErrVerifyDataMismatch,
     #[error("unable to verify key signature, unimplemented")]
@@ -118,6 +122,9 @@ pub(crate) enum Error {
     MpscSend(String),
     #[error("keying material: {0}")]
     KeyingMaterial(#[from] KeyingMaterialExporterError),
+    #[cfg(feature = "dtls-openssl")]
+    #[error("openssl: {0}")]
+    OpenSsl(OpenSslError),
 
     #[allow(non_camel_case_types)]
     #[error("{0}")]
@@ -157,6 +164,23 @@ impl From<p256::elliptic_curve::Error> for Error {
     }
 }
 
+/// OpenSslError is what the OpenSSL DTLS backend failed with
+#[cfg(feature = "dtls-openssl")]
+#[derive(Debug, Error)]
+pub(crate) enum OpenSslError {
+    #[error("{0}")]
+    Stack(#[from] openssl::error::ErrorStack),
+    #[error("{0}")]
+    Ssl(#[from] openssl::ssl::Error),
+}
+
+#[cfg(feature = "dtls-openssl")]
+impl PartialEq for OpenSslError {
+    fn eq(&self, _: &Self) -> bool {
+        false
+    }
+}
+
 impl From<block_modes::InvalidKeyIvLength> for Error {
     fn from(e: block_modes::InvalidKeyIvLength) -> Self {
         Error::Other(e.to_string())
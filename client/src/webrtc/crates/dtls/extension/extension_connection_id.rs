@@ -0,0 +1,44 @@
+use super::*;
+use crate::webrtc::dtls::error::Error::ErrInvalidPacketLength;
+
+/// ConnectionId carries the connection id a peer wants to find on the records sent to it,
+/// an empty one if it only sends them
+/// https://tools.ietf.org/html/rfc9146#section-3
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ExtensionConnectionId {
+    pub(crate) cid: Vec<u8>,
+}
+
+impl ExtensionConnectionId {
+    pub(crate) fn extension_value(&self) -> ExtensionValue {
+        ExtensionValue::ConnectionId
+    }
+
+    pub(crate) fn size(&self) -> usize {
+        2 + 1 + self.cid.len()
+    }
+
+    pub(crate) fn marshal<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_u16::<BigEndian>(1 + self.cid.len() as u16)?;
+        writer.write_u8(self.cid.len() as u8)?;
+        writer.write_all(&self.cid)?;
+
+        Ok(writer.flush()?)
+    }
+
+    pub(crate) fn unmarshal<R: Read>(reader: &mut R) -> Result<Self> {
+        let l = reader.read_u16::<BigEndian>()?; //length
+        if l == 0 {
+            return Err(ErrInvalidPacketLength);
+        }
+
+        let cid_len = reader.read_u8()?;
+        if l != 1 + cid_len as u16 {
+            return Err(ErrInvalidPacketLength);
+        }
+        let mut cid = vec![0u8; cid_len as usize];
+        reader.read_exact(&mut cid)?;
+
+        Ok(ExtensionConnectionId { cid })
+    }
+}
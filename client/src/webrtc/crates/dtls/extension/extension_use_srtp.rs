@@ -1,5 +1,7 @@
 use super::*;
 
+use std::fmt;
+
 // SRTPProtectionProfile defines the parameters and options that are in effect for the SRTP processing
 // https://tools.ietf.org/html/rfc5764#section-4.1.2
 #[allow(non_camel_case_types)]
@@ -24,6 +26,22 @@ impl From<u16> for SrtpProtectionProfile {
     }
 }
 
+impl fmt::Display for SrtpProtectionProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            SrtpProtectionProfile::Srtp_Aes128_Cm_Hmac_Sha1_80 => {
+                write!(f, "SRTP_AES128_CM_HMAC_SHA1_80")
+            }
+            SrtpProtectionProfile::Srtp_Aes128_Cm_Hmac_Sha1_32 => {
+                write!(f, "SRTP_AES128_CM_HMAC_SHA1_32")
+            }
+            SrtpProtectionProfile::Srtp_Aead_Aes_128_Gcm => write!(f, "SRTP_AEAD_AES_128_GCM"),
+            SrtpProtectionProfile::Srtp_Aead_Aes_256_Gcm => write!(f, "SRTP_AEAD_AES_256_GCM"),
+            SrtpProtectionProfile::Unsupported => write!(f, "Unsupported"),
+        }
+    }
+}
+
 // https://tools.ietf.org/html/rfc8422
 #[allow(non_camel_case_types)]
 #[derive(Clone, Debug, PartialEq)]
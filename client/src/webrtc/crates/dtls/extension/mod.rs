@@ -1,3 +1,4 @@
+pub(crate) mod extension_connection_id;
 pub(crate) mod extension_server_name;
 pub(crate) mod extension_supported_elliptic_curves;
 pub(crate) mod extension_supported_point_formats;
@@ -6,6 +7,7 @@ pub(crate) mod extension_use_extended_master_secret;
 pub(crate) mod extension_use_srtp;
 pub(crate) mod renegotiation_info;
 
+use extension_connection_id::*;
 use extension_server_name::*;
 use extension_supported_elliptic_curves::*;
 use extension_supported_point_formats::*;
@@ -28,6 +30,7 @@ pub(crate) enum ExtensionValue {
     SupportedSignatureAlgorithms = 13,
     UseSrtp = 14,
     UseExtendedMasterSecret = 23,
+    ConnectionId = 54,
     RenegotiationInfo = 65281,
     Unsupported,
 }
@@ -41,6 +44,7 @@ impl From<u16> for ExtensionValue {
             13 => ExtensionValue::SupportedSignatureAlgorithms,
             14 => ExtensionValue::UseSrtp,
             23 => ExtensionValue::UseExtendedMasterSecret,
+            54 => ExtensionValue::ConnectionId,
             65281 => ExtensionValue::RenegotiationInfo,
             _ => ExtensionValue::Unsupported,
         }
@@ -55,6 +59,7 @@ pub(crate) enum Extension {
     SupportedSignatureAlgorithms(ExtensionSupportedSignatureAlgorithms),
     UseSrtp(ExtensionUseSrtp),
     UseExtendedMasterSecret(ExtensionUseExtendedMasterSecret),
+    ConnectionId(ExtensionConnectionId),
     RenegotiationInfo(ExtensionRenegotiationInfo),
 }
 
@@ -67,6 +72,7 @@ impl Extension {
             Extension::SupportedSignatureAlgorithms(ext) => ext.extension_value(),
             Extension::UseSrtp(ext) => ext.extension_value(),
             Extension::UseExtendedMasterSecret(ext) => ext.extension_value(),
+            Extension::ConnectionId(ext) => ext.extension_value(),
             Extension::RenegotiationInfo(ext) => ext.extension_value(),
         }
     }
@@ -81,6 +87,7 @@ impl Extension {
             Extension::SupportedSignatureAlgorithms(ext) => ext.size(),
             Extension::UseSrtp(ext) => ext.size(),
             Extension::UseExtendedMasterSecret(ext) => ext.size(),
+            Extension::ConnectionId(ext) => ext.size(),
             Extension::RenegotiationInfo(ext) => ext.size(),
         };
 
@@ -96,6 +103,7 @@ impl Extension {
             Extension::SupportedSignatureAlgorithms(ext) => ext.marshal(writer),
             Extension::UseSrtp(ext) => ext.marshal(writer),
             Extension::UseExtendedMasterSecret(ext) => ext.marshal(writer),
+            Extension::ConnectionId(ext) => ext.marshal(writer),
             Extension::RenegotiationInfo(ext) => ext.marshal(writer),
         }
     }
@@ -121,6 +129,9 @@ impl Extension {
             ExtensionValue::UseExtendedMasterSecret => Ok(Extension::UseExtendedMasterSecret(
                 ExtensionUseExtendedMasterSecret::unmarshal(reader)?,
             )),
+            ExtensionValue::ConnectionId => Ok(Extension::ConnectionId(
+                ExtensionConnectionId::unmarshal(reader)?,
+            )),
             ExtensionValue::RenegotiationInfo => Ok(Extension::RenegotiationInfo(
                 ExtensionRenegotiationInfo::unmarshal(reader)?,
             )),
@@ -6,6 +6,8 @@ use crate::webrtc::dtls::error::Error::ErrInvalidPacketLength;
 /// https://tools.ietf.org/html/rfc5746
 #[derive(Clone, Debug, PartialEq)]
 pub(crate) struct ExtensionRenegotiationInfo {
+    /// renegotiated_connection is the length of the verify data of the connection being
+    /// renegotiated, zero on an initial handshake. Only zero is ever sent.
     pub(crate) renegotiated_connection: u8,
 }
 
@@ -30,11 +32,17 @@ impl ExtensionRenegotiationInfo {
     /// Unmarshal populates the extension from encoded data
     pub(crate) fn unmarshal<R: Read>(reader: &mut R) -> Result<Self> {
         let l = reader.read_u16::<BigEndian>()?; //length
-        if l != 1 {
+        if l == 0 {
             return Err(ErrInvalidPacketLength);
         }
 
         let renegotiated_connection = reader.read_u8()?;
+        if l != 1 + renegotiated_connection as u16 {
+            return Err(ErrInvalidPacketLength);
+        }
+        // the verify data only matters to a renegotiation, which is never accepted
+        let mut verify_data = vec![0u8; renegotiated_connection as usize];
+        reader.read_exact(&mut verify_data)?;
 
         Ok(ExtensionRenegotiationInfo {
             renegotiated_connection,
@@ -133,9 +133,27 @@ impl Flight for Flight0 {
                             state.extended_master_secret = true;
                         }
                     }
+                    Extension::ConnectionId(e) => {
+                        if let Some(connection_id) = &cfg.local_connection_id {
+                            state.connection_id_negotiated = true;
+                            *state.local_connection_id.lock().await = connection_id.clone();
+                            *state.remote_connection_id.lock().await = e.cid.clone();
+                        }
+                    }
                     Extension::ServerName(e) => {
                         state.server_name = e.server_name.clone(); // remote server name
                     }
+                    Extension::RenegotiationInfo(e)
+                        if cfg.disallow_renegotiation && e.renegotiated_connection != 0 =>
+                    {
+                        return Err((
+                            Some(Alert {
+                                alert_level: AlertLevel::Fatal,
+                                alert_description: AlertDescription::HandshakeFailure,
+                            }),
+                            Some(Error::ErrRenegotiationDisallowed),
+                        ));
+                    }
                     _ => {}
                 }
             }
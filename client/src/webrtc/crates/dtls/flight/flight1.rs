@@ -6,6 +6,7 @@ use crate::webrtc::dtls::conn::*;
 use crate::webrtc::dtls::content::*;
 use crate::webrtc::dtls::curve::named_curve::*;
 use crate::webrtc::dtls::error::Error;
+use crate::webrtc::dtls::extension::extension_connection_id::*;
 use crate::webrtc::dtls::extension::extension_server_name::*;
 use crate::webrtc::dtls::extension::extension_supported_elliptic_curves::*;
 use crate::webrtc::dtls::extension::extension_supported_point_formats::*;
@@ -163,6 +164,12 @@ impl Flight for Flight1 {
             ));
         }
 
+        if let Some(connection_id) = &cfg.local_connection_id {
+            extensions.push(Extension::ConnectionId(ExtensionConnectionId {
+                cid: connection_id.clone(),
+            }));
+        }
+
         if !cfg.server_name.is_empty() {
             extensions.push(Extension::ServerName(ExtensionServerName {
                 server_name: cfg.server_name.clone(),
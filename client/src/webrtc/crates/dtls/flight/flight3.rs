@@ -5,6 +5,7 @@ use crate::webrtc::dtls::config::*;
 use crate::webrtc::dtls::content::*;
 use crate::webrtc::dtls::curve::named_curve::*;
 use crate::webrtc::dtls::error::Error;
+use crate::webrtc::dtls::extension::extension_connection_id::*;
 use crate::webrtc::dtls::extension::extension_server_name::*;
 use crate::webrtc::dtls::extension::extension_supported_elliptic_curves::*;
 use crate::webrtc::dtls::extension::extension_supported_point_formats::*;
@@ -216,6 +217,24 @@ impl Flight for Flight3 {
                             state.extended_master_secret = true;
                         }
                     }
+                    Extension::ConnectionId(e) => {
+                        if let Some(connection_id) = &cfg.local_connection_id {
+                            state.connection_id_negotiated = true;
+                            *state.local_connection_id.lock().await = connection_id.clone();
+                            *state.remote_connection_id.lock().await = e.cid.clone();
+                        }
+                    }
+                    Extension::RenegotiationInfo(e)
+                        if cfg.disallow_renegotiation && e.renegotiated_connection != 0 =>
+                    {
+                        return Err((
+                            Some(Alert {
+                                alert_level: AlertLevel::Fatal,
+                                alert_description: AlertDescription::HandshakeFailure,
+                            }),
+                            Some(Error::ErrRenegotiationDisallowed),
+                        ));
+                    }
                     _ => {}
                 };
             }
@@ -383,6 +402,12 @@ impl Flight for Flight3 {
             ));
         }
 
+        if let Some(connection_id) = &cfg.local_connection_id {
+            extensions.push(Extension::ConnectionId(ExtensionConnectionId {
+                cid: connection_id.clone(),
+            }));
+        }
+
         if !cfg.server_name.is_empty() {
             extensions.push(Extension::ServerName(ExtensionServerName {
                 server_name: cfg.server_name.clone(),
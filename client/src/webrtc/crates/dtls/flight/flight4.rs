@@ -9,6 +9,7 @@ use crate::webrtc::dtls::crypto::*;
 use crate::webrtc::dtls::curve::named_curve::*;
 use crate::webrtc::dtls::curve::*;
 use crate::webrtc::dtls::error::Error;
+use crate::webrtc::dtls::extension::extension_connection_id::*;
 use crate::webrtc::dtls::extension::extension_supported_elliptic_curves::*;
 use crate::webrtc::dtls::extension::extension_supported_point_formats::*;
 use crate::webrtc::dtls::extension::extension_use_extended_master_secret::*;
@@ -191,6 +192,18 @@ impl Flight for Flight4 {
                 ])
                 .await;
 
+            if h.algorithm.hash.insecure() {
+                return Err((
+                    Some(Alert {
+                        alert_level: AlertLevel::Fatal,
+                        alert_description: AlertDescription::InsufficientSecurity,
+                    }),
+                    Some(Error::ErrInsecureSignatureHash(
+                        h.algorithm.hash.to_string(),
+                    )),
+                ));
+            }
+
             // Verify that the pair of hash algorithm and signature is listed.
             let mut valid_signature_scheme = false;
             for ss in &cfg.local_signature_schemes {
@@ -487,6 +500,12 @@ impl Flight for Flight4 {
             ));
         }
 
+        if state.connection_id_negotiated {
+            extensions.push(Extension::ConnectionId(ExtensionConnectionId {
+                cid: state.local_connection_id.lock().await.clone(),
+            }));
+        }
+
         if state.srtp_protection_profile != SrtpProtectionProfile::Unsupported {
             extensions.push(Extension::UseSrtp(ExtensionUseSrtp {
                 protection_profiles: vec![state.srtp_protection_profile],
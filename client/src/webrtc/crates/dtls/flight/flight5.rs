@@ -679,6 +679,18 @@ async fn initalize_cipher_suite(
     }
 
     if cfg.local_psk_callback.is_none() {
+        if h.algorithm.hash.insecure() {
+            return Err((
+                Some(Alert {
+                    alert_level: AlertLevel::Fatal,
+                    alert_description: AlertDescription::InsufficientSecurity,
+                }),
+                Some(Error::ErrInsecureSignatureHash(
+                    h.algorithm.hash.to_string(),
+                )),
+            ));
+        }
+
         // Verify that the pair of hash algorithm and signiture is listed.
         let mut valid_signature_scheme = false;
         for ss in &cfg.local_signature_schemes {
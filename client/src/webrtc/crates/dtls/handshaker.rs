@@ -77,6 +77,8 @@ pub(crate) struct HandshakeConfig {
     pub(crate) local_cipher_suites: Vec<CipherSuiteId>, // Available CipherSuites
     pub(crate) local_signature_schemes: Vec<SignatureHashAlgorithm>, // Available signature schemes
     pub(crate) extended_master_secret: ExtendedMasterSecretType, // Policy for the Extended Master Support extension
+    pub(crate) disallow_renegotiation: bool, // Fail on a renegotiation_info that is not empty
+    pub(crate) local_connection_id: Option<Vec<u8>>, // Connection ID to offer, if None the extension is not negotiated
     pub(crate) local_srtp_protection_profiles: Vec<SrtpProtectionProfile>, // Available SRTPProtectionProfiles, if empty no SRTP support
     pub(crate) server_name: String,
     pub(crate) client_auth: ClientAuthType, // If we are a client should we request a client certificate
@@ -101,6 +103,8 @@ impl Default for HandshakeConfig {
             local_cipher_suites: vec![],
             local_signature_schemes: vec![],
             extended_master_secret: ExtendedMasterSecretType::Disable,
+            disallow_renegotiation: false,
+            local_connection_id: None,
             local_srtp_protection_profiles: vec![],
             server_name: String::new(),
             client_auth: ClientAuthType::NoClientCert,
@@ -22,6 +22,8 @@ pub(crate) mod signature_hash_algorithm;
 pub(crate) mod state;
 
 pub(crate) use error::Error;
+#[cfg(feature = "dtls-openssl")]
+pub(crate) use error::OpenSslError;
 
 use cipher_suite::*;
 use extension::extension_use_srtp::SrtpProtectionProfile;
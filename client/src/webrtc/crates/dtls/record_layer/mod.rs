@@ -79,18 +79,25 @@ impl RecordLayer {
 // two DTLS messages into the same datagram: in the same record or in
 // separate records.
 // https://tools.ietf.org/html/rfc6347#section-4.2.3
-pub(crate) fn unpack_datagram(buf: &[u8]) -> Result<Vec<Vec<u8>>> {
+// A ContentType::ConnectionId record has connection_id_len more header bytes in front of
+// the length, RFC 9146 Section 4.
+pub(crate) fn unpack_datagram(buf: &[u8], connection_id_len: usize) -> Result<Vec<Vec<u8>>> {
     let mut out = vec![];
 
     let mut offset = 0;
     while buf.len() != offset {
-        if buf.len() - offset <= RECORD_LAYER_HEADER_SIZE {
+        let header_size = if buf[offset] == ContentType::ConnectionId as u8 {
+            RECORD_LAYER_HEADER_SIZE + connection_id_len
+        } else {
+            RECORD_LAYER_HEADER_SIZE
+        };
+        if buf.len() - offset <= header_size {
             return Err(Error::ErrInvalidPacketLength);
         }
 
-        let pkt_len = RECORD_LAYER_HEADER_SIZE
-            + (((buf[offset + RECORD_LAYER_HEADER_SIZE - 2] as usize) << 8)
-                | buf[offset + RECORD_LAYER_HEADER_SIZE - 1] as usize);
+        let pkt_len = header_size
+            + (((buf[offset + header_size - 2] as usize) << 8)
+                | buf[offset + header_size - 1] as usize);
         if offset + pkt_len > buf.len() {
             return Err(Error::ErrInvalidPacketLength);
         }
@@ -41,6 +41,16 @@ pub(crate) struct RecordLayerHeader {
 
 impl RecordLayerHeader {
     pub(crate) fn marshal<W: Write>(&self, writer: &mut W) -> Result<()> {
+        self.marshal_with_connection_id(&[], writer)
+    }
+
+    // marshal_with_connection_id writes connection_id between the sequence number and the
+    // length if this is a ContentType::ConnectionId record, RFC 9146 Section 4
+    pub(crate) fn marshal_with_connection_id<W: Write>(
+        &self,
+        connection_id: &[u8],
+        writer: &mut W,
+    ) -> Result<()> {
         if self.sequence_number > MAX_SEQUENCE_NUMBER {
             return Err(Error::ErrSequenceNumberOverflow);
         }
@@ -53,12 +63,25 @@ impl RecordLayerHeader {
         let be: [u8; 8] = self.sequence_number.to_be_bytes();
         writer.write_all(&be[2..])?; // uint48 in spec
 
+        if self.content_type == ContentType::ConnectionId {
+            writer.write_all(connection_id)?;
+        }
         writer.write_u16::<BigEndian>(self.content_len)?;
 
         Ok(writer.flush()?)
     }
 
     pub(crate) fn unmarshal<R: Read>(reader: &mut R) -> Result<Self> {
+        Ok(RecordLayerHeader::unmarshal_with_connection_id(reader, 0)?.0)
+    }
+
+    // unmarshal_with_connection_id also reads the connection_id_len bytes of connection id of
+    // a ContentType::ConnectionId record, which carries no length of its own. It is empty for
+    // other records.
+    pub(crate) fn unmarshal_with_connection_id<R: Read>(
+        reader: &mut R,
+        connection_id_len: usize,
+    ) -> Result<(Self, Vec<u8>)> {
         let content_type = reader.read_u8()?.into();
         let major = reader.read_u8()?;
         let minor = reader.read_u8()?;
@@ -73,14 +96,22 @@ impl RecordLayerHeader {
         if protocol_version != PROTOCOL_VERSION1_0 && protocol_version != PROTOCOL_VERSION1_2 {
             return Err(Error::ErrUnsupportedProtocolVersion);
         }
+        let mut connection_id = vec![];
+        if content_type == ContentType::ConnectionId {
+            connection_id = vec![0u8; connection_id_len];
+            reader.read_exact(&mut connection_id)?;
+        }
         let content_len = reader.read_u16::<BigEndian>()?;
 
-        Ok(RecordLayerHeader {
-            content_type,
-            protocol_version,
-            epoch,
-            sequence_number,
-            content_len,
-        })
+        Ok((
+            RecordLayerHeader {
+                content_type,
+                protocol_version,
+                epoch,
+                sequence_number,
+                content_len,
+            },
+            connection_id,
+        ))
     }
 }
@@ -35,6 +35,17 @@ impl From<u8> for HashAlgorithm {
     }
 }
 
+impl HashAlgorithm {
+    /// insecure tells whether the hash is too weak to sign with, RFC 9155 deprecates MD5 and
+    /// SHA-1 in TLS 1.2 signatures
+    pub(crate) fn insecure(&self) -> bool {
+        matches!(
+            *self,
+            HashAlgorithm::Md2 | HashAlgorithm::Md5 | HashAlgorithm::Sha1
+        )
+    }
+}
+
 impl fmt::Display for HashAlgorithm {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match *self {
@@ -32,6 +32,11 @@ pub(crate) struct State {
 
     pub(crate) pre_master_secret: Vec<u8>,
     pub(crate) extended_master_secret: bool,
+    // Connection IDs negotiated as of RFC 9146, empty if not in use. The local one is on the
+    // records the peer sends, the remote one goes on the records sent to the peer.
+    pub(crate) local_connection_id: Arc<Mutex<Vec<u8>>>,
+    pub(crate) remote_connection_id: Arc<Mutex<Vec<u8>>>,
+    pub(crate) connection_id_negotiated: bool,
 
     pub(crate) named_curve: NamedCurve,
     pub(crate) local_keypair: Option<NamedCurveKeypair>,
@@ -81,6 +86,9 @@ impl Default for State {
 
             pre_master_secret: vec![],
             extended_master_secret: false,
+            local_connection_id: Arc::new(Mutex::new(vec![])),
+            remote_connection_id: Arc::new(Mutex::new(vec![])),
+            connection_id_negotiated: false,
 
             named_curve: NamedCurve::Unsupported,
             local_keypair: None,
@@ -103,7 +111,8 @@ impl KeyingMaterialExporter for State {
     /// export_keying_material returns length bytes of exported key material in a new
     /// slice as defined in RFC 5705.
     /// This allows protocols to use DTLS for key establishment, but
-    /// then use some of the keying material for their own purposes
+    /// then use some of the keying material for their own purposes.
+    /// An empty context is taken as no context, RFC 5705 Section 4.
     async fn export_keying_material(
         &self,
         label: &str,
@@ -114,8 +123,8 @@ impl KeyingMaterialExporter for State {
 
         if self.local_epoch.load(Ordering::SeqCst) == 0 {
             return Err(HandshakeInProgress);
-        } else if !context.is_empty() {
-            return Err(ContextUnsupported);
+        } else if context.len() > u16::MAX as usize {
+            return Err(ContextTooLong);
         } else if INVALID_KEYING_LABELS.contains_key(label) {
             return Err(ReservedExportKeyingMaterial);
         }
@@ -139,6 +148,10 @@ impl KeyingMaterialExporter for State {
             seed.extend_from_slice(&remote_random);
             seed.extend_from_slice(&local_random);
         }
+        if !context.is_empty() {
+            seed.extend_from_slice(&(context.len() as u16).to_be_bytes());
+            seed.extend_from_slice(context);
+        }
 
         let cipher_suite = self.cipher_suite.lock().await;
         if let Some(cipher_suite) = &*cipher_suite {
@@ -0,0 +1,186 @@
+use crate::webrtc::util::{redaction::Redacted, spawner::Spawner, Conn, Error};
+
+use async_trait::async_trait;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpSocket;
+use tokio::sync::{mpsc, Mutex};
+use tokio::task::JoinHandle;
+use tokio::time::{timeout, Duration};
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// CONNECT_TIMEOUT is how long an active TCP candidate tries to reach the remote, the pair
+/// usually fails before that when its checks run out
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// PACKET_QUEUE_SIZE is how many received packets wait for the agent before the stream is no
+/// longer read, which makes TCP slow the remote down
+const PACKET_QUEUE_SIZE: usize = 64;
+
+/// ActiveTcpConn is the connection of an active ICE-TCP candidate to one remote passive
+/// candidate. It carries STUN, DTLS and SCTP packets over the stream framed by the 16 bit
+/// length prefix of RFC 4571, and looks like a UDP socket to the agent: every send_to is one
+/// frame to the remote, wherever it is addressed, and every recv_from one frame from it.
+pub(crate) struct ActiveTcpConn {
+    local_addr: SocketAddr,
+    remote_addr: SocketAddr,
+    /// writer is set once the connection is established. Packets sent before are dropped as
+    /// on a lossy path, the connectivity checks are retransmitted anyway.
+    writer: Arc<Mutex<Option<OwnedWriteHalf>>>,
+    packets: Mutex<mpsc::Receiver<Vec<u8>>>,
+    task: JoinHandle<()>,
+}
+
+impl ActiveTcpConn {
+    /// connect binds a socket to local_ip and connects it to remote_addr in the background.
+    /// The socket is bound right away, so that the candidate knows its port.
+    pub(crate) fn connect(
+        local_ip: IpAddr,
+        remote_addr: SocketAddr,
+        spawner: &Spawner,
+    ) -> io::Result<Self> {
+        let socket = if local_ip.is_ipv4() {
+            TcpSocket::new_v4()?
+        } else {
+            TcpSocket::new_v6()?
+        };
+        socket.bind(SocketAddr::new(local_ip, 0))?;
+        let local_addr = socket.local_addr()?;
+
+        let writer = Arc::new(Mutex::new(None));
+        let (packets_tx, packets_rx) = mpsc::channel(PACKET_QUEUE_SIZE);
+        let task_writer = Arc::clone(&writer);
+        let task = spawner.spawn(async move {
+            let stream = match timeout(CONNECT_TIMEOUT, socket.connect(remote_addr)).await {
+                Ok(Ok(stream)) => stream,
+                Ok(Err(err)) => {
+                    log::debug!(
+                        "could not connect {} to {}: {}",
+                        Redacted(&local_addr),
+                        Redacted(&remote_addr),
+                        err
+                    );
+                    return;
+                }
+                Err(_) => {
+                    log::debug!(
+                        "could not connect {} to {}: timed out",
+                        Redacted(&local_addr),
+                        Redacted(&remote_addr)
+                    );
+                    return;
+                }
+            };
+            // every write is a whole packet, holding it back for more data only adds latency
+            let _ = stream.set_nodelay(true);
+            log::debug!(
+                "connected {} to {}",
+                Redacted(&local_addr),
+                Redacted(&remote_addr)
+            );
+
+            let (reader, stream_writer) = stream.into_split();
+            {
+                let mut writer = task_writer.lock().await;
+                *writer = Some(stream_writer);
+            }
+            if let Err(err) = read_frames(reader, packets_tx).await {
+                log::debug!(
+                    "connection {} to {} ended: {}",
+                    Redacted(&local_addr),
+                    Redacted(&remote_addr),
+                    err
+                );
+            }
+        });
+
+        Ok(ActiveTcpConn {
+            local_addr,
+            remote_addr,
+            writer,
+            packets: Mutex::new(packets_rx),
+            task,
+        })
+    }
+}
+
+/// read_frames hands each RFC 4571 frame of the stream to packets until the stream ends or
+/// the connection is closed
+async fn read_frames(mut reader: OwnedReadHalf, packets: mpsc::Sender<Vec<u8>>) -> io::Result<()> {
+    loop {
+        let len = reader.read_u16().await?;
+        let mut packet = vec![0u8; usize::from(len)];
+        reader.read_exact(&mut packet).await?;
+        if packets.send(packet).await.is_err() {
+            return Ok(());
+        }
+    }
+}
+
+#[async_trait]
+impl Conn for ActiveTcpConn {
+    async fn connect(&self, _addr: SocketAddr) -> Result<()> {
+        Err(Error::Other(
+            "active TCP conns connect on creation".to_owned(),
+        ))
+    }
+
+    async fn recv(&self, buf: &mut [u8]) -> Result<usize> {
+        let (n, _) = self.recv_from(buf).await?;
+        Ok(n)
+    }
+
+    async fn recv_from(&self, buf: &mut [u8]) -> Result<(usize, SocketAddr)> {
+        let mut packets = self.packets.lock().await;
+        match packets.recv().await {
+            // like a datagram, whatever does not fit into buf is cut off
+            Some(packet) => {
+                let n = packet.len().min(buf.len());
+                buf[..n].copy_from_slice(&packet[..n]);
+                Ok((n, self.remote_addr))
+            }
+            None => Err(io::Error::from(io::ErrorKind::ConnectionAborted).into()),
+        }
+    }
+
+    async fn send(&self, buf: &[u8]) -> Result<usize> {
+        self.send_to(buf, self.remote_addr).await
+    }
+
+    async fn send_to(&self, buf: &[u8], _target: SocketAddr) -> Result<usize> {
+        let len = u16::try_from(buf.len()).map_err(|_| Error::ErrPacketTooBig)?;
+        let mut frame = Vec::with_capacity(2 + buf.len());
+        frame.extend_from_slice(&len.to_be_bytes());
+        frame.extend_from_slice(buf);
+
+        let mut writer = self.writer.lock().await;
+        match &mut *writer {
+            Some(w) => {
+                w.write_all(&frame).await?;
+                Ok(buf.len())
+            }
+            None => Err(io::Error::from(io::ErrorKind::NotConnected).into()),
+        }
+    }
+
+    async fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.local_addr)
+    }
+
+    async fn remote_addr(&self) -> Option<SocketAddr> {
+        Some(self.remote_addr)
+    }
+
+    async fn close(&self) -> Result<()> {
+        self.task.abort();
+        let mut writer = self.writer.lock().await;
+        if let Some(mut w) = writer.take() {
+            let _ = w.shutdown().await;
+        }
+        Ok(())
+    }
+}
@@ -1,15 +1,23 @@
 use super::*;
 use crate::webrtc::ice::error::*;
+use crate::webrtc::ice::gathering_context::GatheringContext;
 use crate::webrtc::ice::mdns::*;
 use crate::webrtc::ice::network_type::*;
 use crate::webrtc::ice::url::*;
+use crate::webrtc::ice::util::SocketBuffers;
 
+use crate::webrtc::util::conn::send_error::SendErrorStats;
+use crate::webrtc::util::random_source::RandomSource;
+use crate::webrtc::util::spawner::Spawner;
 use crate::webrtc::util::vnet::net::*;
+use crate::webrtc::util::Conn;
+use crate::webrtc::DEFAULT_RECEIVE_MTU;
 
+use std::sync::atomic::AtomicU64;
 use std::time::Duration;
 
-/// The interval at which the agent performs candidate checks in the connecting phase.
-pub(crate) const DEFAULT_CHECK_INTERVAL: Duration = Duration::from_millis(200);
+/// The pacing timer (Ta) of connectivity checks in the connecting phase, RFC 8445 S14.2.
+pub(crate) const DEFAULT_CHECK_INTERVAL: Duration = Duration::from_millis(50);
 
 /// The interval used to keep candidates alive.
 pub(crate) const DEFAULT_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(2);
@@ -32,9 +40,38 @@ pub(crate) const DEFAULT_PRFLX_ACCEPTANCE_MIN_WAIT: Duration = Duration::from_mi
 /// Wait time before nominating a relay candidate.
 pub(crate) const DEFAULT_RELAY_ACCEPTANCE_MIN_WAIT: Duration = Duration::from_millis(2000);
 
+/// Wait time before nominating a TCP candidate pair, on top of the wait of its candidate types.
+/// It gives UDP candidates signaled after the TCP ones a chance to be checked first.
+pub(crate) const TCP_ACCEPTANCE_MIN_WAIT: Duration = Duration::from_millis(500);
+
+/// The most binding requests kept while the remote credentials are unknown, see EarlyCheck.
+pub(crate) const MAX_EARLY_CHECKS: usize = 16;
+
+/// How long a binding request that arrived before the remote credentials is kept for replay.
+pub(crate) const EARLY_CHECK_LIFETIME: Duration = Duration::from_secs(2);
+
+/// KeepaliveKind is what the agent sends on the selected pair to keep it alive when there is
+/// no other traffic, see SettingEngine::set_ice_keepalive_kind.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Default)]
+pub enum KeepaliveKind {
+    /// BindingIndication sends STUN binding indications, which are not answered.
+    #[default]
+    BindingIndication,
+    /// BindingRequest sends STUN binding requests. Their responses keep the remote candidate
+    /// alive for consent freshness (RFC 7675) and are reported as connectivity check results
+    /// with their round trip time.
+    BindingRequest,
+}
+
 /// Max binding request before considering a pair failed.
 pub(crate) const DEFAULT_MAX_BINDING_REQUESTS: u16 = 7;
 
+/// Max remote candidates kept before the lowest priority ones are evicted.
+pub(crate) const DEFAULT_MAX_REMOTE_CANDIDATES: usize = 64;
+
+/// Max candidate pairs kept in the checklist before the lowest priority ones are evicted.
+pub(crate) const DEFAULT_MAX_CANDIDATE_PAIRS: usize = 256;
+
 /// The number of bytes that can be buffered before we start to error.
 pub(crate) const MAX_BUFFER_SIZE: usize = 1000 * 1000; // 1MB
 
@@ -46,6 +83,15 @@ pub(crate) fn default_candidate_types() -> Vec<CandidateType> {
 }
 
 pub(crate) type InterfaceFilterFn = Box<dyn (Fn(&str) -> bool) + Send + Sync>;
+pub(crate) type CandidateFilterFn =
+    Box<dyn (Fn(&Arc<dyn Candidate + Send + Sync>) -> CandidateDisposition) + Send + Sync>;
+/// Called with the local and remote candidate, the round trip time and whether the check
+/// succeeded.
+pub(crate) type ConnectivityCheckResultFn = Box<
+    dyn Fn(&Arc<dyn Candidate + Send + Sync>, &Arc<dyn Candidate + Send + Sync>, Duration, bool)
+        + Send
+        + Sync,
+>;
 
 /// Collects the arguments to `ice::Agent` construction into a single structure, for
 /// future-proofness of the interface.
@@ -81,6 +127,9 @@ pub(crate) struct AgentConfig {
     /// A keepalive interval of 0 means we never send keepalive packets
     pub(crate) keepalive_interval: Option<Duration>,
 
+    /// What is sent as keepalive, see `KeepaliveKind`.
+    pub(crate) keepalive_kind: KeepaliveKind,
+
     /// An optional configuration for disabling or enabling support for specific network types.
     pub(crate) network_types: Vec<NetworkType>,
 
@@ -88,8 +137,8 @@ pub(crate) struct AgentConfig {
     pub(crate) candidate_types: Vec<CandidateType>,
 
     //LoggerFactory logging.LoggerFactory
-    /// Controls how often our internal task loop runs when in the connecting state.
-    /// Only useful for testing.
+    /// Controls how often our internal task loop runs when in the connecting state. At most one
+    /// connectivity check is sent per interval across all candidate pairs.
     pub(crate) check_interval: Duration,
 
     /// The max amount of binding requests the agent will send over a candidate pair for validation
@@ -97,6 +146,15 @@ pub(crate) struct AgentConfig {
     /// request or a nomination we set the pair as failed.
     pub(crate) max_binding_requests: Option<u16>,
 
+    /// The max amount of remote candidates the agent keeps. Beyond it the lowest priority
+    /// candidate is evicted (or the new one dropped), candidates of the nominated or selected pair
+    /// are never evicted.
+    pub(crate) max_remote_candidates: Option<usize>,
+
+    /// The max amount of candidate pairs in the checklist, enforced the same way as
+    /// max_remote_candidates.
+    pub(crate) max_candidate_pairs: Option<usize>,
+
     pub(crate) is_controlling: bool,
 
     /// lite agents do not perform connectivity check and only provide host candidates.
@@ -130,6 +188,56 @@ pub(crate) struct AgentConfig {
     /// A function that you can use in order to whitelist or blacklist the interfaces which are
     /// used to gather ICE candidates.
     pub(crate) interface_filter: Arc<Option<InterfaceFilterFn>>,
+
+    /// Decides for each gathered local candidate whether it is used and advertised, see
+    /// `CandidateDisposition`. None uses and advertises all of them.
+    pub(crate) candidate_filter: Arc<Option<CandidateFilterFn>>,
+
+    /// Called for every binding request of a connectivity check once it got a response or
+    /// expired. None reports nothing.
+    pub(crate) on_connectivity_check_result: Arc<Option<ConnectivityCheckResultFn>>,
+
+    /// A caller owned socket to use for the single host candidate. When set, the agent binds
+    /// no sockets of its own and gathers on no other interface.
+    pub(crate) udp_conn: Option<Arc<dyn Conn + Send + Sync>>,
+
+    /// The DSCP code point set on the sockets the agent binds.
+    pub(crate) dscp: Option<u8>,
+
+    /// Sizes the buffers of the sockets the agent binds and reports what the OS made of it.
+    pub(crate) socket_buffers: Arc<SocketBuffers>,
+
+    /// Receives every packet sent or received on the candidates' sockets. None leaves the
+    /// sockets unwrapped.
+    pub(crate) packet_capture: Option<Arc<dyn PacketSink>>,
+
+    /// Records the results of the connectivity checks.
+    pub(crate) event_log: Arc<EventLog>,
+
+    /// Counts the datagrams dropped or retried for a send error instead of failing the
+    /// connection, see `AgentConn`.
+    pub(crate) send_errors: Arc<SendErrorStats>,
+
+    /// The largest datagram read from the candidates' sockets, larger ones are dropped and
+    /// counted in `oversized_datagrams`. Zero selects `DEFAULT_RECEIVE_MTU`.
+    pub(crate) receive_mtu: usize,
+
+    /// Counts the datagrams dropped for exceeding `receive_mtu`.
+    pub(crate) oversized_datagrams: Arc<AtomicU64>,
+
+    /// Draws the ICE credentials, candidate ids, tie breaker and mDNS name.
+    pub(crate) rng: RandomSource,
+
+    /// Runs the tasks of the agent and its candidates.
+    pub(crate) spawner: Spawner,
+
+    /// Shares the local interface list with other agents. Ignored if net is set.
+    pub(crate) gathering_context: Option<GatheringContext>,
+
+    /// How often the local interfaces are polled for address changes, see
+    /// `Agent::start_network_monitor`. None disables the monitor.
+    #[cfg(feature = "network-monitor")]
+    pub(crate) network_monitor_interval: Option<Duration>,
 }
 
 impl AgentConfig {
@@ -141,6 +249,18 @@ impl AgentConfig {
             a.max_binding_requests = DEFAULT_MAX_BINDING_REQUESTS;
         }
 
+        if let Some(max_remote_candidates) = self.max_remote_candidates {
+            a.max_remote_candidates = max_remote_candidates;
+        } else {
+            a.max_remote_candidates = DEFAULT_MAX_REMOTE_CANDIDATES;
+        }
+
+        if let Some(max_candidate_pairs) = self.max_candidate_pairs {
+            a.max_candidate_pairs = max_candidate_pairs;
+        } else {
+            a.max_candidate_pairs = DEFAULT_MAX_CANDIDATE_PAIRS;
+        }
+
         if let Some(host_acceptance_min_wait) = self.host_acceptance_min_wait {
             a.host_acceptance_min_wait = host_acceptance_min_wait;
         } else {
@@ -183,11 +303,19 @@ impl AgentConfig {
             a.keepalive_interval = DEFAULT_KEEPALIVE_INTERVAL;
         }
 
+        a.keepalive_kind = self.keepalive_kind;
+
         if self.check_interval == Duration::from_secs(0) {
             a.check_interval = DEFAULT_CHECK_INTERVAL;
         } else {
             a.check_interval = self.check_interval;
         }
+
+        if self.receive_mtu == 0 {
+            a.receive_mtu = DEFAULT_RECEIVE_MTU;
+        } else {
+            a.receive_mtu = self.receive_mtu;
+        }
     }
 
     pub(crate) fn init_ext_ip_mapping(
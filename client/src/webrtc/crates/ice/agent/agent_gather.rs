@@ -7,6 +7,10 @@ use crate::webrtc::util::{vnet::net::*, Conn};
 use crate::webrtc::ice::candidate::candidate_base::CandidateBaseConfig;
 use crate::webrtc::ice::candidate::candidate_host::CandidateHostConfig;
 use crate::webrtc::ice::candidate::*;
+use crate::webrtc::ice::packet_capture::CaptureConn;
+use crate::webrtc::ice::rand::generate_cand_id;
+use crate::webrtc::ice::tcp_type::TcpType;
+use std::net::IpAddr;
 use std::sync::Arc;
 use waitgroup::WaitGroup;
 
@@ -18,6 +22,8 @@ pub(crate) struct GatherCandidatesInternalParams {
     pub(crate) net: Arc<Net>,
     pub(crate) interface_filter: Arc<Option<InterfaceFilterFn>>,
     pub(crate) ext_ip_mapper: Arc<Option<ExternalIpMapper>>,
+    pub(crate) udp_conn: Option<Arc<dyn Conn + Send + Sync>>,
+    pub(crate) dscp: Option<u8>,
     pub(crate) agent_internal: Arc<AgentInternal>,
     pub(crate) gathering_state: Arc<AtomicU8>,
     pub(crate) chan_candidate_tx: ChanCandidateTx,
@@ -29,6 +35,8 @@ struct GatherCandidatesLocalParams {
     mdns_name: String,
     interface_filter: Arc<Option<InterfaceFilterFn>>,
     ext_ip_mapper: Arc<Option<ExternalIpMapper>>,
+    udp_conn: Option<Arc<dyn Conn + Send + Sync>>,
+    dscp: Option<u8>,
     net: Arc<Net>,
     agent_internal: Arc<AgentInternal>,
 }
@@ -53,12 +61,14 @@ impl Agent {
                         mdns_name: params.mdns_name.clone(),
                         interface_filter: Arc::clone(&params.interface_filter),
                         ext_ip_mapper: Arc::clone(&params.ext_ip_mapper),
+                        udp_conn: params.udp_conn.clone(),
+                        dscp: params.dscp,
                         net: Arc::clone(&params.net),
                         agent_internal: Arc::clone(&params.agent_internal),
                     };
 
                     let w = wg.worker();
-                    tokio::spawn(async move {
+                    params.agent_internal.spawner.spawn(async move {
                         let _d = w;
 
                         Self::gather_candidates_local(local_params).await;
@@ -87,8 +97,12 @@ impl Agent {
         if GatheringState::from(gathering_state.load(Ordering::SeqCst)) != new_state
             && new_state == GatheringState::Complete
         {
-            let cand_tx = chan_candidate_tx.lock().await;
-            if let Some(tx) = &*cand_tx {
+            // see AgentInternal::add_candidate
+            let cand_tx = {
+                let cand_tx = chan_candidate_tx.lock().await;
+                cand_tx.clone()
+            };
+            if let Some(tx) = cand_tx {
                 let _ = tx.send(None).await;
             }
         }
@@ -103,6 +117,8 @@ impl Agent {
             mdns_name,
             interface_filter,
             ext_ip_mapper,
+            udp_conn,
+            dscp,
             net,
             agent_internal,
         ) = (
@@ -111,12 +127,96 @@ impl Agent {
             params.mdns_name,
             params.interface_filter,
             params.ext_ip_mapper,
+            params.udp_conn,
+            params.dscp,
             params.net,
             params.agent_internal,
         );
 
-        let ips = local_interfaces(&net, &*interface_filter, &network_types).await;
+        // a caller supplied socket is the only host candidate, nothing else gets bound
+        let host_conns = if let Some(conn) = udp_conn {
+            Self::injected_host_conn(
+                &net,
+                &interface_filter,
+                &network_types,
+                conn,
+                &agent_internal,
+            )
+            .await
+            .into_iter()
+            .collect::<Vec<_>>()
+        } else {
+            let ips = local_interfaces(&net, &interface_filter, &network_types).await;
+            Self::listen_host_conns(&net, ips, dscp, &agent_internal).await
+        };
+
+        let host_ips: Vec<IpAddr> = host_conns.iter().map(|(ip, _)| *ip).collect();
+        Self::add_host_candidates(
+            host_conns,
+            mdns_mode,
+            &mdns_name,
+            &ext_ip_mapper,
+            &agent_internal,
+        )
+        .await;
+
+        Self::add_active_tcp_candidates(
+            host_ips,
+            &network_types,
+            mdns_mode,
+            &mdns_name,
+            &agent_internal,
+        )
+        .await;
+    }
+
+    /// listen_host_conns binds a UDP socket on each of the given local addresses, addresses
+    /// that cannot be bound are skipped.
+    pub(crate) async fn listen_host_conns(
+        net: &Arc<Net>,
+        ips: impl IntoIterator<Item = IpAddr>,
+        dscp: Option<u8>,
+        agent_internal: &Arc<AgentInternal>,
+    ) -> Vec<(IpAddr, Arc<dyn Conn + Send + Sync>)> {
+        let mut host_conns = vec![];
         for ip in ips {
+            match listen_udp_in_port_range(
+                net,
+                SocketAddr::new(ip, 0),
+                dscp,
+                &agent_internal.socket_buffers,
+            )
+            .await
+            {
+                Ok(conn) => host_conns.push((ip, conn)),
+                Err(err) => {
+                    log::warn!(
+                        "[{}]: could not listen {} {}: {}",
+                        agent_internal.get_name(),
+                        UDP,
+                        Redacted(&ip),
+                        err
+                    );
+                }
+            }
+        }
+        host_conns
+    }
+
+    /// add_host_candidates creates a host candidate for each bound socket and adds it to the
+    /// agent, which pairs it with the known remote candidates and reports it through
+    /// on_candidate.
+    pub(crate) async fn add_host_candidates(
+        host_conns: Vec<(IpAddr, Arc<dyn Conn + Send + Sync>)>,
+        mdns_mode: MulticastDnsMode,
+        mdns_name: &str,
+        ext_ip_mapper: &Arc<Option<ExternalIpMapper>>,
+        agent_internal: &Arc<AgentInternal>,
+    ) {
+        //TODO: for network in networks
+        let network = UDP.to_owned();
+
+        for (ip, conn) in host_conns {
             let mut mapped_ip = ip;
 
             if mdns_mode != MulticastDnsMode::QueryAndGather && ext_ip_mapper.is_some() {
@@ -128,7 +228,7 @@ impl Agent {
                             log::warn!(
                                 "[{}]: 1:1 NAT mapping is enabled but no external IP is found for {}",
                                 agent_internal.get_name(),
-                                ip
+                                Redacted(&ip)
                             );
                         }
                     }
@@ -136,29 +236,11 @@ impl Agent {
             }
 
             let address = if mdns_mode == MulticastDnsMode::QueryAndGather {
-                mdns_name.clone()
+                mdns_name.to_owned()
             } else {
                 mapped_ip.to_string()
             };
 
-            //TODO: for network in networks
-            let network = UDP.to_owned();
-
-            let conn: Arc<dyn Conn + Send + Sync> =
-                match listen_udp_in_port_range(&net, SocketAddr::new(ip, 0)).await {
-                    Ok(conn) => conn,
-                    Err(err) => {
-                        log::warn!(
-                            "[{}]: could not listen {} {}: {}",
-                            agent_internal.get_name(),
-                            network,
-                            ip,
-                            err
-                        );
-                        continue;
-                    }
-                };
-
             let port = match conn.local_addr().await {
                 Ok(addr) => addr.port(),
                 Err(err) => {
@@ -171,6 +253,12 @@ impl Agent {
                 }
             };
 
+            // the capture sees the candidate's packets from the first connectivity check on
+            let conn: Arc<dyn Conn + Send + Sync> = match &agent_internal.packet_capture {
+                Some(sink) => Arc::new(CaptureConn::new(conn, Arc::clone(sink)).await),
+                None => conn,
+            };
+
             let host_config = CandidateHostConfig {
                 base_config: CandidateBaseConfig {
                     network: network.clone(),
@@ -178,6 +266,7 @@ impl Agent {
                     port,
                     component: COMPONENT_RTP,
                     conn: Some(conn),
+                    candidate_id: generate_cand_id(&agent_internal.rng),
                     ..CandidateBaseConfig::default()
                 },
                 ..CandidateHostConfig::default()
@@ -192,7 +281,7 @@ impl Agent {
                                     "[{}]: Failed to create host candidate: {} {} {}: {:?}",
                                     agent_internal.get_name(),
                                     network,
-                                    mapped_ip,
+                                    Redacted(&mapped_ip),
                                     port,
                                     err
                                 );
@@ -206,7 +295,7 @@ impl Agent {
                             "[{}]: Failed to create host candidate: {} {} {}: {}",
                             agent_internal.get_name(),
                             network,
-                            mapped_ip,
+                            Redacted(&mapped_ip),
                             port,
                             err
                         );
@@ -232,4 +321,127 @@ impl Agent {
             }
         }
     }
+
+    /// add_active_tcp_candidates adds an active TCP host candidate on each of the given
+    /// addresses whose TCP network type is requested. Such a candidate only advertises with
+    /// the discard port 9 that the agent connects to passive TCP candidates of the remote, each
+    /// connection it opens becomes a candidate of its own (RFC 6544 Section 5.1). Nothing
+    /// connects to it, so it is advertised with the interface address rather than through the
+    /// 1:1 NAT mapping, the connections are bound to that address.
+    pub(crate) async fn add_active_tcp_candidates(
+        ips: impl IntoIterator<Item = IpAddr>,
+        network_types: &[NetworkType],
+        mdns_mode: MulticastDnsMode,
+        mdns_name: &str,
+        agent_internal: &Arc<AgentInternal>,
+    ) {
+        let network = TCP.to_owned();
+
+        for ip in ips {
+            let requested = network_types
+                .iter()
+                .any(|t| t.is_tcp() && t.is_ipv4() == ip.is_ipv4());
+            if !requested {
+                continue;
+            }
+
+            let address = if mdns_mode == MulticastDnsMode::QueryAndGather {
+                mdns_name.to_owned()
+            } else {
+                ip.to_string()
+            };
+
+            let host_config = CandidateHostConfig {
+                base_config: CandidateBaseConfig {
+                    network: network.clone(),
+                    address,
+                    port: ACTIVE_TCP_PORT,
+                    component: COMPONENT_RTP,
+                    tcp_type: TcpType::Active,
+                    candidate_id: generate_cand_id(&agent_internal.rng),
+                    ..CandidateBaseConfig::default()
+                },
+            };
+
+            let candidate: Arc<dyn Candidate + Send + Sync> =
+                match host_config.new_candidate_host().await {
+                    Ok(candidate) => {
+                        if mdns_mode == MulticastDnsMode::QueryAndGather {
+                            if let Err(err) = candidate.set_ip(&ip).await {
+                                log::warn!(
+                                    "[{}]: Failed to create active TCP candidate: {} {}: {:?}",
+                                    agent_internal.get_name(),
+                                    network,
+                                    Redacted(&ip),
+                                    err
+                                );
+                                continue;
+                            }
+                        }
+                        Arc::new(candidate)
+                    }
+                    Err(err) => {
+                        log::warn!(
+                            "[{}]: Failed to create active TCP candidate: {} {}: {}",
+                            agent_internal.get_name(),
+                            network,
+                            Redacted(&ip),
+                            err
+                        );
+                        continue;
+                    }
+                };
+
+            if let Err(err) = agent_internal.add_candidate(&candidate).await {
+                log::warn!(
+                    "[{}]: Failed to append to localCandidates and run onCandidateHdlr: {}",
+                    agent_internal.get_name(),
+                    err
+                );
+            }
+        }
+    }
+
+    /// injected_host_conn resolves the address advertised for a caller supplied socket. A
+    /// socket bound to the unspecified address is advertised on the first local interface of
+    /// the same family.
+    async fn injected_host_conn(
+        net: &Arc<Net>,
+        interface_filter: &Arc<Option<InterfaceFilterFn>>,
+        network_types: &[NetworkType],
+        conn: Arc<dyn Conn + Send + Sync>,
+        agent_internal: &Arc<AgentInternal>,
+    ) -> Option<(IpAddr, Arc<dyn Conn + Send + Sync>)> {
+        let local_addr = match conn.local_addr().await {
+            Ok(local_addr) => local_addr,
+            Err(err) => {
+                log::warn!(
+                    "[{}]: could not get local addr of the provided socket: {}",
+                    agent_internal.get_name(),
+                    err
+                );
+                return None;
+            }
+        };
+
+        if !local_addr.ip().is_unspecified() {
+            return Some((local_addr.ip(), conn));
+        }
+
+        let ip = local_interfaces(net, interface_filter, network_types)
+            .await
+            .into_iter()
+            .find(|ip| ip.is_ipv4() == local_addr.is_ipv4());
+        match ip {
+            Some(ip) => Some((ip, conn)),
+            None => {
+                log::warn!(
+                    "[{}]: no local interface to advertise the provided socket {} on",
+                    agent_internal.get_name(),
+                    Redacted(&local_addr)
+                );
+                None
+            }
+        }
+    }
 }
@@ -1,7 +1,19 @@
 use super::agent_transport::*;
 use super::*;
+use crate::webrtc::ice::active_tcp::ActiveTcpConn;
+use crate::webrtc::ice::candidate::candidate_base::CandidateBaseConfig;
+use crate::webrtc::ice::candidate::candidate_host::CandidateHostConfig;
+use crate::webrtc::ice::candidate::candidate_peer_reflexive::CandidatePeerReflexiveConfig;
+use crate::webrtc::ice::candidate::candidate_stats::CandidateRecords;
+use crate::webrtc::ice::control::*;
+use crate::webrtc::ice::priority::PriorityAttr;
+use crate::webrtc::ice::rand::generate_cand_id;
+use crate::webrtc::ice::tcp_type::TcpType;
 use crate::webrtc::ice::util::*;
-use std::sync::atomic::{AtomicBool, AtomicU64};
+use crate::webrtc::stun::textattrs::Username;
+use crate::webrtc::util::random_source::RandomSource;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize};
 
 pub(crate) type ChanCandidateTx =
     Arc<Mutex<Option<mpsc::Sender<Option<Arc<dyn Candidate + Send + Sync>>>>>>;
@@ -36,10 +48,14 @@ pub(crate) struct AgentInternal {
     pub(crate) on_candidate_hdlr: Mutex<Option<OnCandidateHdlrFn>>,
 
     pub(crate) tie_breaker: AtomicU64,
+    /// rng draws the ICE credentials, candidate ids and the tie breaker
+    pub(crate) rng: RandomSource,
     pub(crate) is_controlling: AtomicBool,
     pub(crate) lite: AtomicBool,
 
     pub(crate) start_time: Mutex<Instant>,
+    // When the last connectivity check was sent, used to pace checks across the checklist
+    pub(crate) last_check_time: Mutex<Option<Instant>>,
     pub(crate) nominated_pair: Mutex<Option<Arc<CandidatePair>>>,
 
     pub(crate) connection_state: AtomicU8, //ConnectionState,
@@ -49,16 +65,41 @@ pub(crate) struct AgentInternal {
     pub(crate) ufrag_pwd: Mutex<UfragPwd>,
 
     pub(crate) local_candidates: Mutex<HashMap<NetworkType, Vec<Arc<dyn Candidate + Send + Sync>>>>,
+    // ids of the local candidates the candidate filter keeps from the remote
+    pub(crate) unadvertised_candidates: Mutex<HashSet<String>>,
     pub(crate) remote_candidates:
         Mutex<HashMap<NetworkType, Vec<Arc<dyn Candidate + Send + Sync>>>>,
+    // Stats of the local and remote candidates, kept after they are pruned
+    pub(crate) candidate_records: Mutex<CandidateRecords>,
 
     // LRU of outbound Binding request Transaction IDs
     pub(crate) pending_binding_requests: Mutex<Vec<BindingRequest>>,
+    // Inbound binding requests waiting for the remote credentials, at most one per local
+    // candidate and remote address
+    pub(crate) early_checks: Mutex<Vec<EarlyCheck>>,
 
     pub(crate) agent_conn: Arc<AgentConn>,
 
+    // Counters of remote candidates and pairs dropped or evicted because of the caps below
+    pub(crate) dropped_remote_candidates: AtomicUsize,
+    pub(crate) dropped_candidate_pairs: AtomicUsize,
+
+    // Whether the remote announced trickle ICE, so new local candidates can reach it
+    #[cfg(feature = "network-monitor")]
+    pub(crate) remote_trickle: AtomicBool,
+
+    // Whether the remote announced renomination, so the controlling side can move the
+    // selected pair after nominating one
+    pub(crate) remote_renomination: AtomicBool,
+    // NOMINATION value sent with the next nomination when controlling
+    pub(crate) nomination: AtomicU32,
+    // Highest NOMINATION value the remote nominated a pair with when controlled
+    pub(crate) remote_nomination: AtomicU32,
+
     // the following variables won't be changed after init_with_defaults()
     pub(crate) max_binding_requests: u16,
+    pub(crate) max_remote_candidates: usize,
+    pub(crate) max_candidate_pairs: usize,
     pub(crate) host_acceptance_min_wait: Duration,
     pub(crate) srflx_acceptance_min_wait: Duration,
     pub(crate) prflx_acceptance_min_wait: Duration,
@@ -72,8 +113,21 @@ pub(crate) struct AgentInternal {
     // How often should we send keepalive packets?
     // 0 means never
     pub(crate) keepalive_interval: Duration,
+    // What is sent as keepalive
+    pub(crate) keepalive_kind: KeepaliveKind,
     // How often should we run our internal taskLoop to check for state changes when connecting
     pub(crate) check_interval: Duration,
+    // The largest datagram accepted from a candidate's socket
+    pub(crate) receive_mtu: usize,
+    // Counts the datagrams dropped for exceeding receive_mtu
+    pub(crate) oversized_datagrams: Arc<AtomicU64>,
+    // Sizes the buffers of the host sockets, including the ones a network change rebinds
+    pub(crate) socket_buffers: Arc<SocketBuffers>,
+    pub(crate) candidate_filter: Arc<Option<CandidateFilterFn>>,
+    pub(crate) on_connectivity_check_result: Arc<Option<ConnectivityCheckResultFn>>,
+    pub(crate) packet_capture: Option<Arc<dyn PacketSink>>,
+    pub(crate) event_log: Arc<EventLog>,
+    pub(crate) spawner: Spawner,
 }
 
 impl AgentInternal {
@@ -105,11 +159,13 @@ impl AgentInternal {
             on_selected_candidate_pair_change_hdlr: Mutex::new(None),
             on_candidate_hdlr: Mutex::new(None),
 
-            tie_breaker: AtomicU64::new(rand::random::<u64>()),
+            tie_breaker: AtomicU64::new(config.rng.next_u64()),
+            rng: config.rng.clone(),
             is_controlling: AtomicBool::new(config.is_controlling),
             lite: AtomicBool::new(config.lite),
 
             start_time: Mutex::new(Instant::now()),
+            last_check_time: Mutex::new(None),
             nominated_pair: Mutex::new(None),
 
             connection_state: AtomicU8::new(ConnectionState::New as u8),
@@ -118,6 +174,8 @@ impl AgentInternal {
 
             //won't change after init_with_defaults()
             max_binding_requests: 0,
+            max_remote_candidates: 0,
+            max_candidate_pairs: 0,
             host_acceptance_min_wait: Duration::from_secs(0),
             srflx_acceptance_min_wait: Duration::from_secs(0),
             prflx_acceptance_min_wait: Duration::from_secs(0),
@@ -134,20 +192,44 @@ impl AgentInternal {
             // How often should we send keepalive packets?
             // 0 means never
             keepalive_interval: Duration::from_secs(0),
+            keepalive_kind: KeepaliveKind::default(),
 
             // How often should we run our internal taskLoop to check for state changes when connecting
             check_interval: Duration::from_secs(0),
 
+            receive_mtu: 0,
+            oversized_datagrams: Arc::clone(&config.oversized_datagrams),
+            socket_buffers: Arc::clone(&config.socket_buffers),
+
             ufrag_pwd: Mutex::new(UfragPwd::default()),
 
             local_candidates: Mutex::new(HashMap::new()),
+            unadvertised_candidates: Mutex::new(HashSet::new()),
             remote_candidates: Mutex::new(HashMap::new()),
+            candidate_records: Mutex::new(CandidateRecords::default()),
 
             // LRU of outbound Binding request Transaction IDs
             pending_binding_requests: Mutex::new(vec![]),
+            early_checks: Mutex::new(vec![]),
 
             // AgentConn
-            agent_conn: Arc::new(AgentConn::new()),
+            agent_conn: Arc::new(AgentConn::new(Arc::clone(&config.send_errors))),
+
+            dropped_remote_candidates: AtomicUsize::new(0),
+            dropped_candidate_pairs: AtomicUsize::new(0),
+
+            #[cfg(feature = "network-monitor")]
+            remote_trickle: AtomicBool::new(false),
+
+            remote_renomination: AtomicBool::new(false),
+            nomination: AtomicU32::new(1),
+            remote_nomination: AtomicU32::new(0),
+
+            candidate_filter: Arc::clone(&config.candidate_filter),
+            on_connectivity_check_result: Arc::clone(&config.on_connectivity_check_result),
+            packet_capture: config.packet_capture.clone(),
+            event_log: Arc::clone(&config.event_log),
+            spawner: config.spawner.clone(),
         };
 
         let chan_receivers = ChanReceivers {
@@ -188,6 +270,7 @@ impl AgentInternal {
         self.update_connection_state(ConnectionState::Checking)
             .await;
 
+        self.replay_early_checks().await;
         self.request_connectivity_check();
 
         self.connectivity_checks().await;
@@ -250,9 +333,9 @@ impl AgentInternal {
             done_and_force_candidate_contact_rx
         {
             let ai = Arc::clone(self);
-            tokio::spawn(async move {
+            self.spawner.spawn(async move {
                 loop {
-                    let mut interval = DEFAULT_CHECK_INTERVAL;
+                    let mut interval = ZERO_DURATION;
 
                     let mut update_interval = |x: Duration| {
                         if x != ZERO_DURATION && (interval == ZERO_DURATION || interval > x) {
@@ -273,6 +356,9 @@ impl AgentInternal {
                     // Ensure we run our task loop as quickly as the minimum of our various configured timeouts
                     update_interval(disconnected_timeout);
                     update_interval(failed_timeout);
+                    if interval == ZERO_DURATION {
+                        interval = DEFAULT_CHECK_INTERVAL;
+                    }
 
                     let t = tokio::time::sleep(interval);
                     tokio::pin!(t);
@@ -328,6 +414,7 @@ impl AgentInternal {
 
         if let Some(p) = p {
             p.nominated.store(true, Ordering::SeqCst);
+            self.event_log.milestone(ConnectMilestone::PairNominated);
             {
                 let mut selected_pair = self.agent_conn.selected_pair.lock().await;
                 *selected_pair = Some(p);
@@ -355,32 +442,48 @@ impl AgentInternal {
         }
     }
 
+    /// Sends the next connectivity check. Checks are paced across the whole checklist: at most one
+    /// is sent per `check_interval` (Ta), to the waiting or in progress pair that has been checked
+    /// the least so far.
+    ///
+    /// reference: (IETF ref-8445)[https://tools.ietf.org/html/rfc8445#section-6.1.4.2].
     pub(crate) async fn ping_all_candidates(&self) {
         log::trace!("[{}]: pinging all candidates", self.get_name(),);
 
-        let mut pairs: Vec<(
-            Arc<dyn Candidate + Send + Sync>,
-            Arc<dyn Candidate + Send + Sync>,
-        )> = vec![];
+        {
+            let mut last_check_time = self.last_check_time.lock().await;
+            if let Some(t) = *last_check_time {
+                if Instant::now()
+                    .checked_duration_since(t)
+                    .unwrap_or_else(|| Duration::from_secs(0))
+                    < self.check_interval
+                {
+                    return;
+                }
+            }
+            *last_check_time = Some(Instant::now());
+        }
+
+        let mut next_pair: Option<Arc<CandidatePair>> = None;
 
         {
-            let mut checklist = self.agent_conn.checklist.lock().await;
+            let checklist = self.agent_conn.checklist.lock().await;
             if checklist.is_empty() {
                 log::warn!(
                     "[{}]: pingAllCandidates called with no candidate pairs. Connection is not possible yet.",
                     self.get_name(),
                 );
             }
-            for p in &mut *checklist {
+            for p in &*checklist {
                 let p_state = p.state.load(Ordering::SeqCst);
-                if p_state == CandidatePairState::Waiting as u8 {
-                    p.state
-                        .store(CandidatePairState::InProgress as u8, Ordering::SeqCst);
-                } else if p_state != CandidatePairState::InProgress as u8 {
+                if p_state != CandidatePairState::Waiting as u8
+                    && p_state != CandidatePairState::InProgress as u8
+                {
                     continue;
                 }
 
-                if p.binding_request_count.load(Ordering::SeqCst) > self.max_binding_requests {
+                let binding_request_count = p.binding_request_count.load(Ordering::SeqCst);
+                if binding_request_count > self.max_binding_requests {
                     log::trace!(
                         "[{}]: max requests reached for pair {}, marking it as failed",
                         self.get_name(),
@@ -388,17 +491,27 @@ impl AgentInternal {
                     );
                     p.state
                         .store(CandidatePairState::Failed as u8, Ordering::SeqCst);
+                    self.event_log.record(ConnectionEventKind::IceCheckFailed);
                 } else {
-                    p.binding_request_count.fetch_add(1, Ordering::SeqCst);
-                    let local = p.local.clone();
-                    let remote = p.remote.clone();
-                    pairs.push((local, remote));
+                    let is_next = match &next_pair {
+                        Some(next) => {
+                            binding_request_count
+                                < next.binding_request_count.load(Ordering::SeqCst)
+                        }
+                        None => true,
+                    };
+                    if is_next {
+                        next_pair = Some(Arc::clone(p));
+                    }
                 }
             }
         }
 
-        for (local, remote) in pairs {
-            self.ping_candidate(&local, &remote).await;
+        if let Some(p) = next_pair {
+            p.state
+                .store(CandidatePairState::InProgress as u8, Ordering::SeqCst);
+            p.binding_request_count.fetch_add(1, Ordering::SeqCst);
+            self.ping_candidate(&p.local, &p.remote).await;
         }
     }
 
@@ -413,10 +526,224 @@ impl AgentInternal {
             remote,
             self.is_controlling.load(Ordering::SeqCst),
         ));
+
+        let protected = self.protected_pairs().await;
         let mut checklist = self.agent_conn.checklist.lock().await;
+        if checklist.len() >= self.max_candidate_pairs {
+            let lowest = checklist
+                .iter()
+                .enumerate()
+                .filter(|(_, q)| {
+                    !q.nominated.load(Ordering::SeqCst)
+                        && !protected.iter().any(|r| Arc::ptr_eq(q, r))
+                })
+                .min_by_key(|(_, q)| q.priority())
+                .map(|(i, q)| (i, q.priority()));
+
+            let dropped = self.dropped_candidate_pairs.fetch_add(1, Ordering::SeqCst) + 1;
+            match lowest {
+                Some((i, priority)) if priority < p.priority() => {
+                    let evicted = checklist.remove(i);
+                    log::warn!(
+                        "[{}]: candidate pair limit {} reached, evicting {} ({} dropped so far)",
+                        self.get_name(),
+                        self.max_candidate_pairs,
+                        evicted,
+                        dropped
+                    );
+                }
+                _ => {
+                    log::warn!(
+                        "[{}]: candidate pair limit {} reached, dropping {} ({} dropped so far)",
+                        self.get_name(),
+                        self.max_candidate_pairs,
+                        p,
+                        dropped
+                    );
+                    return;
+                }
+            }
+        }
         checklist.push(p);
     }
 
+    /// Tells whether a local and a remote candidate of the same network type make a candidate
+    /// pair. A TCP pair needs a connection, so only the candidate of a connection an active
+    /// candidate opened is paired, with the passive candidate it was opened to (RFC 6544
+    /// Section 6.2).
+    async fn can_pair(
+        local: &Arc<dyn Candidate + Send + Sync>,
+        remote: &Arc<dyn Candidate + Send + Sync>,
+    ) -> bool {
+        if !local.network_type().is_tcp() {
+            return true;
+        }
+
+        match local.get_conn() {
+            Some(conn) => {
+                remote.tcp_type() == TcpType::Passive
+                    && conn.remote_addr().await == Some(remote.addr().await)
+            }
+            None => false,
+        }
+    }
+
+    /// Connects each active TCP candidate that has no connection to the remote passive TCP
+    /// candidate yet. Every connection is checked as a local candidate of its own, which is
+    /// paired with that remote candidate only and never advertised.
+    pub(crate) async fn connect_active_tcp(
+        self: &Arc<Self>,
+        remote: &Arc<dyn Candidate + Send + Sync>,
+    ) {
+        if remote.tcp_type() != TcpType::Passive {
+            return;
+        }
+        let network_type = remote.network_type();
+        let remote_addr = remote.addr().await;
+
+        // the remote candidate limit may have turned it away
+        if self
+            .find_remote_candidate(network_type, remote_addr)
+            .await
+            .is_none()
+        {
+            return;
+        }
+
+        let local_cands = {
+            let local_candidates = self.local_candidates.lock().await;
+            local_candidates
+                .get(&network_type)
+                .cloned()
+                .unwrap_or_default()
+        };
+
+        let mut active_ips = vec![];
+        let mut connected_ips = HashSet::new();
+        for c in &local_cands {
+            let ip = c.addr().await.ip();
+            match c.get_conn() {
+                Some(conn) => {
+                    if conn.remote_addr().await == Some(remote_addr) {
+                        connected_ips.insert(ip);
+                    }
+                }
+                None => {
+                    if c.tcp_type() == TcpType::Active {
+                        active_ips.push(ip);
+                    }
+                }
+            }
+        }
+
+        for ip in active_ips {
+            if !connected_ips.contains(&ip) {
+                self.add_active_tcp_connection(ip, remote).await;
+            }
+        }
+    }
+
+    async fn add_active_tcp_connection(
+        self: &Arc<Self>,
+        ip: IpAddr,
+        remote: &Arc<dyn Candidate + Send + Sync>,
+    ) {
+        let remote_addr = remote.addr().await;
+        let conn = match ActiveTcpConn::connect(ip, remote_addr, &self.spawner) {
+            Ok(conn) => conn,
+            Err(err) => {
+                log::warn!(
+                    "[{}]: could not connect {} {} to {}: {}",
+                    self.get_name(),
+                    TCP,
+                    Redacted(&ip),
+                    remote,
+                    err
+                );
+                return;
+            }
+        };
+        let port = match conn.local_addr().await {
+            Ok(addr) => addr.port(),
+            Err(err) => {
+                log::warn!("[{}]: could not get local addr: {}", self.get_name(), err);
+                return;
+            }
+        };
+
+        let host_config = CandidateHostConfig {
+            base_config: CandidateBaseConfig {
+                network: TCP.to_owned(),
+                address: ip.to_string(),
+                port,
+                component: COMPONENT_RTP,
+                tcp_type: TcpType::Active,
+                conn: Some(Arc::new(conn)),
+                candidate_id: generate_cand_id(&self.rng),
+                ..CandidateBaseConfig::default()
+            },
+        };
+        let c: Arc<dyn Candidate + Send + Sync> = match host_config.new_candidate_host().await {
+            Ok(c) => Arc::new(c),
+            Err(err) => {
+                log::warn!(
+                    "[{}]: Failed to create active TCP candidate: {} {}: {}",
+                    self.get_name(),
+                    TCP,
+                    Redacted(&ip),
+                    err
+                );
+                return;
+            }
+        };
+        log::debug!("[{}]: connecting {} to {}", self.get_name(), c, remote);
+
+        {
+            let mut unadvertised_candidates = self.unadvertised_candidates.lock().await;
+            unadvertised_candidates.insert(c.id());
+        }
+
+        let initialized_ch = {
+            let started_ch_tx = self.started_ch_tx.lock().await;
+            (*started_ch_tx).as_ref().map(|tx| tx.subscribe())
+        };
+        self.start_candidate(&c, initialized_ch).await;
+
+        {
+            let mut local_candidates = self.local_candidates.lock().await;
+            local_candidates
+                .entry(c.network_type())
+                .or_insert_with(Vec::new)
+                .push(c.clone());
+        }
+        {
+            let mut candidate_records = self.candidate_records.lock().await;
+            candidate_records.record(&*c, true);
+        }
+
+        self.add_pair(c, remote.clone()).await;
+        self.request_connectivity_check();
+    }
+
+    /// protected_pairs returns the pairs that must never be evicted by the candidate and pair
+    /// caps: the selected and the nominated pair.
+    async fn protected_pairs(&self) -> Vec<Arc<CandidatePair>> {
+        let mut protected = vec![];
+        {
+            let selected_pair = self.agent_conn.selected_pair.lock().await;
+            if let Some(p) = &*selected_pair {
+                protected.push(Arc::clone(p));
+            }
+        }
+        {
+            let nominated_pair = self.nominated_pair.lock().await;
+            if let Some(p) = &*nominated_pair {
+                protected.push(Arc::clone(p));
+            }
+        }
+        protected
+    }
+
     pub(crate) async fn find_pair(
         &self,
         local: &Arc<dyn Candidate + Send + Sync>,
@@ -439,9 +766,8 @@ impl AgentInternal {
             (*selected_pair).as_ref().map_or_else(
                 || (false, Duration::from_secs(0)),
                 |selected_pair| {
-                    let disconnected_time = SystemTime::now()
-                        .duration_since(selected_pair.remote.last_received())
-                        .unwrap_or_else(|_| Duration::from_secs(0));
+                    let disconnected_time = Instant::now()
+                        .saturating_duration_since(selected_pair.remote.last_received());
                     (true, disconnected_time)
                 },
             )
@@ -472,7 +798,7 @@ impl AgentInternal {
         valid
     }
 
-    /// Sends STUN Binding Indications to the selected pair.
+    /// Sends a keepalive of keepalive_kind to the selected pair
     /// if no packet has been sent on that pair in the last keepaliveInterval.
     /// Note: the caller should hold the agent lock.
     pub(crate) async fn check_keepalive(&self) {
@@ -489,26 +815,55 @@ impl AgentInternal {
         };
 
         if let (Some(local), Some(remote)) = (local, remote) {
-            let last_sent = SystemTime::now()
-                .duration_since(local.last_sent())
-                .unwrap_or_else(|_| Duration::from_secs(0));
-
-            let last_received = SystemTime::now()
-                .duration_since(remote.last_received())
-                .unwrap_or_else(|_| Duration::from_secs(0));
+            let last_sent = Instant::now().saturating_duration_since(local.last_sent());
+            let last_received = Instant::now().saturating_duration_since(remote.last_received());
 
             if (self.keepalive_interval != Duration::from_secs(0))
                 && ((last_sent > self.keepalive_interval)
                     || (last_received > self.keepalive_interval))
             {
-                // we use binding request instead of indication to support refresh consent schemas
-                // see https://tools.ietf.org/html/rfc7675
-                self.ping_candidate(&local, &remote).await;
+                match self.keepalive_kind {
+                    KeepaliveKind::BindingIndication => {
+                        self.keepalive_candidate(&local, &remote).await;
+                    }
+                    // a binding request is answered, which supports refresh consent schemas
+                    // see https://tools.ietf.org/html/rfc7675
+                    KeepaliveKind::BindingRequest => self.ping_candidate(&local, &remote).await,
+                }
             }
         }
     }
 
-    fn request_connectivity_check(&self) {
+    /// Sends a STUN Binding Indication to remote, it is not answered.
+    async fn keepalive_candidate(
+        &self,
+        local: &Arc<dyn Candidate + Send + Sync>,
+        remote: &Arc<dyn Candidate + Send + Sync>,
+    ) {
+        let (msg, result) = {
+            let ufrag_pwd = self.ufrag_pwd.lock().await;
+            let username = ufrag_pwd.remote_ufrag.clone() + ":" + ufrag_pwd.local_ufrag.as_str();
+            let mut msg = Message::new();
+            let result = msg.build(&[
+                Box::new(BINDING_INDICATION),
+                Box::new(TransactionId::new()),
+                Box::new(Username::new(ATTR_USERNAME, username)),
+                Box::new(MessageIntegrity::new_short_term_integrity(
+                    ufrag_pwd.remote_pwd.clone(),
+                )),
+                Box::new(FINGERPRINT),
+            ]);
+            (msg, result)
+        };
+
+        if let Err(err) = result {
+            log::error!("{}", err);
+        } else {
+            self.send_stun(&msg, local, remote).await;
+        }
+    }
+
+    pub(crate) fn request_connectivity_check(&self) {
         let _ = self.force_candidate_contact_tx.try_send(true);
     }
 
@@ -517,6 +872,7 @@ impl AgentInternal {
         let network_type = c.network_type();
         log::debug!("adding a remote candidate {c} {network_type:?}");
 
+        let protected = self.protected_pairs().await;
         {
             let mut remote_candidates = self.remote_candidates.lock().await;
             if let Some(cands) = remote_candidates.get(&network_type) {
@@ -524,6 +880,68 @@ impl AgentInternal {
                     if cand.equal(&**c) {
                         return;
                     }
+
+                    // a peer reflexive candidate learned from a check becomes the candidate
+                    // signaled for its address later, keeping its pairs (RFC 8445 7.3.1.3)
+                    if cand.candidate_type() == CandidateType::PeerReflexive
+                        && c.candidate_type() != CandidateType::PeerReflexive
+                        && cand.address() == c.address()
+                        && cand.port() == c.port()
+                    {
+                        log::debug!(
+                            "[{}]: peer reflexive candidate {} is signaled as {}",
+                            self.get_name(),
+                            cand,
+                            c.candidate_type()
+                        );
+                        cand.promote(&**c);
+                        let mut candidate_records = self.candidate_records.lock().await;
+                        candidate_records.record(&**cand, false);
+                        return;
+                    }
+                }
+            }
+
+            let count: usize = remote_candidates.values().map(|cands| cands.len()).sum();
+            if count >= self.max_remote_candidates {
+                let lowest = remote_candidates
+                    .iter()
+                    .flat_map(|(nt, cands)| cands.iter().enumerate().map(move |(i, r)| (*nt, i, r)))
+                    .filter(|(_, _, r)| !protected.iter().any(|p| p.remote.equal(&***r)))
+                    .min_by_key(|(_, _, r)| r.priority())
+                    .map(|(nt, i, r)| (nt, i, r.priority()));
+
+                let dropped = self
+                    .dropped_remote_candidates
+                    .fetch_add(1, Ordering::SeqCst)
+                    + 1;
+                match lowest {
+                    Some((nt, i, priority)) if priority < c.priority() => {
+                        let evicted = match remote_candidates.get_mut(&nt) {
+                            Some(cands) => cands.remove(i),
+                            None => return,
+                        };
+                        log::warn!(
+                            "[{}]: remote candidate limit {} reached, evicting {} ({} dropped so far)",
+                            self.get_name(),
+                            self.max_remote_candidates,
+                            evicted,
+                            dropped
+                        );
+
+                        let mut checklist = self.agent_conn.checklist.lock().await;
+                        checklist.retain(|p| !p.remote.equal(&*evicted));
+                    }
+                    _ => {
+                        log::warn!(
+                            "[{}]: remote candidate limit {} reached, dropping {} ({} dropped so far)",
+                            self.get_name(),
+                            self.max_remote_candidates,
+                            c,
+                            dropped
+                        );
+                        return;
+                    }
                 }
             }
 
@@ -533,6 +951,10 @@ impl AgentInternal {
                 remote_candidates.insert(network_type, vec![c.clone()]);
             }
         }
+        {
+            let mut candidate_records = self.candidate_records.lock().await;
+            candidate_records.record(&**c, false);
+        }
 
         let mut local_cands = vec![];
         {
@@ -543,7 +965,9 @@ impl AgentInternal {
         }
 
         for cand in local_cands {
-            self.add_pair(cand, c.clone()).await;
+            if Self::can_pair(&cand, c).await {
+                self.add_pair(cand, c.clone()).await;
+            }
         }
 
         self.request_connectivity_check();
@@ -553,6 +977,29 @@ impl AgentInternal {
         self: &Arc<Self>,
         c: &Arc<dyn Candidate + Send + Sync>,
     ) -> Result<()> {
+        let disposition = match &*self.candidate_filter {
+            Some(filter) => filter(c),
+            None => CandidateDisposition::UseAndAdvertise,
+        };
+        if disposition == CandidateDisposition::Drop {
+            log::debug!("[{}]: candidate filter dropped {}", self.get_name(), c);
+            if let Err(err) = c.close().await {
+                log::warn!(
+                    "[{}]: Failed to close filtered candidate: {}",
+                    self.get_name(),
+                    err
+                );
+            }
+            return Ok(());
+        }
+        if disposition == CandidateDisposition::UseButDontAdvertise {
+            // recorded before the candidate becomes visible, so that it never makes it into
+            // an SDP built meanwhile
+            log::debug!("[{}]: not advertising {}", self.get_name(), c);
+            let mut unadvertised_candidates = self.unadvertised_candidates.lock().await;
+            unadvertised_candidates.insert(c.id());
+        }
+
         let initialized_ch = {
             let started_ch_tx = self.started_ch_tx.lock().await;
             (*started_ch_tx).as_ref().map(|tx| tx.subscribe())
@@ -586,6 +1033,10 @@ impl AgentInternal {
                 local_candidates.insert(network_type, vec![c.clone()]);
             }
         }
+        {
+            let mut candidate_records = self.candidate_records.lock().await;
+            candidate_records.record(&**c, true);
+        }
 
         let mut remote_cands = vec![];
         {
@@ -595,17 +1046,34 @@ impl AgentInternal {
             }
         }
 
-        for cand in remote_cands {
-            log::debug!("adding remote {}", c);
-            self.add_pair(c.clone(), cand).await;
+        for cand in &remote_cands {
+            if Self::can_pair(c, cand).await {
+                log::debug!("adding remote {}", c);
+                self.add_pair(c.clone(), cand.clone()).await;
+            }
+        }
+
+        // a new active TCP candidate connects to the passive candidates already known
+        if c.tcp_type() == TcpType::Active && c.get_conn().is_none() {
+            for cand in &remote_cands {
+                self.connect_active_tcp(cand).await;
+            }
         }
 
         self.request_connectivity_check();
-        {
+
+        if disposition == CandidateDisposition::UseButDontAdvertise {
+            return Ok(());
+        }
+
+        // the sender is cloned out so that the lock is not held while the on_candidate handler
+        // catches up, the handler may close the agent
+        let chan_candidate_tx = {
             let chan_candidate_tx = self.chan_candidate_tx.lock().await;
-            if let Some(tx) = &*chan_candidate_tx {
-                let _ = tx.send(Some(c.clone())).await;
-            }
+            chan_candidate_tx.clone()
+        };
+        if let Some(tx) = chan_candidate_tx {
+            let _ = tx.send(Some(c.clone())).await;
         }
 
         Ok(())
@@ -620,6 +1088,18 @@ impl AgentInternal {
             done_tx.take();
         };
         self.delete_all_candidates().await;
+        {
+            let mut checklist = self.agent_conn.checklist.lock().await;
+            checklist.clear();
+        }
+        {
+            let mut selected_pair = self.agent_conn.selected_pair.lock().await;
+            selected_pair.take();
+        }
+        {
+            let mut nominated_pair = self.nominated_pair.lock().await;
+            nominated_pair.take();
+        }
         {
             let mut started_ch_tx = self.started_ch_tx.lock().await;
             started_ch_tx.take();
@@ -668,6 +1148,10 @@ impl AgentInternal {
             }
             local_candidates.clear();
         }
+        {
+            let mut unadvertised_candidates = self.unadvertised_candidates.lock().await;
+            unadvertised_candidates.clear();
+        }
 
         {
             let mut remote_candidates = self.remote_candidates.lock().await;
@@ -727,6 +1211,9 @@ impl AgentInternal {
                 transaction_id: m.transaction_id,
                 destination: remote.addr().await,
                 is_use_candidate: m.contains(ATTR_USE_CANDIDATE),
+                is_controlling: m.contains(ATTR_ICE_CONTROLLING),
+                local: Arc::clone(local),
+                remote: Arc::clone(remote),
             });
         }
 
@@ -771,6 +1258,162 @@ impl AgentInternal {
         }
     }
 
+    /// Sends a binding error response carrying `code` for the request `m`.
+    pub(crate) async fn send_binding_error(
+        &self,
+        m: &Message,
+        code: ErrorCode,
+        local: &Arc<dyn Candidate + Send + Sync>,
+        remote: &Arc<dyn Candidate + Send + Sync>,
+    ) {
+        let local_pwd = {
+            let ufrag_pwd = self.ufrag_pwd.lock().await;
+            ufrag_pwd.local_pwd.clone()
+        };
+
+        let (out, result) = {
+            let mut out = Message::new();
+            let result = out.build(&[
+                Box::new(m.clone()),
+                Box::new(BINDING_ERROR),
+                Box::new(code),
+                Box::new(MessageIntegrity::new_short_term_integrity(local_pwd)),
+                Box::new(FINGERPRINT),
+            ]);
+            (out, result)
+        };
+
+        if let Err(err) = result {
+            log::warn!(
+                "[{}]: Failed to build error response from: {} to: {} error: {}",
+                self.get_name(),
+                local,
+                remote,
+                err
+            );
+        } else {
+            self.send_stun(&out, local, remote).await;
+        }
+    }
+
+    /// Detects and repairs a role conflict signalled by an inbound binding request. Returns false
+    /// if the request was answered with a 487 (Role Conflict) and must not be processed further.
+    ///
+    /// reference: (IETF ref-8445)[https://tools.ietf.org/html/rfc8445#section-7.3.1.1].
+    async fn repair_role_conflict(
+        &self,
+        m: &Message,
+        local: &Arc<dyn Candidate + Send + Sync>,
+        remote: &Arc<dyn Candidate + Send + Sync>,
+    ) -> bool {
+        let is_controlling = self.is_controlling.load(Ordering::SeqCst);
+        let remote_tie_breaker = if is_controlling {
+            let mut attr = AttrControlling::default();
+            if attr.get_from(m).is_err() {
+                return true;
+            }
+            attr.0
+        } else {
+            let mut attr = AttrControlled::default();
+            if attr.get_from(m).is_err() {
+                return true;
+            }
+            attr.0
+        };
+
+        // The agent with the larger tie-breaker value keeps (or takes) the controlling role.
+        let tie_breaker = self.tie_breaker.load(Ordering::SeqCst);
+        let keep_role = if is_controlling {
+            tie_breaker >= remote_tie_breaker
+        } else {
+            tie_breaker < remote_tie_breaker
+        };
+
+        if keep_role {
+            log::debug!(
+                "[{}]: role conflict with {}, keeping role, isControlling: {}",
+                self.get_name(),
+                remote,
+                is_controlling
+            );
+            self.send_binding_error(m, CODE_ROLE_CONFLICT, local, remote)
+                .await;
+            false
+        } else {
+            self.switch_role(!is_controlling).await;
+            true
+        }
+    }
+
+    /// Handles a binding error response to one of our checks. A 487 (Role Conflict) means the
+    /// remote agent kept its role, so ours is switched and the pair is checked again.
+    ///
+    /// reference: (IETF ref-8445)[https://tools.ietf.org/html/rfc8445#section-7.2.5.1].
+    async fn handle_error_response(&self, m: &Message, remote: SocketAddr) {
+        let mut error_code = ErrorCodeAttribute::default();
+        if let Err(err) = error_code.get_from(m) {
+            log::warn!(
+                "[{}]: discard error response from ({}), {}",
+                self.get_name(),
+                Redacted(&remote),
+                err
+            );
+            return;
+        }
+
+        let pending_request = match self.handle_inbound_binding_success(m.transaction_id).await {
+            Some(pending_request) => pending_request,
+            None => {
+                log::warn!(
+                    "[{}]: discard error response from ({}), unknown TransactionID 0x{:?}",
+                    self.get_name(),
+                    Redacted(&remote),
+                    m.transaction_id
+                );
+                return;
+            }
+        };
+
+        self.report_check_result(&pending_request, false);
+        if error_code.code == CODE_ROLE_CONFLICT {
+            self.switch_role(!pending_request.is_controlling).await;
+        } else {
+            log::debug!(
+                "[{}]: binding error response from ({}): {}",
+                self.get_name(),
+                Redacted(&remote),
+                error_code
+            );
+        }
+    }
+
+    /// Switches the agent to the given role, restarting nomination under the new role.
+    pub(crate) async fn switch_role(&self, is_controlling: bool) {
+        if self.is_controlling.swap(is_controlling, Ordering::SeqCst) == is_controlling {
+            return;
+        }
+
+        log::info!(
+            "[{}]: ICE role switched, isControlling: {}",
+            self.get_name(),
+            is_controlling
+        );
+
+        {
+            let checklist = self.agent_conn.checklist.lock().await;
+            for p in &*checklist {
+                p.ice_role_controlling
+                    .store(is_controlling, Ordering::SeqCst);
+                p.nominated.store(false, Ordering::SeqCst);
+            }
+        }
+        {
+            let mut nominated_pair = self.nominated_pair.lock().await;
+            *nominated_pair = None;
+        }
+        self.start().await;
+    }
+
     /// Removes pending binding requests that are over `maxBindingRequestTimeout` old Let HTO be the
     /// transaction timeout, which SHOULD be 2*RTT if RTT is known or 500 ms otherwise.
     ///
@@ -787,6 +1430,8 @@ impl AgentInternal {
                 .unwrap_or(true)
             {
                 temp.push(binding_request);
+            } else {
+                self.report_check_result(&binding_request, false);
             }
         }
 
@@ -801,6 +1446,19 @@ impl AgentInternal {
         }
     }
 
+    /// Hands the outcome of a binding request to the connectivity check result callback, the
+    /// round trip time of a failed one is how long it went unanswered.
+    pub(crate) fn report_check_result(&self, binding_request: &BindingRequest, success: bool) {
+        if let Some(f) = &*self.on_connectivity_check_result {
+            f(
+                &binding_request.local,
+                &binding_request.remote,
+                binding_request.timestamp.elapsed(),
+                success,
+            );
+        }
+    }
+
     /// Assert that the passed `TransactionID` is in our `pendingBindingRequests` and returns the
     /// destination, If the bindingRequest was valid remove it from our pending cache.
     pub(crate) async fn handle_inbound_binding_success(
@@ -829,13 +1487,14 @@ impl AgentInternal {
     ) {
         if m.typ.method != METHOD_BINDING
             || !(m.typ.class == CLASS_SUCCESS_RESPONSE
+                || m.typ.class == CLASS_ERROR_RESPONSE
                 || m.typ.class == CLASS_REQUEST
                 || m.typ.class == CLASS_INDICATION)
         {
             log::trace!(
                 "[{}]: unhandled STUN from {} to {} class({}) method({})",
                 self.get_name(),
-                remote,
+                Redacted(&remote),
                 local,
                 m.typ.class,
                 m.typ.method
@@ -843,29 +1502,7 @@ impl AgentInternal {
             return;
         }
 
-        if self.is_controlling.load(Ordering::SeqCst) {
-            if m.contains(ATTR_ICE_CONTROLLING) {
-                log::debug!(
-                    "[{}]: inbound isControlling && a.isControlling == true",
-                    self.get_name(),
-                );
-                return;
-            } else if m.contains(ATTR_USE_CANDIDATE) {
-                log::debug!(
-                    "[{}]: useCandidate && a.isControlling == true",
-                    self.get_name(),
-                );
-                return;
-            }
-        } else if m.contains(ATTR_ICE_CONTROLLED) {
-            log::debug!(
-                "[{}]: inbound isControlled && a.isControlling == false",
-                self.get_name(),
-            );
-            return;
-        }
-
-        let remote_candidate = self
+        let mut remote_candidate = self
             .find_remote_candidate(local.network_type(), remote)
             .await;
         if m.typ.class == CLASS_SUCCESS_RESPONSE {
@@ -877,7 +1514,7 @@ impl AgentInternal {
                     log::warn!(
                         "[{}]: discard message from ({}), {}",
                         self.get_name(),
-                        remote,
+                        Redacted(&remote),
                         err
                     );
                     return;
@@ -885,25 +1522,68 @@ impl AgentInternal {
             }
 
             if let Some(rc) = &remote_candidate {
+                local.counters().count(m, false);
+                rc.counters().count(m, false);
                 self.handle_success_response(m, local, rc, remote).await;
             } else {
                 log::warn!(
                     "[{}]: discard success message from ({}), no such remote",
                     self.get_name(),
-                    remote
+                    Redacted(&remote)
                 );
                 return;
             }
+        } else if m.typ.class == CLASS_ERROR_RESPONSE {
+            {
+                let ufrag_pwd = self.ufrag_pwd.lock().await;
+                if let Err(err) =
+                    assert_inbound_message_integrity(m, ufrag_pwd.remote_pwd.as_bytes())
+                {
+                    log::warn!(
+                        "[{}]: discard message from ({}), {}",
+                        self.get_name(),
+                        Redacted(&remote),
+                        err
+                    );
+                    return;
+                }
+            }
+
+            local.counters().count(m, false);
+            if let Some(rc) = &remote_candidate {
+                rc.counters().count(m, false);
+            }
+            self.handle_error_response(m, remote).await;
         } else if m.typ.class == CLASS_REQUEST {
             {
                 let ufrag_pwd = self.ufrag_pwd.lock().await;
+                if ufrag_pwd.remote_ufrag.is_empty() {
+                    // only a peer that knows the local password can fill the buffer
+                    if let Err(err) = assert_inbound_username_prefix(m, &ufrag_pwd.local_ufrag)
+                        .and_then(|_| {
+                            assert_inbound_message_integrity(m, ufrag_pwd.local_pwd.as_bytes())
+                        })
+                    {
+                        log::warn!(
+                            "[{}]: discard message from ({}), {}",
+                            self.get_name(),
+                            Redacted(&remote),
+                            err
+                        );
+                        return;
+                    }
+                    drop(ufrag_pwd);
+                    self.buffer_early_check(m, local, remote).await;
+                    return;
+                }
+
                 let username =
                     ufrag_pwd.local_ufrag.clone() + ":" + ufrag_pwd.remote_ufrag.as_str();
                 if let Err(err) = assert_inbound_username(m, &username) {
                     log::warn!(
                         "[{}]: discard message from ({}), {}",
                         self.get_name(),
-                        remote,
+                        Redacted(&remote),
                         err
                     );
                     return;
@@ -913,7 +1593,7 @@ impl AgentInternal {
                     log::warn!(
                         "[{}]: discard message from ({}), {}",
                         self.get_name(),
-                        remote,
+                        Redacted(&remote),
                         err
                     );
                     return;
@@ -921,18 +1601,34 @@ impl AgentInternal {
             }
 
             if remote_candidate.is_none() {
-                log::error!("[{}]: No remote candidate!", self.get_name(),);
-                return;
+                remote_candidate = self.add_peer_reflexive_candidate(m, local, remote).await;
+                if remote_candidate.is_none() {
+                    return;
+                }
             }
 
             log::trace!(
                 "[{}]: inbound STUN (Request) from {} to {}",
                 self.get_name(),
-                remote,
+                Redacted(&remote),
                 local
             );
 
             if let Some(rc) = &remote_candidate {
+                local.counters().count(m, false);
+                rc.counters().count(m, false);
+                if !self.repair_role_conflict(m, local, rc).await {
+                    return;
+                }
+
+                if self.is_controlling.load(Ordering::SeqCst) && m.contains(ATTR_USE_CANDIDATE) {
+                    log::debug!(
+                        "[{}]: useCandidate && a.isControlling == true",
+                        self.get_name(),
+                    );
+                    return;
+                }
+
                 self.handle_binding_request(m, local, rc).await;
             }
         }
@@ -942,6 +1638,110 @@ impl AgentInternal {
         }
     }
 
+    /// Keeps an authenticated binding request that arrived before the remote credentials until
+    /// replay_early_checks, replacing an earlier one between the same addresses. The oldest
+    /// request is dropped once MAX_EARLY_CHECKS are kept.
+    async fn buffer_early_check(
+        &self,
+        m: &Message,
+        local: &Arc<dyn Candidate + Send + Sync>,
+        remote: SocketAddr,
+    ) {
+        let now = Instant::now();
+        let mut early_checks = self.early_checks.lock().await;
+        early_checks.retain(|check| {
+            now.duration_since(check.received) < EARLY_CHECK_LIFETIME
+                && !(check.remote == remote && check.local.id() == local.id())
+        });
+        if early_checks.len() >= MAX_EARLY_CHECKS {
+            early_checks.remove(0);
+        }
+        log::debug!(
+            "[{}]: keeping a binding request from {} until the remote credentials are set",
+            self.get_name(),
+            Redacted(&remote)
+        );
+        early_checks.push(EarlyCheck {
+            received: now,
+            message: m.clone(),
+            local: Arc::clone(local),
+            remote,
+        });
+    }
+
+    /// Handles the binding requests kept by buffer_early_check, once the remote credentials
+    /// are set. Requests older than EARLY_CHECK_LIFETIME are dropped, the peer has retransmitted
+    /// them by then.
+    pub(crate) async fn replay_early_checks(&self) {
+        let early_checks = {
+            let mut early_checks = self.early_checks.lock().await;
+            std::mem::take(&mut *early_checks)
+        };
+        for mut check in early_checks {
+            if check.received.elapsed() < EARLY_CHECK_LIFETIME {
+                self.handle_inbound(&mut check.message, &check.local, check.remote)
+                    .await;
+            }
+        }
+    }
+
+    /// Creates the remote candidate for an authenticated request from an address no remote
+    /// candidate has, with the priority the request carries, and pairs it with the local
+    /// candidates (RFC 8445 7.3.1.3).
+    async fn add_peer_reflexive_candidate(
+        &self,
+        m: &Message,
+        local: &Arc<dyn Candidate + Send + Sync>,
+        remote: SocketAddr,
+    ) -> Option<Arc<dyn Candidate + Send + Sync>> {
+        let mut priority = PriorityAttr::default();
+        if let Err(err) = priority.get_from(m) {
+            log::warn!(
+                "[{}]: discard message from ({}), {}",
+                self.get_name(),
+                Redacted(&remote),
+                err
+            );
+            return None;
+        }
+
+        let config = CandidatePeerReflexiveConfig {
+            base_config: CandidateBaseConfig {
+                network: local.network_type().network_short(),
+                address: remote.ip().to_string(),
+                port: remote.port(),
+                component: local.component(),
+                priority: priority.0,
+                candidate_id: generate_cand_id(&self.rng),
+                ..CandidateBaseConfig::default()
+            },
+            ..CandidatePeerReflexiveConfig::default()
+        };
+        let prflx_candidate: Arc<dyn Candidate + Send + Sync> =
+            match config.new_candidate_peer_reflexive().await {
+                Ok(c) => Arc::new(c),
+                Err(err) => {
+                    log::error!(
+                        "[{}]: failed to create new remote prflx candidate ({})",
+                        self.get_name(),
+                        err
+                    );
+                    return None;
+                }
+            };
+
+        log::debug!(
+            "[{}]: adding a new peer-reflexive candidate: {}",
+            self.get_name(),
+            Redacted(&remote)
+        );
+        self.add_remote_candidate(&prflx_candidate).await;
+
+        // the remote candidate limit may have turned it away
+        self.find_remote_candidate(local.network_type(), remote)
+            .await
+    }
+
     /// Processes non STUN traffic from a remote candidate, and returns true if it is an actual
     /// remote candidate.
     pub(crate) async fn validate_non_stun_traffic(
@@ -987,7 +1787,10 @@ impl AgentInternal {
                 self.get_name(),
                 err
             );
+            return;
         }
+        local.counters().count(msg, true);
+        remote.counters().count(msg, true);
     }
 
     /// Runs the candidate using the provided connection.
@@ -1008,12 +1811,14 @@ impl AgentInternal {
             let conn = Arc::clone(conn);
             let addr = candidate.addr().await;
             let ai = Arc::clone(self);
-            tokio::spawn(async move {
+            self.spawner.spawn(async move {
                 let _ = ai
                     .recv_loop(cand, closed_ch_rx, initialized_ch, conn, addr)
                     .await;
             });
-        } else {
+        } else if !candidate.network_type().is_tcp() {
+            // an active TCP candidate has no conn of its own, its connections are candidates
+            // of their own, see connect_active_tcp
             log::error!("[{}]: Can't start due to conn is_none", self.get_name(),);
         }
     }
@@ -1025,7 +1830,7 @@ impl AgentInternal {
         mut chan_candidate_pair_rx: mpsc::Receiver<()>,
     ) {
         let ai = Arc::clone(self);
-        tokio::spawn(async move {
+        self.spawner.spawn(async move {
             // CandidatePair and ConnectionState are usually changed at once.
             // Blocking one by the other one causes deadlock.
             while chan_candidate_pair_rx.recv().await.is_some() {
@@ -1047,7 +1852,7 @@ impl AgentInternal {
         });
 
         let ai = Arc::clone(self);
-        tokio::spawn(async move {
+        self.spawner.spawn(async move {
             loop {
                 tokio::select! {
                     opt_state = chan_state_rx.recv() => {
@@ -1102,7 +1907,9 @@ impl AgentInternal {
             }
         }
 
-        let mut buffer = vec![0_u8; RECEIVE_MTU];
+        // one byte more than receive_mtu, so that a datagram exceeding it is noticed although
+        // the socket cuts it off
+        let mut buffer = vec![0_u8; self.receive_mtu + 1];
         let mut n;
         let mut src_addr;
         loop {
@@ -1119,6 +1926,17 @@ impl AgentInternal {
                 _  = closed_ch_rx.recv() => return Err(Error::ErrClosed),
             }
 
+            if n > self.receive_mtu {
+                self.oversized_datagrams.fetch_add(1, Ordering::SeqCst);
+                log::debug!(
+                    "[{}]: dropped a datagram from {} exceeding the receive MTU of {} bytes",
+                    self.get_name(),
+                    Redacted(&src_addr),
+                    self.receive_mtu
+                );
+                continue;
+            }
+
             self.handle_inbound_candidate_msg(&candidate, &buffer[..n], src_addr, addr)
                 .await;
         }
@@ -1143,8 +1961,8 @@ impl AgentInternal {
                 log::warn!(
                     "[{}]: Failed to handle decode ICE from {} to {}: {}",
                     self.get_name(),
-                    addr,
-                    src_addr,
+                    Redacted(&addr),
+                    Redacted(&src_addr),
                     err
                 );
             } else {
@@ -0,0 +1,182 @@
+use super::*;
+use crate::webrtc::ice::gathering_context::GatheringContext;
+use crate::webrtc::ice::util::*;
+
+use std::collections::HashSet;
+use std::net::IpAddr;
+
+impl Agent {
+    /// Polls the local interfaces every `interval` so that the agent survives its local
+    /// addresses changing under it, e.g. a laptop moving to another WiFi network.
+    ///
+    /// Candidates on addresses that went away are closed together with their pairs and host
+    /// candidates are gathered on the new ones. Those are reported through on_candidate for
+    /// trickling and checked against the remote candidates already known. If the selected pair
+    /// was lost the agent goes back to checking, or to disconnected if the remote did not
+    /// announce trickle ICE and cannot learn about the new candidates without an ICE restart.
+    /// Checks carry on in both cases, so a remote that answers them from an unknown address,
+    /// like an ice-lite server, gets the connection back to connected on its own.
+    ///
+    /// Every poll also refreshes the interfaces cached in gathering_context, so agents created
+    /// after a change do not gather on a stale list.
+    pub(crate) fn start_network_monitor(
+        &self,
+        interval: Duration,
+        gathering_context: Option<GatheringContext>,
+    ) {
+        let ai = Arc::clone(&self.internal);
+        let net = Arc::clone(&self.net);
+        let interface_filter = Arc::clone(&self.interface_filter);
+        let network_types = self.network_types.clone();
+        let dscp = self.dscp;
+        let mdns_mode = self.mdns_mode;
+        let mdns_name = self.mdns_name.clone();
+        let ext_ip_mapper = Arc::clone(&self.ext_ip_mapper);
+        let gathering_state = Arc::clone(&self.gathering_state);
+
+        self.internal.spawner.spawn(async move {
+            let mut known: Option<HashSet<IpAddr>> = None;
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+
+                {
+                    let done_tx = ai.done_tx.lock().await;
+                    if done_tx.is_none() {
+                        return;
+                    }
+                }
+
+                // Until gathering starts there is nothing to re-gather, the first gathering
+                // enumerates the interfaces itself
+                if GatheringState::from(gathering_state.load(Ordering::SeqCst))
+                    == GatheringState::New
+                {
+                    known = None;
+                    continue;
+                }
+
+                let interfaces = net.poll_interfaces().await;
+                if let Some(gathering_context) = &gathering_context {
+                    if !net.is_virtual() {
+                        gathering_context.update(interfaces.clone());
+                    }
+                }
+                let current = filter_interfaces(interfaces, &interface_filter, &network_types);
+                let previous = match known.replace(current.clone()) {
+                    Some(previous) => previous,
+                    None => continue,
+                };
+                if previous == current {
+                    continue;
+                }
+
+                let gone: HashSet<IpAddr> = previous.difference(&current).copied().collect();
+                let new: Vec<IpAddr> = current.difference(&previous).copied().collect();
+                log::info!(
+                    "[{}]: local addresses changed, gone: {:?}, new: {:?}",
+                    ai.get_name(),
+                    gone.iter().map(Redacted).collect::<Vec<_>>(),
+                    new.iter().map(Redacted).collect::<Vec<_>>()
+                );
+
+                let lost_selected_pair = ai.delete_local_candidates(&gone).await;
+
+                let host_conns = Self::listen_host_conns(&net, new.clone(), dscp, &ai).await;
+                Self::add_host_candidates(host_conns, mdns_mode, &mdns_name, &ext_ip_mapper, &ai)
+                    .await;
+                Self::add_active_tcp_candidates(new, &network_types, mdns_mode, &mdns_name, &ai)
+                    .await;
+
+                if lost_selected_pair {
+                    if ai.remote_trickle.load(Ordering::SeqCst) {
+                        ai.update_connection_state(ConnectionState::Checking).await;
+                    } else {
+                        log::warn!(
+                            "[{}]: selected candidate pair lost and the remote does not trickle, an ICE restart is needed",
+                            ai.get_name()
+                        );
+                        ai.update_connection_state(ConnectionState::Disconnected)
+                            .await;
+                    }
+                }
+
+                ai.request_connectivity_check();
+            }
+        });
+    }
+}
+
+impl AgentInternal {
+    /// Closes and removes the local candidates on the given addresses, along with their
+    /// candidate pairs. Returns whether the selected pair was one of them.
+    pub(crate) async fn delete_local_candidates(&self, ips: &HashSet<IpAddr>) -> bool {
+        if ips.is_empty() {
+            return false;
+        }
+
+        let mut deleted = vec![];
+        {
+            let mut local_candidates = self.local_candidates.lock().await;
+            for cs in local_candidates.values_mut() {
+                let mut kept = vec![];
+                for c in cs.drain(..) {
+                    if ips.contains(&c.addr().await.ip()) {
+                        deleted.push(c);
+                    } else {
+                        kept.push(c);
+                    }
+                }
+                *cs = kept;
+            }
+        }
+
+        if deleted.is_empty() {
+            return false;
+        }
+
+        {
+            let mut unadvertised_candidates = self.unadvertised_candidates.lock().await;
+            for c in &deleted {
+                unadvertised_candidates.remove(&c.id());
+            }
+        }
+
+        for c in &deleted {
+            if let Err(err) = c.close().await {
+                log::warn!(
+                    "[{}]: Failed to close candidate {}: {}",
+                    self.get_name(),
+                    c,
+                    err
+                );
+            }
+        }
+
+        let is_deleted = |p: &Arc<CandidatePair>| deleted.iter().any(|c| c.equal(&*p.local));
+
+        {
+            let mut checklist = self.agent_conn.checklist.lock().await;
+            checklist.retain(|p| !is_deleted(p));
+        }
+
+        {
+            let mut nominated_pair = self.nominated_pair.lock().await;
+            if let Some(p) = &*nominated_pair {
+                if is_deleted(p) {
+                    *nominated_pair = None;
+                }
+            }
+        }
+
+        let lost_selected_pair = match self.agent_conn.get_selected_pair().await {
+            Some(p) => is_deleted(&p),
+            None => false,
+        };
+        if lost_selected_pair {
+            self.set_selected_pair(None).await;
+        }
+
+        lost_selected_pair
+    }
+}
@@ -1,8 +1,13 @@
+use crate::webrtc::ice::agent::agent_config::TCP_ACCEPTANCE_MIN_WAIT;
 use crate::webrtc::ice::agent::agent_internal::*;
 use crate::webrtc::ice::candidate::*;
 use crate::webrtc::ice::control::*;
+use crate::webrtc::ice::error::*;
+use crate::webrtc::ice::nomination::*;
 use crate::webrtc::ice::priority::*;
 use crate::webrtc::ice::use_candidate::*;
+use crate::webrtc::peer_connection::event_log::ConnectionEventKind;
+use crate::webrtc::util::redaction::Redacted;
 
 use crate::webrtc::stun::{
     agent::*, attributes::*, fingerprint::*, integrity::*, message::*, textattrs::*,
@@ -73,6 +78,20 @@ impl AgentInternal {
                     .as_nanos()
                     > self.host_acceptance_min_wait.as_nanos()
             }
+            CandidateType::ServerReflexive => {
+                Instant::now()
+                    .checked_duration_since(*start_time)
+                    .unwrap_or_else(|| Duration::from_secs(0))
+                    .as_nanos()
+                    > self.srflx_acceptance_min_wait.as_nanos()
+            }
+            CandidateType::PeerReflexive => {
+                Instant::now()
+                    .checked_duration_since(*start_time)
+                    .unwrap_or_else(|| Duration::from_secs(0))
+                    .as_nanos()
+                    > self.prflx_acceptance_min_wait.as_nanos()
+            }
             _ => {
                 log::error!(
                     "is_nominatable invalid candidate type {}",
@@ -83,6 +102,25 @@ impl AgentInternal {
         }
     }
 
+    /// Tells whether a TCP pair may be nominated. That is only once no UDP pair is left to
+    /// check, so that UDP is used whenever it works even if a TCP check succeeds first.
+    async fn is_tcp_nominatable(&self) -> bool {
+        {
+            let start_time = self.start_time.lock().await;
+            if Instant::now().saturating_duration_since(*start_time) <= TCP_ACCEPTANCE_MIN_WAIT {
+                return false;
+            }
+        }
+
+        let checklist = self.agent_conn.checklist.lock().await;
+        !checklist.iter().any(|p| {
+            let state = p.state.load(Ordering::SeqCst);
+            !p.local.network_type().is_tcp()
+                && (state == CandidatePairState::Waiting as u8
+                    || state == CandidatePairState::InProgress as u8)
+        })
+    }
+
     async fn nominate_pair(&self) {
         let result = {
             let nominated_pair = self.nominated_pair.lock().await;
@@ -96,19 +134,26 @@ impl AgentInternal {
                     let ufrag_pwd = self.ufrag_pwd.lock().await;
                     let username =
                         ufrag_pwd.remote_ufrag.clone() + ":" + ufrag_pwd.local_ufrag.as_str();
-                    let mut msg = Message::new();
-                    let result = msg.build(&[
+                    let mut setters: Vec<Box<dyn Setter>> = vec![
                         Box::new(BINDING_REQUEST),
                         Box::new(TransactionId::new()),
                         Box::new(Username::new(ATTR_USERNAME, username)),
-                        Box::new(UseCandidateAttr::default()),
+                        Box::new(UseCandidateAttr),
                         Box::new(AttrControlling(self.tie_breaker.load(Ordering::SeqCst))),
                         Box::new(PriorityAttr(pair.local.priority())),
-                        Box::new(MessageIntegrity::new_short_term_integrity(
-                            ufrag_pwd.remote_pwd.clone(),
-                        )),
-                        Box::new(FINGERPRINT),
-                    ]);
+                    ];
+                    // a renominating remote selects the pair nominated with the highest value
+                    if self.remote_renomination.load(Ordering::SeqCst) {
+                        setters.push(Box::new(NominationAttr(
+                            self.nomination.load(Ordering::SeqCst),
+                        )));
+                    }
+                    setters.push(Box::new(MessageIntegrity::new_short_term_integrity(
+                        ufrag_pwd.remote_pwd.clone(),
+                    )));
+                    setters.push(Box::new(FINGERPRINT));
+                    let mut msg = Message::new();
+                    let result = msg.build(&setters);
                     (msg, result)
                 };
 
@@ -135,6 +180,49 @@ impl AgentInternal {
         }
     }
 
+    /// Pins the pair of the given candidate ids as the selected pair, in place of the one
+    /// nomination picked or would pick. The pair must have passed a connectivity check. It is
+    /// nominated to the remote, which a remote that already selected a pair ignores unless it
+    /// announced renomination.
+    pub(crate) async fn select_candidate_pair(
+        &self,
+        local_id: &str,
+        remote_id: &str,
+    ) -> Result<()> {
+        if !self.is_controlling.load(Ordering::SeqCst) {
+            return Err(Error::ErrSelectPairNotControlling);
+        }
+
+        let pair = {
+            let checklist = self.agent_conn.checklist.lock().await;
+            checklist
+                .iter()
+                .find(|p| p.local.id() == local_id && p.remote.id() == remote_id)
+                .cloned()
+        };
+        let pair = pair.ok_or_else(|| {
+            Error::ErrCandidatePairNotFound(local_id.to_owned(), remote_id.to_owned())
+        })?;
+        if pair.state.load(Ordering::SeqCst) != CandidatePairState::Succeeded as u8 {
+            return Err(Error::ErrCandidatePairNotValidated(
+                local_id.to_owned(),
+                remote_id.to_owned(),
+            ));
+        }
+
+        log::debug!("[{}]: selecting candidate pair {}", self.get_name(), pair);
+        {
+            let mut nominated_pair = self.nominated_pair.lock().await;
+            if nominated_pair.is_some() {
+                self.nomination.fetch_add(1, Ordering::SeqCst);
+            }
+            *nominated_pair = Some(Arc::clone(&pair));
+        }
+        self.set_selected_pair(Some(pair)).await;
+        self.nominate_pair().await;
+        Ok(())
+    }
+
     pub(crate) async fn start(&self) {
         if self.is_controlling.load(Ordering::SeqCst) {
             ControllingSelector::start(self).await;
@@ -226,7 +314,9 @@ impl ControllingSelector for AgentInternal {
         } else {
             let has_nominated_pair =
                 if let Some(p) = self.agent_conn.get_best_valid_candidate_pair().await {
-                    self.is_nominatable(&p.local).await && self.is_nominatable(&p.remote).await
+                    self.is_nominatable(&p.local).await
+                        && self.is_nominatable(&p.remote).await
+                        && (!p.local.network_type().is_tcp() || self.is_tcp_nominatable().await)
                 } else {
                     false
                 };
@@ -295,7 +385,7 @@ impl ControllingSelector for AgentInternal {
             // Assert that NAT is not symmetric
             // https://tools.ietf.org/html/rfc8445#section-7.2.5.2.1
             if transaction_addr != remote_addr {
-                log::debug!("discard message: transaction source and destination does not match expected({}), actual({})", transaction_addr, remote);
+                log::debug!("discard message: transaction source and destination does not match expected({}), actual({})", Redacted(&transaction_addr), remote);
                 return;
             }
 
@@ -304,11 +394,16 @@ impl ControllingSelector for AgentInternal {
                 remote,
                 local
             );
+            self.report_check_result(&pending_request, true);
             let selected_pair_is_none = self.agent_conn.get_selected_pair().await.is_none();
 
             if let Some(p) = self.find_pair(local, remote).await {
                 p.state
                     .store(CandidatePairState::Succeeded as u8, Ordering::SeqCst);
+                self.event_log
+                    .record(ConnectionEventKind::IceCheckSucceeded);
+                p.local.counters().set_succeeded();
+                p.remote.counters().set_succeeded();
                 log::trace!(
                     "Found valid candidate pair: {}, p.state: {}, isUseCandidate: {}, {}",
                     p,
@@ -453,7 +548,7 @@ impl ControlledSelector for AgentInternal {
             // Assert that NAT is not symmetric
             // https://tools.ietf.org/html/rfc8445#section-7.2.5.2.1
             if transaction_addr != remote_addr {
-                log::debug!("discard message: transaction source and destination does not match expected({}), actual({})", transaction_addr, remote);
+                log::debug!("discard message: transaction source and destination does not match expected({}), actual({})", Redacted(&transaction_addr), remote);
                 return;
             }
 
@@ -462,10 +557,15 @@ impl ControlledSelector for AgentInternal {
                 remote,
                 local
             );
+            self.report_check_result(&pending_request, true);
 
             if let Some(p) = self.find_pair(local, remote).await {
                 p.state
                     .store(CandidatePairState::Succeeded as u8, Ordering::SeqCst);
+                self.event_log
+                    .record(ConnectionEventKind::IceCheckSucceeded);
+                p.local.counters().set_succeeded();
+                p.remote.counters().set_succeeded();
                 log::trace!("Found valid candidate pair: {}", p);
             } else {
                 // This shouldn't happen
@@ -491,7 +591,10 @@ impl ControlledSelector for AgentInternal {
         }
 
         if let Some(p) = self.find_pair(local, remote).await {
-            let use_candidate = m.contains(ATTR_USE_CANDIDATE);
+            // a renominating remote nominates with NOMINATION, with or without USE-CANDIDATE
+            let mut nomination = NominationAttr::default();
+            let nomination = nomination.get_from(m).ok().map(|_| nomination.0);
+            let use_candidate = m.contains(ATTR_USE_CANDIDATE) || nomination.is_some();
             if use_candidate {
                 // https://tools.ietf.org/html/rfc8445#section-7.3.1.5
 
@@ -500,8 +603,29 @@ impl ControlledSelector for AgentInternal {
                     // previously sent by this pair produced a successful response and
                     // generated a valid pair (Section 7.2.5.3.2).  The agent sets the
                     // nominated flag value of the valid pair to true.
-                    if self.agent_conn.get_selected_pair().await.is_none() {
-                        self.set_selected_pair(Some(Arc::clone(&p))).await;
+                    match nomination {
+                        // draft-thatcher-ice-renomination: the pair nominated with the
+                        // highest value so far is selected, even after another one was
+                        Some(value) => {
+                            if value > self.remote_nomination.load(Ordering::SeqCst) {
+                                self.remote_nomination.store(value, Ordering::SeqCst);
+                                let selected = self.agent_conn.get_selected_pair().await;
+                                if !selected.is_some_and(|s| Arc::ptr_eq(&s, &p)) {
+                                    log::debug!(
+                                        "[{}]: renominated candidate pair {} with {}",
+                                        self.get_name(),
+                                        p,
+                                        value
+                                    );
+                                    self.set_selected_pair(Some(Arc::clone(&p))).await;
+                                }
+                            }
+                        }
+                        None => {
+                            if self.agent_conn.get_selected_pair().await.is_none() {
+                                self.set_selected_pair(Some(Arc::clone(&p))).await;
+                            }
+                        }
                     }
                     self.send_binding_success(m, local, remote).await;
                 } else {
@@ -0,0 +1,423 @@
+use super::*;
+
+use crate::webrtc::ice::candidate::candidate_base::CandidateBaseConfig;
+use crate::webrtc::ice::candidate::candidate_host::CandidateHostConfig;
+use crate::webrtc::ice::control::AttrControlling;
+use crate::webrtc::ice::priority::PriorityAttr;
+use crate::webrtc::ice::util::assert_inbound_username_prefix;
+use crate::webrtc::stun::textattrs::Username;
+
+use tokio::net::UdpSocket;
+
+/// FLOOD_CANDIDATES is how many remote candidates the flooded agent is handed, far beyond the
+/// default caps
+const FLOOD_CANDIDATES: usize = 10_000;
+const LOCAL_CANDIDATES: usize = 8;
+
+async fn host_candidate(
+    address: String,
+    port: u16,
+    priority: u32,
+) -> Arc<dyn Candidate + Send + Sync> {
+    let c = CandidateHostConfig {
+        base_config: CandidateBaseConfig {
+            network: "udp".to_owned(),
+            address,
+            port,
+            component: COMPONENT_RTP,
+            priority,
+            ..Default::default()
+        },
+    }
+    .new_candidate_host()
+    .await
+    .unwrap();
+    Arc::new(c)
+}
+
+/// flooded_remote_candidates_stay_capped hands an agent with local candidates FLOOD_CANDIDATES
+/// remote ones. The remote candidates and the checklist have to stay within their caps, keep the
+/// highest priority candidates and count every candidate and pair beyond the caps.
+#[tokio::test]
+async fn flooded_remote_candidates_stay_capped() {
+    let agent = Agent::new(AgentConfig {
+        multicast_dns_mode: MulticastDnsMode::Disabled,
+        network_types: vec![NetworkType::Udp4],
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let ai = &agent.internal;
+
+    let mut local_candidates = vec![];
+    for i in 0..LOCAL_CANDIDATES {
+        local_candidates.push(host_candidate(format!("192.168.0.{}", i + 1), 5000, 0).await);
+    }
+    ai.local_candidates
+        .lock()
+        .await
+        .insert(NetworkType::Udp4, local_candidates);
+
+    for i in 0..FLOOD_CANDIDATES {
+        // priorities in no particular order, so that the flood both evicts and is dropped
+        let priority = (i * 7919 % FLOOD_CANDIDATES) as u32 + 1;
+        let address = format!("10.0.{}.{}", i / 250, i % 250 + 1);
+        let c = host_candidate(address, 9000, priority).await;
+        ai.add_remote_candidate(&c).await;
+    }
+
+    let remote_candidates: Vec<_> = ai
+        .remote_candidates
+        .lock()
+        .await
+        .values()
+        .flatten()
+        .cloned()
+        .collect();
+    assert_eq!(remote_candidates.len(), ai.max_remote_candidates);
+    let lowest_kept = remote_candidates
+        .iter()
+        .map(|c| c.priority())
+        .min()
+        .unwrap();
+    assert_eq!(
+        lowest_kept as usize,
+        FLOOD_CANDIDATES - ai.max_remote_candidates + 1
+    );
+
+    let checklist = ai.agent_conn.checklist.lock().await;
+    assert!(checklist.len() <= ai.max_candidate_pairs);
+    assert!(checklist
+        .iter()
+        .all(|p| remote_candidates.iter().any(|c| p.remote.equal(&**c))));
+
+    let (dropped_remote_candidates, dropped_candidate_pairs) = agent.get_dropped_candidates();
+    assert_eq!(
+        dropped_remote_candidates,
+        FLOOD_CANDIDATES - ai.max_remote_candidates
+    );
+    assert!(dropped_candidate_pairs > 0);
+    drop(checklist);
+
+    agent.close().await.unwrap();
+}
+
+/// PEER_UFRAG and PEER_PWD are the credentials the remote of the early check tests answers
+/// with, after its first requests
+const PEER_UFRAG: &str = "peer";
+const PEER_PWD: &str = "peerpasswordpeerpassword";
+
+/// early_check_agent returns an agent without remote credentials and its host candidate on a
+/// loopback UDP socket
+async fn early_check_agent() -> (Agent, Arc<dyn Candidate + Send + Sync>) {
+    loopback_agent(String::new(), String::new()).await
+}
+
+/// loopback_agent returns an agent with the local credentials ufrag and pwd, generated if empty,
+/// without remote credentials and its host candidate on a loopback UDP socket
+async fn loopback_agent(ufrag: String, pwd: String) -> (Agent, Arc<dyn Candidate + Send + Sync>) {
+    let agent = Agent::new(AgentConfig {
+        local_ufrag: ufrag,
+        local_pwd: pwd,
+        multicast_dns_mode: MulticastDnsMode::Disabled,
+        network_types: vec![NetworkType::Udp4],
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+
+    let conn = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let port = conn.local_addr().unwrap().port();
+    let local: Arc<dyn Candidate + Send + Sync> = Arc::new(
+        CandidateHostConfig {
+            base_config: CandidateBaseConfig {
+                network: "udp".to_owned(),
+                address: "127.0.0.1".to_owned(),
+                port,
+                component: COMPONENT_RTP,
+                conn: Some(Arc::new(conn)),
+                ..Default::default()
+            },
+        }
+        .new_candidate_host()
+        .await
+        .unwrap(),
+    );
+    agent
+        .internal
+        .local_candidates
+        .lock()
+        .await
+        .insert(NetworkType::Udp4, vec![Arc::clone(&local)]);
+    (agent, local)
+}
+
+/// binding_request is a connectivity check with username, signed with pwd if there is one
+fn binding_request(username: String, pwd: Option<&str>) -> Message {
+    let mut setters: Vec<Box<dyn Setter>> = vec![
+        Box::new(BINDING_REQUEST),
+        Box::new(TransactionId::new()),
+        Box::new(Username::new(ATTR_USERNAME, username)),
+        Box::new(AttrControlling(1)),
+        Box::new(PriorityAttr(1)),
+    ];
+    if let Some(pwd) = pwd {
+        setters.push(Box::new(MessageIntegrity::new_short_term_integrity(
+            pwd.to_owned(),
+        )));
+    }
+    setters.push(Box::new(FINGERPRINT));
+
+    let mut m = Message::new();
+    m.build(&setters).unwrap();
+    m
+}
+
+/// response reads the next STUN message from remote, None if there is none within a few
+/// hundred milliseconds
+async fn response(remote: &UdpSocket) -> Option<Message> {
+    let mut buf = vec![0u8; 1500];
+    let n = tokio::time::timeout(Duration::from_millis(300), remote.recv(&mut buf))
+        .await
+        .ok()?
+        .unwrap();
+    let mut m = Message::new();
+    m.raw = buf[..n].to_vec();
+    m.decode().unwrap();
+    Some(m)
+}
+
+/// early_check_is_answered_once_credentials_are_set checks that a request signed with the local
+/// password that arrives before the remote credentials is kept and answered once they are set
+#[tokio::test]
+async fn early_check_is_answered_once_credentials_are_set() {
+    let (agent, local) = early_check_agent().await;
+    let ai = &agent.internal;
+    let (local_ufrag, local_pwd) = agent.get_local_user_credentials().await;
+    let remote = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+
+    let mut m = binding_request(format!("{local_ufrag}:{PEER_UFRAG}"), Some(&local_pwd));
+    ai.handle_inbound(&mut m, &local, remote.local_addr().unwrap())
+        .await;
+    assert_eq!(ai.early_checks.lock().await.len(), 1);
+    assert!(response(&remote).await.is_none());
+
+    ai.set_remote_credentials(PEER_UFRAG.to_owned(), PEER_PWD.to_owned())
+        .await
+        .unwrap();
+    ai.replay_early_checks().await;
+
+    let answer = response(&remote)
+        .await
+        .expect("the early check was not answered");
+    assert_eq!(answer.typ, BINDING_SUCCESS);
+    assert_eq!(answer.transaction_id, m.transaction_id);
+    assert!(ai.early_checks.lock().await.is_empty());
+
+    agent.close().await.unwrap();
+}
+
+/// unauthenticated_early_checks_are_not_kept checks that only a request naming the local ufrag
+/// and signed with the local password may fill the early check buffer
+#[tokio::test]
+async fn unauthenticated_early_checks_are_not_kept() {
+    let (agent, local) = early_check_agent().await;
+    let ai = &agent.internal;
+    let (local_ufrag, local_pwd) = agent.get_local_user_credentials().await;
+    let remote = SocketAddr::from(([127, 0, 0, 1], 10000));
+
+    let requests = [
+        binding_request(format!("{local_ufrag}:{PEER_UFRAG}"), None),
+        binding_request(format!("{local_ufrag}:{PEER_UFRAG}"), Some(PEER_PWD)),
+        binding_request(format!("other:{PEER_UFRAG}"), Some(&local_pwd)),
+        binding_request(format!("{local_ufrag}:"), Some(&local_pwd)),
+    ];
+    for mut m in requests {
+        ai.handle_inbound(&mut m, &local, remote).await;
+    }
+    assert!(ai.early_checks.lock().await.is_empty());
+
+    let m = binding_request(format!("other:{PEER_UFRAG}"), Some(&local_pwd));
+    assert_eq!(
+        assert_inbound_username_prefix(&m, &local_ufrag),
+        Err(Error::ErrMismatchUsername)
+    );
+
+    agent.close().await.unwrap();
+}
+
+/// early_checks_are_capped checks that the buffer keeps the latest MAX_EARLY_CHECKS requests
+/// and one request per remote address
+#[tokio::test]
+async fn early_checks_are_capped() {
+    let (agent, local) = early_check_agent().await;
+    let ai = &agent.internal;
+    let (local_ufrag, local_pwd) = agent.get_local_user_credentials().await;
+    let username = format!("{local_ufrag}:{PEER_UFRAG}");
+
+    let remotes: Vec<_> = (0..MAX_EARLY_CHECKS as u16 + 4)
+        .map(|i| SocketAddr::from(([127, 0, 0, 1], 10000 + i)))
+        .collect();
+    for remote in &remotes {
+        let mut m = binding_request(username.clone(), Some(&local_pwd));
+        ai.handle_inbound(&mut m, &local, *remote).await;
+    }
+    {
+        let early_checks = ai.early_checks.lock().await;
+        assert_eq!(early_checks.len(), MAX_EARLY_CHECKS);
+        assert!(early_checks
+            .iter()
+            .zip(&remotes[4..])
+            .all(|(check, remote)| check.remote == *remote));
+    }
+
+    let last = *remotes.last().unwrap();
+    let mut m = binding_request(username, Some(&local_pwd));
+    ai.handle_inbound(&mut m, &local, last).await;
+    let early_checks = ai.early_checks.lock().await;
+    assert_eq!(early_checks.len(), MAX_EARLY_CHECKS);
+    let from_last: Vec<_> = early_checks
+        .iter()
+        .filter(|check| check.remote == last)
+        .collect();
+    assert_eq!(from_last.len(), 1);
+    assert_eq!(from_last[0].message.transaction_id, m.transaction_id);
+    drop(early_checks);
+
+    agent.close().await.unwrap();
+}
+
+/// stale_early_checks_are_not_replayed checks that a request kept longer than
+/// EARLY_CHECK_LIFETIME is dropped instead of answered, the peer has retransmitted it by then
+#[tokio::test]
+async fn stale_early_checks_are_not_replayed() {
+    let (agent, local) = early_check_agent().await;
+    let ai = &agent.internal;
+    let (local_ufrag, local_pwd) = agent.get_local_user_credentials().await;
+    let username = format!("{local_ufrag}:{PEER_UFRAG}");
+    let (stale, fresh) = (
+        UdpSocket::bind("127.0.0.1:0").await.unwrap(),
+        UdpSocket::bind("127.0.0.1:0").await.unwrap(),
+    );
+
+    for remote in [&stale, &fresh] {
+        let mut m = binding_request(username.clone(), Some(&local_pwd));
+        ai.handle_inbound(&mut m, &local, remote.local_addr().unwrap())
+            .await;
+    }
+    {
+        let mut early_checks = ai.early_checks.lock().await;
+        let check = early_checks
+            .iter_mut()
+            .find(|check| check.remote == stale.local_addr().unwrap())
+            .unwrap();
+        check.received = Instant::now()
+            .checked_sub(EARLY_CHECK_LIFETIME + Duration::from_millis(1))
+            .unwrap();
+    }
+
+    ai.set_remote_credentials(PEER_UFRAG.to_owned(), PEER_PWD.to_owned())
+        .await
+        .unwrap();
+    ai.replay_early_checks().await;
+
+    assert!(response(&fresh).await.is_some());
+    assert!(response(&stale).await.is_none());
+
+    agent.close().await.unwrap();
+}
+
+/// LOCAL_UFRAG and LOCAL_PWD are configured local credentials of the shortest lengths RFC 8445
+/// allows
+const LOCAL_UFRAG: &str = "Ab1+";
+const LOCAL_PWD: &str = "abcdefghijklmnopqrstu/";
+
+/// invalid_credentials_are_rejected checks that the agent refuses local credentials RFC 8445
+/// does not allow, when it is created and when it is restarted
+#[tokio::test]
+async fn invalid_credentials_are_rejected() {
+    let tests = [
+        ("Ab1", LOCAL_PWD, Error::ErrLocalUfragInvalid),
+        ("Ab1-", LOCAL_PWD, Error::ErrLocalUfragInvalid),
+        ("ufrag:1", LOCAL_PWD, Error::ErrLocalUfragInvalid),
+        (
+            LOCAL_UFRAG,
+            "abcdefghijklmnopqrstu",
+            Error::ErrLocalPwdInvalid,
+        ),
+        (
+            LOCAL_UFRAG,
+            "abcdefghijklmnopqrstu=",
+            Error::ErrLocalPwdInvalid,
+        ),
+    ];
+
+    for (ufrag, pwd, want) in &tests {
+        let created = Agent::new(AgentConfig {
+            local_ufrag: ufrag.to_string(),
+            local_pwd: pwd.to_string(),
+            multicast_dns_mode: MulticastDnsMode::Disabled,
+            ..Default::default()
+        })
+        .await;
+        assert_eq!(created.err().as_ref(), Some(want), "{} {}", ufrag, pwd);
+    }
+
+    let (agent, _) = loopback_agent(LOCAL_UFRAG.to_owned(), LOCAL_PWD.to_owned()).await;
+    for (ufrag, pwd, want) in tests {
+        assert_eq!(
+            agent.restart(ufrag.to_owned(), pwd.to_owned()).await,
+            Err(want)
+        );
+        assert_eq!(
+            agent.get_local_user_credentials().await,
+            (LOCAL_UFRAG.to_owned(), LOCAL_PWD.to_owned())
+        );
+    }
+
+    agent.close().await.unwrap();
+}
+
+/// configured_credentials_authenticate_binding_requests checks that the agent answers a
+/// connectivity check signed with its configured password, signing the answer with it too, and
+/// ignores one signed with another password
+#[tokio::test]
+async fn configured_credentials_authenticate_binding_requests() {
+    let (agent, local) = loopback_agent(LOCAL_UFRAG.to_owned(), LOCAL_PWD.to_owned()).await;
+    let ai = &agent.internal;
+    assert_eq!(
+        agent.get_local_user_credentials().await,
+        (LOCAL_UFRAG.to_owned(), LOCAL_PWD.to_owned())
+    );
+    ai.set_remote_credentials(PEER_UFRAG.to_owned(), PEER_PWD.to_owned())
+        .await
+        .unwrap();
+    let remote = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let username = format!("{LOCAL_UFRAG}:{PEER_UFRAG}");
+
+    let mut m = binding_request(username.clone(), Some(PEER_PWD));
+    ai.handle_inbound(&mut m, &local, remote.local_addr().unwrap())
+        .await;
+    assert!(response(&remote).await.is_none());
+
+    let mut m = binding_request(username, Some(LOCAL_PWD));
+    ai.handle_inbound(&mut m, &local, remote.local_addr().unwrap())
+        .await;
+    let mut answer = response(&remote)
+        .await
+        .expect("the binding request was not answered");
+    assert_eq!(answer.typ, BINDING_SUCCESS);
+    assert_eq!(answer.transaction_id, m.transaction_id);
+    assert!(
+        MessageIntegrity::new_short_term_integrity(LOCAL_PWD.to_owned())
+            .check(&mut answer)
+            .is_ok()
+    );
+    assert!(
+        MessageIntegrity::new_short_term_integrity(PEER_PWD.to_owned())
+            .check(&mut answer)
+            .is_err()
+    );
+
+    agent.close().await.unwrap();
+}
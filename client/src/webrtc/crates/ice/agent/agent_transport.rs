@@ -1,6 +1,9 @@
 use super::*;
 use crate::webrtc::ice::error::*;
 
+use crate::webrtc::util::conn::send_error::{
+    SendErrorClass, SendErrorStats, TRANSIENT_BACKOFF, TRANSIENT_RETRIES, UNREACHABLE_TIMEOUT,
+};
 use crate::webrtc::util::Conn;
 use async_trait::async_trait;
 use std::io;
@@ -87,10 +90,16 @@ pub(crate) struct AgentConn {
     pub(crate) bytes_received: AtomicUsize,
     pub(crate) bytes_sent: AtomicUsize,
     pub(crate) done: AtomicBool,
+
+    // send_errors counts the datagrams send drops or retries instead of failing, and
+    // unreachable_since is when the current run of sends failing for an unreachable network
+    // began
+    pub(crate) send_errors: Arc<SendErrorStats>,
+    unreachable_since: Mutex<Option<Instant>>,
 }
 
 impl AgentConn {
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(send_errors: Arc<SendErrorStats>) -> Self {
         Self {
             selected_pair: Mutex::new(None),
             checklist: Mutex::new(vec![]),
@@ -101,8 +110,25 @@ impl AgentConn {
             bytes_received: AtomicUsize::new(0),
             bytes_sent: AtomicUsize::new(0),
             done: AtomicBool::new(false),
+            send_errors,
+            unreachable_since: Mutex::new(None),
+        }
+    }
+    /// tolerate tells whether a send error of class drops the datagram rather than failing
+    /// the connection. Unreachable is tolerated until sends failed that way for
+    /// UNREACHABLE_TIMEOUT.
+    async fn tolerate(&self, class: SendErrorClass) -> bool {
+        match class {
+            SendErrorClass::Fatal => false,
+            SendErrorClass::Unreachable => {
+                let mut unreachable_since = self.unreachable_since.lock().await;
+                let since = *unreachable_since.get_or_insert_with(Instant::now);
+                since.elapsed() < UNREACHABLE_TIMEOUT
+            }
+            _ => true,
         }
     }
+
     pub(crate) async fn get_selected_pair(&self) -> Option<Arc<CandidatePair>> {
         let selected_pair = self.selected_pair.lock().await;
         selected_pair.clone()
@@ -197,20 +223,45 @@ impl Conn for AgentConn {
             ));
         }
 
-        let result = if let Some(pair) = self.get_selected_pair().await {
-            pair.write(buf).await
-        } else if let Some(pair) = self.get_best_available_candidate_pair().await {
-            pair.write(buf).await
-        } else {
-            Ok(0)
+        let mut retries = 0;
+        let result = loop {
+            let result = if let Some(pair) = self.get_selected_pair().await {
+                pair.write(buf).await
+            } else if let Some(pair) = self.get_best_available_candidate_pair().await {
+                pair.write(buf).await
+            } else {
+                Ok(0)
+            };
+
+            match result {
+                Err(err)
+                    if retries < TRANSIENT_RETRIES
+                        && send_error_class(&err) == SendErrorClass::Transient =>
+                {
+                    self.send_errors.inc_transient_retries();
+                    tokio::time::sleep(TRANSIENT_BACKOFF * 2u32.pow(retries)).await;
+                    retries += 1;
+                }
+                result => break result,
+            }
         };
 
         match result {
             Ok(n) => {
                 self.bytes_sent.fetch_add(buf.len(), Ordering::SeqCst);
+                *self.unreachable_since.lock().await = None;
                 Ok(n)
             }
-            Err(err) => Err(io::Error::new(io::ErrorKind::Other, err.to_string()).into()),
+            Err(err) => {
+                // like a datagram lost on the way, a dropped one is reported as sent
+                let class = send_error_class(&err);
+                if !self.tolerate(class).await {
+                    return Err(io::Error::new(io::ErrorKind::Other, err.to_string()).into());
+                }
+                self.send_errors.inc(class);
+                log::debug!("dropped a datagram of {} bytes: {}", buf.len(), err);
+                Ok(buf.len())
+            }
         }
     }
 
@@ -242,3 +293,11 @@ impl Conn for AgentConn {
         Ok(())
     }
 }
+
+fn send_error_class(err: &Error) -> SendErrorClass {
+    match err {
+        Error::Io(IoError(err)) => SendErrorClass::of_io(err),
+        Error::Util(err) => SendErrorClass::of(err),
+        _ => SendErrorClass::Fatal,
+    }
+}
@@ -1,24 +1,32 @@
 pub(crate) mod agent_config;
 pub(crate) mod agent_gather;
 pub(crate) mod agent_internal;
+#[cfg(feature = "network-monitor")]
+pub(crate) mod agent_network_monitor;
 pub(crate) mod agent_selector;
 pub(crate) mod agent_transport;
 
+#[cfg(test)]
+mod agent_test;
+
+use crate::webrtc::ice::candidate::candidate_stats::CandidateRecord;
 use crate::webrtc::ice::candidate::*;
 use crate::webrtc::ice::error::*;
 use crate::webrtc::ice::external_ip_mapper::*;
 use crate::webrtc::ice::mdns::*;
 use crate::webrtc::ice::network_type::*;
+use crate::webrtc::ice::packet_capture::PacketSink;
 use crate::webrtc::ice::state::*;
+use crate::webrtc::peer_connection::event_log::{ConnectMilestone, ConnectionEventKind, EventLog};
 use agent_config::*;
 use agent_internal::*;
 
 use crate::webrtc::stun::{
-    agent::*, attributes::*, fingerprint::*, integrity::*, message::*, xoraddr::*,
+    agent::*, attributes::*, error_code::*, fingerprint::*, integrity::*, message::*, xoraddr::*,
 };
-use crate::webrtc::util::{vnet::net::*, Buffer};
+use crate::webrtc::util::{redaction::Redacted, spawner::Spawner, vnet::net::*, Buffer, Conn};
 use std::collections::HashMap;
-use std::net::{Ipv4Addr, SocketAddr};
+use std::net::{IpAddr, SocketAddr};
 
 use crate::webrtc::ice::agent::agent_gather::GatherCandidatesInternalParams;
 use crate::webrtc::ice::rand::*;
@@ -26,27 +34,29 @@ use std::future::Future;
 use std::pin::Pin;
 use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::time::SystemTime;
 use tokio::sync::{broadcast, mpsc, Mutex};
 use tokio::time::{Duration, Instant};
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub(crate) struct BindingRequest {
     pub(crate) timestamp: Instant,
     pub(crate) transaction_id: TransactionId,
     pub(crate) destination: SocketAddr,
     pub(crate) is_use_candidate: bool,
+    pub(crate) is_controlling: bool,
+    pub(crate) local: Arc<dyn Candidate + Send + Sync>,
+    pub(crate) remote: Arc<dyn Candidate + Send + Sync>,
 }
 
-impl Default for BindingRequest {
-    fn default() -> Self {
-        Self {
-            timestamp: Instant::now(),
-            transaction_id: TransactionId::default(),
-            destination: SocketAddr::new(Ipv4Addr::new(0, 0, 0, 0).into(), 0),
-            is_use_candidate: false,
-        }
-    }
+/// EarlyCheck is a binding request that arrived before the remote credentials were known, e.g.
+/// from a peer that checks right after sending its answer to an ICE restart. It is
+/// authenticated against the local password and replayed once the credentials are set, so the
+/// peer gets its response without waiting for a retransmit.
+pub(crate) struct EarlyCheck {
+    pub(crate) received: Instant,
+    pub(crate) message: Message,
+    pub(crate) local: Arc<dyn Candidate + Send + Sync>,
+    pub(crate) remote: SocketAddr,
 }
 
 pub(crate) type OnConnectionStateChangeHdlrFn = Box<
@@ -91,6 +101,8 @@ pub(crate) struct Agent {
     pub(crate) gathering_state: Arc<AtomicU8>, //GatheringState,
     pub(crate) candidate_types: Vec<CandidateType>,
     pub(crate) network_types: Vec<NetworkType>,
+    pub(crate) udp_conn: Option<Arc<dyn Conn + Send + Sync>>,
+    pub(crate) dscp: Option<u8>,
 
     pub(crate) gather_candidate_cancel: Option<GatherCandidateCancelFn>,
 }
@@ -100,7 +112,7 @@ impl Agent {
     pub(crate) async fn new(config: AgentConfig) -> Result<Self> {
         let mut mdns_name = config.multicast_dns_host_name.clone();
         if mdns_name.is_empty() {
-            mdns_name = generate_multicast_dns_name();
+            mdns_name = generate_multicast_dns_name(&config.rng);
         }
 
         if !mdns_name.ends_with(".local") || mdns_name.split('.').count() != 2 {
@@ -153,6 +165,8 @@ impl Agent {
             }
 
             net
+        } else if let Some(gathering_context) = &config.gathering_context {
+            Arc::new(gathering_context.net())
         } else {
             Arc::new(Net::new(None))
         };
@@ -167,6 +181,8 @@ impl Agent {
             gathering_state: Arc::new(AtomicU8::new(0)), //GatheringState::New,
             candidate_types,
             network_types: config.network_types.clone(),
+            udp_conn: config.udp_conn.clone(),
+            dscp: config.dscp,
 
             gather_candidate_cancel: None, //TODO: add cancel
         };
@@ -186,6 +202,15 @@ impl Agent {
             return Err(err);
         }
 
+        #[cfg(feature = "network-monitor")]
+        if let Some(interval) = config.network_monitor_interval {
+            if agent.udp_conn.is_some() {
+                log::warn!("network monitor disabled, the agent uses a provided socket");
+            } else {
+                agent.start_network_monitor(interval, config.gathering_context.clone());
+            }
+        }
+
         Ok(agent)
     }
 
@@ -221,12 +246,19 @@ impl Agent {
         &self,
         c: &Arc<dyn Candidate + Send + Sync>,
     ) -> Result<()> {
+        // Trickling endpoints signal 0.0.0.0 with the discard port 9 until they know their
+        // addresses, nothing can be reached there
+        if matches!(c.address().parse::<IpAddr>(), Ok(ip) if ip.is_unspecified()) {
+            log::debug!("ignoring placeholder remote candidate {}", c);
+            return Ok(());
+        }
+
         // If we have a mDNS Candidate lets fully resolve it before adding it locally
         if c.candidate_type() == CandidateType::Host && c.address().ends_with(".local") {
             if self.mdns_mode == MulticastDnsMode::Disabled {
                 log::warn!(
                     "remote mDNS candidate added, but mDNS is disabled: ({})",
-                    c.address()
+                    Redacted(c.address())
                 );
                 return Ok(());
             }
@@ -237,15 +269,46 @@ impl Agent {
         } else {
             let ai = Arc::clone(&self.internal);
             let candidate = Arc::clone(c);
-            tokio::spawn(async move {
+            self.internal.spawner.spawn(async move {
                 ai.add_remote_candidate(&candidate).await;
+                ai.connect_active_tcp(&candidate).await;
             });
         }
 
         Ok(())
     }
 
-    /// Returns the local candidates.
+    /// Records whether the remote announced trickle ICE, see `start_network_monitor`.
+    #[cfg(feature = "network-monitor")]
+    pub(crate) fn set_remote_trickle(&self, remote_trickle: bool) {
+        self.internal
+            .remote_trickle
+            .store(remote_trickle, Ordering::SeqCst);
+    }
+
+    /// Records the options the remote announced with `a=ice-options`. Of these only
+    /// renomination changes what the agent does, ice2 is what it implements anyway and unknown
+    /// ones are ignored.
+    pub(crate) fn set_remote_ice_options(&self, options: &[String]) {
+        log::debug!(
+            "[{}]: remote ice-options {:?}",
+            self.internal.get_name(),
+            options
+        );
+        self.internal.remote_renomination.store(
+            options.iter().any(|option| option == "renomination"),
+            Ordering::SeqCst,
+        );
+    }
+
+    /// Returns whether the agent is currently controlling. This starts out as the role the
+    /// agent was started in and changes when a role conflict is resolved against it.
+    pub(crate) fn is_controlling(&self) -> bool {
+        self.internal.is_controlling.load(Ordering::SeqCst)
+    }
+
+    /// Returns the local candidates to advertise, candidates the candidate filter marked as
+    /// not advertised are left out.
     pub(crate) async fn get_local_candidates(
         &self,
     ) -> Result<Vec<Arc<dyn Candidate + Send + Sync>>> {
@@ -253,9 +316,12 @@ impl Agent {
 
         {
             let local_candidates = self.internal.local_candidates.lock().await;
+            let unadvertised_candidates = self.internal.unadvertised_candidates.lock().await;
             for candidates in local_candidates.values() {
                 for candidate in candidates {
-                    res.push(Arc::clone(candidate));
+                    if !unadvertised_candidates.contains(&candidate.id()) {
+                        res.push(Arc::clone(candidate));
+                    }
                 }
             }
         }
@@ -263,6 +329,41 @@ impl Agent {
         Ok(res)
     }
 
+    /// Returns the stats records of the local and remote candidates, in the order they were
+    /// added and including pruned ones.
+    pub(crate) async fn get_candidate_records(
+        &self,
+    ) -> (Vec<CandidateRecord>, Vec<CandidateRecord>) {
+        let candidate_records = self.internal.candidate_records.lock().await;
+        (
+            candidate_records.local.iter().cloned().collect(),
+            candidate_records.remote.iter().cloned().collect(),
+        )
+    }
+
+    /// Returns how many remote candidates and candidate pairs were dropped or evicted for
+    /// `AgentConfig::max_remote_candidates` and `max_candidate_pairs`.
+    pub(crate) fn get_dropped_candidates(&self) -> (usize, usize) {
+        (
+            self.internal
+                .dropped_remote_candidates
+                .load(Ordering::SeqCst),
+            self.internal.dropped_candidate_pairs.load(Ordering::SeqCst),
+        )
+    }
+
+    /// Makes the pair of the given local and remote candidate ids the selected pair, see
+    /// `AgentInternal::select_candidate_pair`.
+    pub(crate) async fn select_candidate_pair(
+        &self,
+        local_id: &str,
+        remote_id: &str,
+    ) -> Result<()> {
+        self.internal
+            .select_candidate_pair(local_id, remote_id)
+            .await
+    }
+
     /// Returns the local user credentials.
     pub(crate) async fn get_local_user_credentials(&self) -> (String, String) {
         let ufrag_pwd = self.internal.ufrag_pwd.lock().await;
@@ -286,10 +387,10 @@ impl Agent {
     /// a user must then call `GatherCandidates` explicitly to start generating new ones.
     pub(crate) async fn restart(&self, mut ufrag: String, mut pwd: String) -> Result<()> {
         if ufrag.is_empty() {
-            ufrag = generate_ufrag();
+            ufrag = generate_ufrag(&self.internal.rng);
         }
         if pwd.is_empty() {
-            pwd = generate_pwd();
+            pwd = generate_pwd(&self.internal.rng);
         }
 
         if ufrag.len() * 8 < 24 {
@@ -298,6 +399,12 @@ impl Agent {
         if pwd.len() * 8 < 128 {
             return Err(Error::ErrLocalPwdInsufficientBits);
         }
+        if ufrag.len() < MIN_UFRAG_LEN || !is_ice_chars(&ufrag) {
+            return Err(Error::ErrLocalUfragInvalid);
+        }
+        if pwd.len() < MIN_PWD_LEN || !is_ice_chars(&pwd) {
+            return Err(Error::ErrLocalPwdInvalid);
+        }
 
         if GatheringState::from(self.gathering_state.load(Ordering::SeqCst))
             == GatheringState::Gathering
@@ -326,11 +433,17 @@ impl Agent {
             let mut pending_binding_requests = self.internal.pending_binding_requests.lock().await;
             *pending_binding_requests = vec![];
         }
+        {
+            let mut early_checks = self.internal.early_checks.lock().await;
+            early_checks.clear();
+        }
 
         {
             let mut checklist = self.internal.agent_conn.checklist.lock().await;
             *checklist = vec![];
         }
+        self.internal.nomination.store(1, Ordering::SeqCst);
+        self.internal.remote_nomination.store(0, Ordering::SeqCst);
 
         self.internal.set_selected_pair(None).await;
         self.internal.delete_all_candidates().await;
@@ -374,11 +487,13 @@ impl Agent {
             net: Arc::clone(&self.net),
             interface_filter: self.interface_filter.clone(),
             ext_ip_mapper: Arc::clone(&self.ext_ip_mapper),
+            udp_conn: self.udp_conn.clone(),
+            dscp: self.dscp,
             agent_internal: Arc::clone(&self.internal),
             gathering_state: Arc::clone(&self.gathering_state),
             chan_candidate_tx: Arc::clone(&self.internal.chan_candidate_tx),
         };
-        tokio::spawn(async move {
+        self.internal.spawner.spawn(async move {
             Self::gather_candidates_internal(params).await;
         });
 
@@ -1,16 +1,19 @@
 use super::*;
 use crate::webrtc::ice::candidate::candidate_host::CandidateHostConfig;
+use crate::webrtc::ice::candidate::candidate_peer_reflexive::CandidatePeerReflexiveConfig;
+use crate::webrtc::ice::candidate::candidate_server_reflexive::CandidateServerReflexiveConfig;
 use crate::webrtc::ice::error::*;
+use crate::webrtc::ice::tcp_type::TcpType;
 use crate::webrtc::ice::util::*;
 
 use async_trait::async_trait;
 use crc::{Crc, CRC_32_ISCSI};
 use std::fmt;
-use std::ops::Add;
-use std::sync::atomic::{AtomicU16, AtomicU64, AtomicU8, Ordering};
+use std::sync::atomic::{AtomicU16, AtomicU32, AtomicU64, AtomicU8, Ordering};
 use std::sync::Arc;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::Duration;
 use tokio::sync::{broadcast, Mutex};
+use tokio::time::Instant;
 
 #[derive(Default)]
 pub(crate) struct CandidateBaseConfig {
@@ -22,28 +25,33 @@ pub(crate) struct CandidateBaseConfig {
     pub(crate) priority: u32,
     pub(crate) foundation: String,
     pub(crate) conn: Option<Arc<dyn crate::webrtc::util::Conn + Send + Sync>>,
+    pub(crate) tcp_type: TcpType,
 }
 
 pub(crate) struct CandidateBase {
     pub(crate) id: String,
     pub(crate) network_type: AtomicU8,
-    pub(crate) candidate_type: CandidateType,
+    pub(crate) candidate_type: AtomicU8,
 
     pub(crate) component: AtomicU16,
     pub(crate) address: String,
     pub(crate) port: u16,
     pub(crate) related_address: Option<CandidateRelatedAddress>,
+    pub(crate) tcp_type: TcpType,
 
     pub(crate) resolved_addr: Mutex<SocketAddr>,
 
+    /// created is when the candidate was created, last_sent and last_received count from it
+    pub(crate) created: Instant,
     pub(crate) last_sent: AtomicU64,
     pub(crate) last_received: AtomicU64,
+    pub(crate) counters: Arc<CandidateCounters>,
 
     pub(crate) conn: Option<Arc<dyn crate::webrtc::util::Conn + Send + Sync>>,
     pub(crate) closed_ch: Arc<Mutex<Option<broadcast::Sender<()>>>>,
 
     pub(crate) foundation_override: String,
-    pub(crate) priority_override: u32,
+    pub(crate) priority_override: AtomicU32,
 
     //CandidateHost
     pub(crate) network: String,
@@ -54,23 +62,26 @@ impl Default for CandidateBase {
         Self {
             id: String::new(),
             network_type: AtomicU8::new(0),
-            candidate_type: CandidateType::default(),
+            candidate_type: AtomicU8::new(CandidateType::default() as u8),
 
             component: AtomicU16::new(0),
             address: String::new(),
             port: 0,
             related_address: None,
+            tcp_type: TcpType::default(),
 
             resolved_addr: Mutex::new(SocketAddr::new(IpAddr::from([0, 0, 0, 0]), 0)),
 
+            created: Instant::now(),
             last_sent: AtomicU64::new(0),
             last_received: AtomicU64::new(0),
+            counters: Arc::new(CandidateCounters::default()),
 
             conn: None,
             closed_ch: Arc::new(Mutex::new(None)),
 
             foundation_override: String::new(),
-            priority_override: 0,
+            priority_override: AtomicU32::new(0),
             network: String::new(),
         }
     }
@@ -85,7 +96,7 @@ impl fmt::Display for CandidateBase {
                 "{} {} {}:{}{}",
                 self.network_type(),
                 self.candidate_type(),
-                self.address(),
+                redact_address(&self.address()),
                 self.port(),
                 related_address,
             )
@@ -95,7 +106,7 @@ impl fmt::Display for CandidateBase {
                 "{} {} {}:{}",
                 self.network_type(),
                 self.candidate_type(),
-                self.address(),
+                redact_address(&self.address()),
                 self.port(),
             )
         }
@@ -129,20 +140,16 @@ impl Candidate for CandidateBase {
         self.component.load(Ordering::SeqCst)
     }
 
-    fn set_component(&self, component: u16) {
-        self.component.store(component, Ordering::SeqCst);
+    /// Returns a time indicating the last time this candidate was received, or when it was
+    /// created if it never received anything.
+    fn last_received(&self) -> Instant {
+        self.created + Duration::from_nanos(self.last_received.load(Ordering::SeqCst))
     }
 
-    /// Returns a time indicating the last time this candidate was received.
-    fn last_received(&self) -> SystemTime {
-        UNIX_EPOCH.add(Duration::from_nanos(
-            self.last_received.load(Ordering::SeqCst),
-        ))
-    }
-
-    /// Returns a time indicating the last time this candidate was sent.
-    fn last_sent(&self) -> SystemTime {
-        UNIX_EPOCH.add(Duration::from_nanos(self.last_sent.load(Ordering::SeqCst)))
+    /// Returns a time indicating the last time this candidate was sent, or when it was created
+    /// if it never sent anything.
+    fn last_sent(&self) -> Instant {
+        self.created + Duration::from_nanos(self.last_sent.load(Ordering::SeqCst))
     }
 
     /// Returns candidate NetworkType.
@@ -162,8 +169,9 @@ impl Candidate for CandidateBase {
 
     /// Computes the priority for this ICE Candidate.
     fn priority(&self) -> u32 {
-        if self.priority_override != 0 {
-            return self.priority_override;
+        let priority_override = self.priority_override.load(Ordering::SeqCst);
+        if priority_override != 0 {
+            return priority_override;
         }
 
         // The local preference MUST be an integer from 0 (lowest preference) to
@@ -172,7 +180,7 @@ impl Candidate for CandidateBase {
         // candidates for a particular component for a particular data stream
         // that have the same type, the local preference MUST be unique for each
         // one.
-        (1 << 24) * u32::from(self.candidate_type().preference())
+        (1 << 24) * u32::from(self.type_preference())
             + (1 << 8) * u32::from(self.local_preference())
             + (256 - u32::from(self.component()))
     }
@@ -184,7 +192,23 @@ impl Candidate for CandidateBase {
 
     /// Returns candidate type.
     fn candidate_type(&self) -> CandidateType {
+        CandidateType::from(self.candidate_type.load(Ordering::SeqCst))
+    }
+
+    /// Returns the TCP type, unspecified for UDP candidates.
+    fn tcp_type(&self) -> TcpType {
+        self.tcp_type
+    }
+
+    fn promote(&self, signaled: &dyn Candidate) {
         self.candidate_type
+            .store(signaled.candidate_type() as u8, Ordering::SeqCst);
+        self.priority_override
+            .store(signaled.priority(), Ordering::SeqCst);
+    }
+
+    fn counters(&self) -> Arc<CandidateCounters> {
+        Arc::clone(&self.counters)
     }
 
     /// Returns the string representation of the ICECandidate.
@@ -208,6 +232,10 @@ impl Candidate for CandidateBase {
             .as_str();
         }
 
+        if self.tcp_type != TcpType::Unspecified {
+            val += format!(" tcptype {}", self.tcp_type).as_str();
+        }
+
         val
     }
 
@@ -234,9 +262,8 @@ impl Candidate for CandidateBase {
     }
 
     fn seen(&self, outbound: bool) {
-        let d = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_else(|_| Duration::from_secs(0));
+        // monotonic, so that a clock change does not make the pair look idle or disconnected
+        let d = Instant::now().saturating_duration_since(self.created);
 
         if outbound {
             self.set_last_sent(d);
@@ -263,6 +290,7 @@ impl Candidate for CandidateBase {
             && self.address() == other.address()
             && self.port() == other.port()
             && self.related_address() == other.related_address()
+            && self.tcp_type() == other.tcp_type()
     }
 
     async fn set_ip(&self, ip: &IpAddr) -> Result<()> {
@@ -298,9 +326,35 @@ impl CandidateBase {
         self.last_sent.store(d.as_nanos() as u64, Ordering::SeqCst);
     }
 
+    /// Returns the type preference for this candidate. TCP candidates get half the preference
+    /// of UDP ones, which puts even a TCP host candidate below every UDP candidate, so that UDP
+    /// is used whenever it works (RFC 6544 Section 4.2).
+    pub(crate) fn type_preference(&self) -> u16 {
+        let preference = self.candidate_type().preference();
+        if self.network_type().is_tcp() {
+            preference / 2
+        } else {
+            preference
+        }
+    }
+
     /// Returns the local preference for this candidate.
     pub(crate) fn local_preference(&self) -> u16 {
-        DEFAULT_LOCAL_PREFERENCE
+        if !self.network_type().is_tcp() {
+            return DEFAULT_LOCAL_PREFERENCE;
+        }
+
+        // RFC 6544 Section 4.2: local preference = (2^13) * direction-pref + other-pref,
+        // where the direction preference of host candidates favors active over passive over
+        // simultaneous-open
+        let direction_preference: u16 = match self.tcp_type {
+            TcpType::Active => 6,
+            TcpType::Passive => 4,
+            TcpType::SimultaneousOpen => 2,
+            TcpType::Unspecified => 0,
+        };
+        let other_preference: u16 = 8191;
+        (1 << 13) * direction_preference + other_preference
     }
 }
 
@@ -335,9 +389,18 @@ pub(crate) async fn unmarshal_candidate(raw: &str) -> Result<impl Candidate> {
 
     let typ = split[7];
 
+    let mut rel_addr = String::new();
+    let mut rel_port = 0;
+    let mut tcp_type = TcpType::Unspecified;
+
     if split.len() > 8 {
         let split2 = &split[8..];
 
+        // TcpType, RFC 6544 Section 4.5
+        if let Some(i) = split2.iter().position(|s| *s == "tcptype") {
+            tcp_type = TcpType::from(split2.get(i + 1).copied().unwrap_or_default());
+        }
+
         if split2[0] == "raddr" {
             if split2.len() < 4 {
                 return Err(Error::Other(format!(
@@ -345,6 +408,12 @@ pub(crate) async fn unmarshal_candidate(raw: &str) -> Result<impl Candidate> {
                     Error::ErrParseRelatedAddr
                 )));
             }
+
+            // RelatedAddress
+            rel_addr = split2[1].to_owned();
+
+            // RelatedPort
+            rel_port = split2[3].parse()?;
         }
     }
 
@@ -358,11 +427,46 @@ pub(crate) async fn unmarshal_candidate(raw: &str) -> Result<impl Candidate> {
                     component,
                     priority,
                     foundation,
+                    tcp_type,
                     ..CandidateBaseConfig::default()
                 },
             };
             config.new_candidate_host().await
         }
+        "srflx" => {
+            let config = CandidateServerReflexiveConfig {
+                base_config: CandidateBaseConfig {
+                    network,
+                    address,
+                    port,
+                    component,
+                    priority,
+                    foundation,
+                    tcp_type,
+                    ..CandidateBaseConfig::default()
+                },
+                rel_addr,
+                rel_port,
+            };
+            config.new_candidate_server_reflexive().await
+        }
+        "prflx" => {
+            let config = CandidatePeerReflexiveConfig {
+                base_config: CandidateBaseConfig {
+                    network,
+                    address,
+                    port,
+                    component,
+                    priority,
+                    foundation,
+                    tcp_type,
+                    ..CandidateBaseConfig::default()
+                },
+                rel_addr,
+                rel_port,
+            };
+            config.new_candidate_peer_reflexive().await
+        }
         _ => Err(Error::Other(format!(
             "{:?} ({})",
             Error::ErrUnknownCandidateType,
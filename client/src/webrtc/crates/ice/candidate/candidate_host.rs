@@ -1,8 +1,9 @@
 use super::candidate_base::*;
 use super::*;
 use crate::webrtc::ice::rand::generate_cand_id;
+use crate::webrtc::util::random_source::RandomSource;
 
-use std::sync::atomic::{AtomicU16, AtomicU8};
+use std::sync::atomic::{AtomicU16, AtomicU32, AtomicU8};
 
 /// The config required to create a new `CandidateHost`.
 #[derive(Default)]
@@ -15,20 +16,21 @@ impl CandidateHostConfig {
     pub(crate) async fn new_candidate_host(self) -> Result<CandidateBase> {
         let mut candidate_id = self.base_config.candidate_id;
         if candidate_id.is_empty() {
-            candidate_id = generate_cand_id();
+            candidate_id = generate_cand_id(&RandomSource::default());
         }
 
         let c = CandidateBase {
             id: candidate_id,
             address: self.base_config.address.clone(),
-            candidate_type: CandidateType::Host,
+            candidate_type: AtomicU8::new(CandidateType::Host as u8),
             component: AtomicU16::new(self.base_config.component),
             port: self.base_config.port,
             foundation_override: self.base_config.foundation,
-            priority_override: self.base_config.priority,
+            priority_override: AtomicU32::new(self.base_config.priority),
             network: self.base_config.network,
             network_type: AtomicU8::new(NetworkType::Udp4 as u8),
             conn: self.base_config.conn,
+            tcp_type: self.base_config.tcp_type,
             ..CandidateBase::default()
         };
 
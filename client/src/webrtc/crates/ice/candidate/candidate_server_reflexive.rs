@@ -0,0 +1,55 @@
+use super::candidate_base::*;
+use super::*;
+use crate::webrtc::ice::rand::generate_cand_id;
+use crate::webrtc::util::random_source::RandomSource;
+
+use std::sync::atomic::{AtomicU16, AtomicU32, AtomicU8};
+
+/// The config required to create a new `CandidateServerReflexive`.
+#[derive(Default)]
+pub(crate) struct CandidateServerReflexiveConfig {
+    pub(crate) base_config: CandidateBaseConfig,
+
+    pub(crate) rel_addr: String,
+    pub(crate) rel_port: u16,
+}
+
+impl CandidateServerReflexiveConfig {
+    /// Creates a new server reflexive candidate.
+    pub(crate) async fn new_candidate_server_reflexive(self) -> Result<CandidateBase> {
+        let mut candidate_id = self.base_config.candidate_id;
+        if candidate_id.is_empty() {
+            candidate_id = generate_cand_id(&RandomSource::default());
+        }
+
+        let related_address = if self.rel_addr.is_empty() {
+            None
+        } else {
+            Some(CandidateRelatedAddress {
+                address: self.rel_addr,
+                port: self.rel_port,
+            })
+        };
+
+        let c = CandidateBase {
+            id: candidate_id,
+            address: self.base_config.address.clone(),
+            candidate_type: AtomicU8::new(CandidateType::ServerReflexive as u8),
+            component: AtomicU16::new(self.base_config.component),
+            port: self.base_config.port,
+            related_address,
+            foundation_override: self.base_config.foundation,
+            priority_override: AtomicU32::new(self.base_config.priority),
+            network: self.base_config.network,
+            network_type: AtomicU8::new(NetworkType::Udp4 as u8),
+            conn: self.base_config.conn,
+            tcp_type: self.base_config.tcp_type,
+            ..CandidateBase::default()
+        };
+
+        let ip = self.base_config.address.parse()?;
+        c.set_ip(&ip).await?;
+
+        Ok(c)
+    }
+}
@@ -0,0 +1,138 @@
+use super::*;
+use crate::webrtc::stun::message::{
+    Message, CLASS_ERROR_RESPONSE, CLASS_REQUEST, CLASS_SUCCESS_RESPONSE, METHOD_BINDING,
+};
+
+use std::collections::VecDeque;
+use std::sync::atomic::AtomicU64;
+use std::time::{Duration, UNIX_EPOCH};
+
+/// MAX_CANDIDATE_RECORDS is how many local and how many remote candidates the agent keeps stats
+/// of, the oldest record is dropped beyond
+const MAX_CANDIDATE_RECORDS: usize = 128;
+
+/// CandidateCounters counts the STUN binding traffic of a candidate. They are shared with the
+/// agent's CandidateRecord, so they outlive the candidate when it is pruned.
+#[derive(Default)]
+pub(crate) struct CandidateCounters {
+    requests_sent: AtomicU64,
+    requests_received: AtomicU64,
+    responses_sent: AtomicU64,
+    responses_received: AtomicU64,
+    // nanoseconds since UNIX_EPOCH, zero before any binding traffic
+    last_activity: AtomicU64,
+    succeeded: AtomicBool,
+}
+
+impl CandidateCounters {
+    /// count records a binding message sent or received through the candidate
+    pub(crate) fn count(&self, m: &Message, outbound: bool) {
+        if m.typ.method != METHOD_BINDING {
+            return;
+        }
+
+        let counter = match (m.typ.class, outbound) {
+            (CLASS_REQUEST, true) => &self.requests_sent,
+            (CLASS_REQUEST, false) => &self.requests_received,
+            (CLASS_SUCCESS_RESPONSE | CLASS_ERROR_RESPONSE, true) => &self.responses_sent,
+            (CLASS_SUCCESS_RESPONSE | CLASS_ERROR_RESPONSE, false) => &self.responses_received,
+            _ => return,
+        };
+        counter.fetch_add(1, Ordering::SeqCst);
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_else(|_| Duration::from_secs(0));
+        #[allow(clippy::cast_possible_truncation)]
+        self.last_activity
+            .store(now.as_nanos() as u64, Ordering::SeqCst);
+    }
+
+    /// set_succeeded marks that a pair with the candidate got a success response
+    pub(crate) fn set_succeeded(&self) {
+        self.succeeded.store(true, Ordering::SeqCst);
+    }
+
+    pub(crate) fn requests_sent(&self) -> u64 {
+        self.requests_sent.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn requests_received(&self) -> u64 {
+        self.requests_received.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn responses_sent(&self) -> u64 {
+        self.responses_sent.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn responses_received(&self) -> u64 {
+        self.responses_received.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn last_activity(&self) -> Option<SystemTime> {
+        match self.last_activity.load(Ordering::SeqCst) {
+            0 => None,
+            nanos => Some(UNIX_EPOCH + Duration::from_nanos(nanos)),
+        }
+    }
+
+    pub(crate) fn succeeded(&self) -> bool {
+        self.succeeded.load(Ordering::SeqCst)
+    }
+}
+
+/// CandidateRecord is what the agent remembers of a candidate for its stats, it is kept after
+/// the candidate is pruned.
+#[derive(Clone)]
+pub(crate) struct CandidateRecord {
+    pub(crate) id: String,
+    pub(crate) candidate_type: CandidateType,
+    pub(crate) network_type: NetworkType,
+    pub(crate) address: String,
+    pub(crate) port: u16,
+    pub(crate) priority: u32,
+    pub(crate) counters: Arc<CandidateCounters>,
+}
+
+impl CandidateRecord {
+    fn new(c: &dyn Candidate) -> Self {
+        CandidateRecord {
+            id: c.id(),
+            candidate_type: c.candidate_type(),
+            network_type: c.network_type(),
+            address: c.address(),
+            port: c.port(),
+            priority: c.priority(),
+            counters: c.counters(),
+        }
+    }
+}
+
+/// CandidateRecords are the records of the local and remote candidates of an agent, in the
+/// order they were added.
+#[derive(Default)]
+pub(crate) struct CandidateRecords {
+    pub(crate) local: VecDeque<CandidateRecord>,
+    pub(crate) remote: VecDeque<CandidateRecord>,
+}
+
+impl CandidateRecords {
+    /// record adds or, e.g. after a promotion, updates the record of a candidate
+    pub(crate) fn record(&mut self, c: &dyn Candidate, local: bool) {
+        let records = if local {
+            &mut self.local
+        } else {
+            &mut self.remote
+        };
+
+        let record = CandidateRecord::new(c);
+        if let Some(existing) = records.iter_mut().find(|r| r.id == record.id) {
+            *existing = record;
+            return;
+        }
+        if records.len() == MAX_CANDIDATE_RECORDS {
+            records.pop_front();
+        }
+        records.push_back(record);
+    }
+}
@@ -1,9 +1,15 @@
 pub(crate) mod candidate_base;
 pub(crate) mod candidate_host;
+pub(crate) mod candidate_peer_reflexive;
+pub(crate) mod candidate_server_reflexive;
+pub(crate) mod candidate_stats;
 
 use crate::webrtc::ice::error::Result;
 use crate::webrtc::ice::network_type::*;
+use crate::webrtc::ice::tcp_type::TcpType;
+use crate::webrtc::util::redaction::redact_address;
 use candidate_base::*;
+use candidate_stats::CandidateCounters;
 
 use async_trait::async_trait;
 use std::fmt;
@@ -12,13 +18,16 @@ use std::sync::atomic::{AtomicBool, AtomicU16, AtomicU8, Ordering};
 use std::sync::Arc;
 use std::time::SystemTime;
 use tokio::sync::{broadcast, Mutex};
+use tokio::time::Instant;
 
-pub(crate) const RECEIVE_MTU: usize = 8192;
 pub(crate) const DEFAULT_LOCAL_PREFERENCE: u16 = 65535;
 
 /// Indicates that the candidate is used for RTP.
 pub(crate) const COMPONENT_RTP: u16 = 1;
 
+/// The discard port active TCP candidates are advertised with, see RFC 6544 Section 4.5.
+pub(crate) const ACTIVE_TCP_PORT: u16 = 9;
+
 /// Candidate represents an ICE candidate
 #[async_trait]
 pub(crate) trait Candidate: fmt::Display {
@@ -35,13 +44,12 @@ pub(crate) trait Candidate: fmt::Display {
     /// A component is a piece of a data stream.
     /// An example is one for RTP, and one for RTCP
     fn component(&self) -> u16;
-    fn set_component(&self, c: u16);
 
     /// The last time this candidate received traffic
-    fn last_received(&self) -> SystemTime;
+    fn last_received(&self) -> Instant;
 
     /// The last time this candidate sent traffic
-    fn last_sent(&self) -> SystemTime;
+    fn last_sent(&self) -> Instant;
 
     fn network_type(&self) -> NetworkType;
     fn address(&self) -> String;
@@ -55,6 +63,16 @@ pub(crate) trait Candidate: fmt::Display {
 
     fn candidate_type(&self) -> CandidateType;
 
+    /// The TCP type of an ICE-TCP candidate, see RFC 6544 Section 4.5
+    fn tcp_type(&self) -> TcpType;
+
+    /// Takes the type and priority of a signaled candidate for the same transport address, used
+    /// when a peer reflexive candidate is learned through signaling later.
+    fn promote(&self, signaled: &dyn Candidate);
+
+    /// The counters of the STUN binding traffic through this candidate
+    fn counters(&self) -> Arc<CandidateCounters>;
+
     fn marshal(&self) -> String;
 
     async fn addr(&self) -> SocketAddr;
@@ -72,8 +90,21 @@ pub(crate) trait Candidate: fmt::Display {
 /// Represents the type of candidate `CandidateType` enum.
 #[derive(PartialEq, Debug, Copy, Clone)]
 pub(crate) enum CandidateType {
-    Unspecified,
-    Host,
+    Unspecified = 0,
+    Host = 1,
+    ServerReflexive = 2,
+    PeerReflexive = 3,
+}
+
+impl From<u8> for CandidateType {
+    fn from(v: u8) -> Self {
+        match v {
+            1 => Self::Host,
+            2 => Self::ServerReflexive,
+            3 => Self::PeerReflexive,
+            _ => Self::Unspecified,
+        }
+    }
 }
 
 // String makes CandidateType printable
@@ -81,6 +112,8 @@ impl fmt::Display for CandidateType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let s = match *self {
             CandidateType::Host => "host",
+            CandidateType::ServerReflexive => "srflx",
+            CandidateType::PeerReflexive => "prflx",
             CandidateType::Unspecified => "Unknown candidate type",
         };
         write!(f, "{}", s)
@@ -93,6 +126,22 @@ impl Default for CandidateType {
     }
 }
 
+/// CandidateDisposition is what happens to a gathered local candidate, see
+/// SettingEngine::set_candidate_filter.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Default)]
+pub enum CandidateDisposition {
+    /// UseAndAdvertise uses the candidate for connectivity checks and hands it to the remote
+    /// through on_ice_candidate and the SDP.
+    #[default]
+    UseAndAdvertise,
+    /// UseButDontAdvertise uses the candidate for connectivity checks but never reveals it to
+    /// the remote. Checks still reach remote candidates the remote advertised, which learns
+    /// about the local address only from their source.
+    UseButDontAdvertise,
+    /// Drop closes the candidate, it takes no part in the connection.
+    Drop,
+}
+
 impl CandidateType {
     /// Returns the preference weight of a `CandidateType`.
     ///
@@ -104,6 +153,8 @@ impl CandidateType {
     pub(crate) const fn preference(self) -> u16 {
         match self {
             Self::Host => 126,
+            Self::PeerReflexive => 110,
+            Self::ServerReflexive => 100,
             CandidateType::Unspecified => 0,
         }
     }
@@ -119,7 +170,12 @@ pub(crate) struct CandidateRelatedAddress {
 // String makes CandidateRelatedAddress printable
 impl fmt::Display for CandidateRelatedAddress {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, " related {}:{}", self.address, self.port)
+        write!(
+            f,
+            " related {}:{}",
+            redact_address(&self.address),
+            self.port
+        )
     }
 }
 
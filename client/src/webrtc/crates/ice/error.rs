@@ -20,6 +20,15 @@ pub(crate) enum Error {
     #[error("local password is less than 128 bits long")]
     ErrLocalPwdInsufficientBits,
 
+    /// Indicates the local username fragment is not at least 4 ice-chars, see RFC 8445 Section
+    /// 5.3.
+    #[error("local username fragment must be at least 4 ice-chars")]
+    ErrLocalUfragInvalid,
+
+    /// Indicates the local password is not at least 22 ice-chars, see RFC 8445 Section 5.3.
+    #[error("local password must be at least 22 ice-chars")]
+    ErrLocalPwdInvalid,
+
     /// Indicates the agent is closed.
     #[error("the agent is closed")]
     ErrClosed,
@@ -101,6 +110,18 @@ pub(crate) enum Error {
     #[error("username mismatch")]
     ErrMismatchUsername,
 
+    /// Indicates a candidate pair was selected manually while the agent is controlled.
+    #[error("only the controlling agent can select a candidate pair")]
+    ErrSelectPairNotControlling,
+
+    /// Indicates no candidate pair of the given local and remote candidate ids.
+    #[error("no candidate pair from {0} to {1}")]
+    ErrCandidatePairNotFound(String, String),
+
+    /// Indicates a candidate pair was selected manually before a check on it succeeded.
+    #[error("candidate pair from {0} to {1} has not been validated by a connectivity check")]
+    ErrCandidatePairNotValidated(String, String),
+
     #[error("parse int: {0}")]
     ParseInt(#[from] ParseIntError),
     #[error("parse addr: {0}")]
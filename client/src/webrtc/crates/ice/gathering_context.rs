@@ -0,0 +1,76 @@
+use crate::webrtc::util::vnet::interface::Interface;
+use crate::webrtc::util::vnet::net::Net;
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// GatheringContext is the part of candidate gathering that peer connections can share, see
+/// SettingEngine::set_gathering_context. It caches the list of local interfaces for ttl, so
+/// connections created within it skip enumerating them again. Clones share the cache.
+///
+/// Gathering only covers host candidates, there are no server reflexive mappings or a shared
+/// UDP socket to cache. With the network-monitor feature every poll of a connection's monitor
+/// replaces the cached list, so an interface change is seen by the connections created after it.
+#[derive(Clone)]
+pub struct GatheringContext {
+    inner: Arc<GatheringContextInner>,
+}
+
+struct GatheringContextInner {
+    ttl: Duration,
+    interfaces: Mutex<Option<(Instant, Vec<Interface>)>>,
+}
+
+impl GatheringContext {
+    /// new creates a context that keeps the interface list for ttl after reading it
+    pub fn new(ttl: Duration) -> Self {
+        GatheringContext {
+            inner: Arc::new(GatheringContextInner {
+                ttl,
+                interfaces: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// invalidate drops the cached interface list, the next connection reads it again
+    pub fn invalidate(&self) {
+        *self.inner.interfaces.lock().unwrap() = None;
+    }
+
+    /// net returns the Net an agent gathers with, reading the system interfaces only if the
+    /// cached ones are missing or older than ttl
+    pub(crate) fn net(&self) -> Net {
+        let mut interfaces = self.inner.interfaces.lock().unwrap();
+        if let Some((read_at, ifs)) = &*interfaces {
+            if read_at.elapsed() < self.inner.ttl {
+                return Net::Ifs(ifs.clone());
+            }
+        }
+
+        let net = Net::new(None);
+        if let Net::Ifs(ifs) = &net {
+            *interfaces = Some((Instant::now(), ifs.clone()));
+        }
+        net
+    }
+
+    /// update replaces the cached interface list with one just read from the system
+    #[cfg(feature = "network-monitor")]
+    pub(crate) fn update(&self, ifs: Vec<Interface>) {
+        *self.inner.interfaces.lock().unwrap() = Some((Instant::now(), ifs));
+    }
+}
+
+impl fmt::Debug for GatheringContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let interfaces = self.inner.interfaces.lock().unwrap();
+        f.debug_struct("GatheringContext")
+            .field("ttl", &self.inner.ttl)
+            .field(
+                "interfaces",
+                &interfaces.as_ref().map_or(0, |(_, ifs)| ifs.len()),
+            )
+            .finish()
+    }
+}
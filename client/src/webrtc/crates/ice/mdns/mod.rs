@@ -1,4 +1,5 @@
-use uuid::Uuid;
+use crate::webrtc::util::random_source::RandomSource;
+use uuid::{Builder, Variant, Version};
 
 /// Represents the different Multicast modes that ICE can run.
 #[derive(PartialEq, Debug, Copy, Clone)]
@@ -21,9 +22,14 @@ impl Default for MulticastDnsMode {
     }
 }
 
-pub(crate) fn generate_multicast_dns_name() -> String {
+pub(crate) fn generate_multicast_dns_name(rng: &RandomSource) -> String {
     // https://tools.ietf.org/id/draft-ietf-rtcweb-mdns-ice-candidates-02.html#gathering
     // The unique name MUST consist of a version 4 UUID as defined in [RFC4122], followed by “.local”.
-    let u = Uuid::new_v4();
+    let mut bytes = [0u8; 16];
+    rng.fill_bytes(&mut bytes);
+    let u = Builder::from_bytes(bytes)
+        .set_variant(Variant::RFC4122)
+        .set_version(Version::Random)
+        .build();
     format!("{}.local", u)
 }
@@ -1,15 +1,20 @@
+pub(crate) mod active_tcp;
 pub(crate) mod agent;
 pub(crate) mod candidate;
 pub(crate) mod control;
 mod error;
 pub(crate) mod external_ip_mapper;
+pub(crate) mod gathering_context;
 pub(crate) mod mdns;
 pub(crate) mod network_type;
+pub(crate) mod nomination;
+pub(crate) mod packet_capture;
 pub(crate) mod priority;
 pub(crate) mod rand;
 pub(crate) mod state;
+pub(crate) mod tcp_type;
 pub(crate) mod url;
 pub(crate) mod use_candidate;
-mod util;
+pub(crate) mod util;
 
 pub(crate) use error::Error;
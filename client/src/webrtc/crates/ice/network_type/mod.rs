@@ -4,12 +4,20 @@ use std::fmt;
 use std::net::IpAddr;
 
 pub(crate) const UDP: &str = "udp";
+pub(crate) const TCP: &str = "tcp";
 
 #[must_use]
 pub(crate) fn supported_network_types() -> Vec<NetworkType> {
     vec![NetworkType::Udp4, NetworkType::Udp6]
 }
 
+/// Returns the network types of ICE-TCP, which is only used when enabled, see
+/// SettingEngine::set_ice_tcp.
+#[must_use]
+pub(crate) fn tcp_network_types() -> Vec<NetworkType> {
+    vec![NetworkType::Tcp4, NetworkType::Tcp6]
+}
+
 /// Represents the type of network.
 #[derive(PartialEq, Debug, Copy, Clone, Eq, Hash)]
 pub(crate) enum NetworkType {
@@ -20,6 +28,12 @@ pub(crate) enum NetworkType {
 
     /// Indicates UDP over IPv6.
     Udp6,
+
+    /// Indicates TCP over IPv4.
+    Tcp4,
+
+    /// Indicates TCP over IPv6.
+    Tcp6,
 }
 
 impl From<u8> for NetworkType {
@@ -27,6 +41,8 @@ impl From<u8> for NetworkType {
         match v {
             1 => Self::Udp4,
             2 => Self::Udp6,
+            3 => Self::Tcp4,
+            4 => Self::Tcp6,
             _ => Self::Unspecified,
         }
     }
@@ -37,6 +53,8 @@ impl fmt::Display for NetworkType {
         let s = match *self {
             Self::Udp4 => "udp4",
             Self::Udp6 => "udp6",
+            Self::Tcp4 => "tcp4",
+            Self::Tcp6 => "tcp6",
             Self::Unspecified => "unspecified",
         };
         write!(f, "{}", s)
@@ -55,6 +73,7 @@ impl NetworkType {
     pub(crate) fn network_short(self) -> String {
         match self {
             Self::Udp4 | Self::Udp6 => UDP.to_owned(),
+            Self::Tcp4 | Self::Tcp6 => TCP.to_owned(),
             Self::Unspecified => "Unspecified".to_owned(),
         }
     }
@@ -63,8 +82,8 @@ impl NetworkType {
     #[must_use]
     pub(crate) const fn is_ipv4(self) -> bool {
         match self {
-            Self::Udp4 => true,
-            Self::Udp6 | Self::Unspecified => false,
+            Self::Udp4 | Self::Tcp4 => true,
+            Self::Udp6 | Self::Tcp6 | Self::Unspecified => false,
         }
     }
 
@@ -72,10 +91,16 @@ impl NetworkType {
     #[must_use]
     pub(crate) const fn is_ipv6(self) -> bool {
         match self {
-            Self::Udp6 => true,
-            Self::Udp4 | Self::Unspecified => false,
+            Self::Udp6 | Self::Tcp6 => true,
+            Self::Udp4 | Self::Tcp4 | Self::Unspecified => false,
         }
     }
+
+    /// Returns whether the network type is TCP or not.
+    #[must_use]
+    pub(crate) const fn is_tcp(self) -> bool {
+        matches!(self, Self::Tcp4 | Self::Tcp6)
+    }
 }
 
 /// Determines the type of network based on the short network string and an IP address.
@@ -88,6 +113,12 @@ pub(crate) fn determine_network_type(network: &str, ip: &IpAddr) -> Result<Netwo
         } else {
             Ok(NetworkType::Udp6)
         }
+    } else if net.starts_with(TCP) {
+        if ipv4 {
+            Ok(NetworkType::Tcp4)
+        } else {
+            Ok(NetworkType::Tcp6)
+        }
     } else {
         Err(Error::ErrDetermineNetworkType)
     }
@@ -0,0 +1,32 @@
+use crate::webrtc::stun::attributes::ATTR_NOMINATION;
+use crate::webrtc::stun::checks::check_size;
+use crate::webrtc::stun::message::*;
+
+/// Represents NOMINATION attribute of draft-thatcher-ice-renomination. A controlling agent that
+/// renominates sends increasing values, the controlled agent selects the pair with the highest.
+#[derive(Default, PartialEq, Debug, Copy, Clone)]
+pub(crate) struct NominationAttr(pub(crate) u32);
+
+const NOMINATION_SIZE: usize = 4; // 32 bit, of which the value takes the lower 24
+
+const NOMINATION_VALUE_MASK: u32 = 0x00FF_FFFF;
+
+impl Setter for NominationAttr {
+    // add_to adds NOMINATION attribute to message.
+    fn add_to(&self, m: &mut Message) -> Result<(), crate::webrtc::stun::Error> {
+        let mut v = vec![0_u8; NOMINATION_SIZE];
+        v.copy_from_slice(&(self.0 & NOMINATION_VALUE_MASK).to_be_bytes());
+        m.add(ATTR_NOMINATION, &v);
+        Ok(())
+    }
+}
+
+impl Getter for NominationAttr {
+    // get_from decodes NOMINATION attribute from message.
+    fn get_from(&mut self, m: &Message) -> Result<(), crate::webrtc::stun::Error> {
+        let v = m.get(ATTR_NOMINATION)?;
+        check_size(ATTR_NOMINATION, v.len(), NOMINATION_SIZE)?;
+        self.0 = u32::from_be_bytes([v[0], v[1], v[2], v[3]]) & NOMINATION_VALUE_MASK;
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+pub(crate) mod pcap_file_sink;
+
+use crate::webrtc::util::Conn;
+
+use async_trait::async_trait;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+type Result<T> = std::result::Result<T, crate::webrtc::util::Error>;
+
+/// PacketDirection tells whether a captured packet was sent or received.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PacketDirection {
+    Outbound,
+    Inbound,
+}
+
+/// CapturedPacket is one UDP datagram sent or received by ICE, STUN as well as DTLS.
+#[derive(Debug, Copy, Clone)]
+pub struct CapturedPacket<'a> {
+    pub direction: PacketDirection,
+    pub timestamp: SystemTime,
+    /// local_addr is the address the socket is bound to, which is the unspecified address for a
+    /// socket given to SettingEngine::set_udp_socket unbound to an interface.
+    pub local_addr: SocketAddr,
+    pub remote_addr: SocketAddr,
+    /// payload is the UDP payload, without IP or UDP headers.
+    pub payload: &'a [u8],
+}
+
+/// PacketSink receives every packet ICE sends or receives, see
+/// SettingEngine::set_packet_capture. It is called on the packet path, so it must not block,
+/// PcapFileSink hands the packets to a writer task for example.
+pub trait PacketSink: Send + Sync {
+    fn capture(&self, packet: CapturedPacket<'_>);
+}
+
+/// CaptureConn shows the packets of a candidate's socket to a sink. It is only put in front of
+/// the socket when a sink is set.
+pub(crate) struct CaptureConn {
+    next_conn: Arc<dyn Conn + Send + Sync>,
+    local_addr: SocketAddr,
+    sink: Arc<dyn PacketSink>,
+}
+
+impl CaptureConn {
+    pub(crate) async fn new(
+        next_conn: Arc<dyn Conn + Send + Sync>,
+        sink: Arc<dyn PacketSink>,
+    ) -> Self {
+        let local_addr = next_conn
+            .local_addr()
+            .await
+            .unwrap_or_else(|_| SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0));
+        CaptureConn {
+            next_conn,
+            local_addr,
+            sink,
+        }
+    }
+
+    fn capture(&self, direction: PacketDirection, remote_addr: SocketAddr, payload: &[u8]) {
+        self.sink.capture(CapturedPacket {
+            direction,
+            timestamp: SystemTime::now(),
+            local_addr: self.local_addr,
+            remote_addr,
+            payload,
+        });
+    }
+
+    async fn peer_addr(&self) -> SocketAddr {
+        self.next_conn
+            .remote_addr()
+            .await
+            .unwrap_or_else(|| SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0))
+    }
+}
+
+#[async_trait]
+impl Conn for CaptureConn {
+    async fn connect(&self, addr: SocketAddr) -> Result<()> {
+        self.next_conn.connect(addr).await
+    }
+
+    async fn recv(&self, buf: &mut [u8]) -> Result<usize> {
+        let n = self.next_conn.recv(buf).await?;
+        self.capture(PacketDirection::Inbound, self.peer_addr().await, &buf[..n]);
+        Ok(n)
+    }
+
+    async fn recv_from(&self, buf: &mut [u8]) -> Result<(usize, SocketAddr)> {
+        let (n, addr) = self.next_conn.recv_from(buf).await?;
+        self.capture(PacketDirection::Inbound, addr, &buf[..n]);
+        Ok((n, addr))
+    }
+
+    async fn send(&self, buf: &[u8]) -> Result<usize> {
+        let n = self.next_conn.send(buf).await?;
+        self.capture(PacketDirection::Outbound, self.peer_addr().await, buf);
+        Ok(n)
+    }
+
+    async fn send_to(&self, buf: &[u8], target: SocketAddr) -> Result<usize> {
+        let n = self.next_conn.send_to(buf, target).await?;
+        self.capture(PacketDirection::Outbound, target, buf);
+        Ok(n)
+    }
+
+    async fn local_addr(&self) -> Result<SocketAddr> {
+        self.next_conn.local_addr().await
+    }
+
+    async fn remote_addr(&self) -> Option<SocketAddr> {
+        self.next_conn.remote_addr().await
+    }
+
+    async fn close(&self) -> Result<()> {
+        self.next_conn.close().await
+    }
+}
@@ -0,0 +1,242 @@
+use super::{CapturedPacket, PacketSink};
+
+use std::io::{self, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::UNIX_EPOCH;
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::{mpsc, oneshot};
+
+/// DEFAULT_QUEUE_SIZE is how many packets PcapFileSink::create lets wait for the writer
+pub const DEFAULT_QUEUE_SIZE: usize = 1024;
+
+const PCAP_MAGIC: u32 = 0xa1b2_c3d4;
+const PCAP_VERSION_MAJOR: u16 = 2;
+const PCAP_VERSION_MINOR: u16 = 4;
+const PCAP_SNAPLEN: u32 = 65535;
+/// LINKTYPE_RAW, each record starts with an IPv4 or IPv6 header
+const PCAP_LINKTYPE_RAW: u32 = 101;
+
+const IPV4_HEADER_SIZE: usize = 20;
+const IPV6_HEADER_SIZE: usize = 40;
+const UDP_HEADER_SIZE: usize = 8;
+const IP_PROTOCOL_UDP: u8 = 17;
+const IP_TTL: u8 = 64;
+
+enum WriterMessage {
+    Record(Vec<u8>),
+    Flush(oneshot::Sender<io::Result<()>>),
+}
+
+/// PcapFileSink writes the captured packets to a pcap file that Wireshark and tcpdump open.
+/// The packets are given made up IP and UDP headers carrying their addresses. A writer task
+/// does the file I/O, when it falls behind by more than the queue size packets are dropped
+/// and counted, see dropped.
+pub struct PcapFileSink {
+    writer_tx: mpsc::Sender<WriterMessage>,
+    dropped: AtomicU64,
+}
+
+impl PcapFileSink {
+    /// create truncates or creates the file at path, writes the pcap header and starts the
+    /// writer task. It must be called within a tokio runtime.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::with_queue_size(path, DEFAULT_QUEUE_SIZE)
+    }
+
+    /// with_queue_size is create with a given number of packets that may wait for the writer.
+    pub fn with_queue_size(path: impl AsRef<Path>, queue_size: usize) -> io::Result<Self> {
+        let mut file = std::fs::File::create(path)?;
+        file.write_all(&pcap_header())?;
+
+        let (writer_tx, writer_rx) = mpsc::channel(queue_size.max(1));
+        tokio::spawn(Self::write_loop(tokio::fs::File::from_std(file), writer_rx));
+
+        Ok(PcapFileSink {
+            writer_tx,
+            dropped: AtomicU64::new(0),
+        })
+    }
+
+    /// dropped returns how many packets were not written because the writer fell behind or
+    /// failed.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::SeqCst)
+    }
+
+    /// flush waits until the packets captured so far are written to the file.
+    pub async fn flush(&self) -> io::Result<()> {
+        let (done_tx, done_rx) = oneshot::channel();
+        let closed = || io::Error::new(io::ErrorKind::BrokenPipe, "pcap writer stopped");
+        self.writer_tx
+            .send(WriterMessage::Flush(done_tx))
+            .await
+            .map_err(|_| closed())?;
+        done_rx.await.map_err(|_| closed())?
+    }
+
+    async fn write_loop(file: tokio::fs::File, mut writer_rx: mpsc::Receiver<WriterMessage>) {
+        let mut writer = BufWriter::new(file);
+        while let Some(message) = writer_rx.recv().await {
+            let result = match message {
+                WriterMessage::Record(record) => writer.write_all(&record).await,
+                WriterMessage::Flush(done_tx) => {
+                    let _ = done_tx.send(writer.flush().await);
+                    Ok(())
+                }
+            };
+            if let Err(err) = result {
+                log::warn!("failed to write pcap file: {}", err);
+                return;
+            }
+        }
+
+        if let Err(err) = writer.flush().await {
+            log::warn!("failed to write pcap file: {}", err);
+        }
+    }
+}
+
+impl PacketSink for PcapFileSink {
+    fn capture(&self, packet: CapturedPacket<'_>) {
+        let record = pcap_record(&packet);
+        if self
+            .writer_tx
+            .try_send(WriterMessage::Record(record))
+            .is_err()
+        {
+            self.dropped.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+}
+
+fn pcap_header() -> Vec<u8> {
+    let mut header = Vec::with_capacity(24);
+    header.extend_from_slice(&PCAP_MAGIC.to_le_bytes());
+    header.extend_from_slice(&PCAP_VERSION_MAJOR.to_le_bytes());
+    header.extend_from_slice(&PCAP_VERSION_MINOR.to_le_bytes());
+    header.extend_from_slice(&0i32.to_le_bytes()); // thiszone
+    header.extend_from_slice(&0u32.to_le_bytes()); // sigfigs
+    header.extend_from_slice(&PCAP_SNAPLEN.to_le_bytes());
+    header.extend_from_slice(&PCAP_LINKTYPE_RAW.to_le_bytes());
+    header
+}
+
+/// pcap_record frames a packet as a pcap record holding an IP and UDP header and the payload
+fn pcap_record(packet: &CapturedPacket<'_>) -> Vec<u8> {
+    let (src, dst) = match packet.direction {
+        super::PacketDirection::Outbound => (packet.local_addr, packet.remote_addr),
+        super::PacketDirection::Inbound => (packet.remote_addr, packet.local_addr),
+    };
+    let datagram = ip_datagram(src, dst, packet.payload);
+
+    let timestamp = packet
+        .timestamp
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let orig_len = datagram.len() as u32;
+    let incl_len = orig_len.min(PCAP_SNAPLEN);
+
+    let mut record = Vec::with_capacity(16 + incl_len as usize);
+    record.extend_from_slice(&(timestamp.as_secs() as u32).to_le_bytes());
+    record.extend_from_slice(&timestamp.subsec_micros().to_le_bytes());
+    record.extend_from_slice(&incl_len.to_le_bytes());
+    record.extend_from_slice(&orig_len.to_le_bytes());
+    record.extend_from_slice(&datagram[..incl_len as usize]);
+    record
+}
+
+/// ip_datagram builds an IPv4 datagram, or an IPv6 one if either address is IPv6, in which
+/// case an IPv4 address is written IPv4-mapped.
+fn ip_datagram(src: SocketAddr, dst: SocketAddr, payload: &[u8]) -> Vec<u8> {
+    let udp_len = (UDP_HEADER_SIZE + payload.len()) as u16;
+    let mut udp = Vec::with_capacity(udp_len as usize);
+    udp.extend_from_slice(&src.port().to_be_bytes());
+    udp.extend_from_slice(&dst.port().to_be_bytes());
+    udp.extend_from_slice(&udp_len.to_be_bytes());
+    udp.extend_from_slice(&[0, 0]); // checksum
+    udp.extend_from_slice(payload);
+
+    let mut datagram;
+    match (src.ip(), dst.ip()) {
+        (IpAddr::V4(src_ip), IpAddr::V4(dst_ip)) => {
+            datagram = Vec::with_capacity(IPV4_HEADER_SIZE + udp.len());
+            datagram.push(0x45); // version 4, 5 words of header
+            datagram.push(0); // DSCP and ECN
+            datagram.extend_from_slice(&((IPV4_HEADER_SIZE + udp.len()) as u16).to_be_bytes());
+            datagram.extend_from_slice(&[0, 0, 0x40, 0]); // identification, don't fragment
+            datagram.push(IP_TTL);
+            datagram.push(IP_PROTOCOL_UDP);
+            datagram.extend_from_slice(&[0, 0]); // checksum
+            datagram.extend_from_slice(&src_ip.octets());
+            datagram.extend_from_slice(&dst_ip.octets());
+            let checksum = internet_checksum(&[&datagram]);
+            datagram[10..12].copy_from_slice(&checksum.to_be_bytes());
+
+            let mut pseudo_header = Vec::with_capacity(12);
+            pseudo_header.extend_from_slice(&src_ip.octets());
+            pseudo_header.extend_from_slice(&dst_ip.octets());
+            pseudo_header.extend_from_slice(&[0, IP_PROTOCOL_UDP]);
+            pseudo_header.extend_from_slice(&udp_len.to_be_bytes());
+            set_udp_checksum(&mut udp, &pseudo_header);
+        }
+        (src_ip, dst_ip) => {
+            let src_ip = match src_ip {
+                IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+                IpAddr::V6(ip) => ip,
+            };
+            let dst_ip = match dst_ip {
+                IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+                IpAddr::V6(ip) => ip,
+            };
+
+            datagram = Vec::with_capacity(IPV6_HEADER_SIZE + udp.len());
+            datagram.extend_from_slice(&[0x60, 0, 0, 0]); // version 6, no class or flow label
+            datagram.extend_from_slice(&udp_len.to_be_bytes());
+            datagram.push(IP_PROTOCOL_UDP);
+            datagram.push(IP_TTL);
+            datagram.extend_from_slice(&src_ip.octets());
+            datagram.extend_from_slice(&dst_ip.octets());
+
+            let mut pseudo_header = Vec::with_capacity(40);
+            pseudo_header.extend_from_slice(&src_ip.octets());
+            pseudo_header.extend_from_slice(&dst_ip.octets());
+            pseudo_header.extend_from_slice(&(udp_len as u32).to_be_bytes());
+            pseudo_header.extend_from_slice(&[0, 0, 0, IP_PROTOCOL_UDP]);
+            set_udp_checksum(&mut udp, &pseudo_header);
+        }
+    }
+
+    datagram.extend_from_slice(&udp);
+    datagram
+}
+
+fn set_udp_checksum(udp: &mut [u8], pseudo_header: &[u8]) {
+    let checksum = match internet_checksum(&[pseudo_header, udp]) {
+        // zero means no checksum in UDP, a computed zero is sent as all ones (RFC 768)
+        0 => 0xffff,
+        checksum => checksum,
+    };
+    udp[6..8].copy_from_slice(&checksum.to_be_bytes());
+}
+
+/// internet_checksum is the one's complement sum of RFC 1071 over the concatenated parts,
+/// each part but the last must have an even length
+fn internet_checksum(parts: &[&[u8]]) -> u16 {
+    let mut sum = 0u32;
+    for part in parts {
+        for word in part.chunks(2) {
+            let word = if word.len() == 2 {
+                u16::from_be_bytes([word[0], word[1]])
+            } else {
+                u16::from_be_bytes([word[0], 0])
+            };
+            sum += word as u32;
+        }
+    }
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
@@ -1,4 +1,5 @@
 use crate::webrtc::stun::attributes::ATTR_PRIORITY;
+use crate::webrtc::stun::checks::check_size;
 use crate::webrtc::stun::message::*;
 
 /// Represents PRIORITY attribute.
@@ -16,3 +17,13 @@ impl Setter for PriorityAttr {
         Ok(())
     }
 }
+
+impl Getter for PriorityAttr {
+    // get_from decodes PRIORITY attribute from message.
+    fn get_from(&mut self, m: &Message) -> Result<(), crate::webrtc::stun::Error> {
+        let v = m.get(ATTR_PRIORITY)?;
+        check_size(ATTR_PRIORITY, v.len(), PRIORITY_SIZE)?;
+        self.0 = u32::from_be_bytes([v[0], v[1], v[2], v[3]]);
+        Ok(())
+    }
+}
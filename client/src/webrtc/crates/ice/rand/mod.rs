@@ -1,4 +1,4 @@
-use rand::{thread_rng, Rng};
+use crate::webrtc::util::random_source::RandomSource;
 
 const RUNES_ALPHA: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
 const RUNES_CANDIDATE_ID_FOUNDATION: &[u8] =
@@ -7,39 +7,41 @@ const RUNES_CANDIDATE_ID_FOUNDATION: &[u8] =
 const LEN_UFRAG: usize = 16;
 const LEN_PWD: usize = 32;
 
-//TODO: generates a random string for cryptographic usage.
-pub(crate) fn generate_crypto_random_string(n: usize, runes: &[u8]) -> String {
-    let mut rng = thread_rng();
+/// Minimum ufrag and pwd lengths, see RFC 8445 Section 5.3.
+pub(crate) const MIN_UFRAG_LEN: usize = 4;
+pub(crate) const MIN_PWD_LEN: usize = 22;
 
-    let rand_string: String = (0..n)
-        .map(|_| {
-            let idx = rng.gen_range(0..runes.len());
-            runes[idx] as char
-        })
-        .collect();
-
-    rand_string
+/// generate_crypto_random_string generates a random string for cryptographic usage, drawn
+/// from rng.
+pub(crate) fn generate_crypto_random_string(n: usize, runes: &[u8], rng: &RandomSource) -> String {
+    rng.string(n, runes)
 }
 
 /// https://tools.ietf.org/html/rfc5245#section-15.1
 /// candidate-id = "candidate" ":" foundation
 /// foundation   = 1*32ice-char
 /// ice-char     = ALPHA / DIGIT / "+" / "/"
-pub(crate) fn generate_cand_id() -> String {
+pub(crate) fn generate_cand_id(rng: &RandomSource) -> String {
     format!(
         "candidate:{}",
-        generate_crypto_random_string(32, RUNES_CANDIDATE_ID_FOUNDATION)
+        generate_crypto_random_string(32, RUNES_CANDIDATE_ID_FOUNDATION, rng)
     )
 }
 
 /// Generates ICE pwd.
 /// This internally uses `generate_crypto_random_string`.
-pub(crate) fn generate_pwd() -> String {
-    generate_crypto_random_string(LEN_PWD, RUNES_ALPHA)
+pub(crate) fn generate_pwd(rng: &RandomSource) -> String {
+    generate_crypto_random_string(LEN_PWD, RUNES_ALPHA, rng)
 }
 
 /// ICE user fragment.
 /// This internally uses `generate_crypto_random_string`.
-pub(crate) fn generate_ufrag() -> String {
-    generate_crypto_random_string(LEN_UFRAG, RUNES_ALPHA)
+pub(crate) fn generate_ufrag(rng: &RandomSource) -> String {
+    generate_crypto_random_string(LEN_UFRAG, RUNES_ALPHA, rng)
+}
+
+/// is_ice_chars reports whether s only contains ice-chars, ALPHA / DIGIT / "+" / "/".
+pub(crate) fn is_ice_chars(s: &str) -> bool {
+    s.bytes()
+        .all(|b| RUNES_CANDIDATE_ID_FOUNDATION.contains(&b))
 }
@@ -0,0 +1,51 @@
+use std::fmt;
+
+/// TcpType is the type of an ICE TCP candidate, it tells which side opens the connection, see
+/// RFC 6544 Section 4.5.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Default)]
+pub(crate) enum TcpType {
+    /// Unspecified is the type of UDP candidates.
+    #[default]
+    Unspecified = 0,
+    /// Active candidates open connections and never accept them, they are advertised with the
+    /// discard port 9.
+    Active = 1,
+    /// Passive candidates accept connections and never open them.
+    Passive = 2,
+    /// SimultaneousOpen candidates open connections at the same time as their peer.
+    SimultaneousOpen = 3,
+}
+
+impl From<u8> for TcpType {
+    fn from(v: u8) -> Self {
+        match v {
+            1 => Self::Active,
+            2 => Self::Passive,
+            3 => Self::SimultaneousOpen,
+            _ => Self::Unspecified,
+        }
+    }
+}
+
+impl From<&str> for TcpType {
+    fn from(raw: &str) -> Self {
+        match raw {
+            "active" => Self::Active,
+            "passive" => Self::Passive,
+            "so" => Self::SimultaneousOpen,
+            _ => Self::Unspecified,
+        }
+    }
+}
+
+impl fmt::Display for TcpType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match *self {
+            Self::Active => "active",
+            Self::Passive => "passive",
+            Self::SimultaneousOpen => "so",
+            Self::Unspecified => "unspecified",
+        };
+        write!(f, "{}", s)
+    }
+}
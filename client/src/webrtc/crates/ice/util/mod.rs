@@ -3,10 +3,13 @@ use crate::webrtc::ice::error::*;
 use crate::webrtc::ice::network_type::*;
 
 use crate::webrtc::stun::{attributes::*, integrity::*, message::*, textattrs::*};
-use crate::webrtc::util::{vnet::net::*, Conn};
+use crate::webrtc::util::{redaction::Redacted, vnet::interface::Interface, vnet::net::*, Conn};
 use std::collections::HashSet;
+use std::io;
 use std::net::{IpAddr, SocketAddr};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::net::UdpSocket;
 
 pub(crate) fn create_addr(_network: NetworkType, ip: IpAddr, port: u16) -> SocketAddr {
     /*if network.is_tcp(){
@@ -33,7 +36,27 @@ pub(crate) fn assert_inbound_username(m: &Message, expected_username: &str) -> R
     Ok(())
 }
 
+/// assert_inbound_username_prefix checks that the USERNAME of a request names local_ufrag as the
+/// receiver, for a request that arrives before the remote ufrag is known.
+pub(crate) fn assert_inbound_username_prefix(m: &Message, local_ufrag: &str) -> Result<()> {
+    let mut username = Username::new(ATTR_USERNAME, String::new());
+    username.get_from(m)?;
+
+    let username = username.to_string();
+    match username.split_once(':') {
+        Some((receiver, sender)) if receiver == local_ufrag && !sender.is_empty() => Ok(()),
+        _ => Err(Error::ErrMismatchUsername),
+    }
+}
+
+/// assert_inbound_message_integrity checks MESSAGE-INTEGRITY-SHA256 when the message carries
+/// it and MESSAGE-INTEGRITY otherwise, as RFC 8489 asks of an agent that supports both.
 pub(crate) fn assert_inbound_message_integrity(m: &mut Message, key: &[u8]) -> Result<()> {
+    if m.contains(ATTR_MESSAGE_INTEGRITY_SHA256) {
+        let message_integrity_attr = MessageIntegritySha256(key.to_vec());
+        return Ok(message_integrity_attr.check(m)?);
+    }
+
     let message_integrity_attr = MessageIntegrity(key.to_vec());
     Ok(message_integrity_attr.check(m)?)
 }
@@ -42,9 +65,18 @@ pub(crate) async fn local_interfaces(
     vnet: &Arc<Net>,
     interface_filter: &Option<InterfaceFilterFn>,
     network_types: &[NetworkType],
+) -> HashSet<IpAddr> {
+    filter_interfaces(vnet.get_interfaces().await, interface_filter, network_types)
+}
+
+/// filter_interfaces returns the addresses of the interfaces that pass the interface filter
+/// and belong to one of the requested network types.
+pub(crate) fn filter_interfaces(
+    interfaces: Vec<Interface>,
+    interface_filter: &Option<InterfaceFilterFn>,
+    network_types: &[NetworkType],
 ) -> HashSet<IpAddr> {
     let mut ips = HashSet::new();
-    let interfaces = vnet.get_interfaces().await;
 
     let (mut ipv4requested, mut ipv6requested) = (false, false);
     for typ in network_types {
@@ -77,6 +109,139 @@ pub(crate) async fn local_interfaces(
 pub(crate) async fn listen_udp_in_port_range(
     vnet: &Arc<Net>,
     laddr: SocketAddr,
+    dscp: Option<u8>,
+    socket_buffers: &SocketBuffers,
 ) -> Result<Arc<dyn Conn + Send + Sync>> {
-    return Ok(vnet.bind(laddr).await?);
+    // vnet sockets have no IP header to mark and no kernel buffers to size
+    if vnet.is_virtual() {
+        return Ok(vnet.bind(laddr).await?);
+    }
+
+    let socket = UdpSocket::bind(laddr).await?;
+    if let Some(dscp) = dscp {
+        if let Err(err) = set_dscp(&socket, dscp) {
+            // some platforms refuse the option, which must not fail gathering
+            if !DSCP_WARNED.swap(true, Ordering::SeqCst) {
+                log::warn!(
+                    "could not set DSCP {} on {}: {}",
+                    dscp,
+                    Redacted(&laddr),
+                    err
+                );
+            }
+        }
+    }
+    socket_buffers.apply(&socket);
+    Ok(Arc::new(socket))
+}
+
+static DSCP_WARNED: AtomicBool = AtomicBool::new(false);
+static SOCKET_BUFFERS_WARNED: AtomicBool = AtomicBool::new(false);
+
+/// SocketBufferSizes are the SO_RCVBUF and SO_SNDBUF sizes of a UDP socket in bytes, see
+/// SettingEngine::set_socket_buffer_sizes and SocketStats::socket_buffer_sizes. None leaves
+/// the size at the default of the OS, or could not be read back.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SocketBufferSizes {
+    pub recv: Option<usize>,
+    pub send: Option<usize>,
+}
+
+/// SocketBuffers sizes the buffers of the UDP sockets ICE binds or is handed, and keeps the
+/// sizes the OS reports back. The kernel clamps a size to its limits, e.g. net.core.rmem_max
+/// on Linux, which also reports twice the size set to account for its bookkeeping.
+#[derive(Default, Debug)]
+pub(crate) struct SocketBuffers {
+    requested: SocketBufferSizes,
+    // the sizes read back from the socket sized last, every socket gets the same request
+    effective: Mutex<SocketBufferSizes>,
+}
+
+impl SocketBuffers {
+    pub(crate) fn new(requested: SocketBufferSizes) -> Self {
+        SocketBuffers {
+            requested,
+            ..Default::default()
+        }
+    }
+
+    /// apply sets the requested sizes on socket and records the sizes it reports back. A size
+    /// the OS refuses is logged once and left at the default, it must not fail gathering.
+    pub(crate) fn apply(&self, socket: &UdpSocket) {
+        let socket_ref = socket2::SockRef::from(socket);
+        if let Some(size) = self.requested.recv {
+            if let Err(err) = socket_ref.set_recv_buffer_size(size) {
+                warn_socket_buffers("receive", size, &err);
+            }
+        }
+        if let Some(size) = self.requested.send {
+            if let Err(err) = socket_ref.set_send_buffer_size(size) {
+                warn_socket_buffers("send", size, &err);
+            }
+        }
+
+        let effective = SocketBufferSizes {
+            recv: socket_ref.recv_buffer_size().ok(),
+            send: socket_ref.send_buffer_size().ok(),
+        };
+        *self.effective.lock().unwrap() = effective;
+    }
+
+    /// effective returns the sizes the socket sized last reports, None before any was sized
+    pub(crate) fn effective(&self) -> SocketBufferSizes {
+        *self.effective.lock().unwrap()
+    }
+}
+
+fn warn_socket_buffers(direction: &str, size: usize, err: &io::Error) {
+    if !SOCKET_BUFFERS_WARNED.swap(true, Ordering::SeqCst) {
+        log::warn!(
+            "could not set the socket {} buffer size to {}: {}",
+            direction,
+            size,
+            err
+        );
+    }
+}
+
+/// set_dscp marks every packet sent from the socket with the given DSCP code point, through
+/// IP_TOS for IPv4 and IPV6_TCLASS for IPv6 sockets.
+pub(crate) fn set_dscp(socket: &UdpSocket, dscp: u8) -> io::Result<()> {
+    // DSCP occupies the upper six bits of the TOS / traffic class octet
+    let tos = u32::from(dscp & 0x3f) << 2;
+    if socket.local_addr()?.is_ipv4() {
+        socket2::SockRef::from(socket).set_tos(tos)
+    } else {
+        set_tclass_v6(socket, tos)
+    }
+}
+
+#[cfg(unix)]
+fn set_tclass_v6(socket: &UdpSocket, tclass: u32) -> io::Result<()> {
+    use nix::libc;
+    use std::os::unix::io::AsRawFd;
+
+    let tclass = tclass as libc::c_int;
+    let ret = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::IPPROTO_IPV6,
+            libc::IPV6_TCLASS,
+            &tclass as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(unix))]
+fn set_tclass_v6(_socket: &UdpSocket, _tclass: u32) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "IPV6_TCLASS is not supported on this platform",
+    ))
 }
@@ -0,0 +1,1147 @@
+use async_trait::async_trait;
+
+use super::*;
+
+use crate::webrtc::sctp_transport::transport_events::TransportEventKind;
+
+/// InflightOutbound is what AssociationInflight::gather_outbound took from the in-flight state
+/// for one write loop round, AssociationInternal::gather_outbound packs it into packets.
+#[derive(Default)]
+pub(crate) struct InflightOutbound {
+    /// retransmit are the chunks the T3-rtx timer marked for retransmission
+    pub(crate) retransmit: Vec<ChunkPayloadData>,
+    /// data are the chunks moved from the pending queue into flight
+    pub(crate) data: Vec<ChunkPayloadData>,
+    /// sis_to_reset are the streams whose reset was queued behind their data
+    pub(crate) sis_to_reset: Vec<u16>,
+    pub(crate) fast_retransmit: Vec<Box<dyn Chunk + Send + Sync>>,
+    pub(crate) forward_tsn: Option<Box<dyn Chunk + Send + Sync>>,
+    /// last_tsn is the TSN of the last chunk put in flight
+    pub(crate) last_tsn: u32,
+    pub(crate) cumulative_tsn_ack_point: u32,
+    pub(crate) rto: u64,
+    pub(crate) mtu: u32,
+}
+
+/// AssociationInflight is the send side of an association: the DATA chunks in flight, the
+/// cumulative TSN ack point and the congestion control they drive. It has a lock of its own so
+/// that SACKs, T3-rtx timeouts and the write loop moving chunks into flight do not wait on the
+/// association lock, which inbound DATA holds. Code holding both takes the association lock
+/// first, AssociationInflight never takes it.
+#[derive(Default)]
+pub(crate) struct AssociationInflight {
+    name: String,
+    state: Arc<AtomicU8>,
+    awake_write_loop_ch: Option<Arc<mpsc::Sender<()>>>,
+
+    pub(crate) my_next_tsn: u32, // nextTSN
+    min_tsn2measure_rtt: u32,    // for RTT measurement
+    will_send_forward_tsn: bool,
+    will_retransmit_fast: bool,
+
+    inflight_queue: PayloadQueue,
+    pending_queue: Arc<PendingQueue>,
+    send_buffer: Arc<SendBuffer>,
+    streams: StreamMap,
+    pub(crate) mtu: u32,
+    cumulative_tsn_ack_point: u32,
+    advanced_peer_tsn_ack_point: u32,
+    pub(crate) use_forward_tsn: bool,
+
+    // Congestion control parameters
+    pub(crate) cwnd: u32,     // my congestion window size
+    pub(crate) rwnd: u32,     // calculated peer's receiver windows size
+    pub(crate) ssthresh: u32, // slow start threshold
+    partial_bytes_acked: u32,
+    in_fast_recovery: bool,
+    fast_recover_exit_point: u32,
+
+    // RTX timer
+    pub(crate) rto_mgr: RtoManager,
+    pub(crate) t3rtx: Option<RtxTimer<AssociationInflight>>,
+
+    stats: Arc<AssociationStats>,
+    transport_events: Arc<TransportEvents>,
+}
+
+impl AssociationInflight {
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        name: String,
+        state: Arc<AtomicU8>,
+        awake_write_loop_ch: Arc<mpsc::Sender<()>>,
+        tsn: u32,
+        mtu: u32,
+        pending_queue: Arc<PendingQueue>,
+        send_buffer: Arc<SendBuffer>,
+        streams: StreamMap,
+        stats: Arc<AssociationStats>,
+        transport_events: Arc<TransportEvents>,
+    ) -> Self {
+        let mut a = AssociationInflight {
+            name,
+            state,
+            awake_write_loop_ch: Some(awake_write_loop_ch),
+            my_next_tsn: tsn,
+            min_tsn2measure_rtt: tsn,
+            inflight_queue: PayloadQueue::new(Arc::new(AtomicUsize::new(0))),
+            pending_queue,
+            send_buffer,
+            streams,
+            mtu,
+            cumulative_tsn_ack_point: tsn - 1,
+            advanced_peer_tsn_ack_point: tsn - 1,
+            rto_mgr: RtoManager::new(),
+            stats,
+            transport_events,
+            ..Default::default()
+        };
+
+        // RFC 4690 Sec 7.2.1
+        //  o  The initial cwnd before DATA transmission or after a sufficiently
+        //     long idle period MUST be set to min(4*MTU, max (2*MTU, 4380
+        //     bytes)).
+        a.cwnd = std::cmp::min(4 * a.mtu, std::cmp::max(2 * a.mtu, 4380));
+        log::trace!(
+            "[{}] updated cwnd={} ssthresh={} inflight={} (INI)",
+            a.name,
+            a.cwnd,
+            a.ssthresh,
+            a.inflight_queue.get_num_bytes()
+        );
+
+        a
+    }
+
+    /// close stops the T3-rtx timer and drops the chunks in flight, nothing is sent anymore
+    pub(crate) async fn close(&mut self) {
+        if let Some(t3rtx) = &self.t3rtx {
+            t3rtx.stop().await;
+        }
+        self.inflight_queue = PayloadQueue::new(Arc::clone(&self.inflight_queue.length));
+    }
+
+    /// update_send_buffer reports the bytes in flight to the send buffer, to be called after
+    /// the inflight queue changed, see SendBuffer
+    pub(crate) fn update_send_buffer(&self) {
+        if self.get_state() != AssociationState::Closed {
+            self.send_buffer
+                .set_outstanding_bytes(self.inflight_queue.get_num_bytes());
+        }
+    }
+
+    /// is_empty reports whether no DATA chunk is in flight
+    pub(crate) fn is_empty(&self) -> bool {
+        self.inflight_queue.is_empty()
+    }
+
+    /// get_num_bytes returns the bytes of user data in flight
+    pub(crate) fn get_num_bytes(&self) -> usize {
+        self.inflight_queue.get_num_bytes()
+    }
+
+    fn awake_write_loop(&self) {
+        if let Some(awake_write_loop_ch) = &self.awake_write_loop_ch {
+            let _ = awake_write_loop_ch.try_send(());
+        }
+    }
+
+    fn get_state(&self) -> AssociationState {
+        self.state.load(Ordering::SeqCst).into()
+    }
+
+    /// gather_outbound takes the DATA chunks to send in state: those due for retransmission,
+    /// those cwnd and rwnd let into flight and the fast retransmissions, and the FORWARD TSN
+    /// or I-FORWARD-TSN chunk if one is due.
+    pub(crate) async fn gather_outbound(
+        &mut self,
+        state: AssociationState,
+        use_interleaving: bool,
+    ) -> InflightOutbound {
+        let mut outbound = InflightOutbound::default();
+        match state {
+            AssociationState::Established => {
+                outbound.retransmit = self.get_data_chunks_to_retransmit();
+
+                // Pop unsent data chunks from the pending queue to send as much as
+                // cwnd and rwnd allow.
+                let (data, sis_to_reset) = self.pop_pending_data_chunks_to_send().await;
+                if !data.is_empty() {
+                    // Start timer. (noop if already started)
+                    log::trace!("[{}] T3-rtx timer start (pt1)", self.name);
+                    if let Some(t3rtx) = &self.t3rtx {
+                        t3rtx.start(self.rto_mgr.get_rto()).await;
+                    }
+                }
+                outbound.data = data;
+                outbound.sis_to_reset = sis_to_reset;
+
+                outbound.fast_retransmit = self.get_fast_retransmission_chunks();
+                outbound.forward_tsn = self.get_forward_tsn_chunk(use_interleaving);
+            }
+            AssociationState::ShutdownPending
+            | AssociationState::ShutdownSent
+            | AssociationState::ShutdownReceived => {
+                outbound.retransmit = self.get_data_chunks_to_retransmit();
+                outbound.fast_retransmit = self.get_fast_retransmission_chunks();
+            }
+            _ => {}
+        }
+
+        outbound.last_tsn = self.my_next_tsn.wrapping_sub(1);
+        outbound.cumulative_tsn_ack_point = self.cumulative_tsn_ack_point;
+        outbound.rto = self.rto_mgr.get_rto();
+        outbound.mtu = self.mtu;
+        outbound
+    }
+
+    fn get_fast_retransmission_chunks(&mut self) -> Vec<Box<dyn Chunk + Send + Sync>> {
+        let mut to_fast_retrans: Vec<Box<dyn Chunk + Send + Sync>> = vec![];
+        if !self.will_retransmit_fast {
+            return to_fast_retrans;
+        }
+        self.will_retransmit_fast = false;
+
+        let mut fast_retrans_size = COMMON_HEADER_SIZE;
+
+        let mut i = 0;
+        loop {
+            let tsn = self.cumulative_tsn_ack_point + i + 1;
+            if let Some(c) = self.inflight_queue.get_mut(tsn) {
+                if c.acked || c.abandoned() || c.nsent > 1 || c.miss_indicator < 3 {
+                    i += 1;
+                    continue;
+                }
+
+                // RFC 4960 Sec 7.2.4 Fast Retransmit on Gap Reports
+                //  3)  Determine how many of the earliest (i.e., lowest TSN) DATA chunks
+                //      marked for retransmission will fit into a single packet, subject
+                //      to constraint of the path MTU of the destination transport
+                //      address to which the packet is being sent.  Call this value K.
+                //      Retransmit those K DATA chunks in a single packet.  When a Fast
+                //      Retransmit is being performed, the sender SHOULD ignore the value
+                //      of cwnd and SHOULD NOT delay retransmission for this single
+                //		packet.
+
+                let data_chunk_size = data_chunk_header_size(c) + c.user_data.len() as u32;
+                if self.mtu < fast_retrans_size + data_chunk_size {
+                    break;
+                }
+
+                fast_retrans_size += data_chunk_size;
+                self.stats.inc_fast_retrans();
+                c.nsent += 1;
+            } else {
+                break; // end of pending data
+            }
+
+            if let Some(c) = self.inflight_queue.get(tsn) {
+                self.check_partial_reliability_status(c);
+                to_fast_retrans.push(Box::new(c.clone()));
+                log::trace!(
+                    "[{}] fast-retransmit: tsn={} sent={} htna={}",
+                    self.name,
+                    c.tsn,
+                    c.nsent,
+                    self.fast_recover_exit_point
+                );
+            }
+            i += 1;
+        }
+
+        if !to_fast_retrans.is_empty() {
+            self.transport_events
+                .record(TransportEventKind::Retransmission {
+                    timeout: false,
+                    chunks: to_fast_retrans.len() as u64,
+                });
+        }
+
+        to_fast_retrans
+    }
+
+    fn get_forward_tsn_chunk(
+        &mut self,
+        use_interleaving: bool,
+    ) -> Option<Box<dyn Chunk + Send + Sync>> {
+        if !self.will_send_forward_tsn {
+            return None;
+        }
+        self.will_send_forward_tsn = false;
+
+        if !sna32gt(
+            self.advanced_peer_tsn_ack_point,
+            self.cumulative_tsn_ack_point,
+        ) {
+            return None;
+        }
+
+        if use_interleaving {
+            Some(Box::new(self.create_i_forward_tsn()))
+        } else {
+            Some(Box::new(self.create_forward_tsn()))
+        }
+    }
+
+    async fn process_selective_ack(
+        &mut self,
+        d: &ChunkSelectiveAck,
+    ) -> Result<(HashMap<u16, i64>, u32)> {
+        let mut bytes_acked_per_stream = HashMap::new();
+
+        // New ack point, so pop all ACKed packets from inflight_queue
+        // We add 1 because the "currentAckPoint" has already been popped from the inflight queue
+        // For the first SACK we take care of this by setting the ackpoint to cumAck - 1
+        let mut i = self.cumulative_tsn_ack_point + 1;
+        //log::debug!("[{}] i={} d={}", self.name, i, d.cumulative_tsn_ack);
+        while sna32lte(i, d.cumulative_tsn_ack) {
+            if let Some(c) = self.inflight_queue.pop(i) {
+                if !c.acked {
+                    // RFC 4096 sec 6.3.2.  Retransmission Timer Rules
+                    //   R3)  Whenever a SACK is received that acknowledges the DATA chunk
+                    //        with the earliest outstanding TSN for that address, restart the
+                    //        T3-rtx timer for that address with its current RTO (if there is
+                    //        still outstanding data on that address).
+                    if i == self.cumulative_tsn_ack_point + 1 {
+                        // T3 timer needs to be reset. Stop it for now.
+                        if let Some(t3rtx) = &self.t3rtx {
+                            t3rtx.stop().await;
+                        }
+                    }
+
+                    let n_bytes_acked = c.user_data.len() as i64;
+
+                    // Sum the number of bytes acknowledged per stream
+                    if let Some(amount) = bytes_acked_per_stream.get_mut(&c.stream_identifier) {
+                        *amount += n_bytes_acked;
+                    } else {
+                        bytes_acked_per_stream.insert(c.stream_identifier, n_bytes_acked);
+                    }
+
+                    // RFC 4960 sec 6.3.1.  RTO Calculation
+                    //   C4)  When data is in flight and when allowed by rule C5 below, a new
+                    //        RTT measurement MUST be made each round trip.  Furthermore, new
+                    //        RTT measurements SHOULD be made no more than once per round trip
+                    //        for a given destination transport address.
+                    //   C5)  Karn's algorithm: RTT measurements MUST NOT be made using
+                    //        packets that were retransmitted (and thus for which it is
+                    //        ambiguous whether the reply was for the first instance of the
+                    //        chunk or for a later instance)
+                    if c.nsent == 1 && sna32gte(c.tsn, self.min_tsn2measure_rtt) {
+                        self.min_tsn2measure_rtt = self.my_next_tsn;
+                        let rtt = c.since.elapsed();
+                        let srtt = self.rto_mgr.set_new_rtt(rtt.as_millis() as u64);
+                        log::trace!(
+                            "[{}] SACK: measured-rtt={} srtt={} new-rto={}",
+                            self.name,
+                            rtt.as_millis(),
+                            srtt,
+                            self.rto_mgr.get_rto()
+                        );
+                    }
+                }
+
+                if self.in_fast_recovery && c.tsn == self.fast_recover_exit_point {
+                    log::debug!("[{}] exit fast-recovery", self.name);
+                    self.in_fast_recovery = false;
+                }
+            } else {
+                return Err(Error::ErrInflightQueueTsnPop);
+            }
+
+            i += 1;
+        }
+
+        let mut htna = d.cumulative_tsn_ack;
+
+        // Mark selectively acknowledged chunks as "acked"
+        for g in &d.gap_ack_blocks {
+            for i in g.start..=g.end {
+                let tsn = d.cumulative_tsn_ack + i as u32;
+
+                let (is_existed, is_acked) = if let Some(c) = self.inflight_queue.get(tsn) {
+                    (true, c.acked)
+                } else {
+                    (false, false)
+                };
+                let n_bytes_acked = if is_existed && !is_acked {
+                    self.inflight_queue.mark_as_acked(tsn) as i64
+                } else {
+                    0
+                };
+
+                if let Some(c) = self.inflight_queue.get(tsn) {
+                    if !is_acked {
+                        // Sum the number of bytes acknowledged per stream
+                        if let Some(amount) = bytes_acked_per_stream.get_mut(&c.stream_identifier) {
+                            *amount += n_bytes_acked;
+                        } else {
+                            bytes_acked_per_stream.insert(c.stream_identifier, n_bytes_acked);
+                        }
+
+                        log::trace!("[{}] tsn={} has been sacked", self.name, c.tsn);
+
+                        if c.nsent == 1 {
+                            self.min_tsn2measure_rtt = self.my_next_tsn;
+                            let rtt = c.since.elapsed();
+                            let srtt = self.rto_mgr.set_new_rtt(rtt.as_millis() as u64);
+                            log::trace!(
+                                "[{}] SACK: measured-rtt={} srtt={} new-rto={}",
+                                self.name,
+                                rtt.as_millis(),
+                                srtt,
+                                self.rto_mgr.get_rto()
+                            );
+                        }
+
+                        if sna32lt(htna, tsn) {
+                            htna = tsn;
+                        }
+                    }
+                } else {
+                    return Err(Error::ErrTsnRequestNotExist);
+                }
+            }
+        }
+
+        Ok((bytes_acked_per_stream, htna))
+    }
+
+    async fn on_cumulative_tsn_ack_point_advanced(&mut self, total_bytes_acked: i64) {
+        // RFC 4096, sec 6.3.2.  Retransmission Timer Rules
+        //   R2)  Whenever all outstanding data sent to an address have been
+        //        acknowledged, turn off the T3-rtx timer of that address.
+        if self.inflight_queue.is_empty() {
+            log::trace!(
+                "[{}] SACK: no more packet in-flight (pending={})",
+                self.name,
+                self.pending_queue.len()
+            );
+            if let Some(t3rtx) = &self.t3rtx {
+                t3rtx.stop().await;
+            }
+        } else {
+            log::trace!("[{}] T3-rtx timer start (pt2)", self.name);
+            if let Some(t3rtx) = &self.t3rtx {
+                t3rtx.start(self.rto_mgr.get_rto()).await;
+            }
+        }
+
+        // Update congestion control parameters
+        if self.cwnd <= self.ssthresh {
+            // RFC 4096, sec 7.2.1.  Slow-Start
+            //   o  When cwnd is less than or equal to ssthresh, an SCTP endpoint MUST
+            //		use the slow-start algorithm to increase cwnd only if the current
+            //      congestion window is being fully utilized, an incoming SACK
+            //      advances the Cumulative TSN Ack Point, and the data sender is not
+            //      in Fast Recovery.  Only when these three conditions are met can
+            //      the cwnd be increased; otherwise, the cwnd MUST not be increased.
+            //		If these conditions are met, then cwnd MUST be increased by, at
+            //      most, the lesser of 1) the total size of the previously
+            //      outstanding DATA chunk(s) acknowledged, and 2) the destination's
+            //      path MTU.
+            if !self.in_fast_recovery && self.pending_queue.len() > 0 {
+                self.cwnd += std::cmp::min(total_bytes_acked as u32, self.cwnd); // TCP way
+                                                                                 // self.cwnd += min32(uint32(total_bytes_acked), self.mtu) // SCTP way (slow)
+                log::trace!(
+                    "[{}] updated cwnd={} ssthresh={} acked={} (SS)",
+                    self.name,
+                    self.cwnd,
+                    self.ssthresh,
+                    total_bytes_acked
+                );
+            } else {
+                log::trace!(
+                    "[{}] cwnd did not grow: cwnd={} ssthresh={} acked={} FR={} pending={}",
+                    self.name,
+                    self.cwnd,
+                    self.ssthresh,
+                    total_bytes_acked,
+                    self.in_fast_recovery,
+                    self.pending_queue.len()
+                );
+            }
+        } else {
+            // RFC 4096, sec 7.2.2.  Congestion Avoidance
+            //   o  Whenever cwnd is greater than ssthresh, upon each SACK arrival
+            //      that advances the Cumulative TSN Ack Point, increase
+            //      partial_bytes_acked by the total number of bytes of all new chunks
+            //      acknowledged in that SACK including chunks acknowledged by the new
+            //      Cumulative TSN Ack and by Gap Ack Blocks.
+            self.partial_bytes_acked += total_bytes_acked as u32;
+
+            //   o  When partial_bytes_acked is equal to or greater than cwnd and
+            //      before the arrival of the SACK the sender had cwnd or more bytes
+            //      of data outstanding (i.e., before arrival of the SACK, flight size
+            //      was greater than or equal to cwnd), increase cwnd by MTU, and
+            //      reset partial_bytes_acked to (partial_bytes_acked - cwnd).
+            if self.partial_bytes_acked >= self.cwnd && self.pending_queue.len() > 0 {
+                self.partial_bytes_acked -= self.cwnd;
+                self.cwnd += self.mtu;
+                log::trace!(
+                    "[{}] updated cwnd={} ssthresh={} acked={} (CA)",
+                    self.name,
+                    self.cwnd,
+                    self.ssthresh,
+                    total_bytes_acked
+                );
+            }
+        }
+    }
+
+    fn process_fast_retransmission(
+        &mut self,
+        cum_tsn_ack_point: u32,
+        htna: u32,
+        cum_tsn_ack_point_advanced: bool,
+    ) -> Result<()> {
+        // HTNA algorithm - RFC 4960 Sec 7.2.4
+        // Increment missIndicator of each chunks that the SACK reported missing
+        // when either of the following is met:
+        // a)  Not in fast-recovery
+        //     miss indications are incremented only for missing TSNs prior to the
+        //     highest TSN newly acknowledged in the SACK.
+        // b)  In fast-recovery AND the Cumulative TSN Ack Point advanced
+        //     the miss indications are incremented for all TSNs reported missing
+        //     in the SACK.
+        if !self.in_fast_recovery || cum_tsn_ack_point_advanced {
+            let max_tsn = if !self.in_fast_recovery {
+                // a) increment only for missing TSNs prior to the HTNA
+                htna
+            } else {
+                // b) increment for all TSNs reported missing
+                cum_tsn_ack_point + (self.inflight_queue.len() as u32) + 1
+            };
+
+            let mut tsn = cum_tsn_ack_point + 1;
+            while sna32lt(tsn, max_tsn) {
+                if let Some(c) = self.inflight_queue.get_mut(tsn) {
+                    if !c.acked && !c.abandoned() && c.miss_indicator < 3 {
+                        c.miss_indicator += 1;
+                        if c.miss_indicator == 3 && !self.in_fast_recovery {
+                            // 2)  If not in Fast Recovery, adjust the ssthresh and cwnd of the
+                            //     destination address(es) to which the missing DATA chunks were
+                            //     last sent, according to the formula described in Section 7.2.3.
+                            self.in_fast_recovery = true;
+                            self.fast_recover_exit_point = htna;
+                            self.ssthresh = std::cmp::max(self.cwnd / 2, 4 * self.mtu);
+                            self.cwnd = self.ssthresh;
+                            self.partial_bytes_acked = 0;
+                            self.will_retransmit_fast = true;
+                            self.transport_events.record(
+                                TransportEventKind::CongestionWindowReduced {
+                                    timeout: false,
+                                    cwnd: self.cwnd,
+                                    ssthresh: self.ssthresh,
+                                },
+                            );
+
+                            log::trace!(
+                                "[{}] updated cwnd={} ssthresh={} inflight={} (FR)",
+                                self.name,
+                                self.cwnd,
+                                self.ssthresh,
+                                self.inflight_queue.get_num_bytes()
+                            );
+                        }
+                    }
+                } else {
+                    return Err(Error::ErrTsnRequestNotExist);
+                }
+
+                tsn += 1;
+            }
+        }
+
+        if self.in_fast_recovery && cum_tsn_ack_point_advanced {
+            self.will_retransmit_fast = true;
+        }
+
+        Ok(())
+    }
+
+    /// handle_sack_packet processes a packet of SACK chunks only, which the read loop hands over
+    /// without taking the association lock. The caller checked its verification tag and that
+    /// the association is established, see Association::read_loop.
+    pub(crate) async fn handle_sack_packet(&mut self, p: &Packet) -> Result<()> {
+        for c in &p.chunks {
+            c.check()?;
+            if let Some(sack) = c.as_any().downcast_ref::<ChunkSelectiveAck>() {
+                self.handle_sack(sack).await?;
+            }
+        }
+        self.update_send_buffer();
+
+        Ok(())
+    }
+
+    /// handle_sack processes a SACK, RFC 4960 Section 6.2.1. It returns whether the SACK was
+    /// taken and left no DATA in flight, which moves a shutting down association on, see
+    /// AssociationInternal::handle_sack.
+    pub(crate) async fn handle_sack(&mut self, d: &ChunkSelectiveAck) -> Result<bool> {
+        log::trace!(
+            "[{}] {}, SACK: cumTSN={} a_rwnd={}",
+            self.name,
+            self.cumulative_tsn_ack_point,
+            d.cumulative_tsn_ack,
+            d.advertised_receiver_window_credit
+        );
+        let state = self.get_state();
+        if state != AssociationState::Established
+            && state != AssociationState::ShutdownPending
+            && state != AssociationState::ShutdownReceived
+        {
+            return Ok(false);
+        }
+
+        self.stats.inc_sacks();
+
+        if sna32gt(self.cumulative_tsn_ack_point, d.cumulative_tsn_ack) {
+            // RFC 4960 sec 6.2.1.  Processing a Received SACK
+            // D)
+            //   i) If Cumulative TSN Ack is less than the Cumulative TSN Ack
+            //      Point, then drop the SACK.  Since Cumulative TSN Ack is
+            //      monotonically increasing, a SACK whose Cumulative TSN Ack is
+            //      less than the Cumulative TSN Ack Point indicates an out-of-
+            //      order SACK.
+
+            log::debug!(
+                "[{}] SACK Cumulative ACK {} is older than ACK point {}",
+                self.name,
+                d.cumulative_tsn_ack,
+                self.cumulative_tsn_ack_point
+            );
+
+            return Ok(false);
+        }
+
+        // Process selective ack
+        let (bytes_acked_per_stream, htna) = self.process_selective_ack(d).await?;
+
+        let mut total_bytes_acked = 0;
+        for n_bytes_acked in bytes_acked_per_stream.values() {
+            total_bytes_acked += *n_bytes_acked;
+        }
+
+        let mut cum_tsn_ack_point_advanced = false;
+        if sna32lt(self.cumulative_tsn_ack_point, d.cumulative_tsn_ack) {
+            log::trace!(
+                "[{}] SACK: cumTSN advanced: {} -> {}",
+                self.name,
+                self.cumulative_tsn_ack_point,
+                d.cumulative_tsn_ack
+            );
+
+            self.cumulative_tsn_ack_point = d.cumulative_tsn_ack;
+            cum_tsn_ack_point_advanced = true;
+            self.on_cumulative_tsn_ack_point_advanced(total_bytes_acked)
+                .await;
+        }
+
+        for (si, n_bytes_acked) in &bytes_acked_per_stream {
+            if let Some(s) = self.streams.get(*si) {
+                s.on_buffer_released(*n_bytes_acked).await;
+            }
+        }
+
+        // New rwnd value
+        // RFC 4960 sec 6.2.1.  Processing a Received SACK
+        // D)
+        //   ii) Set rwnd equal to the newly received a_rwnd minus the number
+        //       of bytes still outstanding after processing the Cumulative
+        //       TSN Ack and the Gap Ack Blocks.
+
+        // bytes acked were already subtracted by markAsAcked() method
+        let bytes_outstanding = self.inflight_queue.get_num_bytes() as u32;
+        if bytes_outstanding >= d.advertised_receiver_window_credit {
+            self.rwnd = 0;
+        } else {
+            self.rwnd = d.advertised_receiver_window_credit - bytes_outstanding;
+        }
+
+        self.process_fast_retransmission(d.cumulative_tsn_ack, htna, cum_tsn_ack_point_advanced)?;
+
+        if self.use_forward_tsn {
+            // RFC 3758 Sec 3.5 C1
+            if sna32lt(
+                self.advanced_peer_tsn_ack_point,
+                self.cumulative_tsn_ack_point,
+            ) {
+                self.advanced_peer_tsn_ack_point = self.cumulative_tsn_ack_point
+            }
+
+            // RFC 3758 Sec 3.5 C2
+            let mut i = self.advanced_peer_tsn_ack_point + 1;
+            while let Some(c) = self.inflight_queue.get(i) {
+                if !c.abandoned() {
+                    break;
+                }
+                self.advanced_peer_tsn_ack_point = i;
+                i += 1;
+            }
+
+            // RFC 3758 Sec 3.5 C3
+            if sna32gt(
+                self.advanced_peer_tsn_ack_point,
+                self.cumulative_tsn_ack_point,
+            ) {
+                self.will_send_forward_tsn = true;
+                log::debug!(
+                    "[{}] handleSack {}: sna32GT({}, {})",
+                    self.name,
+                    self.will_send_forward_tsn,
+                    self.advanced_peer_tsn_ack_point,
+                    self.cumulative_tsn_ack_point
+                );
+            }
+            self.awake_write_loop();
+        }
+
+        if !self.inflight_queue.is_empty() {
+            // Start timer. (noop if already started)
+            log::trace!("[{}] T3-rtx timer start (pt3)", self.name);
+            if let Some(t3rtx) = &self.t3rtx {
+                t3rtx.start(self.rto_mgr.get_rto()).await;
+            }
+        }
+
+        if cum_tsn_ack_point_advanced {
+            self.awake_write_loop();
+        }
+
+        Ok(self.inflight_queue.is_empty())
+    }
+
+    /// create_forward_tsn generates ForwardTSN chunk.
+    /// This method will be be called if use_forward_tsn is set to false.
+    fn create_forward_tsn(&self) -> ChunkForwardTsn {
+        // RFC 3758 Sec 3.5 C4
+        let mut stream_map: HashMap<u16, u16> = HashMap::new(); // to report only once per SI
+        let mut i = self.cumulative_tsn_ack_point + 1;
+        while sna32lte(i, self.advanced_peer_tsn_ack_point) {
+            let c = match self.inflight_queue.get(i) {
+                Some(c) => c,
+                None => break,
+            };
+            i += 1;
+
+            // only ordered chunks are reported, an unordered one carries the SSN the next
+            // ordered message of its stream gets, which the peer would skip
+            if c.unordered {
+                continue;
+            }
+            match stream_map.get(&c.stream_identifier) {
+                // to report only once with greatest SSN
+                Some(ssn) if !sna16lt(*ssn, c.stream_sequence_number) => {}
+                _ => {
+                    stream_map.insert(c.stream_identifier, c.stream_sequence_number);
+                }
+            }
+        }
+
+        let mut fwd_tsn = ChunkForwardTsn {
+            new_cumulative_tsn: self.advanced_peer_tsn_ack_point,
+            streams: vec![],
+        };
+
+        let mut stream_str = String::new();
+        for (si, ssn) in &stream_map {
+            stream_str += format!("(si={} ssn={})", si, ssn).as_str();
+            fwd_tsn.streams.push(ChunkForwardTsnStream {
+                identifier: *si,
+                sequence: *ssn,
+            });
+        }
+        log::trace!(
+            "[{}] building fwd_tsn: newCumulativeTSN={} cumTSN={} - {}",
+            self.name,
+            fwd_tsn.new_cumulative_tsn,
+            self.cumulative_tsn_ack_point,
+            stream_str
+        );
+
+        fwd_tsn
+    }
+
+    /// create_i_forward_tsn generates the I-FORWARD-TSN chunk sent instead of ForwardTSN once
+    /// I-DATA is in use. Ordered and unordered messages are reported apart, each with its
+    /// greatest abandoned MID.
+    fn create_i_forward_tsn(&self) -> ChunkIForwardTsn {
+        let mut stream_map: HashMap<(u16, bool), u32> = HashMap::new(); // to report only once per SI and U
+        let mut i = self.cumulative_tsn_ack_point + 1;
+        while sna32lte(i, self.advanced_peer_tsn_ack_point) {
+            if let Some(c) = self.inflight_queue.get(i) {
+                let key = (c.stream_identifier, c.unordered);
+                match stream_map.get(&key) {
+                    Some(mid) if !sna32lt(*mid, c.message_identifier) => {}
+                    _ => {
+                        stream_map.insert(key, c.message_identifier);
+                    }
+                }
+            } else {
+                break;
+            }
+
+            i += 1;
+        }
+
+        let fwd_tsn = ChunkIForwardTsn {
+            new_cumulative_tsn: self.advanced_peer_tsn_ack_point,
+            streams: stream_map
+                .into_iter()
+                .map(
+                    |((identifier, unordered), message_identifier)| ChunkIForwardTsnStream {
+                        identifier,
+                        unordered,
+                        message_identifier,
+                    },
+                )
+                .collect(),
+        };
+        log::trace!(
+            "[{}] building i_fwd_tsn: cumTSN={} - {}",
+            self.name,
+            self.cumulative_tsn_ack_point,
+            fwd_tsn
+        );
+
+        fwd_tsn
+    }
+
+    /// Move the chunk peeked with self.pending_queue.peek() to the inflight_queue.
+    fn move_pending_data_chunk_to_inflight_queue(
+        &mut self,
+        beginning_fragment: bool,
+        unordered: bool,
+    ) -> Option<ChunkPayloadData> {
+        if let Some(mut c) = self.pending_queue.pop(beginning_fragment, unordered) {
+            // Mark all fragements are in-flight now
+            if c.ending_fragment {
+                c.set_all_inflight();
+            }
+
+            // Assign TSN
+            c.tsn = self.generate_next_tsn();
+
+            c.since = Instant::now(); // use to calculate RTT and also for maxPacketLifeTime
+            c.nsent = 1; // being sent for the first time
+
+            self.check_partial_reliability_status(&c);
+
+            log::trace!(
+                "[{}] sending ppi={} tsn={} ssn={} sent={} len={} ({},{})",
+                self.name,
+                c.payload_type as u32,
+                c.tsn,
+                c.stream_sequence_number,
+                c.nsent,
+                c.user_data.len(),
+                c.beginning_fragment,
+                c.ending_fragment
+            );
+
+            self.inflight_queue.push_no_check(c.clone());
+
+            Some(c)
+        } else {
+            log::error!("[{}] failed to pop from pending queue", self.name);
+            None
+        }
+    }
+
+    /// discard_expired_message drops the message of the peeked chunk c from the pending queue if
+    /// its deadline passed before its first fragment could be sent, see
+    /// Stream::write_sctp_with_deadline. Returns whether it was dropped.
+    async fn discard_expired_message(&mut self, c: &ChunkPayloadData) -> bool {
+        let expired = matches!(c.deadline, Some(deadline) if Instant::now() >= deadline);
+        if !expired || !c.beginning_fragment || !c.unordered {
+            return false;
+        }
+
+        let n_bytes = self.pending_queue.discard(c.unordered);
+        log::debug!(
+            "[{}] dropped expired message of {} bytes on stream {}",
+            self.name,
+            n_bytes,
+            c.stream_identifier
+        );
+        if let Some(s) = self.streams.get(c.stream_identifier) {
+            s.messages_expired.fetch_add(1, Ordering::SeqCst);
+            s.on_buffer_released(n_bytes as i64).await;
+        }
+        true
+    }
+
+    /// pop_pending_data_chunks_to_send pops chunks from the pending queues as many as
+    /// the cwnd and rwnd allows to send.
+    async fn pop_pending_data_chunks_to_send(&mut self) -> (Vec<ChunkPayloadData>, Vec<u16>) {
+        let mut chunks = vec![];
+        let mut sis_to_reset = vec![]; // stream identifiers to reset
+        let is_empty = self.pending_queue.len() == 0;
+        if !is_empty {
+            // RFC 4960 sec 6.1.  Transmission of DATA Chunks
+            //   A) At any given time, the data sender MUST NOT transmit new data to
+            //      any destination transport address if its peer's rwnd indicates
+            //      that the peer has no buffer space (i.e., rwnd is 0; see Section
+            //      6.2.1).  However, regardless of the value of rwnd (including if it
+            //      is 0), the data sender can always have one DATA chunk in flight to
+            //      the receiver if allowed by cwnd (see rule B, below).
+
+            while let Some(c) = self.pending_queue.peek() {
+                let (beginning_fragment, unordered, data_len, stream_identifier) = (
+                    c.beginning_fragment,
+                    c.unordered,
+                    c.user_data.len(),
+                    c.stream_identifier,
+                );
+
+                if data_len == 0 {
+                    sis_to_reset.push(stream_identifier);
+                    if self
+                        .pending_queue
+                        .pop(beginning_fragment, unordered)
+                        .is_none()
+                    {
+                        log::error!("failed to pop from pending queue");
+                    }
+                    continue;
+                }
+
+                if self.discard_expired_message(&c).await {
+                    continue;
+                }
+
+                if self.inflight_queue.get_num_bytes() + data_len > self.cwnd as usize {
+                    break; // would exceeds cwnd
+                }
+
+                if data_len > self.rwnd as usize {
+                    break; // no more rwnd
+                }
+
+                self.rwnd -= data_len as u32;
+
+                if let Some(chunk) =
+                    self.move_pending_data_chunk_to_inflight_queue(beginning_fragment, unordered)
+                {
+                    chunks.push(chunk);
+                }
+            }
+
+            // the data sender can always have one DATA chunk in flight to the receiver
+            if chunks.is_empty() && self.inflight_queue.is_empty() {
+                // Send zero window probe
+                let mut probe = self.pending_queue.peek();
+                while let Some(c) = &probe {
+                    if !self.discard_expired_message(c).await {
+                        break;
+                    }
+                    probe = self.pending_queue.peek();
+                }
+                if let Some(c) = probe {
+                    let (beginning_fragment, unordered) = (c.beginning_fragment, c.unordered);
+
+                    if let Some(chunk) = self
+                        .move_pending_data_chunk_to_inflight_queue(beginning_fragment, unordered)
+                    {
+                        chunks.push(chunk);
+                    }
+                }
+            }
+        }
+
+        (chunks, sis_to_reset)
+    }
+
+    fn check_partial_reliability_status(&self, c: &ChunkPayloadData) {
+        if !self.use_forward_tsn {
+            return;
+        }
+
+        // draft-ietf-rtcweb-data-protocol-09.txt section 6
+        //	6.  Procedures
+        //		All Data Channel Establishment Protocol messages MUST be sent using
+        //		ordered delivery and reliable transmission.
+        //
+        if c.payload_type == PayloadProtocolIdentifier::Dcep {
+            return;
+        }
+
+        // PR-SCTP
+        if self.streams.contains(c.stream_identifier) {
+            if !c.set_abandoned(true) {
+                self.transport_events
+                    .record(TransportEventKind::MessagesAbandoned {
+                        stream_identifier: c.stream_identifier,
+                        messages: 1,
+                    });
+            }
+        } else {
+            log::error!("[{}] stream {} not found)", self.name, c.stream_identifier);
+        }
+    }
+
+    /// get_data_chunks_to_retransmit is called when T3-rtx is timed out and retransmit outstanding data chunks
+    /// that are not acked or abandoned yet.
+    fn get_data_chunks_to_retransmit(&mut self) -> Vec<ChunkPayloadData> {
+        let awnd = std::cmp::min(self.cwnd, self.rwnd);
+        let mut chunks = vec![];
+        let mut bytes_to_send = 0;
+        let mut done = false;
+        let mut i = 0;
+        while !done {
+            let tsn = self.cumulative_tsn_ack_point + i + 1;
+            if let Some(c) = self.inflight_queue.get_mut(tsn) {
+                if !c.retransmit {
+                    i += 1;
+                    continue;
+                }
+
+                if i == 0 && self.rwnd < c.user_data.len() as u32 {
+                    // Send it as a zero window probe
+                    done = true;
+                } else if bytes_to_send + c.user_data.len() > awnd as usize {
+                    break;
+                }
+
+                // reset the retransmit flag not to retransmit again before the next
+                // t3-rtx timer fires
+                c.retransmit = false;
+                bytes_to_send += c.user_data.len();
+
+                c.nsent += 1;
+            } else {
+                break; // end of pending data
+            }
+
+            if let Some(c) = self.inflight_queue.get(tsn) {
+                self.check_partial_reliability_status(c);
+
+                log::trace!(
+                    "[{}] retransmitting tsn={} ssn={} sent={}",
+                    self.name,
+                    c.tsn,
+                    c.stream_sequence_number,
+                    c.nsent
+                );
+
+                chunks.push(c.clone());
+            }
+            i += 1;
+        }
+
+        if !chunks.is_empty() {
+            self.transport_events
+                .record(TransportEventKind::Retransmission {
+                    timeout: true,
+                    chunks: chunks.len() as u64,
+                });
+        }
+        chunks
+    }
+
+    /// generate_next_tsn returns the my_next_tsn and increases it. The caller should hold the lock.
+    fn generate_next_tsn(&mut self) -> u32 {
+        let tsn = self.my_next_tsn;
+        self.my_next_tsn += 1;
+        tsn
+    }
+}
+
+#[async_trait]
+impl RtxTimerObserver for AssociationInflight {
+    async fn on_retransmission_timeout(&mut self, id: RtxTimerId, n_rtos: usize) {
+        if id != RtxTimerId::T3RTX {
+            return;
+        }
+
+        self.stats.inc_t3timeouts();
+
+        // RFC 4960 sec 6.3.3
+        //  E1)  For the destination address for which the timer expires, adjust
+        //       its ssthresh with rules defined in Section 7.2.3 and set the
+        //       cwnd <- MTU.
+        // RFC 4960 sec 7.2.3
+        //   When the T3-rtx timer expires on an address, SCTP should perform slow
+        //   start by:
+        //      ssthresh = max(cwnd/2, 4*MTU)
+        //      cwnd = 1*MTU
+
+        self.ssthresh = std::cmp::max(self.cwnd / 2, 4 * self.mtu);
+        self.cwnd = self.mtu;
+        self.transport_events
+            .record(TransportEventKind::CongestionWindowReduced {
+                timeout: true,
+                cwnd: self.cwnd,
+                ssthresh: self.ssthresh,
+            });
+        log::trace!(
+            "[{}] updated cwnd={} ssthresh={} inflight={} (RTO)",
+            self.name,
+            self.cwnd,
+            self.ssthresh,
+            self.inflight_queue.get_num_bytes()
+        );
+
+        // RFC 3758 sec 3.5
+        //  A5) Any time the T3-rtx timer expires, on any destination, the sender
+        //  SHOULD try to advance the "Advanced.Peer.Ack.Point" by following
+        //  the procedures outlined in C2 - C5.
+        if self.use_forward_tsn {
+            // RFC 3758 Sec 3.5 C2
+            let mut i = self.advanced_peer_tsn_ack_point + 1;
+            while let Some(c) = self.inflight_queue.get(i) {
+                if !c.abandoned() {
+                    break;
+                }
+                self.advanced_peer_tsn_ack_point = i;
+                i += 1;
+            }
+
+            // RFC 3758 Sec 3.5 C3
+            if sna32gt(
+                self.advanced_peer_tsn_ack_point,
+                self.cumulative_tsn_ack_point,
+            ) {
+                self.will_send_forward_tsn = true;
+                log::debug!(
+                    "[{}] on_retransmission_timeout {}: sna32GT({}, {})",
+                    self.name,
+                    self.will_send_forward_tsn,
+                    self.advanced_peer_tsn_ack_point,
+                    self.cumulative_tsn_ack_point
+                );
+            }
+        }
+
+        log::debug!(
+            "[{}] T3-rtx timed out: n_rtos={} cwnd={} ssthresh={}",
+            self.name,
+            n_rtos,
+            self.cwnd,
+            self.ssthresh
+        );
+
+        self.inflight_queue.mark_all_to_retrasmit();
+        self.awake_write_loop();
+    }
+
+    async fn on_retransmission_failure(&mut self, id: RtxTimerId) {
+        if id == RtxTimerId::T3RTX {
+            // T3-rtx timer will not fail by design
+            // Justifications:
+            //  * ICE would fail if the connectivity is lost
+            //  * WebRTC spec is not clear how this incident should be reported to ULP
+            log::error!("[{}] retransmission failure: T3-rtx (DATA)", self.name);
+        }
+    }
+}
+
+/// data_chunk_header_size is the size of the DATA or I-DATA chunk header of c
+pub(crate) fn data_chunk_header_size(c: &ChunkPayloadData) -> u32 {
+    if c.interleaved {
+        I_DATA_CHUNK_HEADER_SIZE
+    } else {
+        DATA_CHUNK_HEADER_SIZE
+    }
+}
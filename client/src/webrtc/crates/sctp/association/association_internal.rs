@@ -4,6 +4,7 @@ use async_trait::async_trait;
 
 use super::*;
 
+use crate::webrtc::sctp::chunk::chunk_header::CHUNK_HEADER_SIZE;
 use crate::webrtc::sctp::param::param_forward_tsn_supported::ParamForwardTsnSupported;
 
 #[derive(Default)]
@@ -14,14 +15,14 @@ pub(crate) struct AssociationInternal {
     pub(crate) will_send_shutdown: Arc<AtomicBool>,
     awake_write_loop_ch: Option<Arc<mpsc::Sender<()>>>,
 
-    peer_verification_tag: u32,
+    pub(crate) peer_verification_tag: u32,
     pub(crate) my_verification_tag: u32,
+    // consecutive_bad_verification_tags counts the inbound packets dropped in a row for their
+    // verification tag, see MAX_CONSECUTIVE_BAD_VERIFICATION_TAGS. The read loop resets it for
+    // the SACKs it hands to AssociationInflight directly.
+    pub(crate) consecutive_bad_verification_tags: Arc<AtomicU32>,
 
-    pub(crate) my_next_tsn: u32, // nextTSN
-    peer_last_tsn: u32,          // lastRcvdTSN
-    min_tsn2measure_rtt: u32,    // for RTT measurement
-    will_send_forward_tsn: bool,
-    will_retransmit_fast: bool,
+    peer_last_tsn: u32, // lastRcvdTSN
     will_retransmit_reconfig: bool,
 
     will_send_shutdown_ack: bool,
@@ -37,32 +38,36 @@ pub(crate) struct AssociationInternal {
     destination_port: u16,
     pub(crate) my_max_num_inbound_streams: u16,
     pub(crate) my_max_num_outbound_streams: u16,
+    /// num_inbound_streams and num_outbound_streams are the stream counts negotiated in the
+    /// handshake, see RFC 4960 Section 5.1.1
+    pub(crate) num_inbound_streams: u16,
+    pub(crate) num_outbound_streams: u16,
     my_cookie: Option<ParamStateCookie>,
     payload_queue: PayloadQueue,
-    inflight_queue: PayloadQueue,
+    /// inflight holds the DATA chunks in flight and the congestion control, see
+    /// AssociationInflight
+    pub(crate) inflight: Arc<Mutex<AssociationInflight>>,
     pending_queue: Arc<PendingQueue>,
+    pub(crate) send_buffer: Arc<SendBuffer>,
     control_queue: ControlQueue,
-    pub(crate) mtu: u32,
     max_payload_size: u32, // max DATA chunk payload size
-    cumulative_tsn_ack_point: u32,
-    advanced_peer_tsn_ack_point: u32,
-    use_forward_tsn: bool,
+    /// use_interleaving is whether I-DATA and I-FORWARD-TSN replace DATA and FORWARD-TSN
+    pub(crate) use_interleaving: bool,
 
     // Congestion control parameters
     pub(crate) max_receive_buffer_size: u32,
-    pub(crate) cwnd: u32,     // my congestion window size
-    rwnd: u32,                // calculated peer's receiver windows size
-    pub(crate) ssthresh: u32, // slow start threshold
-    partial_bytes_acked: u32,
-    pub(crate) in_fast_recovery: bool,
-    fast_recover_exit_point: u32,
-
-    // RTX & Ack timer
-    pub(crate) rto_mgr: RtoManager,
+    /// initial_recv_window is the a_rwnd advertised in INIT and INIT-ACK
+    pub(crate) initial_recv_window: u32,
+
+    // my receiver window credit in the last SACK sent, and whether it was below half of
+    // max_receive_buffer_size (shared with streams so reads can trigger a window update)
+    last_advertised_rwnd: u32,
+    pub(crate) rwnd_reduced: Arc<AtomicBool>,
+
+    // RTX & Ack timer, T3-rtx is AssociationInflight's
     pub(crate) t1init: Option<RtxTimer<AssociationInternal>>,
     pub(crate) t1cookie: Option<RtxTimer<AssociationInternal>>,
     pub(crate) t2shutdown: Option<RtxTimer<AssociationInternal>>,
-    pub(crate) t3rtx: Option<RtxTimer<AssociationInternal>>,
     pub(crate) treconfig: Option<RtxTimer<AssociationInternal>>,
     pub(crate) ack_timer: Option<AckTimer<AssociationInternal>>,
 
@@ -70,7 +75,7 @@ pub(crate) struct AssociationInternal {
     pub(crate) stored_init: Option<ChunkInit>,
     stored_cookie_echo: Option<ChunkCookieEcho>,
 
-    streams: HashMap<u16, Arc<Stream>>,
+    streams: StreamMap,
 
     close_loop_ch_tx: Option<broadcast::Sender<()>>,
     accept_ch_tx: Option<mpsc::Sender<Arc<Stream>>>,
@@ -81,8 +86,11 @@ pub(crate) struct AssociationInternal {
     immediate_ack_triggered: bool,
 
     pub(crate) stats: Arc<AssociationStats>,
+    pub(crate) close_reason: Option<CloseReason>,
     ack_state: AckState,
     pub(crate) ack_mode: AckMode, // for testing
+    event_log: Arc<EventLog>,
+    pub(crate) spawner: Spawner,
 }
 
 impl AssociationInternal {
@@ -93,70 +101,132 @@ impl AssociationInternal {
         handshake_completed_ch_tx: mpsc::Sender<Option<Error>>,
         awake_write_loop_ch: Arc<mpsc::Sender<()>>,
     ) -> Self {
-        let max_receive_buffer_size = if config.max_receive_buffer_size == 0 {
+        let mut max_receive_buffer_size = if config.max_receive_buffer_size == 0 {
             INITIAL_RECV_BUF_SIZE
         } else {
             config.max_receive_buffer_size
         };
 
+        let initial_recv_window = if config.initial_recv_window == 0 {
+            max_receive_buffer_size
+        } else if config.initial_recv_window < MIN_RECV_WINDOW {
+            log::warn!(
+                "[{}] initial receive window {} is below the minimum, using {}",
+                config.name,
+                config.initial_recv_window,
+                MIN_RECV_WINDOW
+            );
+            MIN_RECV_WINDOW
+        } else {
+            config.initial_recv_window
+        };
+        // the peer may fill the initial window before it learns of a smaller one
+        max_receive_buffer_size = std::cmp::max(max_receive_buffer_size, initial_recv_window);
+
+        let max_num_inbound_streams = if config.max_num_inbound_streams == 0 {
+            u16::MAX
+        } else {
+            config.max_num_inbound_streams
+        };
+        let max_num_outbound_streams = if config.max_num_outbound_streams == 0 {
+            u16::MAX
+        } else {
+            config.max_num_outbound_streams
+        };
+
+        let remote_port = if config.remote_port == 0 {
+            DEFAULT_SCTP_PORT
+        } else {
+            config.remote_port
+        };
+
         let max_message_size = if config.max_message_size == 0 {
             DEFAULT_MAX_MESSAGE_SIZE
         } else {
             config.max_message_size
         };
 
-        let inflight_queue_length = Arc::new(AtomicUsize::new(0));
+        let mtu = if config.mtu == 0 {
+            INITIAL_MTU
+        } else if config.mtu < MIN_MTU {
+            log::warn!(
+                "[{}] mtu {} is below the minimum, using {}",
+                config.name,
+                config.mtu,
+                MIN_MTU
+            );
+            MIN_MTU
+        } else {
+            config.mtu
+        };
+
+        let max_send_buffer_size = if config.max_send_buffer_size == 0 {
+            DEFAULT_MAX_SEND_BUFFER_SIZE
+        } else {
+            config.max_send_buffer_size
+        };
+
+        let pending_queue = Arc::new(PendingQueue::new(config.strict_priority));
+        let send_buffer = Arc::new(SendBuffer::new(
+            max_send_buffer_size,
+            Arc::clone(&pending_queue),
+        ));
+        let state = Arc::new(AtomicU8::new(AssociationState::Closed as u8));
+        let streams = StreamMap::default();
 
         let mut tsn = random::<u32>();
         if tsn == 0 {
             tsn += 1;
         }
-        let mut a = AssociationInternal {
+        let inflight = AssociationInflight::new(
+            config.name.clone(),
+            Arc::clone(&state),
+            Arc::clone(&awake_write_loop_ch),
+            tsn,
+            mtu,
+            Arc::clone(&pending_queue),
+            Arc::clone(&send_buffer),
+            streams.clone(),
+            Arc::clone(&config.stats),
+            config.transport_events,
+        );
+
+        AssociationInternal {
             name: config.name,
             max_receive_buffer_size,
+            initial_recv_window,
+            last_advertised_rwnd: initial_recv_window,
+            rwnd_reduced: Arc::new(AtomicBool::new(false)),
             max_message_size: Arc::new(AtomicU32::new(max_message_size)),
 
-            my_max_num_outbound_streams: u16::MAX,
-            my_max_num_inbound_streams: u16::MAX,
+            source_port: DEFAULT_SCTP_PORT,
+            destination_port: remote_port,
+            my_max_num_outbound_streams: max_num_outbound_streams,
+            my_max_num_inbound_streams: max_num_inbound_streams,
+            num_outbound_streams: max_num_outbound_streams,
+            num_inbound_streams: max_num_inbound_streams,
             payload_queue: PayloadQueue::new(Arc::new(AtomicUsize::new(0))),
-            inflight_queue: PayloadQueue::new(Arc::clone(&inflight_queue_length)),
-            pending_queue: Arc::new(PendingQueue::new()),
+            inflight: Arc::new(Mutex::new(inflight)),
+            send_buffer,
+            pending_queue,
             control_queue: ControlQueue::new(),
-            mtu: INITIAL_MTU,
-            max_payload_size: INITIAL_MTU - (COMMON_HEADER_SIZE + DATA_CHUNK_HEADER_SIZE),
+            // keep fragments 4-byte aligned so chunk padding never pushes a packet over the MTU
+            max_payload_size: (mtu - (COMMON_HEADER_SIZE + DATA_CHUNK_HEADER_SIZE)) & !3,
             my_verification_tag: random::<u32>(),
-            my_next_tsn: tsn,
             my_next_rsn: tsn,
-            min_tsn2measure_rtt: tsn,
-            state: Arc::new(AtomicU8::new(AssociationState::Closed as u8)),
-            rto_mgr: RtoManager::new(),
-            streams: HashMap::new(),
+            state,
+            streams,
             reconfigs: HashMap::new(),
             reconfig_requests: HashMap::new(),
             accept_ch_tx: Some(accept_ch_tx),
             close_loop_ch_tx: Some(close_loop_ch_tx),
             handshake_completed_ch_tx: Some(handshake_completed_ch_tx),
-            cumulative_tsn_ack_point: tsn - 1,
-            advanced_peer_tsn_ack_point: tsn - 1,
-            stats: Arc::new(AssociationStats::default()),
+            stats: config.stats,
             awake_write_loop_ch: Some(awake_write_loop_ch),
+            event_log: config.event_log,
+            spawner: config.spawner,
             ..Default::default()
-        };
-
-        // RFC 4690 Sec 7.2.1
-        //  o  The initial cwnd before DATA transmission or after a sufficiently
-        //     long idle period MUST be set to min(4*MTU, max (2*MTU, 4380
-        //     bytes)).
-        a.cwnd = std::cmp::min(4 * a.mtu, std::cmp::max(2 * a.mtu, 4380));
-        log::trace!(
-            "[{}] updated cwnd={} ssthresh={} inflight={} (INI)",
-            a.name,
-            a.cwnd,
-            a.ssthresh,
-            a.inflight_queue.get_num_bytes()
-        );
-
-        a
+        }
     }
 
     /// caller must hold self.lock
@@ -164,9 +234,6 @@ impl AssociationInternal {
         if let Some(stored_init) = self.stored_init.take() {
             log::debug!("[{}] sending INIT", self.name);
 
-            self.source_port = 5000; // Spec??
-            self.destination_port = 5000; // Spec??
-
             let outbound = Packet {
                 source_port: self.source_port,
                 destination_port: self.destination_port,
@@ -203,9 +270,12 @@ impl AssociationInternal {
         }
     }
 
-    pub(crate) async fn close(&mut self) -> Result<()> {
+    /// close closes the association and all its streams, giving them reason as the close reason
+    /// unless they were closed earlier.
+    pub(crate) async fn close(&mut self, reason: CloseReason) -> Result<()> {
         if self.get_state() != AssociationState::Closed {
             self.set_state(AssociationState::Closed);
+            self.close_reason = Some(reason.clone());
 
             log::debug!("[{}] closing association..", self.name);
 
@@ -214,10 +284,18 @@ impl AssociationInternal {
             // awake read/write_loop to exit
             self.close_loop_ch_tx.take();
 
-            for si in self.streams.keys().cloned().collect::<Vec<u16>>() {
-                self.unregister_stream(si);
+            // wake up any pending accept_stream
+            self.accept_ch_tx.take();
+
+            for si in self.streams.identifiers() {
+                self.unregister_stream(si, reason.clone());
             }
 
+            // nothing queued is sent anymore, the streams may outlive the association
+            self.inflight.lock().await.close().await;
+            self.pending_queue.clear();
+            self.send_buffer.close();
+
             // Wait for read_loop to end
             //if let Some(read_loop_close_ch) = &mut self.read_loop_close_ch {
             //    let _ = read_loop_close_ch.recv().await;
@@ -249,6 +327,32 @@ impl AssociationInternal {
                 self.name,
                 self.stats.get_num_fast_retrans()
             );
+            log::debug!(
+                "[{}] stats nRwndDrops: {}",
+                self.name,
+                self.stats.get_num_rwnd_drops()
+            );
+            log::debug!(
+                "[{}] stats nBadVerificationTags: {}",
+                self.name,
+                self.stats.get_num_bad_verification_tags()
+            );
+            log::debug!(
+                "[{}] stats nInvalidCookies: {}",
+                self.name,
+                self.stats.get_num_invalid_cookies()
+            );
+            log::debug!(
+                "[{}] stats nOutOfOrder: {} (max distance {})",
+                self.name,
+                self.stats.get_num_out_of_order(),
+                self.stats.get_max_reorder_distance()
+            );
+            log::debug!(
+                "[{}] stats nDuplicateTSNs: {}",
+                self.name,
+                self.stats.get_num_duplicate_tsns()
+            );
         }
 
         Ok(())
@@ -265,9 +369,6 @@ impl AssociationInternal {
         if let Some(t2shutdown) = &self.t2shutdown {
             t2shutdown.stop().await;
         }
-        if let Some(t3rtx) = &self.t3rtx {
-            t3rtx.stop().await;
-        }
         if let Some(treconfig) = &self.treconfig {
             treconfig.stop().await;
         }
@@ -285,28 +386,44 @@ impl AssociationInternal {
 
     /// unregister_stream un-registers a stream from the association
     /// The caller should hold the association write lock.
-    fn unregister_stream(&mut self, stream_identifier: u16) {
-        let s = self.streams.remove(&stream_identifier);
+    fn unregister_stream(&mut self, stream_identifier: u16, reason: CloseReason) {
+        let s = self.streams.remove(stream_identifier);
         if let Some(s) = s {
-            s.closed.store(true, Ordering::SeqCst);
-            s.read_notifier.notify_waiters();
+            s.mark_closed(reason);
         }
     }
 
-    /// handle_inbound parses incoming raw packets
-    pub(crate) async fn handle_inbound(&mut self, raw: &Bytes) -> Result<()> {
-        let p = match Packet::unmarshal(raw) {
-            Ok(p) => p,
-            Err(err) => {
-                log::warn!("[{}] unable to parse SCTP packet {}", self.name, err);
-                return Ok(());
+    /// handle_inbound processes an incoming packet. The read loop parses and validates it before
+    /// taking the association lock, see Packet::unmarshal and Packet::check_packet.
+    pub(crate) async fn handle_inbound(&mut self, p: Packet) -> Result<()> {
+        if !self.is_verification_tag_valid(&p) {
+            log::debug!(
+                "[{}] discarding packet with verification tag {}",
+                self.name,
+                p.verification_tag
+            );
+            self.stats.inc_bad_verification_tags();
+
+            // RFC 4960 Section 5.2.2: a peer that restarted without getting its new INIT through
+            // to us only sends tags we do not know anymore
+            let consecutive_bad_verification_tags = self
+                .consecutive_bad_verification_tags
+                .fetch_add(1, Ordering::SeqCst)
+                + 1;
+            if consecutive_bad_verification_tags >= MAX_CONSECUTIVE_BAD_VERIFICATION_TAGS
+                && self.get_state() == AssociationState::Established
+            {
+                log::warn!(
+                    "[{}] {} packets in a row with an unknown verification tag, the peer restarted",
+                    self.name,
+                    consecutive_bad_verification_tags
+                );
+                return Err(Error::ErrPeerRestart);
             }
-        };
-
-        if let Err(err) = p.check_packet() {
-            log::warn!("[{}] failed validating packet {}", self.name, err);
             return Ok(());
         }
+        self.consecutive_bad_verification_tags
+            .store(0, Ordering::SeqCst);
 
         self.handle_chunk_start();
 
@@ -319,43 +436,71 @@ impl AssociationInternal {
         Ok(())
     }
 
-    fn gather_data_packets_to_retransmit(&mut self, mut raw_packets: Vec<Bytes>) -> Vec<Bytes> {
-        for p in &self.get_data_packets_to_retransmit() {
-            if let Ok(raw) = p.marshal() {
-                raw_packets.push(raw);
-            } else {
-                log::warn!(
-                    "[{}] failed to serialize a DATA packet to be retransmitted",
-                    self.name
-                );
+    /// is_verification_tag_valid checks the verification tag of an inbound packet before any of
+    /// its chunks is processed, RFC 4960 Section 8.5. A packet carrying an INIT has a zero tag,
+    /// which check_packet asserts. ABORT and SHUTDOWN COMPLETE with the T bit set carry the peer's
+    /// own tag, Section 8.5.1. Every other packet has to carry the tag we sent in our INIT or
+    /// INIT ACK.
+    fn is_verification_tag_valid(&self, p: &Packet) -> bool {
+        if p.verification_tag == self.my_verification_tag {
+            return true;
+        }
+
+        p.chunks.iter().any(|c| {
+            let c = c.as_any();
+            match c.downcast_ref::<ChunkInit>() {
+                Some(init) => !init.is_ack,
+                None => {
+                    let reflected_tag = c
+                        .downcast_ref::<ChunkAbort>()
+                        .map(|abort| abort.reflected_tag)
+                        .or_else(|| {
+                            c.downcast_ref::<ChunkShutdownComplete>()
+                                .map(|shutdown_complete| shutdown_complete.reflected_tag)
+                        })
+                        .unwrap_or(false);
+                    reflected_tag && p.verification_tag == self.peer_verification_tag
+                }
             }
-        }
+        })
+    }
 
-        raw_packets
+    fn gather_data_packets_to_retransmit(
+        &self,
+        mut packets: Vec<Packet>,
+        inflight: &mut InflightOutbound,
+    ) -> Vec<Packet> {
+        let chunks = std::mem::take(&mut inflight.retransmit);
+        packets.append(&mut self.bundle_data_chunks_into_packets(None, chunks, inflight.mtu));
+        packets
     }
 
     async fn gather_outbound_data_and_reconfig_packets(
         &mut self,
-        mut raw_packets: Vec<Bytes>,
-    ) -> Vec<Bytes> {
-        // Pop unsent data chunks from the pending queue to send as much as
-        // cwnd and rwnd allow.
-        let (chunks, sis_to_reset) = self.pop_pending_data_chunks_to_send().await;
+        mut packets: Vec<Packet>,
+        inflight: &mut InflightOutbound,
+    ) -> Vec<Packet> {
+        let chunks = std::mem::take(&mut inflight.data);
         if !chunks.is_empty() {
-            // Start timer. (noop if already started)
-            log::trace!("[{}] T3-rtx timer start (pt1)", self.name);
-            if let Some(t3rtx) = &self.t3rtx {
-                t3rtx.start(self.rto_mgr.get_rto()).await;
-            }
-            for p in &self.bundle_data_chunks_into_packets(chunks) {
-                if let Ok(raw) = p.marshal() {
-                    raw_packets.push(raw);
-                } else {
-                    log::warn!("[{}] failed to serialize a DATA packet", self.name);
+            // RFC 4960 sec 6.2.  Acknowledgement on Reception of DATA Chunks
+            //   An implementation MAY bundle a SACK chunk with outbound DATA, which
+            //   also satisfies any pending delayed acknowledgement.
+            let sack = if self.ack_state != AckState::Idle {
+                self.ack_state = AckState::Idle;
+                if let Some(ack_timer) = &mut self.ack_timer {
+                    ack_timer.stop();
                 }
-            }
+                let sack = self.create_selective_ack_chunk().await;
+                log::debug!("[{}] bundling SACK with DATA: {}", self.name, sack);
+                Some(sack)
+            } else {
+                None
+            };
+
+            packets.append(&mut self.bundle_data_chunks_into_packets(sack, chunks, inflight.mtu));
         }
 
+        let sis_to_reset = std::mem::take(&mut inflight.sis_to_reset);
         if !sis_to_reset.is_empty() || self.will_retransmit_reconfig {
             if self.will_retransmit_reconfig {
                 self.will_retransmit_reconfig = false;
@@ -365,26 +510,18 @@ impl AssociationInternal {
                     self.reconfigs.len()
                 );
                 for c in self.reconfigs.values() {
-                    let p = self.create_packet(vec![Box::new(c.clone())]);
-                    if let Ok(raw) = p.marshal() {
-                        raw_packets.push(raw);
-                    } else {
-                        log::warn!(
-                            "[{}] failed to serialize a RECONFIG packet to be retransmitted",
-                            self.name,
-                        );
-                    }
+                    packets.push(self.create_packet(vec![Box::new(c.clone())]));
                 }
             }
 
             if !sis_to_reset.is_empty() {
                 let rsn = self.generate_next_rsn();
-                let tsn = self.my_next_tsn - 1;
+                let tsn = inflight.last_tsn;
                 log::debug!(
                     "[{}] sending RECONFIG: rsn={} tsn={} streams={:?}",
                     self.name,
                     rsn,
-                    self.my_next_tsn - 1,
+                    tsn,
                     sis_to_reset
                 );
 
@@ -399,232 +536,174 @@ impl AssociationInternal {
                 };
                 self.reconfigs.insert(rsn, c.clone()); // store in the map for retransmission
 
-                let p = self.create_packet(vec![Box::new(c)]);
-                if let Ok(raw) = p.marshal() {
-                    raw_packets.push(raw);
-                } else {
-                    log::warn!(
-                        "[{}] failed to serialize a RECONFIG packet to be transmitted",
-                        self.name
-                    );
-                }
+                packets.push(self.create_packet(vec![Box::new(c)]));
             }
 
             if !self.reconfigs.is_empty() {
                 if let Some(treconfig) = &self.treconfig {
-                    treconfig.start(self.rto_mgr.get_rto()).await;
+                    treconfig.start(inflight.rto).await;
                 }
             }
         }
 
-        raw_packets
+        packets
     }
 
     fn gather_outbound_fast_retransmission_packets(
-        &mut self,
-        mut raw_packets: Vec<Bytes>,
-    ) -> Vec<Bytes> {
-        if self.will_retransmit_fast {
-            self.will_retransmit_fast = false;
-
-            let mut to_fast_retrans: Vec<Box<dyn Chunk + Send + Sync>> = vec![];
-            let mut fast_retrans_size = COMMON_HEADER_SIZE;
-
-            let mut i = 0;
-            loop {
-                let tsn = self.cumulative_tsn_ack_point + i + 1;
-                if let Some(c) = self.inflight_queue.get_mut(tsn) {
-                    if c.acked || c.abandoned() || c.nsent > 1 || c.miss_indicator < 3 {
-                        i += 1;
-                        continue;
-                    }
-
-                    // RFC 4960 Sec 7.2.4 Fast Retransmit on Gap Reports
-                    //  3)  Determine how many of the earliest (i.e., lowest TSN) DATA chunks
-                    //      marked for retransmission will fit into a single packet, subject
-                    //      to constraint of the path MTU of the destination transport
-                    //      address to which the packet is being sent.  Call this value K.
-                    //      Retransmit those K DATA chunks in a single packet.  When a Fast
-                    //      Retransmit is being performed, the sender SHOULD ignore the value
-                    //      of cwnd and SHOULD NOT delay retransmission for this single
-                    //		packet.
-
-                    let data_chunk_size = DATA_CHUNK_HEADER_SIZE + c.user_data.len() as u32;
-                    if self.mtu < fast_retrans_size + data_chunk_size {
-                        break;
-                    }
+        &self,
+        mut packets: Vec<Packet>,
+        inflight: &mut InflightOutbound,
+    ) -> Vec<Packet> {
+        let to_fast_retrans = std::mem::take(&mut inflight.fast_retransmit);
+        if !to_fast_retrans.is_empty() {
+            packets.push(self.create_packet(to_fast_retrans));
+        }
 
-                    fast_retrans_size += data_chunk_size;
-                    self.stats.inc_fast_retrans();
-                    c.nsent += 1;
-                } else {
-                    break; // end of pending data
-                }
+        packets
+    }
 
-                if let Some(c) = self.inflight_queue.get(tsn) {
-                    self.check_partial_reliability_status(c);
-                    to_fast_retrans.push(Box::new(c.clone()));
-                    log::trace!(
-                        "[{}] fast-retransmit: tsn={} sent={} htna={}",
-                        self.name,
-                        c.tsn,
-                        c.nsent,
-                        self.fast_recover_exit_point
-                    );
-                }
-                i += 1;
-            }
+    /// Schedules a SACK as window update once the application has consumed enough of the receive
+    /// buffer since a reduced window was advertised, so a sender held back by it resumes without
+    /// waiting for its retransmission timer. A SACK delayed by the ack timer is sent right away
+    /// then, the sender would otherwise wait for that timer.
+    ///
+    /// reference: (IETF ref-9260)[https://tools.ietf.org/html/rfc9260#section-6.2].
+    async fn check_window_update(&mut self) {
+        if self.ack_state == AckState::Immediate || !self.rwnd_reduced.load(Ordering::SeqCst) {
+            return;
+        }
 
-            if !to_fast_retrans.is_empty() {
-                if let Ok(raw) = self.create_packet(to_fast_retrans).marshal() {
-                    raw_packets.push(raw);
-                } else {
-                    log::warn!(
-                        "[{}] failed to serialize a DATA packet to be fast-retransmitted",
-                        self.name
-                    );
-                }
+        let rwnd = self.get_my_receiver_window_credit().await;
+        if rwnd
+            >= self
+                .last_advertised_rwnd
+                .saturating_add(self.max_receive_buffer_size / 4)
+        {
+            log::debug!(
+                "[{}] receive window opened from {} to {}, sending window update",
+                self.name,
+                self.last_advertised_rwnd,
+                rwnd
+            );
+            self.ack_state = AckState::Immediate;
+            if let Some(ack_timer) = &mut self.ack_timer {
+                ack_timer.stop();
             }
         }
-
-        raw_packets
     }
 
-    async fn gather_outbound_sack_packets(&mut self, mut raw_packets: Vec<Bytes>) -> Vec<Bytes> {
+    async fn gather_outbound_sack_packets(&mut self, mut packets: Vec<Packet>) -> Vec<Packet> {
         if self.ack_state == AckState::Immediate {
             self.ack_state = AckState::Idle;
             let sack = self.create_selective_ack_chunk().await;
             log::debug!("[{}] sending SACK: {}", self.name, sack);
-            if let Ok(raw) = self.create_packet(vec![Box::new(sack)]).marshal() {
-                raw_packets.push(raw);
-            } else {
-                log::warn!("[{}] failed to serialize a SACK packet", self.name);
-            }
+            packets.push(self.create_packet(vec![Box::new(sack)]));
         }
 
-        raw_packets
+        packets
     }
 
-    fn gather_outbound_forward_tsn_packets(&mut self, mut raw_packets: Vec<Bytes>) -> Vec<Bytes> {
-        /*log::debug!(
-            "[{}] gatherOutboundForwardTSNPackets {}",
-            self.name,
-            self.will_send_forward_tsn
-        );*/
-        if self.will_send_forward_tsn {
-            self.will_send_forward_tsn = false;
-            if sna32gt(
-                self.advanced_peer_tsn_ack_point,
-                self.cumulative_tsn_ack_point,
-            ) {
-                let fwd_tsn = self.create_forward_tsn();
-                if let Ok(raw) = self.create_packet(vec![Box::new(fwd_tsn)]).marshal() {
-                    raw_packets.push(raw);
-                } else {
-                    log::warn!("[{}] failed to serialize a Forward TSN packet", self.name);
-                }
-            }
+    fn gather_outbound_forward_tsn_packets(
+        &self,
+        mut packets: Vec<Packet>,
+        inflight: &mut InflightOutbound,
+    ) -> Vec<Packet> {
+        if let Some(fwd_tsn) = inflight.forward_tsn.take() {
+            packets.push(self.create_packet(vec![fwd_tsn]));
         }
 
-        raw_packets
+        packets
     }
 
     async fn gather_outbound_shutdown_packets(
         &mut self,
-        mut raw_packets: Vec<Bytes>,
-    ) -> (Vec<Bytes>, bool) {
+        mut packets: Vec<Packet>,
+        inflight: &InflightOutbound,
+    ) -> (Vec<Packet>, bool) {
         let mut ok = true;
 
         if self.will_send_shutdown.load(Ordering::SeqCst) {
             self.will_send_shutdown.store(false, Ordering::SeqCst);
 
             let shutdown = ChunkShutdown {
-                cumulative_tsn_ack: self.cumulative_tsn_ack_point,
+                cumulative_tsn_ack: inflight.cumulative_tsn_ack_point,
             };
 
-            if let Ok(raw) = self.create_packet(vec![Box::new(shutdown)]).marshal() {
-                if let Some(t2shutdown) = &self.t2shutdown {
-                    t2shutdown.start(self.rto_mgr.get_rto()).await;
-                }
-                raw_packets.push(raw);
-            } else {
-                log::warn!("[{}] failed to serialize a Shutdown packet", self.name);
+            packets.push(self.create_packet(vec![Box::new(shutdown)]));
+            if let Some(t2shutdown) = &self.t2shutdown {
+                t2shutdown.start(inflight.rto).await;
             }
         } else if self.will_send_shutdown_ack {
             self.will_send_shutdown_ack = false;
 
             let shutdown_ack = ChunkShutdownAck {};
 
-            if let Ok(raw) = self.create_packet(vec![Box::new(shutdown_ack)]).marshal() {
-                if let Some(t2shutdown) = &self.t2shutdown {
-                    t2shutdown.start(self.rto_mgr.get_rto()).await;
-                }
-                raw_packets.push(raw);
-            } else {
-                log::warn!("[{}] failed to serialize a ShutdownAck packet", self.name);
+            packets.push(self.create_packet(vec![Box::new(shutdown_ack)]));
+            if let Some(t2shutdown) = &self.t2shutdown {
+                t2shutdown.start(inflight.rto).await;
             }
         } else if self.will_send_shutdown_complete {
             self.will_send_shutdown_complete = false;
 
-            let shutdown_complete = ChunkShutdownComplete {};
+            let shutdown_complete = ChunkShutdownComplete::default();
 
-            if let Ok(raw) = self
-                .create_packet(vec![Box::new(shutdown_complete)])
-                .marshal()
-            {
-                raw_packets.push(raw);
-                ok = false;
-            } else {
-                log::warn!(
-                    "[{}] failed to serialize a ShutdownComplete packet",
-                    self.name
-                );
-            }
+            packets.push(self.create_packet(vec![Box::new(shutdown_complete)]));
+            ok = false;
         }
 
-        (raw_packets, ok)
+        (packets, ok)
     }
 
-    /// gather_outbound gathers outgoing packets. The returned bool value set to
-    /// false means the association should be closed down after the final send.
-    pub(crate) async fn gather_outbound(&mut self) -> (Vec<Bytes>, bool) {
-        let mut raw_packets = vec![];
+    /// gather_outbound_control starts a round of the write loop: it takes the control packets
+    /// queued since the last one and schedules a window update if one is due. The round works
+    /// in the state returned, see gather_outbound.
+    pub(crate) async fn gather_outbound_control(&mut self) -> (Vec<Packet>, AssociationState) {
+        let packets: Vec<Packet> = self.control_queue.drain(..).collect();
 
-        if !self.control_queue.is_empty() {
-            for p in self.control_queue.drain(..) {
-                if let Ok(raw) = p.marshal() {
-                    raw_packets.push(raw);
-                } else {
-                    log::warn!("[{}] failed to serialize a control packet", self.name);
-                    continue;
-                }
-            }
+        let state = self.get_state();
+        if state == AssociationState::Established {
+            self.check_window_update().await;
         }
 
-        let state = self.get_state();
+        (packets, state)
+    }
+
+    /// gather_outbound gathers outgoing packets: the control packets and state taken by
+    /// gather_outbound_control, the DATA and FORWARD TSN chunks AssociationInflight gathered
+    /// since, and the SACK, RECONFIG and SHUTDOWN chunks due. The returned bool value set to
+    /// false means the association should be closed down after the final send. The packets are
+    /// serialized by the write loop once it released the association lock.
+    pub(crate) async fn gather_outbound(
+        &mut self,
+        mut packets: Vec<Packet>,
+        state: AssociationState,
+        mut inflight: InflightOutbound,
+    ) -> (Vec<Packet>, bool) {
         match state {
             AssociationState::Established => {
-                raw_packets = self.gather_data_packets_to_retransmit(raw_packets);
-                raw_packets = self
-                    .gather_outbound_data_and_reconfig_packets(raw_packets)
+                packets = self.gather_data_packets_to_retransmit(packets, &mut inflight);
+                packets = self
+                    .gather_outbound_data_and_reconfig_packets(packets, &mut inflight)
                     .await;
-                raw_packets = self.gather_outbound_fast_retransmission_packets(raw_packets);
-                raw_packets = self.gather_outbound_sack_packets(raw_packets).await;
-                raw_packets = self.gather_outbound_forward_tsn_packets(raw_packets);
-                (raw_packets, true)
+                packets = self.gather_outbound_fast_retransmission_packets(packets, &mut inflight);
+                packets = self.gather_outbound_sack_packets(packets).await;
+                packets = self.gather_outbound_forward_tsn_packets(packets, &mut inflight);
+                (packets, true)
             }
             AssociationState::ShutdownPending
             | AssociationState::ShutdownSent
             | AssociationState::ShutdownReceived => {
-                raw_packets = self.gather_data_packets_to_retransmit(raw_packets);
-                raw_packets = self.gather_outbound_fast_retransmission_packets(raw_packets);
-                raw_packets = self.gather_outbound_sack_packets(raw_packets).await;
-                self.gather_outbound_shutdown_packets(raw_packets).await
+                packets = self.gather_data_packets_to_retransmit(packets, &mut inflight);
+                packets = self.gather_outbound_fast_retransmission_packets(packets, &mut inflight);
+                packets = self.gather_outbound_sack_packets(packets).await;
+                self.gather_outbound_shutdown_packets(packets, &inflight)
+                    .await
             }
             AssociationState::ShutdownAckSent => {
-                self.gather_outbound_shutdown_packets(raw_packets).await
+                self.gather_outbound_shutdown_packets(packets, &inflight)
+                    .await
             }
-            _ => (raw_packets, true),
+            _ => (packets, true),
         }
     }
 
@@ -632,6 +711,8 @@ impl AssociationInternal {
     pub(crate) fn set_state(&self, new_state: AssociationState) {
         let old_state = AssociationState::from(self.state.swap(new_state as u8, Ordering::SeqCst));
         if new_state != old_state {
+            self.event_log
+                .record(ConnectionEventKind::SctpAssociationState(new_state));
             log::debug!(
                 "[{}] state change: '{}' => '{}'",
                 self.name,
@@ -663,14 +744,24 @@ impl AssociationInternal {
         {
             // 5.2.2.  Unexpected INIT in States Other than CLOSED, COOKIE-ECHOED,
             //        COOKIE-WAIT, and SHUTDOWN-ACK-SENT
-            return Err(Error::ErrHandleInitState);
+            // An INIT with a new initiate tag is the peer restarting its end, we do not carry
+            // the association over but close it. A retransmission of the INIT that set up the
+            // association is left alone.
+            if i.initiate_tag != self.peer_verification_tag {
+                log::warn!(
+                    "[{}] INIT with initiate tag {} in state '{}', the peer restarted",
+                    self.name,
+                    i.initiate_tag,
+                    state
+                );
+                return Err(Error::ErrPeerRestart);
+            }
+            log::debug!("[{}] ignoring retransmitted INIT", self.name);
+            return Ok(vec![]);
         }
 
         // Should we be setting any of these permanently until we've ACKed further?
-        self.my_max_num_inbound_streams =
-            std::cmp::min(i.num_inbound_streams, self.my_max_num_inbound_streams);
-        self.my_max_num_outbound_streams =
-            std::cmp::min(i.num_outbound_streams, self.my_max_num_outbound_streams);
+        self.negotiate_streams(i);
         self.peer_verification_tag = i.initiate_tag;
         self.source_port = p.destination_port;
         self.destination_port = p.source_port;
@@ -685,17 +776,20 @@ impl AssociationInternal {
             i.initial_tsn - 1
         };
 
+        let inflight = Arc::clone(&self.inflight);
+        let mut inflight = inflight.lock().await;
         for param in &i.params {
             if let Some(v) = param.as_any().downcast_ref::<ParamSupportedExtensions>() {
                 for t in &v.chunk_types {
                     if *t == CT_FORWARD_TSN {
                         log::debug!("[{}] use ForwardTSN (on init)", self.name);
-                        self.use_forward_tsn = true;
+                        inflight.use_forward_tsn = true;
                     }
                 }
+                self.negotiate_interleaving(v, inflight.mtu);
             }
         }
-        if !self.use_forward_tsn {
+        if !inflight.use_forward_tsn {
             log::warn!("[{}] not using ForwardTSN (on init)", self.name);
         }
 
@@ -708,23 +802,26 @@ impl AssociationInternal {
 
         let mut init_ack = ChunkInit {
             is_ack: true,
-            initial_tsn: self.my_next_tsn,
+            initial_tsn: inflight.my_next_tsn,
             num_outbound_streams: self.my_max_num_outbound_streams,
             num_inbound_streams: self.my_max_num_inbound_streams,
             initiate_tag: self.my_verification_tag,
-            advertised_receiver_window_credit: self.max_receive_buffer_size,
+            advertised_receiver_window_credit: self.initial_recv_window,
             ..Default::default()
         };
 
-        if self.my_cookie.is_none() {
-            self.my_cookie = Some(ParamStateCookie::new());
-        }
+        // a retransmitted INIT gets a fresh cookie, the one sent before may have expired
+        self.my_cookie = Some(ParamStateCookie::new(
+            self.my_verification_tag,
+            self.peer_verification_tag,
+        ));
 
         if let Some(my_cookie) = &self.my_cookie {
             init_ack.params = vec![Box::new(my_cookie.clone())];
         }
 
         init_ack.set_forward_tsn_supported();
+        init_ack.set_interleaving_supported();
 
         outbound.chunks = vec![Box::new(init_ack)];
 
@@ -744,10 +841,7 @@ impl AssociationInternal {
             return Ok(vec![]);
         }
 
-        self.my_max_num_inbound_streams =
-            std::cmp::min(i.num_inbound_streams, self.my_max_num_inbound_streams);
-        self.my_max_num_outbound_streams =
-            std::cmp::min(i.num_outbound_streams, self.my_max_num_outbound_streams);
+        self.negotiate_streams(i);
         self.peer_verification_tag = i.initiate_tag;
         self.peer_last_tsn = if i.initial_tsn == 0 {
             u32::MAX
@@ -759,20 +853,22 @@ impl AssociationInternal {
             return Ok(vec![]);
         }
 
-        self.rwnd = i.advertised_receiver_window_credit;
-        log::debug!("[{}] initial rwnd={}", self.name, self.rwnd);
+        let inflight = Arc::clone(&self.inflight);
+        let mut inflight = inflight.lock().await;
+        inflight.rwnd = i.advertised_receiver_window_credit;
+        log::debug!("[{}] initial rwnd={}", self.name, inflight.rwnd);
 
         // RFC 4690 Sec 7.2.1
         //  o  The initial value of ssthresh MAY be arbitrarily high (for
         //     example, implementations MAY use the size of the receiver
         //     advertised window).
-        self.ssthresh = self.rwnd;
+        inflight.ssthresh = inflight.rwnd;
         log::trace!(
             "[{}] updated cwnd={} ssthresh={} inflight={} (INI)",
             self.name,
-            self.cwnd,
-            self.ssthresh,
-            self.inflight_queue.get_num_bytes()
+            inflight.cwnd,
+            inflight.ssthresh,
+            inflight.get_num_bytes()
         );
 
         if let Some(t1init) = &self.t1init {
@@ -788,17 +884,18 @@ impl AssociationInternal {
                 for t in &v.chunk_types {
                     if *t == CT_FORWARD_TSN {
                         log::debug!("[{}] use ForwardTSN (on initAck)", self.name);
-                        self.use_forward_tsn = true;
+                        inflight.use_forward_tsn = true;
                     }
                 }
+                self.negotiate_interleaving(v, inflight.mtu);
             } else {
                 if let Some(_) = param.as_any().downcast_ref::<ParamForwardTsnSupported>() {
                     log::debug!("[{}] use ForwardTSN (on initAck)", self.name);
-                    self.use_forward_tsn = true;
+                    inflight.use_forward_tsn = true;
                 }
             }
         }
-        if !self.use_forward_tsn {
+        if !inflight.use_forward_tsn {
             log::warn!("[{}] not using ForwardTSN (on initAck)", self.name);
         }
 
@@ -810,7 +907,7 @@ impl AssociationInternal {
             self.send_cookie_echo()?;
 
             if let Some(t1cookie) = &self.t1cookie {
-                t1cookie.start(self.rto_mgr.get_rto()).await;
+                t1cookie.start(inflight.rto_mgr.get_rto()).await;
             }
 
             self.set_state(AssociationState::CookieEchoed);
@@ -821,6 +918,64 @@ impl AssociationInternal {
         }
     }
 
+    /// negotiate_streams settles the stream counts from the peer's INIT or INIT-ACK, RFC 4960
+    /// Section 5.1.1: we may send on as many streams as the peer accepts and receive on as many
+    /// as it sends on, each capped by what we advertised.
+    fn negotiate_streams(&mut self, i: &ChunkInit) {
+        self.num_outbound_streams =
+            std::cmp::min(self.my_max_num_outbound_streams, i.num_inbound_streams);
+        self.num_inbound_streams =
+            std::cmp::min(self.my_max_num_inbound_streams, i.num_outbound_streams);
+        log::debug!(
+            "[{}] negotiated streams inbound={} outbound={}",
+            self.name,
+            self.num_inbound_streams,
+            self.num_outbound_streams
+        );
+    }
+
+    /// negotiate_interleaving switches to I-DATA when the peer supports both I-DATA and
+    /// I-FORWARD-TSN, RFC 8260 Section 2.2. Peers without it keep getting DATA.
+    fn negotiate_interleaving(&mut self, v: &ParamSupportedExtensions, mtu: u32) {
+        if !v.chunk_types.contains(&CT_I_DATA) || !v.chunk_types.contains(&CT_I_FORWARD_TSN) {
+            return;
+        }
+
+        log::debug!("[{}] use I-DATA", self.name);
+        self.use_interleaving = true;
+        self.max_payload_size = (mtu - (COMMON_HEADER_SIZE + I_DATA_CHUNK_HEADER_SIZE)) & !3;
+        self.pending_queue.set_interleaving(true);
+    }
+
+    /// clamp_mtu lowers the MTU below the size of a packet the lower layer dropped as too
+    /// large, e.g. after the route MTU shrank, but not below MIN_MTU. Messages already
+    /// fragmented keep their size.
+    pub(crate) async fn clamp_mtu(&mut self, packet_size: usize) {
+        let mut inflight = self.inflight.lock().await;
+        let packet_size = u32::try_from(packet_size).unwrap_or(u32::MAX);
+        let mtu = std::cmp::min(inflight.mtu, packet_size)
+            .saturating_sub(MTU_CLAMP_STEP)
+            .max(MIN_MTU);
+        if mtu >= inflight.mtu {
+            return;
+        }
+
+        log::debug!(
+            "[{}] mtu clamped from {} to {}",
+            self.name,
+            inflight.mtu,
+            mtu
+        );
+        inflight.mtu = mtu;
+        drop(inflight);
+        let header_size = if self.use_interleaving {
+            I_DATA_CHUNK_HEADER_SIZE
+        } else {
+            DATA_CHUNK_HEADER_SIZE
+        };
+        self.max_payload_size = (mtu - (COMMON_HEADER_SIZE + header_size)) & !3;
+    }
+
     async fn handle_heartbeat(&self, c: &ChunkHeartbeat) -> Result<Vec<Packet>> {
         log::trace!("[{}] chunkHeartbeat", self.name);
         if let Some(p) = c.params.first() {
@@ -850,20 +1005,24 @@ impl AssociationInternal {
         let state = self.get_state();
         log::debug!("[{}] COOKIE-ECHO received in state '{}'", self.name, state);
 
-        if let Some(my_cookie) = &self.my_cookie {
+        if self.my_cookie.is_some() {
+            // RFC 4960 5.1.5: a cookie that was not signed by us for this association, or has
+            // expired, is silently discarded
+            if !ParamStateCookie::verify(
+                &c.cookie,
+                self.my_verification_tag,
+                self.peer_verification_tag,
+            ) {
+                log::debug!("[{}] discarding COOKIE-ECHO with invalid cookie", self.name);
+                self.stats.inc_invalid_cookies();
+                return Ok(vec![]);
+            }
+
             match state {
-                AssociationState::Established => {
-                    if my_cookie.cookie != c.cookie {
-                        return Ok(vec![]);
-                    }
-                }
+                AssociationState::Established => {}
                 AssociationState::Closed
                 | AssociationState::CookieWait
                 | AssociationState::CookieEchoed => {
-                    if my_cookie.cookie != c.cookie {
-                        return Ok(vec![]);
-                    }
-
                     if let Some(t1init) = &self.t1init {
                         t1init.stop().await;
                     }
@@ -928,7 +1087,15 @@ impl AssociationInternal {
         );
         self.stats.inc_datas();
 
+        // RFC 8260 Section 2.1, only the negotiated kind of payload chunk may be used
+        if d.interleaved != self.use_interleaving {
+            return Err(Error::ErrPayloadDataNotNegotiated);
+        }
+
         let can_push = self.payload_queue.can_push(d, self.peer_last_tsn);
+        if d.unordered {
+            self.record_arrival_order(d, can_push);
+        }
         let mut stream_handle_data = false;
         if can_push {
             if let Some(_s) = self.get_or_create_stream(d.stream_identifier) {
@@ -938,19 +1105,25 @@ impl AssociationInternal {
                     stream_handle_data = true;
                 } else {
                     // Receive buffer is full
-                    if let Some(last_tsn) = self.payload_queue.get_last_tsn_received() {
-                        if sna32lt(d.tsn, *last_tsn) {
-                            log::debug!("[{}] receive buffer full, but accepted as this is a missing chunk with tsn={} ssn={}", self.name, d.tsn, d.stream_sequence_number);
-                            self.payload_queue.push(d.clone(), self.peer_last_tsn);
-                            stream_handle_data = true; //s.handle_data(d.clone());
-                        }
+                    let is_missing_chunk = self
+                        .payload_queue
+                        .get_last_tsn_received()
+                        .map(|last_tsn| sna32lt(d.tsn, *last_tsn))
+                        .unwrap_or(false);
+                    if is_missing_chunk {
+                        log::debug!("[{}] receive buffer full, but accepted as this is a missing chunk with tsn={} ssn={}", self.name, d.tsn, d.stream_sequence_number);
+                        self.payload_queue.push(d.clone(), self.peer_last_tsn);
+                        stream_handle_data = true; //s.handle_data(d.clone());
                     } else {
+                        // The sender retransmits it once the window reopens, or abandons it on
+                        // a partially reliable stream
                         log::debug!(
                             "[{}] receive buffer full. dropping DATA with tsn={} ssn={}",
                             self.name,
                             d.tsn,
                             d.stream_sequence_number
                         );
+                        self.stats.inc_rwnd_drops();
                     }
                 }
             } else {
@@ -964,7 +1137,7 @@ impl AssociationInternal {
         let immediate_sack = d.immediate_sack;
 
         if stream_handle_data {
-            if let Some(s) = self.streams.get_mut(&d.stream_identifier) {
+            if let Some(s) = self.streams.get(d.stream_identifier) {
                 s.handle_data(d.clone()).await;
             }
         }
@@ -972,6 +1145,32 @@ impl AssociationInternal {
         self.handle_peer_last_tsn_and_acknowledgement(immediate_sack)
     }
 
+    /// record_arrival_order counts an unordered DATA chunk toward the ReorderStats of the
+    /// association and its stream: as a duplicate if its TSN was received before, as out of
+    /// order if a higher TSN was
+    fn record_arrival_order(&self, d: &ChunkPayloadData, is_new: bool) {
+        let stream = self.streams.get(d.stream_identifier);
+        if !is_new {
+            self.stats.inc_duplicate_tsns();
+            if let Some(s) = stream {
+                s.duplicate_tsns.fetch_add(1, Ordering::SeqCst);
+            }
+            return;
+        }
+
+        let highest_tsn = match self.payload_queue.get_last_tsn_received() {
+            Some(last_tsn) if sna32gt(*last_tsn, self.peer_last_tsn) => *last_tsn,
+            _ => self.peer_last_tsn,
+        };
+        if sna32lt(d.tsn, highest_tsn) {
+            let distance = highest_tsn.wrapping_sub(d.tsn);
+            self.stats.inc_out_of_order(distance);
+            if let Some(s) = stream {
+                s.record_out_of_order(distance);
+            }
+        }
+    }
+
     /// A common routine for handle_data and handle_forward_tsn routines
     fn handle_peer_last_tsn_and_acknowledgement(
         &mut self,
@@ -1030,7 +1229,7 @@ impl AssociationInternal {
 
     pub(crate) async fn get_my_receiver_window_credit(&self) -> u32 {
         let mut bytes_queued = 0;
-        for s in self.streams.values() {
+        for s in self.streams.streams() {
             bytes_queued += s.get_num_bytes_in_reassembly_queue().await as u32;
         }
 
@@ -1042,9 +1241,12 @@ impl AssociationInternal {
     }
 
     pub(crate) fn open_stream(&mut self, stream_identifier: u16) -> Result<Arc<Stream>> {
-        if self.streams.contains_key(&stream_identifier) {
+        if self.streams.contains(stream_identifier) {
             return Err(Error::ErrStreamAlreadyExist);
         }
+        if stream_identifier >= self.num_outbound_streams {
+            return Err(Error::ErrStreamsExhausted);
+        }
 
         if let Some(s) = self.create_stream(stream_identifier, false) {
             Ok(Arc::clone(&s))
@@ -1059,10 +1261,14 @@ impl AssociationInternal {
             format!("{}:{}", stream_identifier, self.name),
             stream_identifier,
             self.max_payload_size,
+            self.use_interleaving,
             Arc::clone(&self.max_message_size),
             Arc::clone(&self.state),
+            Arc::clone(&self.rwnd_reduced),
             self.awake_write_loop_ch.clone(),
             Arc::clone(&self.pending_queue),
+            Arc::clone(&self.send_buffer),
+            self.spawner.clone(),
         ));
 
         if accept {
@@ -1091,449 +1297,46 @@ impl AssociationInternal {
 
     /// get_or_create_stream gets or creates a stream. The caller should hold the lock.
     fn get_or_create_stream(&mut self, stream_identifier: u16) -> Option<Arc<Stream>> {
-        if self.streams.contains_key(&stream_identifier) {
-            self.streams.get(&stream_identifier).cloned()
-        } else {
-            self.create_stream(stream_identifier, true)
-        }
-    }
-
-    async fn process_selective_ack(
-        &mut self,
-        d: &ChunkSelectiveAck,
-    ) -> Result<(HashMap<u16, i64>, u32)> {
-        let mut bytes_acked_per_stream = HashMap::new();
-
-        // New ack point, so pop all ACKed packets from inflight_queue
-        // We add 1 because the "currentAckPoint" has already been popped from the inflight queue
-        // For the first SACK we take care of this by setting the ackpoint to cumAck - 1
-        let mut i = self.cumulative_tsn_ack_point + 1;
-        //log::debug!("[{}] i={} d={}", self.name, i, d.cumulative_tsn_ack);
-        while sna32lte(i, d.cumulative_tsn_ack) {
-            if let Some(c) = self.inflight_queue.pop(i) {
-                if !c.acked {
-                    // RFC 4096 sec 6.3.2.  Retransmission Timer Rules
-                    //   R3)  Whenever a SACK is received that acknowledges the DATA chunk
-                    //        with the earliest outstanding TSN for that address, restart the
-                    //        T3-rtx timer for that address with its current RTO (if there is
-                    //        still outstanding data on that address).
-                    if i == self.cumulative_tsn_ack_point + 1 {
-                        // T3 timer needs to be reset. Stop it for now.
-                        if let Some(t3rtx) = &self.t3rtx {
-                            t3rtx.stop().await;
-                        }
-                    }
-
-                    let n_bytes_acked = c.user_data.len() as i64;
-
-                    // Sum the number of bytes acknowledged per stream
-                    if let Some(amount) = bytes_acked_per_stream.get_mut(&c.stream_identifier) {
-                        *amount += n_bytes_acked;
-                    } else {
-                        bytes_acked_per_stream.insert(c.stream_identifier, n_bytes_acked);
-                    }
-
-                    // RFC 4960 sec 6.3.1.  RTO Calculation
-                    //   C4)  When data is in flight and when allowed by rule C5 below, a new
-                    //        RTT measurement MUST be made each round trip.  Furthermore, new
-                    //        RTT measurements SHOULD be made no more than once per round trip
-                    //        for a given destination transport address.
-                    //   C5)  Karn's algorithm: RTT measurements MUST NOT be made using
-                    //        packets that were retransmitted (and thus for which it is
-                    //        ambiguous whether the reply was for the first instance of the
-                    //        chunk or for a later instance)
-                    if c.nsent == 1 && sna32gte(c.tsn, self.min_tsn2measure_rtt) {
-                        self.min_tsn2measure_rtt = self.my_next_tsn;
-                        let rtt = match SystemTime::now().duration_since(c.since) {
-                            Ok(rtt) => rtt,
-                            Err(_) => return Err(Error::ErrInvalidSystemTime),
-                        };
-                        let srtt = self.rto_mgr.set_new_rtt(rtt.as_millis() as u64);
-                        log::trace!(
-                            "[{}] SACK: measured-rtt={} srtt={} new-rto={}",
-                            self.name,
-                            rtt.as_millis(),
-                            srtt,
-                            self.rto_mgr.get_rto()
-                        );
-                    }
-                }
-
-                if self.in_fast_recovery && c.tsn == self.fast_recover_exit_point {
-                    log::debug!("[{}] exit fast-recovery", self.name);
-                    self.in_fast_recovery = false;
-                }
-            } else {
-                return Err(Error::ErrInflightQueueTsnPop);
-            }
-
-            i += 1;
-        }
-
-        let mut htna = d.cumulative_tsn_ack;
-
-        // Mark selectively acknowledged chunks as "acked"
-        for g in &d.gap_ack_blocks {
-            for i in g.start..=g.end {
-                let tsn = d.cumulative_tsn_ack + i as u32;
-
-                let (is_existed, is_acked) = if let Some(c) = self.inflight_queue.get(tsn) {
-                    (true, c.acked)
-                } else {
-                    (false, false)
-                };
-                let n_bytes_acked = if is_existed && !is_acked {
-                    self.inflight_queue.mark_as_acked(tsn) as i64
-                } else {
-                    0
-                };
-
-                if let Some(c) = self.inflight_queue.get(tsn) {
-                    if !is_acked {
-                        // Sum the number of bytes acknowledged per stream
-                        if let Some(amount) = bytes_acked_per_stream.get_mut(&c.stream_identifier) {
-                            *amount += n_bytes_acked;
-                        } else {
-                            bytes_acked_per_stream.insert(c.stream_identifier, n_bytes_acked);
-                        }
-
-                        log::trace!("[{}] tsn={} has been sacked", self.name, c.tsn);
-
-                        if c.nsent == 1 {
-                            self.min_tsn2measure_rtt = self.my_next_tsn;
-                            let rtt = match SystemTime::now().duration_since(c.since) {
-                                Ok(rtt) => rtt,
-                                Err(_) => return Err(Error::ErrInvalidSystemTime),
-                            };
-                            let srtt = self.rto_mgr.set_new_rtt(rtt.as_millis() as u64);
-                            log::trace!(
-                                "[{}] SACK: measured-rtt={} srtt={} new-rto={}",
-                                self.name,
-                                rtt.as_millis(),
-                                srtt,
-                                self.rto_mgr.get_rto()
-                            );
-                        }
-
-                        if sna32lt(htna, tsn) {
-                            htna = tsn;
-                        }
-                    }
-                } else {
-                    return Err(Error::ErrTsnRequestNotExist);
-                }
-            }
-        }
-
-        Ok((bytes_acked_per_stream, htna))
-    }
-
-    async fn on_cumulative_tsn_ack_point_advanced(&mut self, total_bytes_acked: i64) {
-        // RFC 4096, sec 6.3.2.  Retransmission Timer Rules
-        //   R2)  Whenever all outstanding data sent to an address have been
-        //        acknowledged, turn off the T3-rtx timer of that address.
-        if self.inflight_queue.is_empty() {
-            log::trace!(
-                "[{}] SACK: no more packet in-flight (pending={})",
-                self.name,
-                self.pending_queue.len()
-            );
-            if let Some(t3rtx) = &self.t3rtx {
-                t3rtx.stop().await;
-            }
-        } else {
-            log::trace!("[{}] T3-rtx timer start (pt2)", self.name);
-            if let Some(t3rtx) = &self.t3rtx {
-                t3rtx.start(self.rto_mgr.get_rto()).await;
-            }
-        }
-
-        // Update congestion control parameters
-        if self.cwnd <= self.ssthresh {
-            // RFC 4096, sec 7.2.1.  Slow-Start
-            //   o  When cwnd is less than or equal to ssthresh, an SCTP endpoint MUST
-            //		use the slow-start algorithm to increase cwnd only if the current
-            //      congestion window is being fully utilized, an incoming SACK
-            //      advances the Cumulative TSN Ack Point, and the data sender is not
-            //      in Fast Recovery.  Only when these three conditions are met can
-            //      the cwnd be increased; otherwise, the cwnd MUST not be increased.
-            //		If these conditions are met, then cwnd MUST be increased by, at
-            //      most, the lesser of 1) the total size of the previously
-            //      outstanding DATA chunk(s) acknowledged, and 2) the destination's
-            //      path MTU.
-            if !self.in_fast_recovery && self.pending_queue.len() > 0 {
-                self.cwnd += std::cmp::min(total_bytes_acked as u32, self.cwnd); // TCP way
-                                                                                 // self.cwnd += min32(uint32(total_bytes_acked), self.mtu) // SCTP way (slow)
-                log::trace!(
-                    "[{}] updated cwnd={} ssthresh={} acked={} (SS)",
-                    self.name,
-                    self.cwnd,
-                    self.ssthresh,
-                    total_bytes_acked
-                );
-            } else {
-                log::trace!(
-                    "[{}] cwnd did not grow: cwnd={} ssthresh={} acked={} FR={} pending={}",
-                    self.name,
-                    self.cwnd,
-                    self.ssthresh,
-                    total_bytes_acked,
-                    self.in_fast_recovery,
-                    self.pending_queue.len()
-                );
-            }
-        } else {
-            // RFC 4096, sec 7.2.2.  Congestion Avoidance
-            //   o  Whenever cwnd is greater than ssthresh, upon each SACK arrival
-            //      that advances the Cumulative TSN Ack Point, increase
-            //      partial_bytes_acked by the total number of bytes of all new chunks
-            //      acknowledged in that SACK including chunks acknowledged by the new
-            //      Cumulative TSN Ack and by Gap Ack Blocks.
-            self.partial_bytes_acked += total_bytes_acked as u32;
-
-            //   o  When partial_bytes_acked is equal to or greater than cwnd and
-            //      before the arrival of the SACK the sender had cwnd or more bytes
-            //      of data outstanding (i.e., before arrival of the SACK, flight size
-            //      was greater than or equal to cwnd), increase cwnd by MTU, and
-            //      reset partial_bytes_acked to (partial_bytes_acked - cwnd).
-            if self.partial_bytes_acked >= self.cwnd && self.pending_queue.len() > 0 {
-                self.partial_bytes_acked -= self.cwnd;
-                self.cwnd += self.mtu;
-                log::trace!(
-                    "[{}] updated cwnd={} ssthresh={} acked={} (CA)",
-                    self.name,
-                    self.cwnd,
-                    self.ssthresh,
-                    total_bytes_acked
-                );
-            }
+        match self.streams.get(stream_identifier) {
+            Some(s) => Some(s),
+            None => self.create_stream(stream_identifier, true),
         }
     }
 
-    fn process_fast_retransmission(
-        &mut self,
-        cum_tsn_ack_point: u32,
-        htna: u32,
-        cum_tsn_ack_point_advanced: bool,
-    ) -> Result<()> {
-        // HTNA algorithm - RFC 4960 Sec 7.2.4
-        // Increment missIndicator of each chunks that the SACK reported missing
-        // when either of the following is met:
-        // a)  Not in fast-recovery
-        //     miss indications are incremented only for missing TSNs prior to the
-        //     highest TSN newly acknowledged in the SACK.
-        // b)  In fast-recovery AND the Cumulative TSN Ack Point advanced
-        //     the miss indications are incremented for all TSNs reported missing
-        //     in the SACK.
-        if !self.in_fast_recovery || cum_tsn_ack_point_advanced {
-            let max_tsn = if !self.in_fast_recovery {
-                // a) increment only for missing TSNs prior to the HTNA
-                htna
-            } else {
-                // b) increment for all TSNs reported missing
-                cum_tsn_ack_point + (self.inflight_queue.len() as u32) + 1
-            };
-
-            let mut tsn = cum_tsn_ack_point + 1;
-            while sna32lt(tsn, max_tsn) {
-                if let Some(c) = self.inflight_queue.get_mut(tsn) {
-                    if !c.acked && !c.abandoned() && c.miss_indicator < 3 {
-                        c.miss_indicator += 1;
-                        if c.miss_indicator == 3 && !self.in_fast_recovery {
-                            // 2)  If not in Fast Recovery, adjust the ssthresh and cwnd of the
-                            //     destination address(es) to which the missing DATA chunks were
-                            //     last sent, according to the formula described in Section 7.2.3.
-                            self.in_fast_recovery = true;
-                            self.fast_recover_exit_point = htna;
-                            self.ssthresh = std::cmp::max(self.cwnd / 2, 4 * self.mtu);
-                            self.cwnd = self.ssthresh;
-                            self.partial_bytes_acked = 0;
-                            self.will_retransmit_fast = true;
-
-                            log::trace!(
-                                "[{}] updated cwnd={} ssthresh={} inflight={} (FR)",
-                                self.name,
-                                self.cwnd,
-                                self.ssthresh,
-                                self.inflight_queue.get_num_bytes()
-                            );
-                        }
-                    }
-                } else {
-                    return Err(Error::ErrTsnRequestNotExist);
-                }
-
-                tsn += 1;
-            }
-        }
-
-        if self.in_fast_recovery && cum_tsn_ack_point_advanced {
-            self.will_retransmit_fast = true;
-        }
-
-        Ok(())
-    }
-
+    /// handle_sack hands a SACK to AssociationInflight and moves a shutting down association on
+    /// once no DATA is in flight anymore
     async fn handle_sack(&mut self, d: &ChunkSelectiveAck) -> Result<Vec<Packet>> {
-        log::trace!(
-            "[{}] {}, SACK: cumTSN={} a_rwnd={}",
-            self.name,
-            self.cumulative_tsn_ack_point,
-            d.cumulative_tsn_ack,
-            d.advertised_receiver_window_credit
-        );
         let state = self.get_state();
-        if state != AssociationState::Established
-            && state != AssociationState::ShutdownPending
-            && state != AssociationState::ShutdownReceived
-        {
-            return Ok(vec![]);
-        }
-
-        self.stats.inc_sacks();
-
-        if sna32gt(self.cumulative_tsn_ack_point, d.cumulative_tsn_ack) {
-            // RFC 4960 sec 6.2.1.  Processing a Received SACK
-            // D)
-            //   i) If Cumulative TSN Ack is less than the Cumulative TSN Ack
-            //      Point, then drop the SACK.  Since Cumulative TSN Ack is
-            //      monotonically increasing, a SACK whose Cumulative TSN Ack is
-            //      less than the Cumulative TSN Ack Point indicates an out-of-
-            //      order SACK.
-
-            log::debug!(
-                "[{}] SACK Cumulative ACK {} is older than ACK point {}",
-                self.name,
-                d.cumulative_tsn_ack,
-                self.cumulative_tsn_ack_point
-            );
-
+        let all_acked = {
+            let mut inflight = self.inflight.lock().await;
+            let all_acked = inflight.handle_sack(d).await?;
+            inflight.update_send_buffer();
+            all_acked
+        };
+        if !all_acked {
             return Ok(vec![]);
         }
 
-        // Process selective ack
-        let (bytes_acked_per_stream, htna) = self.process_selective_ack(d).await?;
-
-        let mut total_bytes_acked = 0;
-        for n_bytes_acked in bytes_acked_per_stream.values() {
-            total_bytes_acked += *n_bytes_acked;
-        }
-
-        let mut cum_tsn_ack_point_advanced = false;
-        if sna32lt(self.cumulative_tsn_ack_point, d.cumulative_tsn_ack) {
-            log::trace!(
-                "[{}] SACK: cumTSN advanced: {} -> {}",
-                self.name,
-                self.cumulative_tsn_ack_point,
-                d.cumulative_tsn_ack
-            );
-
-            self.cumulative_tsn_ack_point = d.cumulative_tsn_ack;
-            cum_tsn_ack_point_advanced = true;
-            self.on_cumulative_tsn_ack_point_advanced(total_bytes_acked)
-                .await;
-        }
-
-        for (si, n_bytes_acked) in &bytes_acked_per_stream {
-            if let Some(s) = self.streams.get_mut(si) {
-                s.on_buffer_released(*n_bytes_acked).await;
-            }
-        }
-
-        // New rwnd value
-        // RFC 4960 sec 6.2.1.  Processing a Received SACK
-        // D)
-        //   ii) Set rwnd equal to the newly received a_rwnd minus the number
-        //       of bytes still outstanding after processing the Cumulative
-        //       TSN Ack and the Gap Ack Blocks.
-
-        // bytes acked were already subtracted by markAsAcked() method
-        let bytes_outstanding = self.inflight_queue.get_num_bytes() as u32;
-        if bytes_outstanding >= d.advertised_receiver_window_credit {
-            self.rwnd = 0;
-        } else {
-            self.rwnd = d.advertised_receiver_window_credit - bytes_outstanding;
-        }
-
-        self.process_fast_retransmission(d.cumulative_tsn_ack, htna, cum_tsn_ack_point_advanced)?;
-
-        if self.use_forward_tsn {
-            // RFC 3758 Sec 3.5 C1
-            if sna32lt(
-                self.advanced_peer_tsn_ack_point,
-                self.cumulative_tsn_ack_point,
-            ) {
-                self.advanced_peer_tsn_ack_point = self.cumulative_tsn_ack_point
-            }
-
-            // RFC 3758 Sec 3.5 C2
-            let mut i = self.advanced_peer_tsn_ack_point + 1;
-            while let Some(c) = self.inflight_queue.get(i) {
-                if !c.abandoned() {
-                    break;
-                }
-                self.advanced_peer_tsn_ack_point = i;
-                i += 1;
-            }
-
-            // RFC 3758 Sec 3.5 C3
-            if sna32gt(
-                self.advanced_peer_tsn_ack_point,
-                self.cumulative_tsn_ack_point,
-            ) {
-                self.will_send_forward_tsn = true;
-                log::debug!(
-                    "[{}] handleSack {}: sna32GT({}, {})",
-                    self.name,
-                    self.will_send_forward_tsn,
-                    self.advanced_peer_tsn_ack_point,
-                    self.cumulative_tsn_ack_point
-                );
-            }
-            self.awake_write_loop();
-        }
-
-        self.postprocess_sack(state, cum_tsn_ack_point_advanced)
-            .await;
-
-        Ok(vec![])
-    }
-
-    /// The caller must hold the lock. This method was only added because the
-    /// linter was complaining about the "cognitive complexity" of handle_sack.
-    async fn postprocess_sack(
-        &mut self,
-        state: AssociationState,
-        mut should_awake_write_loop: bool,
-    ) {
-        if !self.inflight_queue.is_empty() {
-            // Start timer. (noop if already started)
-            log::trace!("[{}] T3-rtx timer start (pt3)", self.name);
-            if let Some(t3rtx) = &self.t3rtx {
-                t3rtx.start(self.rto_mgr.get_rto()).await;
-            }
-        } else if state == AssociationState::ShutdownPending {
+        if state == AssociationState::ShutdownPending {
             // No more outstanding, send shutdown.
-            should_awake_write_loop = true;
             self.will_send_shutdown.store(true, Ordering::SeqCst);
             self.set_state(AssociationState::ShutdownSent);
+            self.awake_write_loop();
         } else if state == AssociationState::ShutdownReceived {
             // No more outstanding, send shutdown ack.
-            should_awake_write_loop = true;
             self.will_send_shutdown_ack = true;
             self.set_state(AssociationState::ShutdownAckSent);
-        }
-
-        if should_awake_write_loop {
             self.awake_write_loop();
         }
+
+        Ok(vec![])
     }
 
     async fn handle_shutdown(&mut self, _: &ChunkShutdown) -> Result<Vec<Packet>> {
         let state = self.get_state();
 
         if state == AssociationState::Established {
-            if !self.inflight_queue.is_empty() {
+            if !self.inflight.lock().await.is_empty() {
                 self.set_state(AssociationState::ShutdownReceived);
             } else {
                 // No more outstanding, send shutdown ack.
@@ -1574,59 +1377,12 @@ impl AssociationInternal {
             if let Some(t2shutdown) = &self.t2shutdown {
                 t2shutdown.stop().await;
             }
-            self.close().await?;
+            self.close(CloseReason::AssociationShutdown).await?;
         }
 
         Ok(vec![])
     }
 
-    /// create_forward_tsn generates ForwardTSN chunk.
-    /// This method will be be called if use_forward_tsn is set to false.
-    fn create_forward_tsn(&self) -> ChunkForwardTsn {
-        // RFC 3758 Sec 3.5 C4
-        let mut stream_map: HashMap<u16, u16> = HashMap::new(); // to report only once per SI
-        let mut i = self.cumulative_tsn_ack_point + 1;
-        while sna32lte(i, self.advanced_peer_tsn_ack_point) {
-            if let Some(c) = self.inflight_queue.get(i) {
-                if let Some(ssn) = stream_map.get(&c.stream_identifier) {
-                    if sna16lt(*ssn, c.stream_sequence_number) {
-                        // to report only once with greatest SSN
-                        stream_map.insert(c.stream_identifier, c.stream_sequence_number);
-                    }
-                } else {
-                    stream_map.insert(c.stream_identifier, c.stream_sequence_number);
-                }
-            } else {
-                break;
-            }
-
-            i += 1;
-        }
-
-        let mut fwd_tsn = ChunkForwardTsn {
-            new_cumulative_tsn: self.advanced_peer_tsn_ack_point,
-            streams: vec![],
-        };
-
-        let mut stream_str = String::new();
-        for (si, ssn) in &stream_map {
-            stream_str += format!("(si={} ssn={})", si, ssn).as_str();
-            fwd_tsn.streams.push(ChunkForwardTsnStream {
-                identifier: *si,
-                sequence: *ssn,
-            });
-        }
-        log::trace!(
-            "[{}] building fwd_tsn: newCumulativeTSN={} cumTSN={} - {}",
-            self.name,
-            fwd_tsn.new_cumulative_tsn,
-            self.cumulative_tsn_ack_point,
-            stream_str
-        );
-
-        fwd_tsn
-    }
-
     /// create_packet wraps chunks in a packet.
     /// The caller should hold the read lock.
     pub(crate) fn create_packet(&self, chunks: Vec<Box<dyn Chunk + Send + Sync>>) -> Packet {
@@ -1661,22 +1417,77 @@ impl AssociationInternal {
     async fn handle_forward_tsn(&mut self, c: &ChunkForwardTsn) -> Result<Vec<Packet>> {
         log::trace!("[{}] FwdTSN: {}", self.name, c.to_string());
 
-        if !self.use_forward_tsn {
+        if !self.inflight.lock().await.use_forward_tsn {
             log::warn!("[{}] received FwdTSN but not enabled", self.name);
-            // Return an error chunk
-            let cerr = ChunkError {
-                error_causes: vec![ErrorCauseUnrecognizedChunkType::default()],
-            };
+            return Ok(vec![self.unrecognized_chunk_type_packet()]);
+        }
 
-            let outbound = Packet {
-                verification_tag: self.peer_verification_tag,
-                source_port: self.source_port,
-                destination_port: self.destination_port,
-                chunks: vec![Box::new(cerr)],
-            };
-            return Ok(vec![outbound]);
+        if !self.advance_peer_last_tsn(c.new_cumulative_tsn) {
+            return Ok(vec![]);
+        }
+
+        // TSN may be forewared for unordered chunks. ForwardTSN chunk does not
+        // report which stream identifier it skipped for unordered chunks.
+        // Therefore, we need to broadcast this event to all existing streams for
+        // unordered chunks.
+        // See https://github.com/pion/sctp/issues/106
+        for s in self.streams.streams() {
+            s.handle_forward_tsn_for_unordered(c.new_cumulative_tsn)
+                .await;
+        }
+
+        // RFC 3758 Sec 3.6, ordered delivery on the listed streams resumes after the skipped
+        // SSN, or a skipped message that only partly arrived would hold back the ones after it
+        for fs in &c.streams {
+            if let Some(s) = self.streams.get(fs.identifier) {
+                s.handle_forward_tsn_for_ordered(fs.sequence).await;
+            }
+        }
+
+        self.handle_peer_last_tsn_and_acknowledgement(false)
+    }
+
+    async fn handle_i_forward_tsn(&mut self, c: &ChunkIForwardTsn) -> Result<Vec<Packet>> {
+        log::trace!("[{}] I-FwdTSN: {}", self.name, c);
+
+        // RFC 8260 Section 2.3.1, I-FORWARD-TSN goes with I-DATA only
+        if !self.inflight.lock().await.use_forward_tsn || !self.use_interleaving {
+            log::warn!("[{}] received I-FwdTSN but not enabled", self.name);
+            return Ok(vec![self.unrecognized_chunk_type_packet()]);
+        }
+
+        if !self.advance_peer_last_tsn(c.new_cumulative_tsn) {
+            return Ok(vec![]);
+        }
+
+        // unlike ForwardTSN, the skipped unordered messages are reported too
+        for fs in &c.streams {
+            if let Some(s) = self.streams.get(fs.identifier) {
+                s.handle_i_forward_tsn(fs.unordered, fs.message_identifier)
+                    .await;
+            }
         }
 
+        self.handle_peer_last_tsn_and_acknowledgement(false)
+    }
+
+    fn unrecognized_chunk_type_packet(&self) -> Packet {
+        // Return an error chunk
+        let cerr = ChunkError {
+            error_causes: vec![ErrorCauseUnrecognizedChunkType::default()],
+        };
+
+        Packet {
+            verification_tag: self.peer_verification_tag,
+            source_port: self.source_port,
+            destination_port: self.destination_port,
+            chunks: vec![Box::new(cerr)],
+        }
+    }
+
+    /// advance_peer_last_tsn moves the cumulative TSN point up to the one of a FORWARD TSN
+    /// or I-FORWARD-TSN chunk. It returns false if the chunk is out of date.
+    fn advance_peer_last_tsn(&mut self, new_cumulative_tsn: u32) -> bool {
         // From RFC 3758 Sec 3.6:
         //   Note, if the "New Cumulative TSN" value carried in the arrived
         //   FORWARD TSN chunk is found to be behind or at the current cumulative
@@ -1688,17 +1499,17 @@ impl AssociationInternal {
         log::trace!(
             "[{}] should send ack? newCumTSN={} peer_last_tsn={}",
             self.name,
-            c.new_cumulative_tsn,
+            new_cumulative_tsn,
             self.peer_last_tsn
         );
-        if sna32lte(c.new_cumulative_tsn, self.peer_last_tsn) {
+        if sna32lte(new_cumulative_tsn, self.peer_last_tsn) {
             log::trace!("[{}] sending ack on Forward TSN", self.name);
             self.ack_state = AckState::Immediate;
             if let Some(ack_timer) = &mut self.ack_timer {
                 ack_timer.stop();
             }
             self.awake_write_loop();
-            return Ok(vec![]);
+            return false;
         }
 
         // From RFC 3758 Sec 3.6:
@@ -1712,22 +1523,15 @@ impl AssociationInternal {
         //   chunk,
 
         // Advance peer_last_tsn
-        while sna32lt(self.peer_last_tsn, c.new_cumulative_tsn) {
-            self.payload_queue.pop(self.peer_last_tsn + 1); // may not exist
+        while sna32lt(self.peer_last_tsn, new_cumulative_tsn) {
+            // may not exist, the peer gave up on the chunk before it arrived then
+            if self.payload_queue.pop(self.peer_last_tsn + 1).is_none() {
+                self.stats.inc_skipped_tsns();
+            }
             self.peer_last_tsn += 1;
         }
 
-        // TSN may be forewared for unordered chunks. ForwardTSN chunk does not
-        // report which stream identifier it skipped for unordered chunks.
-        // Therefore, we need to broadcast this event to all existing streams for
-        // unordered chunks.
-        // See https://github.com/pion/sctp/issues/106
-        for s in self.streams.values_mut() {
-            s.handle_forward_tsn_for_unordered(c.new_cumulative_tsn)
-                .await;
-        }
-
-        self.handle_peer_last_tsn_and_acknowledgement(false)
+        true
     }
 
     #[allow(clippy::borrowed_box)]
@@ -1762,10 +1566,7 @@ impl AssociationInternal {
                 self.peer_last_tsn
             );
             for id in &p.stream_identifiers {
-                if let Some(s) = self.streams.get(id) {
-                    let stream_identifier = s.stream_identifier;
-                    self.unregister_stream(stream_identifier);
-                }
+                self.unregister_stream(*id, CloseReason::RemoteReset);
             }
             self.reconfig_requests
                 .remove(&p.reconfig_request_sequence_number);
@@ -1788,141 +1589,44 @@ impl AssociationInternal {
         })])
     }
 
-    /// Move the chunk peeked with self.pending_queue.peek() to the inflight_queue.
-    async fn move_pending_data_chunk_to_inflight_queue(
-        &mut self,
-        beginning_fragment: bool,
-        unordered: bool,
-    ) -> Option<ChunkPayloadData> {
-        if let Some(mut c) = self.pending_queue.pop(beginning_fragment, unordered).await {
-            // Mark all fragements are in-flight now
-            if c.ending_fragment {
-                c.set_all_inflight();
-            }
-
-            // Assign TSN
-            c.tsn = self.generate_next_tsn();
-
-            c.since = SystemTime::now(); // use to calculate RTT and also for maxPacketLifeTime
-            c.nsent = 1; // being sent for the first time
-
-            self.check_partial_reliability_status(&c);
-
-            log::trace!(
-                "[{}] sending ppi={} tsn={} ssn={} sent={} len={} ({},{})",
-                self.name,
-                c.payload_type as u32,
-                c.tsn,
-                c.stream_sequence_number,
-                c.nsent,
-                c.user_data.len(),
-                c.beginning_fragment,
-                c.ending_fragment
-            );
-
-            self.inflight_queue.push_no_check(c.clone());
-
-            Some(c)
-        } else {
-            log::error!("[{}] failed to pop from pending queue", self.name);
-            None
-        }
-    }
-
-    /// pop_pending_data_chunks_to_send pops chunks from the pending queues as many as
-    /// the cwnd and rwnd allows to send.
-    async fn pop_pending_data_chunks_to_send(&mut self) -> (Vec<ChunkPayloadData>, Vec<u16>) {
-        let mut chunks = vec![];
-        let mut sis_to_reset = vec![]; // stream identifiers to reset
-        let is_empty = self.pending_queue.len() == 0;
-        if !is_empty {
-            // RFC 4960 sec 6.1.  Transmission of DATA Chunks
-            //   A) At any given time, the data sender MUST NOT transmit new data to
-            //      any destination transport address if its peer's rwnd indicates
-            //      that the peer has no buffer space (i.e., rwnd is 0; see Section
-            //      6.2.1).  However, regardless of the value of rwnd (including if it
-            //      is 0), the data sender can always have one DATA chunk in flight to
-            //      the receiver if allowed by cwnd (see rule B, below).
-
-            while let Some(c) = self.pending_queue.peek().await {
-                let (beginning_fragment, unordered, data_len, stream_identifier) = (
-                    c.beginning_fragment,
-                    c.unordered,
-                    c.user_data.len(),
-                    c.stream_identifier,
-                );
-
-                if data_len == 0 {
-                    sis_to_reset.push(stream_identifier);
-                    if self
-                        .pending_queue
-                        .pop(beginning_fragment, unordered)
-                        .await
-                        .is_none()
-                    {
-                        log::error!("failed to pop from pending queue");
-                    }
-                    continue;
-                }
-
-                if self.inflight_queue.get_num_bytes() + data_len > self.cwnd as usize {
-                    break; // would exceeds cwnd
-                }
-
-                if data_len > self.rwnd as usize {
-                    break; // no more rwnd
-                }
-
-                self.rwnd -= data_len as u32;
-
-                if let Some(chunk) = self
-                    .move_pending_data_chunk_to_inflight_queue(beginning_fragment, unordered)
-                    .await
-                {
-                    chunks.push(chunk);
-                }
-            }
-
-            // the data sender can always have one DATA chunk in flight to the receiver
-            if chunks.is_empty() && self.inflight_queue.is_empty() {
-                // Send zero window probe
-                if let Some(c) = self.pending_queue.peek().await {
-                    let (beginning_fragment, unordered) = (c.beginning_fragment, c.unordered);
-
-                    if let Some(chunk) = self
-                        .move_pending_data_chunk_to_inflight_queue(beginning_fragment, unordered)
-                        .await
-                    {
-                        chunks.push(chunk);
-                    }
-                }
-            }
-        }
-
-        (chunks, sis_to_reset)
-    }
-
     /// bundle_data_chunks_into_packets packs DATA chunks into packets. It tries to bundle
     /// DATA chunks into a packet so long as the resulting packet size does not exceed
-    /// the path MTU.
-    fn bundle_data_chunks_into_packets(&self, chunks: Vec<ChunkPayloadData>) -> Vec<Packet> {
+    /// the path MTU. A SACK, if given, leads the first packet.
+    fn bundle_data_chunks_into_packets(
+        &self,
+        sack: Option<ChunkSelectiveAck>,
+        chunks: Vec<ChunkPayloadData>,
+        mtu: u32,
+    ) -> Vec<Packet> {
         let mut packets = vec![];
-        let mut chunks_to_send = vec![];
+        let mut chunks_to_send: Vec<Box<dyn Chunk + Send + Sync>> = vec![];
         let mut bytes_in_packet = COMMON_HEADER_SIZE;
 
+        // RFC 4960 sec 6.10.  Bundling
+        //   If an endpoint bundles control chunks together with DATA chunks, the
+        //   control chunks MUST be placed before the DATA chunks in the packet.
+        if let Some(sack) = sack {
+            let sack_len = CHUNK_HEADER_SIZE + sack.value_length();
+            bytes_in_packet += (sack_len + get_padding_size(sack_len)) as u32;
+            chunks_to_send.push(Box::new(sack));
+        }
+
         for c in chunks {
             // RFC 4960 sec 6.1.  Transmission of DATA Chunks
             //   Multiple DATA chunks committed for transmission MAY be bundled in a
             //   single packet.  Furthermore, DATA chunks being retransmitted MAY be
             //   bundled with new DATA chunks, as long as the resulting packet size
             //   does not exceed the path MTU.
-            if bytes_in_packet + c.user_data.len() as u32 > self.mtu {
+            let data_len = c.user_data.len();
+            let data_chunk_size =
+                data_chunk_header_size(&c) + (data_len + get_padding_size(data_len)) as u32;
+            if !chunks_to_send.is_empty() && bytes_in_packet + data_chunk_size > mtu {
                 packets.push(self.create_packet(chunks_to_send));
                 chunks_to_send = vec![];
                 bytes_in_packet = COMMON_HEADER_SIZE;
             }
 
-            bytes_in_packet += DATA_CHUNK_HEADER_SIZE + c.user_data.len() as u32;
+            bytes_in_packet += data_chunk_size;
             chunks_to_send.push(Box::new(c));
         }
 
@@ -1933,87 +1637,6 @@ impl AssociationInternal {
         packets
     }
 
-    fn check_partial_reliability_status(&self, c: &ChunkPayloadData) {
-        if !self.use_forward_tsn {
-            return;
-        }
-
-        // draft-ietf-rtcweb-data-protocol-09.txt section 6
-        //	6.  Procedures
-        //		All Data Channel Establishment Protocol messages MUST be sent using
-        //		ordered delivery and reliable transmission.
-        //
-        if c.payload_type == PayloadProtocolIdentifier::Dcep {
-            return;
-        }
-
-        // PR-SCTP
-        if self.streams.contains_key(&c.stream_identifier) {
-            c.set_abandoned(true);
-        } else {
-            log::error!("[{}] stream {} not found)", self.name, c.stream_identifier);
-        }
-    }
-
-    /// get_data_packets_to_retransmit is called when T3-rtx is timed out and retransmit outstanding data chunks
-    /// that are not acked or abandoned yet.
-    fn get_data_packets_to_retransmit(&mut self) -> Vec<Packet> {
-        let awnd = std::cmp::min(self.cwnd, self.rwnd);
-        let mut chunks = vec![];
-        let mut bytes_to_send = 0;
-        let mut done = false;
-        let mut i = 0;
-        while !done {
-            let tsn = self.cumulative_tsn_ack_point + i + 1;
-            if let Some(c) = self.inflight_queue.get_mut(tsn) {
-                if !c.retransmit {
-                    i += 1;
-                    continue;
-                }
-
-                if i == 0 && self.rwnd < c.user_data.len() as u32 {
-                    // Send it as a zero window probe
-                    done = true;
-                } else if bytes_to_send + c.user_data.len() > awnd as usize {
-                    break;
-                }
-
-                // reset the retransmit flag not to retransmit again before the next
-                // t3-rtx timer fires
-                c.retransmit = false;
-                bytes_to_send += c.user_data.len();
-
-                c.nsent += 1;
-            } else {
-                break; // end of pending data
-            }
-
-            if let Some(c) = self.inflight_queue.get(tsn) {
-                self.check_partial_reliability_status(c);
-
-                log::trace!(
-                    "[{}] retransmitting tsn={} ssn={} sent={}",
-                    self.name,
-                    c.tsn,
-                    c.stream_sequence_number,
-                    c.nsent
-                );
-
-                chunks.push(c.clone());
-            }
-            i += 1;
-        }
-
-        self.bundle_data_chunks_into_packets(chunks)
-    }
-
-    /// generate_next_tsn returns the my_next_tsn and increases it. The caller should hold the lock.
-    fn generate_next_tsn(&mut self) -> u32 {
-        let tsn = self.my_next_tsn;
-        self.my_next_tsn += 1;
-        tsn
-    }
-
     /// generate_next_rsn returns the my_next_rsn and increases it. The caller should hold the lock.
     fn generate_next_rsn(&mut self) -> u32 {
         let rsn = self.my_next_rsn;
@@ -2022,9 +1645,16 @@ impl AssociationInternal {
     }
 
     async fn create_selective_ack_chunk(&mut self) -> ChunkSelectiveAck {
+        let advertised_receiver_window_credit = self.get_my_receiver_window_credit().await;
+        self.last_advertised_rwnd = advertised_receiver_window_credit;
+        self.rwnd_reduced.store(
+            advertised_receiver_window_credit < self.max_receive_buffer_size / 2,
+            Ordering::SeqCst,
+        );
+
         ChunkSelectiveAck {
             cumulative_tsn_ack: self.peer_last_tsn,
-            advertised_receiver_window_credit: self.get_my_receiver_window_credit().await,
+            advertised_receiver_window_credit,
             gap_ack_blocks: self.payload_queue.get_gap_ack_blocks(self.peer_last_tsn),
             duplicate_tsn: self.payload_queue.pop_duplicates(),
         }
@@ -2083,6 +1713,8 @@ impl AssociationInternal {
             self.handle_reconfig(c).await?
         } else if let Some(c) = chunk_any.downcast_ref::<ChunkForwardTsn>() {
             self.handle_forward_tsn(c).await?
+        } else if let Some(c) = chunk_any.downcast_ref::<ChunkIForwardTsn>() {
+            self.handle_i_forward_tsn(c).await?
         } else if let Some(c) = chunk_any.downcast_ref::<ChunkShutdown>() {
             self.handle_shutdown(c).await?
         } else if let Some(c) = chunk_any.downcast_ref::<ChunkShutdownAck>() {
@@ -2163,76 +1795,13 @@ impl RtxTimerObserver for AssociationInternal {
                 }
             }
 
-            RtxTimerId::T3RTX => {
-                self.stats.inc_t3timeouts();
-
-                // RFC 4960 sec 6.3.3
-                //  E1)  For the destination address for which the timer expires, adjust
-                //       its ssthresh with rules defined in Section 7.2.3 and set the
-                //       cwnd <- MTU.
-                // RFC 4960 sec 7.2.3
-                //   When the T3-rtx timer expires on an address, SCTP should perform slow
-                //   start by:
-                //      ssthresh = max(cwnd/2, 4*MTU)
-                //      cwnd = 1*MTU
-
-                self.ssthresh = std::cmp::max(self.cwnd / 2, 4 * self.mtu);
-                self.cwnd = self.mtu;
-                log::trace!(
-                    "[{}] updated cwnd={} ssthresh={} inflight={} (RTO)",
-                    self.name,
-                    self.cwnd,
-                    self.ssthresh,
-                    self.inflight_queue.get_num_bytes()
-                );
-
-                // RFC 3758 sec 3.5
-                //  A5) Any time the T3-rtx timer expires, on any destination, the sender
-                //  SHOULD try to advance the "Advanced.Peer.Ack.Point" by following
-                //  the procedures outlined in C2 - C5.
-                if self.use_forward_tsn {
-                    // RFC 3758 Sec 3.5 C2
-                    let mut i = self.advanced_peer_tsn_ack_point + 1;
-                    while let Some(c) = self.inflight_queue.get(i) {
-                        if !c.abandoned() {
-                            break;
-                        }
-                        self.advanced_peer_tsn_ack_point = i;
-                        i += 1;
-                    }
-
-                    // RFC 3758 Sec 3.5 C3
-                    if sna32gt(
-                        self.advanced_peer_tsn_ack_point,
-                        self.cumulative_tsn_ack_point,
-                    ) {
-                        self.will_send_forward_tsn = true;
-                        log::debug!(
-                            "[{}] on_retransmission_timeout {}: sna32GT({}, {})",
-                            self.name,
-                            self.will_send_forward_tsn,
-                            self.advanced_peer_tsn_ack_point,
-                            self.cumulative_tsn_ack_point
-                        );
-                    }
-                }
-
-                log::debug!(
-                    "[{}] T3-rtx timed out: n_rtos={} cwnd={} ssthresh={}",
-                    self.name,
-                    n_rtos,
-                    self.cwnd,
-                    self.ssthresh
-                );
-
-                self.inflight_queue.mark_all_to_retrasmit();
-                self.awake_write_loop();
-            }
-
             RtxTimerId::Reconfig => {
                 self.will_retransmit_reconfig = true;
                 self.awake_write_loop();
             }
+
+            // T3-rtx is AssociationInflight's
+            RtxTimerId::T3RTX => {}
         }
     }
 
@@ -2258,14 +1827,6 @@ impl RtxTimerObserver for AssociationInternal {
             RtxTimerId::T2Shutdown => {
                 log::error!("[{}] retransmission failure: T2-shutdown", self.name);
             }
-
-            RtxTimerId::T3RTX => {
-                // T3-rtx timer will not fail by design
-                // Justifications:
-                //  * ICE would fail if the connectivity is lost
-                //  * WebRTC spec is not clear how this incident should be reported to ULP
-                log::error!("[{}] retransmission failure: T3-rtx (DATA)", self.name);
-            }
             _ => {}
         }
     }
@@ -1,4 +1,4 @@
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 
 #[derive(Default, Debug)]
 pub(crate) struct AssociationStats {
@@ -7,6 +7,17 @@ pub(crate) struct AssociationStats {
     n_t3timeouts: AtomicU64,
     n_ack_timeouts: AtomicU64,
     n_fast_retrans: AtomicU64,
+    n_rwnd_drops: AtomicU64,
+    n_bad_verification_tags: AtomicU64,
+    n_invalid_cookies: AtomicU64,
+    n_out_of_order: AtomicU64,
+    max_reorder_distance: AtomicU32,
+    n_duplicate_tsns: AtomicU64,
+    n_skipped_tsns: AtomicU64,
+    n_packets_sent: AtomicU64,
+    n_bytes_sent: AtomicU64,
+    n_packets_received: AtomicU64,
+    n_bytes_received: AtomicU64,
 }
 
 impl AssociationStats {
@@ -49,4 +60,87 @@ impl AssociationStats {
     pub(crate) fn get_num_fast_retrans(&self) -> u64 {
         self.n_fast_retrans.load(Ordering::SeqCst)
     }
+
+    pub(crate) fn inc_rwnd_drops(&self) {
+        self.n_rwnd_drops.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub(crate) fn get_num_rwnd_drops(&self) -> u64 {
+        self.n_rwnd_drops.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn inc_bad_verification_tags(&self) {
+        self.n_bad_verification_tags.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub(crate) fn get_num_bad_verification_tags(&self) -> u64 {
+        self.n_bad_verification_tags.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn inc_invalid_cookies(&self) {
+        self.n_invalid_cookies.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub(crate) fn get_num_invalid_cookies(&self) -> u64 {
+        self.n_invalid_cookies.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn inc_out_of_order(&self, distance: u32) {
+        self.n_out_of_order.fetch_add(1, Ordering::SeqCst);
+        self.max_reorder_distance
+            .fetch_max(distance, Ordering::SeqCst);
+    }
+
+    pub(crate) fn get_num_out_of_order(&self) -> u64 {
+        self.n_out_of_order.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn get_max_reorder_distance(&self) -> u32 {
+        self.max_reorder_distance.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn inc_duplicate_tsns(&self) {
+        self.n_duplicate_tsns.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub(crate) fn get_num_duplicate_tsns(&self) -> u64 {
+        self.n_duplicate_tsns.load(Ordering::SeqCst)
+    }
+
+    /// inc_skipped_tsns counts a DATA chunk the peer abandoned before it arrived, i.e. a TSN a
+    /// FORWARD TSN moved the cumulative TSN past without it having been received
+    pub(crate) fn inc_skipped_tsns(&self) {
+        self.n_skipped_tsns.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub(crate) fn get_num_skipped_tsns(&self) -> u64 {
+        self.n_skipped_tsns.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn inc_packets_sent(&self, bytes: usize) {
+        self.n_packets_sent.fetch_add(1, Ordering::SeqCst);
+        self.n_bytes_sent.fetch_add(bytes as u64, Ordering::SeqCst);
+    }
+
+    pub(crate) fn get_num_packets_sent(&self) -> u64 {
+        self.n_packets_sent.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn get_num_bytes_sent(&self) -> u64 {
+        self.n_bytes_sent.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn inc_packets_received(&self, bytes: usize) {
+        self.n_packets_received.fetch_add(1, Ordering::SeqCst);
+        self.n_bytes_received
+            .fetch_add(bytes as u64, Ordering::SeqCst);
+    }
+
+    pub(crate) fn get_num_packets_received(&self) -> u64 {
+        self.n_packets_received.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn get_num_bytes_received(&self) -> u64 {
+        self.n_bytes_received.load(Ordering::SeqCst)
+    }
 }
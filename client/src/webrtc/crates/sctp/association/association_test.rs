@@ -0,0 +1,1144 @@
+use super::*;
+
+use std::collections::HashSet;
+use std::net::SocketAddr;
+
+use async_trait::async_trait;
+use rand::Rng;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, timeout};
+
+use crate::webrtc::data_channel::internal::data_channel::{
+    Config as DataChannelConfig, DataChannel,
+};
+use crate::webrtc::data_channel::internal::Error as DataChannelError;
+use crate::webrtc::sctp::chunk::chunk_type::{CT_I_DATA, CT_I_FORWARD_TSN};
+use crate::webrtc::util::Error as UtilError;
+
+/// STRESS_STREAMS is how many streams each end opens per kind of message, reliable and
+/// unreliable
+const STRESS_STREAMS: u16 = 4;
+/// STRESS_MESSAGES is how many messages each stream sends
+const STRESS_MESSAGES: u32 = 150;
+/// STRESS_LOSS_PERCENT is the share of packets the link drops once the association is up
+const STRESS_LOSS_PERCENT: u32 = 2;
+/// STRESS_TIMEOUT bounds the whole exchange, a stall fails the test instead of hanging it
+const STRESS_TIMEOUT: Duration = Duration::from_secs(120);
+/// REORDER_PERCENT is the share of packets a link holds back for REORDER_DELAY by default, so
+/// that the ones sent after them overtake them
+const REORDER_PERCENT: u32 = 10;
+const REORDER_DELAY: Duration = Duration::from_millis(3);
+
+/// Script is called with every packet sent on a link, it may rewrite the packet and drops it by
+/// returning true
+type Script = Box<dyn FnMut(&mut Packet) -> bool + Send>;
+
+/// Link is what both ends of a lossy_pipe share: the percentages of the packets they drop and
+/// hold back, and the script dropping packets by their content
+struct Link {
+    loss_percent: AtomicU32,
+    reorder_percent: AtomicU32,
+    script: std::sync::Mutex<Option<Script>>,
+}
+
+/// LossyConn is one end of an in-memory link that drops some of the packets sent on it and
+/// delays some of the others, as its Link says
+struct LossyConn {
+    rx: Mutex<mpsc::UnboundedReceiver<Vec<u8>>>,
+    tx: mpsc::UnboundedSender<Vec<u8>>,
+    link: Arc<Link>,
+}
+
+/// lossy_pipe returns the two ends of a LossyConn link, which starts out lossless and
+/// reordering REORDER_PERCENT of the packets
+fn lossy_pipe() -> (Arc<LossyConn>, Arc<LossyConn>, Arc<Link>) {
+    let (a_tx, a_rx) = mpsc::unbounded_channel();
+    let (b_tx, b_rx) = mpsc::unbounded_channel();
+    let link = Arc::new(Link {
+        loss_percent: AtomicU32::new(0),
+        reorder_percent: AtomicU32::new(REORDER_PERCENT),
+        script: std::sync::Mutex::new(None),
+    });
+
+    let a = LossyConn {
+        rx: Mutex::new(a_rx),
+        tx: b_tx,
+        link: Arc::clone(&link),
+    };
+    let b = LossyConn {
+        rx: Mutex::new(b_rx),
+        tx: a_tx,
+        link: Arc::clone(&link),
+    };
+    (Arc::new(a), Arc::new(b), link)
+}
+
+#[async_trait]
+impl Conn for LossyConn {
+    async fn connect(&self, _addr: SocketAddr) -> std::result::Result<(), UtilError> {
+        Ok(())
+    }
+
+    async fn recv(&self, buf: &mut [u8]) -> std::result::Result<usize, UtilError> {
+        let mut rx = self.rx.lock().await;
+        let packet = rx.recv().await.ok_or(UtilError::ErrUseClosedNetworkConn)?;
+        let n = std::cmp::min(packet.len(), buf.len());
+        buf[..n].copy_from_slice(&packet[..n]);
+        Ok(n)
+    }
+
+    async fn recv_from(
+        &self,
+        buf: &mut [u8],
+    ) -> std::result::Result<(usize, SocketAddr), UtilError> {
+        let n = self.recv(buf).await?;
+        Ok((n, SocketAddr::from(([0, 0, 0, 0], 0))))
+    }
+
+    async fn send(&self, buf: &[u8]) -> std::result::Result<usize, UtilError> {
+        let (lost, delayed) = {
+            let mut rng = rand::thread_rng();
+            (
+                rng.gen_range(0..100) < self.link.loss_percent.load(Ordering::SeqCst),
+                rng.gen_range(0..100) < self.link.reorder_percent.load(Ordering::SeqCst),
+            )
+        };
+        let mut packet = buf.to_vec();
+        if let Some(script) = self.link.script.lock().unwrap().as_mut() {
+            if let Ok(mut p) = Packet::unmarshal(&Bytes::copy_from_slice(buf)) {
+                if script(&mut p) {
+                    return Ok(buf.len());
+                }
+                packet = p.marshal().unwrap().to_vec();
+            }
+        }
+        if lost {
+            return Ok(buf.len());
+        }
+
+        if delayed {
+            let tx = self.tx.clone();
+            tokio::spawn(async move {
+                sleep(REORDER_DELAY).await;
+                let _ = tx.send(packet);
+            });
+        } else {
+            let _ = self.tx.send(packet);
+        }
+        Ok(buf.len())
+    }
+
+    async fn send_to(
+        &self,
+        buf: &[u8],
+        _target: SocketAddr,
+    ) -> std::result::Result<usize, UtilError> {
+        self.send(buf).await
+    }
+
+    async fn local_addr(&self) -> std::result::Result<SocketAddr, UtilError> {
+        Ok(SocketAddr::from(([0, 0, 0, 0], 0)))
+    }
+
+    async fn remote_addr(&self) -> Option<SocketAddr> {
+        None
+    }
+
+    async fn close(&self) -> std::result::Result<(), UtilError> {
+        Ok(())
+    }
+}
+
+fn config(net_conn: Arc<LossyConn>, name: &str) -> Config {
+    Config {
+        net_conn,
+        max_receive_buffer_size: 0,
+        initial_recv_window: 0,
+        max_num_inbound_streams: 0,
+        max_num_outbound_streams: 0,
+        remote_port: 0,
+        max_message_size: 0,
+        max_send_buffer_size: 0,
+        name: name.to_owned(),
+        bundling_delay: Duration::ZERO,
+        pacing: false,
+        pacing_gain: 0.0,
+        max_send_rate: None,
+        mtu: 0,
+        receive_mtu: 0,
+        strict_priority: false,
+        stats: Arc::default(),
+        send_errors: None,
+        event_log: Arc::default(),
+        transport_events: Arc::default(),
+        spawner: Spawner::default(),
+    }
+}
+
+/// associate connects a client and a server association over a lossless lossy_pipe and returns
+/// them with its Link
+async fn associate() -> (Association, Association, Arc<Link>) {
+    let (client_conn, server_conn, link) = lossy_pipe();
+    let (client, server) = associate_over(client_conn, server_conn).await;
+    (client, server, link)
+}
+
+/// associate_over connects a client and a server association over the ends of a lossy_pipe,
+/// whose script sees the handshake
+async fn associate_over(
+    client_conn: Arc<LossyConn>,
+    server_conn: Arc<LossyConn>,
+) -> (Association, Association) {
+    associate_configured(config(client_conn, "client"), config(server_conn, "server")).await
+}
+
+/// associate_configured connects a client and a server association created with the given
+/// configs
+async fn associate_configured(
+    client_config: Config,
+    server_config: Config,
+) -> (Association, Association) {
+    let (client, server) = tokio::join!(Association::client(client_config), async {
+        let (server, mut handshake_completed_ch_rx) =
+            Association::new(server_config, false).await?;
+        match handshake_completed_ch_rx.recv().await {
+            Some(None) => Ok(server),
+            Some(Some(err)) => Err(err),
+            None => Err(Error::ErrAssociationHandshakeClosed),
+        }
+    });
+    (client.unwrap(), server.unwrap())
+}
+
+/// without_interleaving removes I-DATA and I-FORWARD-TSN from the extensions an INIT or INIT
+/// ACK in p advertises, as a peer without RFC 8260 support sends it
+fn without_interleaving(p: &mut Packet) {
+    for c in &mut p.chunks {
+        let init = match c.as_any().downcast_ref::<ChunkInit>() {
+            Some(init) => init,
+            None => continue,
+        };
+        let params = init
+            .params
+            .iter()
+            .map(
+                |param| match param.as_any().downcast_ref::<ParamSupportedExtensions>() {
+                    Some(v) => Box::new(ParamSupportedExtensions {
+                        chunk_types: v
+                            .chunk_types
+                            .iter()
+                            .filter(|t| **t != CT_I_DATA && **t != CT_I_FORWARD_TSN)
+                            .cloned()
+                            .collect(),
+                    }),
+                    None => param.clone(),
+                },
+            )
+            .collect();
+        *c = Box::new(ChunkInit {
+            is_ack: init.is_ack,
+            initiate_tag: init.initiate_tag,
+            advertised_receiver_window_credit: init.advertised_receiver_window_credit,
+            num_outbound_streams: init.num_outbound_streams,
+            num_inbound_streams: init.num_inbound_streams,
+            initial_tsn: init.initial_tsn,
+            params,
+        });
+    }
+}
+
+/// message returns the message seq of stream_identifier: its number and stream, then a filler
+/// whose length varies so that some messages are fragmented
+fn message(stream_identifier: u16, seq: u32) -> Bytes {
+    let mut m = Vec::new();
+    m.extend_from_slice(&seq.to_be_bytes());
+    m.extend_from_slice(&stream_identifier.to_be_bytes());
+    m.resize(6 + (seq as usize * 997) % 3000, stream_identifier as u8);
+    Bytes::from(m)
+}
+
+/// Received is what the readers of one association got: the messages of each reliable stream in
+/// arrival order, and the numbers of the unreliable messages
+#[derive(Default)]
+struct Received {
+    reliable: std::sync::Mutex<HashMap<u16, Vec<u32>>>,
+    unreliable: std::sync::Mutex<HashSet<(u16, u32)>>,
+    unreliable_duplicates: AtomicU32,
+}
+
+/// send_all opens STRESS_STREAMS reliable and as many unreliable streams from first_stream on
+/// and sends messages messages on each of them concurrently
+async fn send_all(
+    a: &Association,
+    first_stream: u16,
+    messages: u32,
+) -> Vec<JoinHandle<Result<()>>> {
+    let mut writers = vec![];
+    for i in 0..2 * STRESS_STREAMS {
+        let stream_identifier = first_stream + i;
+        let s = a.open_stream(stream_identifier).await.unwrap();
+        // DCEP is the only payload sent reliably and in order, the rest is sent once, unordered
+        let ppi = if i < STRESS_STREAMS {
+            PayloadProtocolIdentifier::Dcep
+        } else {
+            PayloadProtocolIdentifier::Binary
+        };
+        writers.push(tokio::spawn(async move {
+            for seq in 0..messages {
+                match s.write_sctp(&message(stream_identifier, seq), ppi).await {
+                    Ok(_) | Err(Error::ErrSendBufferFull) => {}
+                    Err(err) => return Err(err),
+                }
+            }
+            Ok(())
+        }));
+    }
+    writers
+}
+
+/// receive_all reads the streams the peer opens on a until it is closed, into received
+fn receive_all(a: &Arc<Association>, received: &Arc<Received>) -> JoinHandle<()> {
+    let a = Arc::clone(a);
+    let received = Arc::clone(received);
+    tokio::spawn(async move {
+        while let Some(s) = a.accept_stream().await {
+            let received = Arc::clone(&received);
+            tokio::spawn(async move {
+                let mut buf = vec![0u8; 65536];
+                while let Ok((n, ppi)) = s.read_sctp(&mut buf).await {
+                    let seq = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]);
+                    let stream_identifier = u16::from_be_bytes([buf[4], buf[5]]);
+                    assert_eq!(stream_identifier, s.stream_identifier);
+                    assert_eq!(&buf[..n], &message(stream_identifier, seq)[..]);
+
+                    if ppi == PayloadProtocolIdentifier::Dcep {
+                        let mut reliable = received.reliable.lock().unwrap();
+                        reliable.entry(stream_identifier).or_default().push(seq);
+                    } else if !received
+                        .unreliable
+                        .lock()
+                        .unwrap()
+                        .insert((stream_identifier, seq))
+                    {
+                        received
+                            .unreliable_duplicates
+                            .fetch_add(1, Ordering::SeqCst);
+                    }
+                }
+            });
+        }
+    })
+}
+
+/// reliable_complete reports whether every reliable stream of the peer, from first_stream on,
+/// delivered all messages
+fn reliable_complete(received: &Received, first_stream: u16, messages: u32) -> bool {
+    let reliable = received.reliable.lock().unwrap();
+    (first_stream..first_stream + STRESS_STREAMS)
+        .all(|si| reliable.get(&si).map(Vec::len) == Some(messages as usize))
+}
+
+/// drained reports whether a has nothing queued or in flight anymore
+async fn drained(a: &Association) -> bool {
+    let ai = a.association_internal.lock().await;
+    let inflight = ai.inflight.lock().await;
+    inflight.is_empty()
+        && ai.send_buffer.outstanding_bytes() == 0
+        && ai.send_buffer.pending_bytes() == 0
+}
+
+/// exchange has both ends of a fresh association send messages messages on each of their
+/// streams at the same time, over a link dropping loss_percent of the packets. It returns what
+/// each end received once all reliable messages arrived, and how long that took.
+async fn exchange(
+    loss_percent: u32,
+    messages: u32,
+) -> (
+    Arc<Association>,
+    Arc<Association>,
+    Arc<Received>,
+    Arc<Received>,
+    Duration,
+) {
+    let (client, server, link) = associate().await;
+    let (client, server) = (Arc::new(client), Arc::new(server));
+    link.loss_percent.store(loss_percent, Ordering::SeqCst);
+
+    let (client_received, server_received) =
+        (Arc::new(Received::default()), Arc::new(Received::default()));
+    receive_all(&client, &client_received);
+    receive_all(&server, &server_received);
+
+    // the server opens its streams above the client's
+    let server_first_stream = 2 * STRESS_STREAMS;
+    let start = Instant::now();
+    let mut writers = send_all(&client, 0, messages).await;
+    writers.append(&mut send_all(&server, server_first_stream, messages).await);
+
+    timeout(STRESS_TIMEOUT, async {
+        for writer in writers {
+            writer.await.unwrap().unwrap();
+        }
+        while !reliable_complete(&server_received, 0, messages)
+            || !reliable_complete(&client_received, server_first_stream, messages)
+        {
+            sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("the exchange stalled");
+
+    (
+        client,
+        server,
+        client_received,
+        server_received,
+        start.elapsed(),
+    )
+}
+
+/// concurrent_send_receive_under_loss guards the split of the association into the association
+/// lock and the in-flight lock: SACKs processed beside inbound DATA, T3-rtx timeouts and fast
+/// retransmissions must neither lose, duplicate or reorder a reliable message nor leave data in
+/// flight.
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_send_receive_under_loss() {
+    let (client, server, client_received, server_received, _) =
+        exchange(STRESS_LOSS_PERCENT, STRESS_MESSAGES).await;
+
+    for (received, first_stream) in [
+        (&server_received, 0),
+        (&client_received, 2 * STRESS_STREAMS),
+    ] {
+        let reliable = received.reliable.lock().unwrap();
+        for si in first_stream..first_stream + STRESS_STREAMS {
+            assert_eq!(
+                reliable[&si],
+                (0..STRESS_MESSAGES).collect::<Vec<u32>>(),
+                "stream {}",
+                si
+            );
+        }
+        assert_eq!(received.unreliable_duplicates.load(Ordering::SeqCst), 0);
+    }
+
+    timeout(STRESS_TIMEOUT, async {
+        while !drained(&client).await || !drained(&server).await {
+            sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("data left in flight");
+
+    client.close().await.unwrap();
+    server.close().await.unwrap();
+}
+
+/// ScriptedLoss is what the script of forward_tsn_skips_only_abandoned saw: the message of
+/// each TSN, the TSNs it dropped and let pass, and the streams FORWARD TSN chunks skipped
+#[derive(Default)]
+struct ScriptedLoss {
+    messages: HashMap<u32, (u16, u32)>,
+    sending: HashMap<u16, u32>,
+    dropped: HashSet<u32>,
+    passed: HashSet<u32>,
+    forward_tsns: usize,
+    skipped: Vec<(u16, bool)>,
+}
+
+impl ScriptedLoss {
+    /// lost returns the messages of stream_identifier some chunk of which never got through
+    fn lost(&self, stream_identifier: u16) -> HashSet<u32> {
+        self.dropped
+            .difference(&self.passed)
+            .map(|tsn| self.messages[tsn])
+            .filter(|&(si, _)| si == stream_identifier)
+            .map(|(_, seq)| seq)
+            .collect()
+    }
+}
+
+/// forward_tsn_skips_only_abandoned interleaves a reliable ordered stream with an unreliable
+/// one and drops packets by script: the first transmission of every chunk of each third
+/// unreliable message and of each fifth reliable message, with whatever is bundled with it.
+/// The FORWARD TSNs skipping the abandoned unreliable messages must leave the reliable stream
+/// alone: it delivers every message exactly once and in order, the unreliable stream exactly
+/// the messages whose chunks all got through.
+async fn forward_tsn_skips_only_abandoned(interleaving: bool) {
+    const RELIABLE: u16 = 0;
+    const UNRELIABLE: u16 = 1;
+    const MESSAGES: u32 = 60;
+
+    let (client_conn, server_conn, link) = lossy_pipe();
+    link.reorder_percent.store(0, Ordering::SeqCst);
+    let scripted = Arc::new(std::sync::Mutex::new(ScriptedLoss::default()));
+    let seen = Arc::clone(&scripted);
+    *link.script.lock().unwrap() = Some(Box::new(move |p: &mut Packet| {
+        if !interleaving {
+            without_interleaving(p);
+        }
+
+        let mut seen = seen.lock().unwrap();
+        let mut drop = false;
+        let mut tsns = vec![];
+        for c in &p.chunks {
+            if let Some(fwd) = c.as_any().downcast_ref::<ChunkForwardTsn>() {
+                seen.forward_tsns += 1;
+                let skipped = fwd.streams.iter().map(|s| (s.identifier, true));
+                seen.skipped.extend(skipped);
+            } else if let Some(fwd) = c.as_any().downcast_ref::<ChunkIForwardTsn>() {
+                seen.forward_tsns += 1;
+                let skipped = fwd.streams.iter().map(|s| (s.identifier, !s.unordered));
+                seen.skipped.extend(skipped);
+            }
+            let c = match c.as_any().downcast_ref::<ChunkPayloadData>() {
+                Some(c) => c,
+                None => continue,
+            };
+
+            // only the first fragment starts with the message number, the others are sent
+            // after it on the same stream
+            let message = match seen.messages.get(&c.tsn) {
+                Some(&message) => message,
+                None => {
+                    if c.beginning_fragment {
+                        let seq = u32::from_be_bytes([
+                            c.user_data[0],
+                            c.user_data[1],
+                            c.user_data[2],
+                            c.user_data[3],
+                        ]);
+                        seen.sending.insert(c.stream_identifier, seq);
+                    }
+                    let message = (c.stream_identifier, seen.sending[&c.stream_identifier]);
+                    seen.messages.insert(c.tsn, message);
+                    message
+                }
+            };
+            let nth = if message.0 == UNRELIABLE { 3 } else { 5 };
+            drop |= message.1 % nth == 2 && !seen.dropped.contains(&c.tsn);
+            tsns.push(c.tsn);
+        }
+
+        for tsn in tsns {
+            if drop {
+                seen.dropped.insert(tsn);
+            } else {
+                seen.passed.insert(tsn);
+            }
+        }
+        drop
+    }));
+    let (client, server) = associate_over(client_conn, server_conn).await;
+    let (client, server) = (Arc::new(client), Arc::new(server));
+    assert_eq!(
+        client.association_internal.lock().await.use_interleaving,
+        interleaving
+    );
+
+    let received = Arc::new(Received::default());
+    receive_all(&server, &received);
+
+    let reliable = client.open_stream(RELIABLE).await.unwrap();
+    let unreliable = client.open_stream(UNRELIABLE).await.unwrap();
+    for seq in 0..MESSAGES {
+        reliable
+            .write_sctp(&message(RELIABLE, seq), PayloadProtocolIdentifier::Dcep)
+            .await
+            .unwrap();
+        unreliable
+            .write_sctp(&message(UNRELIABLE, seq), PayloadProtocolIdentifier::Binary)
+            .await
+            .unwrap();
+    }
+
+    let reliable_received = || {
+        let reliable = received.reliable.lock().unwrap();
+        reliable.get(&RELIABLE).map_or(0, Vec::len)
+    };
+    timeout(STRESS_TIMEOUT, async {
+        while reliable_received() < MESSAGES as usize || !drained(&client).await {
+            sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("the reliable stream stalled");
+
+    assert_eq!(
+        received.reliable.lock().unwrap()[&RELIABLE],
+        (0..MESSAGES).collect::<Vec<u32>>()
+    );
+
+    let scripted = scripted.lock().unwrap();
+    assert!(scripted.lost(RELIABLE).is_empty());
+    let lost = scripted.lost(UNRELIABLE);
+    assert!(!lost.is_empty());
+    let expected: Vec<u32> = (0..MESSAGES).filter(|seq| !lost.contains(seq)).collect();
+    // the messages are acknowledged once they are queued, the reader may not have them yet
+    timeout(STRESS_TIMEOUT, async {
+        while received.unreliable.lock().unwrap().len() < expected.len() {
+            sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("unreliable messages missing");
+
+    let mut delivered: Vec<u32> = received
+        .unreliable
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|&(_, seq)| seq)
+        .collect();
+    delivered.sort_unstable();
+    assert_eq!(delivered, expected);
+    assert_eq!(received.unreliable_duplicates.load(Ordering::SeqCst), 0);
+
+    // the abandoned messages are all unordered, no ordered one may be skipped
+    assert!(scripted.forward_tsns > 0);
+    assert!(scripted.skipped.iter().all(|&(_, ordered)| !ordered));
+    drop(scripted);
+
+    client.close().await.unwrap();
+    server.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn forward_tsn_skips_only_abandoned_messages() {
+    forward_tsn_skips_only_abandoned(false).await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn i_forward_tsn_skips_only_abandoned_messages() {
+    forward_tsn_skips_only_abandoned(true).await;
+}
+
+/// sent_payload_chunks connects a client and a server association whose link strips I-DATA
+/// from the handshake unless interleaving is set, and records the stream, the interleaved flag
+/// and the ending fragment flag of every DATA or I-DATA chunk sent on it
+async fn sent_payload_chunks(
+    interleaving: bool,
+) -> (
+    Association,
+    Association,
+    Arc<std::sync::Mutex<Vec<(u16, bool, bool)>>>,
+) {
+    let (client_conn, server_conn, link) = lossy_pipe();
+    link.reorder_percent.store(0, Ordering::SeqCst);
+    let sent = Arc::new(std::sync::Mutex::new(vec![]));
+    let seen = Arc::clone(&sent);
+    *link.script.lock().unwrap() = Some(Box::new(move |p: &mut Packet| {
+        if !interleaving {
+            without_interleaving(p);
+        }
+        let mut seen = seen.lock().unwrap();
+        for c in &p.chunks {
+            if let Some(c) = c.as_any().downcast_ref::<ChunkPayloadData>() {
+                seen.push((c.stream_identifier, c.interleaved, c.ending_fragment));
+            }
+        }
+        false
+    }));
+
+    let (client, server) = associate_over(client_conn, server_conn).await;
+    (client, server, sent)
+}
+
+/// payload_chunks_follow_negotiation checks that I-DATA is used only when the peer advertises
+/// it, RFC 8260 Section 2.2, and that a peer without it gets DATA chunks on both ends
+async fn payload_chunks_follow_negotiation(interleaving: bool) {
+    let (client, server, sent) = sent_payload_chunks(interleaving).await;
+    assert_eq!(
+        client.association_internal.lock().await.use_interleaving,
+        interleaving
+    );
+    assert_eq!(
+        server.association_internal.lock().await.use_interleaving,
+        interleaving
+    );
+
+    assert_data_flows(&client, &server, 1).await;
+    assert_data_flows(&server, &client, 2).await;
+
+    let sent = sent.lock().unwrap().clone();
+    assert!(sent.iter().any(|&(si, _, _)| si == 1));
+    assert!(sent.iter().any(|&(si, _, _)| si == 2));
+    assert!(sent
+        .iter()
+        .all(|&(_, interleaved, _)| interleaved == interleaving));
+
+    client.close().await.unwrap();
+    server.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn i_data_is_negotiated() {
+    payload_chunks_follow_negotiation(true).await;
+}
+
+#[tokio::test]
+async fn data_is_used_without_peer_i_data_support() {
+    payload_chunks_follow_negotiation(false).await;
+}
+
+/// large_message_interleaves_with_other_streams sends a large message on one stream, then a
+/// small one on another. With I-DATA the small message is sent between the fragments of the
+/// large one, RFC 8260 Section 1, with DATA only after all of them. Both arrive intact either
+/// way.
+async fn large_message_interleaves_with_other_streams(interleaving: bool) {
+    const LARGE: u16 = 1;
+    const SMALL: u16 = 2;
+
+    let (client, server, sent) = sent_payload_chunks(interleaving).await;
+    let large = Bytes::from((0..60_000).map(|i| i as u8).collect::<Vec<u8>>());
+    let small = Bytes::from_static(b"small");
+
+    let large_stream = client.open_stream(LARGE).await.unwrap();
+    let small_stream = client.open_stream(SMALL).await.unwrap();
+    large_stream
+        .write_sctp(&large, PayloadProtocolIdentifier::Dcep)
+        .await
+        .unwrap();
+    small_stream
+        .write_sctp(&small, PayloadProtocolIdentifier::Dcep)
+        .await
+        .unwrap();
+
+    let mut buf = vec![0u8; 65536];
+    timeout(Duration::from_secs(5), async {
+        for _ in 0..2 {
+            let s = server.accept_stream().await.unwrap();
+            let n = s.read_sctp(&mut buf).await.unwrap().0;
+            let want = if s.stream_identifier == LARGE {
+                &large
+            } else {
+                &small
+            };
+            assert_eq!(&buf[..n], &want[..]);
+        }
+    })
+    .await
+    .expect("the messages never arrived");
+
+    let sent = sent.lock().unwrap().clone();
+    let large_fragments = sent.iter().filter(|&&(si, _, _)| si == LARGE).count();
+    assert!(large_fragments > 10, "{} fragments", large_fragments);
+    let small_at = sent.iter().position(|&(si, _, _)| si == SMALL).unwrap();
+    let large_ended_at = sent
+        .iter()
+        .position(|&(si, _, ending)| si == LARGE && ending)
+        .unwrap();
+    assert_eq!(small_at < large_ended_at, interleaving);
+
+    client.close().await.unwrap();
+    server.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn i_data_interleaves_messages_of_other_streams() {
+    large_message_interleaves_with_other_streams(true).await;
+}
+
+#[tokio::test]
+async fn data_sends_messages_of_other_streams_after() {
+    large_message_interleaves_with_other_streams(false).await;
+}
+
+/// oversized_message_keeps_the_channel_open checks that a message larger than the read buffer
+/// is dropped with ErrShortBuffer while the data channel goes on delivering the next ones
+#[tokio::test]
+async fn oversized_message_keeps_the_channel_open() {
+    // a fragment of an unreliable message held back past the FORWARD-TSN skipping it is
+    // discarded, the link must keep the order for the message to arrive
+    let (client, server, link) = associate().await;
+    link.reorder_percent.store(0, Ordering::SeqCst);
+    let (client, server) = (Arc::new(client), Arc::new(server));
+
+    let (dialed, accepted) = tokio::join!(
+        DataChannel::dial(&client, 1, DataChannelConfig::default()),
+        DataChannel::accept(&server)
+    );
+    let (dialed, accepted) = (dialed.unwrap(), accepted.unwrap());
+
+    dialed.write(&Bytes::from(vec![1u8; 2000])).await.unwrap();
+    dialed.write(&Bytes::from_static(b"after")).await.unwrap();
+
+    let mut buf = vec![0u8; 1500];
+    assert!(matches!(
+        accepted.read(&mut buf).await,
+        Err(DataChannelError::Sctp(Error::ErrShortBuffer))
+    ));
+    let n = accepted.read(&mut buf).await.unwrap();
+    assert_eq!(&buf[..n], b"after");
+    assert!(!accepted.is_closed());
+
+    client.close().await.unwrap();
+    server.close().await.unwrap();
+}
+
+/// Handshake is what a Script saw of the handshake on a link: the cookie of the COOKIE ECHO and
+/// how many COOKIE ACKs were sent
+#[derive(Default)]
+struct Handshake {
+    cookie_echo: Option<Bytes>,
+    cookie_acks: u32,
+}
+
+/// associate_watched connects a client and a server association like associate and returns the
+/// client end of the link, to inject packets to the server past the Link, with the handshake
+/// chunks on the link watched into a Handshake
+async fn associate_watched() -> (
+    Association,
+    Association,
+    Arc<LossyConn>,
+    Arc<std::sync::Mutex<Handshake>>,
+) {
+    let (client_conn, server_conn, link) = lossy_pipe();
+    link.reorder_percent.store(0, Ordering::SeqCst);
+    let handshake = Arc::new(std::sync::Mutex::new(Handshake::default()));
+    let watched = Arc::clone(&handshake);
+    *link.script.lock().unwrap() = Some(Box::new(move |p: &mut Packet| {
+        let mut handshake = watched.lock().unwrap();
+        for c in &p.chunks {
+            let c = c.as_any();
+            if let Some(cookie_echo) = c.downcast_ref::<ChunkCookieEcho>() {
+                handshake.cookie_echo = Some(cookie_echo.cookie.clone());
+            } else if c.downcast_ref::<ChunkCookieAck>().is_some() {
+                handshake.cookie_acks += 1;
+            }
+        }
+        false
+    }));
+
+    let (client, server) = associate_over(Arc::clone(&client_conn), server_conn).await;
+    (client, server, client_conn, handshake)
+}
+
+/// inject hands a packet with chunks and verification_tag to the association at the other end
+/// of conn
+fn inject(conn: &LossyConn, verification_tag: u32, chunks: Vec<Box<dyn Chunk + Send + Sync>>) {
+    let p = Packet {
+        verification_tag,
+        source_port: DEFAULT_SCTP_PORT,
+        destination_port: DEFAULT_SCTP_PORT,
+        chunks,
+    };
+    conn.tx.send(p.marshal().unwrap().to_vec()).unwrap();
+}
+
+/// wait_for polls condition until it holds, failing the test after a few seconds
+async fn wait_for<F: FnMut() -> bool>(mut condition: F) {
+    timeout(Duration::from_secs(5), async {
+        while !condition() {
+            sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .expect("the condition never held");
+}
+
+async fn verification_tags(a: &Association) -> (u32, u32, AssociationState) {
+    let ai = a.association_internal.lock().await;
+    (
+        ai.my_verification_tag,
+        ai.peer_verification_tag,
+        ai.state.load(Ordering::SeqCst).into(),
+    )
+}
+
+/// assert_data_flows has client send a reliable message on stream_identifier and waits for
+/// server to deliver it
+async fn assert_data_flows(client: &Association, server: &Association, stream_identifier: u16) {
+    let s = client.open_stream(stream_identifier).await.unwrap();
+    s.write_sctp(
+        &message(stream_identifier, 0),
+        PayloadProtocolIdentifier::Dcep,
+    )
+    .await
+    .unwrap();
+
+    let mut buf = vec![0u8; 65536];
+    let n = timeout(Duration::from_secs(5), async {
+        let s = server.accept_stream().await.unwrap();
+        assert_eq!(s.stream_identifier, stream_identifier);
+        s.read_sctp(&mut buf).await.unwrap().0
+    })
+    .await
+    .expect("the message never arrived");
+    assert_eq!(&buf[..n], &message(stream_identifier, 0)[..]);
+}
+
+/// forged_cookie_echo_is_discarded checks that a COOKIE ECHO whose cookie was not signed for
+/// the association is counted and dropped without an answer or a change to the association
+#[tokio::test]
+async fn forged_cookie_echo_is_discarded() {
+    let (client, server, client_conn, handshake) = associate_watched().await;
+    let before = verification_tags(&server).await;
+    let stats = Arc::clone(&server.association_internal.lock().await.stats);
+    let (my_verification_tag, peer_verification_tag, _) = before;
+
+    let mut forged = vec![0u8; 48];
+    rand::thread_rng().fill(&mut forged[..]);
+    let cookies = [
+        Bytes::from(forged),
+        // signed by us, for another association
+        ParamStateCookie::new(my_verification_tag, peer_verification_tag ^ 1).cookie,
+    ];
+    for cookie in cookies {
+        inject(
+            &client_conn,
+            my_verification_tag,
+            vec![Box::new(ChunkCookieEcho { cookie })],
+        );
+    }
+    wait_for(|| stats.get_num_invalid_cookies() == 2).await;
+
+    assert_eq!(verification_tags(&server).await, before);
+    assert_eq!(before.2, AssociationState::Established);
+    assert_eq!(
+        handshake.lock().unwrap().cookie_acks,
+        1,
+        "only the handshake"
+    );
+    assert_eq!(stats.get_num_bad_verification_tags(), 0);
+    assert_data_flows(&client, &server, 1).await;
+
+    client.close().await.unwrap();
+    server.close().await.unwrap();
+}
+
+/// replayed_cookie_echo_is_harmless checks that the COOKIE ECHO of the handshake sent again
+/// once the association is up is only answered, RFC 4960 Section 5.2.4 case D
+#[tokio::test]
+async fn replayed_cookie_echo_is_harmless() {
+    let (client, server, client_conn, handshake) = associate_watched().await;
+    let before = verification_tags(&server).await;
+    let stats = Arc::clone(&server.association_internal.lock().await.stats);
+    let cookie = handshake.lock().unwrap().cookie_echo.clone().unwrap();
+
+    inject(
+        &client_conn,
+        before.0,
+        vec![Box::new(ChunkCookieEcho { cookie })],
+    );
+    wait_for(|| handshake.lock().unwrap().cookie_acks == 2).await;
+
+    assert_eq!(verification_tags(&server).await, before);
+    assert_eq!(
+        verification_tags(&client).await.2,
+        AssociationState::Established
+    );
+    assert_eq!(stats.get_num_invalid_cookies(), 0);
+    assert_eq!(server.close_reason().await, None);
+    assert_data_flows(&client, &server, 1).await;
+
+    client.close().await.unwrap();
+    server.close().await.unwrap();
+}
+
+/// wrong_verification_tag_is_discarded checks that packets carrying neither the tag of the
+/// association nor a reflected one with the T bit set are counted and dropped, RFC 4960 Section
+/// 8.5, and that an ABORT reflecting the peer's tag is only accepted with the T bit
+#[tokio::test]
+async fn wrong_verification_tag_is_discarded() {
+    let (client, server, client_conn, handshake) = associate_watched().await;
+    let before = verification_tags(&server).await;
+    let (my_verification_tag, peer_verification_tag, _) = before;
+    let (stats, consecutive_bad_verification_tags) = {
+        let ai = server.association_internal.lock().await;
+        (
+            Arc::clone(&ai.stats),
+            Arc::clone(&ai.consecutive_bad_verification_tags),
+        )
+    };
+    let cookie = handshake.lock().unwrap().cookie_echo.clone().unwrap();
+
+    // a COOKIE ECHO with the right tag is answered, see replayed_cookie_echo_is_harmless
+    inject(
+        &client_conn,
+        my_verification_tag.wrapping_add(1),
+        vec![Box::new(ChunkCookieEcho {
+            cookie: cookie.clone(),
+        })],
+    );
+    inject(
+        &client_conn,
+        peer_verification_tag,
+        vec![Box::new(ChunkAbort::default())],
+    );
+    inject(
+        &client_conn,
+        peer_verification_tag,
+        vec![Box::new(ChunkShutdownComplete::default())],
+    );
+    wait_for(|| stats.get_num_bad_verification_tags() == 3).await;
+
+    assert_eq!(consecutive_bad_verification_tags.load(Ordering::SeqCst), 3);
+    assert_eq!(verification_tags(&server).await, before);
+    assert_eq!(server.close_reason().await, None);
+    assert_eq!(
+        handshake.lock().unwrap().cookie_acks,
+        1,
+        "only the handshake"
+    );
+
+    inject(
+        &client_conn,
+        my_verification_tag,
+        vec![Box::new(ChunkCookieEcho { cookie })],
+    );
+    wait_for(|| handshake.lock().unwrap().cookie_acks == 2).await;
+    assert_eq!(consecutive_bad_verification_tags.load(Ordering::SeqCst), 0);
+    assert_data_flows(&client, &server, 1).await;
+
+    inject(
+        &client_conn,
+        peer_verification_tag,
+        vec![Box::new(ChunkAbort {
+            reflected_tag: true,
+            error_causes: vec![],
+        })],
+    );
+    timeout(Duration::from_secs(5), async {
+        while server.close_reason().await.is_none() {
+            sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .expect("the ABORT was not accepted");
+    assert_eq!(
+        server.close_reason().await,
+        Some(CloseReason::TransportFailure(TransportError::Aborted))
+    );
+    assert_eq!(stats.get_num_bad_verification_tags(), 3);
+
+    client.close().await.unwrap();
+}
+
+/// SLOW_CONSUMER_WINDOW is the receive buffer of the server in
+/// slow_consumer_bounds_the_receive_buffer
+const SLOW_CONSUMER_WINDOW: u32 = 16 * 1024;
+/// SLOW_CONSUMER_MESSAGES is how many messages the client sends it, many times its window
+const SLOW_CONSUMER_MESSAGES: u32 = 200;
+
+/// slow_consumer_bounds_the_receive_buffer has the client send reliable messages as fast as it
+/// can to a server that does not read them. The server must queue no more than its receive
+/// buffer, dropping the zero window probes, and advertise the window it frees once it is read
+/// from: the reads deliver every message in order without waiting for retransmission timers.
+#[tokio::test]
+async fn slow_consumer_bounds_the_receive_buffer() {
+    let (client_conn, server_conn, link) = lossy_pipe();
+    link.reorder_percent.store(0, Ordering::SeqCst);
+    let mut server_config = config(server_conn, "server");
+    server_config.max_receive_buffer_size = SLOW_CONSUMER_WINDOW;
+    let (client, server) = associate_configured(config(client_conn, "client"), server_config).await;
+    let (client_stats, server_stats, rwnd_reduced) = {
+        let (client_ai, server_ai) = (
+            client.association_internal.lock().await,
+            server.association_internal.lock().await,
+        );
+        (
+            Arc::clone(&client_ai.stats),
+            Arc::clone(&server_ai.stats),
+            Arc::clone(&server_ai.rwnd_reduced),
+        )
+    };
+
+    let s = client.open_stream(1).await.unwrap();
+    let writer = tokio::spawn(async move {
+        for seq in 0..SLOW_CONSUMER_MESSAGES {
+            s.write_sctp(&message(1, seq), PayloadProtocolIdentifier::Dcep)
+                .await
+                .unwrap();
+        }
+    });
+
+    let accepted = timeout(Duration::from_secs(5), server.accept_stream())
+        .await
+        .expect("the stream was never accepted")
+        .unwrap();
+    timeout(Duration::from_secs(5), async {
+        while server_stats.get_num_rwnd_drops() == 0 {
+            let queued = accepted.get_num_bytes_in_reassembly_queue().await;
+            assert!(
+                queued <= (SLOW_CONSUMER_WINDOW + INITIAL_MTU) as usize,
+                "{} bytes queued",
+                queued
+            );
+            sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .expect("the window never filled");
+    assert!(rwnd_reduced.load(Ordering::SeqCst));
+    assert!(!drained(&client).await, "the client kept sending");
+
+    let t3_timeouts = client_stats.get_num_t3timeouts();
+    let mut buf = vec![0u8; 65536];
+    timeout(Duration::from_secs(5), async {
+        for seq in 0..SLOW_CONSUMER_MESSAGES {
+            let (n, _) = accepted.read_sctp(&mut buf).await.unwrap();
+            assert_eq!(&buf[..n], &message(1, seq)[..]);
+            assert!(
+                accepted.get_num_bytes_in_reassembly_queue().await
+                    <= (SLOW_CONSUMER_WINDOW + INITIAL_MTU) as usize
+            );
+        }
+    })
+    .await
+    .expect("the messages stopped arriving");
+    writer.await.unwrap();
+    // the zero window probe dropped before the reads may time out once, the window updates
+    // keep the sender from waiting for its timer after every refill
+    assert!(client_stats.get_num_t3timeouts() <= t3_timeouts + 1);
+
+    timeout(Duration::from_secs(5), async {
+        while !drained(&client).await {
+            sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .expect("data left in flight");
+    assert_eq!(
+        server
+            .association_internal
+            .lock()
+            .await
+            .get_my_receiver_window_credit()
+            .await,
+        SLOW_CONSUMER_WINDOW
+    );
+
+    client.close().await.unwrap();
+    server.close().await.unwrap();
+}
+
+/// concurrent_send_receive_throughput logs the throughput of both ends sending reliable and
+/// unreliable messages at the same time over a lossless link. It measures, it does not check,
+/// run it with `RUST_LOG=info cargo test --release -- --ignored --nocapture`.
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[ignore]
+async fn concurrent_send_receive_throughput() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let messages = 2000;
+    let (client, server, client_received, server_received, elapsed) = exchange(0, messages).await;
+
+    let bytes: usize = (0..messages)
+        .map(|seq| message(0, seq).len())
+        .sum::<usize>()
+        * 2
+        * STRESS_STREAMS as usize;
+    let unreliable = client_received.unreliable.lock().unwrap().len()
+        + server_received.unreliable.lock().unwrap().len();
+    log::info!(
+        "{} reliable bytes each way in {:?}, {:.1} MB/s, {} of {} unreliable messages received",
+        bytes / 2,
+        elapsed,
+        bytes as f64 / elapsed.as_secs_f64() / 1e6,
+        unreliable,
+        2 * STRESS_STREAMS as u32 * messages
+    );
+
+    client.close().await.unwrap();
+    server.close().await.unwrap();
+}
@@ -1,13 +1,22 @@
+mod association_inflight;
 mod association_internal;
-mod association_stats;
+pub(crate) mod association_stats;
+mod pacer;
+mod stream_map;
 
+#[cfg(test)]
+mod association_test;
+
+use crate::webrtc::peer_connection::event_log::{ConnectionEventKind, EventLog};
 use crate::webrtc::sctp::chunk::chunk_abort::ChunkAbort;
 use crate::webrtc::sctp::chunk::chunk_cookie_ack::ChunkCookieAck;
 use crate::webrtc::sctp::chunk::chunk_cookie_echo::ChunkCookieEcho;
 use crate::webrtc::sctp::chunk::chunk_error::ChunkError;
 use crate::webrtc::sctp::chunk::chunk_forward_tsn::{ChunkForwardTsn, ChunkForwardTsnStream};
+use crate::webrtc::sctp::chunk::chunk_header::CHUNK_HEADER_SIZE;
 use crate::webrtc::sctp::chunk::chunk_heartbeat::ChunkHeartbeat;
 use crate::webrtc::sctp::chunk::chunk_heartbeat_ack::ChunkHeartbeatAck;
+use crate::webrtc::sctp::chunk::chunk_i_forward_tsn::{ChunkIForwardTsn, ChunkIForwardTsnStream};
 use crate::webrtc::sctp::chunk::chunk_init::ChunkInit;
 use crate::webrtc::sctp::chunk::chunk_payload_data::{ChunkPayloadData, PayloadProtocolIdentifier};
 use crate::webrtc::sctp::chunk::chunk_reconfig::ChunkReconfig;
@@ -19,7 +28,7 @@ use crate::webrtc::sctp::chunk::chunk_type::*;
 use crate::webrtc::sctp::chunk::Chunk;
 use crate::webrtc::sctp::error::{Error, Result};
 use crate::webrtc::sctp::error_cause::*;
-use crate::webrtc::sctp::packet::Packet;
+use crate::webrtc::sctp::packet::{Packet, PACKET_HEADER_SIZE};
 use crate::webrtc::sctp::param::param_heartbeat_info::ParamHeartbeatInfo;
 use crate::webrtc::sctp::param::param_outgoing_reset_request::ParamOutgoingResetRequest;
 use crate::webrtc::sctp::param::param_reconfig_response::{ParamReconfigResponse, ReconfigResult};
@@ -29,23 +38,32 @@ use crate::webrtc::sctp::param::Param;
 use crate::webrtc::sctp::queue::control_queue::ControlQueue;
 use crate::webrtc::sctp::queue::payload_queue::PayloadQueue;
 use crate::webrtc::sctp::queue::pending_queue::PendingQueue;
+use crate::webrtc::sctp::queue::send_buffer::SendBuffer;
 use crate::webrtc::sctp::stream::*;
 use crate::webrtc::sctp::timer::ack_timer::*;
 use crate::webrtc::sctp::timer::rtx_timer::*;
 use crate::webrtc::sctp::util::*;
+use crate::webrtc::sctp_transport::transport_events::TransportEvents;
 
+use association_inflight::*;
 use association_internal::*;
 use association_stats::*;
+use pacer::*;
+use stream_map::*;
 
+use crate::webrtc::util::conn::send_error::SendErrorStats;
+use crate::webrtc::util::spawner::Spawner;
 use crate::webrtc::util::Conn;
 use bytes::Bytes;
 use rand::random;
+use serde::Serialize;
 use std::collections::{HashMap, VecDeque};
 use std::fmt;
-use std::sync::atomic::{AtomicU32, AtomicU8, AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicU8, AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::time::SystemTime;
+use std::time::Duration;
 use tokio::sync::{broadcast, mpsc, Mutex};
+use tokio::time::Instant;
 
 pub(crate) const RECEIVE_MTU: usize = 8192;
 /// MTU for inbound packet (from DTLS)
@@ -54,14 +72,31 @@ pub(crate) const INITIAL_MTU: u32 = 1228;
 pub(crate) const INITIAL_RECV_BUF_SIZE: u32 = 1024 * 1024;
 pub(crate) const COMMON_HEADER_SIZE: u32 = 12;
 pub(crate) const DATA_CHUNK_HEADER_SIZE: u32 = 16;
+pub(crate) const I_DATA_CHUNK_HEADER_SIZE: u32 = 20;
 pub(crate) const DEFAULT_MAX_MESSAGE_SIZE: u32 = 65536;
+/// bytes of user data queued and in flight an association holds at most, see SendBuffer
+pub(crate) const DEFAULT_MAX_SEND_BUFFER_SIZE: usize = 16 * 1024 * 1024;
+/// pacing gain of Config when pacing is enabled without one
+pub(crate) const DEFAULT_PACING_GAIN: f64 = 1.25;
+/// smallest MTU accepted from Config
+pub(crate) const MIN_MTU: u32 = 512;
+/// how far below the size of a packet dropped as too large the MTU is clamped
+pub(crate) const MTU_CLAMP_STEP: u32 = 64;
+/// smallest a_rwnd an INIT or INIT-ACK may carry, see RFC 4960 Section 3.3.2
+pub(crate) const MIN_RECV_WINDOW: u32 = 1500;
+/// SCTP port of the local end, and of the remote unless Config says otherwise (RFC 8841)
+pub(crate) const DEFAULT_SCTP_PORT: u16 = 5000;
+
+/// packets in a row with an unknown verification tag after which the peer is taken to have
+/// restarted the association, see Error::ErrPeerRestart
+pub(crate) const MAX_CONSECUTIVE_BAD_VERIFICATION_TAGS: u32 = 8;
 
 /// other constants
 pub(crate) const ACCEPT_CH_SIZE: usize = 16;
 
-/// association state enums
-#[derive(Debug, Copy, Clone, PartialEq)]
-pub(crate) enum AssociationState {
+/// AssociationState is the state of the SCTP association, see RFC 4960 section 4
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+pub enum AssociationState {
     Closed = 0,
     CookieWait = 1,
     CookieEchoed = 2,
@@ -184,8 +219,52 @@ impl fmt::Display for AckState {
 pub(crate) struct Config {
     pub(crate) net_conn: Arc<dyn Conn + Send + Sync>,
     pub(crate) max_receive_buffer_size: u32,
+    /// initial_recv_window is the a_rwnd advertised in INIT and INIT-ACK. Zero selects
+    /// max_receive_buffer_size, values below MIN_RECV_WINDOW are raised to it.
+    pub(crate) initial_recv_window: u32,
+    /// max_num_inbound_streams and max_num_outbound_streams are the MIS and OS advertised in
+    /// INIT and INIT-ACK. Zero selects u16::MAX.
+    pub(crate) max_num_inbound_streams: u16,
+    pub(crate) max_num_outbound_streams: u16,
+    /// remote_port is the SCTP port of the remote from its description, the INIT is sent to it.
+    /// Zero selects DEFAULT_SCTP_PORT.
+    pub(crate) remote_port: u16,
     pub(crate) max_message_size: u32,
+    /// max_send_buffer_size caps the bytes of user data queued and in flight, see SendBuffer.
+    /// Zero selects DEFAULT_MAX_SEND_BUFFER_SIZE.
+    pub(crate) max_send_buffer_size: usize,
     pub(crate) name: String,
+    /// bundling_delay holds the write loop after a wakeup so that messages written in quick
+    /// succession share a packet. Zero only bundles what is already queued.
+    pub(crate) bundling_delay: Duration,
+    /// pacing spreads the packets sent within a round trip at pacing_gain times cwnd / srtt,
+    /// see Pacer. A pacing_gain of zero selects DEFAULT_PACING_GAIN.
+    pub(crate) pacing: bool,
+    pub(crate) pacing_gain: f64,
+    /// max_send_rate caps the bits per second of SCTP packets the write loop sends, zero meaning
+    /// no cap. It is read for each packet, so it may change while the association runs.
+    pub(crate) max_send_rate: Option<Arc<AtomicU64>>,
+    /// mtu is the largest SCTP packet handed to the lower layer, DATA chunks are fragmented to
+    /// fit. Zero selects INITIAL_MTU.
+    pub(crate) mtu: u32,
+    /// receive_mtu is the largest packet read from net_conn. Zero selects RECEIVE_MTU.
+    pub(crate) receive_mtu: usize,
+    /// strict_priority makes higher priority streams always send first instead of taking
+    /// weighted turns with lower priority ones, see PendingQueue.
+    pub(crate) strict_priority: bool,
+    /// stats collects the counters of the association, it may be shared with the associations
+    /// that come before and after this one
+    pub(crate) stats: Arc<AssociationStats>,
+    /// send_errors counts the datagrams net_conn dropped instead of failing. A packet dropped
+    /// for its size lowers the MTU, see AssociationInternal::clamp_mtu.
+    pub(crate) send_errors: Option<Arc<SendErrorStats>>,
+    /// event_log records the state changes of the association and a summary of each packet
+    pub(crate) event_log: Arc<EventLog>,
+    /// transport_events receives the retransmissions, abandoned messages and congestion window
+    /// cuts of the association
+    pub(crate) transport_events: Arc<TransportEvents>,
+    /// spawner runs the read and write loops, the timers and the close handlers of the streams
+    pub(crate) spawner: Spawner,
 }
 
 ///Association represents an SCTP association
@@ -208,6 +287,7 @@ pub(crate) struct Config {
 pub(crate) struct Association {
     name: String,
     net_conn: Arc<dyn Conn + Send + Sync>,
+    accept_ch_rx: Mutex<mpsc::Receiver<Arc<Stream>>>,
 
     pub(crate) association_internal: Arc<Mutex<AssociationInternal>>,
 }
@@ -228,21 +308,55 @@ impl Association {
         }
     }
 
+    /// close_reason returns why the association was closed, None while it is open
+    pub(crate) async fn close_reason(&self) -> Option<CloseReason> {
+        let ai = self.association_internal.lock().await;
+        ai.close_reason.clone()
+    }
+
     /// Close ends the SCTP Association and cleans up any state
     pub(crate) async fn close(&self) -> Result<()> {
+        self.close_with_reason(CloseReason::LocalClose).await
+    }
+
+    /// close_with_reason is close for when the association goes away for another reason than a
+    /// local close, the streams still open report reason as their close reason.
+    pub(crate) async fn close_with_reason(&self, reason: CloseReason) -> Result<()> {
         log::debug!("[{}] closing association..", self.name);
 
+        // close before the conn, the read_loop would otherwise see the conn fail and close the
+        // association as a network failure
+        let result = {
+            let mut ai = self.association_internal.lock().await;
+            ai.close(reason).await
+        };
+
         let _ = self.net_conn.close().await;
 
-        let mut ai = self.association_internal.lock().await;
-        ai.close().await
+        result
     }
 
     async fn new(config: Config, is_client: bool) -> Result<(Self, mpsc::Receiver<Option<Error>>)> {
         let net_conn = Arc::clone(&config.net_conn);
+        let receive_mtu = if config.receive_mtu == 0 {
+            RECEIVE_MTU
+        } else {
+            config.receive_mtu
+        };
+        let bundling_delay = config.bundling_delay;
+        let pacing_gain = if !config.pacing {
+            None
+        } else if config.pacing_gain > 0.0 {
+            Some(config.pacing_gain)
+        } else {
+            Some(DEFAULT_PACING_GAIN)
+        };
+        let max_send_rate = config.max_send_rate.clone();
+        let event_log = Arc::clone(&config.event_log);
+        let send_errors = config.send_errors.clone();
 
         let (awake_write_loop_ch_tx, awake_write_loop_ch_rx) = mpsc::channel(1);
-        let (accept_ch_tx, _accept_ch_rx) = mpsc::channel(ACCEPT_CH_SIZE);
+        let (accept_ch_tx, accept_ch_rx) = mpsc::channel(ACCEPT_CH_SIZE);
         let (handshake_completed_ch_tx, handshake_completed_ch_rx) = mpsc::channel(1);
         let (close_loop_ch_tx, _) = broadcast::channel(1);
         let (close_loop_ch_rx1, close_loop_ch_rx2) =
@@ -257,29 +371,43 @@ impl Association {
             Arc::clone(&awake_write_loop_ch),
         );
 
-        let bytes_received = Arc::new(AtomicUsize::new(0));
-        let bytes_sent = Arc::new(AtomicUsize::new(0));
+        let stats = Arc::clone(&ai.stats);
         let name = ai.name.clone();
+        let association_inflight = Arc::clone(&ai.inflight);
+        let (mtu, initial_tsn) = {
+            let inflight = association_inflight.lock().await;
+            (inflight.mtu, inflight.my_next_tsn)
+        };
+        let pacer = if pacing_gain.is_some() || max_send_rate.is_some() {
+            Some(Pacer::new(pacing_gain, max_send_rate, mtu))
+        } else {
+            None
+        };
+        let spawner = ai.spawner.clone();
 
         let mut init = ChunkInit {
-            initial_tsn: ai.my_next_tsn,
+            initial_tsn,
             num_outbound_streams: ai.my_max_num_outbound_streams,
             num_inbound_streams: ai.my_max_num_inbound_streams,
             initiate_tag: ai.my_verification_tag,
-            advertised_receiver_window_credit: ai.max_receive_buffer_size,
+            advertised_receiver_window_credit: ai.initial_recv_window,
             ..Default::default()
         };
         init.set_forward_tsn_supported();
+        init.set_interleaving_supported();
 
         let name1 = name.clone();
         let name2 = name.clone();
 
-        let bytes_received1 = Arc::clone(&bytes_received);
-        let bytes_sent2 = Arc::clone(&bytes_sent);
+        let stats1 = Arc::clone(&stats);
+        let stats2 = stats;
 
         let net_conn1 = Arc::clone(&net_conn);
         let net_conn2 = Arc::clone(&net_conn);
 
+        let event_log1 = Arc::clone(&event_log);
+        let event_log2 = event_log;
+
         let association_internal = Arc::new(Mutex::new(ai));
         let association_internal1 = Arc::clone(&association_internal);
         let association_internal2 = Arc::clone(&association_internal);
@@ -292,52 +420,64 @@ impl Association {
                 Arc::downgrade(&association_internal3),
                 RtxTimerId::T1Init,
                 MAX_INIT_RETRANS,
+                ai.spawner.clone(),
             ));
             ai.t1cookie = Some(RtxTimer::new(
                 Arc::downgrade(&association_internal3),
                 RtxTimerId::T1Cookie,
                 MAX_INIT_RETRANS,
+                ai.spawner.clone(),
             ));
             ai.t2shutdown = Some(RtxTimer::new(
                 Arc::downgrade(&association_internal3),
                 RtxTimerId::T2Shutdown,
                 NO_MAX_RETRANS,
+                ai.spawner.clone(),
             )); // retransmit forever
-            ai.t3rtx = Some(RtxTimer::new(
-                Arc::downgrade(&association_internal3),
+            association_inflight.lock().await.t3rtx = Some(RtxTimer::new(
+                Arc::downgrade(&association_inflight),
                 RtxTimerId::T3RTX,
                 NO_MAX_RETRANS,
+                ai.spawner.clone(),
             )); // retransmit forever
             ai.treconfig = Some(RtxTimer::new(
                 Arc::downgrade(&association_internal3),
                 RtxTimerId::Reconfig,
                 NO_MAX_RETRANS,
+                ai.spawner.clone(),
             )); // retransmit forever
             ai.ack_timer = Some(AckTimer::new(
                 Arc::downgrade(&association_internal3),
                 ACK_INTERVAL,
+                ai.spawner.clone(),
             ));
         }
 
-        tokio::spawn(async move {
+        spawner.spawn(async move {
             Association::read_loop(
                 name1,
-                bytes_received1,
+                stats1,
                 net_conn1,
+                receive_mtu,
                 close_loop_ch_rx1,
                 association_internal1,
+                event_log1,
             )
             .await;
         });
 
-        tokio::spawn(async move {
+        spawner.spawn(async move {
             Association::write_loop(
                 name2,
-                bytes_sent2,
+                stats2,
                 net_conn2,
                 close_loop_ch_rx2,
                 association_internal2,
                 awake_write_loop_ch_rx,
+                bundling_delay,
+                pacer,
+                send_errors,
+                event_log2,
             )
             .await;
         });
@@ -347,7 +487,7 @@ impl Association {
             ai.set_state(AssociationState::CookieWait);
             ai.stored_init = Some(init);
             ai.send_init()?;
-            let rto = ai.rto_mgr.get_rto();
+            let rto = association_inflight.lock().await.rto_mgr.get_rto();
             if let Some(t1init) = &ai.t1init {
                 t1init.start(rto).await;
             }
@@ -357,6 +497,7 @@ impl Association {
             Association {
                 name,
                 net_conn,
+                accept_ch_rx: Mutex::new(accept_ch_rx),
                 association_internal,
             },
             handshake_completed_ch_rx,
@@ -365,16 +506,33 @@ impl Association {
 
     async fn read_loop(
         name: String,
-        bytes_received: Arc<AtomicUsize>,
+        stats: Arc<AssociationStats>,
         net_conn: Arc<dyn Conn + Send + Sync>,
+        receive_mtu: usize,
         mut close_loop_ch: broadcast::Receiver<()>,
         association_internal: Arc<Mutex<AssociationInternal>>,
+        event_log: Arc<EventLog>,
     ) {
         log::debug!("[{}] read_loop entered", name);
 
-        let mut buffer = vec![0u8; RECEIVE_MTU];
+        // a packet of SACKs only is handed to AssociationInflight without the association lock.
+        // Only the read loop moves the state on from Established, so it holds while the packet
+        // is processed.
+        let (state, my_verification_tag, consecutive_bad_verification_tags, association_inflight) = {
+            let ai = association_internal.lock().await;
+            (
+                Arc::clone(&ai.state),
+                ai.my_verification_tag,
+                Arc::clone(&ai.consecutive_bad_verification_tags),
+                Arc::clone(&ai.inflight),
+            )
+        };
+
+        let mut buffer = vec![0u8; receive_mtu];
         let mut done = false;
         let mut n;
+        // the association is already closed when close_loop_ch fires, so the reason is unused then
+        let mut close_reason = CloseReason::LocalClose;
         while !done {
             tokio::select! {
                 _ = close_loop_ch.recv() => break,
@@ -385,6 +543,9 @@ impl Association {
                         }
                         Err(err) => {
                             log::warn!("[{}] failed to read packets on net_conn: {}", name, err);
+                            close_reason = CloseReason::TransportFailure(TransportError::Network(
+                                err.to_string(),
+                            ));
                             break;
                         }
                     }
@@ -396,21 +557,55 @@ impl Association {
             // user data is passed to the reassembly queue without
             // copying.
             log::debug!("[{}] recving {} bytes", name, n);
+            if let Some((chunk_type, chunks)) = chunk_summary(&buffer[..n]) {
+                event_log.record(ConnectionEventKind::SctpPacketReceived { chunk_type, chunks });
+            }
             let inbound = Bytes::from(buffer[..n].to_vec());
-            bytes_received.fetch_add(n, Ordering::SeqCst);
+            stats.inc_packets_received(n);
+
+            // parsing and the checksum need no association state, so they run before its lock
+            // is taken and do not hold up the write loop and the timers
+            let packet = match Packet::unmarshal(&inbound) {
+                Ok(packet) => packet,
+                Err(err) => {
+                    log::warn!("[{}] unable to parse SCTP packet {}", name, err);
+                    continue;
+                }
+            };
+            if let Err(err) = packet.check_packet() {
+                log::warn!("[{}] failed validating packet {}", name, err);
+                continue;
+            }
 
+            let result = if packet.verification_tag == my_verification_tag
+                && AssociationState::from(state.load(Ordering::SeqCst))
+                    == AssociationState::Established
+                && is_sack_only(&packet)
             {
+                consecutive_bad_verification_tags.store(0, Ordering::SeqCst);
+                let mut inflight = association_inflight.lock().await;
+                inflight.handle_sack_packet(&packet).await
+            } else {
                 let mut ai = association_internal.lock().await;
-                if let Err(err) = ai.handle_inbound(&inbound).await {
-                    log::warn!("[{}] failed to handle_inbound: {:?}", name, err);
-                    done = true;
-                }
+                ai.handle_inbound(packet).await
+            };
+            if let Err(err) = result {
+                log::warn!("[{}] failed to handle_inbound: {:?}", name, err);
+                close_reason = CloseReason::TransportFailure(match err {
+                    Error::ErrChunk => TransportError::Aborted,
+                    Error::ErrPeerRestart => {
+                        event_log.record(ConnectionEventKind::SctpPeerRestart);
+                        TransportError::PeerRestart
+                    }
+                    err => TransportError::Protocol(err.to_string()),
+                });
+                done = true;
             }
         }
 
         {
             let mut ai = association_internal.lock().await;
-            if let Err(err) = ai.close().await {
+            if let Err(err) = ai.close(close_reason).await {
                 log::warn!("[{}] failed to close association: {:?}", name, err);
             }
         }
@@ -418,37 +613,103 @@ impl Association {
         log::debug!("[{}] read_loop exited", name);
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn write_loop(
         name: String,
-        bytes_sent: Arc<AtomicUsize>,
+        stats: Arc<AssociationStats>,
         net_conn: Arc<dyn Conn + Send + Sync>,
         mut close_loop_ch: broadcast::Receiver<()>,
         association_internal: Arc<Mutex<AssociationInternal>>,
         mut awake_write_loop_ch: mpsc::Receiver<()>,
+        bundling_delay: Duration,
+        mut pacer: Option<Pacer>,
+        send_errors: Option<Arc<SendErrorStats>>,
+        event_log: Arc<EventLog>,
     ) {
         log::debug!("[{}] write_loop entered", name);
+        let association_inflight = Arc::clone(&association_internal.lock().await.inflight);
         let mut done = false;
+        let mut close_reason = CloseReason::LocalClose;
         while !done {
             //log::debug!("[{}] gather_outbound begin", name);
-            let (raw_packets, mut ok) = {
+            let (control, state, use_interleaving) = {
+                let mut ai = association_internal.lock().await;
+                let (control, state) = ai.gather_outbound_control().await;
+                (control, state, ai.use_interleaving)
+            };
+            // the DATA chunks are taken under the in-flight lock alone, the read loop processes
+            // inbound DATA meanwhile
+            let (inflight, window) = {
+                let mut inflight = association_inflight.lock().await;
+                let gathered = inflight.gather_outbound(state, use_interleaving).await;
+                inflight.update_send_buffer();
+                (
+                    gathered,
+                    (inflight.cwnd, inflight.ssthresh, inflight.rto_mgr.srtt),
+                )
+            };
+            let (packets, mut ok) = {
                 let mut ai = association_internal.lock().await;
-                ai.gather_outbound().await
+                ai.gather_outbound(control, state, inflight).await
             };
+            // serializing copies the user data and computes the checksum, it runs after the
+            // lock is released so the read loop can process SACKs meanwhile
+            let raw_packets: Vec<Bytes> = packets
+                .iter()
+                .filter_map(|p| match p.marshal() {
+                    Ok(raw) => Some(raw),
+                    Err(err) => {
+                        log::warn!("[{}] failed to serialize a packet: {}", name, err);
+                        None
+                    }
+                })
+                .collect();
             //log::debug!("[{}] gather_outbound done with {}", name, raw_packets.len());
 
             for raw in &raw_packets {
+                let (cwnd, ssthresh, srtt) = window;
+                if let Some(send_at) = pacer
+                    .as_mut()
+                    .and_then(|pacer| pacer.schedule(raw.len(), cwnd, ssthresh, srtt))
+                {
+                    tokio::select! {
+                        _ = tokio::time::sleep_until(send_at) => {}
+                        _ = close_loop_ch.recv() => {
+                            done = true;
+                            break;
+                        }
+                    };
+                }
+
                 log::debug!("[{}] sending {} bytes", name, raw.len());
+                let too_large = send_errors
+                    .as_ref()
+                    .map(|send_errors| send_errors.get_num_message_too_large());
                 if let Err(err) = net_conn.send(raw).await {
                     log::warn!("[{}] failed to write packets on net_conn: {}", name, err);
+                    close_reason =
+                        CloseReason::TransportFailure(TransportError::Network(err.to_string()));
                     ok = false;
                     break;
+                } else if send_errors
+                    .as_ref()
+                    .map(|send_errors| send_errors.get_num_message_too_large())
+                    != too_large
+                {
+                    // net_conn dropped the packet as too large for the path
+                    let mut ai = association_internal.lock().await;
+                    ai.clamp_mtu(raw.len()).await;
                 } else {
-                    bytes_sent.fetch_add(raw.len(), Ordering::SeqCst);
+                    stats.inc_packets_sent(raw.len());
+                    if let Some((chunk_type, chunks)) = chunk_summary(raw) {
+                        event_log
+                            .record(ConnectionEventKind::SctpPacketSent { chunk_type, chunks });
+                    }
                 }
                 //log::debug!("[{}] sending {} bytes done", name, raw.len());
             }
 
-            if !ok {
+            if !ok || done {
                 break;
             }
 
@@ -459,12 +720,22 @@ impl Association {
                     done = true;
                 }
             };
+
+            // give further writes a chance to join the next gather
+            if !done && !bundling_delay.is_zero() {
+                tokio::select! {
+                    _ = tokio::time::sleep(bundling_delay) => {}
+                    _ = close_loop_ch.recv() => {
+                        done = true;
+                    }
+                };
+            }
             //log::debug!("[{}] wait awake_write_loop_ch done", name);
         }
 
         {
             let mut ai = association_internal.lock().await;
-            if let Err(err) = ai.close().await {
+            if let Err(err) = ai.close(close_reason).await {
                 log::warn!("[{}] failed to close association: {:?}", name, err);
             }
         }
@@ -477,4 +748,39 @@ impl Association {
         let mut ai = self.association_internal.lock().await;
         ai.open_stream(stream_identifier)
     }
+
+    /// accept_stream accepts a stream opened by the remote, returns None once the association is
+    /// closed
+    pub(crate) async fn accept_stream(&self) -> Option<Arc<Stream>> {
+        let mut accept_ch_rx = self.accept_ch_rx.lock().await;
+        accept_ch_rx.recv().await
+    }
+}
+
+/// is_sack_only reports whether p holds SACK chunks and nothing else
+fn is_sack_only(p: &Packet) -> bool {
+    !p.chunks.is_empty()
+        && p.chunks
+            .iter()
+            .all(|c| c.as_any().downcast_ref::<ChunkSelectiveAck>().is_some())
+}
+
+/// chunk_summary returns the type of the first chunk of a marshaled packet and how many chunks it
+/// holds, without unmarshaling it. None if it holds no chunk.
+fn chunk_summary(raw: &[u8]) -> Option<(u8, u8)> {
+    let mut first = None;
+    let mut chunks = 0u8;
+    let mut offset = PACKET_HEADER_SIZE;
+    while offset + CHUNK_HEADER_SIZE <= raw.len() {
+        first.get_or_insert(raw[offset]);
+        chunks = chunks.saturating_add(1);
+
+        let length = u16::from_be_bytes([raw[offset + 2], raw[offset + 3]]) as usize;
+        if length < CHUNK_HEADER_SIZE {
+            break;
+        }
+        offset += length + get_padding_size(length);
+    }
+
+    first.map(|chunk_type| (chunk_type, chunks))
 }
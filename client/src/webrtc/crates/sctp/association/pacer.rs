@@ -0,0 +1,94 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// packets that go out back to back after the pacer was idle, so a shallow queue is not delayed
+pub(crate) const PACING_BURST_PACKETS: u32 = 2;
+/// gain applied in slow start, where cwnd doubles every round trip
+pub(crate) const SLOW_START_PACING_GAIN: f64 = 2.0;
+
+/// Pacer spreads the packets of the write loop over a round trip instead of sending a whole
+/// congestion window back to back. The pacing rate is gain * cwnd / srtt, raised to
+/// SLOW_START_PACING_GAIN while cwnd is below ssthresh so pacing does not hold back the window
+/// growth. Until a round trip was measured packets are not paced.
+///
+/// max_rate caps the rate in bits per second, zero meaning no cap. It is read for every packet,
+/// so it may change while the association runs. Without a gain only the cap paces packets.
+///
+/// The pacer keeps the time the next packet is due. Idle time accrues credit for at most
+/// PACING_BURST_PACKETS packets of mtu bytes, which go out right away.
+#[derive(Debug)]
+pub(crate) struct Pacer {
+    gain: Option<f64>,
+    max_rate: Option<Arc<AtomicU64>>,
+    mtu: u32,
+    next_send: Option<Instant>,
+}
+
+impl Pacer {
+    pub(crate) fn new(gain: Option<f64>, max_rate: Option<Arc<AtomicU64>>, mtu: u32) -> Self {
+        Pacer {
+            gain,
+            max_rate,
+            mtu,
+            next_send: None,
+        }
+    }
+
+    /// schedule books a packet of n_bytes and returns when it may be sent, None if right away.
+    /// srtt is in milliseconds.
+    pub(crate) fn schedule(
+        &mut self,
+        n_bytes: usize,
+        cwnd: u32,
+        ssthresh: u32,
+        srtt: u64,
+    ) -> Option<Instant> {
+        // in bytes per second
+        let window_rate = match self.gain {
+            Some(gain) if srtt != 0 && cwnd != 0 => {
+                let gain = if cwnd < ssthresh {
+                    gain.max(SLOW_START_PACING_GAIN)
+                } else {
+                    gain
+                };
+                Some(gain * cwnd as f64 / (srtt as f64 / 1000.0))
+            }
+            _ => None,
+        };
+        let max_rate = self
+            .max_rate
+            .as_ref()
+            .map(|max_rate| max_rate.load(Ordering::Relaxed))
+            .filter(|&max_rate| max_rate != 0)
+            .map(|max_rate| max_rate as f64 / 8.0);
+        let rate = match (window_rate, max_rate) {
+            (Some(window_rate), Some(max_rate)) => window_rate.min(max_rate),
+            (Some(rate), None) | (None, Some(rate)) => rate,
+            (None, None) => {
+                self.next_send = None;
+                return None;
+            }
+        };
+
+        let secs_per_byte = 1.0 / rate;
+        let interval = Duration::from_secs_f64(n_bytes as f64 * secs_per_byte);
+        let burst =
+            Duration::from_secs_f64((PACING_BURST_PACKETS * self.mtu) as f64 * secs_per_byte);
+
+        let now = Instant::now();
+        let earliest = now.checked_sub(burst).unwrap_or(now);
+        let send_at = match self.next_send {
+            Some(next_send) if next_send > earliest => next_send,
+            _ => earliest,
+        };
+        self.next_send = Some(send_at + interval);
+
+        if send_at > now {
+            Some(send_at)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use super::*;
+
+/// StreamMap holds the streams of an association by stream identifier. AssociationInternal and
+/// AssociationInflight share it, its lock is only held to look a stream up or to change the
+/// map, never across an await.
+#[derive(Default, Clone)]
+pub(crate) struct StreamMap(Arc<std::sync::Mutex<HashMap<u16, Arc<Stream>>>>);
+
+impl StreamMap {
+    /// get returns the stream of stream_identifier, if it is open
+    pub(crate) fn get(&self, stream_identifier: u16) -> Option<Arc<Stream>> {
+        self.0.lock().unwrap().get(&stream_identifier).cloned()
+    }
+
+    /// contains reports whether the stream of stream_identifier is open
+    pub(crate) fn contains(&self, stream_identifier: u16) -> bool {
+        self.0.lock().unwrap().contains_key(&stream_identifier)
+    }
+
+    pub(crate) fn insert(&self, stream_identifier: u16, s: Arc<Stream>) {
+        self.0.lock().unwrap().insert(stream_identifier, s);
+    }
+
+    pub(crate) fn remove(&self, stream_identifier: u16) -> Option<Arc<Stream>> {
+        self.0.lock().unwrap().remove(&stream_identifier)
+    }
+
+    /// identifiers returns the identifiers of the open streams
+    pub(crate) fn identifiers(&self) -> Vec<u16> {
+        self.0.lock().unwrap().keys().cloned().collect()
+    }
+
+    /// streams returns the open streams, to be awaited on without the lock
+    pub(crate) fn streams(&self) -> Vec<Arc<Stream>> {
+        self.0.lock().unwrap().values().cloned().collect()
+    }
+}
@@ -25,9 +25,14 @@ use std::fmt;
 ///+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
 #[derive(Default, Debug, Clone)]
 pub(crate) struct ChunkAbort {
+    /// reflected_tag is the T bit: the packet carries the sender's own verification tag instead
+    /// of the receiver's, RFC 4960 Section 8.5.1
+    pub(crate) reflected_tag: bool,
     pub(crate) error_causes: Vec<ErrorCause>,
 }
 
+pub(crate) const ABORT_T_BITMASK: u8 = 1;
+
 /// String makes chunkAbort printable
 impl fmt::Display for ChunkAbort {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -45,7 +50,11 @@ impl Chunk for ChunkAbort {
     fn header(&self) -> ChunkHeader {
         ChunkHeader {
             typ: CT_ABORT,
-            flags: 0,
+            flags: if self.reflected_tag {
+                ABORT_T_BITMASK
+            } else {
+                0
+            },
             value_length: self.value_length() as u16,
         }
     }
@@ -67,7 +76,10 @@ impl Chunk for ChunkAbort {
             error_causes.push(e);
         }
 
-        Ok(ChunkAbort { error_causes })
+        Ok(ChunkAbort {
+            reflected_tag: header.flags & ABORT_T_BITMASK != 0,
+            error_causes,
+        })
     }
 
     fn marshal_to(&self, buf: &mut BytesMut) -> Result<usize> {
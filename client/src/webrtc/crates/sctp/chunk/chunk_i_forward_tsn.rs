@@ -0,0 +1,186 @@
+use super::{chunk_header::*, chunk_type::*, *};
+use crate::webrtc::sctp::chunk::chunk_forward_tsn::NEW_CUMULATIVE_TSN_LENGTH;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::fmt;
+
+///I-FORWARD-TSN replaces FORWARD-TSN once I-DATA is in use (RFC 8260 Section 2.3). The
+///skipped messages are reported by Message Identifier, for unordered messages as well, so
+///that the receiver can drop their fragments without looking at TSNs.
+/// 0                   1                   2                   3
+/// 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+///+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///|   Type = 194  |  Flags = 0x00 |        Length = Variable      |
+///+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///|                      New Cumulative TSN                       |
+///+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///|       Stream Identifier       |          Reserved           |U|
+///+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///|                       Message Identifier                      |
+///+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///|                                                               |
+///|                                                               |
+///+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+#[derive(Default, Debug, Clone)]
+pub(crate) struct ChunkIForwardTsn {
+    /// See ChunkForwardTsn
+    pub(crate) new_cumulative_tsn: u32,
+    pub(crate) streams: Vec<ChunkIForwardTsnStream>,
+}
+
+pub(crate) const I_FORWARD_TSN_STREAM_LENGTH: usize = 8;
+const I_FORWARD_TSN_UNORDERED_BITMASK: u16 = 1;
+
+/// makes ChunkIForwardTsn printable
+impl fmt::Display for ChunkIForwardTsn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut res = vec![self.header().to_string()];
+        res.push(format!("New Cumulative TSN: {}", self.new_cumulative_tsn));
+        for s in &self.streams {
+            res.push(format!(" - {}", s));
+        }
+
+        write!(f, "{}", res.join("\n"))
+    }
+}
+
+impl Chunk for ChunkIForwardTsn {
+    fn header(&self) -> ChunkHeader {
+        ChunkHeader {
+            typ: CT_I_FORWARD_TSN,
+            flags: 0,
+            value_length: self.value_length() as u16,
+        }
+    }
+
+    fn unmarshal(buf: &Bytes) -> Result<Self> {
+        let header = ChunkHeader::unmarshal(buf)?;
+
+        if header.typ != CT_I_FORWARD_TSN {
+            return Err(Error::ErrChunkTypeNotIForwardTsn);
+        }
+
+        let value_length = header.value_length();
+        if value_length < NEW_CUMULATIVE_TSN_LENGTH
+            || !(value_length - NEW_CUMULATIVE_TSN_LENGTH)
+                .is_multiple_of(I_FORWARD_TSN_STREAM_LENGTH)
+        {
+            return Err(Error::ErrChunkTooShort);
+        }
+
+        let reader = &mut buf.slice(CHUNK_HEADER_SIZE..CHUNK_HEADER_SIZE + value_length);
+        let new_cumulative_tsn = reader.get_u32();
+
+        let mut streams = vec![];
+        while reader.has_remaining() {
+            let s = ChunkIForwardTsnStream::unmarshal(
+                &reader.copy_to_bytes(I_FORWARD_TSN_STREAM_LENGTH),
+            )?;
+            streams.push(s);
+        }
+
+        Ok(ChunkIForwardTsn {
+            new_cumulative_tsn,
+            streams,
+        })
+    }
+
+    fn marshal_to(&self, writer: &mut BytesMut) -> Result<usize> {
+        self.header().marshal_to(writer)?;
+
+        writer.put_u32(self.new_cumulative_tsn);
+
+        for s in &self.streams {
+            s.marshal_to(writer)?;
+        }
+
+        Ok(writer.len())
+    }
+
+    fn check(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn value_length(&self) -> usize {
+        NEW_CUMULATIVE_TSN_LENGTH + I_FORWARD_TSN_STREAM_LENGTH * self.streams.len()
+    }
+
+    fn as_any(&self) -> &(dyn Any + Send + Sync) {
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ChunkIForwardTsnStream {
+    /// This field holds a stream number that was skipped by this I-FORWARD-TSN.
+    pub(crate) identifier: u16,
+
+    /// Whether the skipped messages are unordered ones, which are numbered apart from the
+    /// ordered ones.
+    pub(crate) unordered: bool,
+
+    /// This field holds the largest Message Identifier of the stream being skipped, for
+    /// ordered and unordered messages alike.
+    pub(crate) message_identifier: u32,
+}
+
+/// makes ChunkIForwardTsnStream printable
+impl fmt::Display for ChunkIForwardTsnStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "si={}, unordered={}, mid={}",
+            self.identifier, self.unordered, self.message_identifier
+        )
+    }
+}
+
+impl Chunk for ChunkIForwardTsnStream {
+    fn header(&self) -> ChunkHeader {
+        ChunkHeader {
+            typ: ChunkType(0),
+            flags: 0,
+            value_length: self.value_length() as u16,
+        }
+    }
+
+    fn unmarshal(buf: &Bytes) -> Result<Self> {
+        if buf.len() < I_FORWARD_TSN_STREAM_LENGTH {
+            return Err(Error::ErrChunkTooShort);
+        }
+
+        let reader = &mut buf.clone();
+        let identifier = reader.get_u16();
+        let flags = reader.get_u16();
+        let message_identifier = reader.get_u32();
+
+        Ok(ChunkIForwardTsnStream {
+            identifier,
+            unordered: flags & I_FORWARD_TSN_UNORDERED_BITMASK != 0,
+            message_identifier,
+        })
+    }
+
+    fn marshal_to(&self, writer: &mut BytesMut) -> Result<usize> {
+        writer.put_u16(self.identifier);
+        writer.put_u16(if self.unordered {
+            I_FORWARD_TSN_UNORDERED_BITMASK
+        } else {
+            0
+        });
+        writer.put_u32(self.message_identifier);
+        Ok(writer.len())
+    }
+
+    fn check(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn value_length(&self) -> usize {
+        I_FORWARD_TSN_STREAM_LENGTH
+    }
+
+    fn as_any(&self) -> &(dyn Any + Send + Sync) {
+        self
+    }
+}
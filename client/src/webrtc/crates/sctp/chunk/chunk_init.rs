@@ -3,6 +3,7 @@ use crate::webrtc::sctp::param::{param_header::*, *};
 use crate::webrtc::sctp::util::get_padding_size;
 
 use crate::webrtc::sctp::param::param_forward_tsn_supported::ParamForwardTsnSupported;
+use crate::webrtc::sctp::param::param_supported_extensions::ParamSupportedExtensions;
 use bytes::{Buf, BufMut, Bytes, BytesMut};
 use std::fmt;
 
@@ -277,4 +278,12 @@ impl ChunkInit {
         // Changed this to work as webrtc-unreliable requires
         self.params.push(Box::new(ParamForwardTsnSupported));
     }
+
+    /// set_interleaving_supported advertises I-DATA and I-FORWARD-TSN, RFC 8260 Section 2.2.
+    /// FORWARD-TSN is listed as well for peers that only look for it here.
+    pub(crate) fn set_interleaving_supported(&mut self) {
+        self.params.push(Box::new(ParamSupportedExtensions {
+            chunk_types: vec![CT_FORWARD_TSN, CT_I_DATA, CT_I_FORWARD_TSN],
+        }));
+    }
 }
@@ -4,13 +4,14 @@ use bytes::{Buf, BufMut, Bytes, BytesMut};
 use std::fmt;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::SystemTime;
+use tokio::time::Instant;
 
 pub(crate) const PAYLOAD_DATA_ENDING_FRAGMENT_BITMASK: u8 = 1;
 pub(crate) const PAYLOAD_DATA_BEGINING_FRAGMENT_BITMASK: u8 = 2;
 pub(crate) const PAYLOAD_DATA_UNORDERED_BITMASK: u8 = 4;
 pub(crate) const PAYLOAD_DATA_IMMEDIATE_SACK: u8 = 8;
 pub(crate) const PAYLOAD_DATA_HEADER_SIZE: usize = 12;
+pub(crate) const I_DATA_HEADER_SIZE: usize = 16;
 
 /// PayloadProtocolIdentifier is an enum for DataChannel payload types
 /// PayloadProtocolIdentifier enums
@@ -93,6 +94,30 @@ impl From<u32> for PayloadProtocolIdentifier {
 ///============================================================
 ///|             Table 1: Fragment Description Flags          |
 ///============================================================
+///
+///The same struct represents an I-DATA chunk (RFC 8260) when interleaved is set. The
+///fragments of a message are then identified by the Message Identifier and put back in
+///order by the Fragment Sequence Number instead of the TSN, so that they can be
+///interleaved with the fragments of messages of other streams. The Payload Protocol
+///Identifier is only carried by the first fragment.
+///
+/// 0                   1                   2                   3
+/// 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+///+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///|   Type = 64   |  Res  |I|U|B|E|       Length = Variable       |
+///+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///|                              TSN                              |
+///+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///|        Stream Identifier      |           Reserved            |
+///+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///|                      Message Identifier                       |
+///+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///|    Payload Protocol Identifier / Fragment Sequence Number     |
+///+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///|                                                               |
+///|                           User Data                           |
+///|                                                               |
+///+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
 #[derive(Debug, Clone)]
 pub(crate) struct ChunkPayloadData {
     pub(crate) unordered: bool,
@@ -106,12 +131,23 @@ pub(crate) struct ChunkPayloadData {
     pub(crate) payload_type: PayloadProtocolIdentifier,
     pub(crate) user_data: Bytes,
 
+    /// Whether this is an I-DATA chunk, only used once both endpoints announced support
+    pub(crate) interleaved: bool,
+    /// I-DATA only, numbers the messages of a stream, separately for ordered and unordered
+    /// ones. Takes the place of the stream sequence number for ordered delivery.
+    pub(crate) message_identifier: u32,
+    /// I-DATA only, the position of the fragment within its message starting at 0
+    pub(crate) fragment_sequence_number: u32,
+
     /// Whether this data chunk was acknowledged (received by peer)
     pub(crate) acked: bool,
     pub(crate) miss_indicator: u32,
 
     /// Partial-reliability parameters used only by sender
-    pub(crate) since: SystemTime,
+    pub(crate) since: Instant,
+    /// deadline by which the message has to be handed to the wire, it is dropped from the
+    /// pending queue after, see Stream::write_sctp_with_deadline
+    pub(crate) deadline: Option<Instant>,
     /// number of transmission made for this chunk
     pub(crate) nsent: u32,
 
@@ -137,9 +173,13 @@ impl Default for ChunkPayloadData {
             stream_sequence_number: 0,
             payload_type: PayloadProtocolIdentifier::default(),
             user_data: Bytes::new(),
+            interleaved: false,
+            message_identifier: 0,
+            fragment_sequence_number: 0,
             acked: false,
             miss_indicator: 0,
-            since: SystemTime::now(),
+            since: Instant::now(),
+            deadline: None,
             nsent: 0,
             abandoned: Arc::new(AtomicBool::new(false)),
             all_inflight: Arc::new(AtomicBool::new(false)),
@@ -172,7 +212,11 @@ impl Chunk for ChunkPayloadData {
         }
 
         ChunkHeader {
-            typ: CT_PAYLOAD_DATA,
+            typ: if self.interleaved {
+                CT_I_DATA
+            } else {
+                CT_PAYLOAD_DATA
+            },
             flags,
             value_length: self.value_length() as u16,
         }
@@ -181,16 +225,22 @@ impl Chunk for ChunkPayloadData {
     fn unmarshal(raw: &Bytes) -> Result<Self> {
         let header = ChunkHeader::unmarshal(raw)?;
 
-        if header.typ != CT_PAYLOAD_DATA {
+        if header.typ != CT_PAYLOAD_DATA && header.typ != CT_I_DATA {
             return Err(Error::ErrChunkTypeNotPayloadData);
         }
+        let interleaved = header.typ == CT_I_DATA;
 
         let immediate_sack = (header.flags & PAYLOAD_DATA_IMMEDIATE_SACK) != 0;
         let unordered = (header.flags & PAYLOAD_DATA_UNORDERED_BITMASK) != 0;
         let beginning_fragment = (header.flags & PAYLOAD_DATA_BEGINING_FRAGMENT_BITMASK) != 0;
         let ending_fragment = (header.flags & PAYLOAD_DATA_ENDING_FRAGMENT_BITMASK) != 0;
 
-        if raw.len() < PAYLOAD_DATA_HEADER_SIZE {
+        let header_size = if interleaved {
+            I_DATA_HEADER_SIZE
+        } else {
+            PAYLOAD_DATA_HEADER_SIZE
+        };
+        if raw.len() < header_size || header.value_length() < header_size {
             return Err(Error::ErrChunkPayloadSmall);
         }
 
@@ -198,11 +248,27 @@ impl Chunk for ChunkPayloadData {
 
         let tsn = reader.get_u32();
         let stream_identifier = reader.get_u16();
-        let stream_sequence_number = reader.get_u16();
-        let payload_type: PayloadProtocolIdentifier = reader.get_u32().into();
-        let user_data = raw.slice(
-            CHUNK_HEADER_SIZE + PAYLOAD_DATA_HEADER_SIZE..CHUNK_HEADER_SIZE + header.value_length(),
-        );
+        let (stream_sequence_number, message_identifier, fragment_sequence_number, payload_type) =
+            if interleaved {
+                reader.advance(2);
+                let message_identifier = reader.get_u32();
+                let ppi_or_fsn = reader.get_u32();
+                if beginning_fragment {
+                    (0, message_identifier, 0, ppi_or_fsn.into())
+                } else {
+                    (
+                        0,
+                        message_identifier,
+                        ppi_or_fsn,
+                        PayloadProtocolIdentifier::Unknown,
+                    )
+                }
+            } else {
+                let stream_sequence_number = reader.get_u16();
+                (stream_sequence_number, 0, 0, reader.get_u32().into())
+            };
+        let user_data =
+            raw.slice(CHUNK_HEADER_SIZE + header_size..CHUNK_HEADER_SIZE + header.value_length());
 
         Ok(ChunkPayloadData {
             unordered,
@@ -215,9 +281,13 @@ impl Chunk for ChunkPayloadData {
             stream_sequence_number,
             payload_type,
             user_data,
+            interleaved,
+            message_identifier,
+            fragment_sequence_number,
             acked: false,
             miss_indicator: 0,
-            since: SystemTime::now(),
+            since: Instant::now(),
+            deadline: None,
             nsent: 0,
             abandoned: Arc::new(AtomicBool::new(false)),
             all_inflight: Arc::new(AtomicBool::new(false)),
@@ -230,8 +300,18 @@ impl Chunk for ChunkPayloadData {
 
         writer.put_u32(self.tsn);
         writer.put_u16(self.stream_identifier);
-        writer.put_u16(self.stream_sequence_number);
-        writer.put_u32(self.payload_type as u32);
+        if self.interleaved {
+            writer.put_u16(0);
+            writer.put_u32(self.message_identifier);
+            if self.beginning_fragment {
+                writer.put_u32(self.payload_type as u32);
+            } else {
+                writer.put_u32(self.fragment_sequence_number);
+            }
+        } else {
+            writer.put_u16(self.stream_sequence_number);
+            writer.put_u32(self.payload_type as u32);
+        }
         writer.extend(self.user_data.clone());
 
         Ok(writer.len())
@@ -242,7 +322,11 @@ impl Chunk for ChunkPayloadData {
     }
 
     fn value_length(&self) -> usize {
-        PAYLOAD_DATA_HEADER_SIZE + self.user_data.len()
+        if self.interleaved {
+            I_DATA_HEADER_SIZE + self.user_data.len()
+        } else {
+            PAYLOAD_DATA_HEADER_SIZE + self.user_data.len()
+        }
     }
 
     fn as_any(&self) -> &(dyn Any + Send + Sync) {
@@ -260,8 +344,10 @@ impl ChunkPayloadData {
         abandoned && all_inflight
     }
 
-    pub(crate) fn set_abandoned(&self, abandoned: bool) {
-        self.abandoned.store(abandoned, Ordering::SeqCst);
+    /// set_abandoned marks the message of the chunk abandoned or not and returns whether it was
+    /// abandoned before
+    pub(crate) fn set_abandoned(&self, abandoned: bool) -> bool {
+        self.abandoned.swap(abandoned, Ordering::SeqCst)
     }
 
     pub(crate) fn set_all_inflight(&mut self) {
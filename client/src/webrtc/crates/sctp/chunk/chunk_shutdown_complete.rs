@@ -11,7 +11,13 @@ use std::fmt;
 ///|   Type = 14   |Reserved     |T|      Length = 4               |
 ///+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
 #[derive(Default, Debug, Clone)]
-pub(crate) struct ChunkShutdownComplete;
+pub(crate) struct ChunkShutdownComplete {
+    /// reflected_tag is the T bit: the packet carries the sender's own verification tag instead
+    /// of the receiver's, RFC 4960 Section 8.5.1
+    pub(crate) reflected_tag: bool,
+}
+
+pub(crate) const SHUTDOWN_COMPLETE_T_BITMASK: u8 = 1;
 
 /// makes chunkShutdownComplete printable
 impl fmt::Display for ChunkShutdownComplete {
@@ -24,7 +30,11 @@ impl Chunk for ChunkShutdownComplete {
     fn header(&self) -> ChunkHeader {
         ChunkHeader {
             typ: CT_SHUTDOWN_COMPLETE,
-            flags: 0,
+            flags: if self.reflected_tag {
+                SHUTDOWN_COMPLETE_T_BITMASK
+            } else {
+                0
+            },
             value_length: self.value_length() as u16,
         }
     }
@@ -36,7 +46,9 @@ impl Chunk for ChunkShutdownComplete {
             return Err(Error::ErrChunkTypeNotShutdownComplete);
         }
 
-        Ok(ChunkShutdownComplete {})
+        Ok(ChunkShutdownComplete {
+            reflected_tag: header.flags & SHUTDOWN_COMPLETE_T_BITMASK != 0,
+        })
     }
 
     fn marshal_to(&self, writer: &mut BytesMut) -> Result<usize> {
@@ -27,6 +27,8 @@ pub(crate) enum Error {
     ErrChunkTooShort,
     #[error("ChunkType is not of type ForwardTsn")]
     ErrChunkTypeNotForwardTsn,
+    #[error("ChunkType is not of type IForwardTsn")]
+    ErrChunkTypeNotIForwardTsn,
     #[error("ChunkType is not of type HEARTBEAT")]
     ErrChunkTypeNotHeartbeat,
     #[error("ChunkType is not of type HEARTBEATACK")]
@@ -78,6 +80,8 @@ pub(crate) enum Error {
     ErrChunkPayloadSmall,
     #[error("ChunkType is not of type PayloadData")]
     ErrChunkTypeNotPayloadData,
+    #[error("DATA and I-DATA mixed, only the negotiated one may be used")]
+    ErrPayloadDataNotNegotiated,
     #[error("ChunkType is not of type Reconfig")]
     ErrChunkTypeNotReconfig,
     #[error("ChunkReconfig has invalid ParamA")]
@@ -126,14 +130,19 @@ pub(crate) enum Error {
     ErrInitChunkBundled,
     #[error("init chunk expects a verification tag of 0 on the packet when out-of-the-blue")]
     ErrInitChunkVerifyTagNotZero,
-    #[error("todo: handle Init when in state")]
-    ErrHandleInitState,
+    /// ErrPeerRestart indicates the peer restarted its end of an established association, it
+    /// sent an INIT with a new initiate tag or kept sending packets with an unknown verification
+    /// tag (RFC 4960 Section 5.2.2)
+    #[error("peer restarted the association")]
+    ErrPeerRestart,
     #[error("no cookie in InitAck")]
     ErrInitAckNoCookie,
     #[error("there already exists a stream with identifier")]
     ErrStreamAlreadyExist,
     #[error("Failed to create a stream with identifier")]
     ErrStreamCreateFailed,
+    #[error("stream identifier is beyond the negotiated number of outbound streams")]
+    ErrStreamsExhausted,
     #[error("unable to be popped from inflight queue TSN")]
     ErrInflightQueueTsnPop,
     #[error("requested non-existent TSN")]
@@ -155,13 +164,15 @@ pub(crate) enum Error {
     ErrOutboundPacketTooLarge,
     #[error("Stream closed")]
     ErrStreamClosed,
+    #[error("message expired before it could be sent")]
+    ErrMessageExpired,
+    #[error("send buffer of the association is full")]
+    ErrSendBufferFull,
     #[error("Short buffer to be filled")]
     ErrShortBuffer,
     #[allow(dead_code)]
     #[error("Io EOF")]
     ErrEof,
-    #[error("Invalid SystemTime")]
-    ErrInvalidSystemTime,
 }
 
 impl From<Error> for io::Error {
@@ -171,6 +182,10 @@ impl From<Error> for io::Error {
             e @ Error::ErrStreamClosed => {
                 io::Error::new(io::ErrorKind::ConnectionAborted, e.to_string())
             }
+            e @ Error::ErrMessageExpired => io::Error::new(io::ErrorKind::TimedOut, e.to_string()),
+            e @ Error::ErrSendBufferFull => {
+                io::Error::new(io::ErrorKind::WouldBlock, e.to_string())
+            }
             e => io::Error::new(io::ErrorKind::Other, e.to_string()),
         }
     }
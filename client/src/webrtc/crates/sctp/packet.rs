@@ -7,6 +7,7 @@ use crate::webrtc::sctp::chunk::chunk_error::ChunkError;
 use crate::webrtc::sctp::chunk::chunk_forward_tsn::ChunkForwardTsn;
 use crate::webrtc::sctp::chunk::chunk_header::*;
 use crate::webrtc::sctp::chunk::chunk_heartbeat::ChunkHeartbeat;
+use crate::webrtc::sctp::chunk::chunk_i_forward_tsn::ChunkIForwardTsn;
 use crate::webrtc::sctp::chunk::chunk_init::ChunkInit;
 use crate::webrtc::sctp::chunk::chunk_payload_data::ChunkPayloadData;
 use crate::webrtc::sctp::chunk::chunk_reconfig::ChunkReconfig;
@@ -116,10 +117,13 @@ impl Packet {
                 CT_COOKIE_ECHO => Box::new(ChunkCookieEcho::unmarshal(&raw.slice(offset..))?),
                 CT_COOKIE_ACK => Box::new(ChunkCookieAck::unmarshal(&raw.slice(offset..))?),
                 CT_HEARTBEAT => Box::new(ChunkHeartbeat::unmarshal(&raw.slice(offset..))?),
-                CT_PAYLOAD_DATA => Box::new(ChunkPayloadData::unmarshal(&raw.slice(offset..))?),
+                CT_PAYLOAD_DATA | CT_I_DATA => {
+                    Box::new(ChunkPayloadData::unmarshal(&raw.slice(offset..))?)
+                }
                 CT_SACK => Box::new(ChunkSelectiveAck::unmarshal(&raw.slice(offset..))?),
                 CT_RECONFIG => Box::new(ChunkReconfig::unmarshal(&raw.slice(offset..))?),
                 CT_FORWARD_TSN => Box::new(ChunkForwardTsn::unmarshal(&raw.slice(offset..))?),
+                CT_I_FORWARD_TSN => Box::new(ChunkIForwardTsn::unmarshal(&raw.slice(offset..))?),
                 CT_ERROR => Box::new(ChunkError::unmarshal(&raw.slice(offset..))?),
                 CT_SHUTDOWN => Box::new(ChunkShutdown::unmarshal(&raw.slice(offset..))?),
                 CT_SHUTDOWN_ACK => Box::new(ChunkShutdownAck::unmarshal(&raw.slice(offset..))?),
@@ -1,8 +1,115 @@
 use super::{param_header::*, param_type::*, *};
 
-use bytes::{Bytes, BytesMut};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
 use rand::Rng;
+use ring::hmac;
 use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long a state cookie is accepted after it was handed out, the Valid.Cookie.Life of
+/// RFC 4960 Section 15.
+const VALID_COOKIE_LIFE: Duration = Duration::from_secs(60);
+
+/// How often the cookie secret is replaced. The previous secret is accepted until the next
+/// rotation, so a cookie stays verifiable for all of VALID_COOKIE_LIFE.
+const COOKIE_SECRET_ROTATION: Duration = VALID_COOKIE_LIFE;
+
+/// timestamp (8) | my verification tag (4) | peer verification tag (4)
+const COOKIE_STATE_LENGTH: usize = 16;
+const COOKIE_MAC_LENGTH: usize = 32;
+
+lazy_static! {
+    static ref COOKIE_SECRETS: Mutex<CookieSecrets> = Mutex::new(CookieSecrets::new());
+}
+
+/// CookieSecrets holds the per-process keys the state cookies are signed with
+struct CookieSecrets {
+    epoch: Instant,
+    current: hmac::Key,
+    previous: hmac::Key,
+    rotated_at: Instant,
+}
+
+impl CookieSecrets {
+    fn new() -> Self {
+        let now = Instant::now();
+        CookieSecrets {
+            epoch: now,
+            current: new_cookie_secret(),
+            previous: new_cookie_secret(),
+            rotated_at: now,
+        }
+    }
+
+    fn rotate(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.rotated_at);
+        if elapsed < COOKIE_SECRET_ROTATION {
+            return;
+        }
+        // a previous secret older than a rotation signs no cookie that could still be valid
+        self.previous = if elapsed < 2 * COOKIE_SECRET_ROTATION {
+            std::mem::replace(&mut self.current, new_cookie_secret())
+        } else {
+            self.current = new_cookie_secret();
+            new_cookie_secret()
+        };
+        self.rotated_at = now;
+    }
+
+    fn sign(
+        &mut self,
+        now: Instant,
+        my_verification_tag: u32,
+        peer_verification_tag: u32,
+    ) -> ParamStateCookie {
+        self.rotate(now);
+
+        let mut cookie = BytesMut::with_capacity(COOKIE_STATE_LENGTH + COOKIE_MAC_LENGTH);
+        cookie.put_u64(now.duration_since(self.epoch).as_millis() as u64);
+        cookie.put_u32(my_verification_tag);
+        cookie.put_u32(peer_verification_tag);
+        let tag = hmac::sign(&self.current, &cookie);
+        cookie.extend_from_slice(tag.as_ref());
+
+        ParamStateCookie {
+            cookie: cookie.freeze(),
+        }
+    }
+
+    fn verify(
+        &mut self,
+        now: Instant,
+        cookie: &Bytes,
+        my_verification_tag: u32,
+        peer_verification_tag: u32,
+    ) -> bool {
+        if cookie.len() != COOKIE_STATE_LENGTH + COOKIE_MAC_LENGTH {
+            return false;
+        }
+        let (state, tag) = cookie.split_at(COOKIE_STATE_LENGTH);
+
+        self.rotate(now);
+        if hmac::verify(&self.current, state, tag).is_err()
+            && hmac::verify(&self.previous, state, tag).is_err()
+        {
+            return false;
+        }
+
+        let mut reader = state;
+        let created = Duration::from_millis(reader.get_u64());
+        let age = now.duration_since(self.epoch).saturating_sub(created);
+        age <= VALID_COOKIE_LIFE
+            && reader.get_u32() == my_verification_tag
+            && reader.get_u32() == peer_verification_tag
+    }
+}
+
+fn new_cookie_secret() -> hmac::Key {
+    let mut secret = [0u8; 32];
+    rand::thread_rng().fill(&mut secret);
+    hmac::Key::new(hmac::HMAC_SHA256, &secret)
+}
 
 #[derive(Default, Debug, Clone, PartialEq)]
 pub(crate) struct ParamStateCookie {
@@ -50,13 +157,72 @@ impl Param for ParamStateCookie {
 }
 
 impl ParamStateCookie {
-    pub(crate) fn new() -> Self {
-        let mut cookie = BytesMut::new();
-        cookie.resize(32, 0);
-        rand::thread_rng().fill(cookie.as_mut());
+    /// new creates the cookie sent in an INIT ACK. It carries the verification tags of the
+    /// association and when it was created, signed with the current cookie secret, so that a
+    /// COOKIE ECHO can only return a cookie this process handed out for the same association.
+    pub(crate) fn new(my_verification_tag: u32, peer_verification_tag: u32) -> Self {
+        let mut secrets = COOKIE_SECRETS.lock().unwrap();
+        secrets.sign(Instant::now(), my_verification_tag, peer_verification_tag)
+    }
 
-        ParamStateCookie {
-            cookie: cookie.freeze(),
-        }
+    /// verify tells whether cookie, returned in a COOKIE ECHO, was created by new for these
+    /// verification tags within the last VALID_COOKIE_LIFE. Cookies signed with the previous
+    /// secret are accepted until the next rotation.
+    pub(crate) fn verify(
+        cookie: &Bytes,
+        my_verification_tag: u32,
+        peer_verification_tag: u32,
+    ) -> bool {
+        let mut secrets = COOKIE_SECRETS.lock().unwrap();
+        secrets.verify(
+            Instant::now(),
+            cookie,
+            my_verification_tag,
+            peer_verification_tag,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_state_cookie_verify() {
+        let mut secrets = CookieSecrets::new();
+        let now = Instant::now();
+        let cookie = secrets.sign(now, 1, 2).cookie;
+
+        assert!(secrets.verify(now, &cookie, 1, 2));
+        assert!(
+            !secrets.verify(now, &cookie, 2, 1),
+            "tags of another association"
+        );
+
+        let mut forged = cookie.to_vec();
+        forged[COOKIE_STATE_LENGTH - 1] ^= 1;
+        assert!(
+            !secrets.verify(now, &Bytes::from(forged), 1, 2),
+            "state changed"
+        );
+        assert!(!secrets.verify(now, &cookie.slice(1..), 1, 2), "truncated");
+        assert!(
+            !CookieSecrets::new().verify(now, &cookie, 1, 2),
+            "other secret"
+        );
+    }
+
+    #[test]
+    fn test_state_cookie_expires() {
+        let mut secrets = CookieSecrets::new();
+        let now = secrets.epoch;
+        let cookie = secrets.sign(now, 1, 2).cookie;
+
+        // signed with the previous secret after the rotation
+        let later = now + VALID_COOKIE_LIFE;
+        assert!(secrets.verify(later, &cookie, 1, 2));
+
+        let expired = later + Duration::from_millis(1);
+        assert!(!secrets.verify(expired, &cookie, 1, 2));
     }
 }
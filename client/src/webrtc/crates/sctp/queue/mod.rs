@@ -2,3 +2,4 @@ pub(crate) mod control_queue;
 pub(crate) mod payload_queue;
 pub(crate) mod pending_queue;
 pub(crate) mod reassembly_queue;
+pub(crate) mod send_buffer;
@@ -1,110 +1,301 @@
 use crate::webrtc::sctp::chunk::chunk_payload_data::ChunkPayloadData;
 
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use tokio::sync::Mutex;
+use std::sync::Mutex;
 
 /// pendingBaseQueue
 pub(crate) type PendingBaseQueue = VecDeque<ChunkPayloadData>;
 
-// TODO: benchmark performance between multiple Atomic+Mutex vs one Mutex<PendingQueueInternal>
+/// DEFAULT_STREAM_PRIORITY is the priority of a stream nobody set one for, the "normal"
+/// priority of RFC 8831 Section 6.4.
+pub(crate) const DEFAULT_STREAM_PRIORITY: u16 = 256;
 
-/// pendingQueue
+/// StreamQueue holds the chunks of one stream waiting to be sent
+#[derive(Debug)]
+struct StreamQueue {
+    priority: u16,
+    unordered: PendingBaseQueue,
+    ordered: PendingBaseQueue,
+    // current is the smooth weighted round-robin counter of the stream
+    current: i64,
+    // in_progress is the sub-queue of a message whose first fragment was sent, used with
+    // interleaving only
+    in_progress: Option<bool>,
+}
+
+impl Default for StreamQueue {
+    fn default() -> Self {
+        StreamQueue {
+            priority: DEFAULT_STREAM_PRIORITY,
+            unordered: PendingBaseQueue::new(),
+            ordered: PendingBaseQueue::new(),
+            current: 0,
+            in_progress: None,
+        }
+    }
+}
+
+impl StreamQueue {
+    fn is_empty(&self) -> bool {
+        self.unordered.is_empty() && self.ordered.is_empty()
+    }
+
+    // Unordered messages of a stream go ahead of its ordered ones, unless a message of the
+    // other sub-queue is half sent
+    fn front(&self) -> Option<(&ChunkPayloadData, bool)> {
+        if let Some(unordered) = self.in_progress {
+            if let Some(c) = self.queue_ref(unordered).front() {
+                return Some((c, unordered));
+            }
+        }
+        match self.unordered.front() {
+            Some(c) => Some((c, true)),
+            None => self.ordered.front().map(|c| (c, false)),
+        }
+    }
+
+    fn queue_ref(&self, unordered: bool) -> &PendingBaseQueue {
+        if unordered {
+            &self.unordered
+        } else {
+            &self.ordered
+        }
+    }
+
+    fn queue(&mut self, unordered: bool) -> &mut PendingBaseQueue {
+        if unordered {
+            &mut self.unordered
+        } else {
+            &mut self.ordered
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct PendingQueueInternal {
+    streams: BTreeMap<u16, StreamQueue>,
+    // selected is the stream and sub-queue of a message whose first fragment was sent, its
+    // remaining fragments have to follow before any other message
+    selected: Option<(u16, bool)>,
+    // next is the stream chosen by the last peek, so that the pop that follows takes the
+    // chunk that was peeked
+    next: Option<u16>,
+    // last is the stream served last, equal priorities take turns starting after it
+    last: Option<u16>,
+}
+
+impl PendingQueueInternal {
+    /// schedule picks the stream whose message goes next, see PendingQueue
+    fn schedule(&mut self, strict_priority: bool) -> Option<u16> {
+        if let Some((si, _)) = self.selected {
+            return Some(si);
+        }
+        if let Some(si) = self.next {
+            return Some(si);
+        }
+
+        // non-empty streams, starting after the last one served so that ties rotate
+        let last = self.last;
+        let mut candidates: Vec<u16> = self
+            .streams
+            .iter()
+            .filter(|(_, s)| !s.is_empty())
+            .map(|(si, _)| *si)
+            .collect();
+        if let Some(last) = last {
+            let split = candidates.partition_point(|si| *si <= last);
+            candidates.rotate_left(split);
+        }
+
+        let si = if strict_priority {
+            let mut best: Option<(u16, u16)> = None;
+            for si in candidates {
+                let priority = self.streams[&si].priority;
+                match best {
+                    Some((_, p)) if priority <= p => {}
+                    _ => best = Some((si, priority)),
+                }
+            }
+            best?.0
+        } else {
+            // smooth weighted round-robin, each stream gets a share of the messages
+            // proportional to its priority
+            let mut total = 0i64;
+            let mut best: Option<(u16, i64)> = None;
+            for si in candidates {
+                let s = self.streams.get_mut(&si)?;
+                let weight = i64::from(s.priority.max(1));
+                s.current += weight;
+                total += weight;
+                match best {
+                    Some((_, c)) if s.current <= c => {}
+                    _ => best = Some((si, s.current)),
+                }
+            }
+            let si = best?.0;
+            if let Some(s) = self.streams.get_mut(&si) {
+                s.current -= total;
+            }
+            si
+        };
+
+        self.next = Some(si);
+        Some(si)
+    }
+}
+
+/// pendingQueue holds the chunks of all streams waiting to be sent. Streams are served one
+/// message at a time, the fragments of a message always go out back to back. Which stream's
+/// message is next depends on the stream priorities: by default streams take turns weighted by
+/// their priority, so a low priority stream is slowed down but never starved. With
+/// strict_priority the highest priority stream with data always goes first. Streams of equal
+/// priority take turns in both modes.
+///
+/// Once I-DATA is negotiated the fragments of different streams may interleave (RFC 8260), the
+/// streams then take turns fragment by fragment instead, so that a large message does not hold
+/// back the other streams. Each stream still sends its own messages one after the other.
+///
+/// Streams push into the queue without the association lock, the write loop takes from it while
+/// holding that lock. The queue's own lock is a blocking one that is never held across an await,
+/// so the write loop does not sit on the association lock waiting for a stream's push to finish.
 #[derive(Debug, Default)]
 pub(crate) struct PendingQueue {
-    unordered_queue: Mutex<PendingBaseQueue>,
-    ordered_queue: Mutex<PendingBaseQueue>,
+    internal: Mutex<PendingQueueInternal>,
+    strict_priority: bool,
+    interleaving: AtomicBool,
     queue_len: AtomicUsize,
     n_bytes: AtomicUsize,
-    selected: AtomicBool,
-    unordered_is_selected: AtomicBool,
 }
 
 impl PendingQueue {
-    pub(crate) fn new() -> Self {
-        PendingQueue::default()
+    pub(crate) fn new(strict_priority: bool) -> Self {
+        PendingQueue {
+            strict_priority,
+            ..Default::default()
+        }
     }
 
-    pub(crate) async fn push(&self, c: ChunkPayloadData) {
+    pub(crate) fn push(&self, c: ChunkPayloadData) {
         self.n_bytes.fetch_add(c.user_data.len(), Ordering::SeqCst);
-        if c.unordered {
-            let mut unordered_queue = self.unordered_queue.lock().await;
-            unordered_queue.push_back(c);
-        } else {
-            let mut ordered_queue = self.ordered_queue.lock().await;
-            ordered_queue.push_back(c);
+        {
+            let mut internal = self.internal.lock().unwrap();
+            let unordered = c.unordered;
+            internal
+                .streams
+                .entry(c.stream_identifier)
+                .or_default()
+                .queue(unordered)
+                .push_back(c);
         }
         self.queue_len.fetch_add(1, Ordering::SeqCst);
     }
 
-    pub(crate) async fn peek(&self) -> Option<ChunkPayloadData> {
-        if self.selected.load(Ordering::SeqCst) {
-            if self.unordered_is_selected.load(Ordering::SeqCst) {
-                let unordered_queue = self.unordered_queue.lock().await;
-                return unordered_queue.get(0).cloned();
-            } else {
-                let ordered_queue = self.ordered_queue.lock().await;
-                return ordered_queue.get(0).cloned();
+    /// append queues chunks in order under one lock, so the chunks of another writer never land
+    /// between them
+    pub(crate) fn append(&self, chunks: Vec<ChunkPayloadData>) {
+        let n_chunks = chunks.len();
+        let n_bytes = chunks.iter().map(|c| c.user_data.len()).sum();
+        self.n_bytes.fetch_add(n_bytes, Ordering::SeqCst);
+        {
+            let mut internal = self.internal.lock().unwrap();
+            for c in chunks {
+                let unordered = c.unordered;
+                internal
+                    .streams
+                    .entry(c.stream_identifier)
+                    .or_default()
+                    .queue(unordered)
+                    .push_back(c);
             }
         }
+        self.queue_len.fetch_add(n_chunks, Ordering::SeqCst);
+    }
 
-        let c = {
-            let unordered_queue = self.unordered_queue.lock().await;
-            unordered_queue.get(0).cloned()
-        };
+    /// set_interleaving switches to scheduling per fragment, see PendingQueue
+    pub(crate) fn set_interleaving(&self, interleaving: bool) {
+        self.interleaving.store(interleaving, Ordering::SeqCst);
+    }
+
+    /// set_priority sets the priority of a stream, it applies from the next message on
+    pub(crate) fn set_priority(&self, stream_identifier: u16, priority: u16) {
+        let mut internal = self.internal.lock().unwrap();
+        internal
+            .streams
+            .entry(stream_identifier)
+            .or_default()
+            .priority = priority;
+    }
 
-        if c.is_some() {
-            return c;
+    pub(crate) fn peek(&self) -> Option<ChunkPayloadData> {
+        let mut internal = self.internal.lock().unwrap();
+        if let Some((si, unordered)) = internal.selected {
+            let s = internal.streams.get_mut(&si)?;
+            return s.queue(unordered).front().cloned();
         }
 
-        let ordered_queue = self.ordered_queue.lock().await;
-        ordered_queue.get(0).cloned()
+        let si = internal.schedule(self.strict_priority)?;
+        internal
+            .streams
+            .get(&si)
+            .and_then(|s| s.front())
+            .map(|(c, _)| c.clone())
     }
 
-    pub(crate) async fn pop(
+    pub(crate) fn pop(
         &self,
         beginning_fragment: bool,
         unordered: bool,
     ) -> Option<ChunkPayloadData> {
-        let popped = if self.selected.load(Ordering::SeqCst) {
-            let popped = if self.unordered_is_selected.load(Ordering::SeqCst) {
-                let mut unordered_queue = self.unordered_queue.lock().await;
-                unordered_queue.pop_front()
-            } else {
-                let mut ordered_queue = self.ordered_queue.lock().await;
-                ordered_queue.pop_front()
-            };
-            if let Some(p) = &popped {
-                if p.ending_fragment {
-                    self.selected.store(false, Ordering::SeqCst);
+        let popped = {
+            let mut internal = self.internal.lock().unwrap();
+            if self.interleaving.load(Ordering::SeqCst) {
+                let si = internal.schedule(self.strict_priority)?;
+                internal.next = None;
+                internal.last = Some(si);
+
+                let s = internal.streams.get_mut(&si)?;
+                let (_, unordered) = s.front()?;
+                let popped = s.queue(unordered).pop_front();
+                if let Some(p) = &popped {
+                    s.in_progress = if p.ending_fragment {
+                        None
+                    } else {
+                        Some(unordered)
+                    };
+                }
+                if s.is_empty() {
+                    s.current = 0;
+                }
+                popped
+            } else if let Some((si, selected_unordered)) = internal.selected {
+                let s = internal.streams.get_mut(&si)?;
+                let popped = s.queue(selected_unordered).pop_front();
+                if s.is_empty() {
+                    s.current = 0;
                 }
-            }
-            popped
-        } else {
-            if !beginning_fragment {
-                return None;
-            }
-            if unordered {
-                let popped = {
-                    let mut unordered_queue = self.unordered_queue.lock().await;
-                    unordered_queue.pop_front()
-                };
                 if let Some(p) = &popped {
-                    if !p.ending_fragment {
-                        self.selected.store(true, Ordering::SeqCst);
-                        self.unordered_is_selected.store(true, Ordering::SeqCst);
+                    if p.ending_fragment {
+                        internal.selected = None;
                     }
                 }
                 popped
             } else {
-                let popped = {
-                    let mut ordered_queue = self.ordered_queue.lock().await;
-                    ordered_queue.pop_front()
-                };
+                if !beginning_fragment {
+                    return None;
+                }
+                let si = internal.schedule(self.strict_priority)?;
+                internal.next = None;
+                internal.last = Some(si);
+
+                let s = internal.streams.get_mut(&si)?;
+                let popped = s.queue(unordered).pop_front();
+                if s.is_empty() {
+                    s.current = 0;
+                }
                 if let Some(p) = &popped {
                     if !p.ending_fragment {
-                        self.selected.store(true, Ordering::SeqCst);
-                        self.unordered_is_selected.store(false, Ordering::SeqCst);
+                        internal.selected = Some((si, unordered));
                     }
                 }
                 popped
@@ -119,7 +310,60 @@ impl PendingQueue {
         popped
     }
 
+    /// discard drops the message whose beginning fragment was just peeked, with all of its
+    /// fragments, e.g. when it expired before it could be sent. Returns the number of bytes
+    /// dropped.
+    pub(crate) fn discard(&self, unordered: bool) -> usize {
+        let (n_chunks, n_bytes) = {
+            let mut internal = self.internal.lock().unwrap();
+            let si = match internal.schedule(self.strict_priority) {
+                Some(si) => si,
+                None => return 0,
+            };
+            internal.next = None;
+            internal.last = Some(si);
+
+            let s = match internal.streams.get_mut(&si) {
+                Some(s) => s,
+                None => return 0,
+            };
+            let (mut n_chunks, mut n_bytes) = (0, 0);
+            while let Some(c) = s.queue(unordered).pop_front() {
+                n_chunks += 1;
+                n_bytes += c.user_data.len();
+                if c.ending_fragment {
+                    break;
+                }
+            }
+            if s.is_empty() {
+                s.current = 0;
+            }
+            (n_chunks, n_bytes)
+        };
+
+        self.n_bytes.fetch_sub(n_bytes, Ordering::SeqCst);
+        self.queue_len.fetch_sub(n_chunks, Ordering::SeqCst);
+        n_bytes
+    }
+
     pub(crate) fn len(&self) -> usize {
         self.queue_len.load(Ordering::SeqCst)
     }
+
+    /// get_num_bytes returns the bytes of user data queued
+    pub(crate) fn get_num_bytes(&self) -> usize {
+        self.n_bytes.load(Ordering::SeqCst)
+    }
+
+    /// clear drops every queued chunk, e.g. once the association is closed
+    pub(crate) fn clear(&self) {
+        {
+            let mut internal = self.internal.lock().unwrap();
+            internal.streams.clear();
+            internal.selected = None;
+            internal.next = None;
+        }
+        self.n_bytes.store(0, Ordering::SeqCst);
+        self.queue_len.store(0, Ordering::SeqCst);
+    }
 }
@@ -3,7 +3,9 @@ use crate::webrtc::sctp::util::*;
 
 use crate::webrtc::sctp::error::{Error, Result};
 
+use bytes::Buf;
 use std::cmp::Ordering;
+use std::collections::VecDeque;
 
 fn sort_chunks_by_tsn(c: &mut Vec<ChunkPayloadData>) {
     c.sort_by(|a, b| {
@@ -15,6 +17,16 @@ fn sort_chunks_by_tsn(c: &mut Vec<ChunkPayloadData>) {
     });
 }
 
+fn sort_chunks_by_fsn(c: &mut [ChunkPayloadData]) {
+    c.sort_by(|a, b| {
+        if sna32lt(a.fragment_sequence_number, b.fragment_sequence_number) {
+            Ordering::Less
+        } else {
+            Ordering::Greater
+        }
+    });
+}
+
 fn sort_chunks_by_ssn(c: &mut Vec<ChunkSet>) {
     c.sort_by(|a, b| {
         if sna16lt(a.ssn, b.ssn) {
@@ -25,11 +37,23 @@ fn sort_chunks_by_ssn(c: &mut Vec<ChunkSet>) {
     });
 }
 
-/// chunkSet is a set of chunks that share the same SSN
+fn sort_chunks_by_mid(c: &mut [ChunkSet]) {
+    c.sort_by(|a, b| {
+        if sna32lt(a.mid, b.mid) {
+            Ordering::Less
+        } else {
+            Ordering::Greater
+        }
+    });
+}
+
+/// chunkSet is a set of chunks that share the same SSN, or the same MID with I-DATA
 #[derive(Debug, Clone)]
 pub(crate) struct ChunkSet {
     /// used only with the ordered chunks
     pub(crate) ssn: u16,
+    /// used only with I-DATA chunks
+    pub(crate) mid: u32,
     pub(crate) ppi: PayloadProtocolIdentifier,
     pub(crate) chunks: Vec<ChunkPayloadData>,
 }
@@ -38,11 +62,26 @@ impl ChunkSet {
     pub(crate) fn new(ssn: u16, ppi: PayloadProtocolIdentifier) -> Self {
         ChunkSet {
             ssn,
+            mid: 0,
             ppi,
             chunks: vec![],
         }
     }
 
+    /// new_interleaved creates the set of the I-DATA chunks of the message mid
+    pub(crate) fn new_interleaved(mid: u32) -> Self {
+        ChunkSet {
+            ssn: 0,
+            mid,
+            ppi: PayloadProtocolIdentifier::Unknown,
+            chunks: vec![],
+        }
+    }
+
+    fn is_interleaved(&self) -> bool {
+        self.chunks.first().map(|c| c.interleaved).unwrap_or(false)
+    }
+
     pub(crate) fn push(&mut self, chunk: ChunkPayloadData) -> bool {
         // check if dup
         for c in &self.chunks {
@@ -51,9 +90,19 @@ impl ChunkSet {
             }
         }
 
-        // append and sort
+        // only the first fragment of an I-DATA message carries the PPI
+        if chunk.beginning_fragment {
+            self.ppi = chunk.payload_type;
+        }
+
+        // append and sort, I-DATA fragments are not contiguous in TSN
+        let interleaved = chunk.interleaved;
         self.chunks.push(chunk);
-        sort_chunks_by_tsn(&mut self.chunks);
+        if interleaved {
+            sort_chunks_by_fsn(&mut self.chunks);
+        } else {
+            sort_chunks_by_tsn(&mut self.chunks);
+        }
 
         // Check if we now have a complete set
         self.is_complete()
@@ -82,6 +131,15 @@ impl ChunkSet {
             return false;
         }
 
+        // 3. for I-DATA, the FSN increases by 1 from 0 instead, RFC 8260 Section 2.1
+        if self.is_interleaved() {
+            return self
+                .chunks
+                .iter()
+                .enumerate()
+                .all(|(i, c)| c.fragment_sequence_number == i as u32);
+        }
+
         // 3.
         let mut last_tsn = 0u32;
         for (i, c) in self.chunks.iter().enumerate() {
@@ -105,15 +163,131 @@ impl ChunkSet {
     }
 }
 
+/// sequence returns the number that orders the fragments of a message, the TSN for DATA and the
+/// FSN for I-DATA
+fn sequence(c: &ChunkPayloadData) -> u32 {
+    if c.interleaved {
+        c.fragment_sequence_number
+    } else {
+        c.tsn
+    }
+}
+
+/// contiguous_run returns the number and size of the fragments from chunks[start] on that
+/// follow each other, and whether the last of them ends the message
+fn contiguous_run(chunks: &[ChunkPayloadData], start: usize) -> (usize, usize, bool) {
+    let mut n_chunks = 0;
+    let mut n_bytes = 0;
+    for c in &chunks[start..] {
+        if n_chunks > 0 && sequence(c) != sequence(&chunks[start]).wrapping_add(n_chunks as u32) {
+            break;
+        }
+        n_chunks += 1;
+        n_bytes += c.user_data.len();
+        if c.ending_fragment {
+            return (n_chunks, n_bytes, true);
+        }
+    }
+    (n_chunks, n_bytes, false)
+}
+
+/// MessagePart places the data returned by ReassemblyQueue::read_part within its message
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct MessagePart {
+    /// counts the messages read from the queue, the parts of a message share it
+    pub(crate) message: u32,
+    /// offset of the data within the message
+    pub(crate) offset: u64,
+    /// set on the part that ends the message
+    pub(crate) is_last: bool,
+}
+
+/// PartialSource is where the fragments of a message being read in parts arrive
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum PartialSource {
+    /// the message was complete when reading started, there are no more fragments
+    Complete,
+    /// the ordered set with this SSN, or MID with I-DATA
+    Ordered {
+        ssn: u16,
+        mid: u32,
+        interleaved: bool,
+    },
+    /// unordered_chunks
+    Unordered,
+    /// the unordered I-DATA set with this MID
+    UnorderedInterleaved(u32),
+}
+
+/// PartialMessage is a message read in parts, see ReassemblyQueue::read_part
+#[derive(Debug)]
+struct PartialMessage {
+    source: PartialSource,
+    ppi: PayloadProtocolIdentifier,
+    message: u32,
+    offset: u64,
+    /// TSN, or FSN with I-DATA, of the fragment after the taken ones
+    next: u32,
+    /// fragments taken from the source and not read yet, the first may be read in part
+    chunks: VecDeque<ChunkPayloadData>,
+    /// whether the ending fragment was taken
+    ended: bool,
+}
+
+impl PartialMessage {
+    /// take moves the fragments that continue the message out of chunks
+    fn take(&mut self, chunks: &mut Vec<ChunkPayloadData>) {
+        if self.ended {
+            return;
+        }
+        let start = match chunks.iter().position(|c| sequence(c) == self.next) {
+            Some(start) => start,
+            None => return,
+        };
+        let (n_chunks, _, ended) = contiguous_run(chunks, start);
+        self.chunks.extend(chunks.drain(start..start + n_chunks));
+        self.next = self.next.wrapping_add(n_chunks as u32);
+        self.ended = ended;
+    }
+
+    /// is_source reports whether cset is the ordered set the message is read from
+    fn is_source(&self, cset: &ChunkSet) -> bool {
+        match self.source {
+            PartialSource::Ordered {
+                ssn,
+                mid,
+                interleaved,
+            } => {
+                if interleaved {
+                    cset.mid == mid
+                } else {
+                    cset.ssn == ssn
+                }
+            }
+            _ => false,
+        }
+    }
+}
+
 #[derive(Default, Debug)]
 pub(crate) struct ReassemblyQueue {
     pub(crate) si: u16,
     pub(crate) next_ssn: u16,
+    /// expected MID for next ordered I-DATA chunk
+    pub(crate) next_mid: u32,
     /// expected SSN for next ordered chunk
     pub(crate) ordered: Vec<ChunkSet>,
     pub(crate) unordered: Vec<ChunkSet>,
     pub(crate) unordered_chunks: Vec<ChunkPayloadData>,
+    /// incomplete unordered I-DATA messages
+    pub(crate) unordered_interleaved: Vec<ChunkSet>,
     pub(crate) n_bytes: usize,
+    /// messages larger than this are returned in parts by read_part, 0 turns it off
+    pub(crate) streaming_threshold: usize,
+    /// the message read_part is in the middle of
+    partial: Option<PartialMessage>,
+    /// number of messages read_part started
+    n_messages: u32,
 }
 
 impl ReassemblyQueue {
@@ -126,10 +300,15 @@ impl ReassemblyQueue {
         ReassemblyQueue {
             si,
             next_ssn: 0, // From RFC 4960 Sec 6.5:
+            next_mid: 0, // From RFC 8260 Sec 2.1
             ordered: vec![],
             unordered: vec![],
             unordered_chunks: vec![],
+            unordered_interleaved: vec![],
             n_bytes: 0,
+            streaming_threshold: 0,
+            partial: None,
+            n_messages: 0,
         }
     }
 
@@ -138,6 +317,10 @@ impl ReassemblyQueue {
             return false;
         }
 
+        if chunk.interleaved {
+            return self.push_interleaved(chunk);
+        }
+
         if chunk.unordered {
             // First, insert into unordered_chunks array
             //atomic.AddUint64(&r.n_bytes, uint64(len(chunk.userData)))
@@ -181,6 +364,51 @@ impl ReassemblyQueue {
         }
     }
 
+    /// push_interleaved files an I-DATA chunk under its MID. Unlike DATA, the fragments of
+    /// different messages may arrive mixed, each message is complete once its FSNs have no gap.
+    fn push_interleaved(&mut self, chunk: ChunkPayloadData) -> bool {
+        let mid = chunk.message_identifier;
+
+        if chunk.unordered {
+            self.n_bytes += chunk.user_data.len();
+
+            let i = match self.unordered_interleaved.iter().position(|s| s.mid == mid) {
+                Some(i) => i,
+                None => {
+                    self.unordered_interleaved
+                        .push(ChunkSet::new_interleaved(mid));
+                    self.unordered_interleaved.len() - 1
+                }
+            };
+            if !self.unordered_interleaved[i].push(chunk) {
+                return false;
+            }
+
+            let cset = self.unordered_interleaved.remove(i);
+            self.unordered.push(cset);
+            return true;
+        }
+
+        if sna32lt(mid, self.next_mid) {
+            return false;
+        }
+
+        self.n_bytes += chunk.user_data.len();
+
+        for s in &mut self.ordered {
+            if s.mid == mid {
+                return s.push(chunk);
+            }
+        }
+
+        let mut cset = ChunkSet::new_interleaved(mid);
+        let ok = cset.push(chunk);
+        self.ordered.push(cset);
+        sort_chunks_by_mid(&mut self.ordered);
+
+        ok
+    }
+
     pub(crate) fn find_complete_unordered_chunk_set(&mut self) -> Option<ChunkSet> {
         let mut start_idx = -1isize;
         let mut n_chunks = 0usize;
@@ -237,6 +465,10 @@ impl ReassemblyQueue {
     }
 
     pub(crate) fn is_readable(&self) -> bool {
+        if let Some(partial) = &self.partial {
+            return !partial.chunks.is_empty() || self.has_next_fragment(partial);
+        }
+
         // Check unordered first
         if !self.unordered.is_empty() {
             // The chunk sets in r.unordered should all be complete.
@@ -246,11 +478,21 @@ impl ReassemblyQueue {
         // Check ordered sets
         if !self.ordered.is_empty() {
             let cset = &self.ordered[0];
-            if cset.is_complete() && sna16lte(cset.ssn, self.next_ssn) {
+            if cset.is_complete() && self.is_due(cset) {
                 return true;
             }
         }
-        false
+        self.find_streamable().is_some()
+    }
+
+    /// is_due reports whether an ordered set is next in line, or late because the ones
+    /// before it were skipped
+    fn is_due(&self, cset: &ChunkSet) -> bool {
+        if cset.is_interleaved() {
+            sna32lte(cset.mid, self.next_mid)
+        } else {
+            sna16lte(cset.ssn, self.next_ssn)
+        }
     }
 
     pub(crate) fn read(&mut self, buf: &mut [u8]) -> Result<(usize, PayloadProtocolIdentifier)> {
@@ -263,11 +505,20 @@ impl ReassemblyQueue {
             if !cset.is_complete() {
                 return Err(Error::ErrTryAgain);
             }
-            if sna16gt(cset.ssn, self.next_ssn) {
-                return Err(Error::ErrTryAgain);
-            }
-            if cset.ssn == self.next_ssn {
-                self.next_ssn += 1;
+            if cset.is_interleaved() {
+                if sna32gt(cset.mid, self.next_mid) {
+                    return Err(Error::ErrTryAgain);
+                }
+                if cset.mid == self.next_mid {
+                    self.next_mid = self.next_mid.wrapping_add(1);
+                }
+            } else {
+                if sna16gt(cset.ssn, self.next_ssn) {
+                    return Err(Error::ErrTryAgain);
+                }
+                if cset.ssn == self.next_ssn {
+                    self.next_ssn += 1;
+                }
             }
             self.ordered.remove(0)
         } else {
@@ -297,6 +548,254 @@ impl ReassemblyQueue {
         }
     }
 
+    /// read_part is read for a queue with a streaming_threshold. A message larger than the
+    /// threshold is returned in parts as its fragments arrive in sequence, starting once that
+    /// many bytes of it are, so the queue does not have to hold all of it. The parts of a
+    /// message are returned in order and before the next message, which keeps ordered
+    /// delivery. Smaller messages are returned whole, like read does.
+    pub(crate) fn read_part(
+        &mut self,
+        buf: &mut [u8],
+    ) -> Result<(usize, PayloadProtocolIdentifier, MessagePart)> {
+        if self.partial.is_none() && !self.start_partial() {
+            let (n, ppi) = self.read(buf)?;
+            let part = MessagePart {
+                message: self.n_messages,
+                offset: 0,
+                is_last: true,
+            };
+            self.n_messages = self.n_messages.wrapping_add(1);
+            return Ok((n, ppi, part));
+        }
+
+        self.refill_partial();
+        let partial = match &mut self.partial {
+            Some(partial) if !partial.chunks.is_empty() => partial,
+            _ => return Err(Error::ErrTryAgain),
+        };
+
+        let mut n_written = 0;
+        while n_written < buf.len() {
+            let c = match partial.chunks.front_mut() {
+                Some(c) => c,
+                None => break,
+            };
+            let n = std::cmp::min(c.user_data.len(), buf.len() - n_written);
+            buf[n_written..n_written + n].copy_from_slice(&c.user_data[..n]);
+            n_written += n;
+            if n == c.user_data.len() {
+                partial.chunks.pop_front();
+            } else {
+                c.user_data.advance(n);
+            }
+        }
+
+        let part = MessagePart {
+            message: partial.message,
+            offset: partial.offset,
+            is_last: partial.ended && partial.chunks.is_empty(),
+        };
+        partial.offset += n_written as u64;
+        let ppi = partial.ppi;
+        self.subtract_num_bytes(n_written);
+
+        if part.is_last {
+            self.finish_partial();
+        }
+
+        Ok((n_written, ppi, part))
+    }
+
+    /// find_streamable returns the source of the next message read_part should start to
+    /// return in parts, and the index of its first fragment or set there
+    fn find_streamable(&self) -> Option<(PartialSource, usize)> {
+        if self.streaming_threshold == 0 {
+            return None;
+        }
+
+        // complete messages go first, as with read
+        if let Some(cset) = self.unordered.first() {
+            let n_bytes: usize = cset.chunks.iter().map(|c| c.user_data.len()).sum();
+            return (n_bytes > self.streaming_threshold).then_some((PartialSource::Complete, 0));
+        }
+
+        if let Some(cset) = self.ordered.first() {
+            let begins = cset
+                .chunks
+                .first()
+                .map(|c| c.beginning_fragment)
+                .unwrap_or(false);
+            if begins && self.is_due(cset) {
+                let (_, n_bytes, _) = contiguous_run(&cset.chunks, 0);
+                if n_bytes > self.streaming_threshold
+                    || (n_bytes == self.streaming_threshold && !cset.is_complete())
+                {
+                    let source = PartialSource::Ordered {
+                        ssn: cset.ssn,
+                        mid: cset.mid,
+                        interleaved: cset.is_interleaved(),
+                    };
+                    return Some((source, 0));
+                }
+            }
+        }
+
+        for (i, c) in self.unordered_chunks.iter().enumerate() {
+            if c.beginning_fragment {
+                let (_, n_bytes, _) = contiguous_run(&self.unordered_chunks, i);
+                if n_bytes >= self.streaming_threshold {
+                    return Some((PartialSource::Unordered, i));
+                }
+            }
+        }
+
+        for (i, cset) in self.unordered_interleaved.iter().enumerate() {
+            let begins = cset
+                .chunks
+                .first()
+                .map(|c| c.beginning_fragment)
+                .unwrap_or(false);
+            if begins && contiguous_run(&cset.chunks, 0).1 >= self.streaming_threshold {
+                return Some((PartialSource::UnorderedInterleaved(cset.mid), i));
+            }
+        }
+
+        None
+    }
+
+    /// start_partial starts to read the message find_streamable picks in parts
+    fn start_partial(&mut self) -> bool {
+        let (source, i) = match self.find_streamable() {
+            Some(found) => found,
+            None => return false,
+        };
+
+        let chunks = match source {
+            PartialSource::Complete => &mut self.unordered[i].chunks,
+            PartialSource::Ordered { .. } => &mut self.ordered[i].chunks,
+            PartialSource::Unordered => &mut self.unordered_chunks,
+            PartialSource::UnorderedInterleaved(_) => &mut self.unordered_interleaved[i].chunks,
+        };
+        let first = &chunks[if source == PartialSource::Unordered {
+            i
+        } else {
+            0
+        }];
+        let mut partial = PartialMessage {
+            source,
+            ppi: first.payload_type,
+            message: self.n_messages,
+            offset: 0,
+            next: sequence(first),
+            chunks: VecDeque::new(),
+            ended: false,
+        };
+        partial.take(chunks);
+        self.n_messages = self.n_messages.wrapping_add(1);
+
+        match source {
+            PartialSource::Complete => {
+                self.unordered.remove(i);
+            }
+            PartialSource::UnorderedInterleaved(_)
+                if self.unordered_interleaved[i].chunks.is_empty() =>
+            {
+                self.unordered_interleaved.remove(i);
+            }
+            _ => {}
+        }
+        self.partial = Some(partial);
+        true
+    }
+
+    /// refill_partial takes the fragments that arrived since the last read_part for the
+    /// message it is in the middle of
+    fn refill_partial(&mut self) {
+        let partial = match &mut self.partial {
+            Some(partial) => partial,
+            None => return,
+        };
+
+        match partial.source {
+            PartialSource::Complete => {}
+            PartialSource::Ordered { .. } => {
+                if let Some(cset) = self.ordered.iter_mut().find(|s| partial.is_source(s)) {
+                    partial.take(&mut cset.chunks);
+                }
+            }
+            PartialSource::Unordered => partial.take(&mut self.unordered_chunks),
+            PartialSource::UnorderedInterleaved(mid) => {
+                if let Some(i) = self.unordered_interleaved.iter().position(|s| s.mid == mid) {
+                    partial.take(&mut self.unordered_interleaved[i].chunks);
+                    if self.unordered_interleaved[i].chunks.is_empty() {
+                        self.unordered_interleaved.remove(i);
+                    }
+                }
+            }
+        }
+    }
+
+    /// has_next_fragment reports whether the fragment that continues partial has arrived
+    fn has_next_fragment(&self, partial: &PartialMessage) -> bool {
+        if partial.ended {
+            return false;
+        }
+        let chunks = match partial.source {
+            PartialSource::Complete => return false,
+            PartialSource::Ordered { .. } => {
+                match self.ordered.iter().find(|s| partial.is_source(s)) {
+                    Some(cset) => &cset.chunks,
+                    None => return false,
+                }
+            }
+            PartialSource::Unordered => &self.unordered_chunks,
+            PartialSource::UnorderedInterleaved(mid) => {
+                match self.unordered_interleaved.iter().find(|s| s.mid == mid) {
+                    Some(cset) => &cset.chunks,
+                    None => return false,
+                }
+            }
+        };
+        chunks.iter().any(|c| sequence(c) == partial.next)
+    }
+
+    /// finish_partial ends the message read in parts, an ordered one makes way for the next
+    fn finish_partial(&mut self) {
+        let partial = match self.partial.take() {
+            Some(partial) => partial,
+            None => return,
+        };
+
+        if let PartialSource::Ordered {
+            ssn,
+            mid,
+            interleaved,
+        } = partial.source
+        {
+            self.ordered.retain(|s| !partial.is_source(s));
+            if interleaved {
+                if mid == self.next_mid {
+                    self.next_mid = self.next_mid.wrapping_add(1);
+                }
+            } else if ssn == self.next_ssn {
+                self.next_ssn = self.next_ssn.wrapping_add(1);
+            }
+        }
+    }
+
+    /// abandon_partial drops the message read in parts if the peer abandoned its remaining
+    /// fragments, its last part is then never returned
+    fn abandon_partial(&mut self, abandoned: impl Fn(&PartialMessage) -> bool) {
+        let n_bytes = match &self.partial {
+            Some(partial) if abandoned(partial) => {
+                partial.chunks.iter().map(|c| c.user_data.len()).sum()
+            }
+            _ => return,
+        };
+        self.subtract_num_bytes(n_bytes);
+        self.partial = None;
+    }
+
     /// Remove all fragments in the unordered sets that contains chunks
     /// equal to or older than `new_cumulative_tsn`.
     /// We know all sets in the r.unordered are complete ones.
@@ -316,6 +815,73 @@ impl ReassemblyQueue {
             }
             self.unordered_chunks.drain(..(last_idx + 1) as usize);
         }
+
+        self.abandon_partial(|p| {
+            p.source == PartialSource::Unordered && sna32lte(p.next, new_cumulative_tsn)
+        });
+    }
+
+    /// forward_tsn_for_ordered drops the incomplete ordered messages up to and including
+    /// last_ssn that a FORWARD TSN skipped, RFC 3758 Section 3.6. Ordered delivery resumes after
+    /// last_ssn, complete messages held back by the skipped ones become readable.
+    pub(crate) fn forward_tsn_for_ordered(&mut self, last_ssn: u16) {
+        let mut n_bytes = 0;
+        self.ordered.retain(|s| {
+            let skipped = sna16lte(s.ssn, last_ssn) && !s.is_complete();
+            if skipped {
+                n_bytes += s.chunks.iter().map(|c| c.user_data.len()).sum::<usize>();
+            }
+            !skipped
+        });
+        self.subtract_num_bytes(n_bytes);
+
+        self.abandon_partial(|p| match p.source {
+            PartialSource::Ordered {
+                ssn,
+                interleaved: false,
+                ..
+            } => !p.ended && sna16lte(ssn, last_ssn),
+            _ => false,
+        });
+
+        if sna16lte(self.next_ssn, last_ssn) {
+            self.next_ssn = last_ssn.wrapping_add(1);
+        }
+    }
+
+    /// forward_tsn_for_message drops the fragments of the messages up to and including mid
+    /// that an I-FORWARD-TSN skipped. Ordered delivery resumes after mid, complete messages
+    /// held back by the skipped ones become readable.
+    pub(crate) fn forward_tsn_for_message(&mut self, unordered: bool, mid: u32) {
+        let sets = if unordered {
+            &mut self.unordered_interleaved
+        } else {
+            &mut self.ordered
+        };
+
+        let mut n_bytes = 0;
+        sets.retain(|s| {
+            let skipped = sna32lte(s.mid, mid) && (unordered || !s.is_complete());
+            if skipped {
+                n_bytes += s.chunks.iter().map(|c| c.user_data.len()).sum::<usize>();
+            }
+            !skipped
+        });
+        self.subtract_num_bytes(n_bytes);
+
+        self.abandon_partial(|p| match p.source {
+            PartialSource::UnorderedInterleaved(m) => unordered && sna32lte(m, mid),
+            PartialSource::Ordered {
+                mid: m,
+                interleaved: true,
+                ..
+            } => !unordered && sna32lte(m, mid),
+            _ => false,
+        });
+
+        if !unordered && sna32lte(self.next_mid, mid) {
+            self.next_mid = mid.wrapping_add(1);
+        }
     }
 
     pub(crate) fn subtract_num_bytes(&mut self, n_bytes: usize) {
@@ -330,3 +896,57 @@ impl ReassemblyQueue {
         self.n_bytes
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bytes::Bytes;
+
+    fn chunk(tsn: u32, ssn: u16, fragment: (bool, bool), data: &'static [u8]) -> ChunkPayloadData {
+        ChunkPayloadData {
+            tsn,
+            stream_sequence_number: ssn,
+            beginning_fragment: fragment.0,
+            ending_fragment: fragment.1,
+            payload_type: PayloadProtocolIdentifier::Binary,
+            user_data: Bytes::from_static(data),
+            ..Default::default()
+        }
+    }
+
+    fn read(q: &mut ReassemblyQueue) -> Option<Vec<u8>> {
+        let mut buf = [0u8; 16];
+        q.read(&mut buf).ok().map(|(n, _)| buf[..n].to_vec())
+    }
+
+    #[test]
+    fn test_forward_tsn_for_ordered_skips_only_incomplete_messages() {
+        let mut q = ReassemblyQueue::new(0);
+        q.push(chunk(10, 0, (true, true), b"a"));
+        // the end of SSN 1 was abandoned, it never arrives
+        q.push(chunk(11, 1, (true, false), b"b1"));
+        q.push(chunk(13, 2, (true, true), b"c"));
+        q.push(chunk(14, 3, (true, false), b"d1"));
+        q.push(chunk(15, 3, (false, true), b"d2"));
+        q.push(ChunkPayloadData {
+            unordered: true,
+            ..chunk(16, 0, (true, true), b"u")
+        });
+
+        assert_eq!(read(&mut q).as_deref(), Some(&b"u"[..]));
+        assert_eq!(read(&mut q).as_deref(), Some(&b"a"[..]));
+        assert_eq!(read(&mut q), None, "SSN 1 holds back the later messages");
+
+        q.forward_tsn_for_ordered(1);
+        assert_eq!(read(&mut q).as_deref(), Some(&b"c"[..]));
+        assert_eq!(read(&mut q).as_deref(), Some(&b"d1d2"[..]));
+        assert_eq!(read(&mut q), None);
+        assert_eq!(q.get_num_bytes(), 0);
+
+        // late retransmissions of the skipped and the delivered messages are not delivered
+        q.push(chunk(12, 1, (false, true), b"b2"));
+        q.push(chunk(13, 2, (true, true), b"c"));
+        assert_eq!(read(&mut q), None);
+        assert_eq!(q.get_num_bytes(), 0);
+    }
+}
@@ -0,0 +1,92 @@
+use crate::webrtc::sctp::error::{Error, Result};
+use crate::webrtc::sctp::queue::pending_queue::PendingQueue;
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::Notify;
+
+/// SendBuffer bounds the user data an association holds for sending: the chunks waiting in the
+/// pending queue and the ones in flight, which are kept until the peer acknowledges them, even
+/// once abandoned. Streams reserve room before queueing a message, the association reports
+/// what is in flight as it changes.
+///
+/// A message always fits into an empty buffer, so one larger than max_size is sent on its own.
+/// Streams writing at the same time may overshoot max_size by a message each.
+#[derive(Debug)]
+pub(crate) struct SendBuffer {
+    max_size: usize,
+    pending_queue: Arc<PendingQueue>,
+    outstanding: AtomicUsize,
+    closed: AtomicBool,
+    released: Notify,
+}
+
+// unbounded, for associations and streams that are replaced right away
+impl Default for SendBuffer {
+    fn default() -> Self {
+        SendBuffer::new(usize::MAX, Arc::default())
+    }
+}
+
+impl SendBuffer {
+    pub(crate) fn new(max_size: usize, pending_queue: Arc<PendingQueue>) -> Self {
+        SendBuffer {
+            max_size,
+            pending_queue,
+            outstanding: AtomicUsize::new(0),
+            closed: AtomicBool::new(false),
+            released: Notify::new(),
+        }
+    }
+
+    /// outstanding_bytes returns the bytes sent but not yet acknowledged by the peer
+    pub(crate) fn outstanding_bytes(&self) -> usize {
+        self.outstanding.load(Ordering::SeqCst)
+    }
+
+    /// pending_bytes returns the bytes queued but not yet sent
+    pub(crate) fn pending_bytes(&self) -> usize {
+        self.pending_queue.get_num_bytes()
+    }
+
+    /// set_outstanding_bytes records the bytes in flight and wakes the writers waiting for room
+    pub(crate) fn set_outstanding_bytes(&self, n_bytes: usize) {
+        self.outstanding.store(n_bytes, Ordering::SeqCst);
+        self.released.notify_waiters();
+    }
+
+    /// reserve returns once a message of n_bytes fits. If it does not, it fails with
+    /// ErrSendBufferFull unless wait is set, in which case it waits for the peer to acknowledge
+    /// enough. Once the buffer is closed it fails with ErrStreamClosed.
+    pub(crate) async fn reserve(&self, n_bytes: usize, wait: bool) -> Result<()> {
+        loop {
+            // registered before checking, a release in between is not missed
+            let released = self.released.notified();
+            tokio::pin!(released);
+            released.as_mut().enable();
+
+            if self.closed.load(Ordering::SeqCst) {
+                return Err(Error::ErrStreamClosed);
+            }
+            let used = self.outstanding_bytes() + self.pending_bytes();
+            if used == 0 || used.saturating_add(n_bytes) <= self.max_size {
+                return Ok(());
+            }
+            if !wait {
+                return Err(Error::ErrSendBufferFull);
+            }
+            released.await;
+        }
+    }
+
+    /// wake makes the waiting writers check again, e.g. after their stream was closed
+    pub(crate) fn wake(&self) {
+        self.released.notify_waiters();
+    }
+
+    /// close fails the waiting and future reservations, the association dropped its queues
+    pub(crate) fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        self.set_outstanding_bytes(0);
+    }
+}
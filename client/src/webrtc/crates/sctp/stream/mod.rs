@@ -1,43 +1,168 @@
 use crate::webrtc::sctp::association::AssociationState;
 use crate::webrtc::sctp::chunk::chunk_payload_data::{ChunkPayloadData, PayloadProtocolIdentifier};
 use crate::webrtc::sctp::error::{Error, Result};
-use crate::webrtc::sctp::queue::reassembly_queue::ReassemblyQueue;
+use crate::webrtc::sctp::queue::reassembly_queue::{MessagePart, ReassemblyQueue};
 
 use crate::webrtc::sctp::queue::pending_queue::PendingQueue;
+use crate::webrtc::sctp::queue::send_buffer::SendBuffer;
+use crate::webrtc::util::spawner::Spawner;
 
 use bytes::Bytes;
 use std::fmt;
 use std::future::Future;
 use std::io;
 use std::pin::Pin;
-use std::sync::atomic::{AtomicBool, AtomicU16, AtomicU32, AtomicU8, AtomicUsize, Ordering};
+use std::sync::atomic::{
+    AtomicBool, AtomicU16, AtomicU32, AtomicU64, AtomicU8, AtomicUsize, Ordering,
+};
 use std::sync::Arc;
 use std::task::{Context, Poll};
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 use tokio::sync::{mpsc, Mutex, Notify};
+use tokio::time::Instant;
 
 pub(crate) type OnBufferedAmountLowFn =
     Box<dyn (FnMut() -> Pin<Box<dyn Future<Output = ()> + Send + 'static>>) + Send + Sync>;
 
+pub(crate) type OnCloseFn = Box<
+    dyn (FnOnce(CloseReason) -> Pin<Box<dyn Future<Output = ()> + Send + 'static>>) + Send + Sync,
+>;
+
+/// CloseReason tells why a stream, and the data channel on top of it, was closed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CloseReason {
+    /// This side closed the channel, or the whole connection.
+    LocalClose,
+    /// The remote reset the stream, which is how a data channel is closed by the peer.
+    RemoteReset,
+    /// The connection underneath failed, see TransportError.
+    TransportFailure(TransportError),
+    /// The remote shut the SCTP association down gracefully.
+    AssociationShutdown,
+}
+
+/// TransportError is the failure behind CloseReason::TransportFailure.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TransportError {
+    /// ICE lost connectivity to the remote and gave up.
+    IceFailed,
+    /// The remote aborted the association, with an ABORT or a fatal ERROR chunk.
+    Aborted,
+    /// Reading from the DTLS connection failed.
+    Network(String),
+    /// The remote sent something the association could not process.
+    Protocol(String),
+    /// The remote came back from an ICE restart with another DTLS certificate, which takes a
+    /// new DTLS connection and SCTP association.
+    RemoteCertificateChanged,
+    /// The remote restarted its SCTP association, see SettingEngine::set_sctp_accept_peer_restart.
+    PeerRestart,
+}
+
+/// ReorderStats describes how the network delivered the DATA chunks of unordered messages,
+/// judged by their TSN, the sequence number the sender assigns to every chunk. Retransmissions
+/// filling a gap count as out of order as well.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ReorderStats {
+    /// out_of_order counts the chunks that arrived after a chunk with a higher TSN
+    pub out_of_order: u64,
+    /// max_reorder_distance is the largest number of TSNs a chunk arrived behind the highest
+    /// TSN received before it
+    pub max_reorder_distance: u32,
+    /// duplicates counts the chunks whose TSN was received before
+    pub duplicates: u64,
+}
+
+impl fmt::Display for CloseReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CloseReason::LocalClose => write!(f, "closed locally"),
+            CloseReason::RemoteReset => write!(f, "reset by the remote"),
+            CloseReason::TransportFailure(err) => write!(f, "transport failure: {}", err),
+            CloseReason::AssociationShutdown => write!(f, "association shut down"),
+        }
+    }
+}
+
+impl std::error::Error for CloseReason {}
+
+impl CloseReason {
+    /// is_failure tells whether the channel was closed because the connection failed, as
+    /// opposed to being closed by either side
+    pub fn is_failure(&self) -> bool {
+        matches!(self, CloseReason::TransportFailure(_))
+    }
+
+    /// into_io_error is the error a send on the closed channel fails with, of kind
+    /// std::io::ErrorKind::ConnectionAborted for a failure and NotConnected otherwise, holding
+    /// self as the inner error
+    pub(crate) fn into_io_error(self) -> std::io::Error {
+        let kind = if self.is_failure() {
+            std::io::ErrorKind::ConnectionAborted
+        } else {
+            std::io::ErrorKind::NotConnected
+        };
+        std::io::Error::new(kind, self)
+    }
+}
+
+impl fmt::Display for TransportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransportError::IceFailed => write!(f, "ICE failed"),
+            TransportError::Aborted => write!(f, "association aborted"),
+            TransportError::Network(err) => write!(f, "network error: {}", err),
+            TransportError::Protocol(err) => write!(f, "protocol error: {}", err),
+            TransportError::RemoteCertificateChanged => {
+                write!(f, "remote DTLS certificate changed")
+            }
+            TransportError::PeerRestart => write!(f, "remote restarted the SCTP association"),
+        }
+    }
+}
+
 // TODO: benchmark performance between multiple Atomic+Mutex vs one Mutex<StreamInternal>
 
 /// Stream represents an SCTP stream
 #[derive(Default)]
 pub(crate) struct Stream {
     pub(crate) max_payload_size: u32,
+    /// use_interleaving is whether the association negotiated I-DATA, RFC 8260
+    pub(crate) use_interleaving: bool,
     pub(crate) max_message_size: Arc<AtomicU32>, // clone from association
     pub(crate) state: Arc<AtomicU8>,             // clone from association
+    pub(crate) rwnd_reduced: Arc<AtomicBool>,    // clone from association
     pub(crate) awake_write_loop_ch: Option<Arc<mpsc::Sender<()>>>,
     pub(crate) pending_queue: Arc<PendingQueue>,
+    pub(crate) send_buffer: Arc<SendBuffer>, // clone from association
+    pub(crate) spawner: Spawner,             // clone from association
 
     pub(crate) stream_identifier: u16,
     pub(crate) reassembly_queue: Mutex<ReassemblyQueue>,
     pub(crate) sequence_number: AtomicU16,
+    /// message identifiers of the next ordered and unordered I-DATA messages
+    pub(crate) message_identifier: AtomicU32,
+    pub(crate) unordered_message_identifier: AtomicU32,
     pub(crate) read_notifier: Notify,
     pub(crate) closed: AtomicBool,
+    /// close_reason is set once, by whatever closed the stream first
+    pub(crate) close_reason: std::sync::Mutex<Option<CloseReason>>,
+    pub(crate) on_close: std::sync::Mutex<Option<OnCloseFn>>,
     pub(crate) buffered_amount: AtomicUsize,
     pub(crate) buffered_amount_low: AtomicUsize,
     pub(crate) on_buffered_amount_low: Mutex<Option<OnBufferedAmountLowFn>>,
+    /// messages_expired counts the messages dropped because their deadline passed before they
+    /// were handed to the wire, see write_sctp_with_deadline
+    pub(crate) messages_expired: AtomicU64,
+    /// out_of_order, max_reorder_distance and duplicate_tsns count unordered chunks, see
+    /// ReorderStats
+    pub(crate) out_of_order: AtomicU64,
+    pub(crate) max_reorder_distance: AtomicU32,
+    pub(crate) duplicate_tsns: AtomicU64,
+    /// ordered_data_received is set once the remote sends an ordered message that is not DCEP
+    pub(crate) ordered_data_received: AtomicBool,
     pub(crate) name: String,
 }
 
@@ -45,45 +170,75 @@ impl fmt::Debug for Stream {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Stream")
             .field("max_payload_size", &self.max_payload_size)
+            .field("use_interleaving", &self.use_interleaving)
             .field("max_message_size", &self.max_message_size)
             .field("state", &self.state)
             .field("awake_write_loop_ch", &self.awake_write_loop_ch)
             .field("stream_identifier", &self.stream_identifier)
             .field("reassembly_queue", &self.reassembly_queue)
             .field("sequence_number", &self.sequence_number)
+            .field("message_identifier", &self.message_identifier)
+            .field(
+                "unordered_message_identifier",
+                &self.unordered_message_identifier,
+            )
             .field("closed", &self.closed)
+            .field("close_reason", &self.close_reason)
             .field("buffered_amount", &self.buffered_amount)
             .field("buffered_amount_low", &self.buffered_amount_low)
+            .field("messages_expired", &self.messages_expired)
+            .field("out_of_order", &self.out_of_order)
+            .field("max_reorder_distance", &self.max_reorder_distance)
+            .field("duplicate_tsns", &self.duplicate_tsns)
+            .field("ordered_data_received", &self.ordered_data_received)
             .field("name", &self.name)
             .finish()
     }
 }
 
 impl Stream {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         name: String,
         stream_identifier: u16,
         max_payload_size: u32,
+        use_interleaving: bool,
         max_message_size: Arc<AtomicU32>,
         state: Arc<AtomicU8>,
+        rwnd_reduced: Arc<AtomicBool>,
         awake_write_loop_ch: Option<Arc<mpsc::Sender<()>>>,
         pending_queue: Arc<PendingQueue>,
+        send_buffer: Arc<SendBuffer>,
+        spawner: Spawner,
     ) -> Self {
         Stream {
             max_payload_size,
+            use_interleaving,
             max_message_size,
             state,
+            rwnd_reduced,
             awake_write_loop_ch,
             pending_queue,
+            send_buffer,
+            spawner,
 
             stream_identifier,
             reassembly_queue: Mutex::new(ReassemblyQueue::new(stream_identifier)),
             sequence_number: AtomicU16::new(0),
+            message_identifier: AtomicU32::new(0),
+            unordered_message_identifier: AtomicU32::new(0),
             read_notifier: Notify::new(),
             closed: AtomicBool::new(false),
+            close_reason: std::sync::Mutex::new(None),
+            on_close: std::sync::Mutex::new(None),
             buffered_amount: AtomicUsize::new(0),
             buffered_amount_low: AtomicUsize::new(0),
             on_buffered_amount_low: Mutex::new(None),
+            messages_expired: AtomicU64::new(0),
+            out_of_order: AtomicU64::new(0),
+            max_reorder_distance: AtomicU32::new(0),
+            duplicate_tsns: AtomicU64::new(0),
+            ordered_data_received: AtomicBool::new(false),
             name,
         }
     }
@@ -111,6 +266,11 @@ impl Stream {
             };
 
             if result.is_ok() {
+                // The association advertised a reduced receive window, let it decide whether
+                // this read freed enough of it to send a window update
+                if self.rwnd_reduced.load(Ordering::SeqCst) {
+                    self.awake_write_loop();
+                }
                 return result;
             } else if let Err(err) = result {
                 if Error::ErrShortBuffer == err {
@@ -124,10 +284,59 @@ impl Stream {
         Err(Error::ErrStreamClosed)
     }
 
+    /// read_sctp_part is read_sctp returning the messages larger than the streaming threshold
+    /// in parts, see set_streaming_threshold and ReassemblyQueue::read_part
+    pub(crate) async fn read_sctp_part(
+        &self,
+        p: &mut [u8],
+    ) -> Result<(usize, PayloadProtocolIdentifier, MessagePart)> {
+        while !self.closed.load(Ordering::SeqCst) {
+            let result = {
+                let mut reassembly_queue = self.reassembly_queue.lock().await;
+                reassembly_queue.read_part(p)
+            };
+
+            match result {
+                Ok(part) => {
+                    if self.rwnd_reduced.load(Ordering::SeqCst) {
+                        self.awake_write_loop();
+                    }
+                    return Ok(part);
+                }
+                Err(Error::ErrShortBuffer) => return Err(Error::ErrShortBuffer),
+                Err(_) => {}
+            }
+
+            self.read_notifier.notified().await;
+        }
+
+        Err(Error::ErrStreamClosed)
+    }
+
+    /// set_streaming_threshold makes read_sctp_part return the messages larger than threshold
+    /// bytes in parts as they arrive, 0 waits for every message to be complete. It should stay
+    /// well below the receive buffer of the association, or a message may never get that far.
+    pub(crate) async fn set_streaming_threshold(&self, threshold: usize) {
+        let readable = {
+            let mut reassembly_queue = self.reassembly_queue.lock().await;
+            reassembly_queue.streaming_threshold = threshold;
+            reassembly_queue.is_readable()
+        };
+
+        if readable {
+            self.read_notifier.notify_one();
+        }
+    }
+
     pub(crate) async fn handle_data(&self, pd: ChunkPayloadData) {
+        if !pd.unordered && pd.payload_type != PayloadProtocolIdentifier::Dcep {
+            self.ordered_data_received.store(true, Ordering::SeqCst);
+        }
+
         let readable = {
             let mut reassembly_queue = self.reassembly_queue.lock().await;
-            if reassembly_queue.push(pd) {
+            // a message read in parts can be readable before it is complete
+            if reassembly_queue.push(pd) || reassembly_queue.streaming_threshold > 0 {
                 let readable = reassembly_queue.is_readable();
                 log::debug!("[{}] reassemblyQueue readable={}", self.name, readable);
                 readable
@@ -158,6 +367,34 @@ impl Stream {
         }
     }
 
+    /// handle_forward_tsn_for_ordered drops the incomplete ordered messages up to and including
+    /// ssn that the peer abandoned, see ChunkForwardTsn
+    pub(crate) async fn handle_forward_tsn_for_ordered(&self, ssn: u16) {
+        let readable = {
+            let mut reassembly_queue = self.reassembly_queue.lock().await;
+            reassembly_queue.forward_tsn_for_ordered(ssn);
+            reassembly_queue.is_readable()
+        };
+
+        if readable {
+            self.read_notifier.notify_one();
+        }
+    }
+
+    /// handle_i_forward_tsn drops the fragments of the messages up to and including mid that
+    /// the peer abandoned, see ChunkIForwardTsn
+    pub(crate) async fn handle_i_forward_tsn(&self, unordered: bool, mid: u32) {
+        let readable = {
+            let mut reassembly_queue = self.reassembly_queue.lock().await;
+            reassembly_queue.forward_tsn_for_message(unordered, mid);
+            reassembly_queue.is_readable()
+        };
+
+        if readable {
+            self.read_notifier.notify_one();
+        }
+    }
+
     /// write writes len(p) bytes from p with the default Payload Protocol Identifier
     pub(crate) async fn write(&self, p: &Bytes) -> Result<usize> {
         self.write_sctp(p, PayloadProtocolIdentifier::Binary).await
@@ -168,11 +405,34 @@ impl Stream {
         &self,
         p: &Bytes,
         ppi: PayloadProtocolIdentifier,
+    ) -> Result<usize> {
+        self.write_sctp_with_deadline(p, ppi, None).await
+    }
+
+    /// write_sctp_with_deadline is write_sctp for a message that is only worth sending until
+    /// deadline. If the deadline already passed it fails with ErrMessageExpired, if it passes
+    /// while the message waits in the pending queue, e.g. for the congestion window, the message
+    /// is dropped from the queue when its turn comes. Either way messages_expired counts it.
+    /// Only unordered messages can be dropped without holding back the ones after them, which
+    /// all messages but DCEP are.
+    ///
+    /// While the send buffer of the association is full a message other than DCEP fails with
+    /// ErrSendBufferFull, DCEP waits for room, see SendBuffer.
+    pub(crate) async fn write_sctp_with_deadline(
+        &self,
+        p: &Bytes,
+        ppi: PayloadProtocolIdentifier,
+        deadline: Option<Instant>,
     ) -> Result<usize> {
         if p.len() > self.max_message_size.load(Ordering::SeqCst) as usize {
             return Err(Error::ErrOutboundPacketTooLarge);
         }
 
+        // a message queued on a closed stream would never be sent
+        if self.closed.load(Ordering::SeqCst) {
+            return Err(Error::ErrStreamClosed);
+        }
+
         let state: AssociationState = self.state.load(Ordering::SeqCst).into();
         match state {
             AssociationState::ShutdownSent
@@ -182,13 +442,79 @@ impl Stream {
             _ => {}
         };
 
-        let chunks = self.packetize(p, ppi);
+        if let Some(deadline) = deadline {
+            if Instant::now() >= deadline {
+                self.messages_expired.fetch_add(1, Ordering::SeqCst);
+                return Err(Error::ErrMessageExpired);
+            }
+        }
+
+        // DCEP is sent reliably and waits for room, a data message is dropped like it would
+        // have been lost
+        self.send_buffer
+            .reserve(p.len(), ppi == PayloadProtocolIdentifier::Dcep)
+            .await?;
+        if self.closed.load(Ordering::SeqCst) {
+            return Err(Error::ErrStreamClosed);
+        }
+
+        let chunks = self.packetize(p, ppi, deadline);
         self.send_payload_data(chunks).await?;
 
         Ok(p.len())
     }
 
-    fn packetize(&self, raw: &Bytes, ppi: PayloadProtocolIdentifier) -> Vec<ChunkPayloadData> {
+    /// write_sctp_batch writes messages as one unit: they are queued together and in order, so
+    /// no message written by another task lands between them and the write loop can bundle
+    /// them, woken once. Either all of them are queued or none, if one is too large or the send
+    /// buffer has no room for all of them. Returns the bytes queued, 0 for an empty batch.
+    pub(crate) async fn write_sctp_batch(
+        &self,
+        messages: &[(Bytes, PayloadProtocolIdentifier)],
+    ) -> Result<usize> {
+        if messages.is_empty() {
+            return Ok(0);
+        }
+
+        let max_message_size = self.max_message_size.load(Ordering::SeqCst) as usize;
+        if messages.iter().any(|(p, _)| p.len() > max_message_size) {
+            return Err(Error::ErrOutboundPacketTooLarge);
+        }
+
+        if self.closed.load(Ordering::SeqCst) {
+            return Err(Error::ErrStreamClosed);
+        }
+
+        let state: AssociationState = self.state.load(Ordering::SeqCst).into();
+        match state {
+            AssociationState::ShutdownSent
+            | AssociationState::ShutdownAckSent
+            | AssociationState::ShutdownPending
+            | AssociationState::ShutdownReceived => return Err(Error::ErrStreamClosed),
+            _ => {}
+        };
+
+        let n_bytes = messages.iter().map(|(p, _)| p.len()).sum();
+        self.send_buffer.reserve(n_bytes, false).await?;
+        if self.closed.load(Ordering::SeqCst) {
+            return Err(Error::ErrStreamClosed);
+        }
+
+        let chunks = messages
+            .iter()
+            .flat_map(|(p, ppi)| self.packetize(p, *ppi, None))
+            .collect();
+        self.send_payload_data(chunks).await?;
+
+        Ok(n_bytes)
+    }
+
+    fn packetize(
+        &self,
+        raw: &Bytes,
+        ppi: PayloadProtocolIdentifier,
+        deadline: Option<Instant>,
+    ) -> Vec<ChunkPayloadData> {
         let mut i = 0;
         let mut remaining = raw.len();
 
@@ -201,6 +527,18 @@ impl Stream {
 
         let head_abandoned = Arc::new(AtomicBool::new(false));
         let head_all_inflight = Arc::new(AtomicBool::new(false));
+
+        // RFC 8260 Section 2.1, ordered and unordered I-DATA messages are numbered apart
+        let message_identifier = if !self.use_interleaving {
+            0
+        } else if unordered {
+            self.unordered_message_identifier
+                .fetch_add(1, Ordering::SeqCst)
+        } else {
+            self.message_identifier.fetch_add(1, Ordering::SeqCst)
+        };
+        let mut fragment_sequence_number = 0;
+
         while remaining != 0 {
             let fragment_size = std::cmp::min(self.max_payload_size as usize, remaining); //self.association.max_payload_size
 
@@ -217,6 +555,10 @@ impl Stream {
                 immediate_sack: false,
                 payload_type: ppi,
                 stream_sequence_number: self.sequence_number.load(Ordering::SeqCst),
+                interleaved: self.use_interleaving,
+                message_identifier,
+                fragment_sequence_number,
+                deadline,
                 abandoned: head_abandoned.clone(), // all fragmented chunks use the same abandoned
                 all_inflight: head_all_inflight.clone(), // all fragmented chunks use the same all_inflight
                 ..Default::default()
@@ -226,6 +568,7 @@ impl Stream {
 
             remaining -= fragment_size;
             i += fragment_size;
+            fragment_sequence_number += 1;
         }
 
         // RFC 4960 Sec 6.6
@@ -250,10 +593,81 @@ impl Stream {
             // https://tools.ietf.org/html/rfc6525
             self.send_reset_request(self.stream_identifier).await?;
         }
+        self.mark_closed(CloseReason::LocalClose);
+
+        Ok(())
+    }
+
+    /// mark_closed closes the stream for reading and writing and records why, a reason set
+    /// earlier is kept. The on_close handler runs on the first call.
+    pub(crate) fn mark_closed(&self, reason: CloseReason) {
         self.closed.store(true, Ordering::SeqCst);
         self.read_notifier.notify_waiters(); // broadcast regardless
+        self.send_buffer.wake();
 
-        Ok(())
+        {
+            let mut close_reason = self.close_reason.lock().unwrap();
+            if close_reason.is_some() {
+                return;
+            }
+            *close_reason = Some(reason.clone());
+        }
+
+        let f = self.on_close.lock().unwrap().take();
+        if let Some(f) = f {
+            self.spawner.spawn(f(reason));
+        }
+    }
+
+    /// close_reason returns why the stream was closed, None while it is open.
+    pub(crate) fn close_reason(&self) -> Option<CloseReason> {
+        self.close_reason.lock().unwrap().clone()
+    }
+
+    /// reorder_stats returns how the unordered chunks received on the stream were delivered
+    pub(crate) fn reorder_stats(&self) -> ReorderStats {
+        ReorderStats {
+            out_of_order: self.out_of_order.load(Ordering::SeqCst),
+            max_reorder_distance: self.max_reorder_distance.load(Ordering::SeqCst),
+            duplicates: self.duplicate_tsns.load(Ordering::SeqCst),
+        }
+    }
+
+    /// ordered_data_received tells whether the remote sent a message other than DCEP with
+    /// ordered delivery
+    pub(crate) fn ordered_data_received(&self) -> bool {
+        self.ordered_data_received.load(Ordering::SeqCst)
+    }
+
+    /// record_out_of_order counts an unordered chunk that arrived distance TSNs behind the
+    /// highest TSN received before it
+    pub(crate) fn record_out_of_order(&self, distance: u32) {
+        self.out_of_order.fetch_add(1, Ordering::SeqCst);
+        self.max_reorder_distance
+            .fetch_max(distance, Ordering::SeqCst);
+    }
+
+    /// on_close sets the handler called once the stream is closed, right away if it already is.
+    pub(crate) fn on_close(&self, f: OnCloseFn) {
+        let reason = {
+            let close_reason = self.close_reason.lock().unwrap();
+            if close_reason.is_none() {
+                *self.on_close.lock().unwrap() = Some(f);
+                return;
+            }
+            close_reason.clone()
+        };
+
+        if let Some(reason) = reason {
+            self.spawner.spawn(f(reason));
+        }
+    }
+
+    /// set_priority sets the send priority of the stream against the other streams of the
+    /// association, see PendingQueue.
+    pub(crate) async fn set_priority(&self, priority: u16) {
+        self.pending_queue
+            .set_priority(self.stream_identifier, priority);
     }
 
     /// set_buffered_amount_low_threshold is used to update the threshold.
@@ -338,9 +752,7 @@ impl Stream {
         }
 
         // Push the chunks into the pending queue first.
-        for c in chunks {
-            self.pending_queue.push(c).await;
-        }
+        self.pending_queue.append(chunks);
 
         self.awake_write_loop();
         Ok(())
@@ -362,7 +774,7 @@ impl Stream {
             ..Default::default()
         };
 
-        self.pending_queue.push(c).await;
+        self.pending_queue.push(c);
 
         self.awake_write_loop();
         Ok(())
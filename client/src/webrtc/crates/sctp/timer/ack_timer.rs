@@ -1,3 +1,4 @@
+use crate::webrtc::util::spawner::Spawner;
 use async_trait::async_trait;
 use std::sync::Weak;
 use tokio::sync::{mpsc, Mutex};
@@ -17,15 +18,21 @@ pub(crate) struct AckTimer<T: 'static + AckTimerObserver + Send> {
     pub(crate) timeout_observer: Weak<Mutex<T>>,
     pub(crate) interval: Duration,
     pub(crate) close_tx: Option<mpsc::Sender<()>>,
+    pub(crate) spawner: Spawner,
 }
 
 impl<T: 'static + AckTimerObserver + Send> AckTimer<T> {
     /// newAckTimer creates a new acknowledgement timer used to enable delayed ack.
-    pub(crate) fn new(timeout_observer: Weak<Mutex<T>>, interval: Duration) -> Self {
+    pub(crate) fn new(
+        timeout_observer: Weak<Mutex<T>>,
+        interval: Duration,
+        spawner: Spawner,
+    ) -> Self {
         AckTimer {
             timeout_observer,
             interval,
             close_tx: None,
+            spawner,
         }
     }
 
@@ -40,7 +47,7 @@ impl<T: 'static + AckTimerObserver + Send> AckTimer<T> {
         let interval = self.interval;
         let timeout_observer = self.timeout_observer.clone();
 
-        tokio::spawn(async move {
+        self.spawner.spawn(async move {
             let timer = tokio::time::sleep(interval);
             tokio::pin!(timer);
 
@@ -1,4 +1,5 @@
 use crate::webrtc::sctp::association::RtxTimerId;
+use crate::webrtc::util::spawner::Spawner;
 use async_trait::async_trait;
 use std::sync::{Arc, Weak};
 use tokio::sync::{mpsc, Mutex};
@@ -93,6 +94,7 @@ pub(crate) struct RtxTimer<T: 'static + RtxTimerObserver + Send> {
     pub(crate) id: RtxTimerId,
     pub(crate) max_retrans: usize,
     pub(crate) close_tx: Arc<Mutex<Option<mpsc::Sender<()>>>>,
+    pub(crate) spawner: Spawner,
 }
 
 impl<T: 'static + RtxTimerObserver + Send> RtxTimer<T> {
@@ -103,12 +105,14 @@ impl<T: 'static + RtxTimerObserver + Send> RtxTimer<T> {
         timeout_observer: Weak<Mutex<T>>,
         id: RtxTimerId,
         max_retrans: usize,
+        spawner: Spawner,
     ) -> Self {
         RtxTimer {
             timeout_observer,
             id,
             max_retrans,
             close_tx: Arc::new(Mutex::new(None)),
+            spawner,
         }
     }
 
@@ -136,7 +140,7 @@ impl<T: 'static + RtxTimerObserver + Send> RtxTimer<T> {
         let close_tx = Arc::clone(&self.close_tx);
         let timeout_observer = self.timeout_observer.clone();
 
-        tokio::spawn(async move {
+        self.spawner.spawn(async move {
             let mut n_rtos = 0;
 
             loop {
@@ -5,45 +5,45 @@ use crate::webrtc::sdp::description::common::*;
 /// MediaDescription represents a media type.
 /// <https://tools.ietf.org/html/rfc4566#section-5.14>
 #[derive(Debug, Default, Clone)]
-pub(crate) struct MediaDescription {
+pub struct MediaDescription {
     /// `m=<media> <port>/<number of ports> <proto> <fmt> ...`
     ///
     /// <https://tools.ietf.org/html/rfc4566#section-5.14>
-    pub(crate) media_name: MediaName,
+    pub media_name: MediaName,
 
     /// `i=<session description>`
     ///
     /// <https://tools.ietf.org/html/rfc4566#section-5.4>
-    pub(crate) media_title: Option<Information>,
+    pub media_title: Option<Information>,
 
     /// `c=<nettype> <addrtype> <connection-address>`
     ///
     /// <https://tools.ietf.org/html/rfc4566#section-5.7>
-    pub(crate) connection_information: Option<ConnectionInformation>,
+    pub connection_information: Option<ConnectionInformation>,
 
     /// `b=<bwtype>:<bandwidth>`
     ///
     /// <https://tools.ietf.org/html/rfc4566#section-5.8>
-    pub(crate) bandwidth: Vec<Bandwidth>,
+    pub bandwidth: Vec<Bandwidth>,
 
     /// `k=<method>`
     ///
     /// `k=<method>:<encryption key>`
     ///
     /// <https://tools.ietf.org/html/rfc4566#section-5.12>
-    pub(crate) encryption_key: Option<EncryptionKey>,
+    pub encryption_key: Option<EncryptionKey>,
 
     /// Attributes are the primary means for extending SDP.  Attributes may
     /// be defined to be used as "session-level" attributes, "media-level"
     /// attributes, or both.
     ///
     /// <https://tools.ietf.org/html/rfc4566#section-5.12>
-    pub(crate) attributes: Vec<Attribute>,
+    pub attributes: Vec<Attribute>,
 }
 
 impl MediaDescription {
     /// attribute returns the value of an attribute and if it exists
-    pub(crate) fn attribute(&self, key: &str) -> Option<Option<&str>> {
+    pub fn attribute(&self, key: &str) -> Option<Option<&str>> {
         for a in &self.attributes {
             if a.key == key {
                 return Some(a.value.as_ref().map(|s| s.as_ref()));
@@ -53,24 +53,24 @@ impl MediaDescription {
     }
 
     /// with_property_attribute adds a property attribute 'a=key' to the media description
-    pub(crate) fn with_property_attribute(mut self, key: String) -> Self {
+    pub fn with_property_attribute(mut self, key: String) -> Self {
         self.attributes.push(Attribute::new(key, None));
         self
     }
 
     /// with_value_attribute adds a value attribute 'a=key:value' to the media description
-    pub(crate) fn with_value_attribute(mut self, key: String, value: String) -> Self {
+    pub fn with_value_attribute(mut self, key: String, value: String) -> Self {
         self.attributes.push(Attribute::new(key, Some(value)));
         self
     }
 
     /// with_fingerprint adds a fingerprint to the media description
-    pub(crate) fn with_fingerprint(self, algorithm: String, value: String) -> Self {
+    pub fn with_fingerprint(self, algorithm: String, value: String) -> Self {
         self.with_value_attribute("fingerprint".to_owned(), algorithm + " " + &value)
     }
 
     /// with_ice_credentials adds ICE credentials to the media description
-    pub(crate) fn with_ice_credentials(self, username: String, password: String) -> Self {
+    pub fn with_ice_credentials(self, username: String, password: String) -> Self {
         self.with_value_attribute("ice-ufrag".to_string(), username)
             .with_value_attribute("ice-pwd".to_string(), password)
     }
@@ -81,9 +81,9 @@ impl MediaDescription {
 /// to write it as: <port>/<number of ports> where number of ports is a an
 /// offsetting range.
 #[derive(Debug, Default, Clone)]
-pub(crate) struct RangedPort {
-    pub(crate) value: isize,
-    pub(crate) range: Option<isize>,
+pub struct RangedPort {
+    pub value: isize,
+    pub range: Option<isize>,
 }
 
 impl fmt::Display for RangedPort {
@@ -98,11 +98,11 @@ impl fmt::Display for RangedPort {
 
 /// MediaName describes the "m=" field storage structure.
 #[derive(Debug, Default, Clone)]
-pub(crate) struct MediaName {
-    pub(crate) media: String,
-    pub(crate) port: RangedPort,
-    pub(crate) protos: Vec<String>,
-    pub(crate) formats: Vec<String>,
+pub struct MediaName {
+    pub media: String,
+    pub port: RangedPort,
+    pub protos: Vec<String>,
+    pub formats: Vec<String>,
 }
 
 impl fmt::Display for MediaName {
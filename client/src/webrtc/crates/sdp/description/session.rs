@@ -5,6 +5,7 @@ use url::Url;
 use crate::webrtc::sdp::error::{Error, Result};
 use crate::webrtc::sdp::lexer::*;
 use crate::webrtc::sdp::util::*;
+use crate::webrtc::util::random_source::RandomSource;
 
 use super::common::*;
 use super::media::*;
@@ -15,21 +16,24 @@ pub(crate) const ATTR_KEY_GROUP: &str = "group";
 pub(crate) const ATTR_KEY_CONNECTION_SETUP: &str = "setup";
 pub(crate) const ATTR_KEY_MID: &str = "mid";
 pub(crate) const ATTR_KEY_ICELITE: &str = "ice-lite";
+pub(crate) const ATTR_KEY_SCTP_PORT: &str = "sctp-port";
+pub(crate) const ATTR_KEY_SCTPMAP: &str = "sctpmap";
+pub(crate) const ATTR_KEY_ICE_OPTIONS: &str = "ice-options";
 
 /// Version describes the value provided by the "v=" field which gives
 /// the version of the Session Description Protocol.
-pub(crate) type Version = isize;
+pub type Version = isize;
 
 /// Origin defines the structure for the "o=" field which provides the
 /// originator of the session plus a session identifier and version number.
 #[derive(Debug, Default, Clone)]
-pub(crate) struct Origin {
-    pub(crate) username: String,
-    pub(crate) session_id: u64,
-    pub(crate) session_version: u64,
-    pub(crate) network_type: String,
-    pub(crate) address_type: String,
-    pub(crate) unicast_address: String,
+pub struct Origin {
+    pub username: String,
+    pub session_id: u64,
+    pub session_version: u64,
+    pub network_type: String,
+    pub address_type: String,
+    pub unicast_address: String,
 }
 
 impl fmt::Display for Origin {
@@ -62,24 +66,24 @@ impl Origin {
 
 /// SessionName describes a structured representations for the "s=" field
 /// and is the textual session name.
-pub(crate) type SessionName = String;
+pub type SessionName = String;
 
 /// EmailAddress describes a structured representations for the "e=" line
 /// which specifies email contact information for the person responsible for
 /// the conference.
-pub(crate) type EmailAddress = String;
+pub type EmailAddress = String;
 
 /// PhoneNumber describes a structured representations for the "p=" line
 /// specify phone contact information for the person responsible for the
 /// conference.
-pub(crate) type PhoneNumber = String;
+pub type PhoneNumber = String;
 
 /// TimeZone defines the structured object for "z=" line which describes
 /// repeated sessions scheduling.
 #[derive(Debug, Default, Clone)]
-pub(crate) struct TimeZone {
-    pub(crate) adjustment_time: u64,
-    pub(crate) offset: i64,
+pub struct TimeZone {
+    pub adjustment_time: u64,
+    pub offset: i64,
 }
 
 impl fmt::Display for TimeZone {
@@ -92,24 +96,24 @@ impl fmt::Display for TimeZone {
 /// which are used to specify the start and stop times for a session as well as
 /// repeat intervals and durations for the scheduled session.
 #[derive(Debug, Default, Clone)]
-pub(crate) struct TimeDescription {
+pub struct TimeDescription {
     /// `t=<start-time> <stop-time>`
     ///
     /// <https://tools.ietf.org/html/rfc4566#section-5.9>
-    pub(crate) timing: Timing,
+    pub timing: Timing,
 
     /// `r=<repeat interval> <active duration> <offsets from start-time>`
     ///
     /// <https://tools.ietf.org/html/rfc4566#section-5.10>
-    pub(crate) repeat_times: Vec<RepeatTime>,
+    pub repeat_times: Vec<RepeatTime>,
 }
 
 /// Timing defines the "t=" field's structured representation for the start and
 /// stop times.
 #[derive(Debug, Default, Clone)]
-pub(crate) struct Timing {
-    pub(crate) start_time: u64,
-    pub(crate) stop_time: u64,
+pub struct Timing {
+    pub start_time: u64,
+    pub stop_time: u64,
 }
 
 impl fmt::Display for Timing {
@@ -121,10 +125,10 @@ impl fmt::Display for Timing {
 /// RepeatTime describes the "r=" fields of the session description which
 /// represents the intervals and durations for repeated scheduled sessions.
 #[derive(Debug, Default, Clone)]
-pub(crate) struct RepeatTime {
-    pub(crate) interval: i64,
-    pub(crate) duration: i64,
-    pub(crate) offsets: Vec<i64>,
+pub struct RepeatTime {
+    pub interval: i64,
+    pub duration: i64,
+    pub offsets: Vec<i64>,
 }
 
 impl fmt::Display for RepeatTime {
@@ -140,77 +144,77 @@ impl fmt::Display for RepeatTime {
 /// SessionDescription is a a well-defined format for conveying sufficient
 /// information to discover and participate in a multimedia session.
 #[derive(Debug, Default, Clone)]
-pub(crate) struct SessionDescription {
+pub struct SessionDescription {
     /// `v=0`
     ///
     /// <https://tools.ietf.org/html/rfc4566#section-5.1>
-    pub(crate) version: Version,
+    pub version: Version,
 
     /// `o=<username> <sess-id> <sess-version> <nettype> <addrtype> <unicast-address>`
     ///
     /// <https://tools.ietf.org/html/rfc4566#section-5.2>
-    pub(crate) origin: Origin,
+    pub origin: Origin,
 
     /// `s=<session name>`
     ///
     /// <https://tools.ietf.org/html/rfc4566#section-5.3>
-    pub(crate) session_name: SessionName,
+    pub session_name: SessionName,
 
     /// `i=<session description>`
     ///
     /// <https://tools.ietf.org/html/rfc4566#section-5.4>
-    pub(crate) session_information: Option<Information>,
+    pub session_information: Option<Information>,
 
     /// `u=<uri>`
     ///
     /// <https://tools.ietf.org/html/rfc4566#section-5.5>
-    pub(crate) uri: Option<Url>,
+    pub uri: Option<Url>,
 
     /// `e=<email-address>`
     ///
     /// <https://tools.ietf.org/html/rfc4566#section-5.6>
-    pub(crate) email_address: Option<EmailAddress>,
+    pub email_address: Option<EmailAddress>,
 
     /// `p=<phone-number>`
     ///
     /// <https://tools.ietf.org/html/rfc4566#section-5.6>
-    pub(crate) phone_number: Option<PhoneNumber>,
+    pub phone_number: Option<PhoneNumber>,
 
     /// `c=<nettype> <addrtype> <connection-address>`
     ///
     /// <https://tools.ietf.org/html/rfc4566#section-5.7>
-    pub(crate) connection_information: Option<ConnectionInformation>,
+    pub connection_information: Option<ConnectionInformation>,
 
     /// `b=<bwtype>:<bandwidth>`
     ///
     /// <https://tools.ietf.org/html/rfc4566#section-5.8>
-    pub(crate) bandwidth: Vec<Bandwidth>,
+    pub bandwidth: Vec<Bandwidth>,
 
     /// <https://tools.ietf.org/html/rfc4566#section-5.9>
     /// <https://tools.ietf.org/html/rfc4566#section-5.10>
-    pub(crate) time_descriptions: Vec<TimeDescription>,
+    pub time_descriptions: Vec<TimeDescription>,
 
     /// `z=<adjustment time> <offset> <adjustment time> <offset> ...`
     ///
     /// <https://tools.ietf.org/html/rfc4566#section-5.11>
-    pub(crate) time_zones: Vec<TimeZone>,
+    pub time_zones: Vec<TimeZone>,
 
     /// `k=<method>`
     ///
     /// `k=<method>:<encryption key>`
     ///
     /// <https://tools.ietf.org/html/rfc4566#section-5.12>
-    pub(crate) encryption_key: Option<EncryptionKey>,
+    pub encryption_key: Option<EncryptionKey>,
 
     /// `a=<attribute>`
     ///
     /// `a=<attribute>:<value>`
     ///
     /// <https://tools.ietf.org/html/rfc4566#section-5.13>
-    pub(crate) attributes: Vec<Attribute>,
+    pub attributes: Vec<Attribute>,
 
     /// <https://tools.ietf.org/html/rfc4566#section-5.14>
-    pub(crate) media_descriptions: Vec<MediaDescription>,
+    pub media_descriptions: Vec<MediaDescription>,
 }
 
 /// Reset cleans the SessionDescription, and sets all fields back to their default values
@@ -219,14 +223,19 @@ impl SessionDescription {
     /// Move to webrtc or its own package?
 
     /// NewJSEPSessionDescription creates a new SessionDescription with
-    /// some settings that are required by the JSEP spec.
-    pub(crate) fn new_jsep_session_description(identity: bool) -> Self {
+    /// some settings that are required by the JSEP spec. The session id is drawn from rng and
+    /// the session version derived from now.
+    pub(crate) fn new_jsep_session_description(
+        identity: bool,
+        rng: &RandomSource,
+        now: SystemTime,
+    ) -> Self {
         let d = SessionDescription {
             version: 0,
             origin: Origin {
                 username: "-".to_string(),
-                session_id: new_session_id(),
-                session_version: SystemTime::now()
+                session_id: new_session_id(rng),
+                session_version: now
                     .duration_since(UNIX_EPOCH)
                     .unwrap_or_else(|_| Duration::from_secs(0))
                     .subsec_nanos() as u64,
@@ -262,30 +271,30 @@ impl SessionDescription {
     }
 
     /// WithPropertyAttribute adds a property attribute 'a=key' to the session description
-    pub(crate) fn with_property_attribute(mut self, key: String) -> Self {
+    pub fn with_property_attribute(mut self, key: String) -> Self {
         self.attributes.push(Attribute::new(key, None));
         self
     }
 
     /// WithValueAttribute adds a value attribute 'a=key:value' to the session description
-    pub(crate) fn with_value_attribute(mut self, key: String, value: String) -> Self {
+    pub fn with_value_attribute(mut self, key: String, value: String) -> Self {
         self.attributes.push(Attribute::new(key, Some(value)));
         self
     }
 
     /// WithFingerprint adds a fingerprint to the session description
-    pub(crate) fn with_fingerprint(self, algorithm: String, value: String) -> Self {
+    pub fn with_fingerprint(self, algorithm: String, value: String) -> Self {
         self.with_value_attribute("fingerprint".to_string(), algorithm + " " + value.as_str())
     }
 
     /// WithMedia adds a media description to the session description
-    pub(crate) fn with_media(mut self, md: MediaDescription) -> Self {
+    pub fn with_media(mut self, md: MediaDescription) -> Self {
         self.media_descriptions.push(md);
         self
     }
 
     /// Attribute returns the value of an attribute and if it exists
-    pub(crate) fn attribute(&self, key: &str) -> Option<&String> {
+    pub fn attribute(&self, key: &str) -> Option<&String> {
         for a in &self.attributes {
             if a.key == key {
                 return a.value.as_ref();
@@ -327,7 +336,7 @@ impl SessionDescription {
     ///    b=* (zero or more bandwidth information lines)
     ///    k=* (encryption key)
     ///    a=* (zero or more media attribute lines)
-    pub(crate) fn marshal(&self) -> String {
+    pub fn marshal(&self) -> String {
         let mut result = String::new();
 
         result += key_value_build("v=", Some(&self.version.to_string())).as_str();
@@ -980,8 +989,8 @@ fn unmarshal_bandwidth(value: &str) -> Result<Bandwidth> {
         parts[0] = parts[0].trim_start_matches("X-");
     } else {
         // Set according to currently registered with IANA
-        // https://tools.ietf.org/html/rfc4566#section-5.8
-        let i = index_of(parts[0], &["CT", "AS"]);
+        // https://tools.ietf.org/html/rfc4566#section-5.8, TIAS by RFC 3890
+        let i = index_of(parts[0], &["CT", "AS", "TIAS"]);
         if i == -1 {
             return Err(Error::SdpInvalidValue(parts[0].to_owned()));
         }
@@ -5,4 +5,4 @@ pub(crate) mod util;
 mod error;
 pub(crate) mod lexer;
 
-pub(crate) use error::Error;
+pub use error::Error;
@@ -1,3 +1,4 @@
+use crate::webrtc::util::random_source::RandomSource;
 use std::fmt;
 
 /// ConnectionRole indicates which of the end points should initiate the connection establishment
@@ -70,9 +71,9 @@ impl From<&str> for ConnectionRole {
 /// Session ID is recommended to be constructed by generating a 64-bit
 /// quantity with the highest bit set to zero and the remaining 63-bits
 /// being cryptographically random.
-pub(crate) fn new_session_id() -> u64 {
+pub(crate) fn new_session_id(rng: &RandomSource) -> u64 {
     let c = u64::MAX ^ (1u64 << 63);
-    rand::random::<u64>() & c
+    rng.next_u64() & c
 }
 
 // Codec represents a codec
@@ -68,7 +68,7 @@ impl MappedAddress {
 
         let family = u16::from_be_bytes([v[0], v[1]]);
         if family != FAMILY_IPV6 && family != FAMILY_IPV4 {
-            return Err(Error::Other(format!("bad value {}", family)));
+            return Err(Error::ErrBadAddressFamily(family));
         }
         self.port = u16::from_be_bytes([v[2], v[3]]);
 
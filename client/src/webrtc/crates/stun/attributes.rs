@@ -47,6 +47,7 @@ impl fmt::Display for AttrType {
             ATTR_USE_CANDIDATE => "USE-CANDIDATE",
             ATTR_ICE_CONTROLLED => "ICE-CONTROLLED",
             ATTR_ICE_CONTROLLING => "ICE-CONTROLLING",
+            ATTR_NOMINATION => "NOMINATION",
             ATTR_CHANNEL_NUMBER => "CHANNEL-NUMBER",
             ATTR_LIFETIME => "LIFETIME",
             ATTR_XOR_PEER_ADDRESS => "XOR-PEER-ADDRESS",
@@ -98,6 +99,9 @@ pub(crate) const ATTR_USE_CANDIDATE: AttrType = AttrType(0x0025); // USE-CANDIDA
 pub(crate) const ATTR_ICE_CONTROLLED: AttrType = AttrType(0x8029); // ICE-CONTROLLED
 pub(crate) const ATTR_ICE_CONTROLLING: AttrType = AttrType(0x802A); // ICE-CONTROLLING
 
+/// Attributes from draft-thatcher-ice-renomination.
+pub(crate) const ATTR_NOMINATION: AttrType = AttrType(0xC001); // NOMINATION
+
 /// Attributes from RFC 5766 TURN.
 pub(crate) const ATTR_CHANNEL_NUMBER: AttrType = AttrType(0x000C); // CHANNEL-NUMBER
 pub(crate) const ATTR_LIFETIME: AttrType = AttrType(0x000D); // LIFETIME
@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use super::attributes::AttrType;
+
 use std::io;
 use std::string::FromUtf8Error;
 use tokio::sync::mpsc::error::SendError as MpscSendError;
@@ -29,8 +31,22 @@ pub(crate) enum Error {
     ErrBadUnknownAttrsSize,
     #[error("collector is closed")]
     ErrCollectorClosed,
-    #[error("{0}")]
-    Other(String),
+    #[error("{0:x} is invalid magic cookie (should be 2112a442)")]
+    ErrInvalidMagicCookie(u32),
+    #[error("buffer length {len} is less than {expected} (expected message size)")]
+    ErrMessageTooShort { len: usize, expected: usize },
+    #[error("buffer length {0} is less than 4 (expected header size)")]
+    ErrAttributeHeaderTooShort(usize),
+    #[error("buffer length {len} is less than {expected} (expected value size for {attr})")]
+    ErrAttributeValueTooShort {
+        attr: AttrType,
+        len: usize,
+        expected: usize,
+    },
+    #[error("bad address family {0}")]
+    ErrBadAddressFamily(u16),
+    #[error("unsupported text attribute {0}")]
+    ErrUnsupportedTextAttribute(AttrType),
     #[error("url parse: {0}")]
     Url(#[from] url::ParseError),
     #[error("utf8: {0}")]
@@ -16,7 +16,11 @@ use std::fmt;
 pub(crate) struct MessageIntegrity(pub(crate) Vec<u8>);
 
 fn new_hmac(key: &[u8], message: &[u8]) -> Vec<u8> {
-    let mac = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, key);
+    new_hmac_with(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, key, message)
+}
+
+fn new_hmac_with(algorithm: hmac::Algorithm, key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mac = hmac::Key::new(algorithm, key);
     hmac::sign(&mac, message).as_ref().to_vec()
 }
 
@@ -31,27 +35,82 @@ impl Setter for MessageIntegrity {
     //
     // CPU costly, see BenchmarkMessageIntegrity_AddTo.
     fn add_to(&self, m: &mut Message) -> Result<()> {
-        for a in &m.attributes.0 {
-            // Message should not contain FINGERPRINT attribute
-            // before MESSAGE-INTEGRITY.
-            if a.typ == ATTR_FINGERPRINT {
-                return Err(Error::ErrFingerprintBeforeIntegrity);
-            }
+        add_integrity(m, ATTR_MESSAGE_INTEGRITY, MESSAGE_INTEGRITY_SIZE, |raw| {
+            new_hmac(&self.0, raw)
+        })
+    }
+}
+
+// add_integrity adds an integrity attribute of type t and size bytes whose value is the
+// HMAC computed by hmac, shared by MESSAGE-INTEGRITY and MESSAGE-INTEGRITY-SHA256.
+fn add_integrity<F>(m: &mut Message, t: AttrType, size: usize, hmac: F) -> Result<()>
+where
+    F: FnOnce(&[u8]) -> Vec<u8>,
+{
+    for a in &m.attributes.0 {
+        // Message should not contain FINGERPRINT attribute
+        // before MESSAGE-INTEGRITY.
+        if a.typ == ATTR_FINGERPRINT {
+            return Err(Error::ErrFingerprintBeforeIntegrity);
+        }
+    }
+    // The text used as input to HMAC is the STUN message,
+    // including the header, up to and including the attribute preceding the
+    // MESSAGE-INTEGRITY attribute.
+    let length = m.length;
+    // Adjusting m.Length to contain MESSAGE-INTEGRITY TLV.
+    m.length += (size + ATTRIBUTE_HEADER_SIZE) as u32;
+    m.write_length(); // writing length to m.Raw
+    let v = hmac(&m.raw); // calculating HMAC for adjusted m.Raw
+    m.length = length; // changing m.Length back
+
+    m.add(t, &v);
+
+    Ok(())
+}
+
+// check_integrity checks the integrity attribute of type t against the HMAC computed by
+// hmac, truncated to the length of the received value, which valid_size must accept.
+fn check_integrity<F>(
+    m: &mut Message,
+    t: AttrType,
+    valid_size: fn(usize) -> bool,
+    hmac: F,
+) -> Result<()>
+where
+    F: FnOnce(&[u8]) -> Vec<u8>,
+{
+    let v = m.get(t)?;
+    if !valid_size(v.len()) {
+        return Err(Error::ErrAttributeSizeInvalid);
+    }
+
+    // Adjusting length in header to match m.Raw that was
+    // used when computing HMAC.
+
+    let length = m.length as usize;
+    let mut after_integrity = false;
+    let mut size_reduced = 0;
+
+    for a in &m.attributes.0 {
+        if after_integrity {
+            size_reduced += nearest_padded_value_length(a.length as usize);
+            size_reduced += ATTRIBUTE_HEADER_SIZE;
+        }
+        if a.typ == t {
+            after_integrity = true;
         }
-        // The text used as input to HMAC is the STUN message,
-        // including the header, up to and including the attribute preceding the
-        // MESSAGE-INTEGRITY attribute.
-        let length = m.length;
-        // Adjusting m.Length to contain MESSAGE-INTEGRITY TLV.
-        m.length += (MESSAGE_INTEGRITY_SIZE + ATTRIBUTE_HEADER_SIZE) as u32;
-        m.write_length(); // writing length to m.Raw
-        let v = new_hmac(&self.0, &m.raw); // calculating HMAC for adjusted m.Raw
-        m.length = length; // changing m.Length back
-
-        m.add(ATTR_MESSAGE_INTEGRITY, &v);
-
-        Ok(())
     }
+    m.length -= size_reduced as u32;
+    m.write_length();
+    // start_of_hmac should be first byte of integrity attribute.
+    let start_of_hmac = MESSAGE_HEADER_SIZE + m.length as usize
+        - (ATTRIBUTE_HEADER_SIZE + nearest_padded_value_length(v.len()));
+    let b = &m.raw[..start_of_hmac]; // data before integrity attribute
+    let expected = hmac(b);
+    m.length = length as u32;
+    m.write_length(); // writing length back
+    check_hmac(&v, &expected[..v.len().min(expected.len())])
 }
 
 pub(crate) const MESSAGE_INTEGRITY_SIZE: usize = 20;
@@ -67,33 +126,176 @@ impl MessageIntegrity {
     //
     // CPU costly, see BenchmarkMessageIntegrity_Check.
     pub(crate) fn check(&self, m: &mut Message) -> Result<()> {
-        let v = m.get(ATTR_MESSAGE_INTEGRITY)?;
-
-        // Adjusting length in header to match m.Raw that was
-        // used when computing HMAC.
-
-        let length = m.length as usize;
-        let mut after_integrity = false;
-        let mut size_reduced = 0;
-
-        for a in &m.attributes.0 {
-            if after_integrity {
-                size_reduced += nearest_padded_value_length(a.length as usize);
-                size_reduced += ATTRIBUTE_HEADER_SIZE;
-            }
-            if a.typ == ATTR_MESSAGE_INTEGRITY {
-                after_integrity = true;
-            }
+        check_integrity(
+            m,
+            ATTR_MESSAGE_INTEGRITY,
+            |size| size == MESSAGE_INTEGRITY_SIZE,
+            |b| new_hmac(&self.0, b),
+        )
+    }
+}
+
+// MessageIntegritySha256 represents MESSAGE-INTEGRITY-SHA256 attribute, the HMAC-SHA256
+// counterpart of MESSAGE-INTEGRITY. The value is added untruncated, a received value may be
+// truncated to any multiple of 4 bytes from 16 to 32.
+//
+// RFC 8489 Section 14.6
+#[derive(Default, Clone)]
+pub(crate) struct MessageIntegritySha256(pub(crate) Vec<u8>);
+
+pub(crate) const MESSAGE_INTEGRITY_SHA256_SIZE: usize = 32;
+const MESSAGE_INTEGRITY_SHA256_MIN_SIZE: usize = 16;
+
+fn new_hmac_sha256(key: &[u8], message: &[u8]) -> Vec<u8> {
+    new_hmac_with(hmac::HMAC_SHA256, key, message)
+}
+
+impl fmt::Display for MessageIntegritySha256 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "KEY: 0x{:x?}", self.0)
+    }
+}
+
+impl Setter for MessageIntegritySha256 {
+    // add_to adds MESSAGE-INTEGRITY-SHA256 attribute to message.
+    fn add_to(&self, m: &mut Message) -> Result<()> {
+        add_integrity(
+            m,
+            ATTR_MESSAGE_INTEGRITY_SHA256,
+            MESSAGE_INTEGRITY_SHA256_SIZE,
+            |raw| new_hmac_sha256(&self.0, raw),
+        )
+    }
+}
+
+impl MessageIntegritySha256 {
+    // check checks MESSAGE-INTEGRITY-SHA256 attribute.
+    pub(crate) fn check(&self, m: &mut Message) -> Result<()> {
+        check_integrity(
+            m,
+            ATTR_MESSAGE_INTEGRITY_SHA256,
+            |size| {
+                (MESSAGE_INTEGRITY_SHA256_MIN_SIZE..=MESSAGE_INTEGRITY_SHA256_SIZE).contains(&size)
+                    && size % 4 == 0
+            },
+            |b| new_hmac_sha256(&self.0, b),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::webrtc::stun::agent::TransactionId;
+    use sha2::{Digest, Sha256};
+
+    // RFC 8489 Appendix B.1, a request with long-term authentication, MESSAGE-INTEGRITY-SHA256
+    // and USERHASH. The message length covers the attributes up to MESSAGE-INTEGRITY-SHA256.
+    const USERNAME: &str = "\u{30DE}\u{30C8}\u{30EA}\u{30C3}\u{30AF}\u{30B9}";
+    // "The\u{00AD}M\u{00AA}tr\u{2168}" after OpaqueString processing
+    const PASSWORD: &str = "TheMatrIX";
+    const REALM: &str = "example.org";
+    const NONCE: &str = "obMatJos2AAACf//499k954d6OL34oL9FSTvy64sA";
+    const SAMPLE_REQUEST: [u8; 164] = [
+        0x00, 0x01, 0x00, 0x90, // Request type and message length
+        0x21, 0x12, 0xa4, 0x42, // Magic cookie
+        0x78, 0xad, 0x34, 0x33, 0xc6, 0xad, 0x72, 0xc0, 0x29, 0xda, 0x41,
+        0x2e, // Transaction ID
+        0x00, 0x1e, 0x00, 0x20, // USERHASH attribute header
+        0x4a, 0x3c, 0xf3, 0x8f, 0xef, 0x69, 0x92, 0xbd, 0xa9, 0x52, 0xc6, 0x78, 0x04, 0x17, 0xda,
+        0x0f, 0x24, 0x81, 0x94, 0x15, 0x56, 0x9e, 0x60, 0xb2, 0x05, 0xc4, 0x6e, 0x41, 0x40, 0x7f,
+        0x17, 0x04, // Userhash value (32 bytes)
+        0x00, 0x15, 0x00, 0x29, // NONCE attribute header
+        0x6f, 0x62, 0x4d, 0x61, 0x74, 0x4a, 0x6f, 0x73, 0x32, 0x41, 0x41, 0x41, 0x43, 0x66, 0x2f,
+        0x2f, 0x34, 0x39, 0x39, 0x6b, 0x39, 0x35, 0x34, 0x64, 0x36, 0x4f, 0x4c, 0x33, 0x34, 0x6f,
+        0x4c, 0x39, 0x46, 0x53, 0x54, 0x76, 0x79, 0x36, 0x34, 0x73, 0x41, 0x00, 0x00,
+        0x00, // Nonce value and padding (3 bytes)
+        0x00, 0x14, 0x00, 0x0b, // REALM attribute header
+        0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65, 0x2e, 0x6f, 0x72, 0x67,
+        0x00, // Realm value (11 bytes) and padding (1 byte)
+        0x00, 0x1d, 0x00, 0x04, // PASSWORD-ALGORITHM attribute header
+        0x00, 0x02, 0x00, 0x00, // PASSWORD-ALGORITHM value (4 bytes)
+        0x00, 0x1c, 0x00, 0x20, // MESSAGE-INTEGRITY-SHA256 attribute header
+        0xb5, 0xc7, 0xbf, 0x00, 0x5b, 0x6c, 0x52, 0xa2, 0x1c, 0x51, 0xc5, 0xe8, 0x92, 0xf8, 0x19,
+        0x24, 0x13, 0x62, 0x96, 0xcb, 0x92, 0x7c, 0x43, 0x14, 0x93, 0x09, 0x27, 0x8c, 0xc6, 0x51,
+        0x8e, 0x65, // HMAC-SHA256 value
+    ];
+
+    // long_term_key derives the key of the SHA-256 password algorithm, RFC 8489 Section 9.2.2
+    fn long_term_key() -> Vec<u8> {
+        Sha256::digest(format!("{}:{}:{}", USERNAME, REALM, PASSWORD).as_bytes()).to_vec()
+    }
+
+    fn decode(raw: &[u8]) -> Message {
+        let mut m = Message::new();
+        m.raw = raw.to_vec();
+        m.decode().unwrap();
+        m
+    }
+
+    #[test]
+    fn test_message_integrity_sha256_sample_request() {
+        let userhash = Sha256::digest(format!("{}:{}", USERNAME, REALM).as_bytes());
+        assert_eq!(userhash.as_slice(), &SAMPLE_REQUEST[24..56]);
+
+        let mut m = Message::new();
+        m.set_type(BINDING_REQUEST);
+        m.transaction_id = TransactionId(SAMPLE_REQUEST[8..20].try_into().unwrap());
+        m.write_header();
+        m.add(ATTR_USER_HASH, &userhash);
+        m.add(ATTR_NONCE, NONCE.as_bytes());
+        m.add(ATTR_REALM, REALM.as_bytes());
+        m.add(ATTR_PASSWORD_ALGORITHM, &[0x00, 0x02, 0x00, 0x00]);
+        MessageIntegritySha256(long_term_key())
+            .add_to(&mut m)
+            .unwrap();
+        assert_eq!(m.raw, SAMPLE_REQUEST);
+
+        let mut m = decode(&SAMPLE_REQUEST);
+        MessageIntegritySha256(long_term_key())
+            .check(&mut m)
+            .unwrap();
+        assert_eq!(
+            m.raw, SAMPLE_REQUEST,
+            "check must restore the message length"
+        );
+
+        let mut wrong_key = long_term_key();
+        wrong_key[0] ^= 1;
+        assert!(MessageIntegritySha256(wrong_key).check(&mut m).is_err());
+    }
+
+    #[test]
+    fn test_message_integrity_sha256_truncated() {
+        let key = b"short-term password".to_vec();
+        let mut m = Message::new();
+        m.set_type(BINDING_REQUEST);
+        m.write_header();
+        m.add(ATTR_REALM, REALM.as_bytes());
+        MessageIntegritySha256(key.clone()).add_to(&mut m).unwrap();
+        let full = m.get(ATTR_MESSAGE_INTEGRITY_SHA256).unwrap();
+        assert_eq!(full, new_hmac_sha256(&key, &m.raw[..m.raw.len() - 36]));
+
+        for size in [16, 20, 28, 32, 12, 18] {
+            let mut t = Message::new();
+            t.set_type(BINDING_REQUEST);
+            t.write_header();
+            t.add(ATTR_REALM, REALM.as_bytes());
+            // the value is computed with the length covering the truncated attribute
+            t.length += (ATTRIBUTE_HEADER_SIZE + size) as u32;
+            t.write_length();
+            let v = new_hmac_sha256(&key, &t.raw);
+            t.length -= (ATTRIBUTE_HEADER_SIZE + size) as u32;
+            t.add(ATTR_MESSAGE_INTEGRITY_SHA256, &v[..size]);
+            let mut t = decode(&t.raw);
+
+            let valid = size >= 16 && size % 4 == 0;
+            assert_eq!(
+                MessageIntegritySha256(key.clone()).check(&mut t).is_ok(),
+                valid,
+                "size {}",
+                size
+            );
         }
-        m.length -= size_reduced as u32;
-        m.write_length();
-        // start_of_hmac should be first byte of integrity attribute.
-        let start_of_hmac = MESSAGE_HEADER_SIZE + m.length as usize
-            - (ATTRIBUTE_HEADER_SIZE + MESSAGE_INTEGRITY_SIZE);
-        let b = &m.raw[..start_of_hmac]; // data before integrity attribute
-        let expected = new_hmac(&self.0, b);
-        m.length = length as u32;
-        m.write_length(); // writing length back
-        check_hmac(&v, &expected)
     }
 }
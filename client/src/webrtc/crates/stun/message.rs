@@ -237,17 +237,13 @@ impl Message {
         let full_size = MESSAGE_HEADER_SIZE + size; // len(m.Raw)
 
         if cookie != MAGIC_COOKIE {
-            return Err(Error::Other(format!(
-                "{:x} is invalid magic cookie (should be {:x})",
-                cookie, MAGIC_COOKIE
-            )));
+            return Err(Error::ErrInvalidMagicCookie(cookie));
         }
         if buf.len() < full_size {
-            return Err(Error::Other(format!(
-                "buffer length {} is less than {} (expected message size)",
-                buf.len(),
-                full_size
-            )));
+            return Err(Error::ErrMessageTooShort {
+                len: buf.len(),
+                expected: full_size,
+            });
         }
 
         // saving header data
@@ -264,11 +260,7 @@ impl Message {
         while offset < size {
             // checking that we have enough bytes to read header
             if b.len() < ATTRIBUTE_HEADER_SIZE {
-                return Err(Error::Other(format!(
-                    "buffer length {} is less than {} (expected header size)",
-                    b.len(),
-                    ATTRIBUTE_HEADER_SIZE
-                )));
+                return Err(Error::ErrAttributeHeaderTooShort(b.len()));
             }
 
             let mut a = RawAttribute {
@@ -283,12 +275,11 @@ impl Message {
             offset += ATTRIBUTE_HEADER_SIZE;
             if b.len() < a_buff_l {
                 // checking size
-                return Err(Error::Other(format!(
-                    "buffer length {} is less than {} (expected value size for {})",
-                    b.len(),
-                    a_buff_l,
-                    a.typ
-                )));
+                return Err(Error::ErrAttributeValueTooShort {
+                    attr: a.typ,
+                    len: b.len(),
+                    expected: a_buff_l,
+                });
             }
             a.value = b[..a_l].to_vec();
             offset += a_buff_l;
@@ -431,6 +422,16 @@ pub(crate) const BINDING_SUCCESS: MessageType = MessageType {
     method: METHOD_BINDING,
     class: CLASS_SUCCESS_RESPONSE,
 };
+// Binding indication message type
+pub(crate) const BINDING_INDICATION: MessageType = MessageType {
+    method: METHOD_BINDING,
+    class: CLASS_INDICATION,
+};
+// Binding error response message type
+pub(crate) const BINDING_ERROR: MessageType = MessageType {
+    method: METHOD_BINDING,
+    class: CLASS_ERROR_RESPONSE,
+};
 
 impl fmt::Display for MessageType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -38,7 +38,7 @@ impl Setter for TextAttribute {
             ATTR_REALM => MAX_REALM_B,
             ATTR_SOFTWARE => MAX_SOFTWARE_B,
             ATTR_NONCE => MAX_NONCE_B,
-            _ => return Err(Error::Other(format!("Unsupported AttrType {}", self.attr))),
+            _ => return Err(Error::ErrUnsupportedTextAttribute(self.attr)),
         };
 
         check_overflow(self.attr, text.len(), max_len)?;
@@ -67,7 +67,7 @@ impl TextAttribute {
             ATTR_REALM => {}
             ATTR_SOFTWARE => {}
             ATTR_NONCE => {}
-            _ => return Err(Error::Other(format!("Unsupported AttrType {}", attr))),
+            _ => return Err(Error::ErrUnsupportedTextAttribute(attr)),
         };
 
         let a = m.get(attr)?;
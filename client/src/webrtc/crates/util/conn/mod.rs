@@ -3,6 +3,7 @@ pub(crate) mod conn_disconnected_packet;
 pub(crate) mod conn_pipe;
 pub(crate) mod conn_udp;
 pub(crate) mod conn_udp_listener;
+pub(crate) mod send_error;
 
 use async_trait::async_trait;
 use std::net::SocketAddr;
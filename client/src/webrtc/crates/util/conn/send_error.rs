@@ -0,0 +1,143 @@
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::webrtc::util::error::IoError;
+use crate::webrtc::util::Error;
+
+/// TRANSIENT_RETRIES is how often a send failing for a full socket buffer is retried before
+/// the datagram is dropped
+pub(crate) const TRANSIENT_RETRIES: u32 = 3;
+/// TRANSIENT_BACKOFF is the wait before the first retry, it doubles with each further one
+pub(crate) const TRANSIENT_BACKOFF: Duration = Duration::from_millis(1);
+/// UNREACHABLE_TIMEOUT is how long sends may keep failing for an unreachable network, e.g.
+/// while switching networks, before the transport fails
+pub(crate) const UNREACHABLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// SendErrorClass tells how the outbound path treats an error of sending a datagram
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum SendErrorClass {
+    /// MessageTooLarge is EMSGSIZE, e.g. after the route MTU shrank. The datagram is dropped
+    /// and the SCTP association lowers its MTU.
+    MessageTooLarge,
+    /// Transient is ENOBUFS or EAGAIN, the socket buffer is full for a moment. The send is
+    /// retried a few times before the datagram is dropped.
+    Transient,
+    /// Blocked is EPERM or EACCES, e.g. a firewall rule. The datagram is dropped.
+    Blocked,
+    /// Unreachable is ENETUNREACH, EHOSTUNREACH or ENETDOWN. The datagram is dropped, unless
+    /// sends failed this way for UNREACHABLE_TIMEOUT.
+    Unreachable,
+    /// Fatal is any other error, e.g. EBADF of a closed socket. It fails the transport.
+    Fatal,
+}
+
+impl SendErrorClass {
+    pub(crate) fn of(err: &Error) -> Self {
+        match err {
+            Error::Io(IoError(err)) => SendErrorClass::of_io(err),
+            _ => SendErrorClass::Fatal,
+        }
+    }
+
+    pub(crate) fn of_io(err: &io::Error) -> Self {
+        if let Some(class) = err.raw_os_error().and_then(os_error_class) {
+            return class;
+        }
+
+        match err.kind() {
+            io::ErrorKind::WouldBlock => SendErrorClass::Transient,
+            io::ErrorKind::PermissionDenied => SendErrorClass::Blocked,
+            _ => SendErrorClass::Fatal,
+        }
+    }
+}
+
+#[cfg(not(windows))]
+fn os_error_class(code: i32) -> Option<SendErrorClass> {
+    use nix::errno::Errno;
+
+    match Errno::from_i32(code) {
+        Errno::EMSGSIZE => Some(SendErrorClass::MessageTooLarge),
+        Errno::ENOBUFS | Errno::EAGAIN => Some(SendErrorClass::Transient),
+        Errno::EPERM | Errno::EACCES => Some(SendErrorClass::Blocked),
+        Errno::ENETUNREACH | Errno::EHOSTUNREACH | Errno::ENETDOWN => {
+            Some(SendErrorClass::Unreachable)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(windows)]
+fn os_error_class(code: i32) -> Option<SendErrorClass> {
+    use winapi::shared::winerror::{
+        WSAEACCES, WSAEHOSTUNREACH, WSAEMSGSIZE, WSAENETDOWN, WSAENETUNREACH, WSAENOBUFS,
+        WSAEWOULDBLOCK,
+    };
+
+    match code as u32 {
+        WSAEMSGSIZE => Some(SendErrorClass::MessageTooLarge),
+        WSAENOBUFS | WSAEWOULDBLOCK => Some(SendErrorClass::Transient),
+        WSAEACCES => Some(SendErrorClass::Blocked),
+        WSAENETUNREACH | WSAEHOSTUNREACH | WSAENETDOWN => Some(SendErrorClass::Unreachable),
+        _ => None,
+    }
+}
+
+/// SendErrorCounts counts the datagrams the outbound path of a session dropped or retried
+/// instead of failing the connection, see SocketStats::send_errors
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SendErrorCounts {
+    /// message_too_large counts the datagrams dropped for EMSGSIZE, each lowers the SCTP MTU
+    pub message_too_large: u64,
+    /// transient_retries counts the sends retried after ENOBUFS or EAGAIN
+    pub transient_retries: u64,
+    /// transient_drops counts the datagrams dropped after the retries failed too
+    pub transient_drops: u64,
+    /// blocked counts the datagrams dropped for EPERM or EACCES, e.g. by a firewall rule
+    pub blocked: u64,
+    /// unreachable counts the datagrams dropped for an unreachable network or host
+    pub unreachable: u64,
+}
+
+/// SendErrorStats collects the SendErrorCounts of a session, it is shared by the ICE agent,
+/// which handles the errors, and the SCTP association, which lowers its MTU on EMSGSIZE
+#[derive(Default, Debug)]
+pub(crate) struct SendErrorStats {
+    n_message_too_large: AtomicU64,
+    n_transient_retries: AtomicU64,
+    n_transient_drops: AtomicU64,
+    n_blocked: AtomicU64,
+    n_unreachable: AtomicU64,
+}
+
+impl SendErrorStats {
+    pub(crate) fn inc(&self, class: SendErrorClass) {
+        let counter = match class {
+            SendErrorClass::MessageTooLarge => &self.n_message_too_large,
+            SendErrorClass::Transient => &self.n_transient_drops,
+            SendErrorClass::Blocked => &self.n_blocked,
+            SendErrorClass::Unreachable => &self.n_unreachable,
+            SendErrorClass::Fatal => return,
+        };
+        counter.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub(crate) fn inc_transient_retries(&self) {
+        self.n_transient_retries.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub(crate) fn get_num_message_too_large(&self) -> u64 {
+        self.n_message_too_large.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn counts(&self) -> SendErrorCounts {
+        SendErrorCounts {
+            message_too_large: self.get_num_message_too_large(),
+            transient_retries: self.n_transient_retries.load(Ordering::SeqCst),
+            transient_drops: self.n_transient_drops.load(Ordering::SeqCst),
+            blocked: self.n_blocked.load(Ordering::SeqCst),
+            unreachable: self.n_unreachable.load(Ordering::SeqCst),
+        }
+    }
+}
@@ -39,6 +39,8 @@ pub(crate) enum Error {
     ErrAlreadyClosed,
     #[error("no remAddr defined")]
     ErrNoRemAddr,
+    #[error("not applicable to this conn")]
+    ErrNotApplicable,
     #[error("address already in use")]
     ErrAddressAlreadyInUse,
     #[error("no such UDPConn")]
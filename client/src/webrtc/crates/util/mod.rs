@@ -29,8 +29,8 @@ pub(crate) trait KeyingMaterialExporter {
 pub(crate) enum KeyingMaterialExporterError {
     #[error("tls handshake is in progress")]
     HandshakeInProgress,
-    #[error("context is not supported for export_keying_material")]
-    ContextUnsupported,
+    #[error("context of export_keying_material is longer than 65535 bytes")]
+    ContextTooLong,
     #[error("export_keying_material can not be used with a reserved label")]
     ReservedExportKeyingMaterial,
     #[error("no cipher suite for export_keying_material")]
@@ -51,6 +51,9 @@ pub(crate) mod buffer;
 pub(crate) mod conn;
 pub(crate) mod ifaces;
 pub(crate) mod marshal;
+pub(crate) mod random_source;
+pub(crate) mod redaction;
+pub(crate) mod spawner;
 pub(crate) mod vnet;
 pub(crate) use crate::webrtc::util::buffer::Buffer;
 pub(crate) use crate::webrtc::util::conn::Conn;
@@ -0,0 +1,63 @@
+use rand::rngs::StdRng;
+use rand::{thread_rng, Rng, RngCore, SeedableRng};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// RandomSource is where a connection draws the random values it announces from: ICE
+/// credentials, candidate ids and tie breaker, the SDP session id and the certificate.
+/// By default that is the thread's cryptographically secure generator. A seeded source
+/// replays the same values on every run, see SettingEngine::set_rng_seed. Clones share the
+/// generator.
+#[derive(Clone, Default)]
+pub(crate) struct RandomSource {
+    seeded: Option<Arc<Mutex<StdRng>>>,
+}
+
+impl RandomSource {
+    /// from_seed creates a source that draws the same values for the same seed
+    pub(crate) fn from_seed(seed: u64) -> Self {
+        RandomSource {
+            seeded: Some(Arc::new(Mutex::new(StdRng::seed_from_u64(seed)))),
+        }
+    }
+
+    /// is_seeded tells whether the values are reproducible
+    pub(crate) fn is_seeded(&self) -> bool {
+        self.seeded.is_some()
+    }
+
+    pub(crate) fn fill_bytes(&self, dest: &mut [u8]) {
+        match &self.seeded {
+            Some(rng) => rng.lock().unwrap().fill_bytes(dest),
+            None => thread_rng().fill_bytes(dest),
+        }
+    }
+
+    pub(crate) fn next_u64(&self) -> u64 {
+        match &self.seeded {
+            Some(rng) => rng.lock().unwrap().next_u64(),
+            None => thread_rng().next_u64(),
+        }
+    }
+
+    /// string returns n characters picked from runes
+    pub(crate) fn string(&self, n: usize, runes: &[u8]) -> String {
+        let pick = |rng: &mut dyn RngCore| -> String {
+            (0..n)
+                .map(|_| runes[rng.gen_range(0..runes.len())] as char)
+                .collect()
+        };
+        match &self.seeded {
+            Some(rng) => pick(&mut *rng.lock().unwrap()),
+            None => pick(&mut thread_rng()),
+        }
+    }
+}
+
+impl fmt::Debug for RandomSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RandomSource")
+            .field("seeded", &self.is_seeded())
+            .finish()
+    }
+}
@@ -0,0 +1,150 @@
+use ring::hmac;
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// REDACTED replaces an address with RedactionMode::Full
+pub(crate) const REDACTED: &str = "<redacted>";
+
+/// HASH_PREFIX starts an address hashed with RedactionMode::Hash
+const HASH_PREFIX: &str = "ip-";
+/// HASH_LENGTH is how many bytes of the keyed hash are kept
+const HASH_LENGTH: usize = 8;
+
+/// RedactionMode tells how IP addresses and host names show up in log lines, stats and
+/// diagnostics, see SettingEngine::set_address_redaction. Ports are kept, and what goes on
+/// the wire is never redacted.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum RedactionMode {
+    /// Off shows addresses as they are
+    #[default]
+    Off,
+    /// Hash replaces an address with a keyed hash of it, e.g. "ip-3f2a9c01d4e5b6a7". The key
+    /// is drawn once per process, so within a process the same address always gives the same
+    /// hash and log lines can still be correlated.
+    Hash,
+    /// Full replaces an address with "<redacted>"
+    Full,
+}
+
+impl From<u8> for RedactionMode {
+    fn from(v: u8) -> Self {
+        match v {
+            1 => RedactionMode::Hash,
+            2 => RedactionMode::Full,
+            _ => RedactionMode::Off,
+        }
+    }
+}
+
+// the formatting of candidates and addresses has no session at hand, so the mode is global
+static MODE: AtomicU8 = AtomicU8::new(RedactionMode::Off as u8);
+
+lazy_static! {
+    static ref HASH_KEY: hmac::Key = hmac::Key::new(hmac::HMAC_SHA256, &rand::random::<[u8; 32]>());
+}
+
+/// set_mode changes how addresses are formatted from now on, in the whole process
+pub(crate) fn set_mode(mode: RedactionMode) {
+    MODE.store(mode as u8, Ordering::SeqCst);
+}
+
+pub(crate) fn mode() -> RedactionMode {
+    MODE.load(Ordering::SeqCst).into()
+}
+
+/// redact_address returns address, an IP address or host name, as the mode shows it. An empty
+/// address, e.g. a missing related address, stays empty.
+pub(crate) fn redact_address(address: &str) -> String {
+    match mode() {
+        _ if address.is_empty() => String::new(),
+        RedactionMode::Off => address.to_owned(),
+        RedactionMode::Hash => hash(address),
+        RedactionMode::Full => REDACTED.to_owned(),
+    }
+}
+
+fn hash(address: &str) -> String {
+    let tag = hmac::sign(&HASH_KEY, address.as_bytes());
+    let hex: String = tag.as_ref()[..HASH_LENGTH]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    format!("{}{}", HASH_PREFIX, hex)
+}
+
+/// redact_candidate returns the value of a candidate attribute, "foundation component
+/// transport priority address port typ ...", with the address and the related address
+/// redacted as the mode says
+pub(crate) fn redact_candidate(value: &str) -> String {
+    if mode() == RedactionMode::Off {
+        return value.to_owned();
+    }
+
+    let mut fields: Vec<String> = value.split(' ').map(str::to_owned).collect();
+    if let Some(address) = fields.get_mut(4) {
+        *address = redact_address(address);
+    }
+    for i in 5..fields.len().saturating_sub(1) {
+        if fields[i] == "raddr" {
+            fields[i + 1] = redact_address(&fields[i + 1]);
+        }
+    }
+    fields.join(" ")
+}
+
+/// redact_sdp returns sdp with the addresses of the origin, connection and candidate lines
+/// redacted as the mode says
+#[cfg(feature = "serde")]
+pub(crate) fn redact_sdp(sdp: &str) -> String {
+    if mode() == RedactionMode::Off {
+        return sdp.to_owned();
+    }
+
+    sdp.split_inclusive('\n')
+        .map(|line| {
+            let content = line.trim_end_matches(['\r', '\n']);
+            let eol = &line[content.len()..];
+            if let Some(value) = content.strip_prefix("a=candidate:") {
+                format!("a=candidate:{}{}", redact_candidate(value), eol)
+            } else if content.starts_with("o=") || content.starts_with("c=") {
+                // the address is the last field of both, "... IN IP4 address"
+                match content.rsplit_once(' ') {
+                    Some((head, address)) => format!("{} {}{}", head, redact_address(address), eol),
+                    None => line.to_owned(),
+                }
+            } else {
+                line.to_owned()
+            }
+        })
+        .collect()
+}
+
+/// Redacted formats an IP address, host name or socket address with the address redacted as
+/// the mode says, e.g. for log lines: `log::debug!("from {}", Redacted(&addr))`
+pub(crate) struct Redacted<T>(pub(crate) T);
+
+impl<T: fmt::Display> fmt::Display for Redacted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if mode() == RedactionMode::Off {
+            return write!(f, "{}", self.0);
+        }
+
+        let value = self.0.to_string();
+        match value.parse::<SocketAddr>() {
+            Ok(addr) => write!(
+                f,
+                "{}:{}",
+                redact_address(&addr.ip().to_string()),
+                addr.port()
+            ),
+            Err(_) => write!(f, "{}", redact_address(&value)),
+        }
+    }
+}
+
+impl<T: fmt::Display> fmt::Debug for Redacted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
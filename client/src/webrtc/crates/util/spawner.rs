@@ -0,0 +1,32 @@
+use std::future::Future;
+use tokio::runtime::Handle;
+use tokio::task::JoinHandle;
+
+/// Spawner is where a connection runs its internal tasks: the ICE agent, DTLS, SCTP and data
+/// channel loops. By default that is the runtime they are spawned from, usually the one the
+/// connection was created on. With a handle they run on its runtime instead, see
+/// SettingEngine::set_runtime_handle. The tasks end when the connection closes either way.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Spawner {
+    handle: Option<Handle>,
+}
+
+impl Spawner {
+    /// new creates a spawner running the tasks on the runtime of handle
+    pub(crate) fn new(handle: Handle) -> Self {
+        Spawner {
+            handle: Some(handle),
+        }
+    }
+
+    pub(crate) fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        match &self.handle {
+            Some(handle) => handle.spawn(future),
+            None => tokio::spawn(future),
+        }
+    }
+}
@@ -5,8 +5,8 @@ use crate::webrtc::util::vnet::net::UDP_STR;
 use std::collections::{HashMap, HashSet};
 use std::net::IpAddr;
 use std::sync::Arc;
-use std::time::SystemTime;
 use tokio::sync::Mutex;
+use tokio::time::Instant;
 
 // EndpointDependencyType defines a type of behavioral dependendency on the
 // remote endpoint's IP address or port number. This is used for the two
@@ -58,18 +58,18 @@ pub(crate) struct Mapping {
     mapped: String,                       // "<mapped-ip>:<mapped-port>"
     bound: String,                        // key: "[<remote-ip>[:<remote-port>]]"
     filters: Arc<Mutex<HashSet<String>>>, // key: "[<remote-ip>[:<remote-port>]]"
-    expires: Arc<Mutex<SystemTime>>,      // time to expire
+    expires: Arc<Mutex<Instant>>,         // time to expire
 }
 
 impl Default for Mapping {
     fn default() -> Self {
         Mapping {
-            proto: String::new(),                             // "udp" or "tcp"
-            local: String::new(),                             // "<local-ip>:<local-port>"
-            mapped: String::new(),                            // "<mapped-ip>:<mapped-port>"
-            bound: String::new(), // key: "[<remote-ip>[:<remote-port>]]"
+            proto: String::new(),                          // "udp" or "tcp"
+            local: String::new(),                          // "<local-ip>:<local-port>"
+            mapped: String::new(),                         // "<mapped-ip>:<mapped-port>"
+            bound: String::new(),                          // key: "[<remote-ip>[:<remote-port>]]"
             filters: Arc::new(Mutex::new(HashSet::new())), // key: "[<remote-ip>[:<remote-port>]]"
-            expires: Arc::new(Mutex::new(SystemTime::now())), // time to expire
+            expires: Arc::new(Mutex::new(Instant::now())), // time to expire
         }
     }
 }
@@ -108,11 +108,7 @@ impl NetworkAddressTranslator {
                     let dst_port = from.destination_addr().port();
                     to.set_destination_addr(&format!("{}:{}", dst_ip, dst_port))?;
                 } else {
-                    return Err(Error::Other(format!(
-                        "drop {} as {:?}",
-                        from,
-                        Error::ErrNoAssociatedLocalAddress
-                    )));
+                    return Err(Error::ErrNoAssociatedLocalAddress);
                 }
             } else {
                 // Normal (NAPT) behavior
@@ -125,12 +121,7 @@ impl NetworkAddressTranslator {
                     {
                         let filters = m.filters.lock().await;
                         if !filters.contains(&filter_key) {
-                            return Err(Error::Other(format!(
-                                "drop {} as the remote {} {:?}",
-                                from,
-                                filter_key,
-                                Error::ErrHasNoPermission
-                            )));
+                            return Err(Error::ErrHasNoPermission);
                         }
                     }
 
@@ -144,11 +135,7 @@ impl NetworkAddressTranslator {
 
                     to.set_destination_addr(&m.local)?;
                 } else {
-                    return Err(Error::Other(format!(
-                        "drop {} as {:?}",
-                        from,
-                        Error::ErrNoNatBindingFound
-                    )));
+                    return Err(Error::ErrNoNatBindingFound);
                 }
             }
 
@@ -171,12 +158,12 @@ impl NetworkAddressTranslator {
         let (in_key, out_key) = {
             let inbound_map = self.inbound_map.lock().await;
             if let Some(m) = inbound_map.get(i_key) {
-                let now = SystemTime::now();
+                let now = Instant::now();
 
                 {
                     let expires = m.expires.lock().await;
                     // check if this Mapping is expired
-                    if now.duration_since(*expires).is_ok() {
+                    if now >= *expires {
                         expired = true;
                     }
                 }
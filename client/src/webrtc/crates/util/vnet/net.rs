@@ -350,32 +350,7 @@ impl Net {
 
             Net::VNet(Arc::new(Mutex::new(vnet)))
         } else {
-            let interfaces = match ifaces::ifaces() {
-                Ok(ifs) => ifs,
-                Err(_) => vec![],
-            };
-
-            let mut m: HashMap<String, Vec<IpNet>> = HashMap::new();
-            for iface in interfaces {
-                if let Some(addrs) = m.get_mut(&iface.name) {
-                    if let Some(addr) = iface.addr {
-                        if let Ok(inet) = Interface::convert(addr, iface.mask) {
-                            addrs.push(inet);
-                        }
-                    }
-                } else if let Some(addr) = iface.addr {
-                    if let Ok(inet) = Interface::convert(addr, iface.mask) {
-                        m.insert(iface.name, vec![inet]);
-                    }
-                }
-            }
-
-            let mut ifs = vec![];
-            for (name, addrs) in m.into_iter() {
-                ifs.push(Interface::new(name, addrs));
-            }
-
-            Net::Ifs(ifs)
+            Net::Ifs(system_interfaces())
         }
     }
 
@@ -390,6 +365,16 @@ impl Net {
         }
     }
 
+    // poll_interfaces returns the current network interfaces. Unlike get_interfaces, which
+    // returns the system interfaces as they were when the Net was created, it reads them again.
+    #[cfg(feature = "network-monitor")]
+    pub(crate) async fn poll_interfaces(&self) -> Vec<Interface> {
+        match self {
+            Net::VNet(_) => self.get_interfaces().await,
+            Net::Ifs(_) => system_interfaces(),
+        }
+    }
+
     // IsVirtual tests if the virtual network is enabled.
     pub(crate) fn is_virtual(&self) -> bool {
         match self {
@@ -408,3 +393,32 @@ impl Net {
         }
     }
 }
+
+fn system_interfaces() -> Vec<Interface> {
+    let interfaces = match ifaces::ifaces() {
+        Ok(ifs) => ifs,
+        Err(_) => vec![],
+    };
+
+    let mut m: HashMap<String, Vec<IpNet>> = HashMap::new();
+    for iface in interfaces {
+        if let Some(addrs) = m.get_mut(&iface.name) {
+            if let Some(addr) = iface.addr {
+                if let Ok(inet) = Interface::convert(addr, iface.mask) {
+                    addrs.push(inet);
+                }
+            }
+        } else if let Some(addr) = iface.addr {
+            if let Ok(inet) = Interface::convert(addr, iface.mask) {
+                m.insert(iface.name, vec![inet]);
+            }
+        }
+    }
+
+    let mut ifs = vec![];
+    for (name, addrs) in m.into_iter() {
+        ifs.push(Interface::new(name, addrs));
+    }
+
+    ifs
+}
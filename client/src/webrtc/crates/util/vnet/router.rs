@@ -77,7 +77,7 @@ impl Nic for Router {
                     }
                 }
                 Err(err) => {
-                    log::warn!("[{}] {}", self.name, err);
+                    log::warn!("[{}] drop {} as {}", self.name, c, err);
                     return;
                 }
             }
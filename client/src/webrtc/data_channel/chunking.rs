@@ -0,0 +1,162 @@
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::collections::{BTreeMap, HashMap};
+use thiserror::Error as ThisError;
+use tokio::time::{Duration, Instant};
+
+/// The header in front of every chunk: transfer id, index and total chunk count of the
+/// transfer, each a big endian u32
+pub(crate) const CHUNK_HEADER_LEN: usize = 12;
+
+/// chunk_count returns how many chunks of chunk_size bytes a message of len bytes is split
+/// into. An empty message still takes one chunk.
+pub(crate) fn chunk_count(len: usize, chunk_size: usize) -> usize {
+    len.div_ceil(chunk_size).max(1)
+}
+
+/// chunk returns the chunk with the given index of data, split into total chunks of chunk_size
+/// bytes, with its header
+pub(crate) fn chunk(
+    data: &Bytes,
+    chunk_size: usize,
+    transfer_id: u32,
+    index: u32,
+    total: u32,
+) -> Bytes {
+    let start = (index as usize * chunk_size).min(data.len());
+    let end = (start + chunk_size).min(data.len());
+
+    let mut buf = BytesMut::with_capacity(CHUNK_HEADER_LEN + end - start);
+    buf.put_u32(transfer_id);
+    buf.put_u32(index);
+    buf.put_u32(total);
+    buf.put_slice(&data[start..end]);
+    buf.freeze()
+}
+
+/// ChunkError is what ChunkReassembler reports for chunks it cannot use and transfers it gives
+/// up on
+#[derive(ThisError, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkError {
+    /// InvalidHeader indicates a message that is not a chunk sent by send_chunked: shorter than
+    /// the header, an index beyond the total, or a total differing from the earlier chunks of
+    /// the transfer
+    #[error("invalid chunk header in a message of {len} bytes")]
+    InvalidHeader { len: usize },
+    /// TimedOut indicates a transfer that received no chunk for the timeout of the reassembler
+    /// and was dropped, with how many of its chunks had arrived
+    #[error("transfer {transfer_id} timed out with {received} of {total} chunks received")]
+    TimedOut {
+        transfer_id: u32,
+        received: u32,
+        total: u32,
+    },
+}
+
+/// ChunkReassembler puts the messages sent with DataChannelSender::send_chunked back together
+/// on the receiving side. Every message received on the channel is handed to push, which
+/// returns the whole message once its last missing chunk arrives. Chunks may arrive in any
+/// order, duplicates are ignored.
+///
+/// A transfer missing chunks, e.g. lost on an unreliable channel, is kept until no chunk of it
+/// arrived for the timeout. expire drops such transfers and reports them, it should be called
+/// periodically, e.g. whenever recv times out.
+#[derive(Debug)]
+pub struct ChunkReassembler {
+    timeout: Duration,
+    transfers: HashMap<u32, Transfer>,
+    duplicates: u64,
+}
+
+#[derive(Debug)]
+struct Transfer {
+    total: u32,
+    chunks: BTreeMap<u32, Bytes>,
+    len: usize,
+    last_chunk: Instant,
+}
+
+impl ChunkReassembler {
+    /// new creates a reassembler that drops transfers after timeout without a new chunk
+    pub fn new(timeout: Duration) -> Self {
+        ChunkReassembler {
+            timeout,
+            transfers: HashMap::new(),
+            duplicates: 0,
+        }
+    }
+
+    /// push takes a message received on the channel and returns the transfer id and the whole
+    /// message if it was the last missing chunk of its transfer
+    pub fn push(&mut self, message: Bytes) -> Result<Option<(u32, Bytes)>, ChunkError> {
+        let len = message.len();
+        if len < CHUNK_HEADER_LEN {
+            return Err(ChunkError::InvalidHeader { len });
+        }
+        let mut payload = message;
+        let transfer_id = payload.get_u32();
+        let index = payload.get_u32();
+        let total = payload.get_u32();
+        if index >= total {
+            return Err(ChunkError::InvalidHeader { len });
+        }
+
+        let transfer = self
+            .transfers
+            .entry(transfer_id)
+            .or_insert_with(|| Transfer {
+                total,
+                chunks: BTreeMap::new(),
+                len: 0,
+                last_chunk: Instant::now(),
+            });
+        if transfer.total != total {
+            return Err(ChunkError::InvalidHeader { len });
+        }
+        transfer.last_chunk = Instant::now();
+        if transfer.chunks.contains_key(&index) {
+            self.duplicates += 1;
+            return Ok(None);
+        }
+        transfer.len += payload.len();
+        transfer.chunks.insert(index, payload);
+        if transfer.chunks.len() < total as usize {
+            return Ok(None);
+        }
+
+        let transfer = self.transfers.remove(&transfer_id).unwrap();
+        let mut data = BytesMut::with_capacity(transfer.len);
+        for chunk in transfer.chunks.into_values() {
+            data.put(chunk);
+        }
+        Ok(Some((transfer_id, data.freeze())))
+    }
+
+    /// expire drops the transfers that received no chunk for the timeout and returns a
+    /// ChunkError::TimedOut for each
+    pub fn expire(&mut self) -> Vec<ChunkError> {
+        let timeout = self.timeout;
+        let mut expired = vec![];
+        self.transfers.retain(|&transfer_id, transfer| {
+            if transfer.last_chunk.elapsed() < timeout {
+                return true;
+            }
+            expired.push(ChunkError::TimedOut {
+                transfer_id,
+                received: transfer.chunks.len() as u32,
+                total: transfer.total,
+            });
+            false
+        });
+        expired
+    }
+
+    /// pending returns how many transfers are incomplete
+    pub fn pending(&self) -> usize {
+        self.transfers.len()
+    }
+
+    /// duplicates returns how many chunks were ignored because they had arrived before
+    pub fn duplicates(&self) -> u64 {
+        self.duplicates
+    }
+}
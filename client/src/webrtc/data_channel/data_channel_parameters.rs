@@ -0,0 +1,104 @@
+use crate::webrtc::data_channel::internal::data_channel::Config;
+use crate::webrtc::data_channel::internal::message::message_channel_open::ChannelType;
+
+/// ChannelReliability is the delivery a data channel runs with, see
+/// RTCDataChannel::negotiated_reliability
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelReliability {
+    /// ordered tells whether messages are delivered in the order they were sent
+    pub ordered: bool,
+    /// max_retransmits is how often a message is retransmitted at most
+    pub max_retransmits: Option<u16>,
+    /// max_packet_lifetime is for how many milliseconds a message is retransmitted at most
+    pub max_packet_lifetime: Option<u16>,
+}
+
+impl ChannelReliability {
+    /// is_reliable tells whether every message is retransmitted until it arrives
+    pub fn is_reliable(&self) -> bool {
+        self.max_retransmits.is_none() && self.max_packet_lifetime.is_none()
+    }
+
+    /// from_channel_type gives the reliability a DCEP channel type and reliability parameter
+    /// stand for
+    pub(crate) fn from_channel_type(channel_type: ChannelType, reliability_parameter: u32) -> Self {
+        let (max_retransmits, max_packet_lifetime) = match channel_type {
+            ChannelType::PartialReliableRexmit | ChannelType::PartialReliableRexmitUnordered => {
+                (Some(reliability_parameter as u16), None)
+            }
+            ChannelType::PartialReliableTimed | ChannelType::PartialReliableTimedUnordered => {
+                (None, Some(reliability_parameter as u16))
+            }
+            ChannelType::Reliable | ChannelType::ReliableUnordered => (None, None),
+        };
+
+        ChannelReliability {
+            ordered: channel_type.ordered(),
+            max_retransmits,
+            max_packet_lifetime,
+        }
+    }
+}
+
+/// DataChannelParameters describes the configuration of the DataChannel.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub(crate) struct DataChannelParameters {
+    pub(crate) label: String,
+    pub(crate) protocol: String,
+    pub(crate) ordered: bool,
+    pub(crate) max_packet_lifetime: Option<u16>,
+    pub(crate) max_retransmits: Option<u16>,
+    pub(crate) priority: u16,
+}
+
+impl DataChannelParameters {
+    /// to_config converts the parameters into the DCEP configuration sent in DATA_CHANNEL_OPEN
+    pub(crate) fn to_config(&self) -> Config {
+        let (channel_type, reliability_parameter) =
+            match (self.max_retransmits, self.max_packet_lifetime) {
+                (Some(max_retransmits), _) if self.ordered => {
+                    (ChannelType::PartialReliableRexmit, max_retransmits as u32)
+                }
+                (Some(max_retransmits), _) => (
+                    ChannelType::PartialReliableRexmitUnordered,
+                    max_retransmits as u32,
+                ),
+                (None, Some(max_packet_lifetime)) if self.ordered => (
+                    ChannelType::PartialReliableTimed,
+                    max_packet_lifetime as u32,
+                ),
+                (None, Some(max_packet_lifetime)) => (
+                    ChannelType::PartialReliableTimedUnordered,
+                    max_packet_lifetime as u32,
+                ),
+                (None, None) if self.ordered => (ChannelType::Reliable, 0),
+                (None, None) => (ChannelType::ReliableUnordered, 0),
+            };
+
+        Config {
+            channel_type,
+            priority: self.priority,
+            reliability_parameter,
+            label: self.label.clone(),
+            protocol: self.protocol.clone(),
+        }
+    }
+}
+
+impl From<&Config> for DataChannelParameters {
+    fn from(config: &Config) -> Self {
+        let reliability = ChannelReliability::from_channel_type(
+            config.channel_type,
+            config.reliability_parameter,
+        );
+
+        DataChannelParameters {
+            label: config.label.clone(),
+            protocol: config.protocol.clone(),
+            ordered: reliability.ordered,
+            max_packet_lifetime: reliability.max_packet_lifetime,
+            max_retransmits: reliability.max_retransmits,
+            priority: config.priority,
+        }
+    }
+}
@@ -1,18 +1,30 @@
-use crate::webrtc::data_channel::internal::error::Result;
-use crate::webrtc::data_channel::internal::{message::message_channel_open::*, message::*};
+use crate::webrtc::data_channel::data_channel_parameters::ChannelReliability;
+use crate::webrtc::data_channel::internal::error::{Error, Result};
+use crate::webrtc::data_channel::internal::{
+    message::message_channel_ack::*, message::message_channel_open::*, message::*,
+};
 
 use crate::webrtc::sctp::{
-    association::Association, chunk::chunk_payload_data::PayloadProtocolIdentifier, stream::*,
+    association::Association, chunk::chunk_payload_data::PayloadProtocolIdentifier,
+    queue::reassembly_queue::MessagePart, stream::*,
 };
 use crate::webrtc::util::marshal::*;
 
 use bytes::{Buf, Bytes};
 use derive_builder::Builder;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use tokio::time::Instant;
 
 /// Config is used to configure the data channel.
 #[derive(Eq, PartialEq, Default, Clone, Debug, Builder)]
 pub(crate) struct Config {
+    #[builder(default)]
+    pub(crate) channel_type: ChannelType,
+    #[builder(default)]
+    pub(crate) priority: u16,
+    #[builder(default)]
+    pub(crate) reliability_parameter: u32,
     #[builder(default)]
     pub(crate) label: String,
     #[builder(default)]
@@ -22,12 +34,17 @@ pub(crate) struct Config {
 /// DataChannel represents a data channel
 #[derive(Debug, Default, Clone)]
 pub(crate) struct DataChannel {
+    pub(crate) config: Config,
     stream: Arc<Stream>,
+    /// acked is set once the remote confirmed the channel, by a DATA_CHANNEL_ACK or by sending
+    /// on it. Channels the remote opened are confirmed from the start.
+    acked: Arc<AtomicBool>,
 }
 
 impl DataChannel {
-    pub(crate) fn new(stream: Arc<Stream>) -> Self {
+    pub(crate) fn new(stream: Arc<Stream>, config: Config) -> Self {
         Self {
+            config,
             stream,
             ..Default::default()
         }
@@ -40,13 +57,74 @@ impl DataChannel {
         config: Config,
     ) -> Result<Self> {
         let stream = association.open_stream(identifier).await?;
+        stream.set_priority(config.priority).await;
 
         Self::client(stream, config).await
     }
 
+    /// Accept is used to accept incoming data channels over SCTP. The label and protocol of the
+    /// returned channel are taken from the remote's DATA_CHANNEL_OPEN, non UTF-8 bytes in them are
+    /// replaced rather than failing the channel. A stream whose first message is not a valid
+    /// DATA_CHANNEL_OPEN is reset, the association carries on.
+    pub(crate) async fn accept(association: &Arc<Association>) -> Result<Self> {
+        let stream = association
+            .accept_stream()
+            .await
+            .ok_or(Error::ErrStreamClosed)?;
+
+        let config = match Self::read_data_channel_open(&stream).await {
+            Ok(config) => config,
+            Err(err) => {
+                if let Err(close_err) = stream.close().await {
+                    log::warn!(
+                        "Failed to reset stream {} after a malformed DATA_CHANNEL_OPEN: {}",
+                        stream.stream_identifier,
+                        close_err
+                    );
+                }
+                return Err(err);
+            }
+        };
+
+        stream.set_priority(config.priority).await;
+        let data_channel = DataChannel::new(stream, config);
+        data_channel.acked.store(true, Ordering::SeqCst);
+        data_channel.write_data_channel_ack().await?;
+
+        Ok(data_channel)
+    }
+
+    /// read_data_channel_open reads the DATA_CHANNEL_OPEN a remote opened stream starts with
+    async fn read_data_channel_open(stream: &Arc<Stream>) -> Result<Config> {
+        let mut buf = vec![0u8; MAX_DATA_CHANNEL_OPEN_LEN];
+        let (n, ppi) = stream.read_sctp(&mut buf).await?;
+
+        if ppi != PayloadProtocolIdentifier::Dcep {
+            return Err(Error::InvalidPayloadProtocolIdentifier(ppi as u8));
+        }
+
+        let mut read_buf = &buf[..n];
+        let msg = Message::unmarshal(&mut read_buf)?;
+
+        if let Message::DataChannelOpen(dco) = msg {
+            Ok(Config {
+                channel_type: dco.channel_type,
+                priority: dco.priority,
+                reliability_parameter: dco.reliability_parameter,
+                label: String::from_utf8_lossy(&dco.label).into_owned(),
+                protocol: String::from_utf8_lossy(&dco.protocol).into_owned(),
+            })
+        } else {
+            Err(Error::InvalidMessageType(msg.message_type() as u8))
+        }
+    }
+
     /// Client opens a data channel over an SCTP stream
     async fn client(stream: Arc<Stream>, config: Config) -> Result<Self> {
         let msg = Message::DataChannelOpen(DataChannelOpen {
+            channel_type: config.channel_type,
+            priority: config.priority,
+            reliability_parameter: config.reliability_parameter,
             label: config.label.bytes().collect(),
             protocol: config.protocol.bytes().collect(),
         })
@@ -56,7 +134,80 @@ impl DataChannel {
             .write_sctp(&msg, PayloadProtocolIdentifier::Dcep)
             .await?;
 
-        Ok(DataChannel::new(stream))
+        Ok(DataChannel::new(stream, config))
+    }
+
+    /// stream_identifier returns the SCTP stream identifier the channel runs on
+    pub(crate) fn stream_identifier(&self) -> u16 {
+        self.stream.stream_identifier
+    }
+
+    /// set_priority sets the send priority of the underlying stream
+    pub(crate) async fn set_priority(&self, priority: u16) {
+        self.stream.set_priority(priority).await;
+    }
+
+    /// is_closed reports whether the underlying stream was reset or closed
+    pub(crate) fn is_closed(&self) -> bool {
+        self.stream.closed.load(Ordering::SeqCst)
+    }
+
+    /// close_reason returns why the underlying stream was closed, None while it is open
+    pub(crate) fn close_reason(&self) -> Option<CloseReason> {
+        self.stream.close_reason()
+    }
+
+    /// close resets the underlying stream, which closes the channel in both directions
+    pub(crate) async fn close(&self) -> Result<()> {
+        Ok(self.stream.close().await?)
+    }
+
+    /// on_close sets the handler called once the underlying stream is closed
+    pub(crate) fn on_close(&self, f: OnCloseFn) {
+        self.stream.on_close(f)
+    }
+
+    /// buffered_amount returns the number of bytes of data currently queued to be sent
+    pub(crate) fn buffered_amount(&self) -> usize {
+        self.stream.buffered_amount.load(Ordering::SeqCst)
+    }
+
+    /// max_message_size returns the largest message the association sends, a read buffer of
+    /// that size fits the messages of a peer with the same limit
+    pub(crate) fn max_message_size(&self) -> usize {
+        self.stream.max_message_size.load(Ordering::SeqCst) as usize
+    }
+
+    /// messages_expired returns how many messages were dropped because their deadline passed
+    /// before they were sent
+    pub(crate) fn messages_expired(&self) -> u64 {
+        self.stream.messages_expired.load(Ordering::SeqCst)
+    }
+
+    /// reorder_stats returns how the unordered messages received on the channel were delivered
+    pub(crate) fn reorder_stats(&self) -> ReorderStats {
+        self.stream.reorder_stats()
+    }
+
+    /// negotiated_reliability returns the reliability the remote runs the channel with as far as
+    /// can be told, None while a channel opened here is not confirmed yet. It is the one from
+    /// the DATA_CHANNEL_OPEN, or fully reliable and ordered once the remote sends an ordered
+    /// message on an unordered channel, which a remote ignoring the channel type does.
+    pub(crate) fn negotiated_reliability(&self) -> Option<ChannelReliability> {
+        if !self.acked.load(Ordering::SeqCst) {
+            return None;
+        }
+
+        if !self.config.channel_type.ordered() && self.stream.ordered_data_received() {
+            return Some(ChannelReliability::from_channel_type(
+                ChannelType::Reliable,
+                0,
+            ));
+        }
+        Some(ChannelReliability::from_channel_type(
+            self.config.channel_type,
+            self.config.reliability_parameter,
+        ))
     }
 
     /// Read reads a packet of len(p) bytes as binary data
@@ -70,6 +221,10 @@ impl DataChannel {
             //TODO: add handling of cancel read_data_channel
             let (mut n, ppi) = match self.stream.read_sctp(buf).await {
                 Ok((n, ppi)) => (n, ppi),
+                // the message did not fit in buf and is dropped, the stream is fine
+                Err(crate::webrtc::sctp::Error::ErrShortBuffer) => {
+                    return Err(crate::webrtc::sctp::Error::ErrShortBuffer.into());
+                }
                 Err(err) => {
                     // When the peer sees that an incoming stream was
                     // reset, it also resets its corresponding outgoing stream.
@@ -83,11 +238,8 @@ impl DataChannel {
             match ppi {
                 PayloadProtocolIdentifier::Dcep => {
                     let mut data = &buf[..n];
-                    match self.handle_dcep(&mut data).await {
-                        Ok(()) => {}
-                        Err(err) => {
-                            log::error!("Failed to handle DCEP: {:?}", err);
-                        }
+                    if let Err(err) = self.handle_dcep(&mut data) {
+                        log::warn!("Ignoring malformed DCEP message: {}", err);
                     }
                     continue;
                 }
@@ -97,6 +249,9 @@ impl DataChannel {
                 _ => {}
             };
 
+            // RFC 8832 S6, user data on the stream confirms the channel like an ACK would
+            self.acked.store(true, Ordering::SeqCst);
+
             match ppi {
                 PayloadProtocolIdentifier::StringEmpty | PayloadProtocolIdentifier::BinaryEmpty => {
                     n = 0;
@@ -108,7 +263,61 @@ impl DataChannel {
         }
     }
 
-    async fn handle_dcep<B>(&self, data: &mut B) -> Result<()>
+    /// set_streaming_threshold makes read_data_channel_part return the messages larger than
+    /// threshold bytes in parts, see Stream::set_streaming_threshold
+    pub(crate) async fn set_streaming_threshold(&self, threshold: usize) {
+        self.stream.set_streaming_threshold(threshold).await;
+    }
+
+    /// read_data_channel_part is read_data_channel returning the messages larger than the
+    /// streaming threshold in parts, with the place of the data in its message
+    pub(crate) async fn read_data_channel_part(
+        &self,
+        buf: &mut [u8],
+    ) -> Result<(usize, bool, MessagePart)> {
+        loop {
+            let (mut n, ppi, part) = match self.stream.read_sctp_part(buf).await {
+                Ok(result) => result,
+                Err(err) => {
+                    self.stream.close().await?;
+
+                    return Err(err.into());
+                }
+            };
+
+            let mut is_string = false;
+            match ppi {
+                PayloadProtocolIdentifier::Dcep => {
+                    if part.offset > 0 || !part.is_last {
+                        log::warn!("Ignoring DCEP message larger than the streaming threshold");
+                        continue;
+                    }
+                    let mut data = &buf[..n];
+                    if let Err(err) = self.handle_dcep(&mut data) {
+                        log::warn!("Ignoring malformed DCEP message: {}", err);
+                    }
+                    continue;
+                }
+                PayloadProtocolIdentifier::String | PayloadProtocolIdentifier::StringEmpty => {
+                    is_string = true;
+                }
+                _ => {}
+            };
+
+            self.acked.store(true, Ordering::SeqCst);
+
+            match ppi {
+                PayloadProtocolIdentifier::StringEmpty | PayloadProtocolIdentifier::BinaryEmpty => {
+                    n = 0;
+                }
+                _ => {}
+            };
+
+            return Ok((n, is_string, part));
+        }
+    }
+
+    fn handle_dcep<B>(&self, data: &mut B) -> Result<()>
     where
         B: Buf,
     {
@@ -116,18 +325,30 @@ impl DataChannel {
 
         match msg {
             Message::DataChannelAck(_) => {
-                log::debug!("Received DATA_CHANNEL_ACK");
+                if self.acked.swap(true, Ordering::SeqCst) {
+                    log::debug!("Ignoring DATA_CHANNEL_ACK on a confirmed data channel");
+                } else {
+                    log::debug!("Received DATA_CHANNEL_ACK");
+                }
             }
-            _ => {
-                // Note: DATA_CHANNEL_OPEN message is handled inside Server() method.
-                // Therefore, the message will not reach here.
-                panic!("client should only ever receive a DataChannelAck message");
+            Message::DataChannelOpen(_) => {
+                // Note: DATA_CHANNEL_OPEN message is handled inside accept().
+                // Therefore, the message should not reach here.
+                log::warn!("Ignoring DATA_CHANNEL_OPEN on an established data channel");
             }
         };
 
         Ok(())
     }
 
+    async fn write_data_channel_ack(&self) -> Result<usize> {
+        let ack = Message::DataChannelAck(DataChannelAck {}).marshal()?;
+        Ok(self
+            .stream
+            .write_sctp(&ack, PayloadProtocolIdentifier::Dcep)
+            .await?)
+    }
+
     /// Write writes len(p) bytes from p as binary data
     pub(crate) async fn write(&self, data: &Bytes) -> Result<usize> {
         self.write_data_channel(data, false).await
@@ -135,6 +356,18 @@ impl DataChannel {
 
     /// WriteDataChannel writes len(p) bytes from p
     pub(crate) async fn write_data_channel(&self, data: &Bytes, is_string: bool) -> Result<usize> {
+        self.write_data_channel_with_deadline(data, is_string, None)
+            .await
+    }
+
+    /// write_data_channel_with_deadline is write_data_channel for a message that is dropped
+    /// unless it is handed to the wire by deadline, see Stream::write_sctp_with_deadline
+    pub(crate) async fn write_data_channel_with_deadline(
+        &self,
+        data: &Bytes,
+        is_string: bool,
+        deadline: Option<Instant>,
+    ) -> Result<usize> {
         let data_len = data.len();
 
         // https://tools.ietf.org/html/draft-ietf-rtcweb-data-channel-12#section-6.6
@@ -154,14 +387,42 @@ impl DataChannel {
         if data_len == 0 {
             let _ = self
                 .stream
-                .write_sctp(&Bytes::from_static(&[0]), ppi)
+                .write_sctp_with_deadline(&Bytes::from_static(&[0]), ppi, deadline)
                 .await?;
             Ok(0)
         } else {
-            Ok(self.stream.write_sctp(data, ppi).await?)
+            Ok(self
+                .stream
+                .write_sctp_with_deadline(data, ppi, deadline)
+                .await?)
         }
     }
 
+    /// write_data_channel_batch writes messages as binary messages in one go, see
+    /// Stream::write_sctp_batch. Returns the bytes of the messages.
+    pub(crate) async fn write_data_channel_batch(&self, messages: &[Bytes]) -> Result<usize> {
+        // an empty message goes out as one zero byte, see write_data_channel_with_deadline
+        let messages: Vec<(Bytes, PayloadProtocolIdentifier)> = messages
+            .iter()
+            .map(|data| {
+                if data.is_empty() {
+                    (
+                        Bytes::from_static(&[0]),
+                        PayloadProtocolIdentifier::BinaryEmpty,
+                    )
+                } else {
+                    (data.clone(), PayloadProtocolIdentifier::Binary)
+                }
+            })
+            .collect();
+        let n_empty = messages
+            .iter()
+            .filter(|(_, ppi)| *ppi == PayloadProtocolIdentifier::BinaryEmpty)
+            .count();
+
+        Ok(self.stream.write_sctp_batch(&messages).await? - n_empty)
+    }
+
     /// SetBufferedAmountLowThreshold is used to update the threshold.
     /// See BufferedAmountLowThreshold().
     pub(crate) fn set_buffered_amount_low_threshold(&self, threshold: usize) {
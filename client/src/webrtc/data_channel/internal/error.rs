@@ -12,6 +12,18 @@ pub(crate) enum Error {
     UnexpectedEndOfBuffer { expected: usize, actual: usize },
     #[error("Unknown MessageType {0}")]
     InvalidMessageType(u8),
+    #[error("Unknown ChannelType {0}")]
+    InvalidChannelType(u8),
+    #[error("DataChannel label of {0} bytes exceeds the limit of 65535")]
+    LabelTooLong(usize),
+    #[error("DataChannel protocol of {0} bytes exceeds the limit of 65535")]
+    ProtocolTooLong(usize),
+    #[error("DataChannel message has {0} unexpected trailing bytes")]
+    TrailingBytes(usize),
+    #[error("Stream closed")]
+    ErrStreamClosed,
+    #[error("Invalid Payload Protocol Identifier {0}")]
+    InvalidPayloadProtocolIdentifier(u8),
 
     #[error("{0}")]
     Util(#[from] crate::webrtc::util::Error),
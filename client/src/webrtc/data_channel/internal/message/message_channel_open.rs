@@ -3,8 +3,153 @@ use crate::webrtc::data_channel::internal::error::Error;
 
 type Result<T> = std::result::Result<T, crate::webrtc::util::Error>;
 
+const CHANNEL_TYPE_RELIABLE: u8 = 0x00;
+const CHANNEL_TYPE_RELIABLE_UNORDERED: u8 = 0x80;
+const CHANNEL_TYPE_PARTIAL_RELIABLE_REXMIT: u8 = 0x01;
+const CHANNEL_TYPE_PARTIAL_RELIABLE_REXMIT_UNORDERED: u8 = 0x81;
+const CHANNEL_TYPE_PARTIAL_RELIABLE_TIMED: u8 = 0x02;
+const CHANNEL_TYPE_PARTIAL_RELIABLE_TIMED_UNORDERED: u8 = 0x82;
+const CHANNEL_TYPE_LEN: usize = 1;
+/// The bit of the channel type that marks unordered delivery, RFC 8832 S5.1
+const CHANNEL_TYPE_UNORDERED_BIT: u8 = 0x80;
+
 const CHANNEL_OPEN_HEADER_LEN: usize = 11;
 
+/// The label and protocol lengths are 16 bit fields, RFC 8832 S5.1
+pub(crate) const MAX_LABEL_LEN: usize = u16::MAX as usize;
+pub(crate) const MAX_PROTOCOL_LEN: usize = u16::MAX as usize;
+
+/// The largest DATA_CHANNEL_OPEN message, including the message type
+pub(crate) const MAX_DATA_CHANNEL_OPEN_LEN: usize =
+    MESSAGE_TYPE_LEN + CHANNEL_OPEN_HEADER_LEN + MAX_LABEL_LEN + MAX_PROTOCOL_LEN;
+
+/// ChannelType determines the reliability of the WebRTC DataChannel
+#[derive(Default, Eq, PartialEq, Copy, Clone, Debug)]
+pub(crate) enum ChannelType {
+    // `Reliable` determines the Data Channel provides a
+    // reliable in-order bi-directional communication.
+    #[default]
+    Reliable,
+    // `ReliableUnordered` determines the Data Channel
+    // provides a reliable unordered bi-directional communication.
+    ReliableUnordered,
+    // `PartialReliableRexmit` determines the Data Channel
+    // provides a partially-reliable in-order bi-directional communication.
+    // User messages will not be retransmitted more times than specified in the Reliability Parameter.
+    PartialReliableRexmit,
+    // `PartialReliableRexmitUnordered` determines
+    //  the Data Channel provides a partial reliable unordered bi-directional communication.
+    // User messages will not be retransmitted more times than specified in the Reliability Parameter.
+    PartialReliableRexmitUnordered,
+    // `PartialReliableTimed` determines the Data Channel
+    // provides a partial reliable in-order bi-directional communication.
+    // User messages might not be transmitted or retransmitted after
+    // a specified life-time given in milli- seconds in the Reliability Parameter.
+    // This life-time starts when providing the user message to the protocol stack.
+    PartialReliableTimed,
+    // The Data Channel provides a partial reliable unordered bi-directional
+    // communication.  User messages might not be transmitted or retransmitted
+    // after a specified life-time given in milli- seconds in the Reliability Parameter.
+    // This life-time starts when providing the user message to the protocol stack.
+    PartialReliableTimedUnordered,
+}
+
+impl ChannelType {
+    /// ordered reports whether messages on this channel type are delivered in order
+    pub(crate) fn ordered(&self) -> bool {
+        matches!(
+            self,
+            Self::Reliable | Self::PartialReliableRexmit | Self::PartialReliableTimed
+        )
+    }
+
+    /// closest maps a channel type byte from a remote's DATA_CHANNEL_OPEN to the channel type it
+    /// stands for. An unknown one is taken for the reliable type with the same ordering, which
+    /// delivers whatever the remote meant to deliver, rather than failing the channel.
+    pub(crate) fn closest(b: u8) -> Self {
+        if let Ok(channel_type) = Self::unmarshal(&mut &[b][..]) {
+            return channel_type;
+        }
+
+        let channel_type = if b & CHANNEL_TYPE_UNORDERED_BIT == 0 {
+            Self::Reliable
+        } else {
+            Self::ReliableUnordered
+        };
+        log::warn!(
+            "Unknown DATA_CHANNEL_OPEN channel type {:#04x}, treating it as {:?}",
+            b,
+            channel_type
+        );
+        channel_type
+    }
+}
+
+impl MarshalSize for ChannelType {
+    fn marshal_size(&self) -> usize {
+        CHANNEL_TYPE_LEN
+    }
+}
+
+impl Marshal for ChannelType {
+    fn marshal_to(&self, mut buf: &mut [u8]) -> Result<usize> {
+        let required_len = self.marshal_size();
+        if buf.remaining_mut() < required_len {
+            return Err(Error::UnexpectedEndOfBuffer {
+                expected: required_len,
+                actual: buf.remaining_mut(),
+            }
+            .into());
+        }
+
+        let byte = match self {
+            Self::Reliable => CHANNEL_TYPE_RELIABLE,
+            Self::ReliableUnordered => CHANNEL_TYPE_RELIABLE_UNORDERED,
+            Self::PartialReliableRexmit => CHANNEL_TYPE_PARTIAL_RELIABLE_REXMIT,
+            Self::PartialReliableRexmitUnordered => CHANNEL_TYPE_PARTIAL_RELIABLE_REXMIT_UNORDERED,
+            Self::PartialReliableTimed => CHANNEL_TYPE_PARTIAL_RELIABLE_TIMED,
+            Self::PartialReliableTimedUnordered => CHANNEL_TYPE_PARTIAL_RELIABLE_TIMED_UNORDERED,
+        };
+
+        buf.put_u8(byte);
+
+        Ok(1)
+    }
+}
+
+impl Unmarshal for ChannelType {
+    fn unmarshal<B>(buf: &mut B) -> Result<Self>
+    where
+        Self: Sized,
+        B: Buf,
+    {
+        let required_len = CHANNEL_TYPE_LEN;
+        if buf.remaining() < required_len {
+            return Err(Error::UnexpectedEndOfBuffer {
+                expected: required_len,
+                actual: buf.remaining(),
+            }
+            .into());
+        }
+
+        let b0 = buf.get_u8();
+
+        match b0 {
+            CHANNEL_TYPE_RELIABLE => Ok(Self::Reliable),
+            CHANNEL_TYPE_RELIABLE_UNORDERED => Ok(Self::ReliableUnordered),
+            CHANNEL_TYPE_PARTIAL_RELIABLE_REXMIT => Ok(Self::PartialReliableRexmit),
+            CHANNEL_TYPE_PARTIAL_RELIABLE_REXMIT_UNORDERED => {
+                Ok(Self::PartialReliableRexmitUnordered)
+            }
+            CHANNEL_TYPE_PARTIAL_RELIABLE_TIMED => Ok(Self::PartialReliableTimed),
+            CHANNEL_TYPE_PARTIAL_RELIABLE_TIMED_UNORDERED => {
+                Ok(Self::PartialReliableTimedUnordered)
+            }
+            _ => Err(Error::InvalidChannelType(b0).into()),
+        }
+    }
+}
+
 /// The data-part of an data-channel OPEN message without the message type.
 ///
 /// # Memory layout
@@ -30,6 +175,9 @@ const CHANNEL_OPEN_HEADER_LEN: usize = 11;
 /// ```
 #[derive(Eq, PartialEq, Clone, Debug)]
 pub(crate) struct DataChannelOpen {
+    pub(crate) channel_type: ChannelType,
+    pub(crate) priority: u16,
+    pub(crate) reliability_parameter: u32,
     pub(crate) label: Vec<u8>,
     pub(crate) protocol: Vec<u8>,
 }
@@ -45,6 +193,13 @@ impl MarshalSize for DataChannelOpen {
 
 impl Marshal for DataChannelOpen {
     fn marshal_to(&self, mut buf: &mut [u8]) -> Result<usize> {
+        if self.label.len() > MAX_LABEL_LEN {
+            return Err(Error::LabelTooLong(self.label.len()).into());
+        }
+        if self.protocol.len() > MAX_PROTOCOL_LEN {
+            return Err(Error::ProtocolTooLong(self.protocol.len()).into());
+        }
+
         let required_len = self.marshal_size();
         if buf.remaining_mut() < required_len {
             return Err(Error::UnexpectedEndOfBuffer {
@@ -54,6 +209,10 @@ impl Marshal for DataChannelOpen {
             .into());
         }
 
+        let n = self.channel_type.marshal_to(buf)?;
+        buf = &mut buf[n..];
+        buf.put_u16(self.priority);
+        buf.put_u32(self.reliability_parameter);
         buf.put_u16(self.label.len() as u16);
         buf.put_u16(self.protocol.len() as u16);
         buf.put_slice(self.label.as_slice());
@@ -76,6 +235,9 @@ impl Unmarshal for DataChannelOpen {
             .into());
         }
 
+        let channel_type = ChannelType::closest(buf.get_u8());
+        let priority = buf.get_u16();
+        let reliability_parameter = buf.get_u32();
         let label_len = buf.get_u16() as usize;
         let protocol_len = buf.get_u16() as usize;
 
@@ -94,6 +256,17 @@ impl Unmarshal for DataChannelOpen {
         buf.copy_to_slice(&mut label[..]);
         buf.copy_to_slice(&mut protocol[..]);
 
-        Ok(Self { label, protocol })
+        // the lengths must account for the whole message
+        if buf.has_remaining() {
+            return Err(Error::TrailingBytes(buf.remaining()).into());
+        }
+
+        Ok(Self {
+            channel_type,
+            priority,
+            reliability_parameter,
+            label,
+            protocol,
+        })
     }
 }
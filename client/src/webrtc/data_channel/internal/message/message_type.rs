@@ -23,6 +23,15 @@ impl MarshalSize for MessageType {
 
 impl Marshal for MessageType {
     fn marshal_to(&self, mut buf: &mut [u8]) -> Result<usize> {
+        let required_len = self.marshal_size();
+        if buf.remaining_mut() < required_len {
+            return Err(Error::UnexpectedEndOfBuffer {
+                expected: required_len,
+                actual: buf.remaining_mut(),
+            }
+            .into());
+        }
+
         let b = match self {
             MessageType::DataChannelAck => MESSAGE_TYPE_ACK,
             MessageType::DataChannelOpen => MESSAGE_TYPE_OPEN,
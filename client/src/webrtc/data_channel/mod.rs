@@ -1,29 +1,60 @@
+pub(crate) mod chunking;
+pub(crate) mod data_channel_parameters;
 pub(crate) mod data_channel_state;
 pub(crate) mod internal;
+#[cfg(feature = "channel-mux")]
+pub(crate) mod mux;
 
 use std::future::Future;
 use std::pin::Pin;
-use std::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU16, AtomicU32, AtomicU8, AtomicUsize, Ordering};
 use std::sync::{Arc, Weak};
+use std::time::Instant;
 
-use crate::webrtc::sctp::stream::OnBufferedAmountLowFn;
+use crate::webrtc::sctp::queue::pending_queue::DEFAULT_STREAM_PRIORITY;
+use crate::webrtc::sctp::stream::{
+    CloseReason, OnBufferedAmountLowFn, ReorderStats, TransportError,
+};
+use bytes::Bytes;
 use tokio::sync::Mutex;
 
+use data_channel_parameters::{ChannelReliability, DataChannelParameters};
 use data_channel_state::RTCDataChannelState;
 
 use crate::webrtc::error::{Error, OnErrorHdlrFn, Result};
+use crate::webrtc::peer_connection::event_log::ConnectMilestone;
 use crate::webrtc::sctp_transport::RTCSctpTransport;
+use crate::webrtc::util::spawner::Spawner;
 
 pub(crate) type OnOpenHdlrFn =
     Box<dyn (FnOnce() -> Pin<Box<dyn Future<Output = ()> + Send + 'static>>) + Send + Sync>;
 
+pub type OnCloseHdlrFn = Box<
+    dyn (FnOnce(CloseReason) -> Pin<Box<dyn Future<Output = ()> + Send + 'static>>) + Send + Sync,
+>;
+
+// The id of a DataChannel that has no SCTP stream yet
+const ID_UNASSIGNED: u32 = u32::MAX;
+
+/// DEFAULT_PRIORITY is the priority of channels opened without one, "normal" in RFC 8831
+pub(crate) const DEFAULT_PRIORITY: u16 = DEFAULT_STREAM_PRIORITY;
+
 /// DataChannel represents a WebRTC DataChannel
 /// The DataChannel interface represents a network channel
 /// which can be used for bidirectional peer-to-peer transfers of arbitrary data
-#[derive(Default)]
-pub(crate) struct RTCDataChannel {
+pub struct RTCDataChannel {
     label: String,
     protocol: String,
+    ordered: bool,
+    max_packet_lifetime: Option<u16>,
+    max_retransmits: Option<u16>,
+    id: AtomicU32,
+    priority: AtomicU16,
+    // accepted is set for a channel the remote opened
+    accepted: bool,
+    // generation counts the reopens, the close of a stream from before one is not the
+    // channel's anymore
+    generation: Arc<AtomicU32>,
 
     ready_state: Arc<AtomicU8>, // DataChannelState
     buffered_amount_low_threshold: AtomicUsize,
@@ -38,25 +69,80 @@ pub(crate) struct RTCDataChannel {
     // binaryType                 string
     on_open_handler: Arc<Mutex<Option<OnOpenHdlrFn>>>,
     on_error_handler: Arc<Mutex<Option<OnErrorHdlrFn>>>,
+    on_close_handler: Arc<Mutex<Option<OnCloseHdlrFn>>>,
 
     on_buffered_amount_low: Mutex<Option<OnBufferedAmountLowFn>>,
 
     sctp_transport: Mutex<Option<Weak<RTCSctpTransport>>>,
     data_channel: Mutex<Option<Arc<crate::webrtc::internal::data_channel::DataChannel>>>,
+    spawner: Spawner,
 }
 
 impl RTCDataChannel {
     // create the DataChannel object before the networking is set up.
-    pub(crate) fn new(label: &str, protocol: &str) -> Self {
+    pub(crate) fn new(params: DataChannelParameters, spawner: Spawner) -> Self {
         RTCDataChannel {
-            label: label.to_string(),
-            protocol: protocol.to_string(),
+            label: params.label,
+            protocol: params.protocol,
+            ordered: params.ordered,
+            max_packet_lifetime: params.max_packet_lifetime,
+            max_retransmits: params.max_retransmits,
+            id: AtomicU32::new(ID_UNASSIGNED),
+            priority: AtomicU16::new(params.priority),
+            accepted: false,
+            generation: Arc::new(AtomicU32::new(0)),
             ready_state: Arc::new(AtomicU8::new(RTCDataChannelState::Connecting as u8)),
+            buffered_amount_low_threshold: AtomicUsize::new(0),
             detach_called: Arc::new(AtomicBool::new(false)),
-            ..Default::default()
+            on_open_handler: Arc::new(Mutex::new(None)),
+            on_error_handler: Arc::new(Mutex::new(None)),
+            on_close_handler: Arc::new(Mutex::new(None)),
+            on_buffered_amount_low: Mutex::new(None),
+            sctp_transport: Mutex::new(None),
+            data_channel: Mutex::new(None),
+            spawner,
         }
     }
 
+    // create the DataChannel object for a channel the remote opened
+    pub(crate) fn new_accepted(params: DataChannelParameters, spawner: Spawner) -> Self {
+        RTCDataChannel {
+            accepted: true,
+            ..RTCDataChannel::new(params, spawner)
+        }
+    }
+
+    /// reopen takes a channel opened here back to connecting after its association went away,
+    /// so that the next open dials it again on a new association. on_open is invoked again once
+    /// it is open, if a handler was set after the last time. Channels the remote opened are
+    /// left closed, it has to open them again itself. Returns whether the channel was reopened.
+    pub(crate) async fn reopen(&self) -> bool {
+        if self.accepted {
+            return false;
+        }
+
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        *self.sctp_transport.lock().await = None;
+        *self.data_channel.lock().await = None;
+        self.id.store(ID_UNASSIGNED, Ordering::SeqCst);
+        self.detach_called.store(false, Ordering::SeqCst);
+        self.set_ready_state(RTCDataChannelState::Connecting);
+        true
+    }
+
+    // is_retired tells whether the channel is closed for good, i.e. closed and not left for
+    // restart_sctp to reopen on the association after a peer restart
+    pub(crate) fn is_retired(&self) -> bool {
+        if self.ready_state() != RTCDataChannelState::Closed {
+            return false;
+        }
+        self.accepted
+            || !matches!(
+                self.close_reason(),
+                Some(CloseReason::TransportFailure(TransportError::PeerRestart))
+            )
+    }
+
     /// open opens the datachannel over the sctp transport
     pub(crate) async fn open(&self, sctp_transport: Arc<RTCSctpTransport>) -> Result<()> {
         if let Some(association) = sctp_transport.association().await {
@@ -69,13 +155,29 @@ impl RTCDataChannel {
                 }
             }
 
-            let cfg = crate::webrtc::internal::data_channel::Config {
+            let cfg = DataChannelParameters {
                 label: self.label.clone(),
                 protocol: self.protocol.clone(),
-            };
+                ordered: self.ordered,
+                max_packet_lifetime: self.max_packet_lifetime,
+                max_retransmits: self.max_retransmits,
+                priority: self.priority(),
+            }
+            .to_config();
 
-            let dc = crate::webrtc::internal::data_channel::DataChannel::dial(&association, 0, cfg)
-                .await?;
+            let dc = match crate::webrtc::internal::data_channel::DataChannel::dial(
+                &association,
+                sctp_transport.generate_data_channel_id().await,
+                cfg,
+            )
+            .await
+            {
+                Ok(dc) => dc,
+                Err(err) => {
+                    self.set_ready_state(RTCDataChannelState::Closed);
+                    return Err(err.into());
+                }
+            };
 
             // buffered_amount_low_threshold and on_buffered_amount_low might be set earlier
             dc.set_buffered_amount_low_threshold(
@@ -89,6 +191,9 @@ impl RTCDataChannel {
             }
 
             self.handle_open(Arc::new(dc)).await;
+            sctp_transport
+                .event_log()
+                .milestone(ConnectMilestone::ChannelOpen);
 
             Ok(())
         } else {
@@ -113,7 +218,7 @@ impl RTCDataChannel {
         let on_open_handler = Arc::clone(&self.on_open_handler);
         let detach_data_channels = true;
         let detach_called = Arc::clone(&self.detach_called);
-        tokio::spawn(async move {
+        self.spawner.spawn(async move {
             let mut handler = on_open_handler.lock().await;
             if let Some(f) = handler.take() {
                 f().await;
@@ -134,12 +239,33 @@ impl RTCDataChannel {
         &self,
         dc: Arc<crate::webrtc::internal::data_channel::DataChannel>,
     ) {
+        self.id
+            .store(dc.stream_identifier() as u32, Ordering::SeqCst);
         {
             let mut data_channel = self.data_channel.lock().await;
             *data_channel = Some(Arc::clone(&dc));
         }
+        // set_priority may have been called while the channel was being opened
+        dc.set_priority(self.priority()).await;
         self.set_ready_state(RTCDataChannelState::Open);
 
+        let ready_state = Arc::clone(&self.ready_state);
+        let on_close_handler = Arc::clone(&self.on_close_handler);
+        let generation = Arc::clone(&self.generation);
+        let opened_generation = generation.load(Ordering::SeqCst);
+        dc.on_close(Box::new(move |reason| {
+            Box::pin(async move {
+                if generation.load(Ordering::SeqCst) != opened_generation {
+                    return;
+                }
+                ready_state.store(RTCDataChannelState::Closed as u8, Ordering::SeqCst);
+                let f = on_close_handler.lock().await.take();
+                if let Some(f) = f {
+                    f(reason).await;
+                }
+            })
+        }));
+
         self.do_open().await;
     }
 
@@ -150,6 +276,138 @@ impl RTCDataChannel {
         *handler = Some(f);
     }
 
+    /// do_error closes a channel that can no longer be opened and hands err to its on_error
+    /// handler
+    pub(crate) async fn do_error(&self, err: Error) {
+        self.set_ready_state(RTCDataChannelState::Closed);
+        let mut handler = self.on_error_handler.lock().await;
+        if let Some(f) = &mut *handler {
+            f(err).await;
+        }
+    }
+
+    /// on_close sets an event handler which is invoked once the channel is closed, with the
+    /// reason it was closed for. It is invoked right away if the channel is closed already.
+    pub async fn on_close(&self, f: OnCloseHdlrFn) {
+        {
+            let mut handler = self.on_close_handler.lock().await;
+            *handler = Some(f);
+        }
+
+        if let Some(reason) = self.close_reason() {
+            let f = self.on_close_handler.lock().await.take();
+            if let Some(f) = f {
+                self.spawner.spawn(f(reason));
+            }
+        }
+    }
+
+    /// close_reason returns why the channel was closed: locally, by the remote resetting its
+    /// stream, by the association shutting down or by a transport failure. It is None while the
+    /// channel is open or being opened.
+    pub fn close_reason(&self) -> Option<CloseReason> {
+        match self.data_channel.try_lock() {
+            Ok(data_channel) => data_channel.as_ref().and_then(|dc| dc.close_reason()),
+            Err(_) => None,
+        }
+    }
+
+    /// send_with_deadline sends data as a binary message that is only worth delivering until
+    /// deadline, e.g. a game state update that a newer one replaces. Unlike max_packet_lifetime,
+    /// which only starts once a message is sent, the deadline also covers the time the message
+    /// waits behind others, e.g. during congestion:
+    ///
+    /// - if the deadline already passed, the message is not queued and an error of kind
+    ///   std::io::ErrorKind::TimedOut is returned
+    /// - if it passes while the message is queued, the message is dropped when its turn comes
+    ///   instead of being sent
+    ///
+    /// Both count toward messages_expired. A message handed to the wire in time is sent once and
+    /// not retransmitted when the remote supports partial reliability (FORWARD-TSN), so it never
+    /// outlives the deadline by more than its flight time. Returns the number of bytes queued.
+    ///
+    /// Once the channel is closed it fails right away, with std::io::ErrorKind::ConnectionAborted
+    /// if the connection failed and NotConnected otherwise. The inner error is then the
+    /// CloseReason, see close_reason.
+    pub async fn send_with_deadline(
+        &self,
+        data: &Bytes,
+        deadline: Instant,
+    ) -> std::io::Result<usize> {
+        let data_channel = self.sending_data_channel().await?;
+        let result = data_channel
+            .write_data_channel_with_deadline(
+                data,
+                false,
+                Some(tokio::time::Instant::from_std(deadline)),
+            )
+            .await;
+        result.map_err(|err| send_error(&data_channel, err))
+    }
+
+    /// send_batch sends messages as binary messages in one go, e.g. the updates of a game tick.
+    /// They are handed to SCTP together and in order, so a message sent by another task never
+    /// lands between them and they can share packets, and the write loop is woken once instead
+    /// of per message. Returns the number of bytes queued, 0 for an empty batch.
+    ///
+    /// The batch is all or nothing: if one message is larger than the remote accepts, or the
+    /// send buffer has no room for all of them, none is queued. The latter fails with
+    /// std::io::ErrorKind::WouldBlock, see SettingEngine::set_sctp_max_send_buffer_size. Once
+    /// the channel is closed it fails like send_with_deadline.
+    pub async fn send_batch(&self, messages: &[Bytes]) -> std::io::Result<usize> {
+        if messages.is_empty() {
+            return Ok(0);
+        }
+        let data_channel = self.sending_data_channel().await?;
+        let result = data_channel.write_data_channel_batch(messages).await;
+        result.map_err(|err| send_error(&data_channel, err))
+    }
+
+    /// sending_data_channel returns the detached channel to send on, or the error to fail a send
+    /// with if there is none or it is closed
+    async fn sending_data_channel(
+        &self,
+    ) -> std::io::Result<Arc<crate::webrtc::internal::data_channel::DataChannel>> {
+        let data_channel = {
+            let data_channel = self.data_channel.lock().await;
+            data_channel.clone()
+        };
+        let data_channel = data_channel.ok_or_else(|| {
+            let err = if self.ready_state() == RTCDataChannelState::Closed {
+                Error::ErrConnectionClosed
+            } else {
+                Error::ErrDetachBeforeOpened
+            };
+            std::io::Error::new(std::io::ErrorKind::NotConnected, err.to_string())
+        })?;
+        if let Some(reason) = data_channel.close_reason() {
+            return Err(reason.into_io_error());
+        }
+        Ok(data_channel)
+    }
+
+    /// messages_expired returns how many messages were dropped because their send_with_deadline
+    /// deadline passed before they were sent. It never waits on a lock and is 0 until the channel
+    /// is open.
+    pub fn messages_expired(&self) -> u64 {
+        match self.data_channel.try_lock() {
+            Ok(data_channel) => data_channel.as_ref().map_or(0, |dc| dc.messages_expired()),
+            Err(_) => 0,
+        }
+    }
+
+    /// reorder_stats returns how much the network reordered and duplicated the unordered
+    /// messages received on the channel, e.g. to check a jitter buffer against. It never waits on
+    /// a lock and is all zeros until the channel is open.
+    pub fn reorder_stats(&self) -> ReorderStats {
+        match self.data_channel.try_lock() {
+            Ok(data_channel) => data_channel
+                .as_ref()
+                .map_or_else(ReorderStats::default, |dc| dc.reorder_stats()),
+            Err(_) => ReorderStats::default(),
+        }
+    }
+
     /// detach allows you to detach the underlying datachannel. This provides
     /// an idiomatic API to work with, however it disables the OnMessage callback.
     /// Before calling Detach you have to enable this behavior by calling
@@ -171,12 +429,118 @@ impl RTCDataChannel {
         }
     }
 
-    /// ready_state represents the state of the DataChannel object.
-    pub(crate) fn ready_state(&self) -> RTCDataChannelState {
-        self.ready_state.load(Ordering::SeqCst).into()
+    /// label represents a label that can be used to distinguish this
+    /// DataChannel object from other DataChannel objects. Scripts are
+    /// allowed to create multiple DataChannel objects with the same label.
+    pub fn label(&self) -> &str {
+        self.label.as_str()
+    }
+
+    /// protocol represents the name of the sub-protocol used with this
+    /// DataChannel, as carried in the DCEP DATA_CHANNEL_OPEN.
+    pub fn protocol(&self) -> &str {
+        self.protocol.as_str()
+    }
+
+    /// id represents the ID for this DataChannel, the SCTP stream identifier it runs on. It is
+    /// None until the channel is opened.
+    pub fn id(&self) -> Option<u16> {
+        u16::try_from(self.id.load(Ordering::SeqCst)).ok()
+    }
+
+    /// try_buffered_amount returns the number of bytes queued to be sent on the channel, or None
+    /// if it is not open yet or is being opened right now. It never waits on a lock.
+    #[cfg(feature = "serde")]
+    pub(crate) fn try_buffered_amount(&self) -> Option<usize> {
+        let data_channel = self.data_channel.try_lock().ok()?;
+        data_channel.as_ref().map(|dc| dc.buffered_amount())
+    }
+
+    /// ordered represents if the DataChannel is ordered, and false if
+    /// out-of-order delivery is allowed. For a channel opened by the remote
+    /// this and the reliability parameters below come from its DATA_CHANNEL_OPEN.
+    pub fn ordered(&self) -> bool {
+        self.ordered
+    }
+
+    /// max_packet_lifetime represents the length of the time window (msec) during
+    /// which transmissions and retransmissions may occur in unreliable mode.
+    pub fn max_packet_lifetime(&self) -> Option<u16> {
+        self.max_packet_lifetime
+    }
+
+    /// max_retransmits represents the maximum number of retransmissions that are
+    /// attempted in unreliable mode.
+    pub fn max_retransmits(&self) -> Option<u16> {
+        self.max_retransmits
+    }
+
+    /// negotiated_reliability returns the reliability the remote runs the channel with, which
+    /// ordered, max_packet_lifetime and max_retransmits only request. It is None until the
+    /// channel is open and, for a channel opened here, the remote confirmed it. A remote that
+    /// ignores the requested channel type shows as fully reliable and ordered once it sends an
+    /// ordered message on an unordered channel. A DATA_CHANNEL_OPEN from the remote with an
+    /// unknown channel type is taken as reliable. It never waits on a lock.
+    pub fn negotiated_reliability(&self) -> Option<ChannelReliability> {
+        let data_channel = self.data_channel.try_lock().ok()?;
+        data_channel.as_ref()?.negotiated_reliability()
+    }
+
+    /// priority is the send priority of the channel against the other channels of the
+    /// connection, as announced in DATA_CHANNEL_OPEN. RFC 8831 defines 128 as below normal, 256
+    /// as normal, 512 as high and 1024 as extra high. See SettingEngine::set_sctp_strict_priority
+    /// for how priorities are applied.
+    pub fn priority(&self) -> u16 {
+        self.priority.load(Ordering::SeqCst)
+    }
+
+    /// set_priority changes the send priority of the channel, see priority. It applies from the
+    /// next message on, the remote is not told about the change.
+    pub async fn set_priority(&self, priority: u16) {
+        // stored once the lock is held, so that a cancelled call changes nothing
+        let data_channel = self.data_channel.lock().await;
+        self.priority.store(priority, Ordering::SeqCst);
+        if let Some(dc) = &*data_channel {
+            dc.set_priority(priority).await;
+        }
+    }
+
+    /// ready_state represents the state of the DataChannel object. An open
+    /// channel turns closed once its SCTP stream is reset by either side or the
+    /// association goes away.
+    pub fn ready_state(&self) -> RTCDataChannelState {
+        let ready_state = self.ready_state.load(Ordering::SeqCst).into();
+        if ready_state != RTCDataChannelState::Open {
+            return ready_state;
+        }
+
+        // the channel is read through its detached DataChannel, so the close is only noticed here
+        let is_closed = match self.data_channel.try_lock() {
+            Ok(data_channel) => data_channel.as_ref().map(|dc| dc.is_closed()),
+            Err(_) => None,
+        };
+        if is_closed == Some(true) {
+            self.set_ready_state(RTCDataChannelState::Closed);
+            return RTCDataChannelState::Closed;
+        }
+
+        ready_state
     }
 
     pub(crate) fn set_ready_state(&self, r: RTCDataChannelState) {
         self.ready_state.store(r as u8, Ordering::SeqCst);
     }
 }
+
+/// send_error is the error a send on data_channel fails with
+fn send_error(
+    data_channel: &crate::webrtc::internal::data_channel::DataChannel,
+    err: crate::webrtc::internal::Error,
+) -> std::io::Error {
+    match (err, data_channel.close_reason()) {
+        // the channel closed while the message was being queued
+        (_, Some(reason)) => reason.into_io_error(),
+        (crate::webrtc::internal::Error::Sctp(err), None) => err.into(),
+        (err, None) => std::io::Error::other(err.to_string()),
+    }
+}
@@ -0,0 +1,352 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    future::poll_fn,
+    io,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, Weak,
+    },
+    task::{Context, Poll, Waker},
+};
+
+use bytes::{BufMut, Bytes, BytesMut};
+use tokio::sync::oneshot;
+
+use crate::session_channel::{DataChannel, DataChannelReceiver, DataChannelSender, TrySendError};
+
+/// The receive queue size of a tag registered with ChannelMux::register and of the default sink
+const DEFAULT_QUEUE_CAPACITY: usize = 256;
+
+/// ChannelMux carries several message streams over one data channel, e.g. inputs, chat and
+/// state updates of a game, without opening a channel for each. Every message starts with a one
+/// byte tag naming its stream. Each registered tag has a sender that puts the tag in front and
+/// a receiver with a bounded queue of its own, so a stream whose receiver falls behind drops its
+/// own messages instead of holding up the others.
+///
+/// The channel is closed once the ChannelMux and every sender and receiver it handed out are
+/// dropped, or close is called.
+pub struct ChannelMux {
+    shared: Arc<MuxShared>,
+}
+
+/// MuxSender sends the messages of one tag, see ChannelMux::register. It can be cloned to send
+/// from several tasks.
+#[derive(Clone)]
+pub struct MuxSender {
+    tag: u8,
+    shared: Arc<MuxShared>,
+    queue: Arc<TagQueue>,
+}
+
+/// MuxReceiver receives the messages of one tag with the tag stripped, see
+/// ChannelMux::register, or the messages of unregistered tags as they arrived, see
+/// ChannelMux::default_sink
+pub struct MuxReceiver {
+    // keeps the read loop running while the receiver is in use
+    _shared: Arc<MuxShared>,
+    queue: Arc<TagQueue>,
+}
+
+/// MuxOverflow decides what happens to a message whose receive queue is full
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MuxOverflow {
+    /// DropOldest discards the oldest queued message, keeping the latest ones, e.g. for state
+    /// updates where only the newest counts
+    #[default]
+    DropOldest,
+    /// DropNewest discards the message that arrived, keeping the first ones, e.g. for events
+    /// that make no sense without the ones before
+    DropNewest,
+}
+
+/// MuxShared is held by the ChannelMux and every sender and receiver, the read loop stops when
+/// the last one is dropped
+struct MuxShared {
+    sender: DataChannelSender,
+    routes: Mutex<Routes>,
+    unknown: AtomicU64,
+    // dropped with the last holder, which wakes the read loop waiting for a message
+    _stop: oneshot::Sender<()>,
+}
+
+#[derive(Default)]
+struct Routes {
+    tags: HashMap<u8, Arc<TagQueue>>,
+    default_sink: Option<Arc<TagQueue>>,
+    // the channel is closed, queues registered from now on start out ended
+    ended: bool,
+}
+
+/// TagQueue holds the received messages of one tag until its receiver takes them, and the
+/// counters of the tag
+struct TagQueue {
+    capacity: usize,
+    overflow: MuxOverflow,
+    state: Mutex<QueueState>,
+    sent: AtomicU64,
+    received: AtomicU64,
+    dropped: AtomicU64,
+}
+
+#[derive(Default)]
+struct QueueState {
+    messages: VecDeque<Bytes>,
+    ended: bool,
+    waker: Option<Waker>,
+}
+
+impl QueueState {
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl TagQueue {
+    fn new(capacity: usize, overflow: MuxOverflow, ended: bool) -> Self {
+        TagQueue {
+            capacity: capacity.max(1),
+            overflow,
+            state: Mutex::new(QueueState {
+                ended,
+                ..Default::default()
+            }),
+            sent: AtomicU64::new(0),
+            received: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// push queues message, making room as overflow says if the queue is full
+    fn push(&self, message: Bytes) {
+        self.received.fetch_add(1, Ordering::SeqCst);
+        let mut state = self.state.lock().unwrap();
+        if state.messages.len() >= self.capacity {
+            self.dropped.fetch_add(1, Ordering::SeqCst);
+            match self.overflow {
+                MuxOverflow::DropOldest => {
+                    state.messages.pop_front();
+                }
+                MuxOverflow::DropNewest => return,
+            }
+        }
+        state.messages.push_back(message);
+        state.wake();
+    }
+
+    /// end tells the receiver that no more messages are coming
+    fn end(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.ended = true;
+        state.wake();
+    }
+
+    fn poll_pop(&self, cx: &mut Context<'_>) -> Poll<Option<Bytes>> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(message) = state.messages.pop_front() {
+            return Poll::Ready(Some(message));
+        }
+        if state.ended {
+            return Poll::Ready(None);
+        }
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl MuxShared {
+    /// route hands message to the queue of its tag with the tag sliced off, or whole to the
+    /// default sink if the tag is not registered
+    fn route(&self, message: Bytes) {
+        let routes = self.routes.lock().unwrap();
+        if let Some(queue) = message.first().and_then(|tag| routes.tags.get(tag)) {
+            queue.push(message.slice(1..));
+            return;
+        }
+        self.unknown.fetch_add(1, Ordering::SeqCst);
+        if let Some(default_sink) = &routes.default_sink {
+            default_sink.push(message);
+        }
+    }
+
+    /// end ends every queue once the channel is closed
+    fn end(&self) {
+        let mut routes = self.routes.lock().unwrap();
+        routes.ended = true;
+        for queue in routes.tags.values() {
+            queue.end();
+        }
+        if let Some(default_sink) = &routes.default_sink {
+            default_sink.end();
+        }
+    }
+}
+
+// read_loop passes the messages of the channel on to the queues until the channel is closed or
+// every holder of shared is dropped
+async fn read_loop(
+    mut receiver: DataChannelReceiver,
+    shared: Weak<MuxShared>,
+    mut stop: oneshot::Receiver<()>,
+) {
+    loop {
+        let message = tokio::select! {
+            message = receiver.recv() => message,
+            _ = &mut stop => return,
+        };
+        let shared = match shared.upgrade() {
+            Some(shared) => shared,
+            None => return,
+        };
+        match message {
+            Some(message) => shared.route(message),
+            None => {
+                shared.end();
+                return;
+            }
+        }
+    }
+}
+
+impl ChannelMux {
+    /// new takes over data_channel and starts routing the messages it receives to the tags
+    /// registered. It must be called within a Tokio runtime, the routing runs on a task of its
+    /// own. Messages of a tag that arrive before it is registered go to the default sink.
+    pub fn new(data_channel: DataChannel) -> Self {
+        let (sender, receiver) = data_channel.split();
+        let (stop_tx, stop_rx) = oneshot::channel();
+        let shared = Arc::new(MuxShared {
+            sender,
+            routes: Mutex::new(Routes::default()),
+            unknown: AtomicU64::new(0),
+            _stop: stop_tx,
+        });
+        tokio::spawn(read_loop(receiver, Arc::downgrade(&shared), stop_rx));
+        ChannelMux { shared }
+    }
+
+    /// register returns the sender and receiver of tag, with a receive queue of 256 messages
+    /// that drops the oldest one when full, see register_with_queue
+    pub fn register(&self, tag: u8) -> (MuxSender, MuxReceiver) {
+        self.register_with_queue(tag, DEFAULT_QUEUE_CAPACITY, MuxOverflow::default())
+    }
+
+    /// register_with_queue returns the sender and receiver of tag, with a receive queue of
+    /// capacity messages whose overflow decides which message is dropped once it is full. A
+    /// capacity of zero is taken as one. Registering a tag again takes it over, the receiver
+    /// registered before returns None once its queue is drained.
+    pub fn register_with_queue(
+        &self,
+        tag: u8,
+        capacity: usize,
+        overflow: MuxOverflow,
+    ) -> (MuxSender, MuxReceiver) {
+        let mut routes = self.shared.routes.lock().unwrap();
+        let queue = Arc::new(TagQueue::new(capacity, overflow, routes.ended));
+        if let Some(previous) = routes.tags.insert(tag, Arc::clone(&queue)) {
+            previous.end();
+        }
+        let sender = MuxSender {
+            tag,
+            shared: Arc::clone(&self.shared),
+            queue: Arc::clone(&queue),
+        };
+        let receiver = MuxReceiver {
+            _shared: Arc::clone(&self.shared),
+            queue,
+        };
+        (sender, receiver)
+    }
+
+    /// default_sink returns a receiver for the messages whose tag is not registered, e.g. from a
+    /// newer server, as they arrived with the tag in front. Without one they are dropped, which
+    /// unknown_messages counts either way. Its queue is like the one of register, and calling
+    /// it again takes the sink over from the receiver returned before.
+    pub fn default_sink(&self) -> MuxReceiver {
+        let mut routes = self.shared.routes.lock().unwrap();
+        let queue = Arc::new(TagQueue::new(
+            DEFAULT_QUEUE_CAPACITY,
+            MuxOverflow::default(),
+            routes.ended,
+        ));
+        if let Some(previous) = routes.default_sink.replace(Arc::clone(&queue)) {
+            previous.end();
+        }
+        MuxReceiver {
+            _shared: Arc::clone(&self.shared),
+            queue,
+        }
+    }
+
+    /// unknown_messages returns how many messages arrived with a tag that was not registered,
+    /// or without any byte to hold a tag
+    pub fn unknown_messages(&self) -> u64 {
+        self.shared.unknown.load(Ordering::SeqCst)
+    }
+
+    /// close closes the data channel in both directions, every receiver returns None once its
+    /// queue is drained
+    pub async fn close(&self) {
+        self.shared.sender.close().await;
+    }
+}
+
+impl MuxSender {
+    /// tag returns the tag the sender puts in front of its messages
+    pub fn tag(&self) -> u8 {
+        self.tag
+    }
+
+    /// send sends payload behind the tag like DataChannelSender::send and returns the length
+    /// of payload. Putting the tag in front copies payload once.
+    pub async fn send(&self, payload: Bytes) -> io::Result<usize> {
+        self.shared.sender.send(self.tagged(&payload)).await?;
+        self.queue.sent.fetch_add(1, Ordering::SeqCst);
+        Ok(payload.len())
+    }
+
+    /// try_send is send without waiting, see DataChannelSender::try_send
+    pub fn try_send(&self, payload: &Bytes) -> Result<usize, TrySendError> {
+        self.shared.sender.try_send(&self.tagged(payload))?;
+        self.queue.sent.fetch_add(1, Ordering::SeqCst);
+        Ok(payload.len())
+    }
+
+    /// messages_sent returns how many messages of the tag were queued to be sent
+    pub fn messages_sent(&self) -> u64 {
+        self.queue.sent.load(Ordering::SeqCst)
+    }
+
+    fn tagged(&self, payload: &Bytes) -> Bytes {
+        let mut message = BytesMut::with_capacity(1 + payload.len());
+        message.put_u8(self.tag);
+        message.extend_from_slice(payload);
+        message.freeze()
+    }
+}
+
+impl MuxReceiver {
+    /// recv returns the next message, or None once the channel is closed or the tag was
+    /// registered again, and the queued messages are drained. The message shares its memory
+    /// with the one received, nothing is copied to strip the tag.
+    pub async fn recv(&mut self) -> Option<Bytes> {
+        poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// poll_recv is recv for use in a Stream implementation or a hand written future
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<Bytes>> {
+        self.queue.poll_pop(cx)
+    }
+
+    /// messages_received returns how many messages arrived for the receiver, including the
+    /// ones dropped
+    pub fn messages_received(&self) -> u64 {
+        self.queue.received.load(Ordering::SeqCst)
+    }
+
+    /// messages_dropped returns how many messages were discarded because the queue was full
+    pub fn messages_dropped(&self) -> u64 {
+        self.queue.dropped.load(Ordering::SeqCst)
+    }
+}
@@ -0,0 +1,93 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::webrtc::dtls::crypto::Certificate;
+use crate::webrtc::dtls::Error;
+use crate::webrtc::util::spawner::Spawner;
+use crate::webrtc::util::Conn;
+
+use super::dtls_connection_info::DtlsConnectionInfo;
+use super::dtls_security_profile::DtlsSecurityProfile;
+
+#[cfg(feature = "dtls-openssl")]
+pub(crate) mod openssl;
+pub(crate) mod vendored;
+
+/// DtlsBackendKind selects the DTLS implementation the handshake and the encryption of the
+/// data channel traffic run on, see SettingEngine::set_dtls_backend.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DtlsBackendKind {
+    /// Vendored is the DTLS 1.2 implementation built into the crate
+    #[default]
+    Vendored,
+
+    /// OpenSsl runs DTLS 1.2 on the system OpenSSL library. It is experimental: Connection IDs
+    /// are not supported and DtlsConnectionInfo::srtp_profile is always None.
+    #[cfg(feature = "dtls-openssl")]
+    OpenSsl,
+}
+
+impl DtlsBackendKind {
+    /// backend returns the implementation of the kind
+    pub(crate) fn backend(self) -> &'static dyn DtlsBackend {
+        match self {
+            DtlsBackendKind::Vendored => &vendored::VendoredBackend,
+            #[cfg(feature = "dtls-openssl")]
+            DtlsBackendKind::OpenSsl => &openssl::OpenSslBackend,
+        }
+    }
+}
+
+/// DtlsHandshakeConfig is what a backend needs to know for one handshake
+pub(crate) struct DtlsHandshakeConfig {
+    pub(crate) is_client: bool,
+    pub(crate) certificate: Certificate,
+    pub(crate) security_profile: DtlsSecurityProfile,
+    pub(crate) receive_mtu: usize,
+    #[cfg(feature = "dtls-connection-id")]
+    pub(crate) connection_id: Option<Vec<u8>>,
+    pub(crate) spawner: Spawner,
+}
+
+/// DtlsBackend runs the DTLS handshake over a datagram connection. The DTLS transport only
+/// talks to the backend and the session it returns, so the SCTP association and the raw
+/// channels do not depend on the implementation.
+#[async_trait]
+pub(crate) trait DtlsBackend: Send + Sync {
+    /// handshake takes the client or server role of config on conn and resolves once the
+    /// handshake completed, fails or conn is closed. The peer's certificate is not verified,
+    /// it is self-signed.
+    async fn handshake(
+        &self,
+        conn: Arc<dyn Conn + Send + Sync>,
+        config: DtlsHandshakeConfig,
+    ) -> Result<Arc<dyn DtlsSession>, Error>;
+}
+
+/// DtlsSession is an established DTLS connection. Its Conn encrypts each datagram sent into
+/// an application data record and decrypts the records received back into datagrams.
+#[async_trait]
+pub(crate) trait DtlsSession: Conn + Send + Sync {
+    /// conn returns the session as the connection SCTP and the raw channels run on
+    fn conn(self: Arc<Self>) -> Arc<dyn Conn + Send + Sync>;
+
+    /// cipher_suite returns the name of the negotiated cipher suite without waiting, None if
+    /// the session is busy
+    #[cfg(feature = "serde")]
+    fn cipher_suite(&self) -> Option<String>;
+
+    /// connection_info returns what the handshake negotiated
+    async fn connection_info(&self) -> Option<DtlsConnectionInfo>;
+
+    /// export_keying_material derives keying material from the master secret, RFC 5705
+    async fn export_keying_material(
+        &self,
+        label: &str,
+        context: &[u8],
+        length: usize,
+    ) -> Result<Vec<u8>, Error>;
+
+    /// remote_certificates returns the DER certificate chain the peer presented
+    async fn remote_certificates(&self) -> Vec<Vec<u8>>;
+}
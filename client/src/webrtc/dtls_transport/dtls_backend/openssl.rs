@@ -0,0 +1,386 @@
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use openssl::error::ErrorStack;
+use openssl::pkey::PKey;
+use openssl::ssl::{
+    ErrorCode, HandshakeError, Ssl, SslContext, SslMethod, SslOptions, SslStream, SslVerifyMode,
+    SslVersion,
+};
+use openssl::x509::X509;
+use tokio::sync::{mpsc, Mutex as AsyncMutex};
+
+use crate::webrtc::dtls::conn::INVALID_KEYING_LABELS;
+use crate::webrtc::dtls::{Error, OpenSslError};
+use crate::webrtc::util::{Conn, Error as UtilError, KeyingMaterialExporterError};
+
+use super::{DtlsBackend, DtlsHandshakeConfig, DtlsSession};
+use crate::webrtc::dtls_transport::dtls_connection_info::DtlsConnectionInfo;
+use crate::webrtc::dtls_transport::dtls_security_profile::DtlsSecurityProfile;
+
+/// DTLS_MTU is the largest datagram OpenSSL packs records and handshake fragments into, the
+/// same the vendored implementation uses
+const DTLS_MTU: u32 = 1200;
+
+/// RETRANSMIT_POLL_INTERVAL is how often a handshake waiting for the peer lets OpenSSL check
+/// its retransmission timer, it resends the last flight once the timer expired
+const RETRANSMIT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// INBOUND_QUEUE_SIZE is how many received datagrams wait for the session to decrypt them
+const INBOUND_QUEUE_SIZE: usize = 64;
+
+type UtilResult<T> = std::result::Result<T, UtilError>;
+
+// the cipher suites and signature schemes the vendored implementation offers
+const CIPHER_LIST: &str = "ECDHE-ECDSA-AES128-GCM-SHA256:ECDHE-ECDSA-AES256-SHA:\
+                           ECDHE-RSA-AES128-GCM-SHA256:ECDHE-RSA-AES256-SHA";
+const SIGALGS_LIST: &str =
+    "ECDSA+SHA256:ECDSA+SHA384:ECDSA+SHA512:RSA+SHA256:RSA+SHA384:RSA+SHA512:ed25519";
+
+/// OpenSslBackend runs the handshake on the system OpenSSL library
+pub(crate) struct OpenSslBackend;
+
+/// Datagrams is the stream OpenSSL reads records from and writes records to, one datagram
+/// per read and write
+#[derive(Default)]
+struct Datagrams {
+    inbound: VecDeque<Vec<u8>>,
+    outbound: Vec<Vec<u8>>,
+}
+
+impl Read for Datagrams {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let datagram = self
+            .inbound
+            .pop_front()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::WouldBlock))?;
+        if datagram.len() > buf.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "datagram larger than the read buffer",
+            ));
+        }
+        buf[..datagram.len()].copy_from_slice(&datagram);
+        Ok(datagram.len())
+    }
+}
+
+impl Write for Datagrams {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.outbound.push(buf.to_vec());
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn openssl_error(err: impl Into<OpenSslError>) -> Error {
+    Error::OpenSsl(err.into())
+}
+
+fn new_ssl(config: &DtlsHandshakeConfig) -> Result<Ssl, ErrorStack> {
+    let mut ctx = SslContext::builder(SslMethod::dtls())?;
+    ctx.set_min_proto_version(Some(SslVersion::DTLS1_2))?;
+    ctx.set_max_proto_version(Some(SslVersion::DTLS1_2))?;
+    ctx.set_cipher_list(CIPHER_LIST)?;
+    ctx.set_sigalgs_list(SIGALGS_LIST)?;
+    ctx.set_options(SslOptions::NO_QUERY_MTU | SslOptions::NO_RENEGOTIATION);
+
+    let mut chain = config.certificate.certificate.iter();
+    if let Some(leaf) = chain.next() {
+        let leaf = X509::from_der(&leaf.0)?;
+        ctx.set_certificate(&leaf)?;
+    }
+    for cert in chain {
+        ctx.add_extra_chain_cert(X509::from_der(&cert.0)?)?;
+    }
+    let private_key = PKey::private_key_from_pkcs8(&config.certificate.private_key.serialized_der)?;
+    ctx.set_private_key(&private_key)?;
+    ctx.check_private_key()?;
+
+    // like the vendored implementation with insecure_skip_verify, the self-signed certificate
+    // of the peer is required but accepted as is
+    let mode = if config.is_client {
+        SslVerifyMode::PEER
+    } else {
+        SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT
+    };
+    ctx.set_verify_callback(mode, |_, _| true);
+
+    let mut ssl = Ssl::new(&ctx.build())?;
+    ssl.set_mtu(DTLS_MTU)?;
+    Ok(ssl)
+}
+
+/// send_all sends the datagrams OpenSSL wrote to conn
+async fn send_all(
+    conn: &Arc<dyn Conn + Send + Sync>,
+    datagrams: Vec<Vec<u8>>,
+) -> Result<(), Error> {
+    for datagram in datagrams {
+        conn.send(&datagram).await?;
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl DtlsBackend for OpenSslBackend {
+    async fn handshake(
+        &self,
+        conn: Arc<dyn Conn + Send + Sync>,
+        config: DtlsHandshakeConfig,
+    ) -> Result<Arc<dyn DtlsSession>, Error> {
+        #[cfg(feature = "dtls-connection-id")]
+        if config.connection_id.is_some() {
+            log::warn!(
+                "the OpenSSL DTLS backend does not support Connection IDs, not offering one"
+            );
+        }
+
+        let ssl = new_ssl(&config).map_err(openssl_error)?;
+
+        // the reader task owns the receiving side of conn, so waiting on the queue can be
+        // interrupted without losing a datagram. It ends once conn is closed.
+        let (inbound_tx, mut inbound_rx) = mpsc::channel(INBOUND_QUEUE_SIZE);
+        let reader_conn = Arc::clone(&conn);
+        let receive_mtu = config.receive_mtu;
+        config.spawner.spawn(async move {
+            let mut buf = vec![0u8; receive_mtu];
+            while let Ok(n) = reader_conn.recv(&mut buf).await {
+                if inbound_tx.send(buf[..n].to_vec()).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut result = if config.is_client {
+            ssl.connect(Datagrams::default())
+        } else {
+            ssl.accept(Datagrams::default())
+        };
+        let mut stream = loop {
+            let mut mid_handshake = match result {
+                Ok(stream) => break stream,
+                Err(HandshakeError::WouldBlock(mid_handshake)) => mid_handshake,
+                Err(HandshakeError::SetupFailure(err)) => return Err(openssl_error(err)),
+                Err(HandshakeError::Failure(mut mid_handshake)) => {
+                    // the alert telling the peer why
+                    let outbound = mem::take(&mut mid_handshake.get_mut().outbound);
+                    let _ = send_all(&conn, outbound).await;
+                    return Err(openssl_error(mid_handshake.into_error()));
+                }
+            };
+
+            let outbound = mem::take(&mut mid_handshake.get_mut().outbound);
+            send_all(&conn, outbound).await?;
+            match tokio::time::timeout(RETRANSMIT_POLL_INTERVAL, inbound_rx.recv()).await {
+                Ok(Some(datagram)) => mid_handshake.get_mut().inbound.push_back(datagram),
+                Ok(None) => return Err(Error::ErrConnClosed),
+                Err(_) => {}
+            }
+            result = mid_handshake.handshake();
+        };
+
+        // the last flight, e.g. the server's Finished when we are the server
+        let outbound = mem::take(&mut stream.get_mut().outbound);
+        send_all(&conn, outbound).await?;
+
+        if config.security_profile == DtlsSecurityProfile::Strict
+            && stream.ssl().extms_support() != Some(true)
+        {
+            return Err(if config.is_client {
+                Error::ErrClientRequiredButNoServerEms
+            } else {
+                Error::ErrServerRequiredButNoClientEms
+            });
+        }
+
+        Ok(Arc::new(OpenSslSession {
+            conn,
+            stream: Mutex::new(stream),
+            inbound: AsyncMutex::new(inbound_rx),
+            closed: AtomicBool::new(false),
+        }))
+    }
+}
+
+/// OpenSslSession is a DTLS connection established by OpenSSL. The stream is only locked
+/// for the synchronous OpenSSL calls, the datagrams they produce are sent after unlocking.
+struct OpenSslSession {
+    conn: Arc<dyn Conn + Send + Sync>,
+    stream: Mutex<SslStream<Datagrams>>,
+    inbound: AsyncMutex<mpsc::Receiver<Vec<u8>>>,
+    closed: AtomicBool,
+}
+
+impl OpenSslSession {
+    fn closed_error() -> UtilError {
+        UtilError::from_std(Error::ErrConnClosed)
+    }
+}
+
+#[async_trait]
+impl Conn for OpenSslSession {
+    async fn connect(&self, _addr: SocketAddr) -> UtilResult<()> {
+        Err(UtilError::ErrNotApplicable)
+    }
+
+    async fn recv(&self, buf: &mut [u8]) -> UtilResult<usize> {
+        let mut inbound = self.inbound.lock().await;
+        loop {
+            let (result, outbound) = {
+                let mut stream = self.stream.lock().unwrap();
+                let result = stream.ssl_read(buf);
+                (result, mem::take(&mut stream.get_mut().outbound))
+            };
+            // reading may answer the peer, e.g. resend our last flight if the peer's was
+            // repeated
+            send_all(&self.conn, outbound)
+                .await
+                .map_err(UtilError::from_std)?;
+            match result {
+                Ok(n) => return Ok(n),
+                Err(err) if err.code() == ErrorCode::WANT_READ => {}
+                Err(err) if err.code() == ErrorCode::ZERO_RETURN => {
+                    return Err(Self::closed_error())
+                }
+                Err(err) => return Err(UtilError::from_std(openssl_error(err))),
+            }
+
+            let datagram = inbound.recv().await.ok_or_else(Self::closed_error)?;
+            let mut stream = self.stream.lock().unwrap();
+            stream.get_mut().inbound.push_back(datagram);
+        }
+    }
+
+    async fn recv_from(&self, buf: &mut [u8]) -> UtilResult<(usize, SocketAddr)> {
+        let raddr = self
+            .conn
+            .remote_addr()
+            .await
+            .ok_or(UtilError::ErrNoRemAddr)?;
+        Ok((self.recv(buf).await?, raddr))
+    }
+
+    async fn send(&self, buf: &[u8]) -> UtilResult<usize> {
+        if self.closed.load(Ordering::SeqCst) {
+            return Err(Self::closed_error());
+        }
+        let (result, outbound) = {
+            let mut stream = self.stream.lock().unwrap();
+            let result = stream.ssl_write(buf);
+            (result, mem::take(&mut stream.get_mut().outbound))
+        };
+        send_all(&self.conn, outbound)
+            .await
+            .map_err(UtilError::from_std)?;
+        result.map_err(|err| UtilError::from_std(openssl_error(err)))
+    }
+
+    async fn send_to(&self, _buf: &[u8], _target: SocketAddr) -> UtilResult<usize> {
+        Err(UtilError::ErrNotApplicable)
+    }
+
+    async fn local_addr(&self) -> UtilResult<SocketAddr> {
+        self.conn.local_addr().await
+    }
+
+    async fn remote_addr(&self) -> Option<SocketAddr> {
+        self.conn.remote_addr().await
+    }
+
+    async fn close(&self) -> UtilResult<()> {
+        if self.closed.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+        let outbound = {
+            let mut stream = self.stream.lock().unwrap();
+            // queues the close_notify alert
+            let _ = stream.shutdown();
+            mem::take(&mut stream.get_mut().outbound)
+        };
+        let _ = send_all(&self.conn, outbound).await;
+        self.conn.close().await
+    }
+}
+
+#[async_trait]
+impl DtlsSession for OpenSslSession {
+    fn conn(self: Arc<Self>) -> Arc<dyn Conn + Send + Sync> {
+        self
+    }
+
+    #[cfg(feature = "serde")]
+    fn cipher_suite(&self) -> Option<String> {
+        let stream = self.stream.try_lock().ok()?;
+        let cipher_suite = stream.ssl().current_cipher()?.standard_name()?;
+        Some(cipher_suite.to_owned())
+    }
+
+    async fn connection_info(&self) -> Option<DtlsConnectionInfo> {
+        let stream = self.stream.lock().unwrap();
+        let ssl = stream.ssl();
+        Some(DtlsConnectionInfo {
+            // the context only allows DTLS 1.2
+            version: "DTLS 1.2".to_owned(),
+            cipher_suite: ssl.current_cipher()?.standard_name()?.to_owned(),
+            ems_used: ssl.extms_support() == Some(true),
+            srtp_profile: None,
+            connection_id_used: false,
+        })
+    }
+
+    async fn export_keying_material(
+        &self,
+        label: &str,
+        context: &[u8],
+        length: usize,
+    ) -> Result<Vec<u8>, Error> {
+        if context.len() > u16::MAX as usize {
+            return Err(KeyingMaterialExporterError::ContextTooLong.into());
+        } else if INVALID_KEYING_LABELS.contains_key(label) {
+            return Err(KeyingMaterialExporterError::ReservedExportKeyingMaterial.into());
+        }
+
+        let mut keying_material = vec![0u8; length];
+        let context = if context.is_empty() {
+            None
+        } else {
+            Some(context)
+        };
+        let stream = self.stream.lock().unwrap();
+        stream
+            .ssl()
+            .export_keying_material(&mut keying_material, label, context)
+            .map_err(openssl_error)?;
+        Ok(keying_material)
+    }
+
+    async fn remote_certificates(&self) -> Vec<Vec<u8>> {
+        let stream = self.stream.lock().unwrap();
+        let ssl = stream.ssl();
+        let mut certificates: Vec<Vec<u8>> = vec![];
+        // as a client the chain starts with the peer's own certificate, as a server it does not
+        let chain = ssl.peer_certificate().into_iter().chain(
+            ssl.peer_cert_chain()
+                .into_iter()
+                .flat_map(|chain| chain.iter().map(|cert| cert.to_owned())),
+        );
+        for cert in chain {
+            if let Ok(der) = cert.to_der() {
+                if !certificates.contains(&der) {
+                    certificates.push(der);
+                }
+            }
+        }
+        certificates
+    }
+}
@@ -0,0 +1,95 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::webrtc::dtls::config::{ClientAuthType, Config, ExtendedMasterSecretType};
+use crate::webrtc::dtls::conn::DTLSConn;
+use crate::webrtc::dtls::extension::extension_use_srtp::SrtpProtectionProfile;
+use crate::webrtc::dtls::Error;
+use crate::webrtc::util::Conn;
+
+use super::{DtlsBackend, DtlsHandshakeConfig, DtlsSession};
+use crate::webrtc::dtls_transport::dtls_connection_info::DtlsConnectionInfo;
+use crate::webrtc::dtls_transport::dtls_security_profile::DtlsSecurityProfile;
+
+/// VendoredBackend runs the handshake on the DTLS implementation of the crate
+pub(crate) struct VendoredBackend;
+
+#[async_trait]
+impl DtlsBackend for VendoredBackend {
+    async fn handshake(
+        &self,
+        conn: Arc<dyn Conn + Send + Sync>,
+        config: DtlsHandshakeConfig,
+    ) -> Result<Arc<dyn DtlsSession>, Error> {
+        let strict = config.security_profile == DtlsSecurityProfile::Strict;
+        let dtls_config = Config {
+            certificates: vec![config.certificate],
+            srtp_protection_profiles: vec![],
+            client_auth: ClientAuthType::RequireAnyClientCert,
+            insecure_skip_verify: true,
+            extended_master_secret: if strict {
+                ExtendedMasterSecretType::Require
+            } else {
+                ExtendedMasterSecretType::Request
+            },
+            disallow_renegotiation: strict,
+            #[cfg(feature = "dtls-connection-id")]
+            connection_id: config.connection_id,
+            receive_mtu: config.receive_mtu,
+            spawner: config.spawner,
+            ..Default::default()
+        };
+
+        let dtls_conn = DTLSConn::new(conn, dtls_config, config.is_client, None).await?;
+        Ok(Arc::new(dtls_conn))
+    }
+}
+
+#[async_trait]
+impl DtlsSession for DTLSConn {
+    fn conn(self: Arc<Self>) -> Arc<dyn Conn + Send + Sync> {
+        self
+    }
+
+    #[cfg(feature = "serde")]
+    fn cipher_suite(&self) -> Option<String> {
+        let cipher_suite = self.state.cipher_suite.try_lock().ok()?;
+        cipher_suite
+            .as_ref()
+            .map(|cipher_suite| cipher_suite.to_string())
+    }
+
+    async fn connection_info(&self) -> Option<DtlsConnectionInfo> {
+        let cipher_suite = {
+            let cipher_suite = self.state.cipher_suite.lock().await;
+            cipher_suite.as_ref()?.to_string()
+        };
+        let srtp_profile = match self.state.srtp_protection_profile {
+            SrtpProtectionProfile::Unsupported => None,
+            profile => Some(profile.to_string()),
+        };
+
+        Some(DtlsConnectionInfo {
+            // flight 3 refuses a server hello of any other version
+            version: "DTLS 1.2".to_owned(),
+            cipher_suite,
+            ems_used: self.state.extended_master_secret,
+            srtp_profile,
+            connection_id_used: self.state.connection_id_negotiated,
+        })
+    }
+
+    async fn export_keying_material(
+        &self,
+        label: &str,
+        context: &[u8],
+        length: usize,
+    ) -> Result<Vec<u8>, Error> {
+        DTLSConn::export_keying_material(self, label, context, length).await
+    }
+
+    async fn remote_certificates(&self) -> Vec<Vec<u8>> {
+        self.state.peer_certificates.clone()
+    }
+}
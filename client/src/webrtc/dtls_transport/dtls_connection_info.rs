@@ -0,0 +1,19 @@
+/// DtlsConnectionInfo describes what the DTLS handshake negotiated, see
+/// SocketHandle::dtls_connection_info
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DtlsConnectionInfo {
+    /// version is the protocol version, always "DTLS 1.2"
+    pub version: String,
+    /// cipher_suite is the name of the cipher suite, e.g.
+    /// "TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256"
+    pub cipher_suite: String,
+    /// ems_used tells whether the master secret was derived with the extended master secret
+    /// extension of RFC 7627
+    pub ems_used: bool,
+    /// srtp_profile is the negotiated SRTP protection profile, None since data channels do not
+    /// use SRTP
+    pub srtp_profile: Option<String>,
+    /// connection_id_used tells whether the server agreed to the Connection IDs of RFC 9146,
+    /// see SettingEngine::set_dtls_connection_id
+    pub connection_id_used: bool,
+}
@@ -0,0 +1,14 @@
+/// DtlsSecurityProfile selects how strict the DTLS handshake is about the peer, see
+/// SettingEngine::set_dtls_security_profile. Either way only DTLS 1.2 is negotiated,
+/// renegotiation is never started and MD5 or SHA-1 signatures are refused.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DtlsSecurityProfile {
+    /// Compatible requests the extended master secret but goes without it if the peer does
+    #[default]
+    Compatible,
+
+    /// Strict fails the handshake unless the peer uses the extended master secret (RFC 7627)
+    /// and announces a renegotiation_info extension that is empty (RFC 5746), i.e. the
+    /// handshake is not a renegotiation.
+    Strict,
+}
@@ -1,8 +1,9 @@
+use serde::Serialize;
 use std::fmt;
 
 /// DTLSTransportState indicates the DTLS transport establishment state.
-#[derive(Debug, Copy, Clone, PartialEq)]
-pub(crate) enum RTCDtlsTransportState {
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+pub enum RTCDtlsTransportState {
     Unspecified = 0,
 
     /// DTLSTransportStateNew indicates that DTLS has not started negotiating
@@ -3,12 +3,16 @@ use std::pin::Pin;
 use std::sync::atomic::{AtomicU8, Ordering};
 use std::sync::Arc;
 
-use crate::webrtc::dtls::config::ClientAuthType;
-use crate::webrtc::dtls::conn::DTLSConn;
-use crate::webrtc::util::Conn;
+use crate::webrtc::util::spawner::Spawner;
+use crate::webrtc::util::{Conn, KeyingMaterialExporterError};
 use tokio::sync::Mutex;
 
+use dtls_backend::{DtlsBackendKind, DtlsHandshakeConfig, DtlsSession};
+use dtls_connection_info::DtlsConnectionInfo;
 use dtls_role::*;
+use dtls_security_profile::DtlsSecurityProfile;
+use packet_interceptor::{InterceptedConn, PacketInterceptor};
+use raw_channel::{RawChannel, RawChannelDemuxFn, RawChannels, RawDemuxConn};
 
 use crate::webrtc::dtls_transport::dtls_parameters::DTLSParameters;
 use crate::webrtc::dtls_transport::dtls_transport_state::RTCDtlsTransportState;
@@ -17,11 +21,17 @@ use crate::webrtc::ice_transport::ice_transport_state::RTCIceTransportState;
 use crate::webrtc::ice_transport::RTCIceTransport;
 use crate::webrtc::mux::mux_func::match_dtls;
 use crate::webrtc::peer_connection::certificate::RTCCertificate;
+use crate::webrtc::peer_connection::event_log::{ConnectionEventKind, EventLog};
 
+pub(crate) mod dtls_backend;
+pub(crate) mod dtls_connection_info;
 pub(crate) mod dtls_fingerprint;
 pub(crate) mod dtls_parameters;
 pub(crate) mod dtls_role;
+pub(crate) mod dtls_security_profile;
 pub(crate) mod dtls_transport_state;
+pub(crate) mod packet_interceptor;
+pub(crate) mod raw_channel;
 
 pub(crate) type OnDTLSTransportStateChangeHdlrFn = Box<
     dyn (FnMut(RTCDtlsTransportState) -> Pin<Box<dyn Future<Output = ()> + Send + 'static>>)
@@ -37,34 +47,115 @@ pub(crate) type OnDTLSTransportStateChangeHdlrFn = Box<
 pub(crate) struct RTCDtlsTransport {
     pub(crate) ice_transport: Arc<RTCIceTransport>,
     pub(crate) certificates: Vec<RTCCertificate>,
+    pub(crate) packet_interceptors: Vec<Arc<dyn PacketInterceptor>>,
+    pub(crate) security_profile: DtlsSecurityProfile,
+    pub(crate) backend: DtlsBackendKind,
+    pub(crate) event_log: Arc<EventLog>,
+    pub(crate) spawner: Spawner,
+    /// connection_id is offered to the server, see SettingEngine::set_dtls_connection_id
+    #[cfg(feature = "dtls-connection-id")]
+    pub(crate) connection_id: Option<Vec<u8>>,
 
     pub(crate) remote_parameters: Mutex<DTLSParameters>,
     pub(crate) state: AtomicU8, //DTLSTransportState,
+    /// handshake_error is why the handshake failed, it is set before the state turns Failed
+    pub(crate) handshake_error: std::sync::Mutex<Option<Arc<Error>>>,
     pub(crate) on_state_change_handler: Arc<Mutex<Option<OnDTLSTransportStateChangeHdlrFn>>>,
-    pub(crate) conn: Mutex<Option<Arc<DTLSConn>>>,
+    pub(crate) conn: Mutex<Option<Arc<dyn DtlsSession>>>,
+    pub(crate) raw_channels: Arc<RawChannels>,
 }
 
 impl RTCDtlsTransport {
     pub(crate) fn new(
         ice_transport: Arc<RTCIceTransport>,
         certificates: Vec<RTCCertificate>,
+        packet_interceptors: Vec<Arc<dyn PacketInterceptor>>,
+        security_profile: DtlsSecurityProfile,
+        backend: DtlsBackendKind,
+        event_log: Arc<EventLog>,
+        spawner: Spawner,
     ) -> Self {
         RTCDtlsTransport {
             ice_transport,
             certificates,
+            packet_interceptors,
+            security_profile,
+            backend,
+            event_log,
+            spawner,
             state: AtomicU8::new(RTCDtlsTransportState::New as u8),
             ..Default::default()
         }
     }
 
-    pub(crate) async fn conn(&self) -> Option<Arc<DTLSConn>> {
+    pub(crate) async fn conn(&self) -> Option<Arc<dyn DtlsSession>> {
         let conn = self.conn.lock().await;
         conn.clone()
     }
 
+    /// sctp_conn returns the DTLS connection for the SCTP association, without the datagrams
+    /// taken by raw channels. None until the handshake completed.
+    pub(crate) async fn sctp_conn(&self) -> Option<Arc<dyn Conn + Send + Sync>> {
+        let conn = self.conn().await?.conn();
+        Some(Arc::new(RawDemuxConn::new(
+            conn,
+            Arc::clone(&self.raw_channels),
+        )))
+    }
+
+    /// create_raw_channel adds a RawChannel for the inbound application data demux matches,
+    /// queueing up to capacity datagrams. It returns None unless the handshake completed and
+    /// the connection is still open.
+    pub(crate) async fn create_raw_channel(
+        &self,
+        demux: RawChannelDemuxFn,
+        capacity: usize,
+    ) -> Option<RawChannel> {
+        let conn = self.conn().await?.conn();
+        self.raw_channels.create(conn, demux, capacity)
+    }
+
+    /// connection_info returns what the handshake negotiated, None until it completed
+    pub(crate) async fn connection_info(&self) -> Option<DtlsConnectionInfo> {
+        self.conn().await?.connection_info().await
+    }
+
+    /// remote_certificates returns the DER certificate chain the remote presented in the
+    /// handshake, empty until it completed
+    pub(crate) async fn remote_certificates(&self) -> Vec<Vec<u8>> {
+        match self.conn().await {
+            Some(conn) => conn.remote_certificates().await,
+            None => vec![],
+        }
+    }
+
+    /// export_keying_material derives length bytes of keying material both peers agree on from
+    /// the DTLS master secret, RFC 5705. It fails until the handshake completed.
+    pub(crate) async fn export_keying_material(
+        &self,
+        label: &str,
+        context: &[u8],
+        length: usize,
+    ) -> Result<Vec<u8>> {
+        let conn = self.conn().await.ok_or_else(|| {
+            crate::webrtc::dtls::Error::from(KeyingMaterialExporterError::HandshakeInProgress)
+        })?;
+        Ok(conn.export_keying_material(label, context, length).await?)
+    }
+
+    /// handshake_error returns why the handshake failed, if it did
+    pub(crate) fn handshake_error(&self) -> Option<Arc<Error>> {
+        self.handshake_error
+            .lock()
+            .ok()
+            .and_then(|error| error.clone())
+    }
+
     /// state_change requires the caller holds the lock
     async fn state_change(&self, state: RTCDtlsTransportState) {
         self.state.store(state as u8, Ordering::SeqCst);
+        self.event_log
+            .record(ConnectionEventKind::DtlsTransportState(state));
         let mut handler = self.on_state_change_handler.lock().await;
         if let Some(f) = &mut *handler {
             f(state).await;
@@ -76,10 +167,21 @@ impl RTCDtlsTransport {
         self.state.load(Ordering::SeqCst).into()
     }
 
+    /// role returns the DTLS role this side takes, the opposite of the one the remote picked with
+    /// a=setup:active or a=setup:passive in its answer. Our offers are always a=setup:actpass,
+    /// so without an explicit choice, e.g. an answer that kept actpass, we act as the client.
+    pub(crate) async fn role(&self) -> DTLSRole {
+        let remote_parameters = self.remote_parameters.lock().await;
+        match remote_parameters.role {
+            DTLSRole::Client => DTLSRole::Server,
+            _ => DTLSRole::Client,
+        }
+    }
+
     async fn prepare_transport(
         &self,
         remote_parameters: DTLSParameters,
-    ) -> Result<(DTLSRole, crate::webrtc::dtls::config::Config)> {
+    ) -> Result<DtlsHandshakeConfig> {
         self.ensure_ice_conn()?;
 
         if self.state() != RTCDtlsTransportState::New {
@@ -98,16 +200,15 @@ impl RTCDtlsTransport {
         };
         self.state_change(RTCDtlsTransportState::Connecting).await;
 
-        Ok((
-            DTLSRole::Client,
-            crate::webrtc::dtls::config::Config {
-                certificates: vec![certificate],
-                srtp_protection_profiles: vec![],
-                client_auth: ClientAuthType::RequireAnyClientCert,
-                insecure_skip_verify: true,
-                ..Default::default()
-            },
-        ))
+        Ok(DtlsHandshakeConfig {
+            is_client: self.role().await == DTLSRole::Client,
+            certificate,
+            security_profile: self.security_profile,
+            receive_mtu: self.ice_transport.receive_mtu(),
+            #[cfg(feature = "dtls-connection-id")]
+            connection_id: self.connection_id.clone(),
+            spawner: self.spawner.clone(),
+        })
     }
 
     /// start DTLS transport negotiation with the parameters of the remote DTLS transport
@@ -115,40 +216,98 @@ impl RTCDtlsTransport {
         let dtls_conn_result = if let Some(dtls_endpoint) =
             self.ice_transport.new_endpoint(Box::new(match_dtls)).await
         {
-            let (_, dtls_config) = self.prepare_transport(remote_parameters).await?;
+            let config = self.prepare_transport(remote_parameters).await?;
+            log::debug!(
+                "starting the DTLS handshake as {} on the {:?} backend",
+                if config.is_client { "client" } else { "server" },
+                self.backend
+            );
+
+            // without interceptors DTLS talks to the endpoint directly
+            let dtls_endpoint = dtls_endpoint as Arc<dyn Conn + Send + Sync>;
+            let dtls_endpoint = if self.packet_interceptors.is_empty() {
+                dtls_endpoint
+            } else {
+                Arc::new(InterceptedConn::new(
+                    dtls_endpoint,
+                    self.packet_interceptors.clone(),
+                ))
+            };
 
             // Connect as DTLS Client/Server, function is blocking and we
             // must not hold the DTLSTransport lock
-            crate::webrtc::dtls::conn::DTLSConn::new(
-                dtls_endpoint as Arc<dyn Conn + Send + Sync>,
-                dtls_config,
-                true,
-                None,
-            )
-            .await
+            self.backend
+                .backend()
+                .handshake(dtls_endpoint, config)
+                .await
         } else {
-            Err(crate::webrtc::dtls::Error::Other(
-                "ice_transport.new_endpoint failed".to_owned(),
-            ))
+            if let Ok(mut handshake_error) = self.handshake_error.lock() {
+                *handshake_error = Some(Arc::new(Error::ErrDTLSEndpointUnavailable));
+            }
+            self.state_change(RTCDtlsTransportState::Failed).await;
+            return Err(Error::ErrDTLSEndpointUnavailable);
         };
 
         let dtls_conn = match dtls_conn_result {
             Ok(dtls_conn) => dtls_conn,
             Err(err) => {
+                let err = Arc::new(Error::from(err));
+                if let Ok(mut handshake_error) = self.handshake_error.lock() {
+                    *handshake_error = Some(Arc::clone(&err));
+                }
                 self.state_change(RTCDtlsTransportState::Failed).await;
-                return Err(err.into());
+                return Err(Error::ErrDTLSHandshakeFailed(err));
             }
         };
 
         {
             let mut conn = self.conn.lock().await;
-            *conn = Some(Arc::new(dtls_conn));
+            *conn = Some(dtls_conn);
         }
         self.state_change(RTCDtlsTransportState::Connected).await;
 
         Ok(())
     }
 
+    /// stop stops and closes the DTLSTransport object.
+    pub(crate) async fn stop(&self) -> Result<()> {
+        let conn = {
+            let mut conn = self.conn.lock().await;
+            conn.take()
+        };
+        self.state_change(RTCDtlsTransportState::Closed).await;
+        self.raw_channels.close();
+
+        if let Some(conn) = conn {
+            conn.close().await?;
+        }
+        Ok(())
+    }
+
+    /// restart replaces the DTLS connection with a new handshake under remote_parameters, for a
+    /// remote that came back from an ICE restart with another certificate. The old connection
+    /// and its raw channels are closed, the ICE connection underneath is kept. Unlike stop the
+    /// state goes back to Connecting instead of Closed.
+    pub(crate) async fn restart(&self, remote_parameters: DTLSParameters) -> Result<()> {
+        let conn = {
+            let mut conn = self.conn.lock().await;
+            conn.take()
+        };
+        if let Some(conn) = conn {
+            if let Err(err) = conn.close().await {
+                log::debug!("closing the DTLS connection for a restart: {}", err);
+            }
+        }
+        self.raw_channels.reset();
+        if let Ok(mut handshake_error) = self.handshake_error.lock() {
+            *handshake_error = None;
+        }
+
+        self.state
+            .store(RTCDtlsTransportState::New as u8, Ordering::SeqCst);
+        self.start(remote_parameters).await
+    }
+
     pub(crate) fn ensure_ice_conn(&self) -> Result<()> {
         if self.ice_transport.state() == RTCIceTransportState::New {
             Err(Error::ErrICEConnectionNotStarted)
@@ -0,0 +1,98 @@
+use crate::webrtc::util::Conn;
+
+use async_trait::async_trait;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+type Result<T> = std::result::Result<T, crate::webrtc::util::Error>;
+
+/// PacketInterceptor sees every DTLS datagram exchanged with the remote, as protected on the
+/// wire, see SettingEngine::add_packet_interceptor. It is called on the send and receive paths,
+/// so it should return quickly and hand heavier work, e.g. writing a capture file, to a task.
+pub trait PacketInterceptor: Send + Sync {
+    /// before_send is called with each datagram before it is handed to ICE. Returning false
+    /// drops it, the DTLS connection considers it sent.
+    fn before_send(&self, packet: &[u8]) -> bool {
+        let _ = packet;
+        true
+    }
+
+    /// after_receive is called with each datagram ICE received before DTLS reads it. Returning
+    /// false drops it.
+    fn after_receive(&self, packet: &[u8]) -> bool {
+        let _ = packet;
+        true
+    }
+}
+
+/// InterceptedConn runs the packets of a conn through the registered interceptors, in the
+/// order they were added. It is only put between DTLS and ICE when there is an interceptor.
+pub(crate) struct InterceptedConn {
+    next_conn: Arc<dyn Conn + Send + Sync>,
+    interceptors: Vec<Arc<dyn PacketInterceptor>>,
+}
+
+impl InterceptedConn {
+    pub(crate) fn new(
+        next_conn: Arc<dyn Conn + Send + Sync>,
+        interceptors: Vec<Arc<dyn PacketInterceptor>>,
+    ) -> Self {
+        InterceptedConn {
+            next_conn,
+            interceptors,
+        }
+    }
+}
+
+#[async_trait]
+impl Conn for InterceptedConn {
+    async fn connect(&self, addr: SocketAddr) -> Result<()> {
+        self.next_conn.connect(addr).await
+    }
+
+    async fn recv(&self, buf: &mut [u8]) -> Result<usize> {
+        loop {
+            let n = self.next_conn.recv(buf).await?;
+            if self.interceptors.iter().all(|i| i.after_receive(&buf[..n])) {
+                return Ok(n);
+            }
+        }
+    }
+
+    async fn recv_from(&self, buf: &mut [u8]) -> Result<(usize, SocketAddr)> {
+        loop {
+            let (n, addr) = self.next_conn.recv_from(buf).await?;
+            if self.interceptors.iter().all(|i| i.after_receive(&buf[..n])) {
+                return Ok((n, addr));
+            }
+        }
+    }
+
+    async fn send(&self, buf: &[u8]) -> Result<usize> {
+        if self.interceptors.iter().all(|i| i.before_send(buf)) {
+            self.next_conn.send(buf).await
+        } else {
+            Ok(buf.len())
+        }
+    }
+
+    async fn send_to(&self, buf: &[u8], target: SocketAddr) -> Result<usize> {
+        if self.interceptors.iter().all(|i| i.before_send(buf)) {
+            self.next_conn.send_to(buf, target).await
+        } else {
+            Ok(buf.len())
+        }
+    }
+
+    async fn local_addr(&self) -> Result<SocketAddr> {
+        self.next_conn.local_addr().await
+    }
+
+    async fn remote_addr(&self) -> Option<SocketAddr> {
+        self.next_conn.remote_addr().await
+    }
+
+    async fn close(&self) -> Result<()> {
+        self.next_conn.close().await
+    }
+}
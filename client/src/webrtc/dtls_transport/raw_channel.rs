@@ -0,0 +1,270 @@
+use crate::webrtc::util::Conn;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, Mutex as AsyncMutex};
+
+type Result<T> = std::result::Result<T, crate::webrtc::util::Error>;
+
+/// RawChannelDemuxFn tells whether an inbound DTLS application data record belongs to a raw
+/// channel, see SocketHandle::create_raw_channel. It is called for every record the remote
+/// sends, so it should only look at a few leading bytes.
+pub type RawChannelDemuxFn = Box<dyn (Fn(&[u8]) -> bool) + Send + Sync>;
+
+/// RawChannel sends and receives datagrams as DTLS application data next to the SCTP
+/// association, e.g. to tunnel a custom protocol over the connection ICE and DTLS set up. The
+/// remote has to tell them apart from SCTP packets the same way, by a leading byte the
+/// protocol controls. SCTP packets of WebRTC start with their source port, usually 5000, so
+/// 0x13 is the first byte to stay away from.
+///
+/// Inbound records go to the first raw channel, in the order they were created, whose demux
+/// function matches and everything else goes to SCTP. datagrams_overlapping counts records a
+/// later channel would have matched as well. Datagrams are only read while the SCTP
+/// association reads, which it does for as long as the session is open.
+pub struct RawChannel {
+    state: Arc<RawChannelState>,
+    raw_channels: Arc<RawChannels>,
+    conn: Arc<dyn Conn + Send + Sync>,
+    receiver: AsyncMutex<mpsc::Receiver<Bytes>>,
+}
+
+impl RawChannel {
+    /// send sends datagram as a single DTLS application data record and returns its length. It
+    /// has to fit into one record, i.e. the path MTU minus the DTLS and UDP overhead. Once the
+    /// channel is closed it fails with std::io::ErrorKind::NotConnected.
+    pub async fn send(&self, datagram: &[u8]) -> io::Result<usize> {
+        if self.state.closed.load(Ordering::SeqCst) {
+            return Err(io::ErrorKind::NotConnected.into());
+        }
+        self.conn
+            .send(datagram)
+            .await
+            .map_err(|err| io::Error::other(err.to_string()))
+    }
+
+    /// recv waits for the next datagram and copies it into buf, returning its length. Like
+    /// with UDP the part that does not fit into buf is discarded. Once the channel is closed
+    /// and the datagrams received before are drained it fails with
+    /// std::io::ErrorKind::NotConnected.
+    pub async fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        let datagram = {
+            let mut receiver = self.receiver.lock().await;
+            receiver.recv().await
+        };
+        match datagram {
+            Some(datagram) => {
+                let n = datagram.len().min(buf.len());
+                buf[..n].copy_from_slice(&datagram[..n]);
+                Ok(n)
+            }
+            None => Err(io::ErrorKind::NotConnected.into()),
+        }
+    }
+
+    /// datagrams_dropped returns how many inbound datagrams were discarded because
+    /// channel_capacity of them were waiting for recv
+    pub fn datagrams_dropped(&self) -> u64 {
+        self.state.dropped.load(Ordering::SeqCst)
+    }
+
+    /// datagrams_overlapping returns how many inbound datagrams this channel took that the demux
+    /// function of a channel created later matched as well. Anything but zero means the demux
+    /// functions overlap and the later channel misses those datagrams.
+    pub fn datagrams_overlapping(&self) -> u64 {
+        self.state.overlapping.load(Ordering::SeqCst)
+    }
+
+    /// close stops the channel, the datagrams its demux function matched go to SCTP again
+    pub fn close(&self) {
+        self.raw_channels.remove(&self.state);
+    }
+}
+
+impl Drop for RawChannel {
+    fn drop(&mut self) {
+        self.raw_channels.remove(&self.state);
+    }
+}
+
+#[derive(Default)]
+struct RawChannelState {
+    closed: AtomicBool,
+    dropped: AtomicU64,
+    overlapping: AtomicU64,
+}
+
+// the sender is only held here, so recv ends once the entry is removed
+struct RawChannelEntry {
+    demux: RawChannelDemuxFn,
+    sender: mpsc::Sender<Bytes>,
+    state: Arc<RawChannelState>,
+}
+
+/// RawChannels holds the raw channels of a DTLS transport in the order they were created
+#[derive(Default)]
+pub(crate) struct RawChannels {
+    channels: Mutex<Vec<RawChannelEntry>>,
+    closed: AtomicBool,
+}
+
+impl RawChannels {
+    /// create adds a raw channel behind the ones created before, queueing up to capacity
+    /// inbound datagrams. It returns None once the DTLS connection is closed.
+    pub(crate) fn create(
+        self: &Arc<Self>,
+        conn: Arc<dyn Conn + Send + Sync>,
+        demux: RawChannelDemuxFn,
+        capacity: usize,
+    ) -> Option<RawChannel> {
+        let (sender, receiver) = mpsc::channel(capacity.max(1));
+        let state = Arc::new(RawChannelState::default());
+        {
+            // checked under the lock, close clears the channels holding it
+            let mut channels = self.channels.lock().unwrap();
+            if self.closed.load(Ordering::SeqCst) {
+                return None;
+            }
+            channels.push(RawChannelEntry {
+                demux,
+                sender,
+                state: Arc::clone(&state),
+            });
+        }
+
+        Some(RawChannel {
+            state,
+            raw_channels: Arc::clone(self),
+            conn,
+            receiver: AsyncMutex::new(receiver),
+        })
+    }
+
+    fn remove(&self, state: &Arc<RawChannelState>) {
+        state.closed.store(true, Ordering::SeqCst);
+        let mut channels = self.channels.lock().unwrap();
+        channels.retain(|entry| !Arc::ptr_eq(&entry.state, state));
+    }
+
+    /// close ends every raw channel, their recv fails once they are drained
+    pub(crate) fn close(&self) {
+        let mut channels = self.channels.lock().unwrap();
+        self.closed.store(true, Ordering::SeqCst);
+        for entry in channels.drain(..) {
+            entry.state.closed.store(true, Ordering::SeqCst);
+        }
+    }
+
+    /// reset ends every raw channel like close, but new ones can be created for the next DTLS
+    /// connection
+    pub(crate) fn reset(&self) {
+        let mut channels = self.channels.lock().unwrap();
+        for entry in channels.drain(..) {
+            entry.state.closed.store(true, Ordering::SeqCst);
+        }
+    }
+
+    /// dispatch hands datagram to the first raw channel that takes it, it returns false if
+    /// none does
+    fn dispatch(&self, datagram: &[u8]) -> bool {
+        let channels = self.channels.lock().unwrap();
+        let mut matching = channels.iter().filter(|entry| (entry.demux)(datagram));
+        let entry = match matching.next() {
+            Some(entry) => entry,
+            None => return false,
+        };
+
+        if matching.next().is_some() && entry.state.overlapping.fetch_add(1, Ordering::SeqCst) == 0
+        {
+            log::warn!("raw channel demux functions overlap, the channel created first wins");
+        }
+        if entry
+            .sender
+            .try_send(Bytes::copy_from_slice(datagram))
+            .is_err()
+        {
+            entry.state.dropped.fetch_add(1, Ordering::SeqCst);
+        }
+        true
+    }
+}
+
+/// RawDemuxConn is the DTLS connection as SCTP sees it, the datagrams a raw channel takes are
+/// passed on to it instead. Once reading fails the raw channels are closed.
+pub(crate) struct RawDemuxConn {
+    next_conn: Arc<dyn Conn + Send + Sync>,
+    raw_channels: Arc<RawChannels>,
+}
+
+impl RawDemuxConn {
+    pub(crate) fn new(
+        next_conn: Arc<dyn Conn + Send + Sync>,
+        raw_channels: Arc<RawChannels>,
+    ) -> Self {
+        RawDemuxConn {
+            next_conn,
+            raw_channels,
+        }
+    }
+}
+
+#[async_trait]
+impl Conn for RawDemuxConn {
+    async fn connect(&self, addr: SocketAddr) -> Result<()> {
+        self.next_conn.connect(addr).await
+    }
+
+    async fn recv(&self, buf: &mut [u8]) -> Result<usize> {
+        loop {
+            let n = match self.next_conn.recv(buf).await {
+                Ok(n) => n,
+                Err(err) => {
+                    self.raw_channels.close();
+                    return Err(err);
+                }
+            };
+            if !self.raw_channels.dispatch(&buf[..n]) {
+                return Ok(n);
+            }
+        }
+    }
+
+    async fn recv_from(&self, buf: &mut [u8]) -> Result<(usize, SocketAddr)> {
+        loop {
+            let (n, addr) = match self.next_conn.recv_from(buf).await {
+                Ok(received) => received,
+                Err(err) => {
+                    self.raw_channels.close();
+                    return Err(err);
+                }
+            };
+            if !self.raw_channels.dispatch(&buf[..n]) {
+                return Ok((n, addr));
+            }
+        }
+    }
+
+    async fn send(&self, buf: &[u8]) -> Result<usize> {
+        self.next_conn.send(buf).await
+    }
+
+    async fn send_to(&self, buf: &[u8], target: SocketAddr) -> Result<usize> {
+        self.next_conn.send_to(buf, target).await
+    }
+
+    async fn local_addr(&self) -> Result<SocketAddr> {
+        self.next_conn.local_addr().await
+    }
+
+    async fn remote_addr(&self) -> Option<SocketAddr> {
+        self.next_conn.remote_addr().await
+    }
+
+    async fn close(&self) -> Result<()> {
+        self.raw_channels.close();
+        self.next_conn.close().await
+    }
+}
@@ -1,8 +1,12 @@
+use crate::webrtc::peer_connection::sdp::sdp_type::RTCSdpType;
+use crate::webrtc::peer_connection::signaling_state::RTCSignalingState;
+
 use rcgen::RcgenError;
 use std::future::Future;
 use std::num::ParseIntError;
 use std::pin::Pin;
 use std::string::FromUtf8Error;
+use std::sync::Arc;
 use thiserror::Error;
 use tokio::sync::mpsc::error::SendError as MpscSendError;
 
@@ -16,6 +20,11 @@ pub(crate) enum Error {
     #[error("connection closed")]
     ErrConnectionClosed,
 
+    /// ErrConnectionFailed indicates an operation executed after the connection
+    /// failed, i.e. ICE or DTLS gave up.
+    #[error("connection failed")]
+    ErrConnectionFailed,
+
     /// ErrNonCertificate indicates that there is no certificate
     #[error("no certificate")]
     ErrNonCertificate,
@@ -25,6 +34,21 @@ pub(crate) enum Error {
     #[error("remote description is not set")]
     ErrNoRemoteDescription,
 
+    /// ErrInvalidSdpMid indicates a candidate for a mid the remote description has no media
+    /// section for
+    #[error("candidate is for mid \"{0}\" which the remote description does not have")]
+    ErrInvalidSdpMid(String),
+
+    /// ErrInvalidSdpMLineIndex indicates a candidate for a media section index beyond the ones
+    /// of the remote description
+    #[error("candidate is for media section {0} which the remote description does not have")]
+    ErrInvalidSdpMLineIndex(u16),
+
+    /// ErrSdpMidAndMLineIndexMissing indicates a candidate with neither sdp_mid nor
+    /// sdp_mline_index while the remote description has more than one media section
+    #[error("candidate has neither sdp_mid nor sdp_mline_index")]
+    ErrSdpMidAndMLineIndexMissing,
+
     /// ErrSessionDescriptionNoFingerprint indicates set_remote_description was called with a SessionDescription that has no
     /// fingerprint
     #[error("set_remote_description called with no fingerprint")]
@@ -62,6 +86,10 @@ pub(crate) enum Error {
 
     #[error("datachannel not opened yet, try calling Detach from OnOpen")]
     ErrDetachBeforeOpened,
+    /// ErrStringSizeLimit indicates that a data channel label or protocol is
+    /// longer than the 65535 bytes DCEP can carry
+    #[error("data channel label or protocol exceeds 65535 bytes")]
+    ErrStringSizeLimit,
     #[error("attempted to start DTLSTransport that is not in new state")]
     ErrInvalidDTLSStart,
     #[error("identity provider is not implemented")]
@@ -78,6 +106,49 @@ pub(crate) enum Error {
     ErrSDPDoesNotMatchOffer,
     #[error("new sdp does not match previous answer")]
     ErrSDPDoesNotMatchAnswer,
+    /// ErrIllegalSdpTypeForState indicates set_remote_description was called with a description
+    /// the signaling state does not expect, e.g. an answer while no offer is waiting for one
+    #[error("remote {sdp_type} is not legal in signaling state {state}, was it sent twice or swapped with the offer?")]
+    ErrIllegalSdpTypeForState {
+        sdp_type: RTCSdpType,
+        state: RTCSignalingState,
+    },
+    /// ErrSdpMediaSectionCountMismatch indicates an answer that does not have one media section
+    /// per offered one, it most likely answers another offer
+    #[error("answer has {answered} media sections but the offer has {offered}, does it answer another offer?")]
+    ErrSdpMediaSectionCountMismatch { offered: usize, answered: usize },
+    /// ErrSdpMidMismatch indicates an answer whose media section has another mid than the
+    /// offered one at the same position
+    #[error("answer media section {index} has mid \"{answered}\" but the offer has \"{offered}\", does it answer another offer?")]
+    ErrSdpMidMismatch {
+        index: usize,
+        offered: String,
+        answered: String,
+    },
+    /// ErrSdpBundleGroupMismatch indicates an answer that bundles a mid the offer did not bundle
+    #[error("answer bundles mid \"{0}\" which the offer did not bundle")]
+    ErrSdpBundleGroupMismatch(String),
+    /// ErrSdpIllegalSetupInAnswer indicates an answer that does not pick a DTLS role with
+    /// a=setup:active or a=setup:passive (RFC 5763 section 5)
+    #[error("answer has a=setup:{0}, an answer must be active or passive; is it an offer?")]
+    ErrSdpIllegalSetupInAnswer(String),
+    /// ErrSdpConflictingSetup indicates an answer whose media sections pick different DTLS roles
+    #[error("answer media sections have conflicting a=setup values")]
+    ErrSdpConflictingSetup,
+    /// ErrRemoteCredentialsChanged indicates an answer with other ICE credentials or another
+    /// DTLS fingerprint than the current remote description, while the offer it answers did not
+    /// request an ICE restart
+    #[error("answer changes the remote {0} but the offer did not request an ICE restart")]
+    ErrRemoteCredentialsChanged(&'static str),
+    /// ErrNoDataMediaSection indicates a remote description that cannot carry data channels: it
+    /// has no m=application section, rejected it with port 0, or the section is not SCTP over
+    /// DTLS. Without it no DTLS handshake would ever start.
+    #[error("remote description has no usable data media section: {0}; is the server set up for data channels?")]
+    ErrNoDataMediaSection(String),
+    /// ErrNoCandidatesGathered indicates ICE gathering completed without a local candidate, so
+    /// no connectivity check could ever be sent, see SettingEngine::set_allow_zero_candidates
+    #[error("ICE gathering completed without a local candidate; are all network interfaces filtered out or down?")]
+    ErrNoCandidatesGathered,
     #[error("provided value is not a valid enum value of type SDPType")]
     ErrPeerConnSDPTypeInvalidValue,
     #[error("invalid state change op")]
@@ -94,6 +165,19 @@ pub(crate) enum Error {
     ErrICETransportNotInNew,
     #[error("SCTP is not established")]
     ErrSCTPNotEstablished,
+    #[error("sdp modifier attribute {0} is already present")]
+    ErrSDPModifierDuplicateAttribute(String),
+    #[error("ICE transport has no endpoint for DTLS")]
+    ErrDTLSEndpointUnavailable,
+    #[error("DTLS handshake failed: {0}")]
+    ErrDTLSHandshakeFailed(#[source] Arc<Error>),
+    #[error("unsupported certificate key pair")]
+    ErrUnsupportedKeyPair,
+    #[error("{algorithm} private key rejected: {reason}")]
+    ErrPrivateKeyRejected {
+        algorithm: &'static str,
+        reason: String,
+    },
 
     #[error("{0}")]
     Util(#[from] crate::webrtc::util::Error),
@@ -118,10 +202,6 @@ pub(crate) enum Error {
     ParseInt(#[from] ParseIntError),
     #[error("parse url: {0}")]
     ParseUrl(#[from] url::ParseError),
-
-    #[allow(non_camel_case_types)]
-    #[error("{0}")]
-    new(String),
 }
 
 pub(crate) type OnErrorHdlrFn =
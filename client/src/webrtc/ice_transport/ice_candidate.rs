@@ -3,26 +3,32 @@ use std::sync::Arc;
 
 use crate::webrtc::ice::candidate::candidate_base::CandidateBaseConfig;
 use crate::webrtc::ice::candidate::candidate_host::CandidateHostConfig;
+use crate::webrtc::ice::candidate::candidate_peer_reflexive::CandidatePeerReflexiveConfig;
+use crate::webrtc::ice::candidate::candidate_server_reflexive::CandidateServerReflexiveConfig;
 use crate::webrtc::ice::candidate::Candidate;
+use crate::webrtc::ice::tcp_type::TcpType;
 use serde::{Deserialize, Serialize};
 
 use crate::webrtc::error::{Error, Result};
 use crate::webrtc::ice_transport::ice_candidate_type::RTCIceCandidateType;
 use crate::webrtc::ice_transport::ice_protocol::RTCIceProtocol;
+use crate::webrtc::util::redaction::redact_address;
 
 /// ICECandidate represents a ice candidate
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub(crate) struct RTCIceCandidate {
-    pub(crate) stats_id: String,
-    pub(crate) foundation: String,
-    pub(crate) priority: u32,
-    pub(crate) address: String,
-    pub(crate) protocol: RTCIceProtocol,
-    pub(crate) port: u16,
-    pub(crate) typ: RTCIceCandidateType,
-    pub(crate) component: u16,
-    pub(crate) related_address: String,
-    pub(crate) related_port: u16,
+#[derive(Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RTCIceCandidate {
+    pub stats_id: String,
+    pub foundation: String,
+    pub priority: u32,
+    pub address: String,
+    pub protocol: RTCIceProtocol,
+    pub port: u16,
+    pub typ: RTCIceCandidateType,
+    pub component: u16,
+    pub related_address: String,
+    pub related_port: u16,
+    /// tcp_type is "active", "passive" or "so" for TCP candidates and empty for UDP ones
+    pub tcp_type: String,
 }
 
 /// Conversion for ice_candidates
@@ -41,6 +47,10 @@ impl From<&Arc<dyn Candidate + Send + Sync>> for RTCIceCandidate {
         } else {
             (String::new(), 0)
         };
+        let tcp_type = match c.tcp_type() {
+            TcpType::Unspecified => String::new(),
+            tcp_type => tcp_type.to_string(),
+        };
 
         RTCIceCandidate {
             stats_id: c.id(),
@@ -53,6 +63,7 @@ impl From<&Arc<dyn Candidate + Send + Sync>> for RTCIceCandidate {
             typ,
             related_address,
             related_port,
+            tcp_type,
         }
     }
 }
@@ -69,15 +80,51 @@ impl RTCIceCandidate {
                         address: self.address.clone(),
                         port: self.port,
                         component: self.component,
-                        //tcp_type: ice.NewTCPType(c.TCPType),
                         foundation: self.foundation.clone(),
                         priority: self.priority,
+                        tcp_type: TcpType::from(self.tcp_type.as_str()),
                         ..Default::default()
                     },
                     ..Default::default()
                 };
                 config.new_candidate_host().await?
             }
+            RTCIceCandidateType::Srflx => {
+                let config = CandidateServerReflexiveConfig {
+                    base_config: CandidateBaseConfig {
+                        candidate_id,
+                        network: self.protocol.to_string(),
+                        address: self.address.clone(),
+                        port: self.port,
+                        component: self.component,
+                        foundation: self.foundation.clone(),
+                        priority: self.priority,
+                        tcp_type: TcpType::from(self.tcp_type.as_str()),
+                        ..Default::default()
+                    },
+                    rel_addr: self.related_address.clone(),
+                    rel_port: self.related_port,
+                };
+                config.new_candidate_server_reflexive().await?
+            }
+            RTCIceCandidateType::Prflx => {
+                let config = CandidatePeerReflexiveConfig {
+                    base_config: CandidateBaseConfig {
+                        candidate_id,
+                        network: self.protocol.to_string(),
+                        address: self.address.clone(),
+                        port: self.port,
+                        component: self.component,
+                        foundation: self.foundation.clone(),
+                        priority: self.priority,
+                        tcp_type: TcpType::from(self.tcp_type.as_str()),
+                        ..Default::default()
+                    },
+                    rel_addr: self.related_address.clone(),
+                    rel_port: self.related_port,
+                };
+                config.new_candidate_peer_reflexive().await?
+            }
             _ => return Err(Error::ErrICECandidateTypeUnknown),
         };
 
@@ -90,18 +137,49 @@ impl fmt::Display for RTCIceCandidate {
         write!(
             f,
             "{} {} {}:{}{}",
-            self.protocol, self.typ, self.address, self.port, self.related_address,
+            self.protocol,
+            self.typ,
+            redact_address(&self.address),
+            self.port,
+            redact_address(&self.related_address),
         )
     }
 }
 
-/// ICECandidateInit is used to serialize ice candidates
+// the addresses are redacted as SettingEngine::set_address_redaction says, like in Display
+impl fmt::Debug for RTCIceCandidate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RTCIceCandidate")
+            .field("stats_id", &self.stats_id)
+            .field("foundation", &self.foundation)
+            .field("priority", &self.priority)
+            .field("address", &redact_address(&self.address))
+            .field("protocol", &self.protocol)
+            .field("port", &self.port)
+            .field("typ", &self.typ)
+            .field("component", &self.component)
+            .field("related_address", &redact_address(&self.related_address))
+            .field("related_port", &self.related_port)
+            .field("tcp_type", &self.tcp_type)
+            .finish()
+    }
+}
+
+/// RTCIceCandidateInit is a remote ICE candidate as signaled, e.g. trickled by a browser, see
+/// SocketHandle::add_ice_candidate. It serializes to the JSON browsers use.
+///
+/// The media section it belongs to is picked by sdp_mid, or by sdp_mline_index without one.
+/// Both may only be left out while the remote description has a single media section. A
+/// username_fragment that differs from the one of the remote description marks a candidate of
+/// an earlier ICE generation, which is dropped.
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub(crate) struct RTCIceCandidateInit {
-    pub(crate) candidate: String,
-    pub(crate) sdp_mid: Option<String>,
+pub struct RTCIceCandidateInit {
+    /// candidate is the a=candidate value, with or without the "candidate:" prefix. Empty
+    /// signals the end of candidates.
+    pub candidate: String,
+    pub sdp_mid: Option<String>,
     #[serde(rename = "sdpMLineIndex")]
-    pub(crate) sdp_mline_index: Option<u16>,
-    pub(crate) username_fragment: Option<String>,
+    pub sdp_mline_index: Option<u16>,
+    pub username_fragment: Option<String>,
 }
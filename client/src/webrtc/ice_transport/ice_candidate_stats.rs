@@ -0,0 +1,73 @@
+use crate::webrtc::ice::candidate::candidate_stats::CandidateRecord;
+use crate::webrtc::ice_transport::ice_candidate_type::RTCIceCandidateType;
+use crate::webrtc::ice_transport::ice_protocol::RTCIceProtocol;
+use crate::webrtc::util::redaction::redact_address;
+
+use serde::Serialize;
+use std::time::SystemTime;
+
+/// CandidateStats counts the STUN binding traffic of a local or remote ICE candidate, e.g. to
+/// tell from the traffic of the unused candidates how the NATs in between behaved.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CandidateStats {
+    pub id: String,
+    pub candidate_type: RTCIceCandidateType,
+    pub protocol: RTCIceProtocol,
+    /// address is None when the stats were taken with redacted addresses, and shown as set
+    /// with SettingEngine::set_address_redaction otherwise
+    pub address: Option<String>,
+    /// port is None when the stats were taken with redacted addresses
+    pub port: Option<u16>,
+    pub priority: u32,
+    pub requests_sent: u64,
+    pub requests_received: u64,
+    pub responses_sent: u64,
+    pub responses_received: u64,
+    /// last_activity is when a binding request or response last went through the candidate
+    pub last_activity: Option<SystemTime>,
+    /// succeeded tells whether a candidate pair with the candidate ever got a success response
+    pub succeeded: bool,
+}
+
+impl CandidateStats {
+    pub(crate) fn new(record: &CandidateRecord, redact_addresses: bool) -> Self {
+        let counters = &record.counters;
+        let (address, port) = if redact_addresses {
+            (None, None)
+        } else {
+            (Some(redact_address(&record.address)), Some(record.port))
+        };
+
+        CandidateStats {
+            id: record.id.clone(),
+            candidate_type: record.candidate_type.into(),
+            protocol: RTCIceProtocol::from(record.network_type.network_short().as_str()),
+            address,
+            port,
+            priority: record.priority,
+            requests_sent: counters.requests_sent(),
+            requests_received: counters.requests_received(),
+            responses_sent: counters.responses_sent(),
+            responses_received: counters.responses_received(),
+            last_activity: counters.last_activity(),
+            succeeded: counters.succeeded(),
+        }
+    }
+}
+
+/// CandidateStatsReport holds the stats of the local and remote candidates in the order they
+/// were learned, see SocketHandle::candidate_stats. Candidates the agent pruned are included.
+#[derive(Default, Debug, Clone, PartialEq, Serialize)]
+pub struct CandidateStatsReport {
+    pub local_candidates: Vec<CandidateStats>,
+    pub remote_candidates: Vec<CandidateStats>,
+    /// no_candidates_gathered tells that gathering completed without a local candidate, which
+    /// only leaves the connection alive with SettingEngine::set_allow_zero_candidates
+    pub no_candidates_gathered: bool,
+    /// dropped_remote_candidates counts the remote candidates dropped or evicted for the cap
+    /// of SettingEngine::set_max_remote_candidates
+    pub dropped_remote_candidates: u64,
+    /// dropped_candidate_pairs counts the candidate pairs dropped or evicted for the cap of
+    /// SettingEngine::set_max_candidate_pairs
+    pub dropped_candidate_pairs: u64,
+}
@@ -0,0 +1,17 @@
+use std::time::Duration;
+
+use crate::webrtc::ice_transport::ice_candidate::RTCIceCandidate;
+
+/// ConnectivityCheckResult is the outcome of one binding request of an ICE connectivity check
+/// or keepalive, see SettingEngine::set_on_connectivity_check_result. The candidates' stats_id
+/// is what SocketHandle::select_candidate_pair takes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConnectivityCheckResult {
+    pub local: RTCIceCandidate,
+    pub remote: RTCIceCandidate,
+    /// rtt is the round trip time of the request, or how long it went unanswered if it failed
+    pub rtt: Duration,
+    /// success tells whether the remote answered with a success response, a request fails on
+    /// an error response or when it times out
+    pub success: bool,
+}
@@ -1,8 +1,9 @@
+use serde::Serialize;
 use std::fmt;
 
 /// RTCIceConnectionState indicates signaling state of the ICE Connection.
-#[derive(Debug, Copy, Clone, PartialEq)]
-pub(crate) enum RTCIceConnectionState {
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+pub enum RTCIceConnectionState {
     Unspecified,
 
     /// ICEConnectionStateNew indicates that any of the ICETransports are
@@ -1,15 +1,24 @@
+use crate::webrtc::api::setting_engine::SettingEngine;
 use crate::webrtc::error::{Error, Result};
 use crate::webrtc::ice_transport::ice_candidate::*;
+use crate::webrtc::ice_transport::ice_check_result::ConnectivityCheckResult;
 use crate::webrtc::ice_transport::ice_gatherer_state::RTCIceGathererState;
 use crate::webrtc::ice_transport::ice_parameters::RTCIceParameters;
+use crate::webrtc::peer_connection::event_log::{ConnectMilestone, EventLog};
 
+use crate::webrtc::ice::agent::agent_config::{
+    CandidateFilterFn, ConnectivityCheckResultFn, InterfaceFilterFn,
+};
 use crate::webrtc::ice::agent::Agent;
 use crate::webrtc::ice::candidate::{Candidate, CandidateType};
 
 use crate::webrtc::ice::mdns::MulticastDnsMode;
+use crate::webrtc::ice::util::{set_dscp, SocketBuffers};
+use crate::webrtc::util::conn::send_error::SendErrorStats;
+use crate::webrtc::util::Conn;
 use std::future::Future;
 use std::pin::Pin;
-use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
@@ -34,7 +43,23 @@ pub(crate) type OnGatheringCompleteHdlrFn =
 /// exchanged in signaling.
 #[derive(Default)]
 pub(crate) struct RTCIceGatherer {
+    pub(crate) setting_engine: Arc<SettingEngine>,
+    pub(crate) event_log: Arc<EventLog>,
+    /// send_errors counts the send errors the agents of the gatherer dropped datagrams for,
+    /// it outlives an agent replaced by an ICE restart
+    pub(crate) send_errors: Arc<SendErrorStats>,
+    /// oversized_datagrams counts the datagrams the agents of the gatherer dropped for
+    /// exceeding the receive MTU, it outlives an agent replaced by an ICE restart too
+    pub(crate) oversized_datagrams: Arc<AtomicU64>,
+    /// socket_buffers sizes the buffers of the sockets of the agents and keeps the sizes the
+    /// OS reports back
+    pub(crate) socket_buffers: Arc<SocketBuffers>,
+
     pub(crate) state: Arc<AtomicU8>, //ICEGathererState,
+    /// candidates_gathered counts the local candidates reported by the agent
+    pub(crate) candidates_gathered: Arc<AtomicUsize>,
+    /// no_candidates_gathered is set when gathering completed before any candidate was found
+    pub(crate) no_candidates_gathered: Arc<AtomicBool>,
     pub(crate) agent: Mutex<Option<Arc<crate::webrtc::ice::agent::Agent>>>,
 
     pub(crate) on_local_candidate_handler: Arc<Mutex<Option<OnLocalCandidateHdlrFn>>>,
@@ -42,11 +67,17 @@ pub(crate) struct RTCIceGatherer {
 
     // Used for gathering_complete_promise
     pub(crate) on_gathering_complete_handler: Arc<Mutex<Option<OnGatheringCompleteHdlrFn>>>,
+    /// on_no_candidates_handler is called when gathering completes without a candidate, before
+    /// the state change and gathering complete handlers
+    pub(crate) on_no_candidates_handler: Arc<Mutex<Option<OnGatheringCompleteHdlrFn>>>,
 }
 
 impl RTCIceGatherer {
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(setting_engine: Arc<SettingEngine>, event_log: Arc<EventLog>) -> Self {
         RTCIceGatherer {
+            socket_buffers: Arc::new(SocketBuffers::new(setting_engine.socket_buffer_sizes)),
+            setting_engine,
+            event_log,
             state: Arc::new(AtomicU8::new(RTCIceGathererState::New as u8)),
             ..Default::default()
         }
@@ -68,11 +99,31 @@ impl RTCIceGatherer {
             mdns_mode = crate::webrtc::ice::mdns::MulticastDnsMode::QueryOnly;
         }
 
+        if let Some(udp_socket) = &self.setting_engine.udp_socket {
+            if let Some(dscp) = self.setting_engine.dscp {
+                if let Err(err) = set_dscp(udp_socket, dscp) {
+                    log::warn!(
+                        "could not set DSCP {} on the provided socket: {}",
+                        dscp,
+                        err
+                    );
+                }
+            }
+            self.socket_buffers.apply(udp_socket);
+        }
+
         let mut config = crate::webrtc::ice::agent::agent_config::AgentConfig {
+            local_ufrag: self.setting_engine.candidates.username_fragment.clone(),
+            local_pwd: self.setting_engine.candidates.password.clone(),
+            max_remote_candidates: self.setting_engine.candidates.max_remote_candidates,
+            max_candidate_pairs: self.setting_engine.candidates.max_candidate_pairs,
             lite: false,
-            disconnected_timeout: None,
-            failed_timeout: None,
-            keepalive_interval: None,
+            disconnected_timeout: self.setting_engine.timeout.ice_disconnected_timeout,
+            failed_timeout: self.setting_engine.timeout.ice_failed_timeout,
+            keepalive_interval: self.setting_engine.timeout.ice_keepalive_interval,
+            keepalive_kind: self.setting_engine.ice_keepalive_kind,
+            check_interval: self.setting_engine.ice_check_interval,
+            max_binding_requests: self.setting_engine.ice_max_binding_requests,
             candidate_types: Vec::new(),
             host_acceptance_min_wait: None,
             srflx_acceptance_min_wait: None,
@@ -81,6 +132,49 @@ impl RTCIceGatherer {
             nat_1to1_ip_candidate_type: CandidateType::Unspecified,
             net: None,
             multicast_dns_mode: mdns_mode,
+            udp_conn: self
+                .setting_engine
+                .udp_socket
+                .clone()
+                .map(|udp_socket| udp_socket as Arc<dyn Conn + Send + Sync>),
+            dscp: self.setting_engine.dscp,
+            socket_buffers: Arc::clone(&self.socket_buffers),
+            packet_capture: self.setting_engine.packet_capture.clone(),
+            gathering_context: self.setting_engine.gathering_context.clone(),
+            rng: self.setting_engine.rng.clone(),
+            spawner: self.setting_engine.spawner.clone(),
+            event_log: Arc::clone(&self.event_log),
+            send_errors: Arc::clone(&self.send_errors),
+            receive_mtu: self.setting_engine.receive_mtu(),
+            oversized_datagrams: Arc::clone(&self.oversized_datagrams),
+            candidate_filter: Arc::new(self.setting_engine.candidate_filter.clone().map(
+                |filter| -> CandidateFilterFn {
+                    Box::new(move |c| filter(&RTCIceCandidate::from(c)))
+                },
+            )),
+            on_connectivity_check_result: Arc::new(
+                self.setting_engine
+                    .on_connectivity_check_result
+                    .clone()
+                    .map(|f| -> ConnectivityCheckResultFn {
+                        Box::new(move |local, remote, rtt, success| {
+                            f(&ConnectivityCheckResult {
+                                local: RTCIceCandidate::from(local),
+                                remote: RTCIceCandidate::from(remote),
+                                rtt,
+                                success,
+                            })
+                        })
+                    }),
+            ),
+            interface_filter: Arc::new(
+                self.setting_engine
+                    .interface_filter
+                    .clone()
+                    .map(|filter| -> InterfaceFilterFn { Box::new(move |name| filter(name)) }),
+            ),
+            #[cfg(feature = "network-monitor")]
+            network_monitor_interval: self.setting_engine.network_monitor_interval,
             //TODO: TCPMux:                 self.setting_engine.iceTCPMux,
             //TODO: ProxyDialer:            self.setting_engine.iceProxyDialer,
             ..Default::default()
@@ -89,12 +183,26 @@ impl RTCIceGatherer {
         let requested_network_types = crate::webrtc::ice::network_type::supported_network_types();
 
         config.network_types.extend(requested_network_types);
+        if self.setting_engine.ice_tcp {
+            config
+                .network_types
+                .extend(crate::webrtc::ice::network_type::tcp_network_types());
+        }
 
-        {
+        // The agent is created without holding the lock, a concurrent create_agent may have
+        // installed one meanwhile, in which case that one is kept.
+        let new_agent = Arc::new(crate::webrtc::ice::agent::Agent::new(config).await?);
+        let installed = {
             let mut agent = self.agent.lock().await;
-            *agent = Some(Arc::new(
-                crate::webrtc::ice::agent::Agent::new(config).await?,
-            ));
+            if agent.is_none() {
+                *agent = Some(Arc::clone(&new_agent));
+                true
+            } else {
+                false
+            }
+        };
+        if !installed {
+            new_agent.close().await?;
         }
 
         Ok(())
@@ -107,22 +215,32 @@ impl RTCIceGatherer {
 
         if let Some(agent) = self.get_agent().await {
             let state = Arc::clone(&self.state);
+            let candidates_gathered = Arc::clone(&self.candidates_gathered);
+            let no_candidates_gathered = Arc::clone(&self.no_candidates_gathered);
             let on_local_candidate_handler = Arc::clone(&self.on_local_candidate_handler);
             let on_state_change_handler = Arc::clone(&self.on_state_change_handler);
             let on_gathering_complete_handler = Arc::clone(&self.on_gathering_complete_handler);
+            let on_no_candidates_handler = Arc::clone(&self.on_no_candidates_handler);
+            let event_log = Arc::clone(&self.event_log);
 
             agent
                 .on_candidate(Box::new(
                     move |candidate: Option<Arc<dyn Candidate + Send + Sync>>| {
                         let state_clone = Arc::clone(&state);
+                        let candidates_gathered_clone = Arc::clone(&candidates_gathered);
+                        let no_candidates_gathered_clone = Arc::clone(&no_candidates_gathered);
                         let on_local_candidate_handler_clone =
                             Arc::clone(&on_local_candidate_handler);
                         let on_state_change_handler_clone = Arc::clone(&on_state_change_handler);
                         let on_gathering_complete_handler_clone =
                             Arc::clone(&on_gathering_complete_handler);
+                        let on_no_candidates_handler_clone = Arc::clone(&on_no_candidates_handler);
+                        let event_log_clone = Arc::clone(&event_log);
 
                         Box::pin(async move {
                             if let Some(cand) = candidate {
+                                candidates_gathered_clone.fetch_add(1, Ordering::SeqCst);
+                                event_log_clone.milestone(ConnectMilestone::CandidateGathered);
                                 let c = RTCIceCandidate::from(&cand);
 
                                 let mut on_local_candidate_handler =
@@ -131,6 +249,16 @@ impl RTCIceGatherer {
                                     handler(Some(c)).await;
                                 }
                             } else {
+                                if candidates_gathered_clone.load(Ordering::SeqCst) == 0
+                                    && !no_candidates_gathered_clone.swap(true, Ordering::SeqCst)
+                                {
+                                    let mut on_no_candidates_handler =
+                                        on_no_candidates_handler_clone.lock().await;
+                                    if let Some(handler) = &mut *on_no_candidates_handler {
+                                        handler().await;
+                                    }
+                                }
+
                                 state_clone
                                     .store(RTCIceGathererState::Complete as u8, Ordering::SeqCst);
 
@@ -169,6 +297,18 @@ impl RTCIceGatherer {
         Ok(())
     }
 
+    /// restart gives the agent new local credentials and drops its candidates and candidate
+    /// pairs for an ICE restart. Gathering runs again with the next gather.
+    pub(crate) async fn restart(&self) -> Result<()> {
+        let agent = self.get_agent().await.ok_or(Error::ErrICEAgentNotExist)?;
+        agent.restart(String::new(), String::new()).await?;
+
+        self.candidates_gathered.store(0, Ordering::SeqCst);
+        self.no_candidates_gathered.store(false, Ordering::SeqCst);
+        self.set_state(RTCIceGathererState::New).await;
+        Ok(())
+    }
+
     /// get_local_parameters returns the ICE parameters of the ICEGatherer.
     pub(crate) async fn get_local_parameters(&self) -> Result<RTCIceParameters> {
         self.create_agent().await?;
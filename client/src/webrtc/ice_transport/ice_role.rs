@@ -2,8 +2,9 @@ use std::fmt;
 
 /// ICERole describes the role ice.Agent is playing in selecting the
 /// preferred the candidate pair.
-#[derive(Debug, Copy, Clone, PartialEq)]
-pub(crate) enum RTCIceRole {
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RTCIceRole {
+    /// Unspecified is the role before ICE started.
     Unspecified,
 
     /// ICERoleControlling indicates that the ICE agent that is responsible
@@ -5,6 +5,7 @@ use std::sync::Arc;
 
 use crate::webrtc::ice::candidate::Candidate;
 use crate::webrtc::ice::state::ConnectionState;
+use crate::webrtc::util::conn::send_error::SendErrorStats;
 use crate::webrtc::util::Conn;
 use tokio::sync::{mpsc, Mutex};
 
@@ -22,7 +23,9 @@ use crate::webrtc::mux::{Config, Mux};
 
 pub(crate) mod ice_candidate;
 pub(crate) mod ice_candidate_pair;
+pub(crate) mod ice_candidate_stats;
 pub(crate) mod ice_candidate_type;
+pub(crate) mod ice_check_result;
 pub(crate) mod ice_connection_state;
 pub(crate) mod ice_credential_type;
 pub(crate) mod ice_gatherer;
@@ -75,6 +78,32 @@ impl RTCIceTransport {
         }
     }
 
+    /// send_errors returns the counters of the datagrams the agents dropped or retried for a
+    /// send error
+    pub(crate) fn send_errors(&self) -> Arc<SendErrorStats> {
+        Arc::clone(&self.gatherer.send_errors)
+    }
+
+    /// receive_mtu returns the largest datagram the agent accepts, see
+    /// SettingEngine::set_receive_mtu
+    pub(crate) fn receive_mtu(&self) -> usize {
+        self.gatherer.setting_engine.receive_mtu()
+    }
+
+    /// role returns the role the agent currently plays, Unspecified before the transport is
+    /// started. After a role conflict it is the role the agent switched to, not the one it
+    /// was started in.
+    pub(crate) async fn role(&self) -> RTCIceRole {
+        if self.internal.lock().await.role == RTCIceRole::Unspecified {
+            return RTCIceRole::Unspecified;
+        }
+        match self.gatherer.get_agent().await {
+            Some(agent) if agent.is_controlling() => RTCIceRole::Controlling,
+            Some(_) => RTCIceRole::Controlled,
+            None => RTCIceRole::Unspecified,
+        }
+    }
+
     /// Start incoming connectivity checks based on its configured role.
     pub(crate) async fn start(
         &self,
@@ -167,6 +196,8 @@ impl RTCIceTransport {
 
             let config = Config {
                 conn: Arc::clone(&conn),
+                receive_mtu: self.gatherer.setting_engine.receive_mtu(),
+                spawner: self.gatherer.setting_engine.spawner.clone(),
             };
 
             {
@@ -208,7 +239,42 @@ impl RTCIceTransport {
         }
     }
 
+    /// stop irreversibly stops the ICETransport, cancelling any pending
+    /// connectivity checks and closing the ICE agent.
+    pub(crate) async fn stop(&self) -> Result<()> {
+        {
+            let mut internal = self.internal.lock().await;
+            internal.cancel_tx.take();
+            internal.mux.take();
+            internal.conn.take();
+        }
+        self.state
+            .store(RTCIceTransportState::Closed as u8, Ordering::SeqCst);
+
+        if let Some(agent) = self.gatherer.get_agent().await {
+            agent.close().await?;
+        }
+        Ok(())
+    }
+
     /// State returns the current ice transport state.
+    /// set_remote_credentials hands the agent the remote credentials of an answer to an ICE
+    /// restart, the connectivity checks use them from then on. Checks the peer sent before
+    /// are answered right away.
+    pub(crate) async fn set_remote_credentials(&self, params: &RTCIceParameters) -> Result<()> {
+        let agent = self
+            .gatherer
+            .get_agent()
+            .await
+            .ok_or(Error::ErrICEAgentNotExist)?;
+        agent
+            .internal
+            .set_remote_credentials(params.username_fragment.clone(), params.password.clone())
+            .await?;
+        agent.internal.replay_early_checks().await;
+        Ok(())
+    }
+
     pub(crate) fn state(&self) -> RTCIceTransportState {
         RTCIceTransportState::from(self.state.load(Ordering::SeqCst))
     }
@@ -20,5 +20,13 @@ pub(crate) mod sctp_transport;
 
 pub(crate) const UNSPECIFIED_STR: &str = "Unspecified";
 
-/// Equal to UDP MTU
-pub(crate) const RECEIVE_MTU: usize = 1460;
+/// DEFAULT_RECEIVE_MTU is the largest inbound datagram accepted unless
+/// SettingEngine::set_receive_mtu says otherwise. It takes a full 1500 byte Ethernet frame with
+/// room to spare.
+pub(crate) const DEFAULT_RECEIVE_MTU: usize = 1628;
+/// MIN_RECEIVE_MTU is the smallest receive MTU accepted, the DTLS and SCTP stacks of WebRTC
+/// endpoints send datagrams of up to 1200 bytes by default
+pub(crate) const MIN_RECEIVE_MTU: usize = 1200;
+/// MAX_RECEIVE_MTU is the largest receive MTU accepted, the buffers between the layers store
+/// packet lengths in 16 bits
+pub(crate) const MAX_RECEIVE_MTU: usize = u16::MAX as usize;
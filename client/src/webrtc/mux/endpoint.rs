@@ -2,9 +2,11 @@ use crate::webrtc::mux::mux_func::MatchFunc;
 use crate::webrtc::util::{Buffer, Conn};
 
 use async_trait::async_trait;
+use std::collections::HashMap;
 use std::io;
 use std::net::SocketAddr;
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
+use tokio::sync::Mutex;
 
 /// Endpoint implements net.Conn. It is used to read muxed packets.
 pub(crate) struct Endpoint {
@@ -12,6 +14,8 @@ pub(crate) struct Endpoint {
     pub(crate) buffer: Buffer,
     pub(crate) match_fn: MatchFunc,
     pub(crate) next_conn: Arc<dyn Conn + Send + Sync>,
+    /// endpoints of the mux, the endpoint removes itself on close
+    pub(crate) endpoints: Weak<Mutex<HashMap<usize, Arc<Endpoint>>>>,
 }
 
 type Result<T> = std::result::Result<T, crate::webrtc::util::Error>;
@@ -51,7 +55,13 @@ impl Conn for Endpoint {
         self.next_conn.remote_addr().await
     }
 
+    /// close removes the endpoint from the mux and ends its reads. The connection underneath
+    /// is shared with the other endpoints and stays open, e.g. for a new DTLS connection.
     async fn close(&self) -> Result<()> {
-        self.next_conn.close().await
+        if let Some(endpoints) = self.endpoints.upgrade() {
+            endpoints.lock().await.remove(&self.id);
+        }
+        self.buffer.close().await;
+        Ok(())
     }
 }
@@ -5,8 +5,7 @@ use crate::webrtc::error::Result;
 use crate::webrtc::mux::endpoint::Endpoint;
 use crate::webrtc::mux::mux_func::MatchFunc;
 
-use crate::webrtc::util::{Buffer, Conn};
-use crate::webrtc::RECEIVE_MTU;
+use crate::webrtc::util::{spawner::Spawner, Buffer, Conn};
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
@@ -21,6 +20,9 @@ const MAX_BUFFER_SIZE: usize = 1000 * 1000; // 1MB
 /// a single structure
 pub(crate) struct Config {
     pub(crate) conn: Arc<dyn Conn + Send + Sync>,
+    /// receive_mtu is the largest packet conn hands over, see SettingEngine::set_receive_mtu
+    pub(crate) receive_mtu: usize,
+    pub(crate) spawner: Spawner,
 }
 
 /// Mux allows multiplexing
@@ -46,8 +48,9 @@ impl Mux {
 
         let next_conn = Arc::clone(&m.next_conn);
         let endpoints = Arc::clone(&m.endpoints);
-        tokio::spawn(async move {
-            Mux::read_loop(next_conn, closed_ch_rx, endpoints).await;
+        let receive_mtu = config.receive_mtu;
+        config.spawner.spawn(async move {
+            Mux::read_loop(next_conn, receive_mtu, closed_ch_rx, endpoints).await;
         });
 
         m
@@ -66,6 +69,7 @@ impl Mux {
             buffer: Buffer::new(0, MAX_BUFFER_SIZE),
             match_fn: f,
             next_conn: Arc::clone(&self.next_conn),
+            endpoints: Arc::downgrade(&self.endpoints),
         });
 
         endpoints.insert(e.id, Arc::clone(&e));
@@ -75,19 +79,21 @@ impl Mux {
 
     async fn read_loop(
         next_conn: Arc<dyn Conn + Send + Sync>,
+        receive_mtu: usize,
         mut closed_ch_rx: mpsc::Receiver<()>,
         endpoints: Arc<Mutex<HashMap<usize, Arc<Endpoint>>>>,
     ) {
-        let mut buf = vec![0u8; RECEIVE_MTU];
-        let mut n = 0usize;
+        let mut buf = vec![0u8; receive_mtu];
         loop {
-            tokio::select! {
+            let n = tokio::select! {
                 _ = closed_ch_rx.recv() => break,
-                result = next_conn.recv(&mut buf) => {
-                    if let Ok(m) = result{
-                        n = m;
+                result = next_conn.recv(&mut buf) => match result {
+                    Ok(n) => n,
+                    Err(err) => {
+                        log::debug!("mux: ending readLoop: {}", err);
+                        break;
                     }
-                }
+                },
             };
 
             if let Err(err) = Mux::dispatch(&buf[..n], &endpoints).await {
@@ -1,12 +1,36 @@
 use crate::webrtc::dtls_transport::dtls_fingerprint::RTCDtlsFingerprint;
 use crate::webrtc::error::{Error, Result};
 use crate::webrtc::peer_connection::math_rand_alpha;
+use crate::webrtc::util::random_source::RandomSource;
 
 use crate::webrtc::dtls::crypto::{CryptoPrivateKey, CryptoPrivateKeyKind};
 use rcgen::{CertificateParams, KeyPair, RcgenError};
-use ring::signature::{EcdsaKeyPair, Ed25519KeyPair, RsaKeyPair};
+use ring::signature::{EcdsaKeyPair, Ed25519KeyPair, KeyPair as _, RsaKeyPair};
 use sha2::{Digest, Sha256};
 
+const ED25519_SEED_LEN: usize = 32;
+
+/// ed25519_pkcs8 encodes the Ed25519 key pair of seed as a PKCS#8 v2 document, the form ring
+/// generates and expects, RFC 8410 Section 7
+fn ed25519_pkcs8(seed: &[u8; ED25519_SEED_LEN]) -> Result<Vec<u8>> {
+    let key_pair =
+        Ed25519KeyPair::from_seed_unchecked(seed).map_err(|e| Error::ErrPrivateKeyRejected {
+            algorithm: "ed25519",
+            reason: e.to_string(),
+        })?;
+
+    let mut der = vec![
+        0x30, 0x53, // SEQUENCE
+        0x02, 0x01, 0x01, // version 2
+        0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, // AlgorithmIdentifier id-Ed25519
+        0x04, 0x22, 0x04, 0x20, // OCTET STRING wrapping the private key
+    ];
+    der.extend_from_slice(seed);
+    der.extend_from_slice(&[0xa1, 0x23, 0x03, 0x21, 0x00]); // [1] public key BIT STRING
+    der.extend_from_slice(key_pair.public_key().as_ref());
+    Ok(der)
+}
+
 /// Certificate represents a x509Cert used to authenticate WebRTC communications.
 pub(crate) struct RTCCertificate {
     pub(crate) certificate: crate::webrtc::dtls::crypto::Certificate,
@@ -24,6 +48,10 @@ impl RTCCertificate {
     /// by DTLS for encrypting data sent over the wire. This method differs from
     /// generate_certificate by allowing to specify a template x509.Certificate to
     /// be used in order to define certificate parameters.
+    ///
+    /// Returns ErrUnsupportedKeyPair for key algorithms other than Ed25519,
+    /// ECDSA P-256 and RSA, and ErrPrivateKeyRejected if ring refuses the
+    /// serialized key.
     pub(crate) fn from_params(mut params: CertificateParams) -> Result<Self> {
         let key_pair = if let Some(key_pair) = params.key_pair.take() {
             if !key_pair.is_compatible(params.alg) {
@@ -38,8 +66,12 @@ impl RTCCertificate {
         let private_key = if key_pair.is_compatible(&rcgen::PKCS_ED25519) {
             CryptoPrivateKey {
                 kind: CryptoPrivateKeyKind::Ed25519(
-                    Ed25519KeyPair::from_pkcs8(&serialized_der)
-                        .map_err(|e| Error::new(e.to_string()))?,
+                    Ed25519KeyPair::from_pkcs8(&serialized_der).map_err(|e| {
+                        Error::ErrPrivateKeyRejected {
+                            algorithm: "ed25519",
+                            reason: e.to_string(),
+                        }
+                    })?,
                 ),
                 serialized_der,
             }
@@ -50,20 +82,27 @@ impl RTCCertificate {
                         &ring::signature::ECDSA_P256_SHA256_ASN1_SIGNING,
                         &serialized_der,
                     )
-                    .map_err(|e| Error::new(e.to_string()))?,
+                    .map_err(|e| Error::ErrPrivateKeyRejected {
+                        algorithm: "ecdsa-p256",
+                        reason: e.to_string(),
+                    })?,
                 ),
                 serialized_der,
             }
         } else if key_pair.is_compatible(&rcgen::PKCS_RSA_SHA256) {
             CryptoPrivateKey {
                 kind: CryptoPrivateKeyKind::Rsa256(
-                    RsaKeyPair::from_pkcs8(&serialized_der)
-                        .map_err(|e| Error::new(e.to_string()))?,
+                    RsaKeyPair::from_pkcs8(&serialized_der).map_err(|e| {
+                        Error::ErrPrivateKeyRejected {
+                            algorithm: "rsa",
+                            reason: e.to_string(),
+                        }
+                    })?,
                 ),
                 serialized_der,
             }
         } else {
-            return Err(Error::new("Unsupported key_pair".to_owned()));
+            return Err(Error::ErrUnsupportedKeyPair);
         };
         params.key_pair = Some(key_pair);
 
@@ -98,10 +137,28 @@ impl RTCCertificate {
         Ok(fingerpints)
     }
 
+    /// generate creates the certificate of a DTLS transport, an ECDSA P-256 one. With a seeded
+    /// rng it is an Ed25519 one with the key derived from rng instead, Ed25519 signatures are
+    /// deterministic, so the certificate and its fingerprint are the same on every run.
+    pub(crate) fn generate(rng: &RandomSource) -> Result<Self> {
+        let key_pair = if rng.is_seeded() {
+            let mut seed = [0u8; ED25519_SEED_LEN];
+            rng.fill_bytes(&mut seed);
+            KeyPair::from_der(&ed25519_pkcs8(&seed)?)?
+        } else {
+            KeyPair::generate(&rcgen::PKCS_ECDSA_P256_SHA256)?
+        };
+
+        RTCCertificate::from_key_pair(key_pair, rng)
+    }
+
     /// from_key_pair causes the creation of an X.509 certificate and
-    /// corresponding private key.
-    pub(crate) fn from_key_pair(key_pair: KeyPair) -> Result<Self> {
-        let mut params = CertificateParams::new(vec![math_rand_alpha(16)]);
+    /// corresponding private key, with a common name drawn from rng.
+    ///
+    /// Returns ErrUnsupportedKeyPair for key algorithms other than Ed25519,
+    /// ECDSA P-256 and RSA.
+    pub(crate) fn from_key_pair(key_pair: KeyPair, rng: &RandomSource) -> Result<Self> {
+        let mut params = CertificateParams::new(vec![math_rand_alpha(16, rng)]);
 
         if key_pair.is_compatible(&rcgen::PKCS_ED25519) {
             params.alg = &rcgen::PKCS_ED25519;
@@ -110,7 +167,7 @@ impl RTCCertificate {
         } else if key_pair.is_compatible(&rcgen::PKCS_RSA_SHA256) {
             params.alg = &rcgen::PKCS_RSA_SHA256;
         } else {
-            return Err(Error::new("Unsupported key_pair".to_owned()));
+            return Err(Error::ErrUnsupportedKeyPair);
         };
         params.key_pair = Some(key_pair);
 
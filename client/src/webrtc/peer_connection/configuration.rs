@@ -0,0 +1,46 @@
+use crate::webrtc::peer_connection::sdp::sdp_modifiers::SdpModifiers;
+
+/// A Configuration defines how peer-to-peer communication via PeerConnection
+/// is established or re-established.
+#[derive(Default, Debug, Clone)]
+pub struct RTCConfiguration {
+    /// sdp_modifiers are applied to every offer and answer the PeerConnection generates.
+    pub sdp_modifiers: SdpModifiers,
+    /// strict_answer_validation makes set_remote_description also reject an answer that
+    /// bundles mids the offer did not bundle, or does not pick a DTLS role with a=setup:active
+    /// or a=setup:passive. An answer with other media sections than the offer is always
+    /// rejected.
+    pub strict_answer_validation: bool,
+}
+
+impl RTCConfiguration {
+    /// builder starts from the default configuration
+    pub fn builder() -> RTCConfigurationBuilder {
+        RTCConfigurationBuilder::default()
+    }
+}
+
+/// RTCConfigurationBuilder builds an RTCConfiguration, see RTCConfiguration::builder
+#[derive(Default, Debug, Clone)]
+pub struct RTCConfigurationBuilder {
+    configuration: RTCConfiguration,
+}
+
+impl RTCConfigurationBuilder {
+    /// sdp_modifiers sets the additions applied to every generated offer and answer
+    pub fn sdp_modifiers(mut self, sdp_modifiers: SdpModifiers) -> Self {
+        self.configuration.sdp_modifiers = sdp_modifiers;
+        self
+    }
+
+    /// strict_answer_validation sets whether the bundle group and a=setup of an answer are
+    /// checked against the offer, off by default
+    pub fn strict_answer_validation(mut self, strict_answer_validation: bool) -> Self {
+        self.configuration.strict_answer_validation = strict_answer_validation;
+        self
+    }
+
+    pub fn build(self) -> RTCConfiguration {
+        self.configuration
+    }
+}
@@ -0,0 +1,363 @@
+use crate::webrtc::dtls_transport::dtls_fingerprint::RTCDtlsFingerprint;
+use crate::webrtc::ice::candidate::{Candidate, CandidatePair, CandidatePairState};
+use crate::webrtc::ice::state::ConnectionState;
+use crate::webrtc::ice_transport::ice_connection_state::RTCIceConnectionState;
+use crate::webrtc::peer_connection::event_log::ConnectionEvent;
+use crate::webrtc::peer_connection::peer_connection_state::RTCPeerConnectionState;
+use crate::webrtc::peer_connection::sdp::session_description::RTCSessionDescription;
+use crate::webrtc::peer_connection::RTCPeerConnection;
+use crate::webrtc::util::redaction::{redact_address, redact_sdp};
+
+use serde::Serialize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+const ICE_PWD_PREFIX: &str = "a=ice-pwd:";
+
+/// Diagnostics is a snapshot of a PeerConnection for troubleshooting, serializable to JSON
+/// through serde. It is taken without waiting on any lock, so it can be requested at any
+/// point of the lifecycle, including from a state-change callback. Parts that do not exist
+/// yet, or whose lock is held at that moment, are left out.
+#[derive(Default, Debug, Clone, Serialize)]
+pub struct Diagnostics {
+    pub peer_connection_state: String,
+    pub signaling_state: String,
+    pub ice_connection_state: String,
+    pub ice_gathering_state: String,
+    /// local_description is the local SDP with the ice-pwd value redacted and the addresses
+    /// shown as set with SettingEngine::set_address_redaction
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub local_description: Option<String>,
+    /// remote_description is the remote SDP with the ice-pwd value redacted and the addresses
+    /// shown as set with SettingEngine::set_address_redaction
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote_description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ice: Option<IceDiagnostics>,
+    pub dtls: DtlsDiagnostics,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sctp: Option<SctpDiagnostics>,
+    pub data_channels: Vec<DataChannelDiagnostics>,
+    /// recent_events are the last state changes, oldest first, see SocketHandle::recent_events
+    pub recent_events: Vec<ConnectionEvent>,
+}
+
+/// IceDiagnostics describes the ICE agent, its candidates and its checklist
+#[derive(Default, Debug, Clone, Serialize)]
+pub struct IceDiagnostics {
+    pub connection_state: String,
+    pub is_controlling: bool,
+    pub local_candidates: Vec<CandidateDiagnostics>,
+    pub remote_candidates: Vec<CandidateDiagnostics>,
+    pub candidate_pairs: Vec<CandidatePairDiagnostics>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub selected_pair: Option<CandidatePairDiagnostics>,
+}
+
+/// CandidateDiagnostics describes a local or remote ICE candidate
+#[derive(Default, Debug, Clone, Serialize)]
+pub struct CandidateDiagnostics {
+    pub id: String,
+    pub candidate_type: String,
+    pub network_type: String,
+    pub address: String,
+    pub port: u16,
+    pub priority: u32,
+}
+
+/// CandidatePairDiagnostics describes a checklist entry, candidates are referred to by id
+#[derive(Default, Debug, Clone, Serialize)]
+pub struct CandidatePairDiagnostics {
+    pub local_candidate_id: String,
+    pub remote_candidate_id: String,
+    pub state: String,
+    pub nominated: bool,
+    pub priority: u64,
+    pub binding_request_count: u16,
+}
+
+/// DtlsDiagnostics describes the DTLS transport
+#[derive(Default, Debug, Clone, Serialize)]
+pub struct DtlsDiagnostics {
+    pub state: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cipher_suite: Option<String>,
+    pub local_fingerprints: Vec<FingerprintDiagnostics>,
+    pub remote_fingerprints: Vec<FingerprintDiagnostics>,
+}
+
+/// FingerprintDiagnostics is a certificate fingerprint as announced in SDP
+#[derive(Default, Debug, Clone, Serialize)]
+pub struct FingerprintDiagnostics {
+    pub algorithm: String,
+    pub value: String,
+}
+
+/// SctpDiagnostics describes the SCTP association
+#[derive(Default, Debug, Clone, Serialize)]
+pub struct SctpDiagnostics {
+    pub state: String,
+    pub max_num_inbound_streams: u16,
+    pub max_num_outbound_streams: u16,
+    pub mtu: u32,
+    pub cwnd: u32,
+    pub ssthresh: u32,
+    /// peer_rwnd is the receiver window the peer last advertised, less the bytes in flight
+    pub peer_rwnd: u32,
+    pub max_receive_buffer_size: u32,
+    /// outstanding_bytes is the user data sent but not acknowledged yet, pending_bytes the user
+    /// data queued but not sent yet. Together they are capped, see
+    /// SettingEngine::set_sctp_max_send_buffer_size.
+    pub outstanding_bytes: usize,
+    pub pending_bytes: usize,
+    pub srtt_ms: u64,
+    pub rto_ms: u64,
+    /// bad_verification_tags counts the inbound packets dropped for a verification tag that does
+    /// not belong to the association. Like the other counters it adds up over the associations
+    /// of the connection, see SettingEngine::set_sctp_accept_peer_restart.
+    pub bad_verification_tags: u64,
+    /// invalid_cookies counts the COOKIE ECHOs dropped for a forged, replayed or expired cookie
+    pub invalid_cookies: u64,
+    /// out_of_order, max_reorder_distance and duplicate_tsns sum up the ReorderStats of all
+    /// streams
+    pub out_of_order: u64,
+    pub max_reorder_distance: u32,
+    pub duplicate_tsns: u64,
+}
+
+/// DataChannelDiagnostics describes a data channel
+#[derive(Default, Debug, Clone, Serialize)]
+pub struct DataChannelDiagnostics {
+    pub label: String,
+    pub protocol: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<u16>,
+    pub ready_state: String,
+    pub ordered: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_retransmits: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_packet_lifetime: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub buffered_amount: Option<usize>,
+    /// messages_expired counts the messages dropped for their deadline, see
+    /// RTCDataChannel::send_with_deadline
+    pub messages_expired: u64,
+    /// out_of_order, max_reorder_distance and duplicate_tsns describe the unordered messages
+    /// received, see RTCDataChannel::reorder_stats
+    pub out_of_order: u64,
+    pub max_reorder_distance: u32,
+    pub duplicate_tsns: u64,
+}
+
+impl RTCPeerConnection {
+    /// diagnostics takes a Diagnostics snapshot of the PeerConnection. It never waits on a
+    /// lock, see Diagnostics.
+    pub(crate) fn diagnostics(&self) -> Diagnostics {
+        let internal = &self.internal;
+
+        let peer_connection_state: RTCPeerConnectionState =
+            internal.peer_connection_state.load(Ordering::SeqCst).into();
+        let ice_connection_state: RTCIceConnectionState =
+            internal.ice_connection_state.load(Ordering::SeqCst).into();
+
+        Diagnostics {
+            peer_connection_state: peer_connection_state.to_string(),
+            signaling_state: self.signaling_state().to_string(),
+            ice_connection_state: ice_connection_state.to_string(),
+            ice_gathering_state: self.ice_gathering_state().to_string(),
+            local_description: try_description(
+                &internal.pending_local_description,
+                &internal.current_local_description,
+            ),
+            remote_description: try_description(
+                &internal.pending_remote_description,
+                &internal.current_remote_description,
+            ),
+            ice: self.ice_diagnostics(),
+            dtls: self.dtls_diagnostics(),
+            sctp: self.sctp_diagnostics(),
+            data_channels: self.data_channel_diagnostics(),
+            recent_events: self.recent_events(),
+        }
+    }
+
+    fn ice_diagnostics(&self) -> Option<IceDiagnostics> {
+        let agent = self.internal.ice_gatherer.agent.try_lock().ok()?.clone()?;
+        let ai = &agent.internal;
+
+        let connection_state: ConnectionState = ai.connection_state.load(Ordering::SeqCst).into();
+        let local_candidates = ai
+            .local_candidates
+            .try_lock()
+            .map(|candidates| candidates.values().flatten().map(candidate).collect())
+            .unwrap_or_default();
+        let remote_candidates = ai
+            .remote_candidates
+            .try_lock()
+            .map(|candidates| candidates.values().flatten().map(candidate).collect())
+            .unwrap_or_default();
+        let candidate_pairs = ai
+            .agent_conn
+            .checklist
+            .try_lock()
+            .map(|checklist| checklist.iter().map(candidate_pair).collect())
+            .unwrap_or_default();
+        let selected_pair = ai
+            .agent_conn
+            .selected_pair
+            .try_lock()
+            .ok()
+            .and_then(|p| p.as_ref().map(candidate_pair));
+
+        Some(IceDiagnostics {
+            connection_state: connection_state.to_string(),
+            is_controlling: ai.is_controlling.load(Ordering::SeqCst),
+            local_candidates,
+            remote_candidates,
+            candidate_pairs,
+            selected_pair,
+        })
+    }
+
+    fn dtls_diagnostics(&self) -> DtlsDiagnostics {
+        let dtls_transport = &self.internal.dtls_transport;
+
+        let cipher_suite = dtls_transport
+            .conn
+            .try_lock()
+            .ok()
+            .and_then(|conn| conn.clone())
+            .and_then(|conn| conn.cipher_suite());
+        let local_fingerprints = dtls_transport
+            .certificates
+            .first()
+            .and_then(|certificate| certificate.get_fingerprints().ok())
+            .map(|fingerprints| fingerprints.iter().map(fingerprint).collect())
+            .unwrap_or_default();
+        let remote_fingerprints = dtls_transport
+            .remote_parameters
+            .try_lock()
+            .map(|parameters| parameters.fingerprints.iter().map(fingerprint).collect())
+            .unwrap_or_default();
+
+        DtlsDiagnostics {
+            state: dtls_transport.state().to_string(),
+            cipher_suite,
+            local_fingerprints,
+            remote_fingerprints,
+        }
+    }
+
+    fn sctp_diagnostics(&self) -> Option<SctpDiagnostics> {
+        let sctp_transport = &self.internal.sctp_transport;
+        let association = sctp_transport.sctp_association.try_lock().ok()?.clone()?;
+        let ai = association.association_internal.try_lock().ok()?;
+        let inflight = ai.inflight.try_lock().ok()?;
+
+        Some(SctpDiagnostics {
+            state: sctp_transport.state().to_string(),
+            max_num_inbound_streams: ai.num_inbound_streams,
+            max_num_outbound_streams: ai.num_outbound_streams,
+            mtu: inflight.mtu,
+            cwnd: inflight.cwnd,
+            ssthresh: inflight.ssthresh,
+            peer_rwnd: inflight.rwnd,
+            max_receive_buffer_size: ai.max_receive_buffer_size,
+            outstanding_bytes: ai.send_buffer.outstanding_bytes(),
+            pending_bytes: ai.send_buffer.pending_bytes(),
+            srtt_ms: inflight.rto_mgr.srtt,
+            rto_ms: inflight.rto_mgr.get_rto(),
+            bad_verification_tags: ai.stats.get_num_bad_verification_tags(),
+            invalid_cookies: ai.stats.get_num_invalid_cookies(),
+            out_of_order: ai.stats.get_num_out_of_order(),
+            max_reorder_distance: ai.stats.get_max_reorder_distance(),
+            duplicate_tsns: ai.stats.get_num_duplicate_tsns(),
+        })
+    }
+
+    fn data_channel_diagnostics(&self) -> Vec<DataChannelDiagnostics> {
+        let data_channels = match self.internal.sctp_transport.data_channels.try_lock() {
+            Ok(data_channels) => data_channels,
+            Err(_) => return vec![],
+        };
+
+        data_channels
+            .iter()
+            .map(|d| {
+                let reorder_stats = d.reorder_stats();
+                DataChannelDiagnostics {
+                    label: d.label().to_owned(),
+                    protocol: d.protocol().to_owned(),
+                    id: d.id(),
+                    ready_state: d.ready_state().to_string(),
+                    ordered: d.ordered(),
+                    max_retransmits: d.max_retransmits(),
+                    max_packet_lifetime: d.max_packet_lifetime(),
+                    buffered_amount: d.try_buffered_amount(),
+                    messages_expired: d.messages_expired(),
+                    out_of_order: reorder_stats.out_of_order,
+                    max_reorder_distance: reorder_stats.max_reorder_distance,
+                    duplicate_tsns: reorder_stats.duplicates,
+                }
+            })
+            .collect()
+    }
+}
+
+fn try_description(
+    pending: &Mutex<Option<RTCSessionDescription>>,
+    current: &Mutex<Option<RTCSessionDescription>>,
+) -> Option<String> {
+    let sdp = match pending.try_lock().ok()?.as_ref() {
+        Some(desc) => desc.sdp.clone(),
+        None => current.try_lock().ok()?.as_ref()?.sdp.clone(),
+    };
+
+    Some(redact_sdp(&redact_ice_pwd(&sdp)))
+}
+
+fn redact_ice_pwd(sdp: &str) -> String {
+    sdp.split_inclusive('\n')
+        .map(|line| {
+            if line.starts_with(ICE_PWD_PREFIX) {
+                let eol = &line[line.trim_end_matches(['\r', '\n']).len()..];
+                format!("{}<redacted>{}", ICE_PWD_PREFIX, eol)
+            } else {
+                line.to_owned()
+            }
+        })
+        .collect()
+}
+
+fn candidate(c: &Arc<dyn Candidate + Send + Sync>) -> CandidateDiagnostics {
+    CandidateDiagnostics {
+        id: c.id(),
+        candidate_type: c.candidate_type().to_string(),
+        network_type: c.network_type().to_string(),
+        address: redact_address(&c.address()),
+        port: c.port(),
+        priority: c.priority(),
+    }
+}
+
+fn candidate_pair(p: &Arc<CandidatePair>) -> CandidatePairDiagnostics {
+    let state: CandidatePairState = p.state.load(Ordering::SeqCst).into();
+
+    CandidatePairDiagnostics {
+        local_candidate_id: p.local.id(),
+        remote_candidate_id: p.remote.id(),
+        state: state.to_string(),
+        nominated: p.nominated.load(Ordering::SeqCst),
+        priority: p.priority(),
+        binding_request_count: p.binding_request_count.load(Ordering::SeqCst),
+    }
+}
+
+fn fingerprint(f: &RTCDtlsFingerprint) -> FingerprintDiagnostics {
+    FingerprintDiagnostics {
+        algorithm: f.algorithm.clone(),
+        value: f.value.clone(),
+    }
+}
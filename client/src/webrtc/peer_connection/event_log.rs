@@ -0,0 +1,170 @@
+use crate::webrtc::dtls_transport::dtls_transport_state::RTCDtlsTransportState;
+use crate::webrtc::ice_transport::ice_connection_state::RTCIceConnectionState;
+use crate::webrtc::peer_connection::peer_connection_state::RTCPeerConnectionState;
+use crate::webrtc::sctp::association::AssociationState;
+
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// DEFAULT_EVENT_LOG_SIZE is how many events a peer connection keeps unless
+/// SettingEngine::set_event_log_size says otherwise
+pub(crate) const DEFAULT_EVENT_LOG_SIZE: usize = 256;
+
+/// ConnectionEvent is an entry of the event log of a peer connection, see
+/// SocketHandle::recent_events.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+pub struct ConnectionEvent {
+    /// at is the time since the peer connection was created
+    pub at: Duration,
+    pub kind: ConnectionEventKind,
+}
+
+/// ConnectionEventKind is what happened in one of the state machines of a peer connection.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub enum ConnectionEventKind {
+    PeerConnectionState(RTCPeerConnectionState),
+    IceConnectionState(RTCIceConnectionState),
+    /// IceCheckSucceeded is a binding request of a candidate pair that got its success response
+    IceCheckSucceeded,
+    /// IceCheckFailed is a candidate pair given up on after its binding requests went unanswered
+    IceCheckFailed,
+    DtlsTransportState(RTCDtlsTransportState),
+    SctpAssociationState(AssociationState),
+    /// SctpPeerRestart is the remote restarting its SCTP association, which closes ours
+    SctpPeerRestart,
+    /// SctpPacketSent is an SCTP packet handed to DTLS, chunk_type is the type of its first
+    /// chunk as numbered by RFC 4960 section 3.2, e.g. 0 for DATA, and chunks how many it holds
+    SctpPacketSent {
+        chunk_type: u8,
+        chunks: u8,
+    },
+    /// SctpPacketReceived is an SCTP packet read from DTLS, see SctpPacketSent
+    SctpPacketReceived {
+        chunk_type: u8,
+        chunks: u8,
+    },
+}
+
+/// ConnectTimings tells how long after the peer connection was created each step of
+/// establishing the session was first reached, None for the steps not reached yet, see
+/// SocketHandle::connect_timings. The steps are listed in the order Socket::connect_session
+/// reaches them, it gathers the local candidates before creating the offer.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize)]
+pub struct ConnectTimings {
+    /// candidate_gathered is when the first local ICE candidate was gathered
+    pub candidate_gathered: Option<Duration>,
+    /// description_applied is when the first session description was set, normally the offer
+    pub description_applied: Option<Duration>,
+    /// check_succeeded is when the first connectivity check got its success response
+    pub check_succeeded: Option<Duration>,
+    /// pair_nominated is when the first candidate pair was selected
+    pub pair_nominated: Option<Duration>,
+    /// dtls_connected is when the DTLS handshake completed
+    pub dtls_connected: Option<Duration>,
+    /// sctp_established is when the SCTP association was established
+    pub sctp_established: Option<Duration>,
+    /// channel_open is when the first data channel opened, either side's
+    pub channel_open: Option<Duration>,
+}
+
+/// ConnectMilestone is a step of ConnectTimings that is not derived from a ConnectionEvent
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum ConnectMilestone {
+    CandidateGathered,
+    DescriptionApplied,
+    PairNominated,
+    ChannelOpen,
+}
+
+/// EventLog keeps the last events of a peer connection, the oldest is dropped once it is full.
+/// Recording takes a timestamp and an uncontended lock, the events are only formatted when
+/// somebody looks at them.
+///
+/// The ConnectTimings are kept next to the events, also when the log records none.
+pub(crate) struct EventLog {
+    started: Instant,
+    capacity: usize,
+    events: Mutex<VecDeque<ConnectionEvent>>,
+    timings: Mutex<ConnectTimings>,
+}
+
+impl EventLog {
+    /// new creates a log keeping capacity events, zero records nothing
+    pub(crate) fn new(capacity: usize) -> Self {
+        EventLog {
+            started: Instant::now(),
+            capacity,
+            events: Mutex::new(VecDeque::with_capacity(capacity)),
+            timings: Mutex::new(ConnectTimings::default()),
+        }
+    }
+
+    pub(crate) fn record(&self, kind: ConnectionEventKind) {
+        let at = self.started.elapsed();
+        match kind {
+            ConnectionEventKind::IceCheckSucceeded => {
+                self.reach(at, |timings| &mut timings.check_succeeded)
+            }
+            ConnectionEventKind::DtlsTransportState(RTCDtlsTransportState::Connected) => {
+                self.reach(at, |timings| &mut timings.dtls_connected)
+            }
+            ConnectionEventKind::SctpAssociationState(AssociationState::Established) => {
+                self.reach(at, |timings| &mut timings.sctp_established)
+            }
+            _ => {}
+        }
+
+        if self.capacity == 0 {
+            return;
+        }
+
+        let event = ConnectionEvent { at, kind };
+        let mut events = self.events.lock().unwrap();
+        if events.len() == self.capacity {
+            events.pop_front();
+        }
+        events.push_back(event);
+    }
+
+    /// recent_events returns the kept events, oldest first
+    pub(crate) fn recent_events(&self) -> Vec<ConnectionEvent> {
+        self.events.lock().unwrap().iter().copied().collect()
+    }
+
+    /// milestone records that a step of ConnectTimings was reached, only the first time counts
+    pub(crate) fn milestone(&self, milestone: ConnectMilestone) {
+        let at = self.started.elapsed();
+        match milestone {
+            ConnectMilestone::CandidateGathered => {
+                self.reach(at, |timings| &mut timings.candidate_gathered)
+            }
+            ConnectMilestone::DescriptionApplied => {
+                self.reach(at, |timings| &mut timings.description_applied)
+            }
+            ConnectMilestone::PairNominated => {
+                self.reach(at, |timings| &mut timings.pair_nominated)
+            }
+            ConnectMilestone::ChannelOpen => self.reach(at, |timings| &mut timings.channel_open),
+        }
+    }
+
+    /// connect_timings returns the steps of establishing the session reached so far
+    pub(crate) fn connect_timings(&self) -> ConnectTimings {
+        *self.timings.lock().unwrap()
+    }
+
+    fn reach(&self, at: Duration, step: impl FnOnce(&mut ConnectTimings) -> &mut Option<Duration>) {
+        let mut timings = self.timings.lock().unwrap();
+        step(&mut timings).get_or_insert(at);
+    }
+}
+
+impl Default for EventLog {
+    /// default records nothing, it stands in until a transport is given its peer connection's log
+    fn default() -> Self {
+        EventLog::new(0)
+    }
+}
@@ -1,4 +1,8 @@
 pub(crate) mod certificate;
+pub(crate) mod configuration;
+#[cfg(feature = "serde")]
+pub(crate) mod diagnostics;
+pub(crate) mod event_log;
 pub(crate) mod operation;
 mod peer_connection_internal;
 pub(crate) mod peer_connection_state;
@@ -7,6 +11,7 @@ pub(crate) mod sdp;
 pub(crate) mod signaling_state;
 
 use crate::webrtc::api::API;
+use crate::webrtc::data_channel::data_channel_parameters::DataChannelParameters;
 use crate::webrtc::data_channel::data_channel_state::RTCDataChannelState;
 use crate::webrtc::data_channel::RTCDataChannel;
 use crate::webrtc::dtls_transport::dtls_fingerprint::RTCDtlsFingerprint;
@@ -15,15 +20,21 @@ use crate::webrtc::dtls_transport::dtls_role::{DTLSRole, DEFAULT_DTLS_ROLE_OFFER
 use crate::webrtc::dtls_transport::dtls_transport_state::RTCDtlsTransportState;
 use crate::webrtc::dtls_transport::RTCDtlsTransport;
 use crate::webrtc::error::{Error, Result};
-use crate::webrtc::ice_transport::ice_candidate::RTCIceCandidate;
+use crate::webrtc::ice_transport::ice_candidate::{RTCIceCandidate, RTCIceCandidateInit};
+use crate::webrtc::ice_transport::ice_candidate_stats::{CandidateStats, CandidateStatsReport};
 use crate::webrtc::ice_transport::ice_connection_state::RTCIceConnectionState;
-use crate::webrtc::ice_transport::ice_gatherer::RTCIceGatherer;
+use crate::webrtc::ice_transport::ice_gatherer::{OnLocalCandidateHdlrFn, RTCIceGatherer};
 use crate::webrtc::ice_transport::ice_gatherer_state::RTCIceGathererState;
 use crate::webrtc::ice_transport::ice_gathering_state::RTCIceGatheringState;
 use crate::webrtc::ice_transport::ice_parameters::RTCIceParameters;
 use crate::webrtc::ice_transport::ice_role::RTCIceRole;
 use crate::webrtc::ice_transport::ice_transport_state::RTCIceTransportState;
 use crate::webrtc::ice_transport::RTCIceTransport;
+use crate::webrtc::internal::message::message_channel_open::{MAX_LABEL_LEN, MAX_PROTOCOL_LEN};
+use crate::webrtc::peer_connection::configuration::RTCConfiguration;
+use crate::webrtc::peer_connection::event_log::{
+    ConnectMilestone, ConnectTimings, ConnectionEvent, ConnectionEventKind, EventLog,
+};
 use crate::webrtc::peer_connection::operation::{Operation, Operations};
 use crate::webrtc::peer_connection::peer_connection_state::RTCPeerConnectionState;
 use crate::webrtc::peer_connection::sdp::sdp_type::RTCSdpType;
@@ -34,36 +45,31 @@ use crate::webrtc::peer_connection::signaling_state::{
 };
 use crate::webrtc::sctp_transport::sctp_transport_capabilities::SCTPTransportCapabilities;
 use crate::webrtc::sctp_transport::sctp_transport_state::RTCSctpTransportState;
-use crate::webrtc::sctp_transport::RTCSctpTransport;
+use crate::webrtc::sctp_transport::transport_events::TransportEvent;
+use crate::webrtc::sctp_transport::{register_data_channel, RTCSctpTransport, SctpTraffic};
 
 use crate::webrtc::ice::candidate::candidate_base::unmarshal_candidate;
 use crate::webrtc::ice::candidate::Candidate;
+use crate::webrtc::ice::util::SocketBufferSizes;
 use crate::webrtc::sdp::description::session::*;
 use crate::webrtc::sdp::util::ConnectionRole;
+use crate::webrtc::util::conn::send_error::SendErrorCounts;
+use crate::webrtc::util::random_source::RandomSource;
 use peer_connection_internal::*;
-use rand::{thread_rng, Rng};
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::sync::{Arc, OnceLock};
+use tokio::sync::{broadcast, mpsc, Mutex};
 
 pub(crate) const MEDIA_SECTION_APPLICATION: &str = "application";
+pub(crate) const MEDIA_FORMAT_DATACHANNEL: &str = "webrtc-datachannel";
 
 const RUNES_ALPHA: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
 
 /// math_rand_alpha generates a mathmatical random alphabet sequence of the requested length.
-pub(crate) fn math_rand_alpha(n: usize) -> String {
-    let mut rng = thread_rng();
-
-    let rand_string: String = (0..n)
-        .map(|_| {
-            let idx = rng.gen_range(0..RUNES_ALPHA.len());
-            RUNES_ALPHA[idx] as char
-        })
-        .collect();
-
-    rand_string
+pub(crate) fn math_rand_alpha(n: usize, rng: &RandomSource) -> String {
+    rng.string(n, RUNES_ALPHA)
 }
 
 pub(crate) type OnSignalingStateChangeHdlrFn = Box<
@@ -90,12 +96,28 @@ pub(crate) type OnDataChannelHdlrFn = Box<
         + Sync,
 >;
 
+/// TransportChange is what an answer in a renegotiation changes about the running transports,
+/// see RTCPeerConnection::remote_transport_change
+struct TransportChange {
+    /// ice_restart is set when the answered offer requested an ICE restart, the agent then
+    /// takes the remote credentials of the answer
+    ice_restart: bool,
+    /// fingerprint_changed is set when the remote DTLS certificate changed, which takes a new
+    /// DTLS handshake
+    fingerprint_changed: bool,
+}
+
 /// PeerConnection represents a WebRTC connection that establishes a
 /// peer-to-peer communications with another PeerConnection instance in a
 /// browser, or to another endpoint implementing the required protocols.
 pub(crate) struct RTCPeerConnection {
     idp_login_url: Option<String>,
 
+    // operations_chain runs create_offer, set_local_description, set_remote_description and
+    // add_ice_candidate one at a time, in the order they were first polled
+    // <https://w3c.github.io/webrtc-pc/#dfn-operations-chain>
+    operations_chain: Mutex<()>,
+
     pub(crate) internal: Arc<PeerConnectionInternal>,
 }
 
@@ -106,8 +128,8 @@ impl RTCPeerConnection {
     /// If you wish to customize the set of available codecs or the set of
     /// active interceptors, create a MediaEngine and call api.new_peer_connection
     /// instead of this function.
-    pub(crate) async fn new() -> Arc<RTCPeerConnection> {
-        let internal = PeerConnectionInternal::new()
+    pub(crate) async fn new(api: &API, configuration: RTCConfiguration) -> Arc<RTCPeerConnection> {
+        let internal = PeerConnectionInternal::new(api, configuration)
             .await
             .expect("can't create peer connection");
 
@@ -117,9 +139,44 @@ impl RTCPeerConnection {
         Arc::new(RTCPeerConnection {
             internal,
             idp_login_url: None,
+            operations_chain: Mutex::new(()),
         })
     }
 
+    /// on_data_channel sets an event handler which is invoked when a data
+    /// channel message arrives from a remote peer.
+    pub(crate) async fn on_data_channel(&self, f: OnDataChannelHdlrFn) {
+        let mut handler = self.internal.on_data_channel_handler.lock().await;
+        *handler = Some(f);
+    }
+
+    /// on_ice_candidate sets an event handler which is invoked when a new ICE
+    /// candidate is found, including candidates gathered after connecting when
+    /// the local addresses change. The end of gathering is signalled with None.
+    pub(crate) async fn on_ice_candidate(&self, f: OnLocalCandidateHdlrFn) {
+        let mut handler = self
+            .internal
+            .ice_gatherer
+            .on_local_candidate_handler
+            .lock()
+            .await;
+        *handler = Some(f);
+    }
+
+    /// on_peer_connection_state_change sets an event handler which is called
+    /// when the PeerConnectionState has changed
+    pub(crate) async fn on_peer_connection_state_change(
+        &self,
+        f: OnPeerConnectionStateChangeHdlrFn,
+    ) {
+        let mut handler = self
+            .internal
+            .on_peer_connection_state_change_handler
+            .lock()
+            .await;
+        *handler = Some(f);
+    }
+
     async fn do_signaling_state_change(&self, new_state: RTCSignalingState) {
         log::info!("signaling state changed to {}", new_state);
         let mut handler = self.internal.on_signaling_state_change_handler.lock().await;
@@ -159,6 +216,7 @@ impl RTCPeerConnection {
     /// create_offer starts the PeerConnection and generates the localDescription
     /// <https://w3c.github.io/webrtc-pc/#dom-rtcpeerconnection-createoffer>
     pub(crate) async fn create_offer(&self) -> Result<RTCSessionDescription> {
+        let _chain = self.operations_chain.lock().await;
         let use_identity = self.idp_login_url.is_some();
         if use_identity {
             return Err(Error::ErrIdentityProviderNotImplemented);
@@ -166,88 +224,65 @@ impl RTCPeerConnection {
             return Err(Error::ErrConnectionClosed);
         }
 
-        // This may be necessary to recompute if, for example, createOffer was called when only an
-        // audio RTCRtpTransceiver was added to connection, but while performing the in-parallel
-        // steps to create an offer, a video RTCRtpTransceiver was added, requiring additional
-        // inspection of video system resources.
-        let offer;
-
-        loop {
-            // in-parallel steps to create an offer
-            // https://w3c.github.io/webrtc-pc/#dfn-in-parallel-steps-to-create-an-offer
-            let is_plan_b = {
-                let current_remote_description =
-                    self.internal.current_remote_description.lock().await;
-                if current_remote_description.is_some() {
-                    description_is_plan_b(current_remote_description.as_ref())?
-                } else {
-                    false
-                }
-            };
-
-            // include unmatched local transceivers
-            if !is_plan_b {
-                // update the greater mid if the remote description provides a greater one
-                {
-                    let current_remote_description =
-                        self.internal.current_remote_description.lock().await;
-                    if let Some(d) = &*current_remote_description {
-                        if let Some(parsed) = &d.parsed {
-                            for media in &parsed.media_descriptions {
-                                if let Some(mid) = get_mid_value(media) {
-                                    if mid.is_empty() {
-                                        continue;
-                                    }
-                                    let numeric_mid = match mid.parse::<isize>() {
-                                        Ok(n) => n,
-                                        Err(_) => continue,
-                                    };
-                                    if numeric_mid
-                                        > self.internal.greater_mid.load(Ordering::SeqCst)
-                                    {
-                                        self.internal
-                                            .greater_mid
-                                            .store(numeric_mid, Ordering::SeqCst);
-                                    }
-                                }
-                            }
+        // in-parallel steps to create an offer
+        // https://w3c.github.io/webrtc-pc/#dfn-in-parallel-steps-to-create-an-offer
+        //
+        // The remote description is snapshotted once and no lock is held while the SDP is
+        // generated, so callbacks running meanwhile can use the PeerConnection freely.
+        let current_remote_description = {
+            let current_remote_description = self.internal.current_remote_description.lock().await;
+            current_remote_description.clone()
+        };
+
+        // include unmatched local transceivers
+        if !description_is_plan_b(current_remote_description.as_ref())? {
+            // update the greater mid if the remote description provides a greater one
+            if let Some(parsed) = current_remote_description
+                .as_ref()
+                .and_then(|d| d.parsed.get())
+            {
+                for media in &parsed.media_descriptions {
+                    if let Some(mid) = get_mid_value(media) {
+                        if mid.is_empty() {
+                            continue;
+                        }
+                        let numeric_mid = match mid.parse::<isize>() {
+                            Ok(n) => n,
+                            Err(_) => continue,
+                        };
+                        if numeric_mid > self.internal.greater_mid.load(Ordering::SeqCst) {
+                            self.internal
+                                .greater_mid
+                                .store(numeric_mid, Ordering::SeqCst);
                         }
                     }
                 }
             }
+        }
 
-            let current_remote_description_is_none = {
-                let current_remote_description =
-                    self.internal.current_remote_description.lock().await;
-                current_remote_description.is_none()
-            };
-
-            let mut d = if current_remote_description_is_none {
-                self.internal.generate_unmatched_sdp(use_identity).await?
-            } else {
-                self.internal
-                    .generate_matched_sdp(
-                        use_identity,
-                        true, /*includeUnmatched */
-                        DEFAULT_DTLS_ROLE_OFFER.to_connection_role(),
-                    )
-                    .await?
-            };
-
-            {
-                let mut sdp_origin = self.internal.sdp_origin.lock().await;
-                update_sdp_origin(&mut sdp_origin, &mut d);
-            }
-            let sdp = d.marshal();
-
-            offer = RTCSessionDescription {
-                sdp_type: RTCSdpType::Offer,
-                sdp,
-                parsed: Some(d),
-            };
+        let mut d = if current_remote_description.is_none() {
+            self.internal.generate_unmatched_sdp(use_identity).await?
+        } else {
+            self.internal
+                .generate_matched_sdp(
+                    use_identity,
+                    true, /*includeUnmatched */
+                    DEFAULT_DTLS_ROLE_OFFER.to_connection_role(),
+                )
+                .await?
+        };
 
-            break;
+        {
+            let mut sdp_origin = self.internal.sdp_origin.lock().await;
+            update_sdp_origin(&mut sdp_origin, &mut d);
         }
+        let sdp = d.marshal();
+
+        let offer = RTCSessionDescription {
+            sdp_type: RTCSdpType::Offer,
+            sdp,
+            parsed: OnceLock::from(d),
+        };
 
         {
             let mut last_offer = self.internal.last_offer.lock().await;
@@ -256,6 +291,87 @@ impl RTCPeerConnection {
         Ok(offer)
     }
 
+    /// create_offer_with_gathering_complete gathers the local candidates and waits for gathering
+    /// to finish before creating the offer, so that it carries every candidate inline followed by
+    /// a=end-of-candidates. This is meant for remotes that cannot take trickled candidates. The
+    /// offer is applied with set_local_description as usual. It fails with
+    /// ErrNoCandidatesGathered if there is no candidate to offer, unless
+    /// SettingEngine::set_allow_zero_candidates is set.
+    pub(crate) async fn create_offer_with_gathering_complete(
+        &self,
+    ) -> Result<RTCSessionDescription> {
+        self.gather_candidates().await?;
+        self.create_offer().await
+    }
+
+    /// gather_candidates gathers the local candidates and waits for gathering to finish, it
+    /// returns right away if that happened before. Doing so ahead of the offer makes
+    /// create_offer_with_gathering_complete return without waiting. It fails like
+    /// create_offer_with_gathering_complete if there is no candidate to offer.
+    pub(crate) async fn gather_candidates(&self) -> Result<()> {
+        if self.internal.is_closed.load(Ordering::SeqCst) {
+            return Err(Error::ErrConnectionClosed);
+        }
+
+        let mut gathering_complete = self.gathering_complete_promise().await;
+        if self.internal.ice_gatherer.state() == RTCIceGathererState::New {
+            self.internal.ice_gatherer.gather().await?;
+        }
+        let _ = gathering_complete.recv().await;
+
+        let ice_gatherer = &self.internal.ice_gatherer;
+        if ice_gatherer.no_candidates_gathered.load(Ordering::SeqCst)
+            && !ice_gatherer.setting_engine.allow_zero_candidates
+        {
+            return Err(Error::ErrNoCandidatesGathered);
+        }
+
+        Ok(())
+    }
+
+    /// restart_ice gives the ICE agent new local credentials and drops its candidates and
+    /// candidate pairs, the next offer then requests an ICE restart. Connectivity is lost until
+    /// the answer to that offer is applied, the candidates are gathered again with it.
+    pub(crate) async fn restart_ice(&self) -> Result<()> {
+        if self.internal.is_closed.load(Ordering::SeqCst) {
+            return Err(Error::ErrConnectionClosed);
+        }
+        self.internal.ice_gatherer.restart().await
+    }
+
+    /// gathering_complete_promise returns a receiver that is closed once ICE gathering has
+    /// completed, or right away if it already has.
+    async fn gathering_complete_promise(&self) -> mpsc::Receiver<()> {
+        let (gathering_complete_tx, gathering_complete_rx) = mpsc::channel(1);
+
+        // the handler would be missed if gathering completed before it is set, so the state is
+        // checked again afterwards
+        let done = Arc::new(Mutex::new(Some(gathering_complete_tx)));
+        let done2 = Arc::clone(&done);
+        {
+            let mut handler = self
+                .internal
+                .ice_gatherer
+                .on_gathering_complete_handler
+                .lock()
+                .await;
+            *handler = Some(Box::new(move || {
+                let done3 = Arc::clone(&done2);
+                Box::pin(async move {
+                    let mut done = done3.lock().await;
+                    done.take();
+                })
+            }));
+        }
+
+        if self.ice_gathering_state() == RTCIceGatheringState::Complete {
+            let mut done = done.lock().await;
+            done.take();
+        }
+
+        gathering_complete_rx
+    }
+
     /// Update the PeerConnectionState given the state of relevant transports
     /// <https://www.w3.org/TR/webrtc/#rtcpeerconnectionstate-enum>
     async fn update_connection_state(
@@ -266,6 +382,7 @@ impl RTCPeerConnection {
         peer_connection_state: &Arc<AtomicU8>,
         ice_connection_state: RTCIceConnectionState,
         dtls_transport_state: RTCDtlsTransportState,
+        event_log: &EventLog,
     ) {
         let  connection_state =
         // The RTCPeerConnection object's [[IsClosed]] slot is true.
@@ -282,7 +399,7 @@ impl RTCPeerConnection {
             // All RTCIceTransports and RTCDtlsTransports are in the "connected", "completed" or "closed"
             // state and at least one of them is in the "connected" or "completed" state.
             RTCPeerConnectionState::Connected
-        }else if ice_connection_state == RTCIceConnectionState::Checking && dtls_transport_state == RTCDtlsTransportState::Connecting{
+        }else if ice_connection_state == RTCIceConnectionState::Checking || dtls_transport_state == RTCDtlsTransportState::Connecting{
         //  Any of the RTCIceTransports or RTCDtlsTransports are in the "connecting" or
         // "checking" state and none of them is in the "failed" state.
              RTCPeerConnectionState::Connecting
@@ -296,6 +413,7 @@ impl RTCPeerConnection {
 
         log::info!("peer connection state changed: {}", connection_state);
         peer_connection_state.store(connection_state as u8, Ordering::SeqCst);
+        event_log.record(ConnectionEventKind::PeerConnectionState(connection_state));
 
         RTCPeerConnection::do_peer_connection_state_change(
             on_peer_connection_state_change_handler,
@@ -533,6 +651,7 @@ impl RTCPeerConnection {
         &self,
         mut desc: RTCSessionDescription,
     ) -> Result<()> {
+        let _chain = self.operations_chain.lock().await;
         if self.internal.is_closed.load(Ordering::SeqCst) {
             return Err(Error::ErrConnectionClosed);
         }
@@ -548,8 +667,11 @@ impl RTCPeerConnection {
             }
         }
 
-        desc.parsed = Some(desc.unmarshal()?);
+        desc.parsed = OnceLock::from(desc.unmarshal()?);
         self.set_description(&desc, StateChangeOp::SetLocal).await?;
+        self.internal
+            .event_log
+            .milestone(ConnectMilestone::DescriptionApplied);
 
         if self.internal.ice_gatherer.state() == RTCIceGathererState::New {
             self.internal.ice_gatherer.gather().await
@@ -574,19 +696,59 @@ impl RTCPeerConnection {
         &self,
         mut desc: RTCSessionDescription,
     ) -> Result<()> {
+        let _chain = self.operations_chain.lock().await;
         if self.internal.is_closed.load(Ordering::SeqCst) {
             return Err(Error::ErrConnectionClosed);
         }
 
-        desc.parsed = Some(desc.unmarshal()?);
+        desc.parsed = OnceLock::from(desc.unmarshal()?);
+        let mut transport_change = None;
+        if matches!(desc.sdp_type, RTCSdpType::Answer | RTCSdpType::Pranswer) {
+            self.validate_remote_answer(&desc).await?;
+            transport_change = self.remote_transport_change(&desc).await?;
+        }
         self.set_description(&desc, StateChangeOp::SetRemote)
             .await?;
+        self.internal
+            .event_log
+            .milestone(ConnectMilestone::DescriptionApplied);
 
-        if let Some(parsed) = &desc.parsed {
+        if let Some(parsed) = desc.parsed.get() {
             let we_offer = true;
 
+            let bandwidth_limit = remote_bandwidth_limit(parsed);
+            if let Some(limit) = bandwidth_limit {
+                log::debug!("remote description limits the bandwidth to {} bit/s", limit);
+            }
+            self.internal
+                .sctp_transport
+                .set_remote_bandwidth_limit(bandwidth_limit);
+
+            let sctp_params = remote_sctp_params(parsed);
+            if let Some(params) = sctp_params.filter(|params| params.legacy) {
+                log::debug!(
+                    "remote description uses a=sctpmap, SCTP port {} with {:?} streams",
+                    params.port,
+                    params.max_streams
+                );
+            }
+            self.internal
+                .sctp_transport
+                .set_remote_sctp_params(sctp_params);
+
             let (remote_ufrag, remote_pwd, candidates) = extract_ice_details(parsed).await?;
 
+            // the candidates of an ICE restart are checked with the new credentials
+            if matches!(&transport_change, Some(change) if change.ice_restart) {
+                self.internal
+                    .ice_transport
+                    .set_remote_credentials(&RTCIceParameters {
+                        username_fragment: remote_ufrag.clone(),
+                        password: remote_pwd.clone(),
+                    })
+                    .await?;
+            }
+
             for candidate in candidates {
                 self.internal
                     .ice_transport
@@ -594,16 +756,57 @@ impl RTCPeerConnection {
                     .await?;
             }
 
+            if let Some(agent) = self.internal.ice_gatherer.get_agent().await {
+                agent.set_remote_ice_options(&extract_ice_options(parsed));
+                #[cfg(feature = "network-monitor")]
+                agent.set_remote_trickle(is_trickle_set(parsed));
+            }
+
             let (fingerprint, fingerprint_hash) = extract_fingerprint(parsed)?;
 
-            // If one of the agents is lite and the other one is not, the lite agent must be the controlling agent.
+            // If one of the agents is lite and the other one is not, the full agent must be the controlling agent.
             // If both or neither agents are lite the offering agent is controlling.
             // RFC 8445 S6.1.1
-            let ice_role = RTCIceRole::Controlling;
+            let remote_is_lite = is_lite_set(parsed);
+            if remote_is_lite {
+                log::debug!("remote description is ice-lite, taking the controlling role");
+            }
+            let ice_role =
+                if let Some(ice_role) = self.internal.ice_gatherer.setting_engine.ice_role {
+                    log::debug!("ICE role {} forced by the setting engine", ice_role);
+                    ice_role
+                } else if remote_is_lite || we_offer {
+                    RTCIceRole::Controlling
+                } else {
+                    RTCIceRole::Controlled
+                };
 
             let pci = Arc::clone(&self.internal);
             let dtls_role = DTLSRole::from(parsed);
             let remote_desc = Arc::new(desc);
+
+            // a renegotiation leaves the running transports alone, unless the remote came back
+            // from the ICE restart with another certificate
+            if let Some(change) = transport_change {
+                if change.fingerprint_changed {
+                    self.internal
+                        .ops
+                        .enqueue(Operation(Box::new(move || {
+                            let pc = Arc::clone(&pci);
+                            let rd = Arc::clone(&remote_desc);
+                            let fp = fingerprint.clone();
+                            let fp_hash = fingerprint_hash.clone();
+                            Box::pin(async move {
+                                log::debug!("remote DTLS fingerprint changed, restarting DTLS");
+                                pc.restart_dtls(dtls_role, fp, fp_hash, rd).await;
+                                false
+                            })
+                        })))
+                        .await?;
+                }
+                return Ok(());
+            }
+
             self.internal
                 .ops
                 .enqueue(Operation(Box::new(move || {
@@ -634,6 +837,127 @@ impl RTCPeerConnection {
         Ok(())
     }
 
+    /// fail_without_data_media_section moves the connection to failed once the remote
+    /// description turned out unable to carry data channels, instead of leaving it to wait for a
+    /// DTLS handshake that never starts. Channels waiting to open are closed and get
+    /// ErrNoDataMediaSection through their on_error handler.
+    async fn fail_without_data_media_section(&self, reason: &str) {
+        log::warn!(
+            "remote description has no usable data media section: {}",
+            reason
+        );
+        self.internal
+            .fail(|| Error::ErrNoDataMediaSection(reason.to_owned()))
+            .await;
+    }
+
+    /// validate_remote_answer rejects an answer or pranswer that is not legal in the signaling
+    /// state or does not respond to the pending local offer, before it changes any state. One
+    /// that cannot carry data channels also fails the connection, see
+    /// fail_without_data_media_section.
+    async fn validate_remote_answer(&self, desc: &RTCSessionDescription) -> Result<()> {
+        let state = self.signaling_state();
+        let legal = match desc.sdp_type {
+            RTCSdpType::Answer => matches!(
+                state,
+                RTCSignalingState::HaveLocalOffer | RTCSignalingState::HaveRemotePranswer
+            ),
+            _ => state == RTCSignalingState::HaveLocalOffer,
+        };
+        if !legal {
+            return Err(Error::ErrIllegalSdpTypeForState {
+                sdp_type: desc.sdp_type,
+                state,
+            });
+        }
+
+        // checked before the answer is matched to the offer, an answer without any media
+        // section would otherwise only be reported as answering another offer
+        if let Some(answer) = desc.parsed.get() {
+            if let Err(err) = validate_data_media_section(answer) {
+                if let Error::ErrNoDataMediaSection(reason) = &err {
+                    self.fail_without_data_media_section(reason).await;
+                }
+                return Err(err);
+            }
+        }
+
+        let offer = {
+            let pending_local_description = self.internal.pending_local_description.lock().await;
+            match pending_local_description.as_ref() {
+                Some(offer) => match offer.parsed.get() {
+                    Some(parsed) => parsed.clone(),
+                    None => offer.unmarshal()?,
+                },
+                None => return Ok(()),
+            }
+        };
+        if let Some(answer) = desc.parsed.get() {
+            validate_answer(
+                &offer,
+                answer,
+                self.internal.configuration.strict_answer_validation,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// remote_transport_change compares an answer with the current remote description, it is
+    /// None for the first answer. Other ICE credentials or another DTLS fingerprint are only
+    /// accepted if the answered offer requested an ICE restart, i.e. carries another local ufrag
+    /// than the current local description, ErrRemoteCredentialsChanged otherwise.
+    async fn remote_transport_change(
+        &self,
+        desc: &RTCSessionDescription,
+    ) -> Result<Option<TransportChange>> {
+        let current_remote_description = {
+            let current_remote_description = self.internal.current_remote_description.lock().await;
+            current_remote_description.clone()
+        };
+        let (current, answer) = match (
+            current_remote_description
+                .as_ref()
+                .and_then(|d| d.parsed.get()),
+            desc.parsed.get(),
+        ) {
+            (Some(current), Some(answer)) => (current, answer),
+            _ => return Ok(None),
+        };
+
+        let ice_restart = {
+            let offer = self.pending_local_description().await;
+            let current_local = self.current_local_description().await;
+            match (
+                offer.as_ref().and_then(|d| d.parsed.get()),
+                current_local.as_ref().and_then(|d| d.parsed.get()),
+            ) {
+                (Some(offer), Some(current_local)) => {
+                    extract_ice_details(offer).await?.0
+                        != extract_ice_details(current_local).await?.0
+                }
+                _ => false,
+            }
+        };
+
+        let (ufrag, pwd, _) = extract_ice_details(current).await?;
+        let (new_ufrag, new_pwd, _) = extract_ice_details(answer).await?;
+        let fingerprint_changed = extract_fingerprint(current)? != extract_fingerprint(answer)?;
+        if !ice_restart {
+            if ufrag != new_ufrag || pwd != new_pwd {
+                return Err(Error::ErrRemoteCredentialsChanged("ICE credentials"));
+            }
+            if fingerprint_changed {
+                return Err(Error::ErrRemoteCredentialsChanged("DTLS fingerprint"));
+            }
+        }
+
+        Ok(Some(TransportChange {
+            ice_restart,
+            fingerprint_changed,
+        }))
+    }
+
     /// remote_description returns pending_remote_description if it is not null and
     /// otherwise it returns current_remote_description. This property is used to
     /// determine if setRemoteDescription has already been called.
@@ -642,16 +966,29 @@ impl RTCPeerConnection {
         self.internal.remote_description().await
     }
 
-    /// add_ice_candidate accepts an ICE candidate string and adds it
-    /// to the existing set of candidates.
-    pub(crate) async fn add_ice_candidate(&self, candidate_str: String) -> Result<()> {
-        if self.remote_description().await.is_none() {
-            return Err(Error::ErrNoRemoteDescription);
+    /// add_ice_candidate accepts an ICE candidate and adds it to the existing set of candidates.
+    /// It has to belong to a media section of the remote description, see RTCIceCandidateInit.
+    pub(crate) async fn add_ice_candidate(&self, candidate: RTCIceCandidateInit) -> Result<()> {
+        let _chain = self.operations_chain.lock().await;
+        let remote_description = match self.remote_description().await {
+            Some(remote_description) => remote_description,
+            None => return Err(Error::ErrNoRemoteDescription),
+        };
+        let parsed = remote_description.parsed()?;
+        let media = candidate_media_description(parsed, &candidate)?;
+        if let Some(ufrag) = &candidate.username_fragment {
+            if ice_ufrag(parsed, media) != Some(ufrag.as_str()) {
+                log::debug!(
+                    "dropping candidate of ufrag {}, an earlier ICE generation",
+                    ufrag
+                );
+                return Ok(());
+            }
         }
 
-        let candidate_value = match candidate_str.strip_prefix("candidate:") {
+        let candidate_value = match candidate.candidate.strip_prefix("candidate:") {
             Some(s) => s,
-            None => candidate_str.as_str(),
+            None => candidate.candidate.as_str(),
         };
 
         let ice_candidate = if !candidate_value.is_empty() {
@@ -671,31 +1008,54 @@ impl RTCPeerConnection {
 
     /// create_data_channel creates a new DataChannel object with the given label
     /// and optional DataChannelInit used to configure properties of the
-    /// underlying channel such as data reliability.
+    /// underlying channel such as data reliability. See RTCDataChannel::priority for priority.
     pub(crate) async fn create_data_channel(
         &self,
         label: &str,
         protocol: &str,
+        priority: u16,
     ) -> Result<Arc<RTCDataChannel>> {
         // https://w3c.github.io/webrtc-pc/#peer-to-peer-data-api (Step #2)
         if self.internal.is_closed.load(Ordering::SeqCst) {
             return Err(Error::ErrConnectionClosed);
         }
+        // a channel created on a failed connection would never open
+        if self.connection_state() == RTCPeerConnectionState::Failed {
+            return Err(Error::ErrConnectionFailed);
+        }
 
-        let d = Arc::new(RTCDataChannel::new(label, protocol));
-
-        {
-            let mut data_channels = self.internal.sctp_transport.data_channels.lock().await;
-            data_channels.push(Arc::clone(&d));
+        // https://w3c.github.io/webrtc-pc/#peer-to-peer-data-api (Step #4, #6)
+        if label.len() > MAX_LABEL_LEN || protocol.len() > MAX_PROTOCOL_LEN {
+            return Err(Error::ErrStringSizeLimit);
         }
+
+        // Streams of this client are always unordered and never retransmit, announce the
+        // channel accordingly in the DCEP DATA_CHANNEL_OPEN.
+        let d = Arc::new(RTCDataChannel::new(
+            DataChannelParameters {
+                label: label.to_owned(),
+                protocol: protocol.to_owned(),
+                ordered: false,
+                max_packet_lifetime: None,
+                max_retransmits: Some(0),
+                priority,
+            },
+            self.internal.sctp_transport.spawner().clone(),
+        ));
+
+        register_data_channel(&self.internal.sctp_transport.data_channels, Arc::clone(&d)).await;
         self.internal
             .sctp_transport
             .data_channels_requested
             .fetch_add(1, Ordering::SeqCst);
 
-        // If SCTP already connected open all the channels
+        // If SCTP already connected open the channel right away, DCEP needs no new offer/answer
         if self.internal.sctp_transport.state() == RTCSctpTransportState::Connected {
             d.open(Arc::clone(&self.internal.sctp_transport)).await?;
+            self.internal
+                .sctp_transport
+                .data_channels_opened
+                .fetch_add(1, Ordering::SeqCst);
         }
 
         Ok(d)
@@ -731,15 +1091,155 @@ impl RTCPeerConnection {
         populate_local_candidates(local_description.as_ref(), ice_gather, ice_gathering_state).await
     }
 
+    /// close ends the PeerConnection, stopping the SCTP, DTLS and ICE transports
+    /// in that order. Closing an already closed PeerConnection is a no-op.
+    /// <https://www.w3.org/TR/webrtc/#dom-rtcpeerconnection-close>
+    pub(crate) async fn close(&self) -> Result<()> {
+        self.internal.close().await
+    }
+
     /// signaling_state attribute returns the signaling state of the
     /// PeerConnection instance.
     pub(crate) fn signaling_state(&self) -> RTCSignalingState {
         self.internal.signaling_state.load(Ordering::SeqCst).into()
     }
 
+    /// connection_state attribute returns the connection state of the
+    /// PeerConnection instance.
+    pub(crate) fn connection_state(&self) -> RTCPeerConnectionState {
+        self.internal
+            .peer_connection_state
+            .load(Ordering::SeqCst)
+            .into()
+    }
+
     /// icegathering_state attribute returns the ICE gathering state of the
     /// PeerConnection instance.
     pub(crate) fn ice_gathering_state(&self) -> RTCIceGatheringState {
         self.internal.ice_gathering_state()
     }
+
+    /// remote_bandwidth_limit returns the bits per second the remote description limits the data
+    /// channels to, None if unlimited
+    pub(crate) fn remote_bandwidth_limit(&self) -> Option<u64> {
+        self.internal.sctp_transport.remote_bandwidth_limit()
+    }
+
+    /// sctp_bad_verification_tags returns how many inbound SCTP packets were dropped for a
+    /// verification tag that does not belong to the association, over all associations
+    pub(crate) fn sctp_bad_verification_tags(&self) -> u64 {
+        self.internal.sctp_transport.bad_verification_tags()
+    }
+
+    /// send_errors returns how many datagrams were dropped or retried for a send error instead
+    /// of failing the connection
+    pub(crate) fn send_errors(&self) -> SendErrorCounts {
+        self.internal.ice_gatherer.send_errors.counts()
+    }
+
+    /// oversized_datagrams returns how many inbound datagrams were dropped for exceeding the
+    /// receive MTU
+    pub(crate) fn oversized_datagrams(&self) -> u64 {
+        self.internal
+            .ice_gatherer
+            .oversized_datagrams
+            .load(Ordering::SeqCst)
+    }
+
+    /// socket_buffer_sizes returns the buffer sizes the OS reports for the ICE sockets
+    pub(crate) fn socket_buffer_sizes(&self) -> SocketBufferSizes {
+        self.internal.ice_gatherer.socket_buffers.effective()
+    }
+
+    /// sctp_traffic returns the SCTP packets and DATA chunks exchanged so far, over all
+    /// associations
+    pub(crate) fn sctp_traffic(&self) -> SctpTraffic {
+        self.internal.sctp_transport.traffic()
+    }
+
+    /// subscribe_transport_events returns a receiver of the retransmissions, abandoned
+    /// messages and congestion window cuts of the SCTP association, see TransportEvents
+    pub(crate) fn subscribe_transport_events(
+        &self,
+        buffer: usize,
+    ) -> broadcast::Receiver<TransportEvent> {
+        self.internal
+            .sctp_transport
+            .subscribe_transport_events(buffer)
+    }
+
+    /// recent_events returns the latest events of the peer connection, oldest first, see
+    /// SettingEngine::set_event_log_size.
+    pub(crate) fn recent_events(&self) -> Vec<ConnectionEvent> {
+        self.internal.event_log.recent_events()
+    }
+
+    /// connect_timings returns when each step of establishing the session was reached, see
+    /// ConnectTimings
+    pub(crate) fn connect_timings(&self) -> ConnectTimings {
+        self.internal.event_log.connect_timings()
+    }
+
+    /// select_candidate_pair makes the candidate pair of the given local and remote candidate
+    /// ids the selected one, overriding nomination. It fails unless the agent is controlling
+    /// and a connectivity check on the pair succeeded.
+    pub(crate) async fn select_candidate_pair(
+        &self,
+        local_id: &str,
+        remote_id: &str,
+    ) -> Result<()> {
+        let agent = self
+            .internal
+            .ice_gatherer
+            .get_agent()
+            .await
+            .ok_or(Error::ErrICEAgentNotExist)?;
+        Ok(agent.select_candidate_pair(local_id, remote_id).await?)
+    }
+
+    /// candidate_stats returns the binding traffic counters of the ICE candidates, without
+    /// their addresses if redact_addresses is set. It is empty before gathering started.
+    pub(crate) async fn candidate_stats(&self, redact_addresses: bool) -> CandidateStatsReport {
+        let ice_gatherer = &self.internal.ice_gatherer;
+        let agent = match ice_gatherer.get_agent().await {
+            Some(agent) => agent,
+            None => return CandidateStatsReport::default(),
+        };
+
+        let (local_records, remote_records) = agent.get_candidate_records().await;
+        let (dropped_remote_candidates, dropped_candidate_pairs) = agent.get_dropped_candidates();
+        let stats = |records: Vec<_>| {
+            records
+                .iter()
+                .map(|record| CandidateStats::new(record, redact_addresses))
+                .collect()
+        };
+        CandidateStatsReport {
+            local_candidates: stats(local_records),
+            remote_candidates: stats(remote_records),
+            no_candidates_gathered: ice_gatherer.no_candidates_gathered.load(Ordering::SeqCst),
+            dropped_remote_candidates: dropped_remote_candidates as u64,
+            dropped_candidate_pairs: dropped_candidate_pairs as u64,
+        }
+    }
+}
+
+impl Drop for RTCPeerConnection {
+    /// drop closes a PeerConnection that was not closed explicitly, so that the tasks and sockets
+    /// of its transports do not outlive it. The close runs in the background on the current
+    /// runtime, if there is none they are left to the process exit.
+    fn drop(&mut self) {
+        if self.internal.is_closed.load(Ordering::SeqCst) {
+            return;
+        }
+
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            let internal = Arc::clone(&self.internal);
+            handle.spawn(async move {
+                if let Err(err) = internal.close().await {
+                    log::warn!("Failed to close dropped PeerConnection: {}", err);
+                }
+            });
+        }
+    }
 }
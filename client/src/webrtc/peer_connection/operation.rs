@@ -6,6 +6,7 @@ use std::sync::Arc;
 use tokio::sync::mpsc;
 
 use crate::webrtc::error::Result;
+use crate::webrtc::util::spawner::Spawner;
 
 /// Operation is a function
 pub(crate) struct Operation(
@@ -29,14 +30,14 @@ pub(crate) struct Operations {
 }
 
 impl Operations {
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(spawner: &Spawner) -> Self {
         let length = Arc::new(AtomicUsize::new(0));
         let (ops_tx, ops_rx) = mpsc::unbounded_channel();
         let (close_tx, close_rx) = mpsc::channel(1);
         let l = Arc::clone(&length);
         let ops_tx = Arc::new(ops_tx);
         let ops_tx2 = Arc::clone(&ops_tx);
-        tokio::spawn(async move {
+        spawner.spawn(async move {
             Operations::start(l, ops_tx, ops_rx, close_rx).await;
         });
 
@@ -1,14 +1,22 @@
+use crate::webrtc::peer_connection::event_log::{
+    ConnectionEventKind, EventLog, DEFAULT_EVENT_LOG_SIZE,
+};
 use crate::webrtc::peer_connection::*;
+use crate::webrtc::sctp::stream::{CloseReason, TransportError};
 use std::sync::atomic::AtomicIsize;
-use tokio::sync::Notify;
+use std::sync::Weak;
 
 pub(crate) struct PeerConnectionInternal {
+    pub(crate) configuration: RTCConfiguration,
+
     /// a value containing the last known greater mid value
     /// we internally generate mids as numbers. Needed since JSEP
     /// requires that when reusing a media section a new unique mid
     /// should be defined (see JSEP 3.4.1).
     pub(crate) greater_mid: AtomicIsize,
-    pub(crate) sdp_origin: Mutex<crate::webrtc::sdp::description::session::Origin>,
+    /// sdp_origin is the origin of the first local description, the later ones repeat it with
+    /// the session version counted up, see update_sdp_origin
+    pub(crate) sdp_origin: Mutex<Option<crate::webrtc::sdp::description::session::Origin>>,
     pub(crate) last_offer: Mutex<String>,
     pub(crate) last_answer: Mutex<String>,
 
@@ -40,16 +48,19 @@ pub(crate) struct PeerConnectionInternal {
     pub(crate) current_remote_description: Arc<Mutex<Option<RTCSessionDescription>>>,
     pub(crate) pending_local_description: Arc<Mutex<Option<RTCSessionDescription>>>,
     pub(crate) pending_remote_description: Arc<Mutex<Option<RTCSessionDescription>>>,
+
+    pub(crate) event_log: Arc<EventLog>,
 }
 
 impl PeerConnectionInternal {
-    pub(crate) async fn new() -> Result<Arc<Self>> {
+    pub(crate) async fn new(api: &API, configuration: RTCConfiguration) -> Result<Arc<Self>> {
         let mut pc = PeerConnectionInternal {
+            configuration,
             greater_mid: AtomicIsize::new(-1),
-            sdp_origin: Mutex::new(Default::default()),
+            sdp_origin: Mutex::new(None),
             last_offer: Mutex::new("".to_owned()),
             last_answer: Mutex::new("".to_owned()),
-            ops: Arc::new(Operations::new()),
+            ops: Arc::new(Operations::new(&api.setting_engine.spawner)),
             is_closed: Arc::new(AtomicBool::new(false)),
             signaling_state: Arc::new(AtomicU8::new(RTCSignalingState::Stable as u8)),
             ice_transport: Arc::new(Default::default()),
@@ -66,19 +77,38 @@ impl PeerConnectionInternal {
             peer_connection_state: Arc::new(AtomicU8::new(RTCPeerConnectionState::New as u8)),
             on_peer_connection_state_change_handler: Arc::new(Default::default()),
             pending_remote_description: Arc::new(Default::default()),
+            event_log: Arc::new(EventLog::new(
+                api.setting_engine
+                    .event_log_size
+                    .unwrap_or(DEFAULT_EVENT_LOG_SIZE),
+            )),
         };
 
         // Create the ice gatherer
-        pc.ice_gatherer = Arc::new(API::new_ice_gatherer()?);
-
-        // Create the ice transport
-        pc.ice_transport = pc.create_ice_transport().await;
+        pc.ice_gatherer = Arc::new(api.new_ice_gatherer(Arc::clone(&pc.event_log))?);
+
+        // Create the ice transport, it is handed the DTLS and SCTP transports once they exist
+        let ice_state_dtls_transport = Arc::new(Mutex::new(None));
+        let ice_failure_sctp_transport = Arc::new(Mutex::new(None));
+        pc.ice_transport = pc
+            .create_ice_transport(
+                api,
+                Arc::clone(&ice_state_dtls_transport),
+                Arc::clone(&ice_failure_sctp_transport),
+            )
+            .await;
 
         // Create the DTLS transport
-        pc.dtls_transport = Arc::new(API::new_dtls_transport(Arc::clone(&pc.ice_transport))?);
+        pc.dtls_transport = Arc::new(
+            api.new_dtls_transport(Arc::clone(&pc.ice_transport), Arc::clone(&pc.event_log))?,
+        );
+        *ice_state_dtls_transport.lock().await = Some(Arc::downgrade(&pc.dtls_transport));
 
         // Create the SCTP transport
-        pc.sctp_transport = Arc::new(API::new_sctp_transport(Arc::clone(&pc.dtls_transport))?);
+        pc.sctp_transport = Arc::new(
+            api.new_sctp_transport(Arc::clone(&pc.dtls_transport), Arc::clone(&pc.event_log))?,
+        );
+        *ice_failure_sctp_transport.lock().await = Some(Arc::downgrade(&pc.sctp_transport));
 
         // Wire up the on datachannel handler
         let on_data_channel_handler = Arc::clone(&pc.on_data_channel_handler);
@@ -94,23 +124,146 @@ impl PeerConnectionInternal {
             }))
             .await;
 
-        Ok(Arc::new(pc))
+        let pc = Arc::new(pc);
+
+        // Bring SCTP up again once the peer restarted its association, if asked to
+        let weak_pc = Arc::downgrade(&pc);
+        let accept_peer_restart = api.setting_engine.sctp_accept_peer_restart;
+        pc.sctp_transport
+            .on_peer_restart(Box::new(move || {
+                let weak_pc2 = Weak::clone(&weak_pc);
+                Box::pin(async move {
+                    if !accept_peer_restart {
+                        log::warn!("SCTP peer restarted, not accepting it");
+                        return;
+                    }
+                    if let Some(pc) = weak_pc2.upgrade() {
+                        pc.restart_sctp().await;
+                    }
+                })
+            }))
+            .await;
+
+        // Fail right away if gathering finds nothing, instead of waiting for checks never sent
+        let weak_pc = Arc::downgrade(&pc);
+        let allow_zero_candidates = api.setting_engine.allow_zero_candidates;
+        {
+            let mut handler = pc.ice_gatherer.on_no_candidates_handler.lock().await;
+            *handler = Some(Box::new(move || {
+                let weak_pc2 = Weak::clone(&weak_pc);
+                Box::pin(async move {
+                    if allow_zero_candidates {
+                        log::warn!("ICE gathering completed without a local candidate, carrying on as zero candidates are allowed");
+                        return;
+                    }
+                    log::warn!("ICE gathering completed without a local candidate");
+                    if let Some(pc) = weak_pc2.upgrade() {
+                        pc.fail(|| Error::ErrNoCandidatesGathered).await;
+                    }
+                })
+            }));
+        }
+
+        Ok(pc)
+    }
+
+    /// fail moves the connection to failed for a reason the transports cannot notice by
+    /// themselves, e.g. a remote description without a data media section. Data channels
+    /// waiting to open are closed and get the error made by err through their on_error handler.
+    pub(crate) async fn fail(&self, err: impl Fn() -> Error) {
+        if self.is_closed.load(Ordering::SeqCst) {
+            return;
+        }
+
+        let data_channels = {
+            let data_channels = self.sctp_transport.data_channels.lock().await;
+            data_channels.clone()
+        };
+        for data_channel in data_channels {
+            if data_channel.ready_state() == RTCDataChannelState::Connecting {
+                data_channel.do_error(err()).await;
+            }
+        }
+
+        if self.peer_connection_state.load(Ordering::SeqCst) == RTCPeerConnectionState::Failed as u8
+        {
+            return;
+        }
+        log::info!(
+            "peer connection state changed: {}",
+            RTCPeerConnectionState::Failed
+        );
+        self.peer_connection_state
+            .store(RTCPeerConnectionState::Failed as u8, Ordering::SeqCst);
+        self.event_log
+            .record(ConnectionEventKind::PeerConnectionState(
+                RTCPeerConnectionState::Failed,
+            ));
+        RTCPeerConnection::do_peer_connection_state_change(
+            &self.on_peer_connection_state_change_handler,
+            RTCPeerConnectionState::Failed,
+        )
+        .await;
+    }
+
+    /// close stops the SCTP, DTLS and ICE transports in that order, see RTCPeerConnection::close.
+    /// It is also what dropping an unclosed RTCPeerConnection runs.
+    pub(crate) async fn close(&self) -> Result<()> {
+        // https://www.w3.org/TR/webrtc/#dom-rtcpeerconnection-close (step #2)
+        if self.is_closed.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        // https://www.w3.org/TR/webrtc/#dom-rtcpeerconnection-close (step #4)
+        self.signaling_state
+            .store(RTCSignalingState::Closed as u8, Ordering::SeqCst);
+
+        // https://www.w3.org/TR/webrtc/#dom-rtcpeerconnection-close (step #7)
+        let data_channels = {
+            let data_channels = self.sctp_transport.data_channels.lock().await;
+            data_channels.clone()
+        };
+        for d in &data_channels {
+            d.set_ready_state(RTCDataChannelState::Closing);
+        }
+
+        // Try closing all transports even if one of them fails, reporting the first error
+        let mut close_errs = vec![];
+        if let Err(err) = self.sctp_transport.stop().await {
+            close_errs.push(err);
+        }
+        for d in &data_channels {
+            d.set_ready_state(RTCDataChannelState::Closed);
+        }
+        if let Err(err) = self.dtls_transport.stop().await {
+            close_errs.push(err);
+        }
+        if let Err(err) = self.ice_transport.stop().await {
+            close_errs.push(err);
+        }
+
+        // https://www.w3.org/TR/webrtc/#dom-rtcpeerconnection-close (step #11)
+        RTCPeerConnection::update_connection_state(
+            &self.on_peer_connection_state_change_handler,
+            &self.is_closed,
+            &self.peer_connection_state,
+            self.ice_connection_state.load(Ordering::SeqCst).into(),
+            self.dtls_transport.state(),
+            &self.event_log,
+        )
+        .await;
+
+        match close_errs.into_iter().next() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
     }
 
     pub(crate) async fn maybe_start_sctp(
         self: &Arc<Self>,
         remote_desc: Arc<RTCSessionDescription>,
     ) -> Result<()> {
-        let dtls_transport = Arc::clone(&self.dtls_transport);
-        let notify = Arc::new(Notify::new());
-
-        // No idea why, but this code here that doesn't do anything is necessary for this app to function ...
-        tokio::spawn(async move {
-            let _holder = Arc::clone(&dtls_transport);
-            notify.notified().await;
-        });
-
-        if let Some(parsed) = &remote_desc.parsed {
+        if let Some(parsed) = remote_desc.parsed.get() {
             if have_application_media_section(parsed) {
                 self.start_sctp().await;
             }
@@ -160,14 +313,19 @@ impl PeerConnectionInternal {
             .fetch_add(opened_dc_count, Ordering::SeqCst);
     }
 
-    pub(crate) async fn remote_description(self: &Arc<Self>) -> Option<RTCSessionDescription> {
-        let pending_remote_description = self.pending_remote_description.lock().await;
-        if pending_remote_description.is_some() {
+    /// remote_description returns the pending remote description, or the current one if there is
+    /// none pending. The two locks are taken one after the other, never together.
+    pub(crate) async fn remote_description(&self) -> Option<RTCSessionDescription> {
+        let pending_remote_description = {
+            let pending_remote_description = self.pending_remote_description.lock().await;
             pending_remote_description.clone()
-        } else {
-            let current_remote_description = self.current_remote_description.lock().await;
-            current_remote_description.clone()
+        };
+        if pending_remote_description.is_some() {
+            return pending_remote_description;
         }
+
+        let current_remote_description = self.current_remote_description.lock().await;
+        current_remote_description.clone()
     }
 
     /// Start all transports. PeerConnection now has enough state
@@ -213,6 +371,7 @@ impl PeerConnectionInternal {
             &self.peer_connection_state,
             self.ice_connection_state.load(Ordering::SeqCst).into(),
             self.dtls_transport.state(),
+            &self.event_log,
         )
         .await;
         if let Err(err) = result {
@@ -220,32 +379,105 @@ impl PeerConnectionInternal {
         }
     }
 
+    /// restart_sctp brings up a new SCTP association over the current DTLS connection after the
+    /// peer restarted its end. The channels opened here are opened anew on it, the ones the peer
+    /// opened stay closed.
+    pub(crate) async fn restart_sctp(&self) {
+        if self.is_closed.load(Ordering::SeqCst) {
+            return;
+        }
+        log::debug!("SCTP peer restarted, starting a new association");
+        self.sctp_transport.reset().await;
+
+        let data_channels = {
+            let data_channels = self.sctp_transport.data_channels.lock().await;
+            data_channels.clone()
+        };
+        for d in data_channels {
+            if matches!(
+                d.close_reason(),
+                Some(CloseReason::TransportFailure(TransportError::PeerRestart))
+            ) {
+                d.reopen().await;
+            }
+        }
+
+        self.start_sctp().await;
+    }
+
+    /// restart_dtls handshakes DTLS again with a remote that answered an ICE restart with
+    /// another certificate. The SCTP association of the old DTLS connection cannot be carried
+    /// over, its channels are closed with TransportError::RemoteCertificateChanged and a new
+    /// association is started once the handshake completed. Channels are then opened anew.
+    pub(crate) async fn restart_dtls(
+        self: &Arc<Self>,
+        dtls_role: DTLSRole,
+        fingerprint: String,
+        fingerprint_hash: String,
+        remote_desc: Arc<RTCSessionDescription>,
+    ) {
+        if let Some(association) = self.sctp_transport.association().await {
+            let reason = CloseReason::TransportFailure(TransportError::RemoteCertificateChanged);
+            if let Err(err) = association.close_with_reason(reason).await {
+                log::warn!("Failed to close SCTP association: {}", err);
+            }
+        }
+        self.sctp_transport.reset().await;
+
+        let result = self
+            .dtls_transport
+            .restart(DTLSParameters {
+                role: dtls_role,
+                fingerprints: vec![RTCDtlsFingerprint {
+                    algorithm: fingerprint_hash,
+                    value: fingerprint,
+                }],
+            })
+            .await;
+        RTCPeerConnection::update_connection_state(
+            &self.on_peer_connection_state_change_handler,
+            &self.is_closed,
+            &self.peer_connection_state,
+            self.ice_connection_state.load(Ordering::SeqCst).into(),
+            self.dtls_transport.state(),
+            &self.event_log,
+        )
+        .await;
+        match result {
+            Ok(()) => {
+                let _ = self.maybe_start_sctp(remote_desc).await;
+            }
+            Err(err) => log::warn!("Failed to restart dtls: {}", err),
+        }
+    }
+
     /// generate_unmatched_sdp generates an SDP that doesn't take remote state into account
     /// This is used for the initial call for CreateOffer
     pub(crate) async fn generate_unmatched_sdp(
         &self,
         use_identity: bool,
     ) -> Result<SessionDescription> {
-        let d = SessionDescription::new_jsep_session_description(use_identity);
+        let setting_engine = &self.ice_gatherer.setting_engine;
+        let d = SessionDescription::new_jsep_session_description(
+            use_identity,
+            &setting_engine.rng,
+            setting_engine.now(),
+        );
 
         let ice_params = self.ice_gatherer.get_local_parameters().await?;
 
+        // the gathering state is read before the candidates, so that an offer announcing
+        // end-of-candidates never misses one gathered in between
+        let ice_gathering_state = self.ice_gathering_state();
         let candidates = self.ice_gatherer.get_local_candidates().await?;
 
-        let mut media_sections = vec![];
-
-        if self
-            .sctp_transport
-            .data_channels_requested
-            .load(Ordering::SeqCst)
-            != 0
-        {
-            media_sections.push(MediaSection {
-                id: format!("{}", media_sections.len()),
-                data: true,
-                ..Default::default()
-            });
-        }
+        // the application section is offered even without a data channel, so that the SCTP
+        // association comes up on connect and channels can be opened later through DCEP alone
+        let media_sections = vec![MediaSection {
+            id: "0".to_owned(),
+            data: true,
+            ..Default::default()
+        }];
 
         let dtls_fingerprints = if let Some(cert) = self.dtls_transport.certificates.first() {
             cert.get_fingerprints()?
@@ -256,9 +488,9 @@ impl PeerConnectionInternal {
         let params = PopulateSdpParams {
             is_icelite: false,
             connection_role: DEFAULT_DTLS_ROLE_OFFER.to_connection_role(),
-            ice_gathering_state: self.ice_gathering_state(),
+            ice_gathering_state,
         };
-        populate_sdp(
+        let d = populate_sdp(
             d,
             &dtls_fingerprints,
             &candidates,
@@ -266,7 +498,9 @@ impl PeerConnectionInternal {
             &media_sections,
             params,
         )
-        .await
+        .await?;
+
+        self.configuration.sdp_modifiers.apply(d)
     }
 
     /// generate_matched_sdp generates a SDP and takes the remote state into account
@@ -277,45 +511,38 @@ impl PeerConnectionInternal {
         include_unmatched: bool,
         connection_role: ConnectionRole,
     ) -> Result<SessionDescription> {
-        let d = SessionDescription::new_jsep_session_description(use_identity);
+        let setting_engine = &self.ice_gatherer.setting_engine;
+        let d = SessionDescription::new_jsep_session_description(
+            use_identity,
+            &setting_engine.rng,
+            setting_engine.now(),
+        );
 
         let ice_params = self.ice_gatherer.get_local_parameters().await?;
+        // see generate_unmatched_sdp
+        let ice_gathering_state = self.ice_gathering_state();
         let candidates = self.ice_gatherer.get_local_candidates().await?;
 
-        let remote_description = {
-            let pending_remote_description = self.pending_remote_description.lock().await;
-            if pending_remote_description.is_some() {
-                pending_remote_description.clone()
-            } else {
-                let current_remote_description = self.current_remote_description.lock().await;
-                current_remote_description.clone()
-            }
-        };
+        let remote_description = self.remote_description().await;
 
         let detected_plan_b = description_is_plan_b(remote_description.as_ref())?;
         let mut media_sections = vec![];
 
-        // If we are offering also include unmatched local transceivers
+        // If we are offering also include unmatched local transceivers, the application section
+        // is always offered, see generate_unmatched_sdp
         if include_unmatched {
-            if self
-                .sctp_transport
-                .data_channels_requested
-                .load(Ordering::SeqCst)
-                != 0
-            {
-                if detected_plan_b {
-                    media_sections.push(MediaSection {
-                        id: "data".to_owned(),
-                        data: true,
-                        ..Default::default()
-                    });
-                } else {
-                    media_sections.push(MediaSection {
-                        id: format!("{}", media_sections.len()),
-                        data: true,
-                        ..Default::default()
-                    });
-                }
+            if detected_plan_b {
+                media_sections.push(MediaSection {
+                    id: "data".to_owned(),
+                    data: true,
+                    ..Default::default()
+                });
+            } else {
+                media_sections.push(MediaSection {
+                    id: format!("{}", media_sections.len()),
+                    data: true,
+                    ..Default::default()
+                });
             }
         }
 
@@ -328,9 +555,9 @@ impl PeerConnectionInternal {
         let params = PopulateSdpParams {
             is_icelite: false,
             connection_role,
-            ice_gathering_state: self.ice_gathering_state(),
+            ice_gathering_state,
         };
-        populate_sdp(
+        let d = populate_sdp(
             d,
             &dtls_fingerprints,
             &candidates,
@@ -338,7 +565,9 @@ impl PeerConnectionInternal {
             &media_sections,
             params,
         )
-        .await
+        .await?;
+
+        self.configuration.sdp_modifiers.apply(d)
     }
 
     pub(crate) fn ice_gathering_state(&self) -> RTCIceGatheringState {
@@ -349,17 +578,26 @@ impl PeerConnectionInternal {
         }
     }
 
-    pub(crate) async fn create_ice_transport(&self) -> Arc<RTCIceTransport> {
-        let ice_transport = Arc::new(API::new_ice_transport(Arc::clone(&self.ice_gatherer)));
+    /// create_ice_transport creates the ICE transport and follows its state, along with the
+    /// state of the DTLS transport found in dtls_transport for the peer connection state. When
+    /// ICE fails the SCTP association found in sctp_transport is closed, so its data channels
+    /// learn about it.
+    pub(crate) async fn create_ice_transport(
+        &self,
+        api: &API,
+        dtls_transport: Arc<Mutex<Option<Weak<RTCDtlsTransport>>>>,
+        sctp_transport: Arc<Mutex<Option<Weak<RTCSctpTransport>>>>,
+    ) -> Arc<RTCIceTransport> {
+        let ice_transport = Arc::new(api.new_ice_transport(Arc::clone(&self.ice_gatherer)));
 
         let ice_connection_state = Arc::clone(&self.ice_connection_state);
         let peer_connection_state = Arc::clone(&self.peer_connection_state);
         let is_closed = Arc::clone(&self.is_closed);
-        let dtls_transport = Arc::clone(&self.dtls_transport);
         let on_ice_connection_state_change_handler =
             Arc::clone(&self.on_ice_connection_state_change_handler);
         let on_peer_connection_state_change_handler =
             Arc::clone(&self.on_peer_connection_state_change_handler);
+        let event_log = Arc::clone(&self.event_log);
 
         ice_transport
             .on_connection_state_change(Box::new(move |state: RTCIceTransportState| {
@@ -383,9 +621,26 @@ impl PeerConnectionInternal {
                 let on_peer_connection_state_change_handler2 =
                     Arc::clone(&on_peer_connection_state_change_handler);
                 let is_closed2 = Arc::clone(&is_closed);
-                let dtls_transport_state = dtls_transport.state();
+                let dtls_transport2 = Arc::clone(&dtls_transport);
                 let peer_connection_state2 = Arc::clone(&peer_connection_state);
+                let sctp_transport2 = Arc::clone(&sctp_transport);
+                let event_log2 = Arc::clone(&event_log);
                 Box::pin(async move {
+                    event_log2.record(ConnectionEventKind::IceConnectionState(cs));
+
+                    if cs == RTCIceConnectionState::Failed {
+                        let sctp_transport = sctp_transport2.lock().await.clone();
+                        if let Some(sctp_transport) = sctp_transport.and_then(|st| st.upgrade()) {
+                            if let Some(association) = sctp_transport.association().await {
+                                let reason =
+                                    CloseReason::TransportFailure(TransportError::IceFailed);
+                                if let Err(err) = association.close_with_reason(reason).await {
+                                    log::warn!("Failed to close SCTP association: {}", err);
+                                }
+                            }
+                        }
+                    }
+
                     RTCPeerConnection::do_ice_connection_state_change(
                         &on_ice_connection_state_change_handler2,
                         &ice_connection_state2,
@@ -393,12 +648,18 @@ impl PeerConnectionInternal {
                     )
                     .await;
 
+                    let dtls_transport = dtls_transport2.lock().await.clone();
+                    let dtls_transport_state = dtls_transport
+                        .and_then(|dt| dt.upgrade())
+                        .map_or(RTCDtlsTransportState::New, |dt| dt.state());
+
                     RTCPeerConnection::update_connection_state(
                         &on_peer_connection_state_change_handler2,
                         &is_closed2,
                         &peer_connection_state2,
                         cs,
                         dtls_transport_state,
+                        &event_log2,
                     )
                     .await;
                 })
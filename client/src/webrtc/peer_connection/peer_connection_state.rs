@@ -1,8 +1,9 @@
+use serde::Serialize;
 use std::fmt;
 
 /// PeerConnectionState indicates the state of the PeerConnection.
-#[derive(Debug, Copy, Clone, PartialEq)]
-pub(crate) enum RTCPeerConnectionState {
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+pub enum RTCPeerConnectionState {
     Unspecified,
 
     /// PeerConnectionStateNew indicates that any of the ICETransports or
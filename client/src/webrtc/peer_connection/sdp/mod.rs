@@ -1,28 +1,38 @@
 use crate::webrtc::dtls_transport::dtls_fingerprint::RTCDtlsFingerprint;
 use crate::webrtc::error::{Error, Result};
-use crate::webrtc::ice_transport::ice_candidate::RTCIceCandidate;
+use crate::webrtc::ice_transport::ice_candidate::{RTCIceCandidate, RTCIceCandidateInit};
 use crate::webrtc::ice_transport::ice_gatherer::RTCIceGatherer;
 use crate::webrtc::ice_transport::ice_gathering_state::RTCIceGatheringState;
 use crate::webrtc::ice_transport::ice_parameters::RTCIceParameters;
 
+pub(crate) mod sdp_modifiers;
 pub(crate) mod sdp_type;
 pub(crate) mod session_description;
 
 use crate::webrtc::ice::candidate::candidate_base::unmarshal_candidate;
 use crate::webrtc::ice::candidate::Candidate;
-use crate::webrtc::peer_connection::MEDIA_SECTION_APPLICATION;
-use crate::webrtc::sdp::description::common::{Address, ConnectionInformation};
+use crate::webrtc::peer_connection::{MEDIA_FORMAT_DATACHANNEL, MEDIA_SECTION_APPLICATION};
+use crate::webrtc::sctp::association::DEFAULT_SCTP_PORT;
+use crate::webrtc::sdp::description::common::{Address, Bandwidth, ConnectionInformation};
 use crate::webrtc::sdp::description::media::{MediaDescription, MediaName, RangedPort};
 use crate::webrtc::sdp::description::session::*;
 use crate::webrtc::sdp::util::ConnectionRole;
+use crate::webrtc::util::redaction::redact_candidate;
 use std::convert::From;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
+
+/// ICE_OPTIONS are the ICE options the agent supports, advertised with `a=ice-options`: RFC 8445
+/// processing and following renomination (draft-thatcher-ice-renomination)
+const ICE_OPTIONS: &str = "ice2 renomination";
 
 /// TrackDetails represents any media source that can be represented in a SDP
 /// This isn't keyed by SSRC because it also needs to support rid based sources
 #[derive(Default, Debug, Clone)]
 pub(crate) struct TrackDetails;
 
+/// add_candidates_to_media_descriptions writes the candidate lines of m, highest priority first,
+/// and closes them with end-of-candidates once gathering is complete. Lines m already has are
+/// replaced, so adding late candidates to a description keeps that order.
 pub(crate) async fn add_candidates_to_media_descriptions(
     candidates: &[RTCIceCandidate],
     mut m: MediaDescription,
@@ -41,24 +51,23 @@ pub(crate) async fn add_candidates_to_media_descriptions(
         m.with_value_attribute("candidate".to_owned(), marshaled)
     };
 
-    for c in candidates {
-        let candidate = c.to_ice().await?;
+    m.attributes
+        .retain(|a| a.key != "candidate" && a.key != "end-of-candidates");
 
-        candidate.set_component(1);
-        m = append_candidate_if_new(&candidate, m);
+    // the sort is stable, candidates of equal priority stay in gathering order
+    let mut candidates: Vec<&RTCIceCandidate> = candidates.iter().collect();
+    candidates.sort_by_key(|c| std::cmp::Reverse(c.priority));
 
-        candidate.set_component(2);
+    // written as the agent has them: the data channel is the only, rtcp-muxed component, so
+    // the component is always 1, and foundation and priority are the ones used in checks
+    for c in candidates {
+        let candidate = c.to_ice().await?;
         m = append_candidate_if_new(&candidate, m);
     }
 
     if ice_gathering_state != RTCIceGatheringState::Complete {
         return Ok(m);
     }
-    for a in &m.attributes {
-        if &a.key == "end-of-candidates" {
-            return Ok(m);
-        }
-    }
 
     Ok(m.with_property_attribute("end-of-candidates".to_owned()))
 }
@@ -85,7 +94,7 @@ pub(crate) async fn add_data_media_section(
                 range: None,
             },
             protos: vec!["UDP".to_owned(), "DTLS".to_owned(), "SCTP".to_owned()],
-            formats: vec!["webrtc-datachannel".to_owned()],
+            formats: vec![MEDIA_FORMAT_DATACHANNEL.to_owned()],
         },
         media_title: None,
         connection_information: Some(ConnectionInformation {
@@ -107,7 +116,7 @@ pub(crate) async fn add_data_media_section(
     )
     .with_value_attribute(ATTR_KEY_MID.to_owned(), params.mid_value)
     .with_property_attribute("sendrecv".to_owned())
-    .with_property_attribute("sctp-port:5000".to_owned())
+    .with_property_attribute(format!("{}:{}", ATTR_KEY_SCTP_PORT, DEFAULT_SCTP_PORT))
     .with_ice_credentials(
         params.ice_params.username_fragment,
         params.ice_params.password,
@@ -159,7 +168,7 @@ pub(crate) async fn populate_local_candidates(
         Some(session_description::RTCSessionDescription {
             sdp_type: sd.sdp_type,
             sdp: parsed.marshal(),
-            parsed: Some(parsed),
+            parsed: OnceLock::from(parsed),
         })
     } else {
         None
@@ -230,6 +239,8 @@ pub(crate) async fn populate_sdp(
         d = d.with_value_attribute(ATTR_KEY_ICELITE.to_owned(), ATTR_KEY_ICELITE.to_owned());
     }
 
+    d = d.with_value_attribute(ATTR_KEY_ICE_OPTIONS.to_owned(), ICE_OPTIONS.to_owned());
+
     Ok(d.with_value_attribute(ATTR_KEY_GROUP.to_owned(), bundle_value))
 }
 
@@ -242,11 +253,50 @@ pub(crate) fn get_mid_value(media: &MediaDescription) -> Option<&String> {
     None
 }
 
+/// candidate_media_description returns the media section of desc a candidate belongs to, by its
+/// sdp_mid or else its sdp_mline_index. Without either it is the only media section, None if
+/// there is none.
+pub(crate) fn candidate_media_description<'a>(
+    desc: &'a SessionDescription,
+    candidate: &RTCIceCandidateInit,
+) -> Result<Option<&'a MediaDescription>> {
+    if let Some(mid) = &candidate.sdp_mid {
+        return desc
+            .media_descriptions
+            .iter()
+            .find(|m| get_mid_value(m) == Some(mid))
+            .map(Some)
+            .ok_or_else(|| Error::ErrInvalidSdpMid(mid.clone()));
+    }
+    if let Some(index) = candidate.sdp_mline_index {
+        return desc
+            .media_descriptions
+            .get(index as usize)
+            .map(Some)
+            .ok_or(Error::ErrInvalidSdpMLineIndex(index));
+    }
+    if desc.media_descriptions.len() > 1 {
+        return Err(Error::ErrSdpMidAndMLineIndexMissing);
+    }
+    Ok(desc.media_descriptions.first())
+}
+
+/// ice_ufrag returns the ICE username fragment of a media section, or of the session if the
+/// section has none
+pub(crate) fn ice_ufrag<'a>(
+    desc: &'a SessionDescription,
+    media: Option<&'a MediaDescription>,
+) -> Option<&'a str> {
+    media
+        .and_then(|m| m.attribute("ice-ufrag").and_then(|o| o))
+        .or_else(|| desc.attribute("ice-ufrag").map(String::as_str))
+}
+
 pub(crate) fn description_is_plan_b(
     desc: Option<&session_description::RTCSessionDescription>,
 ) -> Result<bool> {
     if let Some(desc) = desc {
-        if let Some(parsed) = &desc.parsed {
+        if let Some(parsed) = desc.parsed.get() {
             let detection_regex = regex::Regex::new(r"(?i)^(audio|video|data)$").unwrap();
             for media in &parsed.media_descriptions {
                 if let Some(s) = get_mid_value(media) {
@@ -315,11 +365,24 @@ pub(crate) async fn extract_ice_details(
             remote_pwds.push(pwd.to_owned());
         }
 
+        // inline candidates are fed to the agent like trickled ones, which drops duplicates.
+        // One that cannot be parsed, e.g. of an unsupported transport, is skipped rather than
+        // failing the whole description
         for a in &m.attributes {
             if a.is_ice_candidate() {
                 if let Some(value) = &a.value {
-                    let c: Arc<dyn Candidate + Send + Sync> =
-                        Arc::new(unmarshal_candidate(value).await?);
+                    let c: Arc<dyn Candidate + Send + Sync> = match unmarshal_candidate(value).await
+                    {
+                        Ok(c) => Arc::new(c),
+                        Err(err) => {
+                            log::warn!(
+                                "skipping remote candidate {}: {}",
+                                redact_candidate(value),
+                                err
+                            );
+                            continue;
+                        }
+                    };
                     let candidate = RTCIceCandidate::from(&c);
                     candidates.push(candidate);
                 }
@@ -348,6 +411,120 @@ pub(crate) async fn extract_ice_details(
     Ok((remote_ufrags[0].clone(), remote_pwds[0].clone(), candidates))
 }
 
+/// Returns true if the session description advertises an ICE lite implementation.
+/// RFC 8445 S5.3
+pub(crate) fn is_lite_set(desc: &SessionDescription) -> bool {
+    desc.attributes.iter().any(|a| a.key == ATTR_KEY_ICELITE)
+}
+
+/// extract_ice_options returns the options of the `a=ice-options` attributes at session and
+/// media level, each once in the order they appear (RFC 8839 S5.6)
+pub(crate) fn extract_ice_options(desc: &SessionDescription) -> Vec<String> {
+    let mut options: Vec<String> = vec![];
+    for option in desc
+        .attributes
+        .iter()
+        .chain(desc.media_descriptions.iter().flat_map(|m| &m.attributes))
+        .filter(|a| a.key == ATTR_KEY_ICE_OPTIONS)
+        .filter_map(|a| a.value.as_deref())
+        .flat_map(str::split_whitespace)
+    {
+        if !options.iter().any(|o| o == option) {
+            options.push(option.to_owned());
+        }
+    }
+    options
+}
+
+/// is_trickle_set reports whether the description announces trickle ICE, through
+/// `a=ice-options:trickle` at session or media level (RFC 8840).
+#[cfg(feature = "network-monitor")]
+pub(crate) fn is_trickle_set(desc: &SessionDescription) -> bool {
+    extract_ice_options(desc)
+        .iter()
+        .any(|option| option == "trickle")
+}
+
+/// remote_bandwidth_limit returns the bits per second desc limits the data channels to, from a
+/// b=TIAS (bits per second, RFC 3890) or b=AS (kilobits per second) line of the m=application
+/// section, or else of the session. TIAS wins over AS at the same level. None means unlimited,
+/// as does a zero value.
+pub(crate) fn remote_bandwidth_limit(desc: &SessionDescription) -> Option<u64> {
+    fn level_limit(bandwidth: &[Bandwidth]) -> Option<u64> {
+        let find = |bandwidth_type: &str| {
+            bandwidth
+                .iter()
+                .find(|b| !b.experimental && b.bandwidth_type == bandwidth_type)
+        };
+        find("TIAS")
+            .map(|b| b.bandwidth)
+            .or_else(|| find("AS").map(|b| b.bandwidth.saturating_mul(1000)))
+    }
+
+    desc.media_descriptions
+        .iter()
+        .find(|m| m.media_name.media == MEDIA_SECTION_APPLICATION)
+        .and_then(|m| level_limit(&m.bandwidth))
+        .or_else(|| level_limit(&desc.bandwidth))
+        .filter(|&limit| limit != 0)
+}
+
+/// RemoteSctpParams are the SCTP parameters the m=application section of a remote description
+/// announces
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct RemoteSctpParams {
+    /// port is the SCTP port of the remote
+    pub(crate) port: u16,
+    /// max_streams is the number of streams an a=sctpmap offers, None if it does not say
+    pub(crate) max_streams: Option<u16>,
+    /// legacy is set if the parameters came from the older `DTLS/SCTP <port>` dialect with
+    /// a=sctpmap (draft-ietf-mmusic-sctp-sdp-05) instead of a=sctp-port
+    pub(crate) legacy: bool,
+}
+
+/// remote_sctp_params returns the SCTP parameters of the m=application section of desc, from
+/// a=sctp-port (RFC 8841) or else `a=sctpmap:<port> webrtc-datachannel <streams>`. A section
+/// with both is read as the modern one. None if it has neither.
+pub(crate) fn remote_sctp_params(desc: &SessionDescription) -> Option<RemoteSctpParams> {
+    let media = desc
+        .media_descriptions
+        .iter()
+        .find(|m| m.media_name.media == MEDIA_SECTION_APPLICATION)?;
+
+    if let Some(Some(port)) = media.attribute(ATTR_KEY_SCTP_PORT) {
+        match port.trim().parse() {
+            Ok(port) => {
+                return Some(RemoteSctpParams {
+                    port,
+                    max_streams: None,
+                    legacy: false,
+                })
+            }
+            Err(_) => log::warn!("ignoring malformed a=sctp-port:{}", port),
+        }
+    }
+
+    let sctpmap = match media.attribute(ATTR_KEY_SCTPMAP) {
+        Some(Some(sctpmap)) => sctpmap,
+        _ => return None,
+    };
+    let mut fields = sctpmap.split_whitespace();
+    let port = fields.next().and_then(|port| port.parse().ok());
+    let protocol = fields.next();
+    let max_streams = fields.next().and_then(|streams| streams.parse().ok());
+    match (port, protocol) {
+        (Some(port), Some(MEDIA_FORMAT_DATACHANNEL)) => Some(RemoteSctpParams {
+            port,
+            max_streams,
+            legacy: true,
+        }),
+        _ => {
+            log::warn!("ignoring a=sctpmap:{}", sctpmap);
+            None
+        }
+    }
+}
+
 pub(crate) fn have_application_media_section(desc: &SessionDescription) -> bool {
     for m in &desc.media_descriptions {
         if m.media_name.media == MEDIA_SECTION_APPLICATION {
@@ -358,29 +535,141 @@ pub(crate) fn have_application_media_section(desc: &SessionDescription) -> bool
     false
 }
 
-/// update_sdp_origin saves sdp.Origin in PeerConnection when creating 1st local SDP;
-/// for subsequent calling, it updates Origin for SessionDescription from saved one
-/// and increments session version by one.
-/// <https://tools.ietf.org/html/draft-ietf-rtcweb-jsep-25#section-5.2.2>
-pub(crate) fn update_sdp_origin(origin: &mut Origin, d: &mut SessionDescription) {
-    //TODO: if atomic.CompareAndSwapUint64(&origin.SessionVersion, 0, d.Origin.SessionVersion)
-    if origin.session_version == 0 {
-        // store
-        origin.session_version = d.origin.session_version;
-        //atomic.StoreUint64(&origin.SessionID, d.Origin.SessionID)
-        origin.session_id = d.origin.session_id;
-    } else {
-        // load
-        /*for { // awaiting for saving session id
-            d.Origin.SessionID = atomic.LoadUint64(&origin.SessionID)
-            if d.Origin.SessionID != 0 {
-                break
+/// validate_data_media_section checks that desc has an m=application section that was not
+/// rejected with port 0 and that carries SCTP, announced by a=sctp-port or, in the older
+/// format, a webrtc-datachannel sctpmap
+pub(crate) fn validate_data_media_section(desc: &SessionDescription) -> Result<()> {
+    let media = match desc
+        .media_descriptions
+        .iter()
+        .find(|m| m.media_name.media == MEDIA_SECTION_APPLICATION)
+    {
+        Some(media) => media,
+        None => {
+            return Err(Error::ErrNoDataMediaSection(
+                "no m=application section".to_owned(),
+            ))
+        }
+    };
+
+    if media.media_name.port.value == 0 {
+        return Err(Error::ErrNoDataMediaSection(
+            "m=application section rejected with port 0".to_owned(),
+        ));
+    }
+
+    let has_sctp_port = media.attribute(ATTR_KEY_SCTP_PORT).is_some();
+    let has_datachannel_format = media
+        .media_name
+        .formats
+        .iter()
+        .any(|format| format == MEDIA_FORMAT_DATACHANNEL)
+        || matches!(
+            media.attribute(ATTR_KEY_SCTPMAP),
+            Some(Some(sctpmap)) if sctpmap.contains(MEDIA_FORMAT_DATACHANNEL)
+        );
+    if !has_sctp_port && !has_datachannel_format {
+        return Err(Error::ErrNoDataMediaSection(format!(
+            "m=application section has neither a=sctp-port nor the {} format",
+            MEDIA_FORMAT_DATACHANNEL
+        )));
+    }
+
+    Ok(())
+}
+
+/// validate_answer checks that answer responds to offer: it must have a media section with the
+/// same mid for each offered one. When strict, its BUNDLE group may only name mids the offer
+/// bundled and its media sections must agree on a=setup:active or a=setup:passive.
+pub(crate) fn validate_answer(
+    offer: &SessionDescription,
+    answer: &SessionDescription,
+    strict: bool,
+) -> Result<()> {
+    if offer.media_descriptions.len() != answer.media_descriptions.len() {
+        return Err(Error::ErrSdpMediaSectionCountMismatch {
+            offered: offer.media_descriptions.len(),
+            answered: answer.media_descriptions.len(),
+        });
+    }
+
+    let media_sections = offer
+        .media_descriptions
+        .iter()
+        .zip(&answer.media_descriptions);
+    for (index, (offered, answered)) in media_sections.enumerate() {
+        let offered_mid = get_mid_value(offered);
+        let answered_mid = get_mid_value(answered);
+        if offered_mid != answered_mid {
+            return Err(Error::ErrSdpMidMismatch {
+                index,
+                offered: offered_mid.cloned().unwrap_or_default(),
+                answered: answered_mid.cloned().unwrap_or_default(),
+            });
+        }
+    }
+
+    if !strict {
+        return Ok(());
+    }
+
+    if let Some(answered_bundle) = bundle_group(answer) {
+        let offered_bundle = bundle_group(offer).unwrap_or_default();
+        if let Some(mid) = answered_bundle
+            .into_iter()
+            .find(|mid| !offered_bundle.contains(mid))
+        {
+            return Err(Error::ErrSdpBundleGroupMismatch(mid.to_owned()));
+        }
+    }
+
+    // a section without a=setup is active (RFC 4145 section 4)
+    let mut answered_setup = None;
+    for media in &answer.media_descriptions {
+        let setup = match media.attribute(ATTR_KEY_CONNECTION_SETUP) {
+            Some(Some(setup)) => setup,
+            _ => continue,
+        };
+        if setup != ConnectionRole::Active.to_string()
+            && setup != ConnectionRole::Passive.to_string()
+        {
+            return Err(Error::ErrSdpIllegalSetupInAnswer(setup.to_owned()));
+        }
+        if *answered_setup.get_or_insert(setup) != setup {
+            return Err(Error::ErrSdpConflictingSetup);
+        }
+    }
+
+    Ok(())
+}
+
+/// bundle_group returns the mids of the BUNDLE group of desc
+fn bundle_group(desc: &SessionDescription) -> Option<Vec<&str>> {
+    desc.attributes
+        .iter()
+        .filter(|a| a.key == ATTR_KEY_GROUP)
+        .filter_map(|a| a.value.as_deref())
+        .find_map(|group| {
+            let mut tokens = group.split_whitespace();
+            if tokens.next() == Some("BUNDLE") {
+                Some(tokens.collect())
+            } else {
+                None
             }
-        }*/
-        d.origin.session_id = origin.session_id;
+        })
+}
 
-        //d.Origin.SessionVersion = atomic.AddUint64(&origin.SessionVersion, 1)
-        origin.session_version += 1;
-        d.origin.session_version += 1;
+/// update_sdp_origin saves the origin of the first local description of a PeerConnection.
+/// Every later description gets the saved origin with the session version one above the one
+/// before, so the remote sees the same session id across renegotiations.
+/// <https://tools.ietf.org/html/rfc3264#section-8>
+/// <https://tools.ietf.org/html/draft-ietf-rtcweb-jsep-25#section-5.2.2>
+pub(crate) fn update_sdp_origin(origin: &mut Option<Origin>, d: &mut SessionDescription) {
+    match origin {
+        Some(origin) => {
+            origin.session_version = origin.session_version.wrapping_add(1);
+            d.origin = origin.clone();
+        }
+        None => *origin = Some(d.origin.clone()),
     }
 }
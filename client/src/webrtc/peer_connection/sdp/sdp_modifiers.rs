@@ -0,0 +1,184 @@
+use crate::webrtc::error::{Error, Result};
+use crate::webrtc::peer_connection::MEDIA_SECTION_APPLICATION;
+use crate::webrtc::sdp::description::common::Attribute;
+use crate::webrtc::sdp::description::session::SessionDescription;
+
+const ATTR_KEY_MSID_SEMANTIC: &str = "msid-semantic";
+
+/// SdpModifiers describes additions applied to every offer and answer generated by the
+/// PeerConnection. They are applied through the SessionDescription builder after the
+/// description has been populated, so any key that collides with a generated attribute
+/// (or with another modifier) is rejected with ErrSDPModifierDuplicateAttribute.
+#[derive(Default, Debug, Clone)]
+pub struct SdpModifiers {
+    session_name: Option<String>,
+    session_attributes: Vec<(String, Option<String>)>,
+    media_attributes: Vec<(String, Option<String>)>,
+}
+
+impl SdpModifiers {
+    pub fn new() -> Self {
+        SdpModifiers::default()
+    }
+
+    /// with_session_name replaces the default "s=-" session name
+    pub fn with_session_name(mut self, session_name: String) -> Self {
+        self.session_name = Some(session_name);
+        self
+    }
+
+    /// with_session_property_attribute adds a session-level 'a=key' attribute
+    pub fn with_session_property_attribute(mut self, key: String) -> Self {
+        self.session_attributes.push((key, None));
+        self
+    }
+
+    /// with_session_value_attribute adds a session-level 'a=key:value' attribute
+    pub fn with_session_value_attribute(mut self, key: String, value: String) -> Self {
+        self.session_attributes.push((key, Some(value)));
+        self
+    }
+
+    /// with_msid_semantic adds the session-level 'a=msid-semantic:<semantic>' attribute of
+    /// RFC 8830, e.g. "WMS"
+    pub fn with_msid_semantic(self, semantic: String) -> Self {
+        self.with_session_value_attribute(ATTR_KEY_MSID_SEMANTIC.to_owned(), semantic)
+    }
+
+    /// with_media_property_attribute adds an 'a=key' attribute to the application media section
+    pub fn with_media_property_attribute(mut self, key: String) -> Self {
+        self.media_attributes.push((key, None));
+        self
+    }
+
+    /// with_media_value_attribute adds an 'a=key:value' attribute to the application media
+    /// section
+    pub fn with_media_value_attribute(mut self, key: String, value: String) -> Self {
+        self.media_attributes.push((key, Some(value)));
+        self
+    }
+
+    /// apply injects the modifiers into a generated SessionDescription
+    pub(crate) fn apply(&self, mut d: SessionDescription) -> Result<SessionDescription> {
+        if let Some(session_name) = &self.session_name {
+            d.session_name = session_name.clone();
+        }
+
+        check_duplicates(&d.attributes, &self.session_attributes)?;
+        for (key, value) in &self.session_attributes {
+            d = match value {
+                Some(value) => d.with_value_attribute(key.clone(), value.clone()),
+                None => d.with_property_attribute(key.clone()),
+            };
+        }
+
+        for media in &mut d.media_descriptions {
+            if media.media_name.media != MEDIA_SECTION_APPLICATION {
+                continue;
+            }
+
+            check_duplicates(&media.attributes, &self.media_attributes)?;
+            for (key, value) in &self.media_attributes {
+                media
+                    .attributes
+                    .push(Attribute::new(key.clone(), value.clone()));
+            }
+        }
+
+        Ok(d)
+    }
+}
+
+fn check_duplicates(existing: &[Attribute], added: &[(String, Option<String>)]) -> Result<()> {
+    for (i, (key, _)) in added.iter().enumerate() {
+        if existing.iter().any(|a| &a.key == key) || added[..i].iter().any(|(k, _)| k == key) {
+            return Err(Error::ErrSDPModifierDuplicateAttribute(key.clone()));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io::Cursor;
+
+    const GENERATED: &str = "v=0\r\n\
+        o=- 4215775240449105457 2 IN IP4 0.0.0.0\r\n\
+        s=-\r\n\
+        t=0 0\r\n\
+        a=group:BUNDLE 0\r\n\
+        m=application 9 UDP/DTLS/SCTP webrtc-datachannel\r\n\
+        c=IN IP4 0.0.0.0\r\n\
+        a=mid:0\r\n\
+        a=sctp-port:5000\r\n";
+
+    fn generated() -> SessionDescription {
+        SessionDescription::unmarshal(&mut Cursor::new(GENERATED.as_bytes())).unwrap()
+    }
+
+    #[test]
+    fn test_sdp_modifiers_round_trip() {
+        let modifiers = SdpModifiers::new()
+            .with_session_name("unreliable".to_owned())
+            .with_session_property_attribute("extmap-allow-mixed".to_owned())
+            .with_session_value_attribute("x-session".to_owned(), "one two".to_owned())
+            .with_msid_semantic("WMS".to_owned())
+            .with_media_property_attribute("x-flag".to_owned())
+            .with_media_value_attribute("x-media".to_owned(), "1".to_owned());
+
+        let marshaled = modifiers.apply(generated()).unwrap().marshal();
+        assert!(marshaled.contains("\r\ns=unreliable\r\n"));
+        assert!(marshaled.contains("\r\na=msid-semantic:WMS\r\n"));
+
+        let d = SessionDescription::unmarshal(&mut Cursor::new(marshaled.as_bytes())).unwrap();
+        assert_eq!(d.session_name, "unreliable");
+        assert!(d
+            .attributes
+            .iter()
+            .any(|a| a.key == "extmap-allow-mixed" && a.value.is_none()));
+        assert_eq!(
+            d.attribute("x-session").map(String::as_str),
+            Some("one two")
+        );
+        assert_eq!(
+            d.attribute(ATTR_KEY_MSID_SEMANTIC).map(String::as_str),
+            Some("WMS")
+        );
+        assert_eq!(d.attribute("group").map(String::as_str), Some("BUNDLE 0"));
+
+        let media = &d.media_descriptions[0];
+        assert_eq!(media.attribute("x-flag"), Some(None));
+        assert_eq!(media.attribute("x-media"), Some(Some("1")));
+        assert_eq!(media.attribute("sctp-port"), Some(Some("5000")));
+    }
+
+    #[test]
+    fn test_sdp_modifiers_reject_duplicates() {
+        let tests = vec![
+            (
+                SdpModifiers::new()
+                    .with_session_value_attribute("group".to_owned(), "x".to_owned()),
+                "group",
+            ),
+            (
+                SdpModifiers::new()
+                    .with_msid_semantic("WMS".to_owned())
+                    .with_msid_semantic("WMS".to_owned()),
+                ATTR_KEY_MSID_SEMANTIC,
+            ),
+            (
+                SdpModifiers::new().with_media_property_attribute("sctp-port".to_owned()),
+                "sctp-port",
+            ),
+        ];
+
+        for (modifiers, key) in tests {
+            assert_eq!(
+                modifiers.apply(generated()).unwrap_err(),
+                Error::ErrSDPModifierDuplicateAttribute(key.to_owned())
+            );
+        }
+    }
+}
@@ -3,42 +3,91 @@ use crate::webrtc::error::Result;
 use super::sdp_type::RTCSdpType;
 
 use crate::webrtc::sdp::description::session::SessionDescription;
+use crate::webrtc::sdp::Error as SdpError;
 use serde::{Deserialize, Serialize};
 use std::io::Cursor;
+use std::sync::OnceLock;
 
 /// SessionDescription is used to expose local and remote session descriptions.
+///
+/// The SDP is parsed on the first call to parsed and the result is kept, so repeated access
+/// does not parse it again. Marshaling the parsed form of an offer generated by this crate
+/// gives back the exact same SDP.
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
-pub(crate) struct RTCSessionDescription {
+pub struct RTCSessionDescription {
     #[serde(rename = "type")]
     pub(crate) sdp_type: RTCSdpType,
 
     pub(crate) sdp: String,
 
-    /// This will never be initialized by callers, internal use only
+    /// parsed caches the parsed sdp, see RTCSessionDescription::parsed
     #[serde(skip)]
-    pub(crate) parsed: Option<SessionDescription>,
+    pub(crate) parsed: OnceLock<SessionDescription>,
 }
 
 impl RTCSessionDescription {
+    /// Given SDP representing an offer, wrap it in an RTCSessionDescription, e.g. the offer
+    /// handed to SessionEndpoint::Signaling
+    pub fn offer(sdp: String) -> std::result::Result<RTCSessionDescription, SdpError> {
+        RTCSessionDescription::parse(sdp, RTCSdpType::Offer)
+    }
+
     /// Given SDP representing an answer, wrap it in an RTCSessionDescription
     /// that can be given to an RTCPeerConnection.
-    pub(crate) fn answer(sdp: String) -> Result<RTCSessionDescription> {
-        let mut desc = RTCSessionDescription {
+    pub fn answer(sdp: String) -> std::result::Result<RTCSessionDescription, SdpError> {
+        RTCSessionDescription::parse(sdp, RTCSdpType::Answer)
+    }
+
+    /// from_parsed wraps a SessionDescription, marshaling it to the SDP string. parsed then
+    /// returns it without parsing the string again.
+    pub fn from_parsed(parsed: SessionDescription, sdp_type: RTCSdpType) -> Self {
+        RTCSessionDescription {
+            sdp_type,
+            sdp: parsed.marshal(),
+            parsed: OnceLock::from(parsed),
+        }
+    }
+
+    fn parse(
+        sdp: String,
+        sdp_type: RTCSdpType,
+    ) -> std::result::Result<RTCSessionDescription, SdpError> {
+        let desc = RTCSessionDescription {
             sdp,
-            sdp_type: RTCSdpType::Answer,
-            parsed: None,
+            sdp_type,
+            parsed: OnceLock::new(),
         };
-
-        let parsed = desc.unmarshal()?;
-        desc.parsed = Some(parsed);
+        desc.parsed()?;
 
         Ok(desc)
     }
 
+    /// sdp_type tells whether this is an offer or an answer
+    pub fn sdp_type(&self) -> RTCSdpType {
+        self.sdp_type
+    }
+
+    /// sdp is the SDP string
+    pub fn sdp(&self) -> &str {
+        &self.sdp
+    }
+
+    /// parsed returns the SDP parsed to a SessionDescription, parsing it on the first call
+    pub fn parsed(&self) -> std::result::Result<&SessionDescription, SdpError> {
+        if let Some(parsed) = self.parsed.get() {
+            return Ok(parsed);
+        }
+        let parsed = self.unmarshal_sdp()?;
+        Ok(self.parsed.get_or_init(|| parsed))
+    }
+
     /// Unmarshal is a helper to deserialize the sdp
     pub(crate) fn unmarshal(&self) -> Result<SessionDescription> {
+        Ok(self.unmarshal_sdp()?)
+    }
+
+    fn unmarshal_sdp(&self) -> std::result::Result<SessionDescription, SdpError> {
         let mut reader = Cursor::new(self.sdp.as_bytes());
-        let parsed = SessionDescription::unmarshal(&mut reader)?;
-        Ok(parsed)
+        SessionDescription::unmarshal(&mut reader)
     }
 }
@@ -1,21 +1,31 @@
 pub(crate) mod sctp_transport_capabilities;
 pub(crate) mod sctp_transport_state;
+pub(crate) mod transport_events;
 
 use sctp_transport_state::RTCSctpTransportState;
 
+use crate::webrtc::api::setting_engine::SettingEngine;
+use crate::webrtc::data_channel::data_channel_parameters::DataChannelParameters;
+use crate::webrtc::data_channel::internal::data_channel::DataChannel;
 use crate::webrtc::data_channel::RTCDataChannel;
+use crate::webrtc::dtls_transport::dtls_role::DTLSRole;
 use crate::webrtc::dtls_transport::*;
 use crate::webrtc::error::*;
+use crate::webrtc::peer_connection::event_log::{ConnectMilestone, EventLog};
+use crate::webrtc::peer_connection::sdp::RemoteSctpParams;
 use crate::webrtc::sctp_transport::sctp_transport_capabilities::SCTPTransportCapabilities;
+use crate::webrtc::sctp_transport::transport_events::{TransportEvent, TransportEvents};
 
+use crate::webrtc::sctp::association::association_stats::AssociationStats;
 use crate::webrtc::sctp::association::Association;
+use crate::webrtc::sctp::stream::{CloseReason, TransportError};
+use crate::webrtc::util::spawner::Spawner;
 
-use crate::webrtc::util::Conn;
 use std::future::Future;
 use std::pin::Pin;
-use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU8, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU16, AtomicU32, AtomicU64, AtomicU8, Ordering};
 use std::sync::Arc;
-use tokio::sync::{Mutex, Notify};
+use tokio::sync::{broadcast, Mutex, Notify};
 
 pub(crate) type OnDataChannelHdlrFn = Box<
     dyn (FnMut(Arc<RTCDataChannel>) -> Pin<Box<dyn Future<Output = ()> + Send + 'static>>)
@@ -23,14 +33,28 @@ pub(crate) type OnDataChannelHdlrFn = Box<
         + Sync,
 >;
 
+pub(crate) type OnPeerRestartHdlrFn =
+    Box<dyn (Fn() -> Pin<Box<dyn Future<Output = ()> + Send + 'static>>) + Send + Sync>;
+
+struct AcceptDataChannelParams {
+    notify_rx: Arc<Notify>,
+    sctp_association: Arc<Association>,
+    data_channels: Arc<Mutex<Vec<Arc<RTCDataChannel>>>>,
+    on_data_channel_handler: Arc<Mutex<Option<OnDataChannelHdlrFn>>>,
+    on_peer_restart_handler: Arc<Mutex<Option<OnPeerRestartHdlrFn>>>,
+    data_channels_opened: Arc<AtomicU32>,
+    event_log: Arc<EventLog>,
+    spawner: Spawner,
+}
+
 /// SCTPTransport provides details about the SCTP transport.
 #[derive(Default)]
 pub(crate) struct RTCSctpTransport {
     // removing this causes compile panic, last checked
     #[allow(dead_code)]
     max_message_size: bool,
-    #[allow(dead_code)]
-    setting_engine: bool,
+    setting_engine: Arc<SettingEngine>,
+    event_log: Arc<EventLog>,
 
     pub(crate) dtls_transport: Arc<RTCDtlsTransport>,
 
@@ -41,22 +65,67 @@ pub(crate) struct RTCSctpTransport {
     // so we need a dedicated field
     is_started: AtomicBool,
 
-    sctp_association: Mutex<Option<Arc<Association>>>,
+    pub(crate) sctp_association: Mutex<Option<Arc<Association>>>,
 
     on_data_channel_handler: Arc<Mutex<Option<OnDataChannelHdlrFn>>>,
+    on_peer_restart_handler: Arc<Mutex<Option<OnPeerRestartHdlrFn>>>,
 
     // DataChannels
     pub(crate) data_channels: Arc<Mutex<Vec<Arc<RTCDataChannel>>>>,
     pub(crate) data_channels_opened: Arc<AtomicU32>,
     pub(crate) data_channels_requested: Arc<AtomicU32>,
 
+    // remote_bandwidth_limit is the bits per second the remote description limits the data
+    // section to, zero meaning unlimited. It caps the association's send rate with
+    // SettingEngine::set_sctp_respect_remote_bandwidth.
+    remote_bandwidth_limit: Arc<AtomicU64>,
+
+    // remote_sctp_port and remote_max_streams are what the remote description announces, see
+    // RemoteSctpParams. Zero selects the default port and leaves the stream count to the
+    // handshake.
+    remote_sctp_port: AtomicU16,
+    remote_max_streams: AtomicU16,
+
+    // association_stats is shared by every association of the transport, so that the counters
+    // add up across a restart
+    association_stats: Arc<AssociationStats>,
+    // transport_events is shared by every association of the transport too, so that a
+    // subscription outlives a restart
+    transport_events: Arc<TransportEvents>,
+
+    // next_data_channel_id counts the SCTP stream identifiers handed to locally opened
+    // DataChannels in steps of two, generate_data_channel_id picks the even or odd one.
+    next_data_channel_id: AtomicU16,
+
     notify_tx: Arc<Notify>,
+    // state_changed wakes the tasks waiting in connected
+    state_changed: Notify,
+}
+
+/// SctpTraffic counts the SCTP packets and inbound DATA chunks over all associations of a
+/// transport. The bytes are whole packets, common header and control chunks included.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SctpTraffic {
+    pub(crate) packets_sent: u64,
+    pub(crate) bytes_sent: u64,
+    pub(crate) packets_received: u64,
+    pub(crate) bytes_received: u64,
+    pub(crate) data_chunks_received: u64,
+    /// data_chunks_skipped counts the inbound DATA chunks the peer abandoned before they
+    /// arrived, which FORWARD TSN skipped
+    pub(crate) data_chunks_skipped: u64,
 }
 
 impl RTCSctpTransport {
-    pub(crate) fn new(dtls_transport: Arc<RTCDtlsTransport>) -> Self {
+    pub(crate) fn new(
+        dtls_transport: Arc<RTCDtlsTransport>,
+        setting_engine: Arc<SettingEngine>,
+        event_log: Arc<EventLog>,
+    ) -> Self {
+        let transport_events = Arc::new(TransportEvents::new(setting_engine.spawner.clone()));
         RTCSctpTransport {
-            setting_engine: true,
+            setting_engine,
+            event_log,
             max_message_size: true,
 
             dtls_transport,
@@ -64,10 +133,18 @@ impl RTCSctpTransport {
             is_started: AtomicBool::new(false),
             sctp_association: Mutex::new(None),
             on_data_channel_handler: Arc::new(Mutex::new(None)),
+            on_peer_restart_handler: Arc::new(Mutex::new(None)),
             data_channels: Arc::new(Mutex::new(vec![])),
             data_channels_opened: Arc::new(AtomicU32::new(0)),
             data_channels_requested: Arc::new(AtomicU32::new(0)),
+            remote_bandwidth_limit: Arc::new(AtomicU64::new(0)),
+            remote_sctp_port: AtomicU16::new(0),
+            remote_max_streams: AtomicU16::new(0),
+            association_stats: Arc::new(AssociationStats::default()),
+            transport_events,
+            next_data_channel_id: AtomicU16::new(0),
             notify_tx: Arc::new(Notify::new()),
+            state_changed: Notify::new(),
         }
     }
 
@@ -76,6 +153,17 @@ impl RTCSctpTransport {
         Arc::clone(&self.dtls_transport)
     }
 
+    /// event_log returns the event log of the peer connection the transport belongs to
+    pub(crate) fn event_log(&self) -> &EventLog {
+        &self.event_log
+    }
+
+    /// spawner runs the tasks of the transport and its data channels, see
+    /// SettingEngine::set_runtime_handle
+    pub(crate) fn spawner(&self) -> &Spawner {
+        &self.setting_engine.spawner
+    }
+
     /// Start the SCTPTransport. Since both local and remote parties must mutually
     /// create an SCTPTransport, SCTP SO (Simultaneous Open) is used to establish
     /// a connection over SCTP.
@@ -86,14 +174,35 @@ impl RTCSctpTransport {
         self.is_started.store(true, Ordering::SeqCst);
 
         let dtls_transport = self.transport();
-        if let Some(net_conn) = &dtls_transport.conn().await {
+        if let Some(net_conn) = dtls_transport.sctp_conn().await {
             let sctp_association = Arc::new(
                 crate::webrtc::sctp::association::Association::client(
                     crate::webrtc::sctp::association::Config {
-                        net_conn: Arc::clone(net_conn) as Arc<dyn Conn + Send + Sync>,
-                        max_receive_buffer_size: 0,
+                        net_conn,
+                        max_receive_buffer_size: self.setting_engine.sctp_max_receive_buffer_size,
+                        initial_recv_window: self.setting_engine.sctp_initial_recv_window,
+                        max_num_inbound_streams: self.setting_engine.sctp_max_inbound_streams,
+                        max_num_outbound_streams: self.max_outbound_streams(),
+                        remote_port: self.remote_sctp_port.load(Ordering::SeqCst),
                         max_message_size: 0,
+                        max_send_buffer_size: self.setting_engine.sctp_max_send_buffer_size,
                         name: String::new(),
+                        bundling_delay: self.setting_engine.sctp_bundling_delay,
+                        pacing: self.setting_engine.sctp_pacing,
+                        pacing_gain: self.setting_engine.sctp_pacing_gain,
+                        max_send_rate: if self.setting_engine.sctp_respect_remote_bandwidth {
+                            Some(Arc::clone(&self.remote_bandwidth_limit))
+                        } else {
+                            None
+                        },
+                        mtu: self.setting_engine.sctp_mtu,
+                        receive_mtu: self.setting_engine.receive_mtu(),
+                        strict_priority: self.setting_engine.sctp_strict_priority,
+                        stats: Arc::clone(&self.association_stats),
+                        send_errors: Some(dtls_transport.ice_transport.send_errors()),
+                        event_log: Arc::clone(&self.event_log),
+                        transport_events: Arc::clone(&self.transport_events),
+                        spawner: self.setting_engine.spawner.clone(),
                     },
                 )
                 .await?,
@@ -105,6 +214,21 @@ impl RTCSctpTransport {
             }
             self.state
                 .store(RTCSctpTransportState::Connected as u8, Ordering::SeqCst);
+            self.state_changed.notify_waiters();
+
+            let param = AcceptDataChannelParams {
+                notify_rx: Arc::clone(&self.notify_tx),
+                sctp_association,
+                data_channels: Arc::clone(&self.data_channels),
+                on_data_channel_handler: Arc::clone(&self.on_data_channel_handler),
+                on_peer_restart_handler: Arc::clone(&self.on_peer_restart_handler),
+                data_channels_opened: Arc::clone(&self.data_channels_opened),
+                event_log: Arc::clone(&self.event_log),
+                spawner: self.setting_engine.spawner.clone(),
+            };
+            self.setting_engine.spawner.spawn(async move {
+                RTCSctpTransport::accept_data_channels(param).await;
+            });
 
             Ok(())
         } else {
@@ -112,6 +236,62 @@ impl RTCSctpTransport {
         }
     }
 
+    async fn accept_data_channels(param: AcceptDataChannelParams) {
+        loop {
+            let dc = tokio::select! {
+                _ = param.notify_rx.notified() => break,
+                result = DataChannel::accept(&param.sctp_association) => {
+                    match result {
+                        Ok(dc) => dc,
+                        Err(err) => {
+                            if err == crate::webrtc::internal::Error::ErrStreamClosed {
+                                log::debug!("Stopped accepting data channels: {}", err);
+                                if matches!(
+                                    param.sctp_association.close_reason().await,
+                                    Some(CloseReason::TransportFailure(TransportError::PeerRestart))
+                                ) {
+                                    let handler = param.on_peer_restart_handler.lock().await;
+                                    if let Some(f) = &*handler {
+                                        f().await;
+                                    }
+                                }
+                                break;
+                            }
+                            log::error!("Failed to accept data channel: {}", err);
+                            continue;
+                        }
+                    }
+                }
+            };
+
+            let rtc_dc = Arc::new(RTCDataChannel::new_accepted(
+                DataChannelParameters::from(&dc.config),
+                param.spawner.clone(),
+            ));
+
+            {
+                let mut handler = param.on_data_channel_handler.lock().await;
+                if let Some(f) = &mut *handler {
+                    f(Arc::clone(&rtc_dc)).await;
+                }
+            }
+
+            rtc_dc.handle_open(Arc::new(dc)).await;
+            param.event_log.milestone(ConnectMilestone::ChannelOpen);
+
+            param.data_channels_opened.fetch_add(1, Ordering::SeqCst);
+            register_data_channel(&param.data_channels, rtc_dc).await;
+        }
+    }
+
+    /// live_data_channels returns the registered DataChannels that are not closed for good, in
+    /// the order they were created or accepted
+    pub(crate) async fn live_data_channels(&self) -> Vec<Arc<RTCDataChannel>> {
+        let mut data_channels = self.data_channels.lock().await;
+        data_channels.retain(|d| !d.is_retired());
+        data_channels.clone()
+    }
+
     /// Stop stops the SCTPTransport
     pub(crate) async fn stop(&self) -> Result<()> {
         {
@@ -123,12 +303,30 @@ impl RTCSctpTransport {
 
         self.state
             .store(RTCSctpTransportState::Closed as u8, Ordering::SeqCst);
+        self.state_changed.notify_waiters();
 
         self.notify_tx.notify_waiters();
 
         Ok(())
     }
 
+    /// reset drops the association after it was closed, so that start brings up a new one, e.g.
+    /// over the new DTLS connection after RTCDtlsTransport::restart
+    pub(crate) async fn reset(&self) {
+        {
+            let mut sctp_association = self.sctp_association.lock().await;
+            sctp_association.take();
+        }
+        self.notify_tx.notify_waiters();
+        // the new association has no streams, its channels start over at the first one
+        self.next_data_channel_id.store(0, Ordering::SeqCst);
+
+        self.state
+            .store(RTCSctpTransportState::Connecting as u8, Ordering::SeqCst);
+        self.state_changed.notify_waiters();
+        self.is_started.store(false, Ordering::SeqCst);
+    }
+
     /// on_data_channel sets an event handler which is invoked when a data
     /// channel message arrives from a remote peer.
     pub(crate) async fn on_data_channel(&self, f: OnDataChannelHdlrFn) {
@@ -136,13 +334,125 @@ impl RTCSctpTransport {
         *handler = Some(f);
     }
 
+    /// on_peer_restart sets an event handler which is invoked once an association is closed
+    /// because the peer restarted its end, see TransportError::PeerRestart
+    pub(crate) async fn on_peer_restart(&self, f: OnPeerRestartHdlrFn) {
+        let mut handler = self.on_peer_restart_handler.lock().await;
+        *handler = Some(f);
+    }
+
     /// state returns the current state of the SCTPTransport
     pub(crate) fn state(&self) -> RTCSctpTransportState {
         self.state.load(Ordering::SeqCst).into()
     }
 
+    /// connected waits until the SCTP association is established and returns true, or false if
+    /// the transport is stopped first
+    pub(crate) async fn connected(&self) -> bool {
+        loop {
+            // registered before checking, a state change in between is not missed
+            let state_changed = self.state_changed.notified();
+            tokio::pin!(state_changed);
+            state_changed.as_mut().enable();
+
+            match self.state() {
+                RTCSctpTransportState::Connected => return true,
+                RTCSctpTransportState::Closed => return false,
+                _ => state_changed.await,
+            }
+        }
+    }
+
+    /// remote_bandwidth_limit returns the bits per second the remote description limits the data
+    /// section to, None if unlimited
+    pub(crate) fn remote_bandwidth_limit(&self) -> Option<u64> {
+        match self.remote_bandwidth_limit.load(Ordering::SeqCst) {
+            0 => None,
+            limit => Some(limit),
+        }
+    }
+
+    /// set_remote_bandwidth_limit updates the limit of remote_bandwidth_limit, a running
+    /// association follows it from its next packet
+    pub(crate) fn set_remote_bandwidth_limit(&self, limit: Option<u64>) {
+        self.remote_bandwidth_limit
+            .store(limit.unwrap_or(0), Ordering::SeqCst);
+    }
+
+    /// set_remote_sctp_params records the SCTP port and stream count of the remote description
+    /// for the next association
+    pub(crate) fn set_remote_sctp_params(&self, params: Option<RemoteSctpParams>) {
+        let (port, max_streams) = params
+            .map(|params| (params.port, params.max_streams.unwrap_or(0)))
+            .unwrap_or((0, 0));
+        self.remote_sctp_port.store(port, Ordering::SeqCst);
+        self.remote_max_streams.store(max_streams, Ordering::SeqCst);
+    }
+
+    /// max_outbound_streams is SettingEngine::set_sctp_max_outbound_streams capped to the
+    /// stream count of an a=sctpmap, zero meaning no limit
+    fn max_outbound_streams(&self) -> u16 {
+        let configured = self.setting_engine.sctp_max_outbound_streams;
+        match self.remote_max_streams.load(Ordering::SeqCst) {
+            0 => configured,
+            remote if configured == 0 => remote,
+            remote => std::cmp::min(configured, remote),
+        }
+    }
+
+    /// bad_verification_tags returns how many inbound packets the associations of the transport
+    /// dropped for a verification tag that does not belong to them
+    pub(crate) fn bad_verification_tags(&self) -> u64 {
+        self.association_stats.get_num_bad_verification_tags()
+    }
+
+    /// subscribe_transport_events returns a receiver of the loss and congestion events of the
+    /// associations of the transport, see TransportEvents::subscribe
+    pub(crate) fn subscribe_transport_events(
+        &self,
+        buffer: usize,
+    ) -> broadcast::Receiver<TransportEvent> {
+        self.transport_events.subscribe(buffer)
+    }
+
+    /// traffic returns the packets and DATA chunks the associations of the transport exchanged
+    pub(crate) fn traffic(&self) -> SctpTraffic {
+        let stats = &self.association_stats;
+        SctpTraffic {
+            packets_sent: stats.get_num_packets_sent(),
+            bytes_sent: stats.get_num_bytes_sent(),
+            packets_received: stats.get_num_packets_received(),
+            bytes_received: stats.get_num_bytes_received(),
+            data_chunks_received: stats.get_num_datas(),
+            data_chunks_skipped: stats.get_num_skipped_tsns(),
+        }
+    }
+
+    /// generate_data_channel_id reserves the stream identifier for a locally opened DataChannel.
+    /// The DTLS client uses even identifiers and the DTLS server odd ones (RFC 8832 S6).
+    pub(crate) async fn generate_data_channel_id(&self) -> u16 {
+        let id = self.next_data_channel_id.fetch_add(2, Ordering::SeqCst);
+        if self.dtls_transport.role().await == DTLSRole::Server {
+            id + 1
+        } else {
+            id
+        }
+    }
+
     pub(crate) async fn association(&self) -> Option<Arc<Association>> {
         let sctp_association = self.sctp_association.lock().await;
         sctp_association.clone()
     }
 }
+
+// register_data_channel adds a DataChannel to the registry of the transport, which holds it for
+// as long as it can be used so that no handle needs to be kept by the task that opened it.
+// Channels closed for good are dropped on the way.
+pub(crate) async fn register_data_channel(
+    data_channels: &Mutex<Vec<Arc<RTCDataChannel>>>,
+    d: Arc<RTCDataChannel>,
+) {
+    let mut data_channels = data_channels.lock().await;
+    data_channels.retain(|d| !d.is_retired());
+    data_channels.push(d);
+}
@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+use tokio::time::Instant;
+
+use crate::webrtc::util::spawner::Spawner;
+
+/// COALESCE_INTERVAL is how long events of the same kind are merged into one after one was sent
+const COALESCE_INTERVAL: Duration = Duration::from_millis(50);
+
+/// TransportEvent is a loss or congestion signal of the SCTP association, see
+/// SocketHandle::subscribe_transport_events
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+pub struct TransportEvent {
+    /// at is the time since the peer connection was created
+    pub at: Duration,
+    pub kind: TransportEventKind,
+}
+
+/// TransportEventKind is what the SCTP association noticed. Each kind is sent at most once per
+/// 50ms, for MessagesAbandoned once per stream, what happens in between is merged into the
+/// next event of the kind.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub enum TransportEventKind {
+    /// Retransmission is DATA chunks sent again, after SACKs reported them missing three times
+    /// or, with timeout set, after the retransmission timer expired
+    Retransmission { timeout: bool, chunks: u64 },
+    /// MessagesAbandoned is messages of the data channel on stream_identifier given up on
+    /// because they were lost and the channel does not retransmit them
+    MessagesAbandoned {
+        stream_identifier: u16,
+        messages: u64,
+    },
+    /// CongestionWindowReduced is the congestion window cut after a loss, halved on entering
+    /// fast recovery or, with timeout set, down to one MTU. cwnd and ssthresh are the values
+    /// after the latest cut, in bytes.
+    CongestionWindowReduced {
+        timeout: bool,
+        cwnd: u32,
+        ssthresh: u32,
+    },
+}
+
+impl TransportEventKind {
+    /// key tells which events are merged with each other
+    fn key(&self) -> (u8, u16) {
+        match *self {
+            TransportEventKind::Retransmission { timeout, .. } => (0, timeout as u16),
+            TransportEventKind::MessagesAbandoned {
+                stream_identifier, ..
+            } => (1, stream_identifier),
+            TransportEventKind::CongestionWindowReduced { timeout, .. } => (2, timeout as u16),
+        }
+    }
+
+    /// merge adds the counts of later to self, the values of a congestion window cut are taken
+    /// from the later one
+    fn merge(&mut self, later: TransportEventKind) {
+        match (self, later) {
+            (
+                TransportEventKind::Retransmission { chunks, .. },
+                TransportEventKind::Retransmission {
+                    chunks: later_chunks,
+                    ..
+                },
+            ) => *chunks += later_chunks,
+            (
+                TransportEventKind::MessagesAbandoned { messages, .. },
+                TransportEventKind::MessagesAbandoned {
+                    messages: later_messages,
+                    ..
+                },
+            ) => *messages += later_messages,
+            (merged, later) => *merged = later,
+        }
+    }
+}
+
+/// TransportEvents hands the events of the SCTP associations of a transport to the subscribers,
+/// see TransportEventKind for how they are rate limited. Until somebody subscribes, recording an
+/// event is a single atomic load.
+pub(crate) struct TransportEvents {
+    started: Instant,
+    spawner: Spawner,
+    subscribed: AtomicBool,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    sender: Option<broadcast::Sender<TransportEvent>>,
+    windows: HashMap<(u8, u16), Window>,
+}
+
+/// Window is the interval after an event was sent in which further events of its kind are
+/// merged, they are sent together once it ends
+struct Window {
+    until: Instant,
+    merged: Option<TransportEventKind>,
+}
+
+impl TransportEvents {
+    pub(crate) fn new(spawner: Spawner) -> Self {
+        TransportEvents {
+            started: Instant::now(),
+            spawner,
+            subscribed: AtomicBool::new(false),
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    /// subscribe returns a receiver of the events from now on. The first subscriber sizes the
+    /// channel with buffer, a subscriber falling further behind misses the oldest events. The
+    /// events are no longer collected once every receiver is dropped.
+    pub(crate) fn subscribe(&self, buffer: usize) -> broadcast::Receiver<TransportEvent> {
+        let mut inner = self.inner.lock().unwrap();
+        let receiver = match &inner.sender {
+            Some(sender) if sender.receiver_count() > 0 => sender.subscribe(),
+            _ => {
+                let (sender, receiver) = broadcast::channel(buffer.max(1));
+                inner.sender = Some(sender);
+                receiver
+            }
+        };
+        self.subscribed.store(true, Ordering::SeqCst);
+        receiver
+    }
+
+    /// record sends kind to the subscribers, or merges it into the next event of its kind if one
+    /// was sent less than COALESCE_INTERVAL ago
+    pub(crate) fn record(self: &Arc<Self>, kind: TransportEventKind) {
+        if !self.subscribed.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let now = Instant::now();
+        let mut inner = self.inner.lock().unwrap();
+        let sender = match &inner.sender {
+            Some(sender) if sender.receiver_count() > 0 => sender.clone(),
+            _ => {
+                self.subscribed.store(false, Ordering::SeqCst);
+                *inner = Inner::default();
+                return;
+            }
+        };
+
+        let key = kind.key();
+        if let Some(window) = inner.windows.get_mut(&key) {
+            if now < window.until {
+                match &mut window.merged {
+                    Some(merged) => merged.merge(kind),
+                    None => {
+                        window.merged = Some(kind);
+                        self.flush_at(key, window.until);
+                    }
+                }
+                return;
+            }
+        }
+
+        inner.windows.insert(
+            key,
+            Window {
+                until: now + COALESCE_INTERVAL,
+                merged: None,
+            },
+        );
+        let _ = sender.send(self.event(now, kind));
+    }
+
+    // flush_at sends the events merged in the window of key once it ends, and opens the next
+    // window so that a storm still yields one event per interval
+    fn flush_at(self: &Arc<Self>, key: (u8, u16), until: Instant) {
+        let events = Arc::clone(self);
+        self.spawner.spawn(async move {
+            tokio::time::sleep_until(until).await;
+
+            let now = Instant::now();
+            let mut inner = events.inner.lock().unwrap();
+            let merged = match inner.windows.get_mut(&key) {
+                Some(window) => {
+                    window.until = now + COALESCE_INTERVAL;
+                    window.merged.take()
+                }
+                None => None,
+            };
+            if let (Some(sender), Some(kind)) = (&inner.sender, merged) {
+                let _ = sender.send(events.event(now, kind));
+            }
+        });
+    }
+
+    fn event(&self, now: Instant, kind: TransportEventKind) -> TransportEvent {
+        TransportEvent {
+            at: now.duration_since(self.started),
+            kind,
+        }
+    }
+}
+
+impl Default for TransportEvents {
+    fn default() -> Self {
+        TransportEvents::new(Spawner::default())
+    }
+}
@@ -0,0 +1,82 @@
+//! ice_credentials checks that SettingEngine::set_ice_credentials rejects credentials RFC 8445
+//! does not allow and that the offer carries the ones it accepts.
+
+use std::sync::{Arc, Mutex};
+
+use webrtc_unreliable_client::{
+    ConfigIssue, SessionEndpoint, SettingEngine, Socket, SocketError, SocketOptions,
+};
+
+const UFRAG: &str = "Ab1+";
+const PWD: &str = "abcdefghijklmnopqrstu/";
+
+#[test]
+fn invalid_ice_credentials_are_rejected() {
+    let tests = vec![
+        // too short
+        ("Ab1", PWD, vec![ConfigIssue::IceUfragInvalid]),
+        (
+            UFRAG,
+            "abcdefghijklmnopqrstu",
+            vec![ConfigIssue::IcePwdInvalid],
+        ),
+        // outside of the ice-char alphabet
+        ("Ab1-", PWD, vec![ConfigIssue::IceUfragInvalid]),
+        ("ufrag:1", PWD, vec![ConfigIssue::IceUfragInvalid]),
+        (
+            UFRAG,
+            "abcdefghijklmnopqrstu=",
+            vec![ConfigIssue::IcePwdInvalid],
+        ),
+        (
+            "Äbcd",
+            "abcdefghijklmnopqrstu é",
+            vec![ConfigIssue::IceUfragInvalid, ConfigIssue::IcePwdInvalid],
+        ),
+    ];
+
+    for (ufrag, pwd, issues) in tests {
+        let mut setting_engine = SettingEngine::default();
+        setting_engine.set_ice_credentials(ufrag.to_owned(), pwd.to_owned());
+
+        match SocketOptions::builder()
+            .setting_engine(setting_engine)
+            .build()
+        {
+            Err(SocketError::Options(err)) => assert_eq!(err.issues, issues, "{ufrag} {pwd}"),
+            Err(err) => panic!("unexpected error {:?}", err),
+            Ok(_) => panic!("built options with ufrag {:?} and pwd {:?}", ufrag, pwd),
+        }
+    }
+}
+
+#[tokio::test]
+async fn offer_carries_the_ice_credentials() {
+    let mut setting_engine = SettingEngine::default();
+    setting_engine.set_ice_credentials(UFRAG.to_owned(), PWD.to_owned());
+    let options = SocketOptions::builder()
+        .setting_engine(setting_engine)
+        .build()
+        .unwrap();
+
+    let offer = Arc::new(Mutex::new(None));
+    let sent = Arc::clone(&offer);
+    let endpoint = SessionEndpoint::Signaling(Box::new(move |offer| {
+        *sent.lock().unwrap() = Some(offer);
+        Box::pin(async { Err(anyhow::anyhow!("no server")) })
+    }));
+    assert!(Socket::connect_session_without_channel(endpoint, options)
+        .await
+        .is_err());
+
+    let offer = offer.lock().unwrap().take().expect("no offer was sent");
+    let lines: Vec<&str> = offer.lines().collect();
+    assert!(
+        lines.contains(&format!("a=ice-ufrag:{UFRAG}").as_str()),
+        "{offer}"
+    );
+    assert!(
+        lines.contains(&format!("a=ice-pwd:{PWD}").as_str()),
+        "{offer}"
+    );
+}
@@ -0,0 +1,162 @@
+//! legacy_connect connects with Socket::connect to an in-process webrtc-unreliable echo server
+//! and checks that the session keeps working after connect returned, when the caller only
+//! holds on to the SocketIo, and that a failed connect does not keep the peer connection alive.
+
+use std::net::{SocketAddr, UdpSocket};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::time::{sleep, timeout, Duration};
+
+use webrtc_unreliable::{Server as RtcServer, SessionEndpoint};
+use webrtc_unreliable_client::Socket;
+
+/// REPLY_TIMEOUT is how long the test waits for the first echo after connect returned
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[tokio::test(flavor = "multi_thread")]
+async fn legacy_connect_gets_reply_after_connect_returns() {
+    let addr: SocketAddr = UdpSocket::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let rtc_server = RtcServer::new(addr, addr).await.unwrap();
+    let session_endpoint = rtc_server.session_endpoint();
+    tokio::spawn(echo(rtc_server));
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server_url = format!("http://{}/rtc_session", listener.local_addr().unwrap());
+    tokio::spawn(serve_sessions(listener, session_endpoint));
+
+    let (socket, mut socket_io) = Socket::new();
+    socket.connect(&server_url).await;
+
+    // connect returns before the data channel opens, the messages queued until then are sent
+    // once it is open
+    let received = timeout(REPLY_TIMEOUT, async {
+        loop {
+            socket_io
+                .to_server_sender
+                .send(b"PING".to_vec().into_boxed_slice())
+                .unwrap();
+            tokio::select! {
+                message = socket_io.to_client_receiver.recv() => return message,
+                _ = sleep(Duration::from_millis(200)) => {}
+            }
+        }
+    })
+    .await
+    .expect("no reply from the server");
+
+    assert_eq!(received.as_deref(), Some(&b"PING"[..]));
+}
+
+/// failed_connect_drops_the_peer_connection has the server answer the offer with a malformed
+/// session response, which fails connect. The peer connection holds the SocketIo ends connect
+/// took, the receiver has to see them dropped with it.
+#[tokio::test(flavor = "multi_thread")]
+async fn failed_connect_drops_the_peer_connection() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server_url = format!("http://{}/rtc_session", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        read_body(&mut stream).await;
+        let body = "not a session response";
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let _ = stream.write_all(response.as_bytes()).await;
+    });
+
+    let (socket, mut socket_io) = Socket::new();
+    let connect = tokio::spawn(async move { socket.connect(&server_url).await });
+    assert!(connect.await.unwrap_err().is_panic());
+
+    let received = timeout(REPLY_TIMEOUT, socket_io.to_client_receiver.recv())
+        .await
+        .expect("the peer connection outlived the failed connect");
+    assert_eq!(received, None);
+    // the receiver of the other end is dropped right after, by the same handler
+    timeout(REPLY_TIMEOUT, socket_io.to_server_sender.closed())
+        .await
+        .expect("the peer connection outlived the failed connect");
+}
+
+/// echo sends every message back to the client it came from
+async fn echo(mut rtc_server: RtcServer) {
+    let mut message = Vec::new();
+    loop {
+        let (message_type, remote_addr) = match rtc_server.recv().await {
+            Ok(received) => {
+                message.clear();
+                message.extend_from_slice(received.message.as_ref());
+                (received.message_type, received.remote_addr)
+            }
+            Err(_) => return,
+        };
+        let _ = rtc_server.send(&message, message_type, &remote_addr).await;
+    }
+}
+
+/// serve_sessions answers each HTTP POST of an offer on listener with the response of
+/// session_endpoint, enough HTTP/1.1 for the signaling request of Socket::connect
+async fn serve_sessions(listener: TcpListener, session_endpoint: SessionEndpoint) {
+    loop {
+        let (mut stream, _) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(_) => return,
+        };
+        let mut session_endpoint = session_endpoint.clone();
+        tokio::spawn(async move {
+            let offer = match read_body(&mut stream).await {
+                Some(offer) => offer,
+                None => return,
+            };
+            let body = futures_util::stream::once(async move { Ok::<_, std::io::Error>(offer) });
+            let (status, answer) = match session_endpoint.session_request(body).await {
+                Ok(answer) => ("200 OK", answer),
+                Err(err) => ("400 Bad Request", err.to_string()),
+            };
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                answer.len(),
+                answer
+            );
+            let _ = stream.write_all(response.as_bytes()).await;
+        });
+    }
+}
+
+/// read_body reads an HTTP request from stream and returns its body, as long as Content-Length
+/// says
+async fn read_body(stream: &mut tokio::net::TcpStream) -> Option<Vec<u8>> {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 4096];
+    let header_end = loop {
+        let n = stream.read(&mut buf).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        request.extend_from_slice(&buf[..n]);
+        if let Some(i) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+            break i + 4;
+        }
+    };
+
+    let headers = String::from_utf8_lossy(&request[..header_end]).to_ascii_lowercase();
+    let content_length: usize = headers
+        .lines()
+        .find_map(|line| line.strip_prefix("content-length:"))
+        .and_then(|value| value.trim().parse().ok())?;
+    while request.len() < header_end + content_length {
+        let n = stream.read(&mut buf).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+    Some(request[header_end..header_end + content_length].to_vec())
+}
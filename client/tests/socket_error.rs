@@ -0,0 +1,129 @@
+//! socket_error checks that Socket::connect_session reports a failed signaling step with a typed
+//! FailureCause that keeps the underlying error as its source, and that SocketOptionsBuilder
+//! rejects options that could not connect.
+
+use std::error::Error as _;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+use webrtc_unreliable_client::{
+    AnswerError, ConfigIssue, FailureCause, SessionEndpoint, SettingEngine, SignalingAnswer,
+    Socket, SocketError, SocketOptions,
+};
+
+#[tokio::test]
+async fn signaling_callback_error_is_the_source() {
+    let endpoint = SessionEndpoint::Signaling(Box::new(|_offer| {
+        Box::pin(async { Err(anyhow::anyhow!("server unavailable")) })
+    }));
+    let options = SocketOptions::builder().build().unwrap();
+
+    let err = match Socket::connect_session_without_channel(endpoint, options).await {
+        Err(err) => err,
+        Ok(_) => panic!("connected without an answer"),
+    };
+
+    assert!(matches!(
+        err,
+        SocketError::Signaling(FailureCause::Endpoint(_))
+    ));
+    let cause = err.source().expect("no cause");
+    assert_eq!(
+        cause.source().map(ToString::to_string).as_deref(),
+        Some("server unavailable")
+    );
+    // each error names only itself, so walking the chain prints every message once
+    assert_eq!(err.to_string(), "signaling failed");
+    assert_eq!(cause.to_string(), "signaling endpoint failed");
+}
+
+#[tokio::test]
+async fn malformed_answer_is_an_invalid_answer() {
+    let endpoint = SessionEndpoint::Signaling(Box::new(|_offer| {
+        Box::pin(async {
+            Ok(SignalingAnswer {
+                sdp: "not an sdp".to_owned(),
+                candidate: String::new(),
+            })
+        })
+    }));
+    let options = SocketOptions::builder().build().unwrap();
+
+    let err = match Socket::connect_session_without_channel(endpoint, options).await {
+        Err(err) => err,
+        Ok(_) => panic!("connected with a malformed answer"),
+    };
+
+    assert!(matches!(err.cause(), Some(FailureCause::InvalidAnswer(_))));
+    assert!(err.source().and_then(|cause| cause.source()).is_some());
+}
+
+#[tokio::test]
+async fn incomplete_session_response_is_an_invalid_answer() {
+    for (body, missing) in [
+        ("{}", ("answer", "sdp")),
+        (r#"{"answer":1}"#, ("answer", "sdp")),
+    ] {
+        let endpoint = SessionEndpoint::Http(serve_once(body).await);
+        let options = SocketOptions::builder().build().unwrap();
+
+        let err = match Socket::connect_session_without_channel(endpoint, options).await {
+            Err(err) => err,
+            Ok(_) => panic!("connected with the session response {}", body),
+        };
+
+        assert!(
+            matches!(err, SocketError::Signaling(FailureCause::InvalidAnswer(_))),
+            "{}: {:?}",
+            body,
+            err
+        );
+        let cause = err.source().and_then(|cause| cause.source()).unwrap();
+        assert_eq!(
+            cause.to_string(),
+            AnswerError::Missing(missing.0, missing.1).to_string()
+        );
+    }
+}
+
+#[test]
+fn zero_candidate_caps_are_rejected() {
+    let mut setting_engine = SettingEngine::default();
+    setting_engine.set_max_remote_candidates(0);
+    setting_engine.set_max_candidate_pairs(0);
+
+    match SocketOptions::builder()
+        .setting_engine(setting_engine)
+        .build()
+    {
+        Err(SocketError::Options(err)) => assert_eq!(
+            err.issues,
+            vec![
+                ConfigIssue::MaxRemoteCandidatesZero,
+                ConfigIssue::MaxCandidatePairsZero
+            ]
+        ),
+        Err(err) => panic!("unexpected error {:?}", err),
+        Ok(_) => panic!("built options with zero candidate caps"),
+    }
+}
+
+/// serve_once answers the first HTTP request on a fresh port with body and returns the url
+async fn serve_once(body: &'static str) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/rtc_session", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        // the offer fits in the first reads, the response does not depend on it
+        let mut buf = [0u8; 4096];
+        let _ = stream.read(&mut buf).await;
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let _ = stream.write_all(response.as_bytes()).await;
+    });
+    url
+}